@@ -0,0 +1,83 @@
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use data_guardian::compression::{
+    CompressionConfig, compress_usage_data_with_config, decompress_usage_data,
+};
+use data_guardian::test_support::create_test_data;
+
+const SIZES: [usize; 3] = [100, 10_000, 100_000];
+const LEVELS: [u32; 3] = [1, 6, 9];
+
+fn config(level: u32) -> CompressionConfig {
+    CompressionConfig {
+        level,
+        ..Default::default()
+    }
+}
+
+fn print_ratios() {
+    println!(
+        "{:>8}  {:>5}  {:>12}  {:>12}  {:>6}",
+        "ENTRIES", "LEVEL", "JSON", "COMPRESSED", "RATIO"
+    );
+    for size in SIZES {
+        let data = create_test_data(size);
+        let json = serde_json::to_vec(&data).unwrap().len();
+        for level in LEVELS {
+            let compressed = compress_usage_data_with_config(&data, config(level))
+                .unwrap()
+                .len();
+            println!(
+                "{:>8}  {:>5}  {:>12}  {:>12}  {:>6.2}",
+                size,
+                level,
+                json,
+                compressed,
+                json as f64 / compressed as f64
+            );
+        }
+    }
+}
+
+fn bench_compression(c: &mut Criterion) {
+    print_ratios();
+
+    let mut compress = c.benchmark_group("compress");
+    for size in SIZES {
+        let data = create_test_data(size);
+        compress.throughput(Throughput::Bytes(
+            serde_json::to_vec(&data).unwrap().len() as u64
+        ));
+        for level in LEVELS {
+            compress.bench_with_input(
+                BenchmarkId::new(format!("level_{level}"), size),
+                &data,
+                |b, data| {
+                    b.iter(|| compress_usage_data_with_config(black_box(data), config(level)))
+                },
+            );
+        }
+    }
+    compress.finish();
+
+    let mut decompress = c.benchmark_group("decompress");
+    for size in SIZES {
+        let data = create_test_data(size);
+        decompress.throughput(Throughput::Bytes(
+            serde_json::to_vec(&data).unwrap().len() as u64
+        ));
+        for level in LEVELS {
+            let compressed = compress_usage_data_with_config(&data, config(level)).unwrap();
+            decompress.bench_with_input(
+                BenchmarkId::new(format!("level_{level}"), size),
+                &compressed,
+                |b, compressed| b.iter(|| decompress_usage_data(black_box(compressed))),
+            );
+        }
+    }
+    decompress.finish();
+}
+
+criterion_group!(benches, bench_compression);
+criterion_main!(benches);
@@ -0,0 +1,80 @@
+use std::borrow::Cow;
+use std::hint::black_box;
+use std::path::Path;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use data_guardian::identity::Resolver;
+use data_guardian::monitor::{Collect, ProcessEntry, ProcessSource};
+use sysinfo::Pid;
+
+const PROCESSES: usize = 50_000;
+
+struct SyntheticProcess {
+    name: String,
+    bytes: u64,
+}
+
+impl ProcessEntry for SyntheticProcess {
+    fn start_time(&self) -> u64 {
+        0
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.name)
+    }
+
+    fn exe(&self) -> Option<&Path> {
+        None
+    }
+
+    fn disk_bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    fn cpu_time_ms(&self) -> u64 {
+        self.bytes / 1024
+    }
+
+    fn memory_bytes(&self) -> u64 {
+        self.bytes * 4
+    }
+}
+
+fn process_table() -> Vec<SyntheticProcess> {
+    (0..PROCESSES)
+        .map(|i| SyntheticProcess {
+            name: format!("worker-{}", i % 500),
+            bytes: (i as u64).wrapping_mul(7919),
+        })
+        .collect()
+}
+
+fn bench_map_processes(c: &mut Criterion) {
+    let processes = process_table();
+    let entries: Vec<_> = processes
+        .iter()
+        .enumerate()
+        .map(|(pid, process)| (Pid::from(pid), process))
+        .collect();
+    let collect = Collect {
+        cpu: true,
+        memory: true,
+        ..Default::default()
+    };
+
+    let mut group = c.benchmark_group("map_processes");
+    for threads in [1, 2, 4, 8] {
+        let source = ProcessSource::new(Resolver::default())
+            .with_collect(collect)
+            .with_threads(threads);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &entries,
+            |b, entries| b.iter(|| source.map_processes(black_box(entries))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_map_processes);
+criterion_main!(benches);
@@ -0,0 +1,115 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    version();
+    #[cfg(feature = "ffi")]
+    ffi::build();
+}
+
+/// Exposes the git hash and build date as `DG_GIT_HASH` and `DG_BUILD_DATE`.
+/// Both can be pinned with `DG_GIT_HASH` and `SOURCE_DATE_EPOCH` for
+/// reproducible builds.
+fn version() {
+    println!("cargo:rerun-if-env-changed=DG_GIT_HASH");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    for path in [
+        git(&["rev-parse", "--git-path", "HEAD"]),
+        git(&["symbolic-ref", "-q", "HEAD"])
+            .and_then(|reference| git(&["rev-parse", "--git-path", &reference])),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        println!("cargo:rerun-if-changed={path}");
+    }
+
+    let hash = std::env::var("DG_GIT_HASH")
+        .ok()
+        .filter(|hash| !hash.is_empty())
+        .or_else(|| git(&["rev-parse", "--short=10", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=DG_GIT_HASH={hash}");
+
+    let seconds = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        });
+    let (year, month, day) = civil_date(seconds / 86_400);
+    println!("cargo:rustc-env=DG_BUILD_DATE={year:04}-{month:02}-{day:02}");
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let stdout = stdout.trim();
+    (output.status.success() && !stdout.is_empty()).then(|| stdout.to_string())
+}
+
+/// Converts days since 1970-01-01 into a (year, month, day) date in the
+/// proleptic Gregorian calendar.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(feature = "ffi")]
+mod ffi {
+    const HEADER: &str = "data_guardian.h";
+    const ROUNDTRIP: &str = "tests/ffi/roundtrip.c";
+
+    /// Generates the header into `OUT_DIR`, so building never writes to the
+    /// source tree. A test checks that `include/` holds the same header.
+    pub fn build() {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed={ROUNDTRIP}");
+        let out_dir =
+            std::path::PathBuf::from(std::env::var_os("OUT_DIR").expect("OUT_DIR is set"));
+
+        let mut config = cbindgen::Config {
+            usize_is_size_t: true,
+            ..Default::default()
+        };
+        config.enumeration.rename_variants = cbindgen::RenameRule::QualifiedScreamingSnakeCase;
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src("src/ffi.rs")
+            .with_language(cbindgen::Language::C)
+            .with_include_guard("DATA_GUARDIAN_H")
+            .with_cpp_compat(true)
+            .with_sys_include("stddef.h")
+            .with_sys_include("stdint.h")
+            .with_no_includes()
+            .generate()
+            .expect("Failed to generate the C header")
+            .write_to_file(out_dir.join(HEADER));
+
+        let objects = cc::Build::new()
+            .file(ROUNDTRIP)
+            .include(&out_dir)
+            .warnings_into_errors(true)
+            .cargo_metadata(false)
+            .compile_intermediates();
+        for object in objects {
+            println!("cargo:rustc-link-arg-tests={}", object.display());
+        }
+    }
+}
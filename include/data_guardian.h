@@ -0,0 +1,73 @@
+#ifndef DATA_GUARDIAN_H
+#define DATA_GUARDIAN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+enum DgStatus
+#if defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+  : int32_t
+#endif // defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+ {
+  DG_STATUS_OK = 0,
+  DG_STATUS_NULL_POINTER = 1,
+  DG_STATUS_INVALID_UTF8 = 2,
+  DG_STATUS_INVALID_JSON = 3,
+  DG_STATUS_INVALID_DATA = 4,
+  DG_STATUS_PANIC = 5,
+};
+#ifndef __cplusplus
+#if __STDC_VERSION__ >= 202311L
+typedef enum DgStatus DgStatus;
+#else
+typedef int32_t DgStatus;
+#endif // __STDC_VERSION__ >= 202311L
+#endif // __cplusplus
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Compresses a JSON document into the gzip format of `usage.dat`.
+ *
+ * On success `*out` points to `*out_len` bytes that must be released with
+ * `dg_free`. On error `*out` is null and `*out_len` is zero.
+ *
+ * # Safety
+ *
+ * `json` must point to `len` readable bytes, and `out` and `out_len` must be
+ * valid for writes.
+ */
+DgStatus dg_compress_usage_json(const char *json, size_t len, uint8_t **out, size_t *out_len);
+
+/**
+ * Decompresses the contents of `usage.dat` into a UTF-8 JSON document.
+ *
+ * The output is not NUL-terminated. On success `*out` points to `*out_len`
+ * bytes that must be released with `dg_free`. On error `*out` is null and
+ * `*out_len` is zero.
+ *
+ * # Safety
+ *
+ * `data` must point to `len` readable bytes, and `out` and `out_len` must be
+ * valid for writes.
+ */
+DgStatus dg_decompress_usage_json(const uint8_t *data, size_t len, uint8_t **out, size_t *out_len);
+
+/**
+ * Releases a buffer returned by `dg_compress_usage_json` or
+ * `dg_decompress_usage_json`. Passing null is a no-op.
+ *
+ * # Safety
+ *
+ * `ptr` and `len` must come from one of those functions, and the buffer
+ * must not be used or freed again afterwards.
+ */
+void dg_free(uint8_t *ptr, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DATA_GUARDIAN_H */
@@ -0,0 +1,372 @@
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::compression::{self, CompressionError};
+use super::tracker::UsageTracker;
+
+pub const AGENT_QUEUE_FILE: &str = "agent_queue.dat";
+pub const DEFAULT_MAX_QUEUED_SNAPSHOTS: usize = 100;
+pub const DEFAULT_TIMEOUT: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    pub enabled: bool,
+    pub url: String,
+    pub token: Option<String>,
+    pub host: Option<String>,
+    pub max_queued_snapshots: usize,
+    pub timeout_seconds: u64,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            token: None,
+            host: None,
+            max_queued_snapshots: DEFAULT_MAX_QUEUED_SNAPSHOTS,
+            timeout_seconds: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageSnapshot {
+    pub host: String,
+    pub user: String,
+    pub apps: HashMap<String, u64>,
+    pub period_start: Option<NaiveDate>,
+    pub time: DateTime<Utc>,
+}
+
+impl UsageSnapshot {
+    pub fn new(host: &str, user: &str, tracker: &UsageTracker, time: DateTime<Utc>) -> Self {
+        Self {
+            host: host.to_string(),
+            user: user.to_string(),
+            apps: tracker.usage(),
+            period_start: tracker.period_start,
+            time,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.apps
+            .values()
+            .fold(0u64, |sum, bytes| sum.saturating_add(*bytes))
+    }
+
+    pub fn to_compressed(&self) -> Result<Vec<u8>, CompressionError> {
+        compression::compress_json(self, self.apps.len())
+    }
+
+    pub fn from_compressed(data: &[u8]) -> Result<Self, CompressionError> {
+        compression::decompress_json(data)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotQueue {
+    capacity: usize,
+    snapshots: VecDeque<UsageSnapshot>,
+    dropped: u64,
+}
+
+impl SnapshotQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            snapshots: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    pub fn push(&mut self, snapshot: UsageSnapshot) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+            self.dropped += 1;
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn front(&self) -> Option<&UsageSnapshot> {
+        self.snapshots.front()
+    }
+
+    pub fn pop_front(&mut self) -> Option<UsageSnapshot> {
+        self.snapshots.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn to_compressed(&self) -> Result<Vec<u8>, CompressionError> {
+        compression::compress_json(&self.snapshots, self.snapshots.len())
+    }
+
+    pub fn restore(&mut self, data: &[u8]) -> Result<(), CompressionError> {
+        let snapshots: Vec<UsageSnapshot> = compression::decompress_json(data)?;
+        for snapshot in snapshots {
+            self.push(snapshot);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "monitor")]
+pub fn hostname(config: &AgentConfig) -> String {
+    config
+        .host
+        .clone()
+        .or_else(sysinfo::System::host_name)
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+pub fn username() -> String {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|user| !user.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(feature = "agent")]
+pub use client::*;
+
+#[cfg(feature = "agent")]
+mod client {
+    use std::io;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use thiserror::Error;
+    use tracing::{debug, warn};
+
+    use super::super::compression::CompressionError;
+    use super::super::tracker::UsageTracker;
+    use super::{AgentConfig, SnapshotQueue, UsageSnapshot, hostname, username};
+
+    #[derive(Error, Debug)]
+    pub enum AgentError {
+        #[error("Collector request failed: {0}")]
+        Http(#[from] reqwest::Error),
+        #[error("Collector rejected the snapshot ({0}): {1}")]
+        Status(u16, String),
+        #[error("Failed to encode snapshot: {0}")]
+        Compression(#[from] CompressionError),
+        #[error("Failed to save the snapshot queue: {0}")]
+        Io(#[from] io::Error),
+    }
+
+    #[derive(Debug)]
+    pub struct Agent {
+        client: reqwest::Client,
+        url: String,
+        token: Option<String>,
+        host: String,
+        user: String,
+        queue: SnapshotQueue,
+        queue_path: Option<PathBuf>,
+    }
+
+    impl Agent {
+        pub fn new(config: &AgentConfig, queue_path: Option<PathBuf>) -> Result<Self, AgentError> {
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_secs(config.timeout_seconds))
+                .build()?;
+
+            let mut queue = SnapshotQueue::new(config.max_queued_snapshots);
+            if let Some(path) = &queue_path
+                && let Ok(data) = std::fs::read(path)
+                && let Err(e) = queue.restore(&data)
+            {
+                warn!(error = %e, ?path, "Discarding unreadable agent queue");
+            }
+
+            Ok(Self {
+                client,
+                url: config.url.clone(),
+                token: config.token.clone(),
+                host: hostname(config),
+                user: username(),
+                queue,
+                queue_path,
+            })
+        }
+
+        pub fn queued(&self) -> usize {
+            self.queue.len()
+        }
+
+        pub fn dropped(&self) -> u64 {
+            self.queue.dropped()
+        }
+
+        async fn send(&self, snapshot: &UsageSnapshot) -> Result<(), AgentError> {
+            let mut request = self
+                .client
+                .post(&self.url)
+                .header("Content-Type", "application/json")
+                .header("Content-Encoding", "gzip")
+                .body(snapshot.to_compressed()?);
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+
+            let response = request.send().await?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            let message = response.text().await.unwrap_or_default();
+            Err(AgentError::Status(
+                status.as_u16(),
+                message.trim().to_string(),
+            ))
+        }
+
+        async fn flush(&mut self) -> Result<usize, AgentError> {
+            let mut sent = 0;
+            while let Some(snapshot) = self.queue.front() {
+                self.send(snapshot).await?;
+                self.queue.pop_front();
+                sent += 1;
+            }
+            Ok(sent)
+        }
+
+        async fn save_queue(&self) -> Result<(), AgentError> {
+            let Some(path) = &self.queue_path else {
+                return Ok(());
+            };
+            if self.queue.is_empty() {
+                match tokio::fs::remove_file(path).await {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => return Ok(()),
+                }
+            }
+            tokio::fs::write(path, self.queue.to_compressed()?).await?;
+            Ok(())
+        }
+
+        pub async fn report(&mut self, tracker: &UsageTracker) -> Result<usize, AgentError> {
+            self.queue.push(UsageSnapshot::new(
+                &self.host,
+                &self.user,
+                tracker,
+                Utc::now(),
+            ));
+            let result = self.flush().await;
+            self.save_queue().await?;
+            if let Ok(sent) = result {
+                debug!(sent, "Reported usage to collector");
+            }
+            result
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::sync::Arc;
+
+        use tokio::net::TcpListener;
+
+        use super::super::super::collector::{self, Collector};
+        use super::*;
+
+        #[tokio::test]
+        async fn test_queue_and_flush() {
+            let dir = tempfile::tempdir().unwrap();
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            drop(listener);
+
+            let config = AgentConfig {
+                enabled: true,
+                url: format!("http://{}/collect", addr),
+                token: Some("secret".to_string()),
+                host: Some("laptop-1".to_string()),
+                max_queued_snapshots: 2,
+                timeout_seconds: 2,
+            };
+            let queue_path = dir.path().join("queue.dat");
+            let mut agent = Agent::new(&config, Some(queue_path.clone())).unwrap();
+
+            let mut tracker = UsageTracker::default();
+            for bytes in [10, 20, 30] {
+                tracker.add_usage("firefox", bytes);
+                assert!(agent.report(&tracker).await.is_err());
+            }
+            assert_eq!(agent.queued(), 2);
+            assert_eq!(agent.dropped(), 1);
+            assert!(queue_path.exists());
+
+            let mut agent = Agent::new(&config, Some(queue_path.clone())).unwrap();
+            assert_eq!(agent.queued(), 2);
+
+            let collector = Arc::new(
+                Collector::open(&dir.path().join("collector"), Some("secret".to_string())).unwrap(),
+            );
+            let listener = TcpListener::bind(addr).await.unwrap();
+            tokio::spawn(collector::serve(listener, collector.clone()));
+
+            tracker.add_usage("cargo", 5);
+            assert_eq!(agent.report(&tracker).await.unwrap(), 2);
+            assert_eq!(agent.queued(), 0);
+            assert!(!queue_path.exists());
+
+            let report = collector.report(None);
+            assert_eq!(report.hosts.len(), 1);
+            assert_eq!(report.hosts[0].host, "laptop-1");
+            assert_eq!(report.total, 65);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(bytes: u64) -> UsageSnapshot {
+        let mut tracker = UsageTracker::default();
+        tracker.add_usage("firefox", bytes);
+        UsageSnapshot::new("host", "user", &tracker, Utc::now())
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let snapshot = snapshot(42);
+        assert_eq!(snapshot.total(), 42);
+        let restored = UsageSnapshot::from_compressed(&snapshot.to_compressed().unwrap()).unwrap();
+        assert_eq!(restored, snapshot);
+    }
+
+    #[test]
+    fn test_bounded_queue() {
+        let mut queue = SnapshotQueue::new(2);
+        for bytes in 1..=3 {
+            queue.push(snapshot(bytes));
+        }
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.front().unwrap().total(), 2);
+
+        let mut restored = SnapshotQueue::new(1);
+        restored.restore(&queue.to_compressed().unwrap()).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored.front().unwrap().total(), 3);
+    }
+}
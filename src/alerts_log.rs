@@ -0,0 +1,239 @@
+//! An append-only record of every alert, one JSON object per line, kept
+//! next to the data file as `alerts.log`. Unlike the notification history,
+//! it is written as alerts go out, so it survives crashes and can be read
+//! while the service is stopped.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::history::{NotificationOutcome, NotificationRecord, Severity};
+use super::logging::SizeRotatingWriter;
+use super::notification::AlertKind;
+
+pub const ALERTS_LOG_FILE: &str = "alerts.log";
+pub const ALERTS_LOG_MAX_SIZE: u64 = 1024 * 1024;
+/// Rotated copies kept as `alerts.log.1` and up.
+pub const ALERTS_LOG_MAX_FILES: usize = 3;
+
+/// How one channel fared with an alert.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelOutcome {
+    pub channel: String,
+    #[serde(flatten)]
+    pub outcome: NotificationOutcome,
+}
+
+/// One line of `alerts.log`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertLogEntry {
+    pub time: DateTime<Utc>,
+    pub app: String,
+    pub kind: AlertKind,
+    pub severity: Severity,
+    pub usage: Option<u64>,
+    pub limit: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Sent when at least one channel delivered the alert.
+    #[serde(flatten)]
+    pub outcome: NotificationOutcome,
+    pub channels: Vec<ChannelOutcome>,
+}
+
+impl From<&AlertLogEntry> for NotificationRecord {
+    fn from(entry: &AlertLogEntry) -> Self {
+        Self {
+            app: entry.app.clone(),
+            severity: entry.severity,
+            time: entry.time,
+            outcome: entry.outcome.clone(),
+        }
+    }
+}
+
+/// Appends entries to the log, rotating it by size. The file is opened on
+/// the first write and reopened after a failure, which is logged and
+/// otherwise ignored.
+#[derive(Debug)]
+pub struct AlertsLog {
+    path: PathBuf,
+    writer: Mutex<Option<SizeRotatingWriter>>,
+}
+
+impl AlertsLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            writer: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, entry: &AlertLogEntry) {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = self.write(&mut writer, entry) {
+            warn!(path = ?self.path, error = %e, "Failed to append to the alerts log");
+            *writer = None;
+        }
+    }
+
+    fn write(
+        &self,
+        writer: &mut Option<SizeRotatingWriter>,
+        entry: &AlertLogEntry,
+    ) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let writer = match writer {
+            Some(writer) => writer,
+            None => {
+                if let Some(dir) = self.path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                writer.insert(SizeRotatingWriter::new(
+                    &self.path,
+                    ALERTS_LOG_MAX_SIZE,
+                    ALERTS_LOG_MAX_FILES,
+                )?)
+            }
+        };
+        writer.write_all(&line)?;
+        writer.flush()
+    }
+}
+
+/// Reads `path` and its rotated copies, oldest entry first. Lines that do
+/// not parse, such as one cut short by a crash, are skipped.
+pub fn read_entries(path: &Path) -> io::Result<Vec<AlertLogEntry>> {
+    let mut entries = Vec::new();
+    for index in (0..=ALERTS_LOG_MAX_FILES).rev() {
+        let path = match index {
+            0 => path.to_path_buf(),
+            index => {
+                let mut name = path.as_os_str().to_owned();
+                name.push(format!(".{}", index));
+                PathBuf::from(name)
+            }
+        };
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for line in BufReader::new(file).lines() {
+            if let Ok(entry) = serde_json::from_str(&line?) {
+                entries.push(entry);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(app: &str, outcome: NotificationOutcome) -> AlertLogEntry {
+        AlertLogEntry {
+            time: "2025-06-18T12:00:00Z".parse().unwrap(),
+            app: app.to_string(),
+            kind: AlertKind::Exceeded,
+            severity: Severity::Warning,
+            usage: Some(2048),
+            limit: Some(1024),
+            instance: None,
+            outcome,
+            channels: vec![
+                ChannelOutcome {
+                    channel: "desktop".to_string(),
+                    outcome: NotificationOutcome::Failed("no display".to_string()),
+                },
+                ChannelOutcome {
+                    channel: "syslog".to_string(),
+                    outcome: NotificationOutcome::Sent,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_lines_parse_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(ALERTS_LOG_FILE);
+        let log = AlertsLog::new(&path);
+        let sent = entry("firefox", NotificationOutcome::Sent);
+        let failed = entry("curl", NotificationOutcome::Failed("timeout".to_string()));
+        log.append(&sent);
+        log.append(&failed);
+
+        let text = fs::read_to_string(&path).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            r#"{"time":"2025-06-18T12:00:00Z","app":"firefox","kind":"exceeded","severity":"warning","usage":2048,"limit":1024,"status":"sent","channels":[{"channel":"desktop","status":"failed","error":"no display"},{"channel":"syslog","status":"sent"}]}"#
+        );
+        let parsed = lines
+            .iter()
+            .map(|line| serde_json::from_str::<AlertLogEntry>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(parsed, [sent.clone(), failed.clone()]);
+
+        // A line cut short by a crash is skipped.
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"time\":")
+            .unwrap();
+        assert_eq!(read_entries(&path).unwrap(), [sent, failed]);
+        assert_eq!(
+            NotificationRecord::from(&parsed[1]).outcome,
+            NotificationOutcome::Failed("timeout".to_string())
+        );
+    }
+
+    #[test]
+    fn test_rotation_keeps_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(ALERTS_LOG_FILE);
+        let log = AlertsLog::new(&path);
+        let line_len = serde_json::to_vec(&entry("app_0", NotificationOutcome::Sent))
+            .unwrap()
+            .len() as u64;
+        let per_file = ALERTS_LOG_MAX_SIZE / (line_len + 1);
+        let total = per_file * 2 + 5;
+        for i in 0..total {
+            log.append(&entry(&format!("app_{}", i), NotificationOutcome::Sent));
+        }
+
+        assert!(dir.path().join("alerts.log.2").exists());
+        let apps = read_entries(&path)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.app)
+            .collect::<Vec<_>>();
+        assert_eq!(apps.len() as u64, total);
+        assert_eq!(apps.first().unwrap(), "app_0");
+        assert_eq!(apps.last().unwrap(), &format!("app_{}", total - 1));
+    }
+
+    #[test]
+    fn test_write_failure_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let blocker = dir.path().join("not-a-dir");
+        fs::write(&blocker, "").unwrap();
+        let log = AlertsLog::new(blocker.join(ALERTS_LOG_FILE));
+        log.append(&entry("firefox", NotificationOutcome::Sent));
+        log.append(&entry("curl", NotificationOutcome::Sent));
+        assert!(!log.path().exists());
+    }
+}
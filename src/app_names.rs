@@ -0,0 +1,326 @@
+//! Rules that give an application the same name on every platform, so
+//! `Chrome`, `chrome` and `chrome.exe` are counted as one app.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::merge;
+use super::monitor;
+use super::tracker::UsageTracker;
+
+pub const EXE_SUFFIX: &str = ".exe";
+pub const HELPER_SUFFIX: &str = " Helper";
+/// Process kinds that Chromium and Electron append to their helpers' names,
+/// as in `Google Chrome Helper (Renderer)`.
+pub const HELPER_KINDS: &[&str] = &["(Renderer)", "(GPU)", "(Plugin)", "(Alerts)"];
+
+#[derive(Error, Debug)]
+#[error("Invalid app name rewrite pattern {pattern:?}: {source}")]
+pub struct AppNameError {
+    pub pattern: String,
+    #[source]
+    pub source: regex::Error,
+}
+
+/// A regular expression and what its matches are replaced with; `$1` and
+/// `${name}` refer to groups.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewriteRule {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppNameConfig {
+    pub lowercase: bool,
+    /// Drop a trailing `.exe` in any case.
+    pub strip_exe: bool,
+    /// Drop a trailing ` Helper` and a process kind such as `(Renderer)`,
+    /// so helper processes count toward their app.
+    pub strip_helper_suffixes: bool,
+    /// Applied in order, after the rules above.
+    pub rewrite: Vec<RewriteRule>,
+}
+
+/// The compiled `AppNameConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct AppNames {
+    lowercase: bool,
+    strip_exe: bool,
+    strip_helper_suffixes: bool,
+    rewrite: Vec<(Regex, String)>,
+}
+
+impl AppNames {
+    pub fn new(config: &AppNameConfig) -> Result<Self, AppNameError> {
+        let rewrite = config
+            .rewrite
+            .iter()
+            .map(|rule| {
+                Regex::new(&rule.pattern)
+                    .map(|regex| (regex, rule.replacement.clone()))
+                    .map_err(|source| AppNameError {
+                        pattern: rule.pattern.clone(),
+                        source,
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            lowercase: config.lowercase,
+            strip_exe: config.strip_exe,
+            strip_helper_suffixes: config.strip_helper_suffixes,
+            rewrite,
+        })
+    }
+
+    /// Whether every name is left as it is.
+    pub fn is_empty(&self) -> bool {
+        !self.lowercase && !self.strip_exe && !self.strip_helper_suffixes && self.rewrite.is_empty()
+    }
+
+    /// The name `name` is counted under. Disks, interfaces and watched paths
+    /// keep theirs, and a rule that would leave nothing is skipped.
+    pub fn normalize<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        if self.is_empty() || !monitor::is_app(name) {
+            return Cow::Borrowed(name);
+        }
+        let mut normalized = Cow::Borrowed(name);
+        if self.strip_helper_suffixes {
+            normalized = keep_nonempty(normalized, strip_helper_suffixes);
+        }
+        if self.strip_exe {
+            normalized = keep_nonempty(normalized, strip_exe);
+        }
+        if self.lowercase && normalized.chars().any(char::is_uppercase) {
+            normalized = Cow::Owned(normalized.to_lowercase());
+        }
+        for (regex, replacement) in &self.rewrite {
+            let rewritten = regex.replace_all(&normalized, replacement.as_str());
+            if !rewritten.is_empty() && rewritten != normalized {
+                normalized = Cow::Owned(rewritten.into_owned());
+            }
+        }
+        normalized
+    }
+
+    /// The normalized name, if it differs from `name`.
+    pub fn rename(&self, name: &str) -> Option<String> {
+        let normalized = self.normalize(name);
+        (normalized != name).then(|| normalized.into_owned())
+    }
+
+    /// `data` with normalized names, merging values that now share one.
+    pub fn normalize_map<V>(
+        &self,
+        data: HashMap<String, V>,
+        merge: impl Fn(&mut V, V),
+    ) -> HashMap<String, V> {
+        if self.is_empty() {
+            return data;
+        }
+        let mut normalized = HashMap::with_capacity(data.len());
+        for (name, value) in data {
+            let name = self.rename(&name).unwrap_or(name);
+            match normalized.entry(name) {
+                Entry::Occupied(mut entry) => merge(entry.get_mut(), value),
+                Entry::Vacant(entry) => {
+                    entry.insert(value);
+                }
+            }
+        }
+        normalized
+    }
+
+    /// `tracker` with normalized names, as for data saved before the rules
+    /// changed or on another platform, and how many apps were merged into
+    /// another.
+    pub fn normalize_tracker(&self, tracker: UsageTracker) -> (UsageTracker, usize) {
+        if self.is_empty() {
+            return (tracker, 0);
+        }
+        merge::rename_apps(tracker, |app| self.normalize(app).into_owned())
+    }
+}
+
+fn keep_nonempty<'a>(name: Cow<'a, str>, rule: fn(&str) -> &str) -> Cow<'a, str> {
+    let stripped = rule(&name);
+    if stripped.is_empty() || stripped.len() == name.len() {
+        return name;
+    }
+    match name {
+        Cow::Borrowed(name) => Cow::Borrowed(rule(name)),
+        Cow::Owned(name) => Cow::Owned(rule(&name).to_string()),
+    }
+}
+
+fn strip_helper_suffixes(name: &str) -> &str {
+    let mut name = name.trim_end();
+    if let Some(stripped) = HELPER_KINDS.iter().find_map(|kind| name.strip_suffix(kind)) {
+        name = stripped.trim_end();
+    }
+    name.strip_suffix(HELPER_SUFFIX).unwrap_or(name)
+}
+
+/// `name` without a trailing `.exe` in any case.
+pub fn strip_exe(name: &str) -> &str {
+    let split = name.len().saturating_sub(EXE_SUFFIX.len());
+    match name.get(split..) {
+        Some(suffix) if suffix.eq_ignore_ascii_case(EXE_SUFFIX) => &name[..split],
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracker::{AppRecord, DayUsage};
+
+    fn names(rewrite: &[(&str, &str)]) -> AppNames {
+        AppNames::new(&AppNameConfig {
+            lowercase: true,
+            strip_exe: true,
+            strip_helper_suffixes: true,
+            rewrite: rewrite
+                .iter()
+                .map(|(pattern, replacement)| RewriteRule {
+                    pattern: pattern.to_string(),
+                    replacement: replacement.to_string(),
+                })
+                .collect(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_real_world_names() {
+        let names = names(&[
+            (r"^google chrome$", "chrome"),
+            (r"^firefox(-bin|\.real)$", "firefox"),
+            (r"^python3\.\d+$", "python3"),
+            (r"^msedge$", "microsoft edge"),
+        ]);
+        for (name, expected) in [
+            ("Chrome", "chrome"),
+            ("chrome", "chrome"),
+            ("chrome.exe", "chrome"),
+            ("CHROME.EXE", "chrome"),
+            ("Google Chrome", "chrome"),
+            ("Google Chrome Helper", "chrome"),
+            ("Google Chrome Helper (Renderer)", "chrome"),
+            ("Google Chrome Helper (GPU)", "chrome"),
+            ("Slack Helper (Plugin)", "slack"),
+            ("Code Helper (Renderer) ", "code"),
+            ("Microsoft Edge Helper (Alerts)", "microsoft edge"),
+            ("msedge.exe", "microsoft edge"),
+            ("firefox-bin", "firefox"),
+            ("firefox.real", "firefox"),
+            ("python3.12", "python3"),
+            ("notepad++.exe", "notepad++"),
+            ("Télégram.EXE", "télégram"),
+            ("setup.exe.exe", "setup.exe"),
+            ("kworker/u8:2", "kworker/u8:2"),
+            // Not a suffix, or nothing would be left.
+            ("Helper", "helper"),
+            ("HelperService", "helperservice"),
+            (".exe", ".exe"),
+            ("exe", "exe"),
+            ("(Renderer)", "(renderer)"),
+            ("", ""),
+            // Only applications are renamed.
+            ("disk:SDA", "disk:SDA"),
+            ("net:Wi-Fi", "net:Wi-Fi"),
+            ("path:Downloads", "path:Downloads"),
+        ] {
+            assert_eq!(names.normalize(name), expected, "{:?}", name);
+        }
+
+        let default = AppNames::default();
+        assert!(default.is_empty());
+        assert!(matches!(
+            default.normalize("Chrome.exe"),
+            Cow::Borrowed("Chrome.exe")
+        ));
+        assert!(matches!(names.normalize("chrome"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_rewrite_errors_and_groups() {
+        let error = AppNames::new(&AppNameConfig {
+            rewrite: vec![RewriteRule {
+                pattern: "(".to_string(),
+                replacement: String::new(),
+            }],
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert_eq!(error.pattern, "(");
+
+        let names = names(&[(r"^(\w+)-(\d+)$", "$1"), (r"^.*$", "")]);
+        assert_eq!(names.normalize("Worker-12"), "worker");
+    }
+
+    #[test]
+    fn test_collisions_merge() {
+        let names = names(&[]);
+        let usage: HashMap<_, _> = [
+            ("Chrome".to_string(), 10u64),
+            ("chrome.exe".to_string(), 5),
+            ("Google Chrome Helper".to_string(), 1),
+            ("disk:sda".to_string(), 7),
+        ]
+        .into();
+        let usage = names.normalize_map(usage, |total, bytes| *total += bytes);
+        assert_eq!(
+            usage,
+            [
+                ("chrome".to_string(), 15),
+                ("google chrome".to_string(), 1),
+                ("disk:sda".to_string(), 7)
+            ]
+            .into()
+        );
+
+        let record = |bytes, cpu_time_ms| AppRecord {
+            bytes,
+            cpu_time_ms,
+            ..Default::default()
+        };
+        let day = |bytes| DayUsage {
+            bytes,
+            ..Default::default()
+        };
+        let date = chrono::NaiveDate::from_ymd_opt(2025, 6, 18).unwrap();
+        let mut tracker = UsageTracker::default();
+        tracker
+            .apps
+            .insert("Chrome".to_string(), record(10, Some(4)));
+        tracker
+            .apps
+            .insert("chrome.exe".to_string(), record(5, Some(6)));
+        tracker.apps.insert("curl".to_string(), record(1, None));
+        tracker.days.insert(
+            date,
+            [
+                ("Chrome".to_string(), day(3)),
+                ("chrome.exe".to_string(), day(2)),
+            ]
+            .into(),
+        );
+
+        let (tracker, merged) = names.normalize_tracker(tracker);
+        assert_eq!(merged, 1);
+        assert_eq!(
+            tracker.usage(),
+            [("chrome".to_string(), 15), ("curl".to_string(), 1)].into()
+        );
+        assert_eq!(tracker.apps["chrome"].cpu_time_ms, Some(10));
+        assert_eq!(tracker.days[&date]["chrome"].bytes, 5);
+        assert_eq!(names.normalize_tracker(tracker.clone()), (tracker, 0));
+    }
+}
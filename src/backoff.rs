@@ -0,0 +1,116 @@
+//! Backs off a periodic task that keeps failing, such as scans while
+//! `/proc` is unreadable, and says when the failures are worth an alert.
+
+/// The longest wait between attempts, in intervals.
+pub const MAX_BACKOFF_FACTOR: u32 = 16;
+/// Failures in a row before the task is reported as failing.
+pub const ESCALATE_AFTER: u32 = 5;
+
+pub const SCAN_TASK: &str = "scan";
+pub const SAVE_TASK: &str = "save";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Backoff {
+    failures: u32,
+    skipped: u32,
+}
+
+impl Backoff {
+    /// Called on every tick of the task's interval. Returns `false` for the
+    /// ticks skipped while backing off, so after `n` failures in a row the
+    /// task runs every `2^n` ticks, up to `MAX_BACKOFF_FACTOR`.
+    pub fn ready(&mut self) -> bool {
+        if self.skipped + 1 < self.factor() {
+            self.skipped += 1;
+            return false;
+        }
+        self.skipped = 0;
+        true
+    }
+
+    /// Records a failed attempt. Returns `true` once per run of failures,
+    /// when it reaches `ESCALATE_AFTER`.
+    pub fn failed(&mut self) -> bool {
+        self.failures = self.failures.saturating_add(1);
+        self.skipped = 0;
+        self.failures == ESCALATE_AFTER
+    }
+
+    /// Records a successful attempt and returns the failures in a row it
+    /// ended.
+    pub fn succeeded(&mut self) -> u32 {
+        self.skipped = 0;
+        std::mem::take(&mut self.failures)
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Intervals between attempts at the moment.
+    pub fn factor(&self) -> u32 {
+        1u32.checked_shl(self.failures)
+            .unwrap_or(u32::MAX)
+            .min(MAX_BACKOFF_FACTOR)
+    }
+}
+
+#[cfg(all(test, feature = "monitor"))]
+mod tests {
+    use super::*;
+    use crate::monitor::CounterSource;
+    use crate::test_support::FailingSource;
+
+    /// Runs `ticks` ticks against `source` and returns the ticks that read
+    /// it, and those that escalated.
+    fn run(backoff: &mut Backoff, source: &FailingSource, ticks: u32) -> (Vec<u32>, Vec<u32>) {
+        let (mut attempts, mut escalations) = (Vec::new(), Vec::new());
+        for tick in 0..ticks {
+            if !backoff.ready() {
+                continue;
+            }
+            attempts.push(tick);
+            match source.read() {
+                Ok(_) => {
+                    backoff.succeeded();
+                }
+                Err(_) if backoff.failed() => escalations.push(tick),
+                Err(_) => {}
+            }
+        }
+        (attempts, escalations)
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        let source = FailingSource::failing();
+        let mut backoff = Backoff::default();
+
+        let (attempts, escalations) = run(&mut backoff, &source, 80);
+        assert_eq!(attempts, [0, 2, 6, 14, 30, 46, 62, 78]);
+        assert_eq!(escalations, [30]);
+        assert_eq!(source.reads(), 8);
+        assert_eq!(backoff.failures(), 8);
+        assert_eq!(backoff.factor(), MAX_BACKOFF_FACTOR);
+    }
+
+    #[test]
+    fn test_success_resets_backoff() {
+        let source = FailingSource::failing();
+        let mut backoff = Backoff::default();
+        run(&mut backoff, &source, 31);
+        assert_eq!(backoff.failures(), ESCALATE_AFTER);
+
+        source.set_failing(false);
+        let (attempts, _) = run(&mut backoff, &source, 20);
+        assert_eq!(attempts, [15, 16, 17, 18, 19]);
+        assert_eq!(backoff.factor(), 1);
+        let (attempts, _) = run(&mut backoff, &source, 3);
+        assert_eq!(attempts, [0, 1, 2]);
+
+        // A new run of failures escalates again.
+        source.set_failing(true);
+        let (_, escalations) = run(&mut backoff, &source, 40);
+        assert_eq!(escalations, [30]);
+    }
+}
@@ -0,0 +1,200 @@
+//! A data budget for all apps together, such as an ISP's monthly cap, with
+//! its own billing period.
+
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_MILESTONES: [u32; 4] = [50, 75, 90, 100];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Bytes allowed per billing period. No budget when unset.
+    pub total_bytes: Option<u64>,
+    /// Day of the month a billing period starts. In shorter months the
+    /// period starts on the last day instead.
+    pub start_day: u32,
+    /// Percentages of `total_bytes` that send one alert per period.
+    pub milestones: Vec<u32>,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            total_bytes: None,
+            start_day: 1,
+            milestones: DEFAULT_MILESTONES.to_vec(),
+        }
+    }
+}
+
+impl BudgetConfig {
+    /// First day of the billing period that contains `today`.
+    pub fn period_start(&self, today: NaiveDate) -> NaiveDate {
+        let start = self.start_in(today);
+        if start <= today {
+            return start;
+        }
+        self.start_in(today - Months::new(1))
+    }
+
+    /// First day of the billing period after the one starting on `start`.
+    pub fn period_end(&self, start: NaiveDate) -> NaiveDate {
+        self.start_in(start + Months::new(1))
+    }
+
+    fn start_in(&self, month: NaiveDate) -> NaiveDate {
+        let first = month.with_day(1).unwrap_or(month);
+        let last = (first + Months::new(1)).pred_opt().unwrap_or(first);
+        first
+            .with_day(self.start_day.clamp(1, last.day()))
+            .unwrap_or(first)
+    }
+}
+
+/// Budget usage in the current billing period, kept with the usage data so
+/// a restart neither forgets usage nor repeats milestone alerts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BudgetState {
+    pub period_start: Option<NaiveDate>,
+    pub used: u64,
+    /// Milestones already alerted this period.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notified: Vec<u32>,
+}
+
+impl BudgetState {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Adds `delta` bytes used on `today`. When a new billing period has
+    /// begun, usage restarts from `since(start)`, the usage already recorded
+    /// from the period's first day, so a budget added mid-period still sees
+    /// the days before it. Returns the milestones reached this period that
+    /// have not been alerted yet, lowest first. They stay pending until
+    /// `mark_notified`, so one whose alert fails is offered again.
+    pub fn add(
+        &mut self,
+        config: &BudgetConfig,
+        delta: u64,
+        today: NaiveDate,
+        since: impl FnOnce(NaiveDate) -> u64,
+    ) -> Vec<u32> {
+        let Some(total) = config.total_bytes else {
+            return Vec::new();
+        };
+        let start = config.period_start(today);
+        if self.period_start != Some(start) {
+            self.period_start = Some(start);
+            self.used = since(start);
+            self.notified.clear();
+        } else {
+            self.used = self.used.saturating_add(delta);
+        }
+
+        let percent = self.percent(total);
+        let mut pending: Vec<_> = config
+            .milestones
+            .iter()
+            .copied()
+            .filter(|milestone| *milestone <= percent && !self.notified.contains(milestone))
+            .collect();
+        pending.sort_unstable();
+        pending
+    }
+
+    /// Records that the alert for `milestone` was delivered. It covers the
+    /// lower milestones too, so they are not alerted after it.
+    pub fn mark_notified(&mut self, config: &BudgetConfig, milestone: u32) {
+        for reached in &config.milestones {
+            if *reached <= milestone && !self.notified.contains(reached) {
+                self.notified.push(*reached);
+            }
+        }
+    }
+
+    /// Share of `total` used so far, rounded down.
+    pub fn percent(&self, total: u64) -> u32 {
+        if total == 0 {
+            return u32::MAX;
+        }
+        (self.used as u128 * 100 / total as u128).min(u32::MAX as u128) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_period_bounds() {
+        let config = BudgetConfig {
+            start_day: 15,
+            ..BudgetConfig::default()
+        };
+        assert_eq!(config.period_start(date(6, 15)), date(6, 15));
+        assert_eq!(config.period_start(date(6, 14)), date(5, 15));
+        assert_eq!(config.period_end(date(6, 15)), date(7, 15));
+
+        let config = BudgetConfig {
+            start_day: 31,
+            ..BudgetConfig::default()
+        };
+        assert_eq!(config.period_start(date(2, 27)), date(1, 31));
+        assert_eq!(config.period_start(date(2, 28)), date(2, 28));
+        assert_eq!(config.period_end(date(2, 28)), date(3, 31));
+        assert_eq!(config.period_start(date(4, 29)), date(3, 31));
+    }
+
+    #[test]
+    fn test_milestones_fire_once_per_period() {
+        let config = BudgetConfig {
+            total_bytes: Some(1000),
+            ..BudgetConfig::default()
+        };
+        let mut state = BudgetState::default();
+
+        assert!(state.add(&config, 0, date(6, 10), |_| 400).is_empty());
+        assert_eq!(state.period_start, Some(date(6, 1)));
+        assert_eq!(state.add(&config, 100, date(6, 10), |_| 0), [50]);
+        state.mark_notified(&config, 50);
+        assert!(state.add(&config, 10, date(6, 11), |_| 0).is_empty());
+        assert_eq!(state.add(&config, 490, date(6, 12), |_| 0), [75, 90, 100]);
+        state.mark_notified(&config, 100);
+        assert_eq!(state.notified, [50, 75, 90, 100]);
+        assert!(state.add(&config, 400, date(6, 12), |_| 0).is_empty());
+
+        assert!(
+            state
+                .add(&config, 600, date(7, 1), |start| start.day() as u64)
+                .is_empty()
+        );
+        assert_eq!(state.used, 1);
+        assert!(state.notified.is_empty());
+
+        let disabled = BudgetConfig::default();
+        assert!(state.add(&disabled, 10_000, date(7, 1), |_| 0).is_empty());
+        assert_eq!(state.used, 1);
+    }
+
+    #[test]
+    fn test_failed_milestone_alert_is_retried() {
+        let config = BudgetConfig {
+            total_bytes: Some(1000),
+            ..BudgetConfig::default()
+        };
+        let mut state = BudgetState::default();
+        assert_eq!(state.add(&config, 0, date(6, 10), |_| 800), [50, 75]);
+        // The alert failed, so nothing is marked and both stay pending.
+        assert!(state.notified.is_empty());
+        assert_eq!(state.add(&config, 0, date(6, 10), |_| 0), [50, 75]);
+        state.mark_notified(&config, 75);
+        assert!(state.add(&config, 0, date(6, 10), |_| 0).is_empty());
+        assert_eq!(state.add(&config, 100, date(6, 11), |_| 0), [90]);
+    }
+}
@@ -0,0 +1,217 @@
+//! Suggests data limits from a short sample of real usage, for
+//! `dg calibrate`.
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::monitor::{self, UsageData};
+use super::settings::MIN_DATA_LIMIT;
+use super::tracker::ResetPeriod;
+
+/// Share of sampled intervals whose rate a suggested limit covers.
+pub const PERCENTILE: f64 = 0.95;
+
+/// How long a period is assumed to last when extrapolating. `never` is
+/// treated like a month.
+pub fn period_length(period: ResetPeriod) -> Duration {
+    const DAY: u64 = 24 * 60 * 60;
+    Duration::from_secs(match period {
+        ResetPeriod::Daily => DAY,
+        ResetPeriod::Weekly => 7 * DAY,
+        ResetPeriod::Monthly | ResetPeriod::Never => 30 * DAY,
+    })
+}
+
+/// The usage added in one sampling interval.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sample {
+    pub elapsed: Duration,
+    pub usage: UsageData,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Suggestion {
+    pub app: String,
+    /// Bytes seen while sampling.
+    pub observed_bytes: u64,
+    /// The 95th percentile of the app's rate over the sampled intervals.
+    pub p95_bytes_per_second: f64,
+    /// That rate over a whole period, rounded up to a friendly number.
+    pub limit: u64,
+}
+
+/// Suggested limits for every app that moved data in `samples`, highest
+/// first. An interval in which an app moved nothing counts as a rate of
+/// zero, so an app that bursts once is not judged by the burst alone.
+pub fn suggest(samples: &[Sample], period: ResetPeriod) -> Vec<Suggestion> {
+    let apps = samples
+        .iter()
+        .flat_map(|sample| sample.usage.keys())
+        .filter(|app| monitor::is_app(app))
+        .collect::<BTreeSet<_>>();
+    let seconds = period_length(period).as_secs_f64();
+
+    let mut suggestions = apps
+        .into_iter()
+        .filter_map(|app| {
+            let bytes = |sample: &Sample| sample.usage.get(app).copied().unwrap_or(0);
+            let rates = samples
+                .iter()
+                .filter(|sample| !sample.elapsed.is_zero())
+                .map(|sample| bytes(sample) as f64 / sample.elapsed.as_secs_f64())
+                .collect::<Vec<_>>();
+            let observed_bytes = samples.iter().map(bytes).sum();
+            if observed_bytes == 0 {
+                return None;
+            }
+            let rate = percentile(rates, PERCENTILE);
+            Some(Suggestion {
+                app: app.clone(),
+                observed_bytes,
+                p95_bytes_per_second: rate,
+                limit: round_friendly((rate * seconds).ceil() as u64),
+            })
+        })
+        .collect::<Vec<_>>();
+    suggestions.sort_by(|a, b| b.limit.cmp(&a.limit).then_with(|| a.app.cmp(&b.app)));
+    suggestions
+}
+
+/// A `data_limit` that covers every suggestion, since the limit applies to
+/// each app on its own. `None` when nothing was seen.
+pub fn suggested_data_limit(suggestions: &[Suggestion]) -> Option<u64> {
+    suggestions.iter().map(|suggestion| suggestion.limit).max()
+}
+
+/// The nearest-rank percentile of `values`, or 0 when there are none.
+pub fn percentile(mut values: Vec<f64>, percentile: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let rank = (percentile.clamp(0.0, 1.0) * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+const UNITS: [(u64, &str); 3] = [(1 << 20, "MiB"), (1 << 30, "GiB"), (1 << 40, "TiB")];
+
+/// Rounds `bytes` up to 1, 2 or 5 times a power of ten of MiB, GiB or TiB,
+/// such as 200 MiB or 5 GiB, and never below `MIN_DATA_LIMIT`.
+pub fn round_friendly(bytes: u64) -> u64 {
+    const STEPS: [u64; 9] = [1, 2, 5, 10, 20, 50, 100, 200, 500];
+
+    let bytes = bytes.max(MIN_DATA_LIMIT);
+    let largest = UNITS[UNITS.len() - 1].0;
+    UNITS
+        .iter()
+        .flat_map(|(unit, _)| STEPS.iter().map(move |step| step * unit))
+        .find(|limit| *limit >= bytes)
+        .unwrap_or_else(|| bytes.div_ceil(largest) * largest)
+}
+
+/// `bytes` in the largest binary unit that divides it, such as `200 MiB`.
+pub fn format_limit(bytes: u64) -> String {
+    UNITS
+        .iter()
+        .rev()
+        .find(|(unit, _)| bytes > 0 && bytes.is_multiple_of(*unit))
+        .map_or_else(
+            || format!("{} B", bytes),
+            |(unit, name)| format!("{} {}", bytes / unit, name),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1 << 20;
+    const GIB: u64 = 1 << 30;
+
+    /// Ten-second samples with the given bytes per app.
+    fn series(apps: &[(&str, &[u64])]) -> Vec<Sample> {
+        let len = apps.iter().map(|(_, bytes)| bytes.len()).max().unwrap_or(0);
+        (0..len)
+            .map(|i| Sample {
+                elapsed: Duration::from_secs(10),
+                usage: apps
+                    .iter()
+                    .filter_map(|(app, bytes)| Some((app.to_string(), *bytes.get(i)?)))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_friendly() {
+        assert_eq!(round_friendly(0), MIB);
+        assert_eq!(round_friendly(MIB + 1), 2 * MIB);
+        assert_eq!(round_friendly(3 * MIB), 5 * MIB);
+        assert_eq!(round_friendly(450 * MIB), 500 * MIB);
+        assert_eq!(round_friendly(501 * MIB), GIB);
+        assert_eq!(round_friendly(GIB), GIB);
+        assert_eq!(round_friendly(15 * GIB), 20 * GIB);
+        assert_eq!(round_friendly(600 << 40), 600 << 40);
+
+        assert_eq!(format_limit(500 * MIB), "500 MiB");
+        assert_eq!(format_limit(2048 * MIB), "2 GiB");
+        assert_eq!(format_limit(1000), "1000 B");
+        assert_eq!(format_limit(0), "0 B");
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(Vec::new(), PERCENTILE), 0.0);
+        assert_eq!(percentile(vec![3.0], PERCENTILE), 3.0);
+        let values = (1..=100).map(f64::from).rev().collect();
+        assert_eq!(percentile(values, PERCENTILE), 95.0);
+        let values = (1..=20).map(f64::from).collect::<Vec<_>>();
+        assert_eq!(percentile(values.clone(), PERCENTILE), 19.0);
+        assert_eq!(percentile(values, 1.0), 20.0);
+    }
+
+    #[test]
+    fn test_suggest_from_series() {
+        // A steady 1 KiB/s with one burst, and an app that only shows up
+        // once. Twenty intervals put the 95th percentile on the second
+        // highest rate, so a single burst does not set the limit.
+        let mut steady = vec![10 * 1024; 20];
+        steady[7] = 100 * MIB;
+        let mut once = vec![0; 20];
+        once[3] = 50 * MIB;
+        let samples = series(&[
+            ("steady", &steady),
+            ("once", &once),
+            ("idle", &[0; 20]),
+            ("disk:sda", &steady),
+        ]);
+
+        let suggestions = suggest(&samples, ResetPeriod::Daily);
+        assert_eq!(
+            suggestions
+                .iter()
+                .map(|suggestion| suggestion.app.as_str())
+                .collect::<Vec<_>>(),
+            ["steady", "once"]
+        );
+        let steady = &suggestions[0];
+        assert_eq!(steady.p95_bytes_per_second, 1024.0);
+        assert_eq!(steady.observed_bytes, 19 * 10 * 1024 + 100 * MIB);
+        // 1 KiB/s for a day is about 84 MiB.
+        assert_eq!(steady.limit, 100 * MIB);
+        let once = &suggestions[1];
+        assert_eq!(once.p95_bytes_per_second, 0.0);
+        assert_eq!(once.limit, MIB);
+        assert_eq!(suggested_data_limit(&suggestions), Some(100 * MIB));
+
+        let weekly = suggest(&samples, ResetPeriod::Weekly);
+        assert_eq!(weekly[0].limit, GIB);
+        assert_eq!(
+            suggested_data_limit(&suggest(&[], ResetPeriod::Daily)),
+            None
+        );
+    }
+}
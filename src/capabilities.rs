@@ -0,0 +1,206 @@
+//! What this machine lets the service do, probed once at startup so a
+//! missing capability is reported instead of showing up as zeros.
+
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::paths;
+
+/// Per-process disk counters, which app usage is made of.
+pub const PROCESS_IO: &str = "process_io";
+/// A desktop notification server to show alerts.
+pub const NOTIFICATIONS: &str = "notifications";
+/// A writable data directory.
+pub const DATA_DIR: &str = "data_dir";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capability {
+    pub name: String,
+    pub available: bool,
+    /// Why it is unavailable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Capability {
+    pub fn available(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            available: true,
+            detail: None,
+        }
+    }
+
+    pub fn unavailable(name: &str, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            available: false,
+            detail: Some(detail.into()),
+        }
+    }
+}
+
+/// The result of every probe that ran, in the order they ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Capabilities(pub Vec<Capability>);
+
+impl Capabilities {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether `name` is available. One that was not probed is assumed to
+    /// be.
+    pub fn is_available(&self, name: &str) -> bool {
+        self.0
+            .iter()
+            .find(|capability| capability.name == name)
+            .is_none_or(|capability| capability.available)
+    }
+
+    pub fn unavailable(&self) -> impl Iterator<Item = &Capability> {
+        self.0.iter().filter(|capability| !capability.available)
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, capability) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            let state = if capability.available {
+                "ok"
+            } else {
+                "unavailable"
+            };
+            write!(f, "{} {}", capability.name, state)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether per-process disk counters work, given every process's total
+/// bytes read and written. Some platforms report 0 for all of them.
+pub fn process_io(disk_bytes: impl IntoIterator<Item = u64>) -> Capability {
+    let mut processes = 0;
+    for bytes in disk_bytes {
+        if bytes > 0 {
+            return Capability::available(PROCESS_IO);
+        }
+        processes += 1;
+    }
+    if processes == 0 {
+        return Capability::unavailable(PROCESS_IO, "No processes could be read");
+    }
+    Capability::unavailable(
+        PROCESS_IO,
+        format!(
+            "All {} processes report no disk I/O, so per-process counters are not supported here",
+            processes
+        ),
+    )
+}
+
+/// Whether the desktop notification server answered.
+pub fn notifications(server: Result<(), String>) -> Capability {
+    match server {
+        Ok(()) => Capability::available(NOTIFICATIONS),
+        Err(e) => Capability::unavailable(NOTIFICATIONS, e),
+    }
+}
+
+/// Whether files can be written to `dir`.
+pub fn data_dir(dir: &Path) -> Capability {
+    match paths::ensure_writable(dir) {
+        Ok(()) => Capability::available(DATA_DIR),
+        Err(e) => Capability::unavailable(DATA_DIR, format!("{}: {}", dir.display(), e)),
+    }
+}
+
+/// The total bytes read and written by every running process.
+#[cfg(feature = "monitor")]
+pub fn read_process_io() -> Vec<u64> {
+    use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().with_disk_usage(),
+    );
+    system
+        .processes()
+        .values()
+        .map(|process| {
+            let usage = process.disk_usage();
+            usage
+                .total_read_bytes
+                .saturating_add(usage.total_written_bytes)
+        })
+        .collect()
+}
+
+/// Asks the desktop notification server for its name.
+#[cfg(all(target_os = "linux", feature = "notifications"))]
+pub fn reach_notification_server() -> Result<(), String> {
+    notify_rust::get_server_information()
+        .map(|_| ())
+        .map_err(|e| format!("No desktop notification server: {}", e))
+}
+
+/// Only Linux has a server to ask; elsewhere notifications are assumed to
+/// work.
+#[cfg(all(not(target_os = "linux"), feature = "notifications"))]
+pub fn reach_notification_server() -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(not(feature = "notifications"))]
+pub fn reach_notification_server() -> Result<(), String> {
+    Err("Built without the notifications feature".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_io() {
+        assert!(process_io([0, 0, 4096]).available);
+        let zeros = process_io([0, 0, 0]);
+        assert!(!zeros.available);
+        assert!(zeros.detail.unwrap().starts_with("All 3 processes"));
+        assert!(!process_io([]).available);
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, b"").unwrap();
+        assert!(data_dir(dir.path()).available);
+        assert!(!data_dir(&file.join("data")).available);
+
+        let capabilities = Capabilities(vec![
+            process_io([0]),
+            notifications(Err("No desktop notification server".to_string())),
+            data_dir(dir.path()),
+        ]);
+        assert!(!capabilities.is_available(PROCESS_IO));
+        assert!(capabilities.is_available(DATA_DIR));
+        assert!(Capabilities::default().is_available(PROCESS_IO));
+        assert_eq!(
+            capabilities.to_string(),
+            "process_io unavailable, notifications unavailable, data_dir ok"
+        );
+        assert_eq!(capabilities.unavailable().count(), 2);
+        assert_eq!(
+            serde_json::to_value(&capabilities).unwrap()[2],
+            serde_json::json!({ "name": "data_dir", "available": true })
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::path::{Path, PathBuf};
+
+use super::monitor::{CounterKey, CounterSource, Counters, MonitorError, ProcessData};
+
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+#[derive(Debug)]
+pub struct CgroupSource {
+    root: PathBuf,
+}
+
+impl CgroupSource {
+    pub fn detect() -> Result<Self, MonitorError> {
+        Self::new(CGROUP_ROOT)
+    }
+
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self, MonitorError> {
+        let root = root.into();
+        if !root.join("cgroup.controllers").is_file() {
+            return Err(MonitorError::CgroupV2Unavailable(root));
+        }
+        let path = root.join("cgroup.subtree_control");
+        let subtree =
+            std::fs::read_to_string(&path).map_err(|source| MonitorError::Io { path, source })?;
+        if !subtree
+            .split_whitespace()
+            .any(|controller| controller == "io")
+        {
+            return Err(MonitorError::IoControllerDisabled(root));
+        }
+        Ok(Self { root })
+    }
+
+    fn walk(&self, dir: &Path, data: &mut ProcessData) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let children: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+            .map(|entry| entry.path())
+            .collect();
+
+        let unit = dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(unit_name);
+        if let Some(unit) = unit
+            && !children.iter().any(|child| is_systemd_node(child))
+        {
+            if let Ok(stat) = std::fs::read_to_string(dir.join("io.stat")) {
+                let key = dir.strip_prefix(&self.root).unwrap_or(dir).to_path_buf();
+                data.insert(
+                    CounterKey::Cgroup(key),
+                    Counters::new(unit, io_stat_bytes(&stat)).with_written(io_stat_written(&stat)),
+                );
+            }
+            return;
+        }
+        for child in children {
+            self.walk(&child, data);
+        }
+    }
+}
+
+impl CounterSource for CgroupSource {
+    fn read(&self) -> Result<ProcessData, MonitorError> {
+        std::fs::read_dir(&self.root).map_err(|source| MonitorError::Io {
+            path: self.root.clone(),
+            source,
+        })?;
+        let mut data = ProcessData::new();
+        self.walk(&self.root, &mut data);
+        Ok(data)
+    }
+}
+
+fn is_systemd_node(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "slice" || ext == "scope" || ext == "service")
+}
+
+pub fn unit_name(name: &str) -> Option<String> {
+    if let Some(service) = name.strip_suffix(".service") {
+        return Some(service.to_string());
+    }
+    let scope = name.strip_suffix(".scope")?;
+    let name = match scope.rsplit_once('-') {
+        Some((base, instance))
+            if !base.is_empty() && instance.bytes().all(|byte| byte.is_ascii_digit()) =>
+        {
+            base
+        }
+        _ => scope,
+    };
+    Some(name.to_string())
+}
+
+pub fn io_stat_bytes(stat: &str) -> u64 {
+    io_stat_sum(stat, &["rbytes", "wbytes"])
+}
+
+pub fn io_stat_written(stat: &str) -> u64 {
+    io_stat_sum(stat, &["wbytes"])
+}
+
+fn io_stat_sum(stat: &str, keys: &[&str]) -> u64 {
+    stat.lines()
+        .flat_map(|line| line.split_whitespace().skip(1))
+        .filter_map(|field| {
+            let (key, bytes) = field.split_once('=')?;
+            keys.contains(&key).then(|| bytes.parse::<u64>().ok())?
+        })
+        .fold(0u64, |sum, bytes| sum.saturating_add(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_cgroup(root: &Path, path: &str, io_stat: Option<&str>) {
+        let dir = root.join(path);
+        std::fs::create_dir_all(&dir).unwrap();
+        if let Some(io_stat) = io_stat {
+            std::fs::write(dir.join("io.stat"), io_stat).unwrap();
+        }
+    }
+
+    fn cgroup_root(subtree: &str) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(
+            root.path().join("cgroup.controllers"),
+            "cpu io memory pids\n",
+        )
+        .unwrap();
+        std::fs::write(root.path().join("cgroup.subtree_control"), subtree).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_detect_errors() {
+        let empty = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            CgroupSource::new(empty.path()),
+            Err(MonitorError::CgroupV2Unavailable(_))
+        ));
+
+        let root = cgroup_root("cpu memory\n");
+        assert!(matches!(
+            CgroupSource::new(root.path()),
+            Err(MonitorError::IoControllerDisabled(_))
+        ));
+
+        let source = CgroupSource::new(cgroup_root("io\n").path()).unwrap();
+        match source.read() {
+            Err(MonitorError::Io { path, .. }) => assert_eq!(path, source.root),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_unit_name() {
+        assert_eq!(unit_name("nginx.service"), Some("nginx".to_string()));
+        assert_eq!(unit_name("session-2.scope"), Some("session".to_string()));
+        assert_eq!(
+            unit_name("app-gnome-org.gnome.Terminal-3412.scope"),
+            Some("app-gnome-org.gnome.Terminal".to_string())
+        );
+        assert_eq!(
+            unit_name("docker-4f1c.scope"),
+            Some("docker-4f1c".to_string())
+        );
+        assert_eq!(unit_name("user.slice"), None);
+        assert_eq!(unit_name("app"), None);
+    }
+
+    #[test]
+    fn test_io_stat_bytes() {
+        let stat = "8:0 rbytes=1000 wbytes=200 rios=3 wios=4 dbytes=50 dios=1\n\
+                    259:0 rbytes=10 wbytes=5 rios=1 wios=1 dbytes=0 dios=0\n";
+        assert_eq!(io_stat_bytes(stat), 1215);
+        assert_eq!(io_stat_bytes(""), 0);
+        assert_eq!(io_stat_bytes("8:0 rbytes=x wbytes=7\n"), 7);
+        assert_eq!(io_stat_written(stat), 205);
+    }
+
+    #[test]
+    fn test_read_units() {
+        let root = cgroup_root("cpu io memory\n");
+        write_cgroup(
+            root.path(),
+            "system.slice/nginx.service",
+            Some("8:0 rbytes=100 wbytes=20\n"),
+        );
+        write_cgroup(
+            root.path(),
+            "user.slice/user-1000.slice/user@1000.service",
+            Some("8:0 rbytes=9999 wbytes=9999\n"),
+        );
+        write_cgroup(
+            root.path(),
+            "user.slice/user-1000.slice/user@1000.service/app.slice/app-flatpak-org.mozilla.firefox-2345.scope",
+            Some("8:0 rbytes=300 wbytes=0\n"),
+        );
+        write_cgroup(
+            root.path(),
+            "user.slice/user-1000.slice/user@1000.service/app.slice/app-flatpak-org.mozilla.firefox-2345.scope/app",
+            Some("8:0 rbytes=300 wbytes=0\n"),
+        );
+        write_cgroup(root.path(), "system.slice/empty.service", None);
+
+        let source = CgroupSource::new(root.path()).unwrap();
+        let mut units: Vec<_> = source
+            .read()
+            .unwrap()
+            .into_values()
+            .map(|counters| (counters.name, counters.bytes))
+            .collect();
+        units.sort();
+        assert_eq!(
+            units,
+            [
+                ("app-flatpak-org.mozilla.firefox".to_string(), 300),
+                ("nginx".to_string(), 120),
+            ]
+        );
+        assert!(
+            source
+                .read()
+                .unwrap()
+                .contains_key(&CounterKey::Cgroup(PathBuf::from(
+                    "system.slice/nginx.service"
+                )))
+        );
+    }
+}
@@ -0,0 +1,2253 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use color_eyre::Result;
+use color_eyre::eyre::{Context, bail};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{
+    control_endpoint, load_alerts_log, load_notification_history, load_persisted_data,
+    read_persisted_data, require_persistence_paths, save_persisted_data,
+};
+use data_guardian::calibrate::{self, Sample};
+use data_guardian::collector::{self, Collector};
+use data_guardian::compare::{self, ChangeKind, Comparison};
+use data_guardian::control::{ControlClient, ControlRequest, ControlResponse};
+use data_guardian::effective_config::EffectiveConfig;
+use data_guardian::events::{Event, EventFilter, EventKind};
+use data_guardian::history::NotificationOutcome;
+use data_guardian::import::{self, ImportFormat};
+use data_guardian::influx;
+use data_guardian::logging::LogFormat;
+use data_guardian::merge::{self, MergeInput, MergeStrategy};
+use data_guardian::monitor::{self, Sampler, UsageData};
+use data_guardian::notification::{Alert, AlertKind, DEFAULT_APP_NAME, NotificationManager};
+use data_guardian::paths::ResolvedPath;
+use data_guardian::persistence::PersistenceError;
+use data_guardian::replay::{self, Outcome, Trace};
+use data_guardian::report::{self, SCHEMA_VERSION, UsageSource};
+use data_guardian::self_check::SelfUsage;
+use data_guardian::settings::{Settings, SettingsError};
+use data_guardian::status::{self, Health, StatusReport};
+use data_guardian::tracker::{self, AppResources, ExceedanceStats};
+use data_guardian::version;
+
+#[derive(Debug, Parser)]
+#[command(name = "dg", version, long_version = version::LONG_VERSION, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Read settings from this file instead of the default location
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Store usage data, history and the control socket in this directory
+    #[arg(long, global = true, value_name = "DIR", alias = "state-dir")]
+    pub data_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub overrides: SettingsOverrides,
+
+    /// Print only versioned JSON on stdout and send logs to stderr
+    #[arg(long, global = true)]
+    pub json: bool,
+}
+
+/// Settings given on the command line, which win over the config file on
+/// startup and on every reload.
+#[derive(Debug, Clone, Default, Args)]
+pub struct SettingsOverrides {
+    /// Log output format
+    #[arg(long, global = true, value_enum)]
+    pub log_format: Option<LogFormat>,
+
+    /// Also write logs to this file
+    #[arg(long, global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// Log level for Data Guardian (error, warn, info, debug, trace)
+    #[arg(long, global = true)]
+    pub log_level: Option<String>,
+}
+
+impl SettingsOverrides {
+    pub fn apply(&self, settings: &mut Settings) -> Result<(), SettingsError> {
+        if let Some(format) = self.log_format {
+            settings.log_format = format;
+        }
+        if let Some(path) = &self.log_file {
+            settings.log_file = Some(path.clone());
+        }
+        if let Some(level) = &self.log_level {
+            settings.log_level = level.clone();
+        }
+        settings.validate()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// InfluxDB line protocol with the current totals
+    Influx,
+    /// One row per day and application from the daily history (needs the arrow feature)
+    Parquet,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Print the notifications sent while the service was running
+    History {
+        /// Only show notifications newer than this (e.g. 30m, 24h, 7d)
+        #[arg(long)]
+        since: Option<humantime::Duration>,
+    },
+    /// Print the accumulated usage per application
+    Report {
+        /// Show how often each application went over its limit instead
+        #[arg(long, conflicts_with = "compare")]
+        stats: bool,
+        /// Compare usage in the current period with the previous one
+        #[arg(long)]
+        compare: bool,
+        /// Output format for --compare
+        #[arg(long, value_enum, default_value_t, requires = "compare")]
+        format: ReportFormat,
+    },
+    /// Show whether the service is running and what it is tracking
+    Status {
+        /// Have the service scan now instead of showing its last scan
+        #[arg(long)]
+        fresh: bool,
+        /// Print the settings the service last started or reloaded with,
+        /// where each came from, and the paths it uses
+        #[arg(long, conflicts_with = "fresh")]
+        effective_config: bool,
+    },
+    /// Open a live, full-screen view of per-application usage
+    Dashboard {
+        /// Time between samples (e.g. 1s, 500ms)
+        #[arg(long, default_value = "1s")]
+        interval: humantime::Duration,
+        /// Number of samples kept for the total I/O sparkline
+        #[arg(long, default_value_t = 60)]
+        samples: usize,
+    },
+    /// Sample live usage for a while and suggest a data limit
+    Calibrate {
+        /// How long to sample (e.g. 10m, 1h)
+        #[arg(long, default_value = "10m")]
+        duration: humantime::Duration,
+        /// Time between samples (e.g. 10s)
+        #[arg(long, default_value = "10s")]
+        interval: humantime::Duration,
+        /// Write the suggested data_limit to the config file
+        #[arg(long)]
+        write: bool,
+    },
+    /// Save raw process snapshots to a trace file for `dg replay`
+    Record {
+        /// How long to record (e.g. 10m, 1h)
+        #[arg(long, default_value = "10m")]
+        duration: humantime::Duration,
+        /// Time between snapshots (defaults to check_interval_seconds)
+        #[arg(long)]
+        interval: Option<humantime::Duration>,
+        #[arg(long, short, default_value = "trace.dgz")]
+        output: PathBuf,
+    },
+    /// Count a recorded trace as the service would and print every step
+    Replay {
+        /// Trace written by `dg record`
+        trace: PathBuf,
+        /// Settings to replay with instead of the ones in effect
+        #[arg(long, value_name = "PATH")]
+        settings: Option<PathBuf>,
+    },
+    /// Print or change a single setting
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Write the persisted usage in another format
+    #[command(group(ArgGroup::new("output_format").required(true)))]
+    Export {
+        /// InfluxDB line protocol (same as --format influx)
+        #[arg(long, group = "output_format")]
+        influx: bool,
+        #[arg(long, value_enum, group = "output_format")]
+        format: Option<ExportFormat>,
+        /// Write to this file instead of stdout (required for parquet and --json)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Combine data files from several machines into one
+    Merge {
+        /// File to write; must not be one of the inputs
+        output: PathBuf,
+        /// Data files to combine; they are only read
+        inputs: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value = "sum")]
+        strategy: MergeStrategy,
+        /// Host label for keep-per-host, once per input in order (defaults to INPUT.host)
+        #[arg(long = "host", value_name = "LABEL")]
+        hosts: Vec<String>,
+    },
+    /// Add usage history from vnstat or nethogs to the data file
+    Import {
+        /// File to read
+        file: PathBuf,
+        #[arg(long, value_enum)]
+        from: ImportFormat,
+        /// How imported usage combines with usage already recorded
+        #[arg(long, value_enum, default_value = "sum")]
+        strategy: MergeStrategy,
+        /// Host label for keep-per-host
+        #[arg(long, value_name = "LABEL")]
+        host: Option<String>,
+    },
+    /// Run a server instead of the monitoring service
+    #[command(group(ArgGroup::new("mode").required(true)))]
+    Serve {
+        /// Accept usage snapshots from agents and serve an aggregate report
+        #[arg(long, group = "mode")]
+        collect: bool,
+        /// Address to listen on (defaults to collector.listen_addr)
+        #[arg(long)]
+        listen: Option<SocketAddr>,
+    },
+    /// Send a test notification through every configured channel
+    NotifyTest,
+    /// Check that the running service is scanning and saving (exit 0 ok, 1 unhealthy, 3 not running)
+    Health,
+    /// Print what is over its limit (exit 0 nothing, 2 something, 3 no data)
+    Check,
+    /// Ask the running service to write its state to disk
+    Flush,
+    /// Forget the accumulated usage of an application
+    Reset { app: String },
+    /// Stop scanning processes until resumed
+    Pause,
+    /// Resume scanning processes
+    Resume,
+    /// Ask the running service to reload its configuration
+    Reload,
+    /// Silence notifications for an application, or for all applications
+    Snooze {
+        app: Option<String>,
+        /// How long to stay silent (e.g. 30m, 2h)
+        #[arg(long = "for")]
+        duration: Option<humantime::Duration>,
+    },
+    /// Show how long each application's notifications stay on cooldown
+    Cooldowns,
+    /// Let the next notification for an application, or for all applications, through
+    ClearCooldown { app: Option<String> },
+    /// Print events from the running service as they happen
+    Follow {
+        /// Only these kinds of events (e.g. alerts,saves)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        only: Vec<EventKind>,
+        /// Only events about this application; `*` matches any run of characters
+        #[arg(long)]
+        app: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the value in effect for a setting (e.g. data_limit, mqtt.broker)
+    Get { key: String },
+    /// Write a setting to the config file and ask the running service to reload
+    Set {
+        key: String,
+        /// A TOML value, a byte count such as 2GB, or plain text
+        value: String,
+        /// Leave the running service on its current settings
+        #[arg(long)]
+        no_reload: bool,
+    },
+}
+
+pub async fn send_control(request: &ControlRequest) -> Result<Option<ControlResponse>> {
+    let client = ControlClient::new(control_endpoint().ok_or(PersistenceError::NoStateDir)?);
+    match client.request(request).await {
+        Ok(response) => Ok(Some(response)),
+        Err(e) if e.is_not_running() => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn require_daemon(request: ControlRequest) -> Result<ControlResponse> {
+    match send_control(&request).await? {
+        Some(response) if response.ok => Ok(response),
+        Some(response) => bail!(response.error.unwrap_or_default()),
+        None => bail!("Data Guardian is not running"),
+    }
+}
+
+pub async fn daemon_status() -> Result<Option<StatusReport>> {
+    request_status(&ControlRequest::Status).await
+}
+
+async fn request_status(request: &ControlRequest) -> Result<Option<StatusReport>> {
+    match send_control(request).await? {
+        Some(ControlResponse {
+            ok: true,
+            data: Some(data),
+            ..
+        }) => Ok(Some(serde_json::from_value(data)?)),
+        _ => Ok(None),
+    }
+}
+
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub async fn check_health() -> Health {
+    match control_endpoint() {
+        Some(endpoint) => health_of(&ControlClient::new(endpoint)).await,
+        None => Health::unhealthy(PersistenceError::NoStateDir.to_string()),
+    }
+}
+
+async fn health_of(client: &ControlClient) -> Health {
+    let response = tokio::time::timeout(HEALTH_TIMEOUT, client.request(&ControlRequest::Status));
+    match response.await {
+        Ok(Ok(ControlResponse {
+            ok: true,
+            data: Some(data),
+            ..
+        })) => match serde_json::from_value::<StatusReport>(data) {
+            Ok(report) => Health::check(&report, Utc::now()),
+            Err(e) => Health::unhealthy(format!("invalid status response: {}", e)),
+        },
+        Ok(Ok(response)) => Health::unhealthy(response.error.unwrap_or_default()),
+        Ok(Err(e)) if e.is_not_running() => Health::not_running("Data Guardian is not running"),
+        Ok(Err(e)) => Health::unhealthy(e.to_string()),
+        Err(_) => Health::unhealthy(format!(
+            "no answer within {}",
+            humantime::format_duration(HEALTH_TIMEOUT)
+        )),
+    }
+}
+
+/// Asks the running service first and falls back to the data file, so it
+/// also answers while the service is stopped.
+pub async fn check(settings: &Settings, json: bool) -> Result<report::Check> {
+    let check = match daemon_status().await.ok().flatten() {
+        Some(report) => report::Check::new(true, Some(report.offenders)),
+        None => match read_persisted_data().await {
+            Ok(tracker) => report::Check::new(
+                false,
+                tracker.map(|tracker| status::over_limit(&tracker.usage(), settings)),
+            ),
+            Err(e) => report::Check::failed(e.to_string()),
+        },
+    };
+    if json {
+        print_json(&check)?;
+    } else if let Some(error) = &check.error {
+        eprintln!("{}", error);
+    } else if check.status == report::CheckState::NoData {
+        eprintln!("No usage data yet");
+    } else {
+        for offender in &check.offenders {
+            println!("{}", offender);
+        }
+    }
+    Ok(check)
+}
+
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+pub async fn run_control(command: Command, json: bool) -> Result<()> {
+    let (name, request) = match command {
+        Command::Flush => ("flush", ControlRequest::Flush),
+        Command::Pause => ("pause", ControlRequest::Pause),
+        Command::Resume => ("resume", ControlRequest::Resume),
+        Command::Reload => ("reload", ControlRequest::Reload),
+        Command::Snooze { app, duration } => (
+            "snooze",
+            ControlRequest::Snooze {
+                app,
+                seconds: duration.map(|duration| Duration::from(duration).as_secs()),
+            },
+        ),
+        command => bail!("{:?} is not a control command", command),
+    };
+    require_daemon(request).await?;
+    match json {
+        true => print_json(&report::Ack::new(name, "OK")),
+        false => {
+            println!("OK");
+            Ok(())
+        }
+    }
+}
+
+pub async fn notify_test(
+    settings: &Settings,
+    notifier: &NotificationManager,
+    json: bool,
+) -> Result<()> {
+    let status = daemon_status().await;
+    let alert = Alert::new(DEFAULT_APP_NAME).with_test();
+    if json {
+        let report = status.as_ref().ok().and_then(Option::as_ref);
+        let channels: Vec<_> = notifier
+            .test_channels(&alert)
+            .into_iter()
+            .map(|(channel, result)| report::ChannelResult {
+                channel,
+                ok: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            })
+            .collect();
+        let total = channels.len();
+        let failed = channels.iter().filter(|channel| !channel.ok).count();
+        print_json(&report::NotifyTest {
+            schema_version: SCHEMA_VERSION,
+            data_limit: settings.data_limit,
+            cooldown_seconds: notifier.cooldown().as_secs(),
+            critical_cooldown_seconds: notifier.critical_cooldown().as_secs(),
+            service_running: status.as_ref().ok().map(Option::is_some),
+            paused: report.map(|report| report.paused),
+            snoozed_seconds: report.and_then(|report| report.snoozes.all_seconds),
+            snoozed_apps: report
+                .map(|report| report.snoozes.apps.clone())
+                .unwrap_or_default(),
+            channels,
+        })?;
+        return check_channels(total, failed);
+    }
+
+    let format = |duration: Duration| humantime::format_duration(duration).to_string();
+    println!("Data limit: {} bytes", settings.data_limit);
+    println!(
+        "Cooldown: {} ({} for critical alerts)",
+        format(notifier.cooldown()),
+        format(notifier.critical_cooldown())
+    );
+    match status {
+        Ok(Some(report)) => {
+            println!(
+                "Service: running{}",
+                if report.paused { ", paused" } else { "" }
+            );
+            match report.snoozes.all_seconds {
+                Some(all) => println!(
+                    "Snoozed: everything for {}",
+                    format(Duration::from_secs(all))
+                ),
+                None => println!("Snoozed: no"),
+            }
+            for (app, remaining) in &report.snoozes.apps {
+                println!(
+                    "Snoozed: {} for {}",
+                    app,
+                    format(Duration::from_secs(*remaining))
+                );
+            }
+        }
+        Ok(None) => println!("Service: not running (snooze state unknown)"),
+        Err(e) => println!("Service: unknown ({})", e),
+    }
+    println!();
+
+    let results = notifier.test_channels(&alert);
+    let mut failed = 0;
+    for (channel, result) in &results {
+        match result {
+            Ok(()) => println!("{:<10} ok", channel),
+            Err(e) => {
+                failed += 1;
+                println!("{:<10} failed: {}", channel, e);
+            }
+        }
+    }
+    check_channels(results.len(), failed)
+}
+
+fn check_channels(total: usize, failed: usize) -> Result<()> {
+    if total == 0 {
+        bail!("No notification channels are available on this platform");
+    }
+    if failed > 0 {
+        bail!("{} of {} notification channels failed", failed, total);
+    }
+    Ok(())
+}
+
+/// Samples usage every `interval` for `duration`, then prints a suggested
+/// limit per app. With `write`, the largest becomes `data_limit` in the
+/// config file.
+pub async fn calibrate(
+    settings: &Settings,
+    duration: Duration,
+    interval: Duration,
+    write: Option<&ResolvedPath>,
+    json: bool,
+) -> Result<()> {
+    if interval.is_zero() || interval > duration {
+        bail!("--interval must be positive and no longer than --duration");
+    }
+    let source = settings.source()?;
+    let mut sampler = Sampler::new().with_max_rate(settings.max_bytes_per_second);
+    sampler.advance(monitor::snapshot(source.clone()).await?);
+    info!(
+        duration = %humantime::format_duration(duration),
+        "Sampling usage; keep using the machine as usual"
+    );
+
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    let started = tokio::time::Instant::now();
+    let mut last = started;
+    let mut samples = Vec::new();
+    while last - started < duration {
+        ticker.tick().await;
+        let current = monitor::snapshot(source.clone()).await?;
+        let now = tokio::time::Instant::now();
+        samples.push(Sample {
+            elapsed: now - last,
+            usage: sampler.advance(current).usage,
+        });
+        last = now;
+    }
+
+    let apps = calibrate::suggest(&samples, settings.reset_period);
+    let data_limit = calibrate::suggested_data_limit(&apps);
+    let written_to = match (write, data_limit) {
+        (Some(config), Some(limit)) => {
+            let mut saved = match config.path.exists() {
+                true => Settings::from_file(&config.path)?,
+                false => Settings::default(),
+            };
+            saved.data_limit = limit;
+            saved.save(&config.path)?;
+            Some(config.path.display().to_string())
+        }
+        _ => None,
+    };
+    if json {
+        return print_json(&report::Calibration {
+            schema_version: SCHEMA_VERSION,
+            sampled_seconds: (last - started).as_secs(),
+            reset_period: settings.reset_period,
+            apps,
+            data_limit,
+            written_to,
+        });
+    }
+
+    let Some(data_limit) = data_limit else {
+        println!("No application moved any data while sampling");
+        return Ok(());
+    };
+    print!("{}", render_calibration(&apps));
+    println!(
+        "\nSuggested data_limit = {} ({}), enough for every application over {}",
+        data_limit,
+        calibrate::format_limit(data_limit),
+        humantime::format_duration(calibrate::period_length(settings.reset_period)),
+    );
+    match written_to {
+        Some(path) => println!("Wrote data_limit to {}", path),
+        None => println!("Run again with --write to save it to the config file"),
+    }
+    Ok(())
+}
+
+pub fn config_get(settings: &Settings, key: &str, json: bool) -> Result<()> {
+    let value = settings.get(key)?;
+    if json {
+        return print_json(&report::ConfigValue {
+            schema_version: SCHEMA_VERSION,
+            key: key.to_string(),
+            value,
+            written_to: None,
+            reloaded: None,
+        });
+    }
+    println!("{}", render_value(&value));
+    Ok(())
+}
+
+/// Sets `key` in the config file, leaving the file untouched when the
+/// resulting settings are invalid, and asks a running service to reload.
+pub async fn config_set(
+    config: &ResolvedPath,
+    key: &str,
+    value: &str,
+    reload: bool,
+    json: bool,
+) -> Result<()> {
+    let current = match config.path.exists() {
+        true => Settings::from_file(&config.path)?,
+        false => Settings::default(),
+    };
+    let updated = current.with_value(key, value)?;
+    let changes = updated.save(&config.path)?;
+    let reloaded = match reload && !changes.is_empty() {
+        true => match send_control(&ControlRequest::Reload).await {
+            Ok(Some(response)) if response.ok => Some(true),
+            Ok(Some(response)) => {
+                warn!(
+                    error = response.error.unwrap_or_default(),
+                    "The running service failed to reload"
+                );
+                Some(false)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!(error = %e, "Failed to ask the running service to reload");
+                Some(false)
+            }
+        },
+        false => None,
+    };
+    let value = updated.get(key)?;
+    if json {
+        return print_json(&report::ConfigValue {
+            schema_version: SCHEMA_VERSION,
+            key: key.to_string(),
+            value,
+            written_to: (!changes.is_empty()).then(|| config.path.display().to_string()),
+            reloaded,
+        });
+    }
+
+    match changes.is_empty() {
+        true => println!("{} is already {}", key, render_value(&value)),
+        false => println!(
+            "Set {} = {} in {}",
+            key,
+            render_value(&value),
+            config.path.display()
+        ),
+    }
+    match reloaded {
+        Some(true) => println!("Reloaded the running service"),
+        Some(false) => println!("The running service did not reload; run dg reload to retry"),
+        None => {}
+    }
+    Ok(())
+}
+
+fn render_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(value) => value.clone(),
+        serde_json::Value::Null => "(not set)".to_string(),
+        value => value.to_string(),
+    }
+}
+
+/// Records snapshots until `duration` has passed, then writes them all.
+pub async fn record(
+    settings: &Settings,
+    duration: Duration,
+    interval: Duration,
+    output: &Path,
+    json: bool,
+) -> Result<()> {
+    if interval.is_zero() || interval > duration {
+        bail!("--interval must be positive and no longer than --duration");
+    }
+    let source = settings.source()?;
+    let mut trace = Trace::new();
+    info!(
+        duration = %humantime::format_duration(duration),
+        output = %output.display(),
+        "Recording snapshots"
+    );
+
+    let mut ticker = tokio::time::interval(interval);
+    let started = tokio::time::Instant::now();
+    loop {
+        ticker.tick().await;
+        trace.push(Utc::now(), &monitor::snapshot(source.clone()).await?);
+        if started.elapsed() >= duration {
+            break;
+        }
+    }
+
+    let bytes = trace.write(output)?;
+    if json {
+        return print_json(&report::Recording {
+            schema_version: SCHEMA_VERSION,
+            frames: trace.frames.len(),
+            bytes,
+            path: output.display().to_string(),
+        });
+    }
+    println!(
+        "Wrote {} snapshots to {} ({})",
+        trace.frames.len(),
+        output.display(),
+        settings.format_bytes(bytes)
+    );
+    Ok(())
+}
+
+pub fn replay(settings: Settings, trace: &Path, json: bool) -> Result<()> {
+    let trace = Trace::read(trace)?;
+    let format = settings.clone();
+    let steps = replay::replay(&trace, settings);
+    if json {
+        return print_json(&report::Replay {
+            schema_version: SCHEMA_VERSION,
+            recorded_by: trace.recorded_by,
+            steps,
+        });
+    }
+    for step in &steps {
+        print!("{}", render_step(step, &format));
+    }
+    Ok(())
+}
+
+/// One line per delta, rejected delta and alert decision of `step`, in UTC
+/// so a trace prints the same everywhere.
+fn render_step(step: &replay::Step, settings: &Settings) -> String {
+    let time = step.time.format("%Y-%m-%d %H:%M:%S");
+    let mut lines = format!("{}  scan     {} processes\n", time, step.processes);
+    for (app, delta) in step.deltas.iter().filter(|(_, delta)| **delta > 0) {
+        lines.push_str(&format!(
+            "{}  delta    {} +{} = {}\n",
+            time,
+            app,
+            settings.format_bytes(*delta),
+            settings.format_bytes(step.totals.get(app).copied().unwrap_or(0))
+        ));
+    }
+    for (app, delta) in &step.rejected {
+        lines.push_str(&format!(
+            "{}  rejected {} +{} above max_bytes_per_second\n",
+            time,
+            app,
+            settings.format_bytes(*delta)
+        ));
+    }
+    for decision in &step.decisions {
+        let mut line = format!(
+            "{}  alert    {} {}",
+            time,
+            decision.app,
+            decision.kind.as_str()
+        );
+        if let (Some(usage), Some(limit)) = (decision.usage, decision.limit) {
+            let (usage, limit) = match decision.kind {
+                AlertKind::Cpu => (format!("{} s", usage), format!("{} s", limit)),
+                _ => (settings.format_bytes(usage), settings.format_bytes(limit)),
+            };
+            line.push_str(&format!(" {} of {}", usage, limit));
+        }
+        line.push_str(match decision.outcome {
+            Outcome::Alert => ", would send",
+            Outcome::AlreadyAlerted => ", already sent this period",
+            Outcome::Grace => ", held back for the grace period",
+        });
+        lines.push_str(&line);
+        lines.push('\n');
+    }
+    lines
+}
+
+fn render_calibration(apps: &[calibrate::Suggestion]) -> String {
+    let width = apps
+        .iter()
+        .map(|suggestion| suggestion.app.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("APPLICATION".len());
+    let mut table = format!(
+        "{:<width$}  {:>16}  {:>16}  SUGGESTED LIMIT\n",
+        "APPLICATION", "OBSERVED BYTES", "P95 BYTES/S"
+    );
+    for suggestion in apps {
+        table.push_str(&format!(
+            "{:<width$}  {:>16}  {:>16.0}  {}\n",
+            suggestion.app,
+            suggestion.observed_bytes,
+            suggestion.p95_bytes_per_second,
+            calibrate::format_limit(suggestion.limit)
+        ));
+    }
+    table
+}
+
+pub async fn print_cooldowns(json: bool) -> Result<()> {
+    let response = require_daemon(ControlRequest::Cooldowns).await?;
+    let cooldowns: report::Cooldowns = serde_json::from_value(response.data.unwrap_or_default())?;
+    if json {
+        return print_json(&cooldowns);
+    }
+    if cooldowns.apps.is_empty() {
+        println!("No application is on cooldown");
+    }
+    for (app, remaining) in &cooldowns.apps {
+        println!(
+            "{}: {}",
+            app,
+            humantime::format_duration(Duration::from_secs(*remaining))
+        );
+    }
+    Ok(())
+}
+
+pub async fn clear_cooldown(app: Option<String>, json: bool) -> Result<()> {
+    let response = require_daemon(ControlRequest::ClearCooldown { app: app.clone() }).await?;
+    let cleared: u64 = serde_json::from_value(response.data.unwrap_or_default())?;
+    let message = match (app, cleared) {
+        (Some(app), 0) => format!("{} was not on cooldown", app),
+        (Some(app), _) => format!("Cleared the cooldown of {}", app),
+        (None, cleared) => format!("Cleared {} cooldowns", cleared),
+    };
+    match json {
+        true => print_json(&report::Ack::new("clear-cooldown", message)),
+        false => {
+            println!("{}", message);
+            Ok(())
+        }
+    }
+}
+
+pub async fn reset_app(app: &str, json: bool) -> Result<()> {
+    let request = ControlRequest::Reset {
+        app: app.to_string(),
+    };
+    match send_control(&request).await? {
+        Some(response) if !response.ok => bail!(response.error.unwrap_or_default()),
+        Some(_) => {}
+        None => {
+            let mut tracker = load_persisted_data().await.unwrap_or_default();
+            if !tracker.reset_app(app) {
+                bail!("No usage recorded for {}", app);
+            }
+            save_persisted_data(&tracker).await?;
+        }
+    }
+
+    let message = format!("Reset usage for {}", app);
+    match json {
+        true => print_json(&report::Ack::new("reset", message)),
+        false => {
+            println!("{}", message);
+            Ok(())
+        }
+    }
+}
+
+/// Prints `effective-config.json`, indented unless `json`.
+pub fn print_effective_config(json: bool) -> Result<()> {
+    let state_dir = require_persistence_paths()?.state_dir;
+    let snapshot = match EffectiveConfig::read(&state_dir) {
+        Ok(snapshot) => snapshot,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => bail!(
+            "No effective configuration in {}; it is written when the service starts",
+            state_dir.display()
+        ),
+        Err(e) => {
+            return Err(e).context(format!(
+                "Failed to read {}",
+                EffectiveConfig::path(&state_dir).display()
+            ));
+        }
+    };
+    if json {
+        return print_json(&snapshot);
+    }
+    println!("{}", serde_json::to_string_pretty(&snapshot)?);
+    Ok(())
+}
+
+pub async fn print_status(settings: &Settings, json: bool, fresh: bool) -> Result<()> {
+    let request = if fresh {
+        ControlRequest::Scan
+    } else {
+        ControlRequest::Status
+    };
+    let report = request_status(&request).await?;
+    if json {
+        return print_json(&match report {
+            Some(report) => status_json(&report, settings),
+            None => report::Status::not_running(),
+        });
+    }
+    let Some(report) = report else {
+        println!("Data Guardian is not running");
+        if let Some(tracker) = load_persisted_data().await {
+            let state = &tracker.state;
+            if let Some(last_scan) = state.last_scan {
+                println!(
+                    "{}",
+                    render_last_scan(
+                        last_scan,
+                        state.ticks,
+                        state.written_by.as_deref(),
+                        Utc::now()
+                    )
+                );
+            }
+            if let Some(last_save) = state.last_save {
+                println!(
+                    "Last saved at {}",
+                    last_save.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+                );
+            }
+        }
+        return Ok(());
+    };
+
+    let uptime = humantime::format_duration(Duration::from_secs(report.uptime));
+    println!(
+        "Data Guardian is running ({}) for {}",
+        if report.paused { "paused" } else { "active" },
+        uptime
+    );
+    println!("Version {}", report.version);
+    if !report.settings_summary.instance_label.is_empty() {
+        println!("Instance {}", report.settings_summary.instance_label);
+    }
+    println!(
+        "{} scans, {} saves and {} alerts since start ({} suppressed, {} failed)",
+        report.self_metrics.total_ticks,
+        report.self_metrics.total_saves,
+        report.alerts.sent,
+        report.alerts.suppressed,
+        report.alerts.failed
+    );
+    for (task, failures) in [
+        ("Scans", report.self_metrics.scan_failures),
+        ("Saves", report.self_metrics.save_failures),
+    ] {
+        if failures > 0 {
+            println!(
+                "{} failed {} times in a row, retrying less often",
+                task, failures
+            );
+        }
+    }
+    for paused in &report.paused_channels {
+        println!(
+            "Notification channel {} paused for {} after {} failures in a row",
+            paused.channel,
+            humantime::format_duration(Duration::from_secs(paused.remaining_seconds)),
+            paused.failures
+        );
+    }
+    if !report.other_monitors.is_empty() {
+        let names: Vec<_> = report
+            .other_monitors
+            .monitors
+            .iter()
+            .map(|monitor| format!("{} ({})", monitor.name, monitor.process))
+            .collect();
+        println!(
+            "Other data monitors running: {}{}",
+            names.join(", "),
+            if report.other_monitors.network_alerts_log_only {
+                "; network interface alerts go to the log only"
+            } else {
+                ""
+            }
+        );
+    }
+    for capability in report.capabilities.unavailable() {
+        println!(
+            "Unavailable on this machine: {} ({})",
+            capability.name,
+            capability.detail.as_deref().unwrap_or("no details")
+        );
+    }
+    if let Some(session) = &report.session {
+        if session.is_waiting() {
+            println!(
+                "Waiting for root process {}, counting nothing until it starts",
+                session.root
+            );
+        } else {
+            println!(
+                "Counting only the {} processes under {}",
+                session.processes, session.root
+            );
+        }
+    }
+    if let Some(incident) = &report.data_incident {
+        let date = incident.time.with_timezone(&Local).format("%Y-%m-%d");
+        match &incident.restored_from {
+            Some(source) => println!(
+                "Usage data was unreadable on {} and restored from {} ({})",
+                date, source, incident.error
+            ),
+            None => println!(
+                "Usage history reset on {} due to corruption ({})",
+                date, incident.error
+            ),
+        }
+    }
+    if let Some(crash) = &report.last_crash {
+        println!(
+            "Recovered from a crash at {} ({}{})",
+            crash.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+            crash.message,
+            if crash.saved {
+                ""
+            } else {
+                ", usage since the last save was lost"
+            }
+        );
+    }
+    if let Some(last_persist) = report.last_persist {
+        println!(
+            "Last saved at {}",
+            last_persist
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+    if let Some(tick) = &report.self_metrics.last_tick {
+        println!(
+            "{}",
+            render_last_scan(
+                tick.time,
+                tick.tick,
+                Some(&report.version.version),
+                Utc::now()
+            )
+        );
+        println!(
+            "Last scan took {:.1} ms over {} processes (avg {:.1} ms, max lag {:.1} ms)",
+            tick.snapshot_ms,
+            tick.processes,
+            report.self_metrics.avg_snapshot_ms,
+            report.self_metrics.max_lag_ms
+        );
+        println!(
+            "Tracking {} applications ({} seen in the last scan, {} counted as {})",
+            tick.tracked_apps,
+            tick.tick_apps,
+            tick.overflow_apps,
+            tracker::OTHER_APP
+        );
+    }
+    if let Some(usage) = &report.self_metrics.self_usage {
+        println!("{}", render_self_usage(usage, settings));
+    }
+    println!();
+    print!(
+        "{}",
+        render_report(
+            &status_usage(&report),
+            &display_names(&report),
+            &status_resources(&report),
+            settings
+        )
+    );
+    Ok(())
+}
+
+/// Such as "Last scan 37s ago (scan 1520) by v1.0.2". The scan number is
+/// left out when unknown.
+pub fn render_last_scan(
+    time: DateTime<Utc>,
+    tick: u64,
+    version: Option<&str>,
+    now: DateTime<Utc>,
+) -> String {
+    let ago = Duration::from_secs((now - time).num_seconds().max(0) as u64);
+    let mut line = format!("Last scan {} ago", humantime::format_duration(ago));
+    if tick > 0 {
+        line.push_str(&format!(" (scan {})", tick));
+    }
+    if let Some(version) = version {
+        line.push_str(&format!(" by v{}", version));
+    }
+    line
+}
+
+/// One line on the service's own disk use, from the last self-check.
+pub fn render_self_usage(usage: &SelfUsage, settings: &Settings) -> String {
+    let mut line = match (usage.read_bytes, usage.written_bytes) {
+        (Some(read), Some(written)) => format!(
+            "Data Guardian itself read {} and wrote {}",
+            settings.format_bytes(read),
+            settings.format_bytes(written)
+        ),
+        _ => "Data Guardian's own I/O is not reported on this platform".to_string(),
+    };
+    if let Some(per_hour) = usage.io_bytes_per_hour {
+        line.push_str(&format!(" ({}/h)", settings.format_bytes(per_hour)));
+    }
+    line.push_str(&format!(
+        "; data files take {}",
+        settings.format_bytes(usage.data_dir_bytes)
+    ));
+    if let Some(log_bytes) = usage.log_bytes {
+        line.push_str(&format!(", logs {}", settings.format_bytes(log_bytes)));
+    }
+    if usage.persistence_interval_seconds > settings.persistence_interval_seconds {
+        line.push_str(&format!(
+            "; saving every {} to write less",
+            humantime::format_duration(Duration::from_secs(usage.persistence_interval_seconds))
+        ));
+    }
+    line
+}
+
+pub fn status_json(report: &StatusReport, settings: &Settings) -> report::Status {
+    let (apps, disks, interfaces, paths) = report::usage_rows(
+        &status_usage(report),
+        &display_names(report),
+        &status_resources(report),
+        settings,
+    );
+    report::Status {
+        schema_version: SCHEMA_VERSION,
+        running: true,
+        paused: report.paused,
+        version: Some(report.version.to_string()),
+        instance: Some(report.settings_summary.instance_label.clone()),
+        uptime_seconds: Some(report.uptime),
+        last_persist: report.last_persist,
+        totals: Some(report::Totals {
+            scans: report.self_metrics.total_ticks,
+            saves: report.self_metrics.total_saves,
+            alerts_sent: report.alerts.sent,
+            alerts_suppressed: report.alerts.suppressed,
+            alerts_failed: report.alerts.failed,
+        }),
+        apps,
+        disks,
+        interfaces,
+        paths,
+        data_incident: report.data_incident.as_ref().map(Into::into),
+        last_crash: report.last_crash.clone(),
+        self_usage: report.self_metrics.self_usage.as_ref().map(Into::into),
+        paused_channels: report.paused_channels.clone(),
+        other_monitors: report.other_monitors.clone(),
+        session: report.session.clone(),
+        capabilities: report.capabilities.clone(),
+    }
+}
+
+pub fn status_usage(report: &StatusReport) -> UsageData {
+    report
+        .apps
+        .iter()
+        .map(|app| (app.name.clone(), app.usage))
+        .chain(
+            report
+                .disks
+                .iter()
+                .map(|disk| (format!("{}{}", monitor::DISK_PREFIX, disk.name), disk.usage)),
+        )
+        .chain(report.interfaces.iter().map(|interface| {
+            (
+                format!("{}{}", monitor::NET_PREFIX, interface.name),
+                interface.usage,
+            )
+        }))
+        .chain(
+            report
+                .paths
+                .iter()
+                .map(|path| (format!("{}{}", monitor::PATH_PREFIX, path.name), path.usage)),
+        )
+        .collect()
+}
+
+pub fn display_names(report: &StatusReport) -> HashMap<String, String> {
+    report
+        .apps
+        .iter()
+        .filter_map(|app| Some((app.name.clone(), app.display_name.clone()?)))
+        .collect()
+}
+
+pub fn status_resources(report: &StatusReport) -> HashMap<String, AppResources> {
+    report
+        .apps
+        .iter()
+        .filter(|app| app.cpu_time_ms.is_some() || app.peak_memory_bytes.is_some())
+        .map(|app| {
+            let resources = AppResources {
+                cpu_time_ms: app.cpu_time_ms,
+                peak_memory_bytes: app.peak_memory_bytes,
+            };
+            (app.name.clone(), resources)
+        })
+        .collect()
+}
+
+pub fn render_report(
+    usage: &UsageData,
+    display_names: &HashMap<String, String>,
+    resources: &HashMap<String, AppResources>,
+    settings: &Settings,
+) -> String {
+    let mut apps: Vec<_> = usage
+        .iter()
+        .filter(|(name, _)| monitor::is_app(name))
+        .map(|(name, usage)| {
+            let label = match display_names.get(name) {
+                Some(display_name) => format!("{} ({})", display_name, name),
+                None => name.clone(),
+            };
+            let resources = resources.get(name).copied().unwrap_or_default();
+            (name.as_str(), label, usage, resources)
+        })
+        .collect();
+    apps.sort_by(|(_, a_name, a_usage, _), (_, b_name, b_usage, _)| {
+        b_usage.cmp(a_usage).then_with(|| a_name.cmp(b_name))
+    });
+
+    let width = apps
+        .iter()
+        .map(|(_, name, _, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("APPLICATION".len());
+    let show_cpu = apps.iter().any(|(_, _, _, res)| res.cpu_time_ms.is_some());
+    let show_memory = apps
+        .iter()
+        .any(|(_, _, _, res)| res.peak_memory_bytes.is_some());
+
+    let row = |name: &str, bytes: &str, limit: &str, cpu: &str, memory: &str, status: &str| {
+        let mut line = format!("{:<width$}  {:>16}  {:<20}  ", name, bytes, limit);
+        if show_cpu {
+            line.push_str(&format!("{:>12}  ", cpu));
+        }
+        if show_memory {
+            line.push_str(&format!("{:>16}  ", memory));
+        }
+        line.push_str(status);
+        line.push('\n');
+        line
+    };
+
+    let mut report = row(
+        "APPLICATION",
+        "USAGE",
+        "LIMIT",
+        "CPU SECONDS",
+        "PEAK MEMORY",
+        "STATUS",
+    );
+    for (app, name, usage, res) in apps {
+        let Some(limit) = settings.effective_limit(app) else {
+            continue;
+        };
+        let mut status = Vec::new();
+        if *usage > limit.value {
+            status.push("over limit");
+        }
+        if let (Some(cpu), Some(limit)) = (res.cpu_time_ms, settings.cpu_limit_seconds)
+            && cpu > limit.saturating_mul(1000)
+        {
+            status.push("over cpu limit");
+        }
+        if let (Some(memory), Some(limit)) = (res.peak_memory_bytes, settings.memory_limit_bytes)
+            && memory > limit
+        {
+            status.push("over memory limit");
+        }
+        let cpu = res
+            .cpu_time_ms
+            .map(|ms| format!("{:.1}", ms as f64 / 1000.0))
+            .unwrap_or_default();
+        let memory = res
+            .peak_memory_bytes
+            .map(|bytes| settings.format_bytes(bytes))
+            .unwrap_or_default();
+        report.push_str(&row(
+            &name,
+            &settings.format_bytes(*usage),
+            &format!(
+                "{} ({})",
+                settings.format_bytes(limit.value),
+                limit.source.as_str()
+            ),
+            &cpu,
+            &memory,
+            &status.join(", "),
+        ));
+    }
+
+    let rows = |label: fn(&str) -> Option<&str>| {
+        usage
+            .iter()
+            .filter_map(|(name, usage)| {
+                let limit = settings.effective_limit(name).map(|limit| limit.value);
+                Some((label(name)?, *usage, limit))
+            })
+            .collect()
+    };
+    report.push_str(&limit_table("DEVICE", rows(monitor::disk_name), settings));
+    report.push_str(&limit_table(
+        "INTERFACE",
+        rows(monitor::interface_name),
+        settings,
+    ));
+    report.push_str(&limit_table("PATH", rows(monitor::path_label), settings));
+    report
+}
+
+/// Table of disks, network interfaces or watched paths, each with an
+/// optional limit. Empty when there are no rows.
+fn limit_table(
+    heading: &str,
+    mut rows: Vec<(&str, u64, Option<u64>)>,
+    settings: &Settings,
+) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+    rows.sort();
+    let width = rows
+        .iter()
+        .map(|(name, _, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(heading.len());
+    let mut table = format!(
+        "\n{:<width$}  {:>16}  {:>16}  STATUS\n",
+        heading, "USAGE", "LIMIT"
+    );
+    for (name, usage, limit) in rows {
+        let status = if limit.is_some_and(|limit| usage > limit) {
+            "over limit"
+        } else {
+            ""
+        };
+        let limit = limit
+            .map(|limit| settings.format_bytes(limit))
+            .unwrap_or_default();
+        table.push_str(&format!(
+            "{:<width$}  {:>16}  {:>16}  {}\n",
+            name,
+            settings.format_bytes(usage),
+            limit,
+            status
+        ));
+    }
+    table
+}
+
+pub async fn print_report(settings: &Settings, stats: bool, json: bool) -> Result<()> {
+    if stats {
+        return print_exceedances(json).await;
+    }
+
+    let (source, usage, display_names, resources) = match daemon_status().await? {
+        Some(report) => (
+            UsageSource::Service,
+            status_usage(&report),
+            display_names(&report),
+            status_resources(&report),
+        ),
+        None => {
+            let tracker = load_persisted_data().await.unwrap_or_default();
+            let usage = tracker.usage();
+            (
+                UsageSource::DataFile,
+                usage,
+                HashMap::new(),
+                tracker.resources(),
+            )
+        }
+    };
+    if json {
+        return print_json(&report::Report::new(
+            source,
+            &usage,
+            &display_names,
+            &resources,
+            settings,
+        ));
+    }
+    if usage.is_empty() {
+        println!("No usage recorded");
+        return Ok(());
+    }
+
+    print!(
+        "{}",
+        render_report(&usage, &display_names, &resources, settings)
+    );
+    Ok(())
+}
+
+async fn print_exceedances(json: bool) -> Result<()> {
+    let now = Utc::now();
+    let exceedances: HashMap<_, _> = match daemon_status().await? {
+        Some(report) => report
+            .apps
+            .into_iter()
+            .filter_map(|app| Some((app.name, app.exceedance?)))
+            .collect(),
+        None => load_persisted_data()
+            .await
+            .unwrap_or_default()
+            .exceedances()
+            .into_iter()
+            .map(|(app, stats)| (app, stats.at(now)))
+            .collect(),
+    };
+    if json {
+        return print_json(&report::Exceedances::new(&exceedances));
+    }
+    if exceedances.is_empty() {
+        println!("No application has gone over its limit");
+        return Ok(());
+    }
+
+    print!("{}", render_exceedances(&exceedances));
+    Ok(())
+}
+
+/// Renders exceedance stats whose `seconds_over` already includes any
+/// ongoing exceedance.
+pub fn render_exceedances(exceedances: &HashMap<String, ExceedanceStats>) -> String {
+    let mut apps: Vec<_> = exceedances.iter().collect();
+    apps.sort_by(|(a_name, a), (b_name, b)| {
+        b.events
+            .cmp(&a.events)
+            .then_with(|| b.seconds_over.cmp(&a.seconds_over))
+            .then_with(|| a_name.cmp(b_name))
+    });
+
+    let width = apps
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("APPLICATION".len());
+    let time = |time: Option<chrono::DateTime<Utc>>| {
+        time.map(|time| {
+            time.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+    };
+
+    let mut report = format!(
+        "{:<width$}  {:>6}  {:<16}  {:<16}  {:>12}  STATUS\n",
+        "APPLICATION", "EVENTS", "FIRST EXCEEDED", "LAST EXCEEDED", "TIME OVER"
+    );
+    for (name, stats) in apps {
+        report.push_str(&format!(
+            "{:<width$}  {:>6}  {:<16}  {:<16}  {:>12}  {}\n",
+            name,
+            stats.events,
+            time(stats.first_exceeded),
+            time(stats.last_exceeded),
+            humantime::format_duration(Duration::from_secs(stats.seconds_over)).to_string(),
+            if stats.is_over() { "over limit" } else { "" }
+        ));
+    }
+    report
+}
+
+pub async fn print_comparison(settings: &Settings, format: ReportFormat) -> Result<()> {
+    let tracker = load_persisted_data().await.unwrap_or_default();
+    let today = settings.reset_timezone().date(Utc::now());
+    let Some(comparison) = compare::compare(&tracker, settings.reset_period, today) else {
+        bail!("Cannot compare periods around {}", today);
+    };
+
+    match format {
+        ReportFormat::Table => print!("{}", render_comparison(&comparison, settings)),
+        ReportFormat::Json => print_json(&report::Comparison::from(&comparison))?,
+        ReportFormat::Csv => print!("{}", render_comparison_csv(&comparison)),
+    }
+    Ok(())
+}
+
+pub fn render_comparison(comparison: &Comparison, settings: &Settings) -> String {
+    let mut report = format!(
+        "Comparing {} to {} (so far) with {} to {}\n",
+        comparison.current_start,
+        comparison.current_end,
+        comparison.previous_start,
+        comparison.previous_end
+    );
+    if comparison.partial {
+        match comparison.history_start {
+            Some(start) => report.push_str(&format!(
+                "Partial comparison: history only goes back to {}\n",
+                start
+            )),
+            None => report.push_str("Partial comparison: no daily history recorded yet\n"),
+        }
+    }
+    if comparison.apps.is_empty() {
+        report.push_str("No usage recorded\n");
+        return report;
+    }
+
+    let width = comparison
+        .apps
+        .iter()
+        .map(|change| change.app.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("APPLICATION".len());
+    report.push('\n');
+    report.push_str(&format!(
+        "{:<width$}  {:>16}  {:>16}  {:>17}  {:>8}\n",
+        "APPLICATION", "CURRENT", "PREVIOUS", "CHANGE", "PERCENT"
+    ));
+    for change in &comparison.apps {
+        let percent = match (change.kind, change.change_percent) {
+            (ChangeKind::New, _) | (_, None) => "new".to_string(),
+            (ChangeKind::Gone, _) => "gone".to_string(),
+            (_, Some(percent)) => format!("{:+.1}%", percent),
+        };
+        let sign = if change.change_bytes < 0 { '-' } else { '+' };
+        let change_bytes = format!(
+            "{}{}",
+            sign,
+            settings.format_bytes(change.change_bytes.unsigned_abs())
+        );
+        report.push_str(&format!(
+            "{:<width$}  {:>16}  {:>16}  {:>17}  {:>8}\n",
+            change.app,
+            settings.format_bytes(change.current),
+            settings.format_bytes(change.previous),
+            change_bytes,
+            percent
+        ));
+    }
+    report
+}
+
+pub fn render_comparison_csv(comparison: &Comparison) -> String {
+    let mut csv = String::from("app,current,previous,change_bytes,change_percent,kind\n");
+    for change in &comparison.apps {
+        let percent = change
+            .change_percent
+            .map(|percent| format!("{:.1}", percent))
+            .unwrap_or_default();
+        let kind = serde_json::to_value(change.kind)
+            .ok()
+            .and_then(|kind| kind.as_str().map(str::to_string))
+            .unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            csv_field(&change.app),
+            change.current,
+            change.previous,
+            change.change_bytes,
+            percent,
+            kind
+        ));
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub async fn export(
+    settings: &Settings,
+    format: ExportFormat,
+    output: Option<PathBuf>,
+    json: bool,
+) -> Result<()> {
+    if json && output.is_none() {
+        bail!("--json needs --output <PATH>");
+    }
+    let config = require_persistence_paths()?;
+    let Some(tracker) = load_persisted_data().await else {
+        bail!("No persisted usage data found");
+    };
+
+    let (rows, output) = match format {
+        ExportFormat::Influx => {
+            let time = tokio::fs::metadata(config.data_path())
+                .await
+                .and_then(|metadata| metadata.modified())
+                .map(chrono::DateTime::<Utc>::from)
+                .unwrap_or_else(|_| Utc::now());
+            let host = settings
+                .influx
+                .host
+                .clone()
+                .unwrap_or_else(|| settings.instance_label());
+            let points = influx::points(&tracker.usage(), &host, time);
+            let lines = influx::render(&points);
+            match &output {
+                Some(path) => tokio::fs::write(path, lines).await?,
+                None => print!("{}", lines),
+            }
+            (points.len(), output)
+        }
+        ExportFormat::Parquet => {
+            let Some(path) = output else {
+                bail!("--format parquet needs --output <PATH>");
+            };
+            (
+                export_parquet(&tracker, &settings.instance_label(), &path)?,
+                Some(path),
+            )
+        }
+    };
+    match (json, output) {
+        (true, Some(path)) => print_json(&report::Export {
+            schema_version: SCHEMA_VERSION,
+            format: match format {
+                ExportFormat::Influx => "influx",
+                ExportFormat::Parquet => "parquet",
+            }
+            .to_string(),
+            path: path.display().to_string(),
+            rows: rows as u64,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(feature = "arrow")]
+fn export_parquet(
+    tracker: &tracker::UsageTracker,
+    instance: &str,
+    path: &std::path::Path,
+) -> Result<usize> {
+    let file = std::fs::File::create(path)?;
+    let rows = data_guardian::parquet_export::write_parquet(tracker, instance, file)?;
+    info!(?path, rows, "Exported daily usage");
+    Ok(rows)
+}
+
+#[cfg(not(feature = "arrow"))]
+fn export_parquet(
+    _tracker: &tracker::UsageTracker,
+    _instance: &str,
+    _path: &std::path::Path,
+) -> Result<usize> {
+    bail!("Parquet export needs a build with the arrow feature");
+}
+
+pub async fn merge(
+    output: &Path,
+    inputs: &[PathBuf],
+    strategy: MergeStrategy,
+    hosts: Vec<String>,
+    json: bool,
+) -> Result<()> {
+    if inputs.is_empty() {
+        bail!("dg merge needs at least one input file");
+    }
+    if !hosts.is_empty() && hosts.len() != inputs.len() {
+        bail!(
+            "--host must be given once per input ({} labels for {} inputs)",
+            hosts.len(),
+            inputs.len()
+        );
+    }
+    let output_path = std::fs::canonicalize(output).unwrap_or_else(|_| output.to_path_buf());
+    let mut hosts = hosts.into_iter();
+    let mut others = Vec::with_capacity(inputs.len());
+    for path in inputs {
+        if std::fs::canonicalize(path).is_ok_and(|path| path == output_path) {
+            bail!("{} is both an input and the output", path.display());
+        }
+        let contents = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let tracker = tracker::UsageTracker::from_compressed(&contents)
+            .with_context(|| format!("Failed to load usage data from {}", path.display()))?;
+        let mut input = MergeInput::new(tracker);
+        if let Some(host) = hosts.next() {
+            input = input.with_host(host);
+        } else if strategy == MergeStrategy::KeepPerHost {
+            let mut sidecar = path.clone().into_os_string();
+            sidecar.push(".");
+            sidecar.push(merge::HOST_SIDECAR_EXTENSION);
+            if let Ok(host) = tokio::fs::read_to_string(&sidecar).await {
+                input = input.with_host(host.trim());
+            }
+        }
+        others.push(input);
+    }
+
+    let merged = merge::merge_usage(tracker::UsageTracker::default(), others, strategy)?;
+    let compressed = merged.to_compressed()?;
+    tokio::fs::write(output, compressed)
+        .await
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    let apps = merged.apps.len() as u64;
+    if json {
+        return print_json(&report::Merge {
+            schema_version: SCHEMA_VERSION,
+            output: output.display().to_string(),
+            inputs: inputs
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect(),
+            strategy,
+            apps,
+        });
+    }
+    println!(
+        "Merged {} files into {} ({} applications)",
+        inputs.len(),
+        output.display(),
+        apps
+    );
+    Ok(())
+}
+
+/// Skipped records listed as examples after an import.
+const SKIPPED_EXAMPLES: usize = 5;
+
+pub async fn import(
+    settings: &Settings,
+    from: ImportFormat,
+    file: &Path,
+    strategy: MergeStrategy,
+    host: Option<String>,
+    json: bool,
+) -> Result<()> {
+    if send_control(&ControlRequest::Status).await?.is_some() {
+        bail!(
+            "Stop the service before importing; its next save would overwrite the imported usage"
+        );
+    }
+    let text = tokio::fs::read_to_string(file)
+        .await
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let timezone = settings.reset_timezone();
+    let today = timezone.date(Utc::now());
+    let imported = match from {
+        ImportFormat::Vnstat => import::import_vnstat(&text, today)
+            .with_context(|| format!("Failed to import {}", file.display()))?,
+        ImportFormat::Nethogs => {
+            // Traces carry no dates; count them on the day they were written.
+            let modified = tokio::fs::metadata(file).await?.modified()?;
+            import::import_nethogs(&text, timezone.date(modified.into()), today)
+        }
+    };
+
+    let base = load_persisted_data().await.unwrap_or_default();
+    let mut input = MergeInput::new(imported.to_tracker(base.period_start));
+    if let Some(host) = host {
+        input = input.with_host(host);
+    }
+    let merged = merge::merge_usage(base, vec![input], strategy)?;
+    save_persisted_data(&merged).await?;
+
+    let apps = imported.apps().len() as u64;
+    let skipped = imported.skipped_counts();
+    if json {
+        return print_json(&report::Import {
+            schema_version: SCHEMA_VERSION,
+            from,
+            file: file.display().to_string(),
+            strategy,
+            records: imported.records as u64,
+            apps,
+            skipped: skipped
+                .iter()
+                .map(|(reason, count)| (reason.as_str().to_string(), *count as u64))
+                .collect(),
+        });
+    }
+    println!(
+        "Imported {} records for {} applications from {}",
+        imported.records,
+        apps,
+        file.display()
+    );
+    if !imported.skipped.is_empty() {
+        let counts = skipped
+            .iter()
+            .map(|(reason, count)| format!("{} {}", count, reason))
+            .collect::<Vec<_>>();
+        println!(
+            "Skipped {} records: {}",
+            imported.skipped.len(),
+            counts.join(", ")
+        );
+        for skipped in imported.skipped.iter().take(SKIPPED_EXAMPLES) {
+            println!("  {}: {}", skipped.reason, skipped.record);
+        }
+    }
+    Ok(())
+}
+
+pub async fn serve_collector(settings: &Settings, listen: Option<SocketAddr>) -> Result<()> {
+    let dir = match &settings.collector.data_dir {
+        Some(dir) => dir.clone(),
+        None => require_persistence_paths()?.state_dir.join("collector"),
+    };
+    let collector = Arc::new(Collector::open(&dir, settings.collector.token.clone())?);
+    if settings.collector.token.is_none() {
+        warn!("collector.token is not set; accepting snapshots from anyone");
+    }
+
+    let addr = listen.unwrap_or(settings.collector.listen_addr);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!(%addr, ?dir, "Collecting usage snapshots");
+    tokio::select! {
+        result = collector::serve(listener, collector) => result?,
+        _ = tokio::signal::ctrl_c() => info!("Shutting down collector"),
+    }
+    Ok(())
+}
+
+/// Prints events from the running service until it stops.
+pub async fn follow(settings: &Settings, filter: EventFilter, json: bool) -> Result<()> {
+    let client = ControlClient::new(control_endpoint().ok_or(PersistenceError::NoStateDir)?);
+    let mut events = match client.subscribe(filter).await {
+        Ok(events) => events,
+        Err(e) if e.is_not_running() => bail!("Data Guardian is not running"),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(event) = events.next().await? {
+        match json {
+            true => print_json(&report::FollowEvent::new(event))?,
+            false => println!("{}", render_event(&event, settings)),
+        }
+    }
+    eprintln!("Data Guardian stopped");
+    Ok(())
+}
+
+pub fn render_event(event: &Event, settings: &Settings) -> String {
+    let time = event
+        .time()
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S");
+    let (kind, details) = match event {
+        Event::Delta { apps, .. } => (
+            "delta",
+            apps.iter()
+                .map(|(app, bytes)| format!("{} +{}", app, settings.format_bytes(*bytes)))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        Event::Alert(entry) => {
+            let mut line = format!(
+                "{} {} ({})",
+                entry.app,
+                entry.kind.as_str(),
+                entry.severity.as_str()
+            );
+            if let (Some(usage), Some(limit)) = (entry.usage, entry.limit) {
+                line.push_str(&format!(
+                    " {} of {}",
+                    settings.format_bytes(usage),
+                    settings.format_bytes(limit)
+                ));
+            }
+            match &entry.outcome {
+                NotificationOutcome::Sent => line.push_str(", sent"),
+                NotificationOutcome::Failed(e) => line.push_str(&format!(", failed: {}", e)),
+            }
+            ("alert", line)
+        }
+        Event::Save {
+            size_bytes,
+            duration_ms,
+            ..
+        } => (
+            "save",
+            format!(
+                "{} in {:.1} ms",
+                settings.format_bytes(*size_bytes),
+                duration_ms
+            ),
+        ),
+        Event::Reload { fields, .. } => ("reload", fields.join(", ")),
+    };
+    format!("{}  {:<6}  {}", time, kind, details)
+}
+
+pub async fn print_history(since: Option<Duration>, json: bool) -> Result<()> {
+    let mut records = load_notification_history().await.unwrap_or_default();
+    // The history file is only written at shutdown; the alerts log has
+    // everything since, and the same alerts carry the same time.
+    records.extend(load_alerts_log().await);
+    records.sort_by_key(|record| record.time);
+    records.dedup_by(|a, b| a.time == b.time && a.app == b.app);
+
+    if let Some(since) = since {
+        let cutoff = Utc::now() - chrono::Duration::from_std(since)?;
+        records.retain(|record| record.time >= cutoff);
+    }
+    if json {
+        return print_json(&report::History::new(&records));
+    }
+
+    if records.is_empty() {
+        println!("No notifications recorded");
+        return Ok(());
+    }
+
+    for record in records {
+        let time = record
+            .time
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S");
+        let outcome = match record.outcome {
+            NotificationOutcome::Sent => "sent".to_string(),
+            NotificationOutcome::Failed(e) => format!("failed: {}", e),
+        };
+        println!(
+            "{}  {:<8}  {}  {}",
+            time,
+            format!("{:?}", record.severity).to_lowercase(),
+            record.app,
+            outcome
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_guardian::format::BytesStyle;
+    use data_guardian::watched_paths::WatchedPath;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_health_of_dropped_connection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        let client = ControlClient::new(data_guardian::control::ControlEndpoint::Socket(
+            path.clone(),
+        ));
+        assert_eq!(
+            health_of(&client).await.status,
+            report::HealthState::NotRunning
+        );
+
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            drop(stream);
+        });
+        let health = health_of(&client).await;
+        assert_eq!(
+            health.status,
+            report::HealthState::Unhealthy,
+            "{:?}",
+            health.problems
+        );
+        assert_eq!(health.exit_code(), report::EXIT_UNHEALTHY);
+    }
+
+    #[test]
+    fn test_render_step() {
+        let settings = Settings::default();
+        let step = replay::Step {
+            time: "2025-06-18T12:00:00Z".parse().unwrap(),
+            processes: 3,
+            deltas: [("curl".to_string(), 2000), ("idle".to_string(), 0)].into(),
+            rejected: [("steam".to_string(), 9000)].into(),
+            totals: [("curl".to_string(), 5000), ("idle".to_string(), 0)].into(),
+            decisions: vec![replay::Decision {
+                app: "curl".to_string(),
+                kind: AlertKind::Exceeded,
+                outcome: Outcome::AlreadyAlerted,
+                usage: Some(5000),
+                limit: Some(4000),
+            }],
+        };
+        assert_eq!(
+            render_step(&step, &settings),
+            "2025-06-18 12:00:00  scan     3 processes\n\
+             2025-06-18 12:00:00  delta    curl +2.0 KB = 5.0 KB\n\
+             2025-06-18 12:00:00  rejected steam +9.0 KB above max_bytes_per_second\n\
+             2025-06-18 12:00:00  alert    curl exceeded 5.0 KB of 4.0 KB, already sent this period\n"
+        );
+    }
+
+    #[test]
+    fn test_render_event() {
+        let settings = Settings::default();
+        let time = "2025-06-18T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let stamp = time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S");
+
+        let delta = Event::Delta {
+            time,
+            apps: [("curl".to_string(), 2000), ("apt".to_string(), 10)].into(),
+        };
+        assert_eq!(
+            render_event(&delta, &settings),
+            format!("{}  delta   apt +10 B, curl +2.0 KB", stamp)
+        );
+
+        let alert = Event::Alert(data_guardian::alerts_log::AlertLogEntry {
+            time,
+            app: "firefox".to_string(),
+            kind: data_guardian::notification::AlertKind::Exceeded,
+            severity: data_guardian::history::Severity::Warning,
+            usage: Some(2000),
+            limit: Some(1000),
+            instance: None,
+            outcome: NotificationOutcome::Failed("no display".to_string()),
+            channels: Vec::new(),
+        });
+        assert_eq!(
+            render_event(&alert, &settings),
+            format!(
+                "{}  alert   firefox exceeded (warning) 2.0 KB of 1.0 KB, failed: no display",
+                stamp
+            )
+        );
+
+        let reload = Event::Reload {
+            time,
+            fields: vec![
+                "data_limit".to_string(),
+                "check_interval_seconds".to_string(),
+            ],
+        };
+        assert!(
+            render_event(&reload, &settings)
+                .ends_with("reload  data_limit, check_interval_seconds")
+        );
+    }
+
+    #[test]
+    fn test_render_report() {
+        let settings = Settings::default();
+        let usage = UsageData::from([
+            ("small".to_string(), 10),
+            ("large".to_string(), settings.data_limit + 1),
+        ]);
+
+        let report = render_report(&usage, &HashMap::new(), &HashMap::new(), &settings);
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("APPLICATION"));
+        assert!(lines[0].contains("LIMIT"));
+        assert!(lines[1].starts_with("large") && lines[1].ends_with("over limit"));
+        assert!(lines[1].contains("1.1 GB (default)"));
+        assert!(lines[2].starts_with("small") && lines[2].contains("10 B"));
+        assert!(lines[1].contains("1.1 GB"));
+
+        let binary = Settings {
+            bytes_style: BytesStyle::Binary,
+            ..Default::default()
+        };
+        let report = render_report(&usage, &HashMap::new(), &HashMap::new(), &binary);
+        assert!(report.lines().nth(1).unwrap().contains("1.0 GiB"));
+
+        let display_names = HashMap::from([("small".to_string(), "Small App".to_string())]);
+        let report = render_report(&usage, &display_names, &HashMap::new(), &settings);
+        assert!(
+            report
+                .lines()
+                .nth(2)
+                .unwrap()
+                .starts_with("Small App (small)")
+        );
+    }
+
+    #[test]
+    fn test_render_report_resources() {
+        let settings = Settings {
+            cpu_limit_seconds: Some(60),
+            ..Default::default()
+        };
+        let usage = UsageData::from([("cc1".to_string(), 10), ("vim".to_string(), 5)]);
+        let resources = HashMap::from([(
+            "cc1".to_string(),
+            AppResources {
+                cpu_time_ms: Some(61_500),
+                peak_memory_bytes: None,
+            },
+        )]);
+
+        let report = render_report(&usage, &HashMap::new(), &resources, &settings);
+        let lines: Vec<_> = report.lines().collect();
+        assert!(lines[0].contains("CPU SECONDS") && !lines[0].contains("PEAK MEMORY"));
+        assert!(lines[1].contains("61.5") && lines[1].ends_with("over cpu limit"));
+        assert!(lines[2].starts_with("vim"));
+    }
+
+    #[test]
+    fn test_render_exceedances() {
+        let first = Utc::now();
+        let exceedances = HashMap::from([
+            (
+                "rare".to_string(),
+                ExceedanceStats {
+                    events: 1,
+                    first_exceeded: Some(first),
+                    last_exceeded: Some(first),
+                    over_since: None,
+                    seconds_over: 90,
+                },
+            ),
+            (
+                "frequent".to_string(),
+                ExceedanceStats {
+                    events: 3,
+                    first_exceeded: Some(first),
+                    last_exceeded: Some(first),
+                    over_since: Some(first),
+                    seconds_over: 7200,
+                },
+            ),
+        ]);
+
+        let report = render_exceedances(&exceedances);
+        let lines: Vec<_> = report.lines().collect();
+        assert!(lines[0].starts_with("APPLICATION"));
+        assert!(lines[1].starts_with("frequent"));
+        assert!(lines[1].contains("2h"));
+        assert!(lines[1].ends_with("over limit"));
+        assert!(lines[2].starts_with("rare"));
+        assert!(lines[2].contains("1m 30s"));
+    }
+
+    fn comparison() -> Comparison {
+        let date = |d| chrono::NaiveDate::from_ymd_opt(2025, 6, d).unwrap();
+        Comparison {
+            period: tracker::ResetPeriod::Weekly,
+            current_start: date(16),
+            current_end: date(18),
+            previous_start: date(9),
+            previous_end: date(15),
+            history_start: Some(date(12)),
+            partial: true,
+            apps: vec![
+                compare::AppChange::new("growing".to_string(), 300, 200),
+                compare::AppChange::new("new, app".to_string(), 20, 0),
+                compare::AppChange::new("gone".to_string(), 0, 50),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_render_self_usage() {
+        let settings = Settings::default();
+        let mut usage = SelfUsage {
+            time: Utc::now(),
+            read_bytes: Some(2_000_000),
+            written_bytes: Some(5_000_000),
+            io_bytes_per_hour: Some(70_000),
+            written_bytes_per_hour: Some(50_000),
+            data_dir_bytes: 300_000,
+            log_bytes: Some(1_500),
+            persistence_interval_seconds: settings.persistence_interval_seconds,
+        };
+        assert_eq!(
+            render_self_usage(&usage, &settings),
+            "Data Guardian itself read 2.0 MB and wrote 5.0 MB (70.0 KB/h); data files take 300.0 KB, logs 1.5 KB"
+        );
+
+        usage.read_bytes = None;
+        usage.io_bytes_per_hour = None;
+        usage.log_bytes = None;
+        usage.persistence_interval_seconds = settings.persistence_interval_seconds * 4;
+        assert_eq!(
+            render_self_usage(&usage, &settings),
+            "Data Guardian's own I/O is not reported on this platform; data files take 300.0 KB; saving every 20m to write less"
+        );
+    }
+
+    #[test]
+    fn test_render_last_scan() {
+        let now: DateTime<Utc> = "2025-06-18T12:00:00Z".parse().unwrap();
+        let time = now - chrono::TimeDelta::seconds(37);
+        assert_eq!(
+            render_last_scan(time, 1520, Some("1.0.2"), now),
+            "Last scan 37s ago (scan 1520) by v1.0.2"
+        );
+        assert_eq!(render_last_scan(now, 0, None, now), "Last scan 0s ago");
+    }
+
+    #[test]
+    fn test_render_comparison() {
+        let report = render_comparison(&comparison(), &Settings::default());
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Comparing 2025-06-16 to 2025-06-18 (so far) with 2025-06-09 to 2025-06-15"
+        );
+        assert!(lines[1].contains("history only goes back to 2025-06-12"));
+        assert!(lines[4].starts_with("growing") && lines[4].ends_with("+50.0%"));
+        assert!(lines[4].contains("300 B") && lines[4].contains("+100 B"));
+        assert!(lines[5].ends_with("new"));
+        assert!(lines[6].ends_with("gone") && lines[6].contains("-50 B"));
+    }
+
+    #[test]
+    fn test_render_comparison_csv() {
+        let csv = render_comparison_csv(&comparison());
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "app,current,previous,change_bytes,change_percent,kind"
+        );
+        assert_eq!(lines[1], "growing,300,200,100,50.0,up");
+        assert_eq!(lines[2], "\"new, app\",20,0,20,,new");
+        assert_eq!(lines[3], "gone,0,50,-50,-100.0,gone");
+    }
+
+    #[test]
+    fn test_render_report_disks() {
+        let settings = Settings {
+            disk_limits: HashMap::from([("sda1".to_string(), 500)]),
+            watched_paths: HashMap::from([(
+                "cache".to_string(),
+                WatchedPath {
+                    path: "/var/cache".into(),
+                    limit: Some(100),
+                    ..WatchedPath::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let usage = UsageData::from([
+            ("firefox".to_string(), 10),
+            ("disk:sda1".to_string(), 1000),
+            ("disk:nvme0n1p2".to_string(), 20),
+            ("path:cache".to_string(), 50),
+        ]);
+
+        let report = render_report(&usage, &HashMap::new(), &HashMap::new(), &settings);
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert!(lines[1].starts_with("firefox"));
+        assert!(lines[2].is_empty());
+        assert!(lines[3].starts_with("DEVICE") && lines[3].contains("LIMIT"));
+        assert!(lines[4].starts_with("nvme0n1p2") && !lines[4].contains("over limit"));
+        assert!(lines[5].starts_with("sda1") && lines[5].ends_with("over limit"));
+        assert!(lines[7].starts_with("PATH"));
+        assert!(lines[8].starts_with("cache") && !lines[8].contains("over limit"));
+    }
+}
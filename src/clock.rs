@@ -0,0 +1,273 @@
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use tokio::sync::watch;
+
+/// Scans further apart than this many check intervals are treated as a gap,
+/// such as the machine sleeping or the wall clock being changed.
+pub const GAP_FACTOR: u32 = 5;
+
+/// A tick this much later than scheduled counts as missed.
+pub const MISSED_TICK_SLACK: Duration = Duration::from_millis(5);
+
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<watch::Sender<Instant>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(watch::Sender::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.send_modify(|now| *now += duration);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.borrow()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        let deadline = self.now() + duration;
+        let mut now = self.now.subscribe();
+        Box::pin(async move {
+            let _ = now.wait_for(|now| *now >= deadline).await;
+        })
+    }
+}
+
+/// Ticks every `period`. After a missed tick, such as one held up by a slow
+/// scan, the next comes a full period later rather than right away, so a
+/// slow scan never causes a burst of scans.
+#[derive(Debug)]
+pub struct Ticker {
+    clock: Arc<dyn Clock>,
+    period: Duration,
+    next: Instant,
+}
+
+impl Ticker {
+    pub fn new(clock: Arc<dyn Clock>, period: Duration) -> Self {
+        let next = clock.now();
+        Self {
+            clock,
+            period,
+            next,
+        }
+    }
+
+    pub async fn tick(&mut self) -> Instant {
+        let now = self.clock.now();
+        if now < self.next {
+            self.clock.sleep(self.next - now).await;
+        }
+
+        let scheduled = self.next;
+        let now = self.clock.now();
+        self.next = if now > scheduled + MISSED_TICK_SLACK {
+            now + self.period
+        } else {
+            scheduled + self.period
+        };
+        scheduled
+    }
+
+    pub fn lateness(&self, scheduled: Instant) -> Duration {
+        self.clock.now().saturating_duration_since(scheduled)
+    }
+}
+
+/// Time between two scans that were much further apart than scheduled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockGap {
+    /// Wall-clock time of the scan before the gap.
+    pub since: DateTime<Utc>,
+    /// Elapsed monotonic time, which on some platforms stops while asleep.
+    pub monotonic: Duration,
+    /// Elapsed wall-clock time; negative if the clock was set back.
+    pub wall: TimeDelta,
+}
+
+/// Compares each scan's monotonic and wall-clock time with the previous one.
+#[derive(Debug, Clone)]
+pub struct GapDetector {
+    threshold: Duration,
+    last: Option<(Instant, DateTime<Utc>)>,
+}
+
+impl GapDetector {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            threshold: interval * GAP_FACTOR,
+            last: None,
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.threshold = interval * GAP_FACTOR;
+    }
+
+    /// Records a scan at `now` and returns the gap since the previous scan if
+    /// either clock moved by more than `GAP_FACTOR` intervals.
+    pub fn check(&mut self, now: Instant, wall: DateTime<Utc>) -> Option<ClockGap> {
+        let (last, last_wall) = self.last.replace((now, wall))?;
+        let gap = ClockGap {
+            since: last_wall,
+            monotonic: now.saturating_duration_since(last),
+            wall: wall - last_wall,
+        };
+        let threshold = TimeDelta::from_std(self.threshold).unwrap_or(TimeDelta::MAX);
+        (gap.monotonic > self.threshold || gap.wall.abs() > threshold).then_some(gap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_secs(10);
+
+    #[tokio::test]
+    async fn test_mock_clock_sleep() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let sleep = tokio::spawn(clock.sleep(PERIOD));
+
+        clock.advance(PERIOD / 2);
+        tokio::task::yield_now().await;
+        assert!(!sleep.is_finished());
+
+        clock.advance(PERIOD / 2);
+        sleep.await.unwrap();
+        assert_eq!(clock.now() - start, PERIOD);
+    }
+
+    #[tokio::test]
+    async fn test_ticker() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let mut ticker = Ticker::new(Arc::new(clock.clone()), PERIOD);
+
+        assert_eq!(ticker.tick().await, start);
+        let mut next = Box::pin(ticker.tick());
+        assert!(poll_once(&mut next).is_none());
+        clock.advance(PERIOD);
+        assert_eq!(next.await, start + PERIOD);
+
+        clock.advance(PERIOD * 3 + PERIOD / 2);
+        let scheduled = ticker.tick().await;
+        assert_eq!(scheduled, start + PERIOD * 2);
+        assert_eq!(ticker.lateness(scheduled), PERIOD * 2 + PERIOD / 2);
+
+        let realigned = clock.now() + PERIOD;
+        clock.advance(PERIOD);
+        assert_eq!(ticker.tick().await, realigned);
+    }
+
+    #[tokio::test]
+    async fn test_ticker_delays_after_slow_tick() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let mut ticker = Ticker::new(Arc::new(clock.clone()), PERIOD);
+        let step = PERIOD / 10;
+
+        let mut starts = Vec::new();
+        for work in [step, PERIOD + PERIOD / 2, step, step] {
+            let mut tick = Box::pin(ticker.tick());
+            while poll_once(&mut tick).is_none() {
+                clock.advance(step);
+            }
+            starts.push(clock.now() - start);
+            clock.advance(work);
+        }
+        // The scan after the slow one starts late, and the one after that
+        // a full period later instead of catching up.
+        assert_eq!(
+            starts,
+            [Duration::ZERO, PERIOD, PERIOD * 5 / 2, PERIOD * 7 / 2]
+        );
+    }
+
+    #[test]
+    fn test_gap_detector() {
+        let clock = MockClock::new();
+        let mut wall: DateTime<Utc> = "2025-06-18T22:00:00Z".parse().unwrap();
+        let mut gaps = GapDetector::new(PERIOD);
+        let mut scan = |elapsed: Duration, wall_elapsed: TimeDelta| {
+            clock.advance(elapsed);
+            wall += wall_elapsed;
+            gaps.check(clock.now(), wall)
+        };
+        let step = TimeDelta::from_std(PERIOD).unwrap();
+
+        assert_eq!(scan(Duration::ZERO, TimeDelta::zero()), None);
+        assert_eq!(scan(PERIOD, step), None);
+        assert_eq!(scan(PERIOD * GAP_FACTOR, step * GAP_FACTOR as i32), None);
+
+        // Suspend: the monotonic clock stops, the wall clock keeps going.
+        let gap = scan(PERIOD, TimeDelta::hours(8)).unwrap();
+        assert_eq!(
+            gap.since,
+            "2025-06-18T22:01:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(gap.wall, TimeDelta::hours(8));
+        assert_eq!(gap.monotonic, PERIOD);
+        assert_eq!(scan(PERIOD, step), None);
+
+        // Platforms whose monotonic clock counts the time asleep.
+        assert!(scan(PERIOD * 100, step).is_some());
+        // The wall clock being set back.
+        assert_eq!(
+            scan(PERIOD, -TimeDelta::hours(1)).unwrap().wall,
+            -TimeDelta::hours(1)
+        );
+    }
+
+    fn poll_once<F: Future + Unpin>(future: &mut F) -> Option<F::Output> {
+        let waker = std::task::Waker::noop();
+        let mut context = std::task::Context::from_waker(waker);
+        match Pin::new(future).poll(&mut context) {
+            std::task::Poll::Ready(output) => Some(output),
+            std::task::Poll::Pending => None,
+        }
+    }
+}
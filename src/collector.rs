@@ -0,0 +1,343 @@
+#![cfg_attr(not(feature = "monitor"), allow(unused_imports, dead_code))]
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+#[cfg(feature = "monitor")]
+use tokio::net::TcpListener;
+use tracing::{debug, warn};
+
+use super::agent::UsageSnapshot;
+use super::compression::{self, CompressionError};
+#[cfg(feature = "monitor")]
+use super::http::{self, Request, Response};
+
+pub const DEFAULT_COLLECTOR_PORT: u16 = 9187;
+pub const COLLECT_PATH: &str = "/collect";
+pub const REPORT_PATH: &str = "/report";
+const HOST_FILE_EXTENSION: &str = "dat";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CollectorConfig {
+    pub listen_addr: SocketAddr,
+    pub token: Option<String>,
+    pub data_dir: Option<PathBuf>,
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), DEFAULT_COLLECTOR_PORT),
+            token: None,
+            data_dir: None,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CollectorError {
+    #[error("IO error in collector storage: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to encode host record: {0}")]
+    Compression(#[from] CompressionError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostRecord {
+    pub snapshot: UsageSnapshot,
+    pub received: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostSummary {
+    pub host: String,
+    pub user: String,
+    pub total: u64,
+    pub apps: usize,
+    pub time: DateTime<Utc>,
+    pub received: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppSummary {
+    pub name: String,
+    pub usage: u64,
+    pub hosts: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregateReport {
+    pub total: u64,
+    pub hosts: Vec<HostSummary>,
+    pub apps: Vec<AppSummary>,
+}
+
+#[derive(Debug)]
+pub struct Collector {
+    dir: PathBuf,
+    token: Option<String>,
+    hosts: Mutex<HashMap<String, HostRecord>>,
+}
+
+impl Collector {
+    pub fn open(dir: &Path, token: Option<String>) -> Result<Self, CollectorError> {
+        std::fs::create_dir_all(dir)?;
+
+        let mut hosts = HashMap::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_none_or(|ext| ext != HOST_FILE_EXTENSION)
+            {
+                continue;
+            }
+            match std::fs::read(&path)
+                .map_err(CollectorError::from)
+                .and_then(|data| Ok(compression::decompress_json::<HostRecord>(&data)?))
+            {
+                Ok(record) => {
+                    hosts.insert(record.snapshot.host.clone(), record);
+                }
+                Err(e) => warn!(error = %e, ?path, "Skipping unreadable host record"),
+            }
+        }
+        debug!(hosts = hosts.len(), ?dir, "Loaded host records");
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            token,
+            hosts: Mutex::new(hosts),
+        })
+    }
+
+    pub fn accept(&self, snapshot: UsageSnapshot) -> Result<bool, CollectorError> {
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = hosts.get(&snapshot.host)
+            && existing.snapshot.time > snapshot.time
+        {
+            return Ok(false);
+        }
+
+        let record = HostRecord {
+            snapshot,
+            received: Utc::now(),
+        };
+        let path = self.dir.join(host_file_name(&record.snapshot.host));
+        let tmp = path.with_extension("tmp");
+        std::fs::write(
+            &tmp,
+            compression::compress_json(&record, record.snapshot.apps.len())?,
+        )?;
+        std::fs::rename(&tmp, &path)?;
+
+        hosts.insert(record.snapshot.host.clone(), record);
+        Ok(true)
+    }
+
+    pub fn report(&self, host: Option<&str>) -> AggregateReport {
+        let hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut report = AggregateReport::default();
+        let mut apps: HashMap<&str, AppSummary> = HashMap::new();
+        for record in hosts
+            .values()
+            .filter(|record| host.is_none_or(|host| host == record.snapshot.host))
+        {
+            let snapshot = &record.snapshot;
+            let total = snapshot.total();
+            report.total = report.total.saturating_add(total);
+            report.hosts.push(HostSummary {
+                host: snapshot.host.clone(),
+                user: snapshot.user.clone(),
+                total,
+                apps: snapshot.apps.len(),
+                time: snapshot.time,
+                received: record.received,
+            });
+            for (name, usage) in &snapshot.apps {
+                let app = apps.entry(name).or_insert_with(|| AppSummary {
+                    name: name.clone(),
+                    usage: 0,
+                    hosts: 0,
+                });
+                app.usage = app.usage.saturating_add(*usage);
+                app.hosts += 1;
+            }
+        }
+
+        report
+            .hosts
+            .sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.host.cmp(&b.host)));
+        report.apps = apps.into_values().collect();
+        report
+            .apps
+            .sort_by(|a, b| b.usage.cmp(&a.usage).then_with(|| a.name.cmp(&b.name)));
+        report
+    }
+
+    #[cfg(feature = "monitor")]
+    fn authorized(&self, request: &Request) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|value| value == token)
+    }
+
+    #[cfg(feature = "monitor")]
+    pub fn handle(&self, request: &Request) -> Response {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("POST", COLLECT_PATH) => {
+                if !self.authorized(request) {
+                    return Response::error(401, "Invalid or missing token");
+                }
+                let snapshot = match UsageSnapshot::from_compressed(&request.body) {
+                    Ok(snapshot) if !snapshot.host.is_empty() => snapshot,
+                    Ok(_) => return Response::error(400, "Snapshot has no host"),
+                    Err(e) => return Response::error(400, &e.to_string()),
+                };
+                let host = snapshot.host.clone();
+                match self.accept(snapshot) {
+                    Ok(stored) => {
+                        debug!(%host, stored, "Received usage snapshot");
+                        Response::no_content()
+                    }
+                    Err(e) => {
+                        warn!(error = %e, %host, "Failed to store usage snapshot");
+                        Response::error(500, "Failed to store snapshot")
+                    }
+                }
+            }
+            ("GET", REPORT_PATH) => {
+                Response::json(&self.report(request.query.get("host").map(String::as_str)))
+            }
+            (_, COLLECT_PATH | REPORT_PATH) => Response::method_not_allowed(),
+            _ => Response::not_found(),
+        }
+    }
+}
+
+fn host_file_name(host: &str) -> String {
+    let name: String = host
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() || c == '-' || c == '_' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("{}.{}", name, HOST_FILE_EXTENSION)
+}
+
+#[cfg(feature = "monitor")]
+pub async fn serve(listener: TcpListener, collector: Arc<Collector>) -> io::Result<()> {
+    http::serve_with_body(listener, http::MAX_BODY_SIZE, move |request| {
+        collector.handle(request)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::super::tracker::UsageTracker;
+    use super::*;
+
+    fn snapshot(host: &str, apps: &[(&str, u64)], time: DateTime<Utc>) -> UsageSnapshot {
+        let mut tracker = UsageTracker::default();
+        for (app, bytes) in apps {
+            tracker.add_usage(app, *bytes);
+        }
+        UsageSnapshot::new(host, "dev", &tracker, time)
+    }
+
+    #[cfg(feature = "monitor")]
+    fn post(body: Vec<u8>, token: Option<&str>) -> Request {
+        Request {
+            method: "POST".to_string(),
+            path: COLLECT_PATH.to_string(),
+            query: HashMap::new(),
+            headers: token
+                .map(|token| {
+                    HashMap::from([("authorization".to_string(), format!("Bearer {}", token))])
+                })
+                .unwrap_or_default(),
+            body,
+        }
+    }
+
+    #[test]
+    fn test_merge_and_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let collector = Collector::open(dir.path(), None).unwrap();
+        let now = Utc::now();
+
+        assert!(
+            collector
+                .accept(snapshot("a", &[("firefox", 100), ("cargo", 50)], now))
+                .unwrap()
+        );
+        assert!(
+            collector
+                .accept(snapshot("b.local", &[("firefox", 30)], now))
+                .unwrap()
+        );
+        assert!(
+            !collector
+                .accept(snapshot("a", &[("firefox", 1)], now - Duration::minutes(5)))
+                .unwrap()
+        );
+
+        let report = collector.report(None);
+        assert_eq!(report.total, 180);
+        assert_eq!(report.hosts[0].host, "a");
+        assert_eq!(report.apps[0].name, "firefox");
+        assert_eq!(report.apps[0].usage, 130);
+        assert_eq!(report.apps[0].hosts, 2);
+        assert_eq!(collector.report(Some("b.local")).total, 30);
+
+        let reopened = Collector::open(dir.path(), None).unwrap();
+        assert_eq!(reopened.report(None), report);
+        assert!(dir.path().join("b_local.dat").exists());
+    }
+
+    #[cfg(feature = "monitor")]
+    #[test]
+    fn test_handle_collect() {
+        let dir = tempfile::tempdir().unwrap();
+        let collector = Collector::open(dir.path(), Some("secret".to_string())).unwrap();
+        let body = snapshot("a", &[("firefox", 1)], Utc::now())
+            .to_compressed()
+            .unwrap();
+
+        assert_eq!(collector.handle(&post(body.clone(), None)).status, 401);
+        assert_eq!(
+            collector.handle(&post(body.clone(), Some("wrong"))).status,
+            401
+        );
+        assert_eq!(
+            collector
+                .handle(&post(b"junk".to_vec(), Some("secret")))
+                .status,
+            400
+        );
+        assert_eq!(collector.handle(&post(body, Some("secret"))).status, 204);
+        assert_eq!(collector.report(None).hosts.len(), 1);
+
+        let mut get = post(Vec::new(), None);
+        get.method = "GET".to_string();
+        assert_eq!(collector.handle(&get).status, 405);
+        get.path = REPORT_PATH.to_string();
+        assert_eq!(collector.handle(&get).status, 200);
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::monitor;
+use super::tracker::{ResetPeriod, UsageTracker};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// No usage in the previous period.
+    New,
+    /// No usage in the current period.
+    Gone,
+    Up,
+    Down,
+    Same,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppChange {
+    pub app: String,
+    pub current: u64,
+    pub previous: u64,
+    pub change_bytes: i64,
+    /// Missing for new apps, which have nothing to compare against.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_percent: Option<f64>,
+    pub kind: ChangeKind,
+}
+
+impl AppChange {
+    pub fn new(app: String, current: u64, previous: u64) -> Self {
+        let change = i128::from(current) - i128::from(previous);
+        let kind = match (current, previous) {
+            (_, 0) => ChangeKind::New,
+            (0, _) => ChangeKind::Gone,
+            _ if change > 0 => ChangeKind::Up,
+            _ if change < 0 => ChangeKind::Down,
+            _ => ChangeKind::Same,
+        };
+        Self {
+            app,
+            current,
+            previous,
+            change_bytes: change.clamp(i64::MIN.into(), i64::MAX.into()) as i64,
+            change_percent: (previous > 0).then(|| change as f64 * 100.0 / previous as f64),
+            kind,
+        }
+    }
+}
+
+/// Usage of the current period so far against the whole previous period,
+/// taken from the tracker's daily buckets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comparison {
+    pub period: ResetPeriod,
+    pub current_start: NaiveDate,
+    /// Last day counted in the current period, inclusive.
+    pub current_end: NaiveDate,
+    pub previous_start: NaiveDate,
+    /// Last day of the previous period, inclusive.
+    pub previous_end: NaiveDate,
+    /// First day with recorded usage.
+    pub history_start: Option<NaiveDate>,
+    /// Set when the history does not reach back to `previous_start`, so the
+    /// previous totals are incomplete.
+    pub partial: bool,
+    pub apps: Vec<AppChange>,
+}
+
+/// Compares the period containing `today` with the one before it. Periods
+/// follow `reset_period`, or weeks when usage never resets. Disks are left
+/// out, and apps are sorted by the largest increase first.
+pub fn compare(
+    tracker: &UsageTracker,
+    reset_period: ResetPeriod,
+    today: NaiveDate,
+) -> Option<Comparison> {
+    let period = match reset_period {
+        ResetPeriod::Never => ResetPeriod::Weekly,
+        period => period,
+    };
+    let current_start = period.period_start(today)?;
+    let previous_end = current_start.pred_opt()?;
+    let previous_start = period.period_start(previous_end)?;
+
+    let current = tracker.usage_between(current_start, today.succ_opt()?);
+    let previous = tracker.usage_between(previous_start, current_start);
+    let names: BTreeSet<&String> = current.keys().chain(previous.keys()).collect();
+    let usage = |usage: &HashMap<String, u64>, app: &str| usage.get(app).copied().unwrap_or(0);
+    let mut apps: Vec<_> = names
+        .into_iter()
+        .filter(|app| monitor::is_app(app))
+        .map(|app| AppChange::new(app.clone(), usage(&current, app), usage(&previous, app)))
+        .filter(|change| change.current > 0 || change.previous > 0)
+        .collect();
+    apps.sort_by(|a, b| {
+        b.change_bytes
+            .cmp(&a.change_bytes)
+            .then_with(|| a.app.cmp(&b.app))
+    });
+
+    let history_start = tracker.days.keys().next().copied();
+    Some(Comparison {
+        period,
+        current_start,
+        current_end: today,
+        previous_start,
+        previous_end,
+        history_start,
+        partial: history_start.is_none_or(|start| start > previous_start),
+        apps,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_app_change() {
+        let change = AppChange::new("app".to_string(), 150, 100);
+        assert_eq!(change.kind, ChangeKind::Up);
+        assert_eq!(change.change_bytes, 50);
+        assert_eq!(change.change_percent, Some(50.0));
+
+        let new = AppChange::new("app".to_string(), 10, 0);
+        assert_eq!(new.kind, ChangeKind::New);
+        assert_eq!(new.change_percent, None);
+
+        let gone = AppChange::new("app".to_string(), 0, 10);
+        assert_eq!(gone.kind, ChangeKind::Gone);
+        assert_eq!(gone.change_percent, Some(-100.0));
+        assert_eq!(
+            AppChange::new("app".to_string(), 5, 5).kind,
+            ChangeKind::Same
+        );
+    }
+
+    #[test]
+    fn test_compare_weeks() {
+        let mut tracker = UsageTracker::default();
+        // Monday 2025-06-09 starts the previous week.
+        tracker.add_daily_usage("steady", 100, 0, date(2025, 6, 9));
+        tracker.add_daily_usage("steady", 100, 0, date(2025, 6, 15));
+        tracker.add_daily_usage("growing", 10, 0, date(2025, 6, 10));
+        tracker.add_daily_usage("gone", 50, 0, date(2025, 6, 11));
+        tracker.add_daily_usage("disk:sda", 50, 0, date(2025, 6, 11));
+        tracker.add_daily_usage("steady", 200, 0, date(2025, 6, 16));
+        tracker.add_daily_usage("growing", 500, 0, date(2025, 6, 17));
+        tracker.add_daily_usage("new", 20, 0, date(2025, 6, 18));
+        tracker.add_daily_usage("future", 20, 0, date(2025, 6, 19));
+
+        let comparison = compare(&tracker, ResetPeriod::Never, date(2025, 6, 18)).unwrap();
+        assert_eq!(comparison.period, ResetPeriod::Weekly);
+        assert_eq!(comparison.current_start, date(2025, 6, 16));
+        assert_eq!(comparison.previous_start, date(2025, 6, 9));
+        assert_eq!(comparison.previous_end, date(2025, 6, 15));
+        assert!(!comparison.partial);
+
+        let apps: Vec<_> = comparison
+            .apps
+            .iter()
+            .map(|change| (change.app.as_str(), change.kind))
+            .collect();
+        assert_eq!(
+            apps,
+            [
+                ("growing", ChangeKind::Up),
+                ("new", ChangeKind::New),
+                ("steady", ChangeKind::Same),
+                ("gone", ChangeKind::Gone),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_partial_history() {
+        let mut tracker = UsageTracker::default();
+        tracker.add_daily_usage("app", 10, 0, date(2025, 6, 20));
+
+        let comparison = compare(&tracker, ResetPeriod::Monthly, date(2025, 6, 21)).unwrap();
+        assert_eq!(comparison.previous_start, date(2025, 5, 1));
+        assert_eq!(comparison.history_start, Some(date(2025, 6, 20)));
+        assert!(comparison.partial);
+        assert_eq!(comparison.apps[0].kind, ChangeKind::New);
+
+        let empty = compare(
+            &UsageTracker::default(),
+            ResetPeriod::Daily,
+            date(2025, 6, 21),
+        );
+        assert!(empty.unwrap().partial);
+    }
+}
@@ -0,0 +1,606 @@
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use flate2::{
+    Compress, Compression, Crc, Decompress, FlushCompress, FlushDecompress, GzBuilder, Status,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+
+/// Written next to the data file when `compression_dictionary = "trained"`.
+pub const DICTIONARY_FILE: &str = "usage.dict";
+/// The deflate window; dictionary bytes further back could not be referenced.
+pub const MAX_DICTIONARY_SIZE: usize = 32 * 1024;
+/// Trained from representative usage data by `test_builtin_dictionary`.
+pub const BUILTIN_DICTIONARY: &[u8] = include_bytes!("usage.dict");
+
+/// Starts data compressed with a dictionary, followed by the dictionary id,
+/// the CRC-32 and length of the JSON, and a raw deflate stream.
+const DICTIONARY_MAGIC: &[u8; 4] = b"DGzD";
+const DICTIONARY_HEADER_LEN: usize = 16;
+/// Fragments shorter than this are cheaper to encode than to reference.
+const MIN_FRAGMENT_LEN: usize = 4;
+
+/// Which preset dictionary the data file is compressed with. Files written
+/// with a dictionary cannot be read by versions without dictionary support.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DictionaryKind {
+    #[default]
+    None,
+    /// Shipped with the binary.
+    Builtin,
+    /// Trained on this machine's own data and kept in `usage.dict`.
+    Trained,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionConfig {
+    pub level: u32,
+    pub capacity_multiplier: f32,
+    /// Id of a registered dictionary. Compression goes ahead without one
+    /// when it is not registered.
+    pub dictionary: Option<u32>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            level: 9,
+            capacity_multiplier: 0.5,
+            dictionary: None,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CompressionError {
+    #[error("Failed to serialize data: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("IO error during compression: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid compression level: {0}")]
+    InvalidLevel(u32),
+    #[error("Data was compressed with dictionary {0:08x}, which is not available")]
+    UnknownDictionary(u32),
+    #[error("Dictionary of {0} bytes is larger than {MAX_DICTIONARY_SIZE} bytes")]
+    DictionaryTooLarge(usize),
+    #[error("Checksum mismatch in decompressed data")]
+    ChecksumMismatch,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dictionary {
+    id: u32,
+    bytes: Vec<u8>,
+}
+
+impl Dictionary {
+    pub fn new(bytes: Vec<u8>) -> Result<Self, CompressionError> {
+        if bytes.len() > MAX_DICTIONARY_SIZE {
+            return Err(CompressionError::DictionaryTooLarge(bytes.len()));
+        }
+        Ok(Self {
+            id: checksum(&bytes),
+            bytes,
+        })
+    }
+
+    /// The CRC-32 of the dictionary, recorded in the data it compresses.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+static REGISTERED: Mutex<Vec<Arc<Dictionary>>> = Mutex::new(Vec::new());
+
+pub fn builtin_dictionary() -> Arc<Dictionary> {
+    static BUILTIN: OnceLock<Arc<Dictionary>> = OnceLock::new();
+    BUILTIN
+        .get_or_init(|| {
+            Arc::new(Dictionary::new(BUILTIN_DICTIONARY.to_vec()).expect("builtin dictionary fits"))
+        })
+        .clone()
+}
+
+/// Makes a dictionary available for compression and decompression, and
+/// returns its id.
+pub fn register_dictionary(dictionary: Dictionary) -> u32 {
+    let id = dictionary.id();
+    let mut registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+    if !registered.iter().any(|known| known.id() == id) {
+        registered.push(Arc::new(dictionary));
+    }
+    id
+}
+
+fn find_dictionary(id: u32) -> Option<Arc<Dictionary>> {
+    let builtin = builtin_dictionary();
+    if builtin.id() == id {
+        return Some(builtin);
+    }
+    REGISTERED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|known| known.id() == id)
+        .cloned()
+}
+
+/// Builds a dictionary of at most `max_size` bytes from JSON samples. It
+/// holds the fragments between structural characters that repeat the most,
+/// weighted by length, with the most valuable last, where references to
+/// them are shortest. Byte counts, dates and other runs of 4 or more
+/// digits change from save to save, so fragments are cut off where one
+/// starts.
+pub fn train_dictionary(samples: &[&[u8]], max_size: usize) -> Vec<u8> {
+    let max_size = max_size.min(MAX_DICTIONARY_SIZE);
+    let mut counts: HashMap<&[u8], u64> = HashMap::new();
+    for sample in samples {
+        let mut start = 0;
+        for (i, byte) in sample.iter().enumerate() {
+            if matches!(byte, b'{' | b'}' | b'[' | b']' | b',') {
+                let fragment = stable_prefix(&sample[start..=i]);
+                if fragment.len() >= MIN_FRAGMENT_LEN {
+                    *counts.entry(fragment).or_default() += 1;
+                }
+                start = i + 1;
+            }
+        }
+    }
+
+    let score = |fragment: &[u8], count: u64| count.saturating_mul(fragment.len() as u64);
+    let mut fragments: Vec<_> = counts.into_iter().filter(|&(_, count)| count > 1).collect();
+    fragments.sort_by(|&(a, a_count), &(b, b_count)| {
+        score(b, b_count)
+            .cmp(&score(a, a_count))
+            .then_with(|| a.cmp(b))
+    });
+    let mut size = 0;
+    let mut chosen = Vec::new();
+    for (fragment, _) in fragments {
+        if size + fragment.len() <= max_size {
+            size += fragment.len();
+            chosen.push(fragment);
+        }
+    }
+    chosen.reverse();
+    chosen.concat()
+}
+
+fn stable_prefix(fragment: &[u8]) -> &[u8] {
+    match fragment
+        .windows(4)
+        .position(|window| window.iter().all(u8::is_ascii_digit))
+    {
+        Some(end) => &fragment[..end],
+        None => fragment,
+    }
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+/// Runs `input` through the compressor, with `Sync` until it is flushed to
+/// a byte boundary, or with `Finish` until the stream ends.
+fn deflate(
+    compress: &mut Compress,
+    mut input: &[u8],
+    output: &mut Vec<u8>,
+    flush: FlushCompress,
+) -> io::Result<()> {
+    loop {
+        output.reserve(1024.max(input.len() / 2));
+        let consumed = compress.total_in();
+        let status = compress.compress_vec(input, output, flush)?;
+        input = &input[(compress.total_in() - consumed) as usize..];
+        let done = match flush {
+            FlushCompress::Finish => status == Status::StreamEnd,
+            _ => input.is_empty() && output.len() < output.capacity(),
+        };
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+/// Deflates `json` after priming the compressor's window with the
+/// dictionary. Only the output after the dictionary is kept, so it is
+/// a stream that continues one holding the dictionary.
+fn compress_with_dictionary(
+    json: &[u8],
+    dictionary: &Dictionary,
+    level: u32,
+) -> Result<Vec<u8>, CompressionError> {
+    let mut compress = Compress::new(Compression::new(level), false);
+    let mut primed = Vec::new();
+    deflate(
+        &mut compress,
+        dictionary.as_bytes(),
+        &mut primed,
+        FlushCompress::Sync,
+    )?;
+
+    let mut output = Vec::with_capacity(DICTIONARY_HEADER_LEN + json.len() / 4);
+    output.extend_from_slice(DICTIONARY_MAGIC);
+    output.extend_from_slice(&dictionary.id().to_le_bytes());
+    output.extend_from_slice(&checksum(json).to_le_bytes());
+    output.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    deflate(&mut compress, json, &mut output, FlushCompress::Finish)?;
+    Ok(output)
+}
+
+/// Inflates data from `compress_with_dictionary` by putting the dictionary
+/// in front of it as a stored block, then dropping it from the output.
+fn decompress_with_dictionary(data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    let header = data
+        .get(..DICTIONARY_HEADER_LEN)
+        .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    let field = |index: usize| u32::from_le_bytes(header[index..index + 4].try_into().unwrap());
+    let (id, crc, len) = (field(4), field(8), field(12) as usize);
+    let dictionary = find_dictionary(id).ok_or(CompressionError::UnknownDictionary(id))?;
+    let dictionary = dictionary.as_bytes();
+
+    let stored_len = dictionary.len() as u16;
+    let mut input = Vec::with_capacity(5 + dictionary.len() + data.len());
+    input.push(0);
+    input.extend_from_slice(&stored_len.to_le_bytes());
+    input.extend_from_slice(&(!stored_len).to_le_bytes());
+    input.extend_from_slice(dictionary);
+    input.extend_from_slice(&data[DICTIONARY_HEADER_LEN..]);
+
+    let mut decompress = Decompress::new(false);
+    let mut output = Vec::with_capacity(dictionary.len() + len);
+    loop {
+        output.reserve(1024);
+        let consumed = decompress.total_in() as usize;
+        let written = output.len();
+        let status = decompress
+            .decompress_vec(&input[consumed..], &mut output, FlushDecompress::Finish)
+            .map_err(io::Error::from)?;
+        if status == Status::StreamEnd {
+            break;
+        }
+        if decompress.total_in() as usize == consumed && output.len() == written {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+    }
+    let json = output.split_off(dictionary.len().min(output.len()));
+    if json.len() != len || checksum(&json) != crc {
+        return Err(CompressionError::ChecksumMismatch);
+    }
+    Ok(json)
+}
+
+pub fn compress_json_with_config<T: Serialize + ?Sized>(
+    value: &T,
+    len_hint: usize,
+    config: CompressionConfig,
+) -> Result<Vec<u8>, CompressionError> {
+    if config.level > 9 {
+        return Err(CompressionError::InvalidLevel(config.level));
+    }
+
+    if let Some(id) = config.dictionary {
+        match find_dictionary(id) {
+            Some(dictionary) => {
+                let json = serde_json::to_vec(value)?;
+                return compress_with_dictionary(&json, &dictionary, config.level);
+            }
+            None => warn!(
+                dictionary = format!("{:08x}", id),
+                "Compression dictionary not available, compressing without it"
+            ),
+        }
+    }
+
+    let estimated_capacity = (len_hint as f32 * config.capacity_multiplier) as usize;
+    let mut encoder = GzBuilder::new().comment("DataGuardian usage data").write(
+        Vec::with_capacity(estimated_capacity.max(64)),
+        Compression::new(config.level),
+    );
+
+    serde_json::to_writer(&mut encoder, value)?;
+    Ok(encoder.finish()?)
+}
+
+pub fn compress_json<T: Serialize + ?Sized>(
+    value: &T,
+    len_hint: usize,
+) -> Result<Vec<u8>, CompressionError> {
+    compress_json_with_config(value, len_hint, CompressionConfig::default())
+}
+
+pub fn decompress_json<T: DeserializeOwned>(data: &[u8]) -> Result<T, CompressionError> {
+    let decompressed = match data.starts_with(DICTIONARY_MAGIC) {
+        true => decompress_with_dictionary(data)?,
+        false => {
+            let mut decoder = flate2::read::GzDecoder::new(data);
+            let mut decompressed = Vec::with_capacity(data.len() * 2);
+            decoder.read_to_end(&mut decompressed)?;
+            decompressed
+        }
+    };
+    Ok(serde_json::from_slice(&decompressed)?)
+}
+
+pub fn compress_usage_data_with_config(
+    data: &HashMap<String, u64>,
+    config: CompressionConfig,
+) -> Result<Vec<u8>, CompressionError> {
+    compress_json_with_config(data, data.len(), config)
+}
+
+pub fn compress_usage_data(data: &HashMap<String, u64>) -> Result<Vec<u8>, CompressionError> {
+    compress_usage_data_with_config(data, CompressionConfig::default())
+}
+
+pub fn decompress_usage_data(data: &[u8]) -> Result<HashMap<String, u64>, CompressionError> {
+    decompress_json(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Days, NaiveDate, TimeDelta, Utc};
+
+    use super::super::test_support::create_test_data;
+    use super::super::tracker::{ResetPeriod, UsageTracker};
+    use super::*;
+
+    const BUILTIN_DICTIONARY_SIZE: usize = 8 * 1024;
+    const SAMPLE_APPS: [&str; 24] = [
+        "firefox",
+        "chrome",
+        "code",
+        "slack",
+        "spotify",
+        "steam",
+        "dropbox",
+        "thunderbird",
+        "discord",
+        "zoom",
+        "curl",
+        "wget",
+        "git",
+        "apt",
+        "dnf",
+        "snapd",
+        "packagekitd",
+        "systemd-resolved",
+        "NetworkManager",
+        "containerd",
+        "(other)",
+        "disk:sda1",
+        "disk:nvme0n1p2",
+        "path:downloads",
+    ];
+
+    /// Usage data shaped like a service's: a handful of apps with a week of
+    /// daily totals, at a few sizes.
+    fn sample_trackers() -> Vec<UsageTracker> {
+        let start: DateTime<Utc> = "2025-06-01T00:00:00Z".parse().unwrap();
+        let first_day = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        [4, 12, 24]
+            .into_iter()
+            .map(|apps| {
+                let mut tracker = UsageTracker::default();
+                tracker.roll_over(ResetPeriod::Monthly, first_day);
+                for (i, app) in SAMPLE_APPS.iter().take(apps).enumerate() {
+                    let seen = start + TimeDelta::minutes(i as i64 * 7);
+                    tracker.mark_seen(app, seen);
+                    tracker.mark_active(app, seen + TimeDelta::hours(3));
+                    let delta = (i as u64 + 1) * 3_145_728;
+                    tracker.add_usage(app, delta * 7);
+                    for day in 0..7 {
+                        let date = first_day.checked_add_days(Days::new(day)).unwrap();
+                        tracker.add_daily_usage(app, delta, delta / 3, date);
+                    }
+                }
+                let now = start + TimeDelta::days(7);
+                tracker.record_scan(now);
+                tracker.mark_saved(now, "1.0.2");
+                tracker
+            })
+            .collect()
+    }
+
+    fn sample_payloads() -> Vec<Vec<u8>> {
+        sample_trackers()
+            .iter()
+            .map(|tracker| serde_json::to_vec(tracker).unwrap())
+            .collect()
+    }
+
+    fn train(payloads: &[Vec<u8>], max_size: usize) -> Vec<u8> {
+        let samples: Vec<_> = payloads.iter().map(Vec::as_slice).collect();
+        train_dictionary(&samples, max_size)
+    }
+
+    #[test]
+    fn test_compression_roundtrip() {
+        let data = create_test_data(2);
+        let compressed = compress_usage_data(&data).unwrap();
+        let decompressed = decompress_usage_data(&compressed).unwrap();
+        assert_eq!(data, decompressed);
+    }
+
+    #[test]
+    fn test_compression_efficiency() {
+        let data = create_test_data(1000);
+        let compressed = compress_usage_data(&data).unwrap();
+        let json_size = serde_json::to_vec(&data).unwrap().len();
+
+        assert!(
+            compressed.len() < json_size,
+            "Compressed size {} should be less than JSON size {}",
+            compressed.len(),
+            json_size
+        );
+    }
+
+    #[test]
+    fn test_compression_empty_data() {
+        let data = HashMap::new();
+        let compressed = compress_usage_data(&data).unwrap();
+        let decompressed = decompress_usage_data(&compressed).unwrap();
+        assert_eq!(data, decompressed);
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        let result = decompress_usage_data(b"invalid data");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_custom_compression_config() {
+        let data = create_test_data(100);
+
+        for level in 0..=9 {
+            let config = CompressionConfig {
+                level,
+                ..Default::default()
+            };
+            let compressed = compress_usage_data_with_config(&data, config).unwrap();
+            let decompressed = decompress_usage_data(&compressed).unwrap();
+            assert_eq!(data, decompressed);
+        }
+
+        let config = CompressionConfig {
+            level: 10,
+            ..Default::default()
+        };
+        let result = compress_usage_data_with_config(&data, config);
+        assert!(matches!(result, Err(CompressionError::InvalidLevel(10))));
+    }
+
+    #[test]
+    fn test_compression_deterministic() {
+        let data = create_test_data(100);
+        let config = CompressionConfig::default();
+
+        let compressed1 = compress_usage_data_with_config(&data, config).unwrap();
+        let compressed2 = compress_usage_data_with_config(&data, config).unwrap();
+        assert_eq!(compressed1, compressed2);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let data = vec![("a".to_string(), 1u64), ("b".to_string(), 2u64)];
+        let compressed = compress_json(&data, data.len()).unwrap();
+        let decompressed: Vec<(String, u64)> = decompress_json(&compressed).unwrap();
+        assert_eq!(data, decompressed);
+    }
+
+    #[test]
+    fn test_large_data_compression() {
+        let data = create_test_data(10000);
+        let compressed = compress_usage_data(&data).unwrap();
+        let decompressed = decompress_usage_data(&compressed).unwrap();
+        assert_eq!(data, decompressed);
+    }
+
+    /// Set `DG_UPDATE_DICTIONARY=1` to rewrite `src/usage.dict` after
+    /// changing the samples or the trainer.
+    #[test]
+    fn test_builtin_dictionary() {
+        let trained = train(&sample_payloads(), BUILTIN_DICTIONARY_SIZE);
+        if std::env::var_os("DG_UPDATE_DICTIONARY").is_some() {
+            let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/usage.dict");
+            std::fs::write(path, &trained).unwrap();
+            return;
+        }
+        assert_eq!(
+            trained, BUILTIN_DICTIONARY,
+            "run with DG_UPDATE_DICTIONARY=1"
+        );
+        assert!(!BUILTIN_DICTIONARY.is_empty());
+
+        let tracker = &sample_trackers()[0];
+        let plain = tracker.to_compressed().unwrap();
+        let config = CompressionConfig {
+            dictionary: Some(builtin_dictionary().id()),
+            ..Default::default()
+        };
+        let compressed = compress_json_with_config(tracker, 4, config).unwrap();
+        assert!(compressed.starts_with(DICTIONARY_MAGIC));
+        assert!(
+            compressed.len() * 4 < plain.len() * 3,
+            "{} bytes with the dictionary, {} without",
+            compressed.len(),
+            plain.len()
+        );
+        assert_eq!(
+            &decompress_json::<UsageTracker>(&compressed).unwrap(),
+            tracker
+        );
+    }
+
+    #[test]
+    fn test_trained_dictionary() {
+        let payloads = sample_payloads();
+        let dictionary = Dictionary::new(train(&payloads[1..], 4096)).unwrap();
+        assert!(!dictionary.as_bytes().is_empty() && dictionary.as_bytes().len() <= 4096);
+        let id = register_dictionary(dictionary.clone());
+        assert_eq!(register_dictionary(dictionary), id);
+
+        let data = create_test_data(50);
+        let config = CompressionConfig {
+            dictionary: Some(id),
+            ..Default::default()
+        };
+        for level in [0, 1, 9] {
+            let compressed =
+                compress_usage_data_with_config(&data, CompressionConfig { level, ..config })
+                    .unwrap();
+            assert_eq!(decompress_usage_data(&compressed).unwrap(), data);
+        }
+        assert!(matches!(
+            Dictionary::new(vec![b'x'; MAX_DICTIONARY_SIZE + 1]),
+            Err(CompressionError::DictionaryTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_missing_dictionary() {
+        let data = create_test_data(10);
+        let unknown = Dictionary::new(b"never registered".to_vec()).unwrap();
+        let json = serde_json::to_vec(&data).unwrap();
+        let compressed = compress_with_dictionary(&json, &unknown, 9).unwrap();
+        assert!(matches!(
+            decompress_usage_data(&compressed),
+            Err(CompressionError::UnknownDictionary(id)) if id == unknown.id()
+        ));
+
+        // Without its dictionary, compression falls back to plain gzip.
+        let config = CompressionConfig {
+            dictionary: Some(unknown.id()),
+            ..Default::default()
+        };
+        let compressed = compress_usage_data_with_config(&data, config).unwrap();
+        assert!(compressed.starts_with(&[0x1f, 0x8b]));
+        assert_eq!(decompress_usage_data(&compressed).unwrap(), data);
+
+        let builtin = CompressionConfig {
+            dictionary: Some(builtin_dictionary().id()),
+            ..Default::default()
+        };
+        let mut corrupt = compress_usage_data_with_config(&data, builtin).unwrap();
+        corrupt[8] ^= 0xff;
+        assert!(matches!(
+            decompress_usage_data(&corrupt),
+            Err(CompressionError::ChecksumMismatch)
+        ));
+        assert!(decompress_usage_data(&corrupt[..DICTIONARY_HEADER_LEN - 1]).is_err());
+    }
+}
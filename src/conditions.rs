@@ -0,0 +1,284 @@
+#[cfg(target_os = "linux")]
+use std::path::Path;
+
+use tokio::task::JoinError;
+
+use super::filter;
+
+#[cfg(target_os = "linux")]
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+#[cfg(target_os = "linux")]
+const ROUTE_TABLE: &str = "/proc/net/route";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    OnBattery,
+    Interface,
+}
+
+impl PauseReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OnBattery => "on_battery",
+            Self::Interface => "interface",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Conditions {
+    pub on_battery: Option<bool>,
+    pub interface: Option<String>,
+}
+
+impl Conditions {
+    pub async fn probe(check_battery: bool, check_interface: bool) -> Result<Self, JoinError> {
+        tokio::task::spawn_blocking(move || Self {
+            on_battery: check_battery.then(on_battery).flatten(),
+            interface: check_interface.then(default_interface).flatten(),
+        })
+        .await
+    }
+
+    pub fn pause_reason(
+        &self,
+        pause_on_battery: bool,
+        interface_pattern: Option<&str>,
+    ) -> Option<PauseReason> {
+        if pause_on_battery && self.on_battery == Some(true) {
+            return Some(PauseReason::OnBattery);
+        }
+        match (interface_pattern, &self.interface) {
+            (Some(pattern), Some(interface)) if interface_matches(pattern, interface) => None,
+            (Some(_), _) => Some(PauseReason::Interface),
+            (None, _) => None,
+        }
+    }
+}
+
+pub fn interface_matches(pattern: &str, interface: &str) -> bool {
+    filter::pattern_matches(pattern, interface)
+}
+
+#[cfg(target_os = "linux")]
+pub fn on_battery() -> Option<bool> {
+    power_supply_on_battery(Path::new(POWER_SUPPLY_DIR))
+}
+
+#[cfg(target_os = "linux")]
+fn power_supply_on_battery(dir: &Path) -> Option<bool> {
+    let read = |path: &Path, file: &str| {
+        std::fs::read_to_string(path.join(file))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+
+    let mut battery = None;
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        if read(&path, "scope") == "Device" {
+            continue;
+        }
+        match read(&path, "type").as_str() {
+            "Mains" | "USB" if read(&path, "online") == "1" => return Some(false),
+            "Battery" => {
+                let discharging = read(&path, "status") == "Discharging";
+                battery = Some(battery.unwrap_or(false) || discharging);
+            }
+            _ => {}
+        }
+    }
+    battery
+}
+
+#[cfg(target_os = "linux")]
+pub fn default_interface() -> Option<String> {
+    parse_route_table(&std::fs::read_to_string(ROUTE_TABLE).ok()?)
+}
+
+#[cfg(target_os = "linux")]
+fn parse_route_table(table: &str) -> Option<String> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [iface, "00000000", _, _, _, _, metric, "00000000", ..] => {
+                    Some((metric.parse::<u32>().unwrap_or(u32::MAX), *iface))
+                }
+                _ => None,
+            }
+        })
+        .min()
+        .map(|(_, iface)| iface.to_string())
+}
+
+#[cfg(target_os = "macos")]
+pub fn on_battery() -> Option<bool> {
+    let output = std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let source = output.lines().next()?;
+    if source.contains("'Battery Power'") {
+        Some(true)
+    } else if source.contains("'AC Power'") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn default_interface() -> Option<String> {
+    let output = std::process::Command::new("route")
+        .args(["-n", "get", "default"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("interface:"))
+        .map(|interface| interface.trim().to_string())
+}
+
+#[cfg(windows)]
+pub fn on_battery() -> Option<bool> {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return None;
+    }
+    match status.ACLineStatus {
+        0 => Some(true),
+        1 => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(windows)]
+pub fn default_interface() -> Option<String> {
+    use std::net::Ipv4Addr;
+
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        ConvertInterfaceIndexToLuid, ConvertInterfaceLuidToAlias, GetBestInterface,
+    };
+    use windows_sys::Win32::NetworkManagement::Ndis::{IF_MAX_STRING_SIZE, NET_LUID_LH};
+
+    let destination = u32::from_ne_bytes(Ipv4Addr::new(8, 8, 8, 8).octets());
+    let mut index = 0;
+    if unsafe { GetBestInterface(destination, &mut index) } != 0 {
+        return None;
+    }
+
+    let mut luid = NET_LUID_LH { Value: 0 };
+    if unsafe { ConvertInterfaceIndexToLuid(index, &mut luid) } != 0 {
+        return None;
+    }
+
+    let mut alias = [0u16; IF_MAX_STRING_SIZE as usize + 1];
+    if unsafe { ConvertInterfaceLuidToAlias(&luid, alias.as_mut_ptr(), alias.len()) } != 0 {
+        return None;
+    }
+    let len = alias.iter().position(|c| *c == 0).unwrap_or(alias.len());
+    Some(String::from_utf16_lossy(&alias[..len]))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn on_battery() -> Option<bool> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn default_interface() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interface_matches() {
+        assert!(interface_matches("usb0", "usb0"));
+        assert!(interface_matches("usb*", "usb0"));
+        assert!(interface_matches("*wwan*", "wwan0"));
+        assert!(interface_matches("en*s*", "enp0s20f0u1"));
+        assert!(interface_matches("Wi-Fi*", "wi-fi 2"));
+        assert!(!interface_matches("usb*", "wlan0"));
+        assert!(!interface_matches("usb0", "usb01"));
+        assert!(!interface_matches("*0", "eth1"));
+    }
+
+    #[test]
+    fn test_pause_reason() {
+        let conditions = Conditions {
+            on_battery: Some(true),
+            interface: Some("wlan0".to_string()),
+        };
+        assert_eq!(conditions.pause_reason(false, None), None);
+        assert_eq!(
+            conditions.pause_reason(true, None),
+            Some(PauseReason::OnBattery)
+        );
+        assert_eq!(conditions.pause_reason(false, Some("wlan*")), None);
+        assert_eq!(
+            conditions.pause_reason(false, Some("usb*")),
+            Some(PauseReason::Interface)
+        );
+
+        let offline = Conditions::default();
+        assert_eq!(offline.pause_reason(true, None), None);
+        assert_eq!(
+            offline.pause_reason(true, Some("usb*")),
+            Some(PauseReason::Interface)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_route_table() {
+        let table = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+wlan0\t0000A8C0\t00000000\t0001\t0\t0\t600\t00FFFFFF\t0\t0\t0
+wlan0\t00000000\t0100A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+usb0\t00000000\t012AA8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0
+";
+        assert_eq!(parse_route_table(table), Some("usb0".to_string()));
+        assert_eq!(parse_route_table(table.lines().next().unwrap()), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_power_supply_on_battery() {
+        let dir = tempfile::tempdir().unwrap();
+        let supply = |name: &str, files: &[(&str, &str)]| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(&path).unwrap();
+            for (file, value) in files {
+                std::fs::write(path.join(file), format!("{}\n", value)).unwrap();
+            }
+        };
+
+        assert_eq!(power_supply_on_battery(dir.path()), None);
+
+        supply(
+            "hidpp_battery_0",
+            &[
+                ("type", "Battery"),
+                ("scope", "Device"),
+                ("status", "Discharging"),
+            ],
+        );
+        assert_eq!(power_supply_on_battery(dir.path()), None);
+
+        supply("BAT0", &[("type", "Battery"), ("status", "Discharging")]);
+        supply("AC", &[("type", "Mains"), ("online", "0")]);
+        assert_eq!(power_supply_on_battery(dir.path()), Some(true));
+
+        supply("AC", &[("type", "Mains"), ("online", "1")]);
+        assert_eq!(power_supply_on_battery(dir.path()), Some(false));
+    }
+}
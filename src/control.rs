@@ -0,0 +1,819 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+use super::events::{Event, EventFilter};
+
+pub const CONTROL_SOCKET_NAME: &str = "control.sock";
+pub const CONTROL_QUEUE_SIZE: usize = 16;
+/// How long a subscriber may leave an event unread before it is
+/// disconnected.
+pub const SUBSCRIBER_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Named pipes are called `DataGuardian-<user>` on Windows.
+pub const PIPE_NAME_PREFIX: &str = "DataGuardian";
+/// How often, and how long apart, a client retries a pipe whose instances
+/// are all busy.
+#[cfg(windows)]
+const PIPE_BUSY_ATTEMPTS: u32 = 20;
+#[cfg(windows)]
+const PIPE_BUSY_WAIT: std::time::Duration = std::time::Duration::from_millis(50);
+
+#[derive(Error, Debug)]
+pub enum ControlError {
+    #[error("Control socket I/O failed: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid control message: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Daemon closed the connection without replying")]
+    Closed,
+    #[error("Control socket is not yet supported on this platform")]
+    Unsupported,
+    #[error("Service reported an error: {0}")]
+    Service(String),
+    #[error("Subscriber fell {0} events behind")]
+    Lagged(u64),
+    #[error("Subscriber stopped reading events")]
+    Stalled,
+}
+
+impl ControlError {
+    pub fn is_not_running(&self) -> bool {
+        match self {
+            Self::Io(e) => matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+            ),
+            Self::Unsupported => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum ControlRequest {
+    Status,
+    /// Scans right away instead of waiting for the next tick, then answers
+    /// like `Status`.
+    Scan,
+    Flush,
+    Reset {
+        app: String,
+    },
+    Pause,
+    Resume,
+    Reload,
+    Snooze {
+        #[serde(default)]
+        app: Option<String>,
+        #[serde(default)]
+        seconds: Option<u64>,
+    },
+    Cooldowns,
+    /// Clears the cooldown of `app`, or of every app when unset.
+    #[serde(rename = "clear_cooldown")]
+    ClearCooldown {
+        #[serde(default)]
+        app: Option<String>,
+    },
+    /// Switches the connection to pushing events that match the filter, one
+    /// JSON line each, until the client hangs up. Answered by the control
+    /// endpoint itself rather than the service loop.
+    Subscribe(EventFilter),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl ControlResponse {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            data: None,
+        }
+    }
+
+    pub fn data<T: Serialize>(data: &T) -> Self {
+        match serde_json::to_value(data) {
+            Ok(data) => Self {
+                data: Some(data),
+                ..Self::ok()
+            },
+            Err(e) => Self::error(e),
+        }
+    }
+
+    pub fn error(error: impl ToString) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.to_string()),
+            data: None,
+        }
+    }
+}
+
+pub type ControlMessage = (ControlRequest, oneshot::Sender<ControlResponse>);
+
+pub fn channel() -> (mpsc::Sender<ControlMessage>, mpsc::Receiver<ControlMessage>) {
+    mpsc::channel(CONTROL_QUEUE_SIZE)
+}
+
+/// A connected socket or pipe.
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+/// Where the service listens for control requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlEndpoint {
+    /// A socket file only its owner can open.
+    Socket(PathBuf),
+    /// A named pipe only the current user and SYSTEM can open, such as
+    /// `\\.\pipe\DataGuardian-alice`.
+    Pipe(String),
+}
+
+impl ControlEndpoint {
+    /// A socket in `runtime_dir` on Unix, the user's named pipe on Windows.
+    pub fn for_platform(runtime_dir: &Path) -> Self {
+        if cfg!(windows) {
+            Self::Pipe(pipe_name(&current_user()))
+        } else {
+            Self::Socket(runtime_dir.join(CONTROL_SOCKET_NAME))
+        }
+    }
+}
+
+impl fmt::Display for ControlEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Socket(path) => write!(f, "{}", path.display()),
+            Self::Pipe(name) => f.write_str(name),
+        }
+    }
+}
+
+/// The pipe name for `user`. Backslashes are not allowed after the
+/// `\\.\pipe\` prefix, so they are replaced.
+pub fn pipe_name(user: &str) -> String {
+    format!(r"\\.\pipe\{}-{}", PIPE_NAME_PREFIX, user.replace('\\', "_"))
+}
+
+fn current_user() -> String {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "default".to_string())
+}
+
+/// Sends requests to the running service over whichever transport the
+/// platform uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlClient {
+    endpoint: ControlEndpoint,
+}
+
+impl ControlClient {
+    pub fn new(endpoint: ControlEndpoint) -> Self {
+        Self { endpoint }
+    }
+
+    pub fn for_platform(runtime_dir: &Path) -> Self {
+        Self::new(ControlEndpoint::for_platform(runtime_dir))
+    }
+
+    pub fn endpoint(&self) -> &ControlEndpoint {
+        &self.endpoint
+    }
+
+    async fn connect(&self) -> Result<Box<dyn Connection>, ControlError> {
+        match &self.endpoint {
+            ControlEndpoint::Socket(path) => connect(path).await,
+            ControlEndpoint::Pipe(name) => pipe_connect(name).await,
+        }
+    }
+
+    pub async fn request(&self, request: &ControlRequest) -> Result<ControlResponse, ControlError> {
+        exchange(self.connect().await?, request).await
+    }
+
+    /// Asks the service to push events matching `filter`.
+    pub async fn subscribe(&self, filter: EventFilter) -> Result<EventStream, ControlError> {
+        let mut stream = self.connect().await?;
+        write_line(&mut stream, &ControlRequest::Subscribe(filter)).await?;
+        let mut lines = BufReader::new(stream).lines();
+        let response: ControlResponse = match lines.next_line().await? {
+            Some(line) => serde_json::from_str(&line)?,
+            None => return Err(ControlError::Closed),
+        };
+        if !response.ok {
+            return Err(ControlError::Service(response.error.unwrap_or_default()));
+        }
+        Ok(EventStream { lines })
+    }
+}
+
+/// Events pushed by the service after `ControlClient::subscribe`.
+pub struct EventStream {
+    lines: Lines<BufReader<Box<dyn Connection>>>,
+}
+
+impl EventStream {
+    /// The next event, or `None` once the service closes the connection.
+    /// Fails with `ControlError::Service` when the service says why it
+    /// disconnected, such as the client falling behind.
+    pub async fn next(&mut self) -> Result<Option<Event>, ControlError> {
+        let Some(line) = self.lines.next_line().await? else {
+            return Ok(None);
+        };
+        match serde_json::from_str(&line) {
+            Ok(event) => Ok(Some(event)),
+            Err(e) => match serde_json::from_str::<ControlResponse>(&line) {
+                Ok(response) => Err(ControlError::Service(response.error.unwrap_or_default())),
+                Err(_) => Err(e.into()),
+            },
+        }
+    }
+}
+
+/// Listens on `endpoint` until it fails. Subscribers get the events sent on
+/// `events`.
+pub async fn serve_endpoint(
+    endpoint: &ControlEndpoint,
+    requests: mpsc::Sender<ControlMessage>,
+    events: broadcast::Sender<Event>,
+) -> io::Result<()> {
+    match endpoint {
+        ControlEndpoint::Socket(path) => serve(path, requests, events).await,
+        ControlEndpoint::Pipe(name) => serve_pipe(name, requests, events).await,
+    }
+}
+
+/// Answers newline-delimited JSON requests on one connection until the
+/// client hangs up or subscribes.
+async fn handle_connection<S>(
+    stream: S,
+    requests: mpsc::Sender<ControlMessage>,
+    events: broadcast::Sender<Event>,
+) -> Result<(), ControlError>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(ControlRequest::Subscribe(filter)) => {
+                let events = events.subscribe();
+                write_line(&mut writer, &ControlResponse::ok()).await?;
+                return stream_events(&mut lines, &mut writer, events, &filter).await;
+            }
+            Ok(request) => {
+                let (reply_tx, reply_rx) = oneshot::channel();
+                requests
+                    .send((request, reply_tx))
+                    .await
+                    .map_err(|_| ControlError::Closed)?;
+                reply_rx.await.map_err(|_| ControlError::Closed)?
+            }
+            Err(e) => ControlResponse::error(format!("Invalid request: {}", e)),
+        };
+        write_line(&mut writer, &response).await?;
+    }
+    Ok(())
+}
+
+/// Pushes the events `filter` passes until the client hangs up. A client
+/// that falls more than the channel's capacity behind, or leaves an event
+/// unread for `SUBSCRIBER_WRITE_TIMEOUT`, is disconnected; the service
+/// never waits for it.
+async fn stream_events<R, W>(
+    lines: &mut Lines<R>,
+    writer: &mut W,
+    mut events: broadcast::Receiver<Event>,
+    filter: &EventFilter,
+) -> Result<(), ControlError>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                if line?.is_none() {
+                    return Ok(());
+                }
+            }
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if let Some(event) = filter.apply(&event) {
+                        tokio::time::timeout(SUBSCRIBER_WRITE_TIMEOUT, write_line(writer, &event))
+                            .await
+                            .map_err(|_| ControlError::Stalled)??;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    let response = ControlResponse::error(format!(
+                        "Disconnected after falling {} events behind",
+                        skipped
+                    ));
+                    let _ =
+                        tokio::time::timeout(SUBSCRIBER_WRITE_TIMEOUT, write_line(writer, &response))
+                            .await;
+                    return Err(ControlError::Lagged(skipped));
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+}
+
+async fn write_line<W, T>(writer: &mut W, value: &T) -> Result<(), ControlError>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut encoded = serde_json::to_vec(value)?;
+    encoded.push(b'\n');
+    writer.write_all(&encoded).await?;
+    Ok(())
+}
+
+/// Sends one request on a connected stream and reads the reply.
+async fn exchange<S>(
+    mut stream: S,
+    request: &ControlRequest,
+) -> Result<ControlResponse, ControlError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    write_line(&mut stream, request).await?;
+
+    let mut line = String::new();
+    if BufReader::new(stream).read_line(&mut line).await? == 0 {
+        return Err(ControlError::Closed);
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(unix)]
+pub async fn serve(
+    path: &Path,
+    requests: mpsc::Sender<ControlMessage>,
+    events: broadcast::Sender<Event>,
+) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    use tokio::fs::DirBuilder;
+    use tokio::net::{UnixListener, UnixStream};
+
+    if path.exists() {
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "another daemon is already listening on the control socket",
+            ));
+        }
+        tokio::fs::remove_file(path).await?;
+    }
+    // Bound in a directory only this user can enter, and moved into place
+    // once private, so nobody can connect while it has the umask's mode.
+    let staging = path.with_file_name(format!(".control-{}", std::process::id()));
+    let _ = tokio::fs::remove_dir_all(&staging).await;
+    DirBuilder::new().mode(0o700).create(&staging).await?;
+    let staged = staging.join(CONTROL_SOCKET_NAME);
+    let bound = async {
+        let listener = UnixListener::bind(&staged)?;
+        tokio::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600)).await?;
+        tokio::fs::rename(&staged, path).await?;
+        Ok::<_, io::Error>(listener)
+    }
+    .await;
+    let _ = tokio::fs::remove_dir_all(&staging).await;
+    let listener = bound?;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let requests = requests.clone();
+        let events = events.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, requests, events).await {
+                debug!(error = %e, "Control connection failed");
+            }
+        });
+    }
+}
+
+pub async fn request(
+    path: &Path,
+    request: &ControlRequest,
+) -> Result<ControlResponse, ControlError> {
+    exchange(connect(path).await?, request).await
+}
+
+#[cfg(unix)]
+async fn connect(path: &Path) -> Result<Box<dyn Connection>, ControlError> {
+    Ok(Box::new(tokio::net::UnixStream::connect(path).await?))
+}
+
+#[cfg(not(unix))]
+pub async fn serve(
+    _path: &Path,
+    _requests: mpsc::Sender<ControlMessage>,
+    _events: broadcast::Sender<Event>,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        ControlError::Unsupported.to_string(),
+    ))
+}
+
+#[cfg(not(unix))]
+async fn connect(_path: &Path) -> Result<Box<dyn Connection>, ControlError> {
+    Err(ControlError::Unsupported)
+}
+
+/// Like `serve`, over a named pipe. Fails when another process already
+/// owns the pipe.
+#[cfg(windows)]
+pub async fn serve_pipe(
+    name: &str,
+    requests: mpsc::Sender<ControlMessage>,
+    events: broadcast::Sender<Event>,
+) -> io::Result<()> {
+    let security = pipe::Security::current_user()?;
+    let mut server = security.create(name, true).map_err(|e| {
+        if e.raw_os_error() == Some(pipe::ERROR_ACCESS_DENIED as i32) {
+            io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "another daemon is already listening on the control pipe",
+            )
+        } else {
+            e
+        }
+    })?;
+
+    loop {
+        server.connect().await?;
+        // Create the next instance before handing this one off, so a client
+        // never finds the pipe missing.
+        let connected = std::mem::replace(&mut server, security.create(name, false)?);
+        let requests = requests.clone();
+        let events = events.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(connected, requests, events).await {
+                debug!(error = %e, "Control connection failed");
+            }
+        });
+    }
+}
+
+pub async fn pipe_request(
+    name: &str,
+    request: &ControlRequest,
+) -> Result<ControlResponse, ControlError> {
+    exchange(pipe_connect(name).await?, request).await
+}
+
+#[cfg(windows)]
+async fn pipe_connect(name: &str) -> Result<Box<dyn Connection>, ControlError> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    let mut attempts = 0;
+    let stream = loop {
+        match ClientOptions::new().open(name) {
+            Ok(stream) => break stream,
+            Err(e)
+                if e.raw_os_error() == Some(pipe::ERROR_PIPE_BUSY as i32)
+                    && attempts < PIPE_BUSY_ATTEMPTS =>
+            {
+                attempts += 1;
+                tokio::time::sleep(PIPE_BUSY_WAIT).await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+    Ok(Box::new(stream))
+}
+
+#[cfg(not(windows))]
+pub async fn serve_pipe(
+    _name: &str,
+    _requests: mpsc::Sender<ControlMessage>,
+    _events: broadcast::Sender<Event>,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        ControlError::Unsupported.to_string(),
+    ))
+}
+
+#[cfg(not(windows))]
+async fn pipe_connect(_name: &str) -> Result<Box<dyn Connection>, ControlError> {
+    Err(ControlError::Unsupported)
+}
+
+#[cfg(windows)]
+mod pipe {
+    use std::ffi::c_void;
+    use std::io;
+
+    use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE, LocalFree};
+    pub use windows_sys::Win32::Foundation::{ERROR_ACCESS_DENIED, ERROR_PIPE_BUSY};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+        SDDL_REVISION_1,
+    };
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, SECURITY_ATTRIBUTES, TOKEN_QUERY, TOKEN_USER, TokenUser,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    /// A security descriptor that grants the pipe to one user and SYSTEM.
+    pub struct Security {
+        descriptor: *mut c_void,
+    }
+
+    // SAFETY: the descriptor is never written after it is created, and is
+    // freed once, on drop.
+    unsafe impl Send for Security {}
+
+    impl Security {
+        pub fn current_user() -> io::Result<Self> {
+            let sddl = format!("D:P(A;;GA;;;{})(A;;GA;;;SY)", current_user_sid()?);
+            let sddl = sddl.encode_utf16().chain(Some(0)).collect::<Vec<_>>();
+            let mut descriptor = std::ptr::null_mut();
+            if unsafe {
+                ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                    sddl.as_ptr(),
+                    SDDL_REVISION_1,
+                    &mut descriptor,
+                    std::ptr::null_mut(),
+                )
+            } == 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { descriptor })
+        }
+
+        /// Creates an instance of the pipe that rejects remote clients.
+        pub fn create(&self, name: &str, first: bool) -> io::Result<NamedPipeServer> {
+            let mut attributes = SECURITY_ATTRIBUTES {
+                nLength: size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: self.descriptor,
+                bInheritHandle: 0,
+            };
+            unsafe {
+                ServerOptions::new()
+                    .first_pipe_instance(first)
+                    .reject_remote_clients(true)
+                    .create_with_security_attributes_raw(
+                        name,
+                        (&mut attributes as *mut SECURITY_ATTRIBUTES).cast(),
+                    )
+            }
+        }
+    }
+
+    impl Drop for Security {
+        fn drop(&mut self) {
+            unsafe { LocalFree(self.descriptor) };
+        }
+    }
+
+    /// The SID of the account this process runs as, such as
+    /// `S-1-5-21-1-2-3-1001`.
+    fn current_user_sid() -> io::Result<String> {
+        let mut token: HANDLE = std::ptr::null_mut();
+        if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut len = 0;
+        unsafe { GetTokenInformation(token, TokenUser, std::ptr::null_mut(), 0, &mut len) };
+        // u64 keeps the buffer aligned for TOKEN_USER.
+        let mut buffer = vec![0u64; (len as usize).div_ceil(8)];
+        let found = unsafe {
+            GetTokenInformation(token, TokenUser, buffer.as_mut_ptr().cast(), len, &mut len)
+        };
+        let error = io::Error::last_os_error();
+        unsafe { CloseHandle(token) };
+        if found == 0 {
+            return Err(error);
+        }
+
+        let user = unsafe { &*buffer.as_ptr().cast::<TOKEN_USER>() };
+        let mut sid = std::ptr::null_mut();
+        if unsafe { ConvertSidToStringSidW(user.User.Sid, &mut sid) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let len = (0..).take_while(|i| unsafe { *sid.add(*i) } != 0).count();
+        let string = String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(sid, len) });
+        unsafe { LocalFree(sid.cast()) };
+        Ok(string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::events::EventKind;
+
+    #[test]
+    fn test_request_format() {
+        assert_eq!(
+            serde_json::from_str::<ControlRequest>(r#"{"command":"reset","app":"firefox"}"#)
+                .unwrap(),
+            ControlRequest::Reset {
+                app: "firefox".to_string()
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<ControlRequest>(r#"{"command":"snooze"}"#).unwrap(),
+            ControlRequest::Snooze {
+                app: None,
+                seconds: None
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&ControlRequest::Pause).unwrap(),
+            r#"{"command":"pause"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ControlRequest>(r#"{"command":"scan"}"#).unwrap(),
+            ControlRequest::Scan
+        );
+        assert_eq!(
+            serde_json::from_str::<ControlRequest>(r#"{"command":"clear_cooldown","app":"curl"}"#)
+                .unwrap(),
+            ControlRequest::ClearCooldown {
+                app: Some("curl".to_string())
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&ControlRequest::Subscribe(EventFilter {
+                only: vec![EventKind::Alerts],
+                app: Some("chrome".to_string()),
+            }))
+            .unwrap(),
+            r#"{"command":"subscribe","only":["alerts"],"app":"chrome"}"#
+        );
+    }
+
+    #[test]
+    fn test_platform_endpoint() {
+        assert_eq!(pipe_name("alice"), r"\\.\pipe\DataGuardian-alice");
+        assert_eq!(
+            pipe_name(r"CORP\alice"),
+            r"\\.\pipe\DataGuardian-CORP_alice"
+        );
+
+        let dir = Path::new("data");
+        let endpoint = ControlEndpoint::for_platform(dir);
+        #[cfg(windows)]
+        assert!(matches!(endpoint, ControlEndpoint::Pipe(_)));
+        #[cfg(not(windows))]
+        assert_eq!(
+            endpoint,
+            ControlEndpoint::Socket(dir.join(CONTROL_SOCKET_NAME))
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_control_socket_roundtrip() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONTROL_SOCKET_NAME);
+        let (tx, mut rx) = channel();
+        let events = broadcast::Sender::new(8);
+
+        let server_path = path.clone();
+        let server_events = events.clone();
+        tokio::spawn(async move { serve(&server_path, tx, server_events).await });
+        tokio::spawn(async move {
+            while let Some((request, reply)) = rx.recv().await {
+                let response = match request {
+                    ControlRequest::Status => ControlResponse::data(&"running"),
+                    _ => ControlResponse::error("unsupported"),
+                };
+                let _ = reply.send(response);
+            }
+        });
+
+        while !path.exists() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        // The socket is private from the moment it appears.
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let client = ControlClient::new(ControlEndpoint::Socket(path.clone()));
+        let response = client.request(&ControlRequest::Status).await.unwrap();
+        assert!(response.ok);
+        assert_eq!(response.data, Some(Value::from("running")));
+        let entries = std::fs::read_dir(dir.path()).unwrap().count();
+        assert_eq!(entries, 1, "The staging directory is removed");
+
+        let response = client.request(&ControlRequest::Flush).await.unwrap();
+        assert!(!response.ok);
+        assert_eq!(response.error.as_deref(), Some("unsupported"));
+
+        let mut stream = client
+            .subscribe(EventFilter {
+                only: vec![EventKind::Reloads],
+                app: None,
+            })
+            .await
+            .unwrap();
+        let time = "2025-06-18T12:00:00Z".parse().unwrap();
+        let reload = Event::Reload {
+            time,
+            fields: vec!["data_limit".to_string()],
+        };
+        events
+            .send(Event::Save {
+                time,
+                size_bytes: 10,
+                duration_ms: 1.0,
+            })
+            .unwrap();
+        events.send(reload.clone()).unwrap();
+        assert_eq!(stream.next().await.unwrap(), Some(reload));
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_is_disconnected() {
+        let (client, server) = tokio::io::duplex(1024);
+        let (tx, _rx) = channel();
+        let events = broadcast::Sender::new(2);
+        let server = tokio::spawn(handle_connection(server, tx, events.clone()));
+
+        let (reader, mut writer) = tokio::io::split(client);
+        let mut lines = BufReader::new(reader).lines();
+        write_line(
+            &mut writer,
+            &ControlRequest::Subscribe(EventFilter::default()),
+        )
+        .await
+        .unwrap();
+        let response: ControlResponse =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert!(response.ok);
+
+        // Nothing runs the server between these sends, so it is three
+        // events behind when it next looks.
+        for size_bytes in 0..5 {
+            let _ = events.send(Event::Save {
+                time: Utc::now(),
+                size_bytes,
+                duration_ms: 1.0,
+            });
+        }
+        let response: ControlResponse =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(
+            response.error.as_deref(),
+            Some("Disconnected after falling 3 events behind")
+        );
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(ControlError::Lagged(3))
+        ));
+        assert_eq!(lines.next_line().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_request_not_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = request(
+            &dir.path().join(CONTROL_SOCKET_NAME),
+            &ControlRequest::Status,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.is_not_running());
+    }
+}
@@ -0,0 +1,220 @@
+//! Saves the latest usage when the service panics, and leaves a marker so
+//! the next start can say that it crashed.
+
+use std::any::Any;
+use std::future::Future;
+use std::panic::{AssertUnwindSafe, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, TryLockError};
+use std::task::{Context, Poll};
+use std::thread::ThreadId;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::tracker::UsageTracker;
+
+/// Written next to the data file by the panic hook.
+pub const CRASH_MARKER_FILE: &str = "crash.json";
+
+/// What the panic hook knew when the service crashed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CrashMarker {
+    pub time: DateTime<Utc>,
+    pub message: String,
+    pub location: Option<String>,
+    /// Whether the latest usage made it to the data file.
+    pub saved: bool,
+}
+
+impl CrashMarker {
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Reads and removes the marker left by a crash, if there is one. A
+    /// marker that cannot be parsed still counts, with what is known.
+    pub fn take(path: &Path) -> Option<Self> {
+        let contents = std::fs::read(path).ok()?;
+        let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+        let _ = std::fs::remove_file(path);
+        Some(serde_json::from_slice(&contents).unwrap_or_else(|e| Self {
+            time: modified.map_or_else(|_| Utc::now(), DateTime::from),
+            message: format!("unreadable crash marker: {}", e),
+            location: None,
+            saved: false,
+        }))
+    }
+}
+
+/// Holds the latest usage where a panic hook can reach it. The hook only
+/// handles panics on the thread that installed it, since panics in tasks
+/// and blocking reads are caught and reported as errors.
+#[derive(Debug)]
+pub struct CrashGuard {
+    data_path: PathBuf,
+    marker_path: PathBuf,
+    latest: Mutex<Option<UsageTracker>>,
+    recorded: Mutex<Option<CrashMarker>>,
+}
+
+impl CrashGuard {
+    pub fn new(data_path: PathBuf, marker_path: PathBuf) -> Self {
+        Self {
+            data_path,
+            marker_path,
+            latest: Mutex::new(None),
+            recorded: Mutex::new(None),
+        }
+    }
+
+    /// Remembers `tracker` as the usage to save on a crash.
+    pub fn update(&self, tracker: &UsageTracker) {
+        *self.latest.lock().unwrap_or_else(|e| e.into_inner()) = Some(tracker.clone());
+    }
+
+    /// Saves the latest usage and writes the crash marker. Only the first
+    /// call does anything, so a panic caught twice is recorded once.
+    pub fn record(&self, message: String, location: Option<String>) -> Option<CrashMarker> {
+        let mut recorded = match self.recorded.try_lock() {
+            Ok(recorded) => recorded,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+        if recorded.is_some() {
+            return None;
+        }
+        let marker = CrashMarker {
+            time: Utc::now(),
+            message,
+            location,
+            saved: self.save(),
+        };
+        let _ = marker.write(&self.marker_path);
+        *recorded = Some(marker.clone());
+        Some(marker)
+    }
+
+    /// Marks the recorded crash as saved, after a later save succeeded
+    /// where the hook's did not.
+    pub fn mark_saved(&self) {
+        let mut recorded = self.recorded.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(marker) = recorded.as_mut().filter(|marker| !marker.saved) {
+            marker.saved = true;
+            let _ = marker.write(&self.marker_path);
+        }
+    }
+
+    /// Writes the latest usage next to the data file, then renames it into
+    /// place. Gives up rather than wait when the panic came from inside
+    /// `update`.
+    fn save(&self) -> bool {
+        let latest = match self.latest.try_lock() {
+            Ok(latest) => latest,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return false,
+        };
+        let Some(compressed) = latest
+            .as_ref()
+            .and_then(|tracker| tracker.to_compressed().ok())
+        else {
+            return false;
+        };
+        let mut new_path = self.data_path.clone().into_os_string();
+        new_path.push(".crash");
+        let new_path = PathBuf::from(new_path);
+        std::fs::write(&new_path, compressed).is_ok()
+            && std::fs::rename(&new_path, &self.data_path).is_ok()
+    }
+
+    /// Records panics on the current thread before running the previous
+    /// hook.
+    pub fn install_hook(self: &Arc<Self>) {
+        let guard = self.clone();
+        let thread = std::thread::current().id();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            guard.on_panic(thread, info);
+            previous(info);
+        }));
+    }
+
+    fn on_panic(&self, thread: ThreadId, info: &PanicHookInfo<'_>) {
+        if std::thread::current().id() != thread {
+            return;
+        }
+        self.record(
+            panic_message(info.payload()),
+            info.location().map(ToString::to_string),
+        );
+    }
+}
+
+/// The text a panic was raised with.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string())
+}
+
+/// Runs `future`, returning the payload instead of unwinding when it
+/// panics, so the caller can save what it has before re-raising.
+pub fn catch_unwind<F: Future>(future: F) -> CatchUnwind<F> {
+    CatchUnwind(Box::pin(future))
+}
+
+pub struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self.0.as_mut();
+        match std::panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "monitor"))]
+mod tests {
+    use super::*;
+    use crate::monitor::CounterSource;
+    use crate::test_support::{PANICKING_SOURCE_MESSAGE, PanickingSource};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_panicking_source_leaves_marker() {
+        let dir = tempdir().unwrap();
+        let data_path = dir.path().join("usage.dat");
+        let marker_path = dir.path().join(CRASH_MARKER_FILE);
+        let guard = Arc::new(CrashGuard::new(data_path.clone(), marker_path.clone()));
+        let mut tracker = UsageTracker::default();
+        tracker.add_usage("firefox", 100);
+        guard.update(&tracker);
+        guard.install_hook();
+
+        let result = catch_unwind(async { PanickingSource.read() }).await;
+        let payload = result.unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), PANICKING_SOURCE_MESSAGE);
+
+        let saved = UsageTracker::from_compressed(&std::fs::read(&data_path).unwrap()).unwrap();
+        assert_eq!(saved.usage(), tracker.usage());
+        let marker = CrashMarker::take(&marker_path).unwrap();
+        assert_eq!(marker.message, PANICKING_SOURCE_MESSAGE);
+        assert!(marker.saved);
+        assert!(marker.location.unwrap().contains("test_support.rs"));
+        assert!(CrashMarker::take(&marker_path).is_none());
+
+        // Only the first panic is recorded.
+        assert!(guard.record("again".to_string(), None).is_none());
+        guard.mark_saved();
+        assert!(!marker_path.exists());
+    }
+}
@@ -0,0 +1,522 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use color_eyre::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Sparkline, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use tokio::sync::mpsc;
+use tokio::time::{Instant, interval};
+
+use crate::cli;
+use crate::{load_persisted_data, save_persisted_data};
+use data_guardian::i18n::Localizer;
+use data_guardian::monitor::{self, CounterSource, Sampler, UsageData};
+use data_guardian::settings::Settings;
+use data_guardian::top::{AppUsageView, SortKey, UsageSnapshot};
+use data_guardian::tracker::UsageTracker;
+
+const BAR_WIDTH: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Source {
+    Daemon { paused: bool, uptime: u64 },
+    Standalone,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Quit,
+    Reset(String),
+}
+
+#[derive(Debug)]
+struct Dashboard {
+    snapshot: UsageSnapshot,
+    history: VecDeque<u64>,
+    samples: usize,
+    primed: bool,
+    sort: SortKey,
+    filter: String,
+    filtering: bool,
+    selected: usize,
+    source: Option<Source>,
+    message: Option<String>,
+    localizer: Localizer,
+}
+
+impl Dashboard {
+    fn new(limit: u64, samples: usize) -> Self {
+        Self {
+            snapshot: UsageSnapshot::new(UsageData::new(), limit),
+            history: VecDeque::with_capacity(samples.max(1)),
+            samples: samples.max(1),
+            primed: false,
+            sort: SortKey::default(),
+            filter: String::new(),
+            filtering: false,
+            selected: 0,
+            source: None,
+            message: None,
+            localizer: Localizer::default(),
+        }
+    }
+
+    fn set_source(&mut self, source: Source) {
+        let unchanged = matches!(
+            (&self.source, &source),
+            (Some(Source::Daemon { .. }), Source::Daemon { .. })
+                | (Some(Source::Standalone), Source::Standalone)
+        );
+        if !unchanged {
+            self.primed = false;
+        }
+        self.source = Some(source);
+    }
+
+    fn update(&mut self, usage: UsageData, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let mut total = 0u64;
+        self.snapshot.rates = usage
+            .iter()
+            .map(|(name, bytes)| {
+                let delta = match (self.primed, self.snapshot.apps.get(name)) {
+                    (false, _) => 0,
+                    (true, Some(prev)) => bytes.saturating_sub(*prev),
+                    (true, None) => *bytes,
+                };
+                total = total.saturating_add(delta);
+                let rate = if seconds > 0.0 {
+                    delta as f64 / seconds
+                } else {
+                    0.0
+                };
+                (name.clone(), rate)
+            })
+            .collect();
+
+        if self.history.len() == self.samples {
+            self.history.pop_front();
+        }
+        self.history.push_back(total);
+        self.snapshot.apps = usage;
+        self.primed = true;
+        self.clamp_selection();
+    }
+
+    fn rows(&self) -> Vec<AppUsageView> {
+        let filter = self.filter.to_lowercase();
+        self.snapshot
+            .top_n(usize::MAX, self.sort)
+            .into_iter()
+            .filter(|row| row.name.to_lowercase().contains(&filter))
+            .collect()
+    }
+
+    fn clamp_selection(&mut self) {
+        self.selected = self.selected.min(self.rows().len().saturating_sub(1));
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+
+        if self.filtering {
+            match key.code {
+                KeyCode::Char(c) => self.filter.push(c),
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                KeyCode::Enter => self.filtering = false,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.filtering = false;
+                }
+                _ => {}
+            }
+            self.selected = 0;
+            return None;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(Action::Quit),
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected += 1;
+                self.clamp_selection();
+            }
+            KeyCode::Char('s') => self.sort = self.sort.next(),
+            KeyCode::Char('/') => self.filtering = true,
+            KeyCode::Char('r') => {
+                return self
+                    .rows()
+                    .into_iter()
+                    .nth(self.selected)
+                    .map(|row| Action::Reset(row.name));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn header(&self) -> Row<'static> {
+        let title = |name: &str, column: Option<SortKey>| match column == Some(self.sort) {
+            true => format!("{} ▼", name),
+            false => name.to_string(),
+        };
+        Row::new([
+            title("APPLICATION", Some(SortKey::Name)),
+            title("USAGE", Some(SortKey::Usage)),
+            title("LIMIT", None),
+            title("USED", None),
+            title("RATE", Some(SortKey::Rate)),
+        ])
+        .style(Style::new().add_modifier(Modifier::BOLD))
+    }
+
+    fn footer(&self) -> Vec<Line<'static>> {
+        let source = match &self.source {
+            Some(Source::Daemon { paused, uptime }) => format!(
+                "Connected to Data Guardian ({}, up {})",
+                if *paused { "paused" } else { "active" },
+                humantime::format_duration(Duration::from_secs(*uptime))
+            ),
+            Some(Source::Standalone) => {
+                "Standalone sampling: Data Guardian is not running".to_string()
+            }
+            None => "Waiting for the first sample".to_string(),
+        };
+        let input = match (self.filtering, &self.message) {
+            (true, _) => format!("Filter: {}_", self.filter),
+            (false, Some(message)) => message.clone(),
+            (false, None) => "q quit  s sort  / filter  r reset selected  ↑↓ select".to_string(),
+        };
+        vec![Line::from(source), Line::from(input)]
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [table_area, sparkline_area, footer_area] = Layout::vertical([
+            Constraint::Min(5),
+            Constraint::Length(5),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+
+        let rows: Vec<_> = self
+            .rows()
+            .into_iter()
+            .map(|row| {
+                let ratio = row.percent.unwrap_or(0.0) / 100.0;
+                let style = if row.over {
+                    Style::new().fg(Color::Red)
+                } else {
+                    Style::new()
+                };
+                Row::new([
+                    Cell::from(row.name),
+                    Cell::from(self.localizer.format_bytes(row.bytes)),
+                    Cell::from(self.localizer.format_bytes(row.limit)),
+                    Cell::from(bar(ratio, BAR_WIDTH)),
+                    Cell::from(format!(
+                        "{}/s",
+                        self.localizer.format_bytes(row.rate.round() as u64)
+                    )),
+                ])
+                .style(style)
+            })
+            .collect();
+
+        let title = match self.filter.is_empty() {
+            true => " Applications ".to_string(),
+            false => format!(" Applications matching '{}' ", self.filter),
+        };
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(12),
+                Constraint::Length(12),
+                Constraint::Length(BAR_WIDTH as u16 + 6),
+                Constraint::Length(14),
+            ],
+        )
+        .header(self.header())
+        .block(Block::new().borders(Borders::ALL).title(title))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state = TableState::new().with_selected(Some(self.selected));
+        frame.render_stateful_widget(table, table_area, &mut state);
+
+        let last = self.history.back().copied().unwrap_or(0);
+        let sparkline = Sparkline::default()
+            .block(Block::new().borders(Borders::ALL).title(format!(
+                " Total I/O: {}/sample ",
+                self.localizer.format_bytes(last)
+            )))
+            .data(self.history.iter().copied())
+            .style(Style::new().fg(Color::Cyan));
+        frame.render_widget(sparkline, sparkline_area);
+
+        frame.render_widget(Paragraph::new(self.footer()), footer_area);
+    }
+}
+
+fn bar(ratio: f64, width: usize) -> String {
+    let filled = ((ratio.min(1.0) * width as f64).round() as usize).min(width);
+    format!(
+        "{}{} {:>3.0}%",
+        "█".repeat(filled),
+        "░".repeat(width - filled),
+        ratio * 100.0
+    )
+}
+
+#[derive(Debug)]
+struct Standalone {
+    tracker: UsageTracker,
+    sampler: Sampler,
+    source: Arc<dyn CounterSource>,
+}
+
+impl Standalone {
+    async fn load(settings: &Settings) -> Result<Self> {
+        Ok(Self {
+            tracker: load_persisted_data().await.unwrap_or_default(),
+            sampler: Sampler::new().with_max_rate(settings.max_bytes_per_second),
+            source: settings.source()?,
+        })
+    }
+
+    async fn sample(&mut self) -> Result<UsageData> {
+        let current = monitor::snapshot(self.source.clone()).await?;
+        for (app, delta) in self.sampler.advance(current).usage {
+            self.tracker.add_usage(&app, delta);
+        }
+        Ok(self.tracker.usage())
+    }
+}
+
+async fn reset(app: &str, standalone: Option<&mut Standalone>) -> Result<()> {
+    let Some(standalone) = standalone else {
+        return cli::reset_app(app, false).await;
+    };
+    standalone.tracker.reset_app(app);
+    if let Some(mut tracker) = load_persisted_data().await
+        && tracker.reset_app(app)
+    {
+        save_persisted_data(&tracker).await?;
+    }
+    Ok(())
+}
+
+pub async fn run(settings: &Settings, period: Duration, samples: usize) -> Result<()> {
+    let (tx, events) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if tx.blocking_send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, events, settings, period, samples).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    mut events: mpsc::Receiver<Event>,
+    settings: &Settings,
+    period: Duration,
+    samples: usize,
+) -> Result<()> {
+    let mut dashboard = Dashboard::new(settings.data_limit, samples);
+    let mut standalone: Option<Standalone> = None;
+    let mut ticker = interval(period);
+    let mut last_sample = Instant::now();
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                let usage = match cli::daemon_status().await {
+                    Ok(Some(report)) => {
+                        standalone = None;
+                        dashboard.snapshot.limit = report.settings_summary.data_limit;
+                        dashboard.set_source(Source::Daemon {
+                            paused: report.paused,
+                            uptime: report.uptime,
+                        });
+                        cli::status_usage(&report)
+                    }
+                    _ => {
+                        let sampler = match &mut standalone {
+                            Some(sampler) => sampler,
+                            None => standalone.insert(Standalone::load(settings).await?),
+                        };
+                        dashboard.snapshot.limit = settings.data_limit;
+                        dashboard.set_source(Source::Standalone);
+                        sampler.sample().await?
+                    }
+                };
+                dashboard.update(usage, last_sample.elapsed());
+                last_sample = Instant::now();
+            }
+            Some(event) = events.recv() => {
+                let Event::Key(key) = event else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                dashboard.message = None;
+                match dashboard.handle_key(key) {
+                    Some(Action::Quit) => return Ok(()),
+                    Some(Action::Reset(app)) => {
+                        dashboard.message = Some(match reset(&app, standalone.as_mut()).await {
+                            Ok(()) => format!("Reset usage for {}", app),
+                            Err(e) => format!("Failed to reset {}: {}", app, e),
+                        });
+                    }
+                    None => {}
+                }
+            }
+        }
+        terminal.draw(|frame| dashboard.draw(frame))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+
+    fn usage(entries: &[(&str, u64)]) -> UsageData {
+        entries
+            .iter()
+            .map(|(name, bytes)| (name.to_string(), *bytes))
+            .collect()
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn names(dashboard: &Dashboard) -> Vec<String> {
+        dashboard.rows().into_iter().map(|row| row.name).collect()
+    }
+
+    #[test]
+    fn test_update_rates() {
+        let mut dashboard = Dashboard::new(1000, 2);
+        dashboard.update(usage(&[("a", 100)]), Duration::from_secs(2));
+        assert_eq!(dashboard.snapshot.rates["a"], 0.0);
+
+        dashboard.update(usage(&[("a", 300), ("b", 50)]), Duration::from_secs(2));
+        assert_eq!(dashboard.snapshot.rates["a"], 100.0);
+        assert_eq!(dashboard.snapshot.rates["b"], 25.0);
+
+        dashboard.update(usage(&[("a", 0), ("b", 50)]), Duration::from_secs(2));
+        assert_eq!(dashboard.snapshot.rates["a"], 0.0);
+        assert_eq!(dashboard.history, [250, 0]);
+
+        dashboard.set_source(Source::Standalone);
+        dashboard.update(usage(&[("a", 900)]), Duration::from_secs(2));
+        assert_eq!(dashboard.snapshot.rates["a"], 0.0);
+    }
+
+    #[test]
+    fn test_sort_and_filter() {
+        let mut dashboard = Dashboard::new(1000, 10);
+        dashboard.update(
+            usage(&[("Firefox", 100), ("cargo", 500), ("fish", 10)]),
+            Duration::from_secs(1),
+        );
+        dashboard.update(
+            usage(&[("Firefox", 400), ("cargo", 500), ("fish", 20)]),
+            Duration::from_secs(1),
+        );
+        assert_eq!(names(&dashboard), ["cargo", "Firefox", "fish"]);
+
+        dashboard.handle_key(key(KeyCode::Char('s')));
+        assert_eq!(dashboard.sort, SortKey::Rate);
+        assert_eq!(names(&dashboard), ["Firefox", "fish", "cargo"]);
+
+        dashboard.handle_key(key(KeyCode::Char('s')));
+        assert_eq!(names(&dashboard), ["Firefox", "cargo", "fish"]);
+
+        for code in [KeyCode::Char('/'), KeyCode::Char('f'), KeyCode::Char('I')] {
+            dashboard.handle_key(key(code));
+        }
+        assert_eq!(names(&dashboard), ["Firefox", "fish"]);
+        assert_eq!(dashboard.handle_key(key(KeyCode::Char('q'))), None);
+        assert!(names(&dashboard).is_empty());
+
+        dashboard.handle_key(key(KeyCode::Esc));
+        assert!(!dashboard.filtering);
+        assert_eq!(names(&dashboard).len(), 3);
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut dashboard = Dashboard::new(1000, 10);
+        dashboard.update(usage(&[("a", 2), ("b", 1)]), Duration::from_secs(1));
+
+        dashboard.handle_key(key(KeyCode::Down));
+        dashboard.handle_key(key(KeyCode::Down));
+        assert_eq!(dashboard.selected, 1);
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('r'))),
+            Some(Action::Reset("b".to_string()))
+        );
+
+        dashboard.handle_key(key(KeyCode::Char('k')));
+        assert_eq!(dashboard.selected, 0);
+        assert_eq!(
+            dashboard.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Some(Action::Quit)
+        );
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('q'))),
+            Some(Action::Quit)
+        );
+    }
+
+    #[test]
+    fn test_bar() {
+        assert_eq!(bar(0.0, 4), "░░░░   0%");
+        assert_eq!(bar(0.5, 4), "██░░  50%");
+        assert_eq!(bar(2.5, 4), "████ 250%");
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut dashboard = Dashboard::new(100, 10);
+        dashboard.set_source(Source::Standalone);
+        dashboard.update(
+            usage(&[("over", 150), ("under", 10)]),
+            Duration::from_secs(1),
+        );
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 16)).unwrap();
+        terminal.draw(|frame| dashboard.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("USAGE ▼"));
+        assert!(screen.contains("over"));
+        assert!(screen.contains("150%"));
+        assert!(screen.contains("Standalone sampling"));
+    }
+}
@@ -0,0 +1,368 @@
+//! The settings the service is actually running with, where each value came
+//! from, and the paths it resolved, written to the state directory on
+//! startup and after every reload for support requests.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use flate2::Crc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::paths::ResolvedPath;
+use super::report::SCHEMA_VERSION;
+use super::settings::{self, Settings, SettingsFieldChange};
+use super::version::VERSION_INFO;
+
+pub const EFFECTIVE_CONFIG_FILE: &str = "effective-config.json";
+/// Replaces credentials, such as `mqtt.password`, in the snapshot.
+pub const REDACTED: &str = "<redacted>";
+/// Environment variables that set a setting start with this.
+pub const ENV_PREFIX: &str = "DATAGUARDIAN_";
+
+/// Where a setting's value comes from. Later sources win, except that the
+/// config file wins over the environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueSource {
+    Default,
+    Env,
+    File,
+    /// Moved into range with `validation = "clamp"`.
+    Clamped,
+    CommandLine,
+}
+
+/// A path the service picked, and why.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigPath {
+    pub path: PathBuf,
+    pub source: String,
+}
+
+impl From<&ResolvedPath> for ConfigPath {
+    fn from(resolved: &ResolvedPath) -> Self {
+        Self {
+            path: resolved.path.clone(),
+            source: resolved.source.as_str().to_string(),
+        }
+    }
+}
+
+/// The config file as it was read, so a later edit shows up as a different
+/// checksum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub exists: bool,
+    /// Hex CRC-32 of the file's bytes.
+    pub crc32: Option<String>,
+    pub size_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EffectiveConfig {
+    pub schema_version: u32,
+    pub written_at: DateTime<Utc>,
+    pub version: String,
+    /// Every setting, with credentials replaced by [`REDACTED`].
+    pub settings: Value,
+    /// The source of each setting, by its dotted name.
+    pub sources: BTreeMap<String, ValueSource>,
+    pub paths: BTreeMap<String, ConfigPath>,
+    pub config_file: Option<ConfigFile>,
+}
+
+impl EffectiveConfig {
+    /// The snapshot of `settings` as loaded from `config` and the
+    /// environment. `overrides` are the changes made by command line flags
+    /// and `adjustments` those made by clamping.
+    pub fn new(
+        settings: &Settings,
+        config: Option<&ResolvedPath>,
+        overrides: &[SettingsFieldChange],
+        adjustments: &[SettingsFieldChange],
+    ) -> Self {
+        let text = config.and_then(|config| std::fs::read(&config.path).ok());
+        let file_keys = text
+            .as_deref()
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .map(file_keys)
+            .unwrap_or_default();
+        let env_keys = env_keys(std::env::vars());
+        let value = serde_json::to_value(settings).unwrap_or_default();
+        let sources = sources(&value, &file_keys, &env_keys, overrides, adjustments);
+        Self {
+            schema_version: SCHEMA_VERSION,
+            written_at: Utc::now(),
+            version: VERSION_INFO.to_string(),
+            settings: redact(String::new(), value),
+            sources,
+            paths: config
+                .map(|config| ("config".to_string(), config.into()))
+                .into_iter()
+                .collect(),
+            config_file: config.map(|config| ConfigFile {
+                path: config.path.clone(),
+                exists: text.is_some(),
+                crc32: text.as_deref().map(|bytes| format!("{:08x}", crc32(bytes))),
+                size_bytes: text.as_ref().map(|bytes| bytes.len() as u64),
+            }),
+        }
+    }
+
+    pub fn with_path(mut self, name: &str, path: &ResolvedPath) -> Self {
+        self.paths.insert(name.to_string(), path.into());
+        self
+    }
+
+    pub fn path(state_dir: &Path) -> PathBuf {
+        state_dir.join(EFFECTIVE_CONFIG_FILE)
+    }
+
+    /// Writes the snapshot to a temporary file and renames it over the old
+    /// one, so a crash never leaves a half-written snapshot behind.
+    pub fn write(&self, state_dir: &Path) -> io::Result<PathBuf> {
+        let path = Self::path(state_dir);
+        let temp = path.with_extension("json.tmp");
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::create_dir_all(state_dir)?;
+        std::fs::write(&temp, json)?;
+        std::fs::File::open(&temp)?.sync_all()?;
+        std::fs::rename(&temp, &path)?;
+        Ok(path)
+    }
+
+    pub fn read(state_dir: &Path) -> io::Result<Self> {
+        let bytes = std::fs::read(Self::path(state_dir))?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+/// The dotted names of the values set in the TOML `text`.
+fn file_keys(text: &str) -> Vec<String> {
+    let Ok(document) = text.parse::<toml_edit::DocumentMut>() else {
+        return Vec::new();
+    };
+    let mut keys = Vec::new();
+    collect_keys(String::new(), document.as_table(), &mut keys);
+    keys
+}
+
+fn collect_keys(prefix: String, table: &dyn toml_edit::TableLike, keys: &mut Vec<String>) {
+    for (key, item) in table.iter() {
+        let key = join(&prefix, key);
+        match item.as_table_like() {
+            Some(table) if !table.is_empty() => collect_keys(key, table, keys),
+            _ => keys.push(key),
+        }
+    }
+}
+
+/// The dotted names set by `DATAGUARDIAN_*` variables, which use `__` to
+/// separate nested names, as in `DATAGUARDIAN_MQTT__BROKER`.
+fn env_keys(vars: impl IntoIterator<Item = (String, String)>) -> Vec<String> {
+    vars.into_iter()
+        .filter_map(|(name, _)| {
+            let key = name.strip_prefix(ENV_PREFIX)?;
+            Some(key.to_lowercase().replace("__", "."))
+        })
+        .collect()
+}
+
+fn sources(
+    settings: &Value,
+    file_keys: &[String],
+    env_keys: &[String],
+    overrides: &[SettingsFieldChange],
+    adjustments: &[SettingsFieldChange],
+) -> BTreeMap<String, ValueSource> {
+    let fields = |changes: &[SettingsFieldChange]| -> Vec<String> {
+        changes.iter().map(|change| change.field.clone()).collect()
+    };
+    let layers = [
+        (fields(overrides), ValueSource::CommandLine),
+        (fields(adjustments), ValueSource::Clamped),
+        (file_keys.to_vec(), ValueSource::File),
+        (env_keys.to_vec(), ValueSource::Env),
+    ];
+    let mut leaves = Vec::new();
+    collect_leaves(String::new(), settings, &mut leaves);
+    leaves
+        .into_iter()
+        .map(|leaf| {
+            let source = layers
+                .iter()
+                .find(|(keys, _)| keys.iter().any(|key| overlaps(key, &leaf)))
+                .map_or(ValueSource::Default, |(_, source)| *source);
+            (leaf, source)
+        })
+        .collect()
+}
+
+/// Whether setting `key` sets `leaf`: they are the same, or one is a table
+/// holding the other.
+fn overlaps(key: &str, leaf: &str) -> bool {
+    let within = |inner: &str, outer: &str| {
+        inner
+            .strip_prefix(outer)
+            .is_some_and(|rest| rest.starts_with('.'))
+    };
+    key == leaf || within(leaf, key) || within(key, leaf)
+}
+
+fn collect_leaves(prefix: String, value: &Value, leaves: &mut Vec<String>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                collect_leaves(join(&prefix, key), value, leaves);
+            }
+        }
+        _ => leaves.push(prefix),
+    }
+}
+
+/// `value` with every credential under `field` replaced. Header names in
+/// `otel.headers` stay visible; only their values are hidden.
+fn redact(field: String, value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let value = redact(join(&field, &key), value);
+                    (key, value)
+                })
+                .collect(),
+        ),
+        Value::Null => Value::Null,
+        _ if settings::is_secret_field(&field) => Value::String(REDACTED.to_string()),
+        value => value,
+    }
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    match prefix {
+        "" => key.to_string(),
+        _ => format!("{}.{}", prefix, key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::PathSource;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sources() {
+        let text = r#"
+            data_limit = 2097152
+            [mqtt]
+            broker = "tcp://broker:1883"
+            password = "hunter2"
+            [disk_limits]
+            sda1 = 500
+            [otel.headers]
+            authorization = "Bearer abc"
+        "#;
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, text).unwrap();
+        let settings = Settings {
+            check_interval_seconds: 60,
+            log_level: "debug".to_string(),
+            ..Settings::from_file(&config_path).unwrap()
+        };
+        let env = env_keys([
+            (
+                "DATAGUARDIAN_CHECK_INTERVAL_SECONDS".to_string(),
+                "60".to_string(),
+            ),
+            (
+                "DATAGUARDIAN_MQTT__BROKER".to_string(),
+                "ignored".to_string(),
+            ),
+            ("HOME".to_string(), "/root".to_string()),
+        ]);
+        assert_eq!(env, ["check_interval_seconds", "mqtt.broker"]);
+        let change = |field: &str| SettingsFieldChange {
+            field: field.to_string(),
+            old: Value::Null,
+            new: Value::Null,
+        };
+
+        let value = serde_json::to_value(&settings).unwrap();
+        let sources = sources(
+            &value,
+            &file_keys(text),
+            &env,
+            &[change("log_level")],
+            &[change("history_days")],
+        );
+        assert_eq!(sources["data_limit"], ValueSource::File);
+        assert_eq!(sources["mqtt.broker"], ValueSource::File);
+        assert_eq!(sources["mqtt.password"], ValueSource::File);
+        assert_eq!(sources["disk_limits.sda1"], ValueSource::File);
+        assert_eq!(sources["otel.headers.authorization"], ValueSource::File);
+        assert_eq!(sources["check_interval_seconds"], ValueSource::Env);
+        assert_eq!(sources["log_level"], ValueSource::CommandLine);
+        assert_eq!(sources["history_days"], ValueSource::Clamped);
+        assert_eq!(sources["mqtt.topic_prefix"], ValueSource::Default);
+        assert_eq!(sources["interface_limits"], ValueSource::Default);
+        assert!(!sources.contains_key("mqtt"));
+
+        let redacted = redact(String::new(), value);
+        assert_eq!(redacted["mqtt"]["password"], REDACTED);
+        assert_eq!(redacted["mqtt"]["broker"], "tcp://broker:1883");
+        assert_eq!(redacted["otel"]["headers"]["authorization"], REDACTED);
+        assert_eq!(redacted["influx"]["token"], Value::Null);
+        assert_eq!(redacted["data_limit"], 2097152);
+        assert!(!redacted.to_string().contains("hunter2"));
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "[agent]\ntoken = \"s3cret\"\n").unwrap();
+        let config = ResolvedPath {
+            path: config_path.clone(),
+            source: PathSource::Flag,
+        };
+        let settings = Settings::from_file(&config_path).unwrap();
+        let state_dir = ResolvedPath {
+            path: dir.path().join("state"),
+            source: PathSource::Env,
+        };
+
+        let snapshot = EffectiveConfig::new(&settings, Some(&config), &[], &[])
+            .with_path("state_dir", &state_dir);
+        let path = snapshot.write(&state_dir.path).unwrap();
+        assert_eq!(path, state_dir.path.join(EFFECTIVE_CONFIG_FILE));
+        assert!(!path.with_extension("json.tmp").exists());
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("s3cret"));
+
+        let read = EffectiveConfig::read(&state_dir.path).unwrap();
+        assert_eq!(read, snapshot);
+        assert_eq!(read.sources["agent.token"], ValueSource::File);
+        assert_eq!(read.paths["state_dir"].source, "environment");
+        assert_eq!(read.paths["config"].path, config_path);
+        let file = read.config_file.unwrap();
+        assert!(file.exists);
+        assert_eq!(file.size_bytes, Some(25));
+        assert_eq!(file.crc32.as_deref().map(str::len), Some(8));
+
+        std::fs::write(&config_path, "").unwrap();
+        let changed = EffectiveConfig::new(&Settings::default(), Some(&config), &[], &[]);
+        assert_ne!(changed.config_file.unwrap().crc32, file.crc32);
+    }
+}
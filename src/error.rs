@@ -0,0 +1,51 @@
+use thiserror::Error;
+
+use super::compression::CompressionError;
+#[cfg(feature = "monitor")]
+use super::monitor::MonitorError;
+use super::notification::NotificationError;
+use super::persistence::PersistenceError;
+use super::settings::SettingsError;
+
+#[derive(Error, Debug)]
+pub enum DataGuardianError {
+    #[error("{0}")]
+    Notification(#[from] NotificationError),
+    #[error("{0}")]
+    Compression(#[from] CompressionError),
+    #[error("{0}")]
+    Settings(#[from] SettingsError),
+    #[cfg(feature = "monitor")]
+    #[error("{0}")]
+    Monitor(#[from] MonitorError),
+    #[error("{0}")]
+    Persistence(#[from] PersistenceError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+
+    #[test]
+    fn test_conversions_keep_source_and_text() {
+        let error = DataGuardianError::from(NotificationError::Cooldown);
+        assert_eq!(error.to_string(), "Notification in cooldown");
+        assert!(
+            error
+                .source()
+                .is_some_and(|source| source.is::<NotificationError>())
+        );
+
+        let error = DataGuardianError::from(CompressionError::InvalidLevel(10));
+        assert_eq!(error.to_string(), "Invalid compression level: 10");
+        assert!(error.source().is_some());
+
+        let error = DataGuardianError::from(SettingsError::InvalidDataLimit(1, 1024));
+        assert_eq!(error.to_string(), "Invalid data limit: 1 (min: 1024)");
+        assert!(matches!(error, DataGuardianError::Settings(_)));
+    }
+}
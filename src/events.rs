@@ -0,0 +1,188 @@
+//! Live events from the running service, streamed to `dg follow` over the
+//! control socket.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use super::alerts_log::AlertLogEntry;
+use super::filter;
+
+/// Events a subscriber may fall behind by before it is disconnected.
+pub const EVENT_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Event {
+    /// Bytes each app used since the previous scan, for apps that used any.
+    Delta {
+        time: DateTime<Utc>,
+        apps: BTreeMap<String, u64>,
+    },
+    /// An alert, once every channel has had its try.
+    Alert(AlertLogEntry),
+    Save {
+        time: DateTime<Utc>,
+        size_bytes: u64,
+        duration_ms: f64,
+    },
+    /// A reload that changed the settings named in `fields`.
+    Reload {
+        time: DateTime<Utc>,
+        fields: Vec<String>,
+    },
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::Delta { .. } => EventKind::Deltas,
+            Self::Alert(_) => EventKind::Alerts,
+            Self::Save { .. } => EventKind::Saves,
+            Self::Reload { .. } => EventKind::Reloads,
+        }
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        match self {
+            Self::Delta { time, .. } | Self::Save { time, .. } | Self::Reload { time, .. } => *time,
+            Self::Alert(entry) => entry.time,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Deltas,
+    Alerts,
+    Saves,
+    Reloads,
+}
+
+/// The events one subscriber asked for.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventFilter {
+    /// Every kind when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub only: Vec<EventKind>,
+    /// An app name, in which `*` stands for any run of characters. Events
+    /// that are not about an app, such as saves, are left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+}
+
+impl EventFilter {
+    /// `event` as this subscriber should see it, with deltas trimmed to the
+    /// matching apps, or `None` when nothing is left.
+    pub fn apply(&self, event: &Event) -> Option<Event> {
+        if !self.only.is_empty() && !self.only.contains(&event.kind()) {
+            return None;
+        }
+        let Some(pattern) = &self.app else {
+            return Some(event.clone());
+        };
+        match event {
+            Event::Delta { time, apps } => {
+                let apps: BTreeMap<_, _> = apps
+                    .iter()
+                    .filter(|(app, _)| filter::pattern_matches(pattern, app))
+                    .map(|(app, bytes)| (app.clone(), *bytes))
+                    .collect();
+                (!apps.is_empty()).then_some(Event::Delta { time: *time, apps })
+            }
+            Event::Alert(entry) => {
+                filter::pattern_matches(pattern, &entry.app).then(|| event.clone())
+            }
+            Event::Save { .. } | Event::Reload { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{NotificationOutcome, Severity};
+    use crate::notification::AlertKind;
+
+    fn alert(app: &str) -> Event {
+        Event::Alert(AlertLogEntry {
+            time: "2025-06-18T12:00:00Z".parse().unwrap(),
+            app: app.to_string(),
+            kind: AlertKind::Exceeded,
+            severity: Severity::Warning,
+            usage: Some(2048),
+            limit: Some(1024),
+            instance: None,
+            outcome: NotificationOutcome::Sent,
+            channels: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn test_filter() {
+        let time = "2025-06-18T12:00:00Z".parse().unwrap();
+        let delta = Event::Delta {
+            time,
+            apps: BTreeMap::from([
+                ("chrome".to_string(), 300),
+                ("Chromium".to_string(), 200),
+                ("curl".to_string(), 100),
+            ]),
+        };
+        let save = Event::Save {
+            time,
+            size_bytes: 512,
+            duration_ms: 1.5,
+        };
+
+        let everything = EventFilter::default();
+        assert_eq!(everything.apply(&save), Some(save.clone()));
+        assert_eq!(everything.apply(&delta), Some(delta.clone()));
+
+        let alerts = EventFilter {
+            only: vec![EventKind::Alerts],
+            app: None,
+        };
+        assert_eq!(alerts.apply(&delta), None);
+        assert_eq!(alerts.apply(&alert("curl")), Some(alert("curl")));
+
+        let chrome = EventFilter {
+            only: Vec::new(),
+            app: Some("chrom*".to_string()),
+        };
+        assert_eq!(
+            chrome.apply(&delta),
+            Some(Event::Delta {
+                time,
+                apps: BTreeMap::from([("chrome".to_string(), 300), ("Chromium".to_string(), 200)]),
+            })
+        );
+        assert_eq!(chrome.apply(&alert("curl")), None);
+        assert_eq!(chrome.apply(&save), None);
+        let curl_delta = Event::Delta {
+            time,
+            apps: BTreeMap::from([("curl".to_string(), 100)]),
+        };
+        assert_eq!(chrome.apply(&curl_delta), None);
+    }
+
+    #[test]
+    fn test_event_format() {
+        let line = serde_json::to_string(&alert("firefox")).unwrap();
+        assert_eq!(
+            line,
+            r#"{"type":"alert","time":"2025-06-18T12:00:00Z","app":"firefox","kind":"exceeded","severity":"warning","usage":2048,"limit":1024,"status":"sent","channels":[]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Event>(&line).unwrap(),
+            alert("firefox")
+        );
+
+        let filter: EventFilter = serde_json::from_str(r#"{"only":["alerts","saves"]}"#).unwrap();
+        assert_eq!(filter.only, [EventKind::Alerts, EventKind::Saves]);
+        assert_eq!(filter.app, None);
+    }
+}
@@ -0,0 +1,201 @@
+use std::ffi::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use serde_json::Value;
+
+use super::compression::{self, CompressionError};
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DgStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidUtf8 = 2,
+    InvalidJson = 3,
+    InvalidData = 4,
+    Panic = 5,
+}
+
+impl From<CompressionError> for DgStatus {
+    fn from(error: CompressionError) -> Self {
+        match error {
+            CompressionError::Serialization(_) => Self::InvalidJson,
+            CompressionError::Io(_)
+            | CompressionError::InvalidLevel(_)
+            | CompressionError::UnknownDictionary(_)
+            | CompressionError::DictionaryTooLarge(_)
+            | CompressionError::ChecksumMismatch => Self::InvalidData,
+        }
+    }
+}
+
+/// Compresses a JSON document into the gzip format of `usage.dat`.
+///
+/// On success `*out` points to `*out_len` bytes that must be released with
+/// `dg_free`. On error `*out` is null and `*out_len` is zero.
+///
+/// # Safety
+///
+/// `json` must point to `len` readable bytes, and `out` and `out_len` must be
+/// valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dg_compress_usage_json(
+    json: *const c_char,
+    len: usize,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+) -> DgStatus {
+    call(json.cast(), len, out, out_len, |input| {
+        let text = std::str::from_utf8(input).map_err(|_| DgStatus::InvalidUtf8)?;
+        let value: Value = serde_json::from_str(text).map_err(|_| DgStatus::InvalidJson)?;
+        Ok(compression::compress_json(&value, input.len())?)
+    })
+}
+
+/// Decompresses the contents of `usage.dat` into a UTF-8 JSON document.
+///
+/// The output is not NUL-terminated. On success `*out` points to `*out_len`
+/// bytes that must be released with `dg_free`. On error `*out` is null and
+/// `*out_len` is zero.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, and `out` and `out_len` must be
+/// valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dg_decompress_usage_json(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+) -> DgStatus {
+    call(data, len, out, out_len, |input| {
+        let value: Value = compression::decompress_json(input)?;
+        serde_json::to_vec(&value).map_err(|_| DgStatus::InvalidJson)
+    })
+}
+
+/// Releases a buffer returned by `dg_compress_usage_json` or
+/// `dg_decompress_usage_json`. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `ptr` and `len` must come from one of those functions, and the buffer
+/// must not be used or freed again afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dg_free(ptr: *mut u8, len: usize) {
+    if ptr.is_null() {
+        return;
+    }
+    // SAFETY: the caller passes back a pointer and length from `into_raw`.
+    drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)) });
+}
+
+fn call(
+    input: *const u8,
+    len: usize,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+    f: impl FnOnce(&[u8]) -> Result<Vec<u8>, DgStatus>,
+) -> DgStatus {
+    if out.is_null() || out_len.is_null() {
+        return DgStatus::NullPointer;
+    }
+    // SAFETY: both pointers were checked for null and the caller guarantees
+    // they are valid for writes.
+    unsafe {
+        *out = ptr::null_mut();
+        *out_len = 0;
+    }
+    if input.is_null() {
+        return DgStatus::NullPointer;
+    }
+    // SAFETY: the caller guarantees `input` points to `len` readable bytes.
+    let input = unsafe { slice::from_raw_parts(input, len) };
+
+    let output = match panic::catch_unwind(AssertUnwindSafe(|| f(input))) {
+        Ok(Ok(output)) => output,
+        Ok(Err(status)) => return status,
+        Err(_) => return DgStatus::Panic,
+    };
+    let output = Box::into_raw(output.into_boxed_slice());
+    // SAFETY: see above.
+    unsafe {
+        *out_len = output.len();
+        *out = output.cast();
+    }
+    DgStatus::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run<T>(
+        f: unsafe extern "C" fn(*const T, usize, *mut *mut u8, *mut usize) -> DgStatus,
+        input: &[u8],
+    ) -> Result<Vec<u8>, DgStatus> {
+        let mut out = ptr::null_mut();
+        let mut out_len = 0;
+        let status = unsafe { f(input.as_ptr().cast(), input.len(), &mut out, &mut out_len) };
+        if status != DgStatus::Ok {
+            assert!(out.is_null());
+            assert_eq!(out_len, 0);
+            return Err(status);
+        }
+        let output = unsafe { slice::from_raw_parts(out, out_len) }.to_vec();
+        unsafe { dg_free(out, out_len) };
+        Ok(output)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let json = br#"{"firefox":1024,"curl":7}"#;
+        let compressed = run(dg_compress_usage_json, json).unwrap();
+        assert_eq!(
+            compression::decompress_usage_data(&compressed).unwrap(),
+            [("firefox".to_string(), 1024), ("curl".to_string(), 7)].into()
+        );
+
+        let decompressed = run(dg_decompress_usage_json, &compressed).unwrap();
+        let value: Value = serde_json::from_slice(&decompressed).unwrap();
+        assert_eq!(value, serde_json::from_slice::<Value>(json).unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            run(dg_compress_usage_json, b"\xff\xfe"),
+            Err(DgStatus::InvalidUtf8)
+        );
+        assert_eq!(
+            run(dg_compress_usage_json, b"{not json"),
+            Err(DgStatus::InvalidJson)
+        );
+        assert_eq!(
+            run(dg_decompress_usage_json, b"not gzip"),
+            Err(DgStatus::InvalidData)
+        );
+
+        let mut out = ptr::null_mut();
+        let mut out_len = 0;
+        let status = unsafe { dg_compress_usage_json(ptr::null(), 0, &mut out, &mut out_len) };
+        assert_eq!(status, DgStatus::NullPointer);
+        let status =
+            unsafe { dg_compress_usage_json(c"{}".as_ptr(), 2, ptr::null_mut(), &mut out_len) };
+        assert_eq!(status, DgStatus::NullPointer);
+        unsafe { dg_free(ptr::null_mut(), 0) };
+    }
+
+    #[test]
+    fn test_panic_is_caught() {
+        let mut out = ptr::null_mut();
+        let mut out_len = 0;
+        let status = call(b"{}".as_ptr(), 2, &mut out, &mut out_len, |_| {
+            panic!("boom")
+        });
+        assert_eq!(status, DgStatus::Panic);
+        assert!(out.is_null());
+    }
+}
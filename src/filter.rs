@@ -0,0 +1,246 @@
+//! Which processes count, by the account that runs them.
+
+use thiserror::Error;
+
+/// UIDs below this belong to the system on most Unix distributions.
+pub const FIRST_USER_UID: u32 = 1000;
+
+/// SYSTEM, LOCAL SERVICE and NETWORK SERVICE.
+const SYSTEM_SIDS: [&str; 3] = ["S-1-5-18", "S-1-5-19", "S-1-5-20"];
+/// Per-service SIDs start with this.
+const SERVICE_SID_PREFIX: &str = "S-1-5-80-";
+
+#[derive(Error, Debug)]
+pub enum FilterError {
+    #[error("Unknown user: {0}")]
+    UnknownUser(String),
+    #[error("Failed to look up user {0}: {1}")]
+    Lookup(String, String),
+}
+
+/// The account a process runs as: a UID on Unix, a SID such as `S-1-5-18`
+/// on Windows.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Owner {
+    Uid(u32),
+    Sid(String),
+}
+
+impl Owner {
+    /// Root and service accounts on Unix, SYSTEM and service accounts on
+    /// Windows.
+    pub fn is_system(&self) -> bool {
+        match self {
+            Self::Uid(uid) => *uid < FIRST_USER_UID,
+            Self::Sid(sid) => {
+                SYSTEM_SIDS.contains(&sid.as_str()) || sid.starts_with(SERVICE_SID_PREFIX)
+            }
+        }
+    }
+
+    /// Resolves a user name, a numeric UID or a SID to an owner.
+    pub fn resolve(user: &str) -> Result<Self, FilterError> {
+        if let Ok(uid) = user.parse() {
+            return Ok(Self::Uid(uid));
+        }
+        if user.starts_with("S-1-") {
+            return Ok(Self::Sid(user.to_string()));
+        }
+        lookup(user)
+    }
+}
+
+#[cfg(unix)]
+fn lookup(user: &str) -> Result<Owner, FilterError> {
+    match nix::unistd::User::from_name(user) {
+        Ok(Some(found)) => Ok(Owner::Uid(found.uid.as_raw())),
+        Ok(None) => Err(FilterError::UnknownUser(user.to_string())),
+        Err(e) => Err(FilterError::Lookup(user.to_string(), e.to_string())),
+    }
+}
+
+#[cfg(all(windows, feature = "monitor"))]
+fn lookup(user: &str) -> Result<Owner, FilterError> {
+    sysinfo::Users::new_with_refreshed_list()
+        .list()
+        .iter()
+        .find(|found| found.name().eq_ignore_ascii_case(user))
+        .map(|found| Owner::Sid(found.id().to_string()))
+        .ok_or_else(|| FilterError::UnknownUser(user.to_string()))
+}
+
+#[cfg(not(any(unix, all(windows, feature = "monitor"))))]
+fn lookup(user: &str) -> Result<Owner, FilterError> {
+    Err(FilterError::UnknownUser(user.to_string()))
+}
+
+/// Decides whether a process is monitored, checking in this order:
+///
+/// 1. An owner in `ignored_users` is skipped.
+/// 2. An owner in `watched_users` is monitored.
+/// 3. When `watched_users` is not empty, everyone else is skipped.
+/// 4. With `ignore_system_processes`, system owners are skipped.
+/// 5. Everything else is monitored.
+///
+/// A process whose owner cannot be read matches no list and is not a
+/// system process.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterChain {
+    ignore_system: bool,
+    ignored: Vec<Owner>,
+    watched: Vec<Owner>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_ignore_system(mut self, ignore_system: bool) -> Self {
+        self.ignore_system = ignore_system;
+        self
+    }
+
+    pub fn with_ignored(mut self, owners: Vec<Owner>) -> Self {
+        self.ignored = owners;
+        self
+    }
+
+    pub fn with_watched(mut self, owners: Vec<Owner>) -> Self {
+        self.watched = owners;
+        self
+    }
+
+    /// Resolves the user names in the settings, failing on the first one
+    /// that does not exist.
+    pub fn resolve(
+        ignore_system: bool,
+        ignored_users: &[String],
+        watched_users: &[String],
+    ) -> Result<Self, FilterError> {
+        let resolve = |users: &[String]| -> Result<Vec<Owner>, FilterError> {
+            users.iter().map(|user| Owner::resolve(user)).collect()
+        };
+        Ok(Self::new()
+            .with_ignore_system(ignore_system)
+            .with_ignored(resolve(ignored_users)?)
+            .with_watched(resolve(watched_users)?))
+    }
+
+    /// Whether every process is monitored, so the owner need not be read.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn allows(&self, owner: Option<&Owner>) -> bool {
+        let listed = |owners: &[Owner]| owner.is_some_and(|owner| owners.contains(owner));
+        if listed(&self.ignored) {
+            return false;
+        }
+        if listed(&self.watched) {
+            return true;
+        }
+        if !self.watched.is_empty() {
+            return false;
+        }
+        !(self.ignore_system && owner.is_some_and(Owner::is_system))
+    }
+}
+
+/// Case-insensitive match of `text` against `pattern`, in which `*` stands
+/// for any run of characters.
+pub fn pattern_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT: Owner = Owner::Uid(0);
+    const DAEMON: Owner = Owner::Uid(1);
+    const ALICE: Owner = Owner::Uid(1000);
+    const BOB: Owner = Owner::Uid(1001);
+
+    /// Which of root, daemon, alice, bob and an unknown owner `chain` allows.
+    fn allowed(chain: &FilterChain) -> [bool; 5] {
+        [
+            chain.allows(Some(&ROOT)),
+            chain.allows(Some(&DAEMON)),
+            chain.allows(Some(&ALICE)),
+            chain.allows(Some(&BOB)),
+            chain.allows(None),
+        ]
+    }
+
+    #[test]
+    fn test_is_system() {
+        assert!(ROOT.is_system());
+        assert!(Owner::Uid(FIRST_USER_UID - 1).is_system());
+        assert!(!ALICE.is_system());
+        assert!(Owner::Sid("S-1-5-18".to_string()).is_system());
+        assert!(Owner::Sid("S-1-5-80-123-456".to_string()).is_system());
+        assert!(!Owner::Sid("S-1-5-21-1-2-3-1001".to_string()).is_system());
+    }
+
+    #[test]
+    fn test_filter_precedence() {
+        assert!(FilterChain::new().is_empty());
+        assert_eq!(allowed(&FilterChain::new()), [true; 5]);
+
+        let system = FilterChain::new().with_ignore_system(true);
+        assert_eq!(allowed(&system), [false, false, true, true, true]);
+
+        let ignored = FilterChain::new().with_ignored(vec![BOB]);
+        assert_eq!(allowed(&ignored), [true, true, true, false, true]);
+
+        // Watching a user skips everyone else, including unknown owners.
+        let watched = FilterChain::new().with_watched(vec![ALICE]);
+        assert_eq!(allowed(&watched), [false, false, true, false, false]);
+
+        // A watched system user is kept despite ignore_system_processes.
+        let watched_root = system.clone().with_watched(vec![ROOT, ALICE]);
+        assert_eq!(allowed(&watched_root), [true, false, true, false, false]);
+
+        // Ignoring wins over watching.
+        let both = watched_root.with_ignored(vec![ALICE]);
+        assert_eq!(allowed(&both), [true, false, false, false, false]);
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(Owner::resolve("1001").unwrap(), BOB);
+        assert_eq!(
+            Owner::resolve("S-1-5-18").unwrap(),
+            Owner::Sid("S-1-5-18".to_string())
+        );
+        #[cfg(unix)]
+        assert_eq!(Owner::resolve("root").unwrap(), ROOT);
+        assert!(matches!(
+            Owner::resolve("no-such-user-dg"),
+            Err(FilterError::UnknownUser(_))
+        ));
+
+        let chain = FilterChain::resolve(true, &["1001".to_string()], &[]).unwrap();
+        assert_eq!(allowed(&chain), [false, false, true, false, true]);
+        assert!(FilterChain::resolve(false, &[], &["no-such-user-dg".to_string()]).is_err());
+    }
+}
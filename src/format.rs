@@ -0,0 +1,246 @@
+//! Human-readable byte counts for tables, notifications and logs. JSON,
+//! CSV and metrics keep raw byte counts.
+
+use serde::{Deserialize, Serialize};
+
+const SI_UNITS: [&str; 7] = ["B", "KB", "MB", "GB", "TB", "PB", "EB"];
+const BINARY_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Whether byte counts are shown in powers of 1000 or 1024.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BytesStyle {
+    /// KB, MB, GB, each 1000 times the last.
+    #[default]
+    Si,
+    /// KiB, MiB, GiB, each 1024 times the last.
+    Binary,
+}
+
+impl BytesStyle {
+    fn base(self) -> u128 {
+        match self {
+            Self::Si => 1000,
+            Self::Binary => 1024,
+        }
+    }
+
+    fn units(self) -> &'static [&'static str; 7] {
+        match self {
+            Self::Si => &SI_UNITS,
+            Self::Binary => &BINARY_UNITS,
+        }
+    }
+}
+
+/// Separators used when writing numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Separators<'a> {
+    pub decimal: &'a str,
+    pub group: &'a str,
+}
+
+impl Default for Separators<'_> {
+    fn default() -> Self {
+        Self {
+            decimal: ".",
+            group: ",",
+        }
+    }
+}
+
+/// Formats `bytes` with English separators, such as "1.5 GB" or
+/// "1,023 B".
+pub fn format_bytes(bytes: u64, style: BytesStyle) -> String {
+    format_bytes_with(bytes, style, Separators::default())
+}
+
+/// Formats `bytes` in the largest unit that keeps the value at 1 or more,
+/// rounded half up to one decimal. A value that rounds up to the next unit
+/// is shown in that unit, so 999,950 bytes is "1.0 MB" rather than
+/// "1000.0 KB".
+pub fn format_bytes_with(bytes: u64, style: BytesStyle, separators: Separators<'_>) -> String {
+    let base = style.base();
+    let units = style.units();
+    if u128::from(bytes) < base {
+        return format!(
+            "{} {}",
+            group_digits(bytes.into(), separators.group),
+            units[0]
+        );
+    }
+
+    let mut unit = 1;
+    let mut tenths = round_tenths(bytes, base);
+    while tenths >= base * 10 && unit < units.len() - 1 {
+        unit += 1;
+        tenths = round_tenths(bytes, base.pow(unit as u32));
+    }
+    format!(
+        "{}{}{} {}",
+        group_digits(tenths / 10, separators.group),
+        separators.decimal,
+        tenths % 10,
+        units[unit]
+    )
+}
+
+/// Parses a byte count such as `2GB`, `1.5 GiB` or `1048576`. Units are
+/// case-insensitive, and a bare `K`, `M` or `G` means the SI unit.
+pub fn parse_bytes(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let unit = unit.trim().to_ascii_lowercase();
+    let (unit, style) = match unit.strip_suffix("ib") {
+        Some(unit) => (unit, BytesStyle::Binary),
+        None => (unit.strip_suffix('b').unwrap_or(&unit), BytesStyle::Si),
+    };
+    let power = ["", "k", "m", "g", "t", "p", "e"]
+        .iter()
+        .position(|prefix| *prefix == unit)?;
+    if power == 0 && style == BytesStyle::Binary {
+        return None;
+    }
+    if !number.contains('.') {
+        let multiplier = u64::try_from(style.base().pow(power as u32)).ok()?;
+        return number.parse::<u64>().ok()?.checked_mul(multiplier);
+    }
+    let bytes = number.parse::<f64>().ok()? * (style.base() as f64).powi(power as i32);
+    (bytes < u64::MAX as f64).then(|| bytes.round() as u64)
+}
+
+/// `bytes / divisor` in tenths, rounded half up.
+fn round_tenths(bytes: u64, divisor: u128) -> u128 {
+    (u128::from(bytes) * 20 / divisor).div_ceil(2)
+}
+
+fn group_digits(value: u128, separator: &str) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push_str(separator);
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0, BytesStyle::Si), "0 B");
+        assert_eq!(format_bytes(999, BytesStyle::Si), "999 B");
+        assert_eq!(format_bytes(1000, BytesStyle::Si), "1.0 KB");
+        assert_eq!(format_bytes(1023, BytesStyle::Binary), "1,023 B");
+        assert_eq!(format_bytes(1024, BytesStyle::Binary), "1.0 KiB");
+        assert_eq!(format_bytes(1_500_000, BytesStyle::Si), "1.5 MB");
+        assert_eq!(format_bytes(1 << 30, BytesStyle::Binary), "1.0 GiB");
+        assert_eq!(format_bytes(u64::MAX, BytesStyle::Si), "18.4 EB");
+        assert_eq!(format_bytes(u64::MAX, BytesStyle::Binary), "16.0 EiB");
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(format_bytes(1_049, BytesStyle::Si), "1.0 KB");
+        assert_eq!(format_bytes(1_050, BytesStyle::Si), "1.1 KB");
+        assert_eq!(format_bytes(999_949, BytesStyle::Si), "999.9 KB");
+        assert_eq!(format_bytes(999_950, BytesStyle::Si), "1.0 MB");
+        assert_eq!(format_bytes(1023 * 1024, BytesStyle::Binary), "1,023.0 KiB");
+        assert_eq!(format_bytes(1024 * 1024 - 1, BytesStyle::Binary), "1.0 MiB");
+    }
+
+    #[test]
+    fn test_separators() {
+        let german = Separators {
+            decimal: ",",
+            group: ".",
+        };
+        assert_eq!(
+            format_bytes_with(2_340_000_000, BytesStyle::Si, german),
+            "2,3 GB"
+        );
+        assert_eq!(
+            format_bytes_with(1000, BytesStyle::Binary, german),
+            "1.000 B"
+        );
+        assert_eq!(
+            format_bytes_with(1000 * 1024, BytesStyle::Binary, german),
+            "1.000,0 KiB"
+        );
+        assert_eq!(
+            group_digits(1_234_567, "\u{202f}"),
+            "1\u{202f}234\u{202f}567"
+        );
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("1048576"), Some(1_048_576));
+        assert_eq!(parse_bytes("2GB"), Some(2_000_000_000));
+        assert_eq!(parse_bytes("2 gb"), Some(2_000_000_000));
+        assert_eq!(parse_bytes("1.5 GiB"), Some(1_610_612_736));
+        assert_eq!(parse_bytes("500k"), Some(500_000));
+        assert_eq!(parse_bytes("16 EiB"), None);
+        assert_eq!(parse_bytes("1 iB"), None);
+        assert_eq!(parse_bytes("GB"), None);
+        assert_eq!(parse_bytes("2 apples"), None);
+    }
+
+    /// Byte counts on either side of a unit boundary, and near the top of
+    /// the exabyte range.
+    fn boundary_bytes() -> impl Strategy<Value = u64> {
+        prop_oneof![
+            (1u32..=6, any::<bool>(), -3i128..=3).prop_map(|(power, binary, offset)| {
+                let base: i128 = if binary { 1024 } else { 1000 };
+                (base.pow(power) + offset) as u64
+            }),
+            0u64..2048,
+            u64::MAX - 1024..=u64::MAX,
+            any::<u64>(),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_value_stays_below_next_unit(bytes in boundary_bytes(), binary: bool) {
+            let style = if binary { BytesStyle::Binary } else { BytesStyle::Si };
+            let formatted = format_bytes_with(
+                bytes,
+                style,
+                Separators { decimal: ".", group: "" },
+            );
+            let (value, unit) = formatted.split_once(' ').unwrap();
+            let value: f64 = value.parse().unwrap();
+            let index = style.units().iter().position(|u| *u == unit).unwrap();
+            prop_assert!(value < style.base() as f64);
+            prop_assert!(index == 0 || value >= 1.0);
+            let scale = (style.base() as f64).powi(index as i32);
+            prop_assert!((value * scale - bytes as f64).abs() <= 0.05 * scale + 0.5);
+        }
+
+        #[test]
+        fn test_formatted_value_never_decreases(bytes in boundary_bytes(), binary: bool) {
+            let style = if binary { BytesStyle::Binary } else { BytesStyle::Si };
+            let scaled = |bytes: u64| {
+                let formatted = format_bytes_with(
+                    bytes,
+                    style,
+                    Separators { decimal: ".", group: "" },
+                );
+                let (value, unit) = formatted.split_once(' ').unwrap();
+                let index = style.units().iter().position(|u| *u == unit).unwrap();
+                value.parse::<f64>().unwrap() * (style.base() as f64).powi(index as i32)
+            };
+            let next = bytes.saturating_add(1);
+            prop_assert!(scaled(next) >= scaled(bytes));
+        }
+    }
+}
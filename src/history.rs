@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_HISTORY_CAPACITY: usize = 1000;
+pub const DEFAULT_HISTORY_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "error", rename_all = "lowercase")]
+pub enum NotificationOutcome {
+    Sent,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationRecord {
+    pub app: String,
+    pub severity: Severity,
+    pub time: DateTime<Utc>,
+    pub outcome: NotificationOutcome,
+}
+
+#[derive(Debug, Clone)]
+pub struct NotificationHistory {
+    records: VecDeque<NotificationRecord>,
+    capacity: usize,
+    retention: Duration,
+}
+
+impl Default for NotificationHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY, DEFAULT_HISTORY_RETENTION)
+    }
+}
+
+impl NotificationHistory {
+    pub fn new(capacity: usize, retention: Duration) -> Self {
+        Self {
+            records: VecDeque::with_capacity(capacity.min(DEFAULT_HISTORY_CAPACITY)),
+            capacity,
+            retention,
+        }
+    }
+
+    pub fn push(&mut self, record: NotificationRecord) {
+        while self.records.len() >= self.capacity && self.capacity > 0 {
+            self.records.pop_front();
+        }
+        if self.capacity > 0 {
+            self.records.push_back(record);
+        }
+        self.prune(Utc::now());
+    }
+
+    pub fn extend(&mut self, records: impl IntoIterator<Item = NotificationRecord>) {
+        for record in records {
+            self.push(record);
+        }
+    }
+
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        let Ok(retention) = chrono::Duration::from_std(self.retention) else {
+            return;
+        };
+        let cutoff = now - retention;
+        while self.records.front().is_some_and(|r| r.time < cutoff) {
+            self.records.pop_front();
+        }
+    }
+
+    pub fn since(&self, cutoff: DateTime<Utc>) -> Vec<NotificationRecord> {
+        self.records
+            .iter()
+            .filter(|r| r.time >= cutoff)
+            .cloned()
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> Vec<NotificationRecord> {
+        self.records.iter().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(app: &str, time: DateTime<Utc>) -> NotificationRecord {
+        NotificationRecord {
+            app: app.to_string(),
+            severity: Severity::Warning,
+            time,
+            outcome: NotificationOutcome::Sent,
+        }
+    }
+
+    #[test]
+    fn test_history_capacity() {
+        let mut history = NotificationHistory::new(3, DEFAULT_HISTORY_RETENTION);
+        let now = Utc::now();
+        for i in 0..5 {
+            history.push(record(&format!("app_{}", i), now));
+        }
+
+        let apps: Vec<_> = history.snapshot().into_iter().map(|r| r.app).collect();
+        assert_eq!(apps, ["app_2", "app_3", "app_4"]);
+    }
+
+    #[test]
+    fn test_history_retention() {
+        let mut history = NotificationHistory::new(10, Duration::from_secs(60));
+        let now = Utc::now();
+        history.push(record("old", now - chrono::Duration::seconds(120)));
+        history.push(record("new", now));
+
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.snapshot()[0].app, "new");
+    }
+
+    #[test]
+    fn test_history_since() {
+        let mut history = NotificationHistory::default();
+        let now = Utc::now();
+        history.push(record("earlier", now - chrono::Duration::hours(2)));
+        history.push(record("later", now));
+
+        let recent = history.since(now - chrono::Duration::hours(1));
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].app, "later");
+    }
+
+    #[test]
+    fn test_history_zero_capacity() {
+        let mut history = NotificationHistory::new(0, DEFAULT_HISTORY_RETENTION);
+        history.push(record("app", Utc::now()));
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn test_record_serialization() {
+        let mut failed = record("app", Utc::now());
+        failed.outcome = NotificationOutcome::Failed("no daemon".to_string());
+
+        let serialized = serde_json::to_string(&failed).unwrap();
+        let deserialized: NotificationRecord = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(failed, deserialized);
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::debug;
+
+pub const MAX_REQUEST_SIZE: usize = 8 * 1024;
+pub const MAX_BODY_SIZE: usize = 1024 * 1024;
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn ok(content_type: &'static str, body: String) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    pub fn json<T: serde::Serialize>(value: &T) -> Self {
+        match serde_json::to_string(value) {
+            Ok(body) => Self::ok("application/json", body),
+            Err(e) => Self::error(500, &e.to_string()),
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{}\n", message),
+        }
+    }
+
+    pub fn no_content() -> Self {
+        Self {
+            status: 204,
+            content_type: "text/plain; charset=utf-8",
+            body: String::new(),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::error(404, "Not Found")
+    }
+
+    pub fn method_not_allowed() -> Self {
+        Self::error(405, "Method Not Allowed")
+    }
+
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        );
+        writer.write_all(head.as_bytes()).await?;
+        writer.write_all(self.body.as_bytes()).await?;
+        writer.shutdown().await
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+pub async fn read_request<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Request> {
+    read_request_with_body(reader, 0).await
+}
+
+pub async fn read_request_with_body<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max_body: usize,
+) -> io::Result<Request> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+
+    tokio::time::timeout(REQUEST_TIMEOUT, async {
+        let head_len = loop {
+            if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break end + 4;
+            }
+            if buf.len() >= MAX_REQUEST_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "request too large",
+                ));
+            }
+            let n = reader.read(&mut chunk).await?;
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        };
+
+        let mut request = parse_head(&String::from_utf8_lossy(&buf[..head_len]))?;
+        let length = match request.header("content-length") {
+            Some(length) => length.trim().parse::<usize>().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid content length")
+            })?,
+            None => 0,
+        };
+        if length > max_body {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request body too large",
+            ));
+        }
+
+        let mut body = buf.split_off(head_len);
+        while body.len() < length {
+            let n = reader.read(&mut chunk).await?;
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            body.extend_from_slice(&chunk[..n]);
+        }
+        body.truncate(length);
+        request.body = body;
+        Ok(request)
+    })
+    .await
+    .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+}
+
+fn parse_head(head: &str) -> io::Result<Request> {
+    let mut lines = head.lines();
+    let mut parts = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "malformed request line",
+        ));
+    };
+
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: parse_query(query),
+        headers,
+        body: Vec::new(),
+    })
+}
+
+pub async fn serve<F>(listener: TcpListener, handler: F) -> io::Result<()>
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    serve_with_body(listener, 0, move |request| match request.method.as_str() {
+        "GET" => handler(request),
+        _ => Response::method_not_allowed(),
+    })
+    .await
+}
+
+pub async fn serve_with_body<F>(
+    listener: TcpListener,
+    max_body: usize,
+    handler: F,
+) -> io::Result<()>
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    loop {
+        let (mut stream, peer) = listener.accept().await?;
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            let response = match read_request_with_body(&mut stream, max_body).await {
+                Ok(request) => handler(&request),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    debug!(error = %e, %peer, "Rejected HTTP request");
+                    Response::error(413, &e.to_string())
+                }
+                Err(e) => {
+                    debug!(error = %e, %peer, "Failed to read HTTP request");
+                    return;
+                }
+            };
+            if let Err(e) = response.write_to(&mut stream).await {
+                debug!(error = %e, %peer, "Failed to write HTTP response");
+            }
+        });
+    }
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()));
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_request() {
+        let mut raw: &[u8] = b"GET /status?top=3&app=my%20app HTTP/1.1\r\nHost: x\r\n\r\n";
+        let request = read_request(&mut raw).await.unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/status");
+        assert_eq!(request.query["top"], "3");
+        assert_eq!(request.query["app"], "my app");
+        assert_eq!(request.header("Host"), Some("x"));
+    }
+
+    #[tokio::test]
+    async fn test_read_request_body() {
+        let raw = b"POST /collect HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+        let request = read_request_with_body(&mut &raw[..], 16).await.unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.body, b"hello");
+
+        let error = read_request_with_body(&mut &raw[..], 4).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(read_request(&mut &raw[..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_request_incomplete() {
+        let mut raw: &[u8] = b"GET / HTTP/1.1\r\n";
+        assert!(read_request(&mut raw).await.is_err());
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%2Fb"), "a/b");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+}
@@ -0,0 +1,755 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use config::{Config, File};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use super::format::{self, BytesStyle, Separators};
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+pub const ALERT_TITLE: &str = "alert.title";
+pub const ALERT_BODY: &str = "alert.body";
+pub const ALERT_BODY_USAGE: &str = "alert.body_usage";
+pub const ALERT_BODY_REPEAT_DAILY: &str = "alert.body_repeat_daily";
+pub const ALERT_BODY_REPEAT_WEEKLY: &str = "alert.body_repeat_weekly";
+pub const ALERT_BODY_REPEAT_MONTHLY: &str = "alert.body_repeat_monthly";
+pub const ALERT_TITLE_FORECAST: &str = "alert.title_forecast";
+pub const ALERT_BODY_FORECAST_DAILY: &str = "alert.body_forecast_daily";
+pub const ALERT_BODY_FORECAST_WEEKLY: &str = "alert.body_forecast_weekly";
+pub const ALERT_BODY_FORECAST_MONTHLY: &str = "alert.body_forecast_monthly";
+pub const ALERT_TITLE_ANOMALY: &str = "alert.title_anomaly";
+pub const ALERT_BODY_ANOMALY: &str = "alert.body_anomaly";
+pub const ALERT_TITLE_CPU: &str = "alert.title_cpu";
+pub const ALERT_BODY_CPU: &str = "alert.body_cpu";
+pub const ALERT_TITLE_MEMORY: &str = "alert.title_memory";
+pub const ALERT_BODY_MEMORY: &str = "alert.body_memory";
+pub const ALERT_TITLE_STARTED: &str = "alert.title_started";
+pub const ALERT_BODY_STARTED: &str = "alert.body_started";
+pub const ALERT_TITLE_SUMMARY: &str = "alert.title_summary";
+pub const ALERT_BODY_SUMMARY: &str = "alert.body_summary";
+pub const ALERT_BODY_SUMMARY_OVER: &str = "alert.body_summary_over";
+pub const ALERT_TITLE_DATA_LOSS: &str = "alert.title_data_loss";
+pub const ALERT_BODY_DATA_LOST: &str = "alert.body_data_lost";
+pub const ALERT_BODY_DATA_RESTORED: &str = "alert.body_data_restored";
+pub const ALERT_TITLE_BUDGET: &str = "alert.title_budget";
+pub const ALERT_BODY_BUDGET: &str = "alert.body_budget";
+pub const ALERT_TITLE_FAILING: &str = "alert.title_failing";
+pub const ALERT_BODY_SCAN_FAILING: &str = "alert.body_scan_failing";
+pub const ALERT_BODY_SAVE_FAILING: &str = "alert.body_save_failing";
+pub const ALERT_TITLE_CLAMPED: &str = "alert.title_clamped";
+pub const ALERT_BODY_CLAMPED: &str = "alert.body_clamped";
+pub const ALERT_TITLE_SELF_USAGE: &str = "alert.title_self_usage";
+pub const ALERT_BODY_SELF_IO: &str = "alert.body_self_io";
+pub const ALERT_BODY_SELF_STORAGE: &str = "alert.body_self_storage";
+pub const ALERT_TITLE_TEST: &str = "alert.title_test";
+pub const ALERT_BODY_TEST: &str = "alert.body_test";
+pub const ACTION_SNOOZE: &str = "action.snooze";
+pub const ACTION_SHOW_REPORT: &str = "action.show_report";
+pub const TRAY_SNOOZE_ALL: &str = "tray.snooze_all";
+pub const TRAY_OPEN_REPORT: &str = "tray.open_report";
+pub const TRAY_QUIT: &str = "tray.quit";
+pub const TRAY_NO_USAGE: &str = "tray.no_usage";
+pub const DECIMAL_SEPARATOR: &str = "number.decimal_separator";
+pub const GROUP_SEPARATOR: &str = "number.group_separator";
+
+const EN: &[(&str, &str)] = &[
+    (ALERT_TITLE, "Data Limit Exceeded"),
+    (
+        ALERT_BODY,
+        "Application '{app}' has exceeded the data threshold.",
+    ),
+    (
+        ALERT_BODY_USAGE,
+        "Application '{app}' has used {usage} of its {limit} limit.",
+    ),
+    (
+        ALERT_BODY_REPEAT_DAILY,
+        "Application '{app}' has exceeded its limit {count} days in a row ({usage} of {limit}).",
+    ),
+    (
+        ALERT_BODY_REPEAT_WEEKLY,
+        "Application '{app}' has exceeded its limit {count} weeks in a row ({usage} of {limit}).",
+    ),
+    (
+        ALERT_BODY_REPEAT_MONTHLY,
+        "Application '{app}' has exceeded its limit {count} months in a row ({usage} of {limit}).",
+    ),
+    (ALERT_TITLE_FORECAST, "Data Limit Forecast"),
+    (
+        ALERT_BODY_FORECAST_DAILY,
+        "{app} is on pace to use {projected} of its {limit} daily limit.",
+    ),
+    (
+        ALERT_BODY_FORECAST_WEEKLY,
+        "{app} is on pace to use {projected} of its {limit} weekly limit.",
+    ),
+    (
+        ALERT_BODY_FORECAST_MONTHLY,
+        "{app} is on pace to use {projected} of its {limit} monthly limit.",
+    ),
+    (ALERT_TITLE_ANOMALY, "Unusual Data Usage"),
+    (
+        ALERT_BODY_ANOMALY,
+        "'{app}' transferred {delta} since the last check, far above its usual {baseline}.",
+    ),
+    (ALERT_TITLE_CPU, "CPU Limit Exceeded"),
+    (
+        ALERT_BODY_CPU,
+        "Application '{app}' has used {usage} of CPU time, above its {limit} limit.",
+    ),
+    (ALERT_TITLE_MEMORY, "Memory Limit Exceeded"),
+    (
+        ALERT_BODY_MEMORY,
+        "Application '{app}' is using {usage} of memory, above its {limit} limit.",
+    ),
+    (ALERT_TITLE_STARTED, "Data Guardian Started"),
+    (
+        ALERT_BODY_STARTED,
+        "Monitoring {count} apps with a {limit} limit.",
+    ),
+    (ALERT_TITLE_SUMMARY, "Data Guardian Stopped"),
+    (
+        ALERT_BODY_SUMMARY,
+        "{usage} moved this session. No apps are over their limit.",
+    ),
+    (
+        ALERT_BODY_SUMMARY_OVER,
+        "{usage} moved this session. Over the limit: {apps}.",
+    ),
+    (ALERT_TITLE_DATA_LOSS, "Usage Data Damaged"),
+    (
+        ALERT_BODY_DATA_LOST,
+        "The usage data file could not be read and was moved aside. Usage history starts from zero.",
+    ),
+    (
+        ALERT_BODY_DATA_RESTORED,
+        "The usage data file could not be read and was moved aside. Usage was restored from a backup; recent usage may be missing.",
+    ),
+    (ALERT_TITLE_BUDGET, "Data Budget"),
+    (
+        ALERT_BODY_BUDGET,
+        "You have used {percent}% of your data budget ({usage} of {limit}) with {days} days left in the period.",
+    ),
+    (ALERT_TITLE_FAILING, "Monitoring Problem"),
+    (
+        ALERT_BODY_SCAN_FAILING,
+        "Scanning processes failed {count} times in a row. Data Guardian keeps trying less often; see the service log.",
+    ),
+    (
+        ALERT_BODY_SAVE_FAILING,
+        "Saving usage data failed {count} times in a row. Data Guardian keeps trying less often; see the service log.",
+    ),
+    (ALERT_TITLE_CLAMPED, "Settings Adjusted"),
+    (
+        ALERT_BODY_CLAMPED,
+        "These settings were out of range and have been moved to the nearest allowed value: {settings}. See the service log.",
+    ),
+    (ALERT_TITLE_SELF_USAGE, "Data Guardian Disk Use"),
+    (
+        ALERT_BODY_SELF_IO,
+        "Data Guardian itself read and wrote {usage} in the last hour, more than the {limit} allowed by self_check.",
+    ),
+    (
+        ALERT_BODY_SELF_STORAGE,
+        "Data Guardian's data and log files take up {usage}, more than the {limit} allowed by self_check.",
+    ),
+    (ALERT_TITLE_TEST, "Test Notification"),
+    (ALERT_BODY_TEST, "Notifications from {app} are working."),
+    (ACTION_SNOOZE, "Snooze 1h"),
+    (ACTION_SHOW_REPORT, "Show report"),
+    (TRAY_SNOOZE_ALL, "Snooze all 1h"),
+    (TRAY_OPEN_REPORT, "Open report"),
+    (TRAY_QUIT, "Quit"),
+    (TRAY_NO_USAGE, "No usage recorded yet"),
+    (DECIMAL_SEPARATOR, "."),
+    (GROUP_SEPARATOR, ","),
+];
+
+const DE: &[(&str, &str)] = &[
+    (ALERT_TITLE, "Datenlimit überschritten"),
+    (
+        ALERT_BODY,
+        "Die Anwendung '{app}' hat den Datenschwellenwert überschritten.",
+    ),
+    (
+        ALERT_BODY_USAGE,
+        "Die Anwendung '{app}' hat {usage} von {limit} verbraucht.",
+    ),
+    (
+        ALERT_BODY_REPEAT_DAILY,
+        "Die Anwendung '{app}' hat ihr Limit {count} Tage in Folge überschritten ({usage} von {limit}).",
+    ),
+    (
+        ALERT_BODY_REPEAT_WEEKLY,
+        "Die Anwendung '{app}' hat ihr Limit {count} Wochen in Folge überschritten ({usage} von {limit}).",
+    ),
+    (
+        ALERT_BODY_REPEAT_MONTHLY,
+        "Die Anwendung '{app}' hat ihr Limit {count} Monate in Folge überschritten ({usage} von {limit}).",
+    ),
+    (ALERT_TITLE_FORECAST, "Datenlimit-Prognose"),
+    (
+        ALERT_BODY_FORECAST_DAILY,
+        "{app} wird voraussichtlich {projected} seines Tageslimits von {limit} verbrauchen.",
+    ),
+    (
+        ALERT_BODY_FORECAST_WEEKLY,
+        "{app} wird voraussichtlich {projected} seines Wochenlimits von {limit} verbrauchen.",
+    ),
+    (
+        ALERT_BODY_FORECAST_MONTHLY,
+        "{app} wird voraussichtlich {projected} seines Monatslimits von {limit} verbrauchen.",
+    ),
+    (ALERT_TITLE_ANOMALY, "Ungewöhnliche Datennutzung"),
+    (
+        ALERT_BODY_ANOMALY,
+        "'{app}' hat seit der letzten Prüfung {delta} übertragen, weit mehr als die üblichen {baseline}.",
+    ),
+    (ALERT_TITLE_CPU, "CPU-Limit überschritten"),
+    (
+        ALERT_BODY_CPU,
+        "Die Anwendung '{app}' hat {usage} CPU-Zeit verbraucht, mehr als ihr Limit von {limit}.",
+    ),
+    (ALERT_TITLE_MEMORY, "Speicherlimit überschritten"),
+    (
+        ALERT_BODY_MEMORY,
+        "Die Anwendung '{app}' belegt {usage} Arbeitsspeicher, mehr als ihr Limit von {limit}.",
+    ),
+    (ALERT_TITLE_STARTED, "Data Guardian gestartet"),
+    (
+        ALERT_BODY_STARTED,
+        "Überwacht {count} Apps mit einem Limit von {limit}.",
+    ),
+    (ALERT_TITLE_SUMMARY, "Data Guardian beendet"),
+    (
+        ALERT_BODY_SUMMARY,
+        "{usage} in dieser Sitzung übertragen. Keine App ist über ihrem Limit.",
+    ),
+    (
+        ALERT_BODY_SUMMARY_OVER,
+        "{usage} in dieser Sitzung übertragen. Über dem Limit: {apps}.",
+    ),
+    (ALERT_TITLE_DATA_LOSS, "Nutzungsdaten beschädigt"),
+    (
+        ALERT_BODY_DATA_LOST,
+        "Die Datei mit den Nutzungsdaten war nicht lesbar und wurde beiseitegelegt. Der Verlauf beginnt bei null.",
+    ),
+    (
+        ALERT_BODY_DATA_RESTORED,
+        "Die Datei mit den Nutzungsdaten war nicht lesbar und wurde beiseitegelegt. Die Nutzung wurde aus einer Sicherung wiederhergestellt; die jüngste Nutzung kann fehlen.",
+    ),
+    (ALERT_TITLE_BUDGET, "Datenbudget"),
+    (
+        ALERT_BODY_BUDGET,
+        "Sie haben {percent} % Ihres Datenbudgets verbraucht ({usage} von {limit}), und der Zeitraum dauert noch {days} Tage.",
+    ),
+    (ALERT_TITLE_FAILING, "Überwachungsproblem"),
+    (
+        ALERT_BODY_SCAN_FAILING,
+        "Das Durchsuchen der Prozesse ist {count}-mal in Folge fehlgeschlagen. Data Guardian versucht es seltener weiter; siehe Dienstprotokoll.",
+    ),
+    (
+        ALERT_BODY_SAVE_FAILING,
+        "Das Speichern der Nutzungsdaten ist {count}-mal in Folge fehlgeschlagen. Data Guardian versucht es seltener weiter; siehe Dienstprotokoll.",
+    ),
+    (ALERT_TITLE_CLAMPED, "Einstellungen angepasst"),
+    (
+        ALERT_BODY_CLAMPED,
+        "Diese Einstellungen lagen außerhalb des gültigen Bereichs und wurden auf den nächsten erlaubten Wert gesetzt: {settings}. Siehe Dienstprotokoll.",
+    ),
+    (ALERT_TITLE_SELF_USAGE, "Speichernutzung von Data Guardian"),
+    (
+        ALERT_BODY_SELF_IO,
+        "Data Guardian selbst hat in der letzten Stunde {usage} gelesen und geschrieben, mehr als die von self_check erlaubten {limit}.",
+    ),
+    (
+        ALERT_BODY_SELF_STORAGE,
+        "Die Daten- und Protokolldateien von Data Guardian belegen {usage}, mehr als die von self_check erlaubten {limit}.",
+    ),
+    (ALERT_TITLE_TEST, "Testbenachrichtigung"),
+    (
+        ALERT_BODY_TEST,
+        "Benachrichtigungen von {app} funktionieren.",
+    ),
+    (ACTION_SNOOZE, "1 Std. schlummern"),
+    (ACTION_SHOW_REPORT, "Bericht anzeigen"),
+    (TRAY_SNOOZE_ALL, "Alle 1 Std. schlummern"),
+    (TRAY_OPEN_REPORT, "Bericht öffnen"),
+    (TRAY_QUIT, "Beenden"),
+    (TRAY_NO_USAGE, "Noch keine Nutzung erfasst"),
+    (DECIMAL_SEPARATOR, ","),
+    (GROUP_SEPARATOR, "."),
+];
+
+const FR: &[(&str, &str)] = &[
+    (ALERT_TITLE, "Limite de données dépassée"),
+    (
+        ALERT_BODY,
+        "L'application « {app} » a dépassé le seuil de données.",
+    ),
+    (
+        ALERT_BODY_USAGE,
+        "L'application « {app} » a utilisé {usage} sur sa limite de {limit}.",
+    ),
+    (
+        ALERT_BODY_REPEAT_DAILY,
+        "L'application « {app} » a dépassé sa limite {count} jours de suite ({usage} sur {limit}).",
+    ),
+    (
+        ALERT_BODY_REPEAT_WEEKLY,
+        "L'application « {app} » a dépassé sa limite {count} semaines de suite ({usage} sur {limit}).",
+    ),
+    (
+        ALERT_BODY_REPEAT_MONTHLY,
+        "L'application « {app} » a dépassé sa limite {count} mois de suite ({usage} sur {limit}).",
+    ),
+    (ALERT_TITLE_FORECAST, "Prévision de limite de données"),
+    (
+        ALERT_BODY_FORECAST_DAILY,
+        "« {app} » est en voie d'utiliser {projected} sur sa limite quotidienne de {limit}.",
+    ),
+    (
+        ALERT_BODY_FORECAST_WEEKLY,
+        "« {app} » est en voie d'utiliser {projected} sur sa limite hebdomadaire de {limit}.",
+    ),
+    (
+        ALERT_BODY_FORECAST_MONTHLY,
+        "« {app} » est en voie d'utiliser {projected} sur sa limite mensuelle de {limit}.",
+    ),
+    (ALERT_TITLE_ANOMALY, "Utilisation de données inhabituelle"),
+    (
+        ALERT_BODY_ANOMALY,
+        "« {app} » a transféré {delta} depuis la dernière vérification, bien plus que ses {baseline} habituels.",
+    ),
+    (ALERT_TITLE_CPU, "Limite CPU dépassée"),
+    (
+        ALERT_BODY_CPU,
+        "L'application « {app} » a utilisé {usage} de temps CPU, au-delà de sa limite de {limit}.",
+    ),
+    (ALERT_TITLE_MEMORY, "Limite de mémoire dépassée"),
+    (
+        ALERT_BODY_MEMORY,
+        "L'application « {app} » utilise {usage} de mémoire, au-delà de sa limite de {limit}.",
+    ),
+    (ALERT_TITLE_STARTED, "Data Guardian démarré"),
+    (
+        ALERT_BODY_STARTED,
+        "Surveillance de {count} applications avec une limite de {limit}.",
+    ),
+    (ALERT_TITLE_SUMMARY, "Data Guardian arrêté"),
+    (
+        ALERT_BODY_SUMMARY,
+        "{usage} transférés pendant cette session. Aucune application ne dépasse sa limite.",
+    ),
+    (
+        ALERT_BODY_SUMMARY_OVER,
+        "{usage} transférés pendant cette session. Au-delà de la limite : {apps}.",
+    ),
+    (ALERT_TITLE_DATA_LOSS, "Données d'utilisation endommagées"),
+    (
+        ALERT_BODY_DATA_LOST,
+        "Le fichier des données d'utilisation était illisible et a été mis de côté. L'historique repart de zéro.",
+    ),
+    (
+        ALERT_BODY_DATA_RESTORED,
+        "Le fichier des données d'utilisation était illisible et a été mis de côté. L'utilisation a été restaurée depuis une sauvegarde ; l'utilisation récente peut manquer.",
+    ),
+    (ALERT_TITLE_BUDGET, "Budget de données"),
+    (
+        ALERT_BODY_BUDGET,
+        "Vous avez utilisé {percent} % de votre budget de données ({usage} sur {limit}) et il reste {days} jours dans la période.",
+    ),
+    (ALERT_TITLE_FAILING, "Problème de surveillance"),
+    (
+        ALERT_BODY_SCAN_FAILING,
+        "L'analyse des processus a échoué {count} fois de suite. Data Guardian réessaie moins souvent ; consultez le journal du service.",
+    ),
+    (
+        ALERT_BODY_SAVE_FAILING,
+        "L'enregistrement des données d'utilisation a échoué {count} fois de suite. Data Guardian réessaie moins souvent ; consultez le journal du service.",
+    ),
+    (ALERT_TITLE_CLAMPED, "Paramètres ajustés"),
+    (
+        ALERT_BODY_CLAMPED,
+        "Ces paramètres étaient hors limites et ont été ramenés à la valeur autorisée la plus proche : {settings}. Voir le journal du service.",
+    ),
+    (
+        ALERT_TITLE_SELF_USAGE,
+        "Utilisation disque de Data Guardian",
+    ),
+    (
+        ALERT_BODY_SELF_IO,
+        "Data Guardian a lui-même lu et écrit {usage} au cours de la dernière heure, plus que les {limit} autorisés par self_check.",
+    ),
+    (
+        ALERT_BODY_SELF_STORAGE,
+        "Les fichiers de données et de journal de Data Guardian occupent {usage}, plus que les {limit} autorisés par self_check.",
+    ),
+    (ALERT_TITLE_TEST, "Notification de test"),
+    (ALERT_BODY_TEST, "Les notifications de {app} fonctionnent."),
+    (ACTION_SNOOZE, "Reporter 1 h"),
+    (ACTION_SHOW_REPORT, "Afficher le rapport"),
+    (TRAY_SNOOZE_ALL, "Tout reporter 1 h"),
+    (TRAY_OPEN_REPORT, "Ouvrir le rapport"),
+    (TRAY_QUIT, "Quitter"),
+    (TRAY_NO_USAGE, "Aucune utilisation enregistrée"),
+    (DECIMAL_SEPARATOR, ","),
+    (GROUP_SEPARATOR, "\u{202f}"),
+];
+
+const ES: &[(&str, &str)] = &[
+    (ALERT_TITLE, "Límite de datos superado"),
+    (
+        ALERT_BODY,
+        "La aplicación '{app}' ha superado el umbral de datos.",
+    ),
+    (
+        ALERT_BODY_USAGE,
+        "La aplicación '{app}' ha usado {usage} de su límite de {limit}.",
+    ),
+    (
+        ALERT_BODY_REPEAT_DAILY,
+        "La aplicación '{app}' ha superado su límite {count} días seguidos ({usage} de {limit}).",
+    ),
+    (
+        ALERT_BODY_REPEAT_WEEKLY,
+        "La aplicación '{app}' ha superado su límite {count} semanas seguidas ({usage} de {limit}).",
+    ),
+    (
+        ALERT_BODY_REPEAT_MONTHLY,
+        "La aplicación '{app}' ha superado su límite {count} meses seguidos ({usage} de {limit}).",
+    ),
+    (ALERT_TITLE_FORECAST, "Previsión de límite de datos"),
+    (
+        ALERT_BODY_FORECAST_DAILY,
+        "'{app}' va camino de usar {projected} de su límite diario de {limit}.",
+    ),
+    (
+        ALERT_BODY_FORECAST_WEEKLY,
+        "'{app}' va camino de usar {projected} de su límite semanal de {limit}.",
+    ),
+    (
+        ALERT_BODY_FORECAST_MONTHLY,
+        "'{app}' va camino de usar {projected} de su límite mensual de {limit}.",
+    ),
+    (ALERT_TITLE_ANOMALY, "Uso de datos inusual"),
+    (
+        ALERT_BODY_ANOMALY,
+        "'{app}' ha transferido {delta} desde la última comprobación, muy por encima de sus {baseline} habituales.",
+    ),
+    (ALERT_TITLE_CPU, "Límite de CPU superado"),
+    (
+        ALERT_BODY_CPU,
+        "La aplicación '{app}' ha usado {usage} de tiempo de CPU, por encima de su límite de {limit}.",
+    ),
+    (ALERT_TITLE_MEMORY, "Límite de memoria superado"),
+    (
+        ALERT_BODY_MEMORY,
+        "La aplicación '{app}' está usando {usage} de memoria, por encima de su límite de {limit}.",
+    ),
+    (ALERT_TITLE_STARTED, "Data Guardian iniciado"),
+    (
+        ALERT_BODY_STARTED,
+        "Supervisando {count} aplicaciones con un límite de {limit}.",
+    ),
+    (ALERT_TITLE_SUMMARY, "Data Guardian detenido"),
+    (
+        ALERT_BODY_SUMMARY,
+        "{usage} transferidos en esta sesión. Ninguna aplicación supera su límite.",
+    ),
+    (
+        ALERT_BODY_SUMMARY_OVER,
+        "{usage} transferidos en esta sesión. Por encima del límite: {apps}.",
+    ),
+    (ALERT_TITLE_DATA_LOSS, "Datos de uso dañados"),
+    (
+        ALERT_BODY_DATA_LOST,
+        "El archivo de datos de uso no se pudo leer y se apartó. El historial empieza de cero.",
+    ),
+    (
+        ALERT_BODY_DATA_RESTORED,
+        "El archivo de datos de uso no se pudo leer y se apartó. El uso se restauró desde una copia de seguridad; puede faltar el uso reciente.",
+    ),
+    (ALERT_TITLE_BUDGET, "Presupuesto de datos"),
+    (
+        ALERT_BODY_BUDGET,
+        "Ha usado el {percent} % de su presupuesto de datos ({usage} de {limit}) y quedan {days} días en el periodo.",
+    ),
+    (ALERT_TITLE_FAILING, "Problema de supervisión"),
+    (
+        ALERT_BODY_SCAN_FAILING,
+        "El análisis de procesos ha fallado {count} veces seguidas. Data Guardian sigue intentándolo con menos frecuencia; consulte el registro del servicio.",
+    ),
+    (
+        ALERT_BODY_SAVE_FAILING,
+        "El guardado de los datos de uso ha fallado {count} veces seguidas. Data Guardian sigue intentándolo con menos frecuencia; consulte el registro del servicio.",
+    ),
+    (ALERT_TITLE_CLAMPED, "Ajustes corregidos"),
+    (
+        ALERT_BODY_CLAMPED,
+        "Estos ajustes estaban fuera de rango y se han llevado al valor permitido más cercano: {settings}. Consulte el registro del servicio.",
+    ),
+    (ALERT_TITLE_SELF_USAGE, "Uso de disco de Data Guardian"),
+    (
+        ALERT_BODY_SELF_IO,
+        "Data Guardian ha leído y escrito {usage} en la última hora, más de los {limit} que permite self_check.",
+    ),
+    (
+        ALERT_BODY_SELF_STORAGE,
+        "Los archivos de datos y de registro de Data Guardian ocupan {usage}, más de los {limit} que permite self_check.",
+    ),
+    (ALERT_TITLE_TEST, "Notificación de prueba"),
+    (ALERT_BODY_TEST, "Las notificaciones de {app} funcionan."),
+    (ACTION_SNOOZE, "Posponer 1 h"),
+    (ACTION_SHOW_REPORT, "Mostrar informe"),
+    (TRAY_SNOOZE_ALL, "Posponer todo 1 h"),
+    (TRAY_OPEN_REPORT, "Abrir informe"),
+    (TRAY_QUIT, "Salir"),
+    (TRAY_NO_USAGE, "Todavía no hay uso registrado"),
+    (DECIMAL_SEPARATOR, ","),
+    (GROUP_SEPARATOR, "."),
+];
+
+const BUNDLED: &[(&str, &[(&str, &str)])] = &[("en", EN), ("de", DE), ("fr", FR), ("es", ES)];
+
+#[derive(Error, Debug)]
+pub enum I18nError {
+    #[error("Failed to load catalog {0}: {1}")]
+    Catalog(String, config::ConfigError),
+    #[error("IO error while reading catalogs: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+pub type Catalog = HashMap<String, String>;
+
+#[derive(Debug, Clone)]
+pub struct Localizer {
+    locale: String,
+    catalogs: HashMap<String, Catalog>,
+    bytes_style: BytesStyle,
+}
+
+impl Default for Localizer {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOCALE)
+    }
+}
+
+impl Localizer {
+    pub fn new(locale: &str) -> Self {
+        let catalogs = BUNDLED
+            .iter()
+            .map(|(locale, messages)| {
+                let catalog = messages
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect();
+                (locale.to_string(), catalog)
+            })
+            .collect();
+
+        Self {
+            locale: normalize_locale(locale),
+            catalogs,
+            bytes_style: BytesStyle::default(),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(&detect_locale().unwrap_or_else(|| DEFAULT_LOCALE.to_string()))
+    }
+
+    pub fn with_bytes_style(mut self, style: BytesStyle) -> Self {
+        self.bytes_style = style;
+        self
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    pub fn register(&mut self, locale: &str, catalog: Catalog) {
+        self.catalogs
+            .entry(normalize_locale(locale))
+            .or_default()
+            .extend(catalog);
+    }
+
+    pub fn load_dir(&mut self, dir: &Path) -> Result<(), I18nError> {
+        if !dir.is_dir() {
+            debug!(?dir, "No catalog directory found");
+            return Ok(());
+        }
+
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "toml") {
+                continue;
+            }
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let mut catalog = Catalog::new();
+            Config::builder()
+                .add_source(File::from(path.as_path()))
+                .build()
+                .and_then(Config::try_deserialize)
+                .and_then(|table| flatten_table(String::new(), table, &mut catalog))
+                .map_err(|e| I18nError::Catalog(path.display().to_string(), e))?;
+
+            debug!(%locale, entries = catalog.len(), "Loaded message catalog");
+            self.register(locale, catalog);
+        }
+        Ok(())
+    }
+
+    pub fn message<'a>(&'a self, key: &'a str) -> &'a str {
+        self.catalogs
+            .get(&self.locale)
+            .and_then(|catalog| catalog.get(key))
+            .or_else(|| {
+                self.catalogs
+                    .get(DEFAULT_LOCALE)
+                    .and_then(|catalog| catalog.get(key))
+            })
+            .map(String::as_str)
+            .unwrap_or_else(|| {
+                warn!(%key, locale = %self.locale, "Missing message key");
+                key
+            })
+    }
+
+    pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        args.iter()
+            .fold(self.message(key).to_string(), |message, (name, value)| {
+                message.replace(&format!("{{{}}}", name), value)
+            })
+    }
+
+    pub fn format_bytes(&self, bytes: u64) -> String {
+        let separators = Separators {
+            decimal: self.message(DECIMAL_SEPARATOR),
+            group: self.message(GROUP_SEPARATOR),
+        };
+        format::format_bytes_with(bytes, self.bytes_style, separators)
+    }
+}
+
+fn flatten_table(
+    prefix: String,
+    table: config::Map<String, config::Value>,
+    catalog: &mut Catalog,
+) -> Result<(), config::ConfigError> {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value.kind {
+            config::ValueKind::Table(table) => flatten_table(key, table, catalog)?,
+            _ => {
+                catalog.insert(key, value.into_string()?);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn detect_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+        .map(|value| normalize_locale(&value))
+}
+
+fn normalize_locale(locale: &str) -> String {
+    locale
+        .split(['_', '-', '.', '@'])
+        .next()
+        .unwrap_or(DEFAULT_LOCALE)
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_bundled_catalogs_complete() {
+        let english: Vec<_> = EN.iter().map(|(key, _)| *key).collect();
+        for (locale, messages) in BUNDLED {
+            let keys: Vec<_> = messages.iter().map(|(key, _)| *key).collect();
+            assert_eq!(keys, english, "Catalog {} is incomplete", locale);
+        }
+    }
+
+    #[test]
+    fn test_locale_normalization() {
+        assert_eq!(normalize_locale("de_DE.UTF-8"), "de");
+        assert_eq!(normalize_locale("fr-CA"), "fr");
+        assert_eq!(normalize_locale("ES"), "es");
+        assert_eq!(normalize_locale("sr@latin"), "sr");
+    }
+
+    #[test]
+    fn test_format_message() {
+        let localizer = Localizer::new("de_DE.UTF-8");
+        assert_eq!(
+            localizer.format(ALERT_BODY, &[("app", "firefox")]),
+            "Die Anwendung 'firefox' hat den Datenschwellenwert überschritten."
+        );
+    }
+
+    #[test]
+    fn test_fallback_to_english() {
+        let mut localizer = Localizer::new("xx");
+        assert_eq!(localizer.message(ALERT_TITLE), "Data Limit Exceeded");
+
+        localizer.register(
+            "xx",
+            Catalog::from([(ALERT_TITLE.to_string(), "X".to_string())]),
+        );
+        assert_eq!(localizer.message(ALERT_TITLE), "X");
+        assert_eq!(localizer.message(ACTION_SNOOZE), "Snooze 1h");
+        assert_eq!(localizer.message("missing.key"), "missing.key");
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        let english = Localizer::new("en");
+        assert_eq!(english.format_bytes(999), "999 B");
+        assert_eq!(english.format_bytes(1_500_000), "1.5 MB");
+        assert_eq!(english.format_bytes(u64::MAX), "18.4 EB");
+
+        let french = Localizer::new("fr");
+        assert_eq!(french.format_bytes(2_340_000_000), "2,3 GB");
+
+        let german = Localizer::new("de").with_bytes_style(BytesStyle::Binary);
+        assert_eq!(german.format_bytes(1000 * 1024), "1.000,0 KiB");
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("pt.toml"),
+            "[alert]\ntitle = \"Limite de dados excedido\"\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("ignored.txt"), "not a catalog").unwrap();
+
+        let mut localizer = Localizer::new("pt_BR");
+        localizer.load_dir(dir.path()).unwrap();
+        assert_eq!(localizer.message(ALERT_TITLE), "Limite de dados excedido");
+        assert_eq!(localizer.message(ACTION_SNOOZE), "Snooze 1h");
+    }
+}
@@ -0,0 +1,665 @@
+#![cfg_attr(not(feature = "monitor"), allow(unused_imports, dead_code))]
+
+use std::collections::HashMap;
+use std::path::Path;
+#[cfg(any(
+    target_os = "macos",
+    all(target_os = "linux", feature = "docker"),
+    windows,
+    test
+))]
+use std::path::PathBuf;
+#[cfg(feature = "monitor")]
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "monitor")]
+use sysinfo::Pid;
+
+#[cfg(target_os = "linux")]
+const APPIMAGE_MOUNT_PREFIX: &str = "/tmp/.mount_";
+#[cfg(target_os = "linux")]
+const CONTAINER_ID_LEN: usize = 64;
+#[cfg(target_os = "linux")]
+const SHORT_CONTAINER_ID_LEN: usize = 12;
+#[cfg(all(target_os = "linux", feature = "docker"))]
+const DEFAULT_DOCKER_SOCKET: &str = "/var/run/docker.sock";
+#[cfg(all(target_os = "linux", feature = "docker"))]
+const DOCKER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+#[cfg(any(windows, test))]
+const FALLBACK_TRANSLATIONS: [&str; 3] = ["040904b0", "040904e4", "04090000"];
+#[cfg(any(windows, test))]
+const MAX_FRIENDLY_NAME_LEN: usize = 128;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdentifyBy {
+    #[default]
+    Process,
+    Bundle,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IdentityConfig {
+    pub resolve_sandboxed_apps: bool,
+    pub identify_by: IdentifyBy,
+    pub friendly_names: bool,
+    pub group_containers: bool,
+}
+
+#[cfg(feature = "monitor")]
+#[derive(Debug, Default)]
+pub struct Resolver {
+    config: IdentityConfig,
+    cache: Mutex<HashMap<(Pid, u64), String>>,
+    #[cfg(any(target_os = "macos", test))]
+    bundles: Mutex<HashMap<PathBuf, String>>,
+    #[cfg(target_os = "linux")]
+    containers: Mutex<HashMap<String, String>>,
+    #[cfg(any(windows, test))]
+    versions: Mutex<HashMap<PathBuf, Option<String>>>,
+    display_names: Mutex<HashMap<String, String>>,
+}
+
+#[cfg(feature = "monitor")]
+impl Resolver {
+    pub fn new(config: IdentityConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.resolve_sandboxed_apps
+            || self.config.group_containers
+            || self.config.identify_by != IdentifyBy::Process
+    }
+
+    pub fn resolve(&self, pid: Pid, start_time: u64, name: &str, exe: Option<&Path>) -> String {
+        if !self.is_enabled() {
+            return name.to_string();
+        }
+
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache
+            .entry((pid, start_time))
+            .or_insert_with(|| self.identify(pid, exe).unwrap_or_else(|| name.to_string()))
+            .clone()
+    }
+
+    pub fn retain(&self, live: impl Fn(Pid, u64) -> bool) {
+        self.cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(pid, start_time), _| live(*pid, *start_time));
+    }
+
+    #[cfg(any(windows, test))]
+    pub fn describe(&self, name: &str, exe: Option<&Path>) {
+        let Some(exe) = exe.filter(|_| self.config.friendly_names) else {
+            return;
+        };
+        let friendly = self
+            .versions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(exe.to_path_buf())
+            .or_insert_with(|| version_friendly_name(exe))
+            .clone();
+        if let Some(friendly) = friendly.filter(|friendly| friendly != name) {
+            self.display_names
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(name.to_string(), friendly);
+        }
+    }
+
+    pub fn display_names(&self) -> HashMap<String, String> {
+        self.display_names
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    #[cfg_attr(
+        not(any(target_os = "linux", target_os = "macos")),
+        allow(unused_variables)
+    )]
+    fn identify(&self, pid: Pid, exe: Option<&Path>) -> Option<String> {
+        #[cfg(target_os = "linux")]
+        if self.config.group_containers
+            && let Some(app) = self.container_name(pid)
+        {
+            return Some(app);
+        }
+        #[cfg(target_os = "linux")]
+        if self.config.resolve_sandboxed_apps
+            && let Some(app) = sandbox_name(pid, exe)
+        {
+            return Some(app);
+        }
+        #[cfg(target_os = "macos")]
+        if self.config.identify_by == IdentifyBy::Bundle
+            && let Some(app) = exe.and_then(|exe| self.bundle_name(exe))
+        {
+            return Some(app);
+        }
+        None
+    }
+
+    #[cfg(target_os = "linux")]
+    fn container_name(&self, pid: Pid) -> Option<String> {
+        let cgroup = read_cgroup(pid)?;
+        let id = container_id(&cgroup)?;
+        let mut containers = self.containers.lock().unwrap_or_else(|e| e.into_inner());
+        let name = containers.entry(id.to_string()).or_insert_with(|| {
+            #[cfg(feature = "docker")]
+            if let Some(name) = docker_container_name(id) {
+                return format!("container:{}", name);
+            }
+            format!("container:{}", &id[..SHORT_CONTAINER_ID_LEN])
+        });
+        Some(name.clone())
+    }
+
+    #[cfg(any(target_os = "macos", test))]
+    fn bundle_name(&self, exe: &Path) -> Option<String> {
+        let bundle = bundle_path(exe)?;
+        let mut bundles = self.bundles.lock().unwrap_or_else(|e| e.into_inner());
+        let name = bundles
+            .entry(bundle.to_path_buf())
+            .or_insert_with(|| bundle_display_name(bundle));
+        Some(name.clone())
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "monitor"))]
+fn read_cgroup(pid: Pid) -> Option<String> {
+    std::fs::read_to_string(Path::new("/proc").join(pid.to_string()).join("cgroup")).ok()
+}
+
+#[cfg(all(target_os = "linux", feature = "monitor"))]
+fn sandbox_name(pid: Pid, exe: Option<&Path>) -> Option<String> {
+    if let Some(app) = read_cgroup(pid).and_then(|cgroup| sandbox_app_id(&cgroup)) {
+        return Some(app);
+    }
+    if exe.is_some_and(|exe| exe.starts_with(APPIMAGE_MOUNT_PREFIX)) {
+        let environ =
+            std::fs::read(Path::new("/proc").join(pid.to_string()).join("environ")).ok()?;
+        return environ
+            .split(|byte| *byte == 0)
+            .find_map(|var| var.strip_prefix(b"APPIMAGE="))
+            .and_then(|path| appimage_name(&String::from_utf8_lossy(path)));
+    }
+    None
+}
+
+#[cfg(any(target_os = "macos", test))]
+pub fn bundle_path(exe: &Path) -> Option<&Path> {
+    exe.ancestors()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "app"))
+        .last()
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn bundle_display_name(bundle: &Path) -> String {
+    plist::Value::from_file(bundle.join("Contents").join("Info.plist"))
+        .ok()
+        .and_then(|info| {
+            let info = info.into_dictionary()?;
+            ["CFBundleDisplayName", "CFBundleName"]
+                .iter()
+                .find_map(|key| info.get(key)?.as_string().map(str::to_string))
+                .filter(|name| !name.trim().is_empty())
+        })
+        .or_else(|| Some(bundle.file_stem()?.to_string_lossy().into_owned()))
+        .unwrap_or_default()
+}
+
+#[cfg(windows)]
+fn version_friendly_name(exe: &Path) -> Option<String> {
+    use std::os::windows::ffi::OsStrExt;
+
+    use windows_sys::Win32::Storage::FileSystem::{GetFileVersionInfoSizeW, GetFileVersionInfoW};
+
+    let path: Vec<u16> = exe.as_os_str().encode_wide().chain(Some(0)).collect();
+    let size = unsafe { GetFileVersionInfoSizeW(path.as_ptr(), std::ptr::null_mut()) };
+    if size == 0 {
+        return None;
+    }
+    let mut data = vec![0u8; size as usize];
+    if unsafe { GetFileVersionInfoW(path.as_ptr(), 0, size, data.as_mut_ptr().cast()) } == 0 {
+        return None;
+    }
+
+    let translations = query_version_value(&data, "\\VarFileInfo\\Translation", 1)
+        .map(utf16_units)
+        .unwrap_or_default();
+    let read = |field: &str| {
+        translation_keys(&translations)
+            .iter()
+            .find_map(|translation| {
+                let key = format!("\\StringFileInfo\\{}\\{}", translation, field);
+                clean_version_string(&utf16_units(query_version_value(&data, &key, 2)?))
+            })
+    };
+    read("FileDescription").or_else(|| read("ProductName"))
+}
+
+#[cfg(windows)]
+fn query_version_value<'a>(data: &'a [u8], key: &str, unit_size: usize) -> Option<&'a [u8]> {
+    use windows_sys::Win32::Storage::FileSystem::VerQueryValueW;
+
+    let key: Vec<u16> = key.encode_utf16().chain(Some(0)).collect();
+    let mut value = std::ptr::null_mut();
+    let mut len = 0u32;
+    let found = unsafe { VerQueryValueW(data.as_ptr().cast(), key.as_ptr(), &mut value, &mut len) };
+    if found == 0 || value.is_null() {
+        return None;
+    }
+    let offset = (value as usize).checked_sub(data.as_ptr() as usize)?;
+    let value = data.get(offset..)?;
+    Some(&value[..(len as usize * unit_size).min(value.len())])
+}
+
+#[cfg(windows)]
+fn utf16_units(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+#[cfg(all(test, not(windows)))]
+fn version_friendly_name(_exe: &Path) -> Option<String> {
+    None
+}
+
+#[cfg(any(windows, test))]
+fn translation_keys(translations: &[u16]) -> Vec<String> {
+    let mut keys: Vec<String> = translations
+        .chunks_exact(2)
+        .map(|pair| format!("{:04x}{:04x}", pair[0], pair[1]))
+        .collect();
+    for fallback in FALLBACK_TRANSLATIONS {
+        if !keys.iter().any(|key| key == fallback) {
+            keys.push(fallback.to_string());
+        }
+    }
+    keys
+}
+
+#[cfg(any(windows, test))]
+fn clean_version_string(raw: &[u16]) -> Option<String> {
+    let len = raw.iter().position(|c| *c == 0).unwrap_or(raw.len());
+    let value = String::from_utf16(&raw[..len]).ok()?;
+    let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    let valid = !value.is_empty()
+        && value.chars().count() <= MAX_FRIENDLY_NAME_LEN
+        && !value
+            .chars()
+            .any(|c| c.is_control() || c == char::REPLACEMENT_CHARACTER);
+    valid.then_some(value)
+}
+
+#[cfg(target_os = "linux")]
+fn cgroup_segments(cgroup: &str) -> impl Iterator<Item = &str> {
+    cgroup
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .flat_map(|path| path.rsplit('/'))
+}
+
+#[cfg(target_os = "linux")]
+pub fn sandbox_app_id(cgroup: &str) -> Option<String> {
+    cgroup_segments(cgroup).find_map(|segment| {
+        let unit = segment.strip_suffix(".scope")?;
+        if let Some(flatpak) = unit.strip_prefix("app-flatpak-") {
+            let (id, instance) = flatpak.rsplit_once('-')?;
+            return instance
+                .bytes()
+                .all(|byte| byte.is_ascii_digit())
+                .then(|| id.to_string());
+        }
+        let snap = unit.strip_prefix("snap.")?;
+        let (name, _) = snap.split_once('.')?;
+        (!name.is_empty()).then(|| name.to_string())
+    })
+}
+
+#[cfg(target_os = "linux")]
+pub fn container_id(cgroup: &str) -> Option<&str> {
+    cgroup_segments(cgroup).find_map(|segment| {
+        let unit = segment.strip_suffix(".scope").unwrap_or(segment);
+        let id = unit.rsplit('-').next()?;
+        (id.len() == CONTAINER_ID_LEN && id.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .then_some(id)
+    })
+}
+
+#[cfg(all(target_os = "linux", feature = "docker"))]
+fn docker_container_name(id: &str) -> Option<String> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let socket = std::env::var("DOCKER_HOST")
+        .ok()
+        .and_then(|host| host.strip_prefix("unix://").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DOCKER_SOCKET));
+    let mut stream = UnixStream::connect(&socket).ok()?;
+    stream.set_read_timeout(Some(DOCKER_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(DOCKER_TIMEOUT)).ok()?;
+    write!(
+        stream,
+        "GET /containers/{}/json HTTP/1.0\r\nHost: docker\r\n\r\n",
+        id
+    )
+    .ok()?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+    let body = response
+        .strip_prefix("HTTP/1.")
+        .filter(|rest| rest.get(1..5) == Some(" 200"))
+        .and_then(|rest| rest.split_once("\r\n\r\n"))?
+        .1;
+    inspect_container_name(body)
+}
+
+#[cfg(any(all(target_os = "linux", feature = "docker"), test))]
+pub fn inspect_container_name(body: &str) -> Option<String> {
+    let inspect: serde_json::Value = serde_json::from_str(body).ok()?;
+    let name = inspect.get("Name")?.as_str()?.trim_start_matches('/');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(target_os = "linux")]
+pub fn appimage_name(path: &str) -> Option<String> {
+    let stem = Path::new(path).file_stem()?.to_str()?;
+    let name: Vec<&str> = stem
+        .split(['-', '_'])
+        .take_while(|part| {
+            !part.starts_with(|c: char| c.is_ascii_digit())
+                && !matches!(*part, "x86" | "amd64" | "aarch64" | "arm64")
+        })
+        .collect();
+    match name.join("-") {
+        name if name.is_empty() => Some(stem.to_string()),
+        name => Some(name),
+    }
+}
+
+#[cfg(all(test, feature = "monitor"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_resolver_keeps_name() {
+        let resolver = Resolver::default();
+        assert!(!resolver.is_enabled());
+        assert_eq!(
+            resolver.resolve(Pid::from(1), 0, "bwrap", None),
+            "bwrap".to_string()
+        );
+    }
+
+    #[test]
+    fn test_resolver_cache() {
+        let resolver = Resolver::new(IdentityConfig {
+            resolve_sandboxed_apps: true,
+            ..Default::default()
+        });
+        let pid = Pid::from(u32::MAX as usize);
+        assert_eq!(resolver.resolve(pid, 1, "first", None), "first");
+        assert_eq!(resolver.resolve(pid, 1, "second", None), "first");
+        assert_eq!(resolver.resolve(pid, 2, "second", None), "second");
+
+        resolver.retain(|_, start_time| start_time == 2);
+        assert_eq!(resolver.resolve(pid, 1, "third", None), "third");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sandbox_app_id() {
+        assert_eq!(
+            sandbox_app_id(
+                "0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-flatpak-org.mozilla.firefox-2345.scope\n"
+            ),
+            Some("org.mozilla.firefox".to_string())
+        );
+        assert_eq!(
+            sandbox_app_id(
+                "0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-flatpak-com.valvesoftware.Steam-81234.scope/app\n"
+            ),
+            Some("com.valvesoftware.Steam".to_string())
+        );
+        assert_eq!(
+            sandbox_app_id(
+                "0::/user.slice/user-1000.slice/user@1000.service/app.slice/snap.spotify.spotify-8d0b6c1e-7f43-4f6f-a0c1-0e5f2b6a6b1d.scope\n"
+            ),
+            Some("spotify".to_string())
+        );
+        assert_eq!(
+            sandbox_app_id(
+                "12:pids:/user.slice/user-1000.slice/user@1000.service/snap.firefox.firefox.7a4cbd36-0c56-4a5b-a9d6-42c3a3d5f8f1.scope\n1:name=systemd:/user.slice/user-1000.slice/session-2.scope\n"
+            ),
+            Some("firefox".to_string())
+        );
+        assert_eq!(
+            sandbox_app_id(
+                "0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-gnome-org.gnome.Terminal-3412.scope\n"
+            ),
+            None
+        );
+        assert_eq!(sandbox_app_id("0::/init.scope\n"), None);
+        assert_eq!(sandbox_app_id(""), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_appimage_name() {
+        assert_eq!(
+            appimage_name("/home/me/Apps/Obsidian-1.5.3.AppImage"),
+            Some("Obsidian".to_string())
+        );
+        assert_eq!(
+            appimage_name("/opt/Nextcloud-3.13.0-x86_64.AppImage"),
+            Some("Nextcloud".to_string())
+        );
+        assert_eq!(
+            appimage_name("/opt/balena-etcher_x86_64.AppImage"),
+            Some("balena-etcher".to_string())
+        );
+        assert_eq!(appimage_name("/opt/1.AppImage"), Some("1".to_string()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_container_id() {
+        let id = "4f1c2b8e9d0a7c6b5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a29";
+        for cgroup in [
+            format!("0::/system.slice/docker-{}.scope\n", id),
+            format!("12:pids:/docker/{}\n0::/\n", id),
+            format!(
+                "0::/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod1234.slice/cri-containerd-{}.scope\n",
+                id
+            ),
+            format!("0::/machine.slice/libpod-{}.scope/container\n", id),
+        ] {
+            assert_eq!(container_id(&cgroup), Some(id));
+        }
+        assert_eq!(
+            container_id("0::/user.slice/user-1000.slice/session-2.scope\n"),
+            None
+        );
+        assert_eq!(container_id(&format!("0::/docker/{}\n", &id[..60])), None);
+        assert_eq!(
+            container_id(
+                "0::/docker/zz1c2b8e9d0a7c6b5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a29\n"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_inspect_container_name() {
+        assert_eq!(
+            inspect_container_name(r#"{"Id":"4f1c","Name":"/postgres","State":{}}"#),
+            Some("postgres".to_string())
+        );
+        assert_eq!(inspect_container_name(r#"{"Name":"/"}"#), None);
+        assert_eq!(
+            inspect_container_name(r#"{"message":"No such container"}"#),
+            None
+        );
+        assert_eq!(inspect_container_name("not json"), None);
+    }
+
+    #[test]
+    fn test_clean_version_string() {
+        let utf16 = |value: &str| value.encode_utf16().collect::<Vec<u16>>();
+        assert_eq!(
+            clean_version_string(&utf16("Google Chrome\0")),
+            Some("Google Chrome".to_string())
+        );
+        assert_eq!(
+            clean_version_string(&utf16("  Microsoft\u{a0}Teams  ")),
+            Some("Microsoft Teams".to_string())
+        );
+        assert_eq!(
+            clean_version_string(&utf16("微信")),
+            Some("微信".to_string())
+        );
+        assert_eq!(
+            clean_version_string(&utf16("Übersetzer\0garbage")),
+            Some("Übersetzer".to_string())
+        );
+        assert_eq!(clean_version_string(&utf16("   \0")), None);
+        assert_eq!(clean_version_string(&[]), None);
+        assert_eq!(clean_version_string(&[0xd800, 0x41]), None);
+        assert_eq!(clean_version_string(&utf16("App\u{1}Name")), None);
+        assert_eq!(clean_version_string(&utf16(&"x".repeat(200))), None);
+    }
+
+    #[test]
+    fn test_translation_keys() {
+        assert_eq!(
+            translation_keys(&[0x0407, 0x04b0, 0x0409]),
+            vec!["040704b0", "040904b0", "040904e4", "04090000"]
+        );
+        assert_eq!(translation_keys(&[0x0409, 0x04b0])[0], "040904b0");
+        assert_eq!(translation_keys(&[]).len(), FALLBACK_TRANSLATIONS.len());
+    }
+
+    #[test]
+    fn test_display_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("chrome.exe");
+        let resolver = Resolver::new(IdentityConfig {
+            friendly_names: true,
+            ..Default::default()
+        });
+        resolver
+            .versions
+            .lock()
+            .unwrap()
+            .insert(exe.clone(), Some("Google Chrome".to_string()));
+
+        resolver.describe("chrome.exe", Some(&exe));
+        resolver.describe("svchost.exe", None);
+        assert_eq!(
+            resolver.display_names(),
+            HashMap::from([("chrome.exe".to_string(), "Google Chrome".to_string())])
+        );
+
+        let disabled = Resolver::default();
+        disabled.describe("chrome.exe", Some(&exe));
+        assert!(disabled.display_names().is_empty());
+    }
+
+    fn write_bundle(root: &Path, bundle: &str, plist: Option<&str>) -> PathBuf {
+        let contents = root.join(bundle).join("Contents");
+        std::fs::create_dir_all(contents.join("MacOS")).unwrap();
+        if let Some(plist) = plist {
+            std::fs::write(contents.join("Info.plist"), plist).unwrap();
+        }
+        contents.join("MacOS")
+    }
+
+    fn info_plist(entries: &[(&str, &str)]) -> String {
+        let entries: String = entries
+            .iter()
+            .map(|(key, value)| format!("<key>{}</key><string>{}</string>", key, value))
+            .collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0"><dict>{}</dict></plist>"#,
+            entries
+        )
+    }
+
+    #[test]
+    fn test_bundle_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let resolver = Resolver::new(IdentityConfig {
+            identify_by: IdentifyBy::Bundle,
+            ..Default::default()
+        });
+
+        let chrome = write_bundle(
+            dir.path(),
+            "Google Chrome.app",
+            Some(&info_plist(&[
+                ("CFBundleName", "Chrome"),
+                ("CFBundleDisplayName", "Google Chrome"),
+            ])),
+        );
+        let helper = write_bundle(
+            &dir.path().join("Google Chrome.app/Contents/Frameworks"),
+            "Google Chrome Helper (Renderer).app",
+            Some(&info_plist(&[("CFBundleName", "Google Chrome Helper")])),
+        );
+        assert_eq!(
+            resolver.bundle_name(&helper.join("Google Chrome Helper (Renderer)")),
+            Some("Google Chrome".to_string())
+        );
+        assert_eq!(
+            resolver.bundle_name(&chrome.join("Google Chrome")),
+            Some("Google Chrome".to_string())
+        );
+
+        let safari = write_bundle(
+            dir.path(),
+            "Safari.app",
+            Some(&info_plist(&[("CFBundleName", "Safari")])),
+        );
+        assert_eq!(
+            resolver.bundle_name(&safari.join("Safari")),
+            Some("Safari".to_string())
+        );
+
+        let plain = write_bundle(dir.path(), "Plain Tool.app", Some("not a plist"));
+        assert_eq!(
+            resolver.bundle_name(&plain.join("tool")),
+            Some("Plain Tool".to_string())
+        );
+
+        assert_eq!(resolver.bundle_name(&dir.path().join("usr/bin/curl")), None);
+    }
+
+    #[test]
+    fn test_bundle_names_are_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let resolver = Resolver::new(IdentityConfig {
+            identify_by: IdentifyBy::Bundle,
+            ..Default::default()
+        });
+        let plist = info_plist(&[("CFBundleName", "Slack")]);
+        let exe = write_bundle(dir.path(), "Slack.app", Some(&plist)).join("Slack");
+
+        assert_eq!(resolver.bundle_name(&exe), Some("Slack".to_string()));
+        std::fs::remove_file(dir.path().join("Slack.app/Contents/Info.plist")).unwrap();
+        assert_eq!(resolver.bundle_name(&exe), Some("Slack".to_string()));
+    }
+}
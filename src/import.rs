@@ -0,0 +1,373 @@
+//! Usage history from other tools, for `dg import`.
+//!
+//! The mapping is lossy. From a vnstat JSON export (`vnstat --json d`), each
+//! interface's daily totals become `net:<interface>` entries in the daily
+//! history, with received plus sent as bytes and sent as written. Hourly,
+//! monthly and yearly totals are left out. From a nethogs trace
+//! (`nethogs -t -v 2`, totals in bytes), the last total of each process is
+//! added to its program's name on a single day, as nethogs does not record
+//! dates. Days outside the daily history are skipped.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use chrono::{Days, NaiveDate};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::monitor::NET_PREFIX;
+use super::tracker::{AppRecord, DAILY_HISTORY_DAYS, DayUsage, UsageTracker};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    /// JSON from `vnstat --json d`
+    Vnstat,
+    /// Trace from `nethogs -t -v 2`
+    Nethogs,
+}
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Not a vnstat JSON export: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("Not a vnstat JSON export: it has no interfaces list")]
+    NoInterfaces,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SkipReason {
+    /// A record without the fields or numbers it needs.
+    Malformed,
+    /// Traffic nethogs could not tie to a program.
+    UnknownProcess,
+    /// A day older than the daily history, or in the future.
+    OutsideHistory,
+}
+
+impl SkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Malformed => "malformed",
+            Self::UnknownProcess => "unknown_process",
+            Self::OutsideHistory => "outside_history",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Malformed => "malformed",
+            Self::UnknownProcess => "not tied to a program",
+            Self::OutsideHistory => "outside the daily history",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    pub reason: SkipReason,
+    /// The record as it appeared in the file, or a short description.
+    pub record: String,
+}
+
+/// Daily usage read from another tool, and what had to be left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Imported {
+    pub days: BTreeMap<NaiveDate, HashMap<String, DayUsage>>,
+    /// Records that were imported.
+    pub records: usize,
+    pub skipped: Vec<Skipped>,
+    today: NaiveDate,
+}
+
+impl Imported {
+    fn new(today: NaiveDate) -> Self {
+        Self {
+            today,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, date: NaiveDate, app: String, bytes: u64, written: u64, record: String) {
+        let oldest = self
+            .today
+            .checked_sub_days(Days::new(DAILY_HISTORY_DAYS))
+            .unwrap_or(NaiveDate::MIN);
+        if date < oldest || date > self.today {
+            return self.skip(SkipReason::OutsideHistory, record);
+        }
+        let day = self.days.entry(date).or_default().entry(app).or_default();
+        day.bytes = day.bytes.saturating_add(bytes);
+        day.written = day.written.saturating_add(written.min(bytes));
+        self.records += 1;
+    }
+
+    fn skip(&mut self, reason: SkipReason, record: String) {
+        self.skipped.push(Skipped { reason, record });
+    }
+
+    /// Names of the apps and interfaces that received usage.
+    pub fn apps(&self) -> HashSet<&str> {
+        self.days
+            .values()
+            .flat_map(|apps| apps.keys().map(String::as_str))
+            .collect()
+    }
+
+    /// Skipped records per reason.
+    pub fn skipped_counts(&self) -> BTreeMap<SkipReason, usize> {
+        self.skipped
+            .iter()
+            .fold(BTreeMap::new(), |mut counts, skipped| {
+                *counts.entry(skipped.reason).or_default() += 1;
+                counts
+            })
+    }
+
+    /// A tracker to merge into one whose period started at `period_start`.
+    /// Totals only count days from `period_start` on, or every day when the
+    /// period never resets.
+    pub fn to_tracker(&self, period_start: Option<NaiveDate>) -> UsageTracker {
+        let mut tracker = UsageTracker {
+            period_start,
+            days: self.days.clone(),
+            ..Default::default()
+        };
+        for (date, apps) in &self.days {
+            if period_start.is_some_and(|start| *date < start) {
+                continue;
+            }
+            for (app, usage) in apps {
+                let record: &mut AppRecord = tracker.apps.entry(app.clone()).or_default();
+                record.bytes = record.bytes.saturating_add(usage.bytes);
+            }
+        }
+        tracker
+    }
+}
+
+/// Reads a vnstat JSON export. Both the format of vnstat 1 (`jsonversion`
+/// 1, in KiB) and vnstat 2 (in bytes) are understood.
+pub fn import_vnstat(text: &str, today: NaiveDate) -> Result<Imported, ImportError> {
+    let value: Value = serde_json::from_str(text)?;
+    let interfaces = value
+        .get("interfaces")
+        .and_then(Value::as_array)
+        .ok_or(ImportError::NoInterfaces)?;
+    let scale = match value.get("jsonversion").and_then(Value::as_str) {
+        Some("1") => 1024,
+        _ => 1,
+    };
+
+    let mut imported = Imported::new(today);
+    for interface in interfaces {
+        let name = interface
+            .get("name")
+            .or_else(|| interface.get("id"))
+            .and_then(Value::as_str);
+        let traffic = interface.get("traffic");
+        let days = traffic
+            .and_then(|traffic| traffic.get("day").or_else(|| traffic.get("days")))
+            .and_then(Value::as_array);
+        let (Some(name), Some(days)) = (name, days) else {
+            imported.skip(SkipReason::Malformed, compact(interface));
+            continue;
+        };
+        for day in days {
+            let record = format!("{} {}", name, compact(day));
+            match vnstat_day(day, scale) {
+                Some((date, rx, tx)) => {
+                    let app = format!("{}{}", NET_PREFIX, name);
+                    imported.add(date, app, rx.saturating_add(tx), tx, record);
+                }
+                None => imported.skip(SkipReason::Malformed, record),
+            }
+        }
+    }
+    Ok(imported)
+}
+
+fn vnstat_day(day: &Value, scale: u64) -> Option<(NaiveDate, u64, u64)> {
+    let date = day.get("date")?;
+    let part = |key| date.get(key).and_then(Value::as_u64);
+    let date = NaiveDate::from_ymd_opt(
+        i32::try_from(part("year")?).ok()?,
+        u32::try_from(part("month")?).ok()?,
+        u32::try_from(part("day")?).ok()?,
+    )?;
+    let rx = day.get("rx").and_then(Value::as_u64)?.checked_mul(scale)?;
+    let tx = day.get("tx").and_then(Value::as_u64)?.checked_mul(scale)?;
+    Some((date, rx, tx))
+}
+
+/// Reads a nethogs trace and adds each program's usage to `date`. Totals are
+/// cumulative, so the last line of each process counts.
+pub fn import_nethogs(text: &str, date: NaiveDate, today: NaiveDate) -> Imported {
+    let mut imported = Imported::new(today);
+    let mut totals = BTreeMap::new();
+    let mut unknown = HashSet::new();
+    for line in text.lines() {
+        // Banners and "Refreshing:" lines have no fields.
+        let Some((process, values)) = line.trim().split_once('\t') else {
+            continue;
+        };
+        let Some((process, sent, received)) = nethogs_line(process, values) else {
+            imported.skip(SkipReason::Malformed, line.trim().to_string());
+            continue;
+        };
+        match process {
+            Some(key) => {
+                totals.insert(key, (sent, received));
+            }
+            None => {
+                let name = line.trim().split('\t').next().unwrap_or_default();
+                if unknown.insert(name.to_string()) {
+                    imported.skip(SkipReason::UnknownProcess, name.to_string());
+                }
+            }
+        }
+    }
+    for ((program, pid), (sent, received)) in totals {
+        let app = Path::new(&program)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| program.clone());
+        let record = format!("{}/{}", program, pid);
+        imported.add(date, app, sent.saturating_add(received), sent, record);
+    }
+    imported
+}
+
+/// A program's path and PID.
+type Process = (String, u32);
+
+/// Splits `program/pid/uid` and the sent and received totals. The process
+/// is `None` for traffic nethogs could not tie to a program.
+fn nethogs_line(process: &str, values: &str) -> Option<(Option<Process>, u64, u64)> {
+    let mut values = values.split('\t').map(|value| {
+        let value = value.trim().parse::<f64>().ok()?;
+        (value.is_finite() && value >= 0.0).then(|| value.round() as u64)
+    });
+    let sent = values.next()??;
+    let received = values.next()??;
+    if values.next().is_some() {
+        return None;
+    }
+    if process.starts_with("unknown ") {
+        return Some((None, sent, received));
+    }
+    let mut parts = process.rsplitn(3, '/');
+    let _uid = parts.next()?;
+    let pid = parts.next()?.parse().ok()?;
+    let program = parts.next().filter(|program| !program.is_empty())?;
+    Some((Some((program.to_string(), pid)), sent, received))
+}
+
+fn compact(value: &Value) -> String {
+    const MAX_LEN: usize = 80;
+    let text = value.to_string();
+    match text.char_indices().nth(MAX_LEN) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_import_vnstat() {
+        let json = r#"{
+            "vnstatversion": "2.9",
+            "jsonversion": "2",
+            "interfaces": [
+                {
+                    "name": "wlan0",
+                    "traffic": {
+                        "total": {"rx": 700, "tx": 70},
+                        "day": [
+                            {"id": 1, "date": {"year": 2025, "month": 6, "day": 17}, "rx": 300, "tx": 30},
+                            {"id": 2, "date": {"year": 2025, "month": 6, "day": 18}, "rx": 400, "tx": 40},
+                            {"id": 3, "date": {"year": 2025, "month": 1, "day": 2}, "rx": 1, "tx": 1},
+                            {"id": 4, "date": {"year": 2025, "month": 6}, "rx": 1, "tx": 1}
+                        ]
+                    }
+                },
+                {"name": "lo"}
+            ]
+        }"#;
+        let imported = import_vnstat(json, date(6, 18)).unwrap();
+
+        assert_eq!(imported.records, 2);
+        assert_eq!(
+            imported.days[&date(6, 18)]["net:wlan0"],
+            DayUsage {
+                bytes: 440,
+                written: 40,
+                exceeded: 0
+            }
+        );
+        assert_eq!(
+            imported.skipped_counts(),
+            BTreeMap::from([(SkipReason::Malformed, 2), (SkipReason::OutsideHistory, 1)])
+        );
+
+        let tracker = imported.to_tracker(Some(date(6, 18)));
+        assert_eq!(tracker.apps["net:wlan0"].bytes, 440);
+        assert_eq!(imported.to_tracker(None).apps["net:wlan0"].bytes, 770);
+    }
+
+    #[test]
+    fn test_import_vnstat_1_in_kib() {
+        let json = r#"{"jsonversion": "1", "interfaces": [{"id": "eth0", "traffic": {
+            "days": [{"id": 0, "date": {"year": 2025, "month": 6, "day": 18}, "rx": 2, "tx": 1}]
+        }}]}"#;
+        let imported = import_vnstat(json, date(6, 18)).unwrap();
+        assert_eq!(imported.days[&date(6, 18)]["net:eth0"].bytes, 3 * 1024);
+
+        assert!(matches!(
+            import_vnstat("{}", date(6, 18)),
+            Err(ImportError::NoInterfaces)
+        ));
+        assert!(matches!(
+            import_vnstat("Refreshing:", date(6, 18)),
+            Err(ImportError::InvalidJson(_))
+        ));
+    }
+
+    #[test]
+    fn test_import_nethogs() {
+        let trace = "Adding local address: 10.0.0.2\n\
+            Refreshing:\n\
+            /usr/lib/firefox/firefox/1234/1000\t100\t1000\n\
+            unknown TCP/0/0\t5\t5\n\
+            Refreshing:\n\
+            /usr/lib/firefox/firefox/1234/1000\t200\t2000\n\
+            /usr/lib/firefox/firefox/1300/1000\t1e+03\t0\n\
+            /usr/bin/curl/99/1000\t10\t20\n\
+            unknown TCP/0/0\t6\t6\n\
+            /usr/bin/wget\tabc\t1\n";
+        let imported = import_nethogs(trace, date(6, 18), date(6, 18));
+
+        assert_eq!(imported.records, 3);
+        let day = &imported.days[&date(6, 18)];
+        assert_eq!(day["firefox"].bytes, 3200);
+        assert_eq!(day["firefox"].written, 1200);
+        assert_eq!(day["curl"].bytes, 30);
+        assert_eq!(imported.apps().len(), 2);
+        assert_eq!(
+            imported.skipped_counts(),
+            BTreeMap::from([(SkipReason::Malformed, 1), (SkipReason::UnknownProcess, 1)])
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const MEASUREMENT: &str = "data_usage";
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 5000;
+pub const DEFAULT_MAX_BUFFERED_POINTS: usize = 50_000;
+pub const DEFAULT_TIMEOUT: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InfluxConfig {
+    pub enabled: bool,
+    pub url: String,
+    pub org: String,
+    pub bucket: String,
+    pub token: Option<String>,
+    pub host: Option<String>,
+    pub max_batch_size: usize,
+    pub max_buffered_points: usize,
+    pub timeout_seconds: u64,
+}
+
+impl Default for InfluxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "http://localhost:8086".to_string(),
+            org: String::new(),
+            bucket: String::new(),
+            token: None,
+            host: None,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            max_buffered_points: DEFAULT_MAX_BUFFERED_POINTS,
+            timeout_seconds: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Point {
+    pub host: String,
+    pub app: String,
+    pub bytes: u64,
+    pub time: DateTime<Utc>,
+}
+
+impl Point {
+    pub fn line(&self) -> String {
+        format!(
+            "{},host={},app={} bytes={}i {}",
+            MEASUREMENT,
+            escape_tag(&self.host),
+            escape_tag(&self.app),
+            self.bytes,
+            self.time.timestamp()
+        )
+    }
+}
+
+pub fn escape_tag(value: &str) -> String {
+    if value.is_empty() {
+        return "_".to_string();
+    }
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' | '=' | ' ' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => escaped.push('_'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn points(usage: &HashMap<String, u64>, host: &str, time: DateTime<Utc>) -> Vec<Point> {
+    let mut points: Vec<_> = usage
+        .iter()
+        .map(|(app, bytes)| Point {
+            host: host.to_string(),
+            app: app.clone(),
+            bytes: *bytes,
+            time,
+        })
+        .collect();
+    points.sort_by(|a, b| a.app.cmp(&b.app));
+    points
+}
+
+pub fn render(points: &[Point]) -> String {
+    points.iter().map(|point| point.line() + "\n").collect()
+}
+
+#[cfg(feature = "monitor")]
+pub fn hostname(config: &InfluxConfig) -> String {
+    config
+        .host
+        .clone()
+        .or_else(sysinfo::System::host_name)
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+#[cfg(feature = "influx")]
+pub use exporter::*;
+
+#[cfg(feature = "influx")]
+mod exporter {
+    use std::collections::{HashMap, VecDeque};
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use thiserror::Error;
+    use tracing::{debug, warn};
+
+    use super::{InfluxConfig, Point, hostname, points, render};
+
+    #[derive(Error, Debug)]
+    pub enum InfluxError {
+        #[error("InfluxDB request failed: {0}")]
+        Http(#[from] reqwest::Error),
+        #[error("InfluxDB rejected the write ({0}): {1}")]
+        Status(u16, String),
+    }
+
+    #[derive(Debug)]
+    pub struct InfluxExporter {
+        client: reqwest::Client,
+        write_url: String,
+        org: String,
+        bucket: String,
+        token: Option<String>,
+        host: String,
+        max_batch_size: usize,
+        max_buffered_points: usize,
+        buffer: VecDeque<Point>,
+        dropped: u64,
+    }
+
+    impl InfluxExporter {
+        pub fn new(config: &InfluxConfig) -> Result<Self, InfluxError> {
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_secs(config.timeout_seconds))
+                .build()?;
+            Ok(Self {
+                client,
+                write_url: format!("{}/api/v2/write", config.url.trim_end_matches('/')),
+                org: config.org.clone(),
+                bucket: config.bucket.clone(),
+                token: config.token.clone(),
+                host: hostname(config),
+                max_batch_size: config.max_batch_size.max(1),
+                max_buffered_points: config.max_buffered_points,
+                buffer: VecDeque::new(),
+                dropped: 0,
+            })
+        }
+
+        pub fn buffered(&self) -> usize {
+            self.buffer.len()
+        }
+
+        pub fn dropped(&self) -> u64 {
+            self.dropped
+        }
+
+        fn enqueue(&mut self, points: Vec<Point>) {
+            self.buffer.extend(points);
+            let overflow = self.buffer.len().saturating_sub(self.max_buffered_points);
+            if overflow > 0 {
+                self.buffer.drain(..overflow);
+                self.dropped += overflow as u64;
+                warn!(
+                    dropped = overflow,
+                    buffered = self.buffer.len(),
+                    "Dropping oldest InfluxDB points"
+                );
+            }
+        }
+
+        async fn write(&self, body: String) -> Result<(), InfluxError> {
+            let mut request = self
+                .client
+                .post(&self.write_url)
+                .query(&[
+                    ("org", self.org.as_str()),
+                    ("bucket", self.bucket.as_str()),
+                    ("precision", "s"),
+                ])
+                .header("Content-Type", "text/plain; charset=utf-8")
+                .body(body);
+            if let Some(token) = &self.token {
+                request = request.header("Authorization", format!("Token {}", token));
+            }
+
+            let response = request.send().await?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            let message = response.text().await.unwrap_or_default();
+            Err(InfluxError::Status(
+                status.as_u16(),
+                message.trim().to_string(),
+            ))
+        }
+
+        pub async fn export(&mut self, usage: &HashMap<String, u64>) -> Result<(), InfluxError> {
+            let points = points(usage, &self.host, Utc::now());
+            self.enqueue(points);
+
+            while !self.buffer.is_empty() {
+                let len = self.buffer.len().min(self.max_batch_size);
+                let batch: Vec<_> = self.buffer.range(..len).cloned().collect();
+                self.write(render(&batch)).await?;
+                self.buffer.drain(..len);
+                debug!(points = len, "Wrote usage to InfluxDB");
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::sync::{Arc, Mutex};
+
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        use super::*;
+
+        async fn mock_server(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<(String, String)>>>) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let requests = Arc::new(Mutex::new(Vec::new()));
+
+            let received = requests.clone();
+            tokio::spawn(async move {
+                for status in statuses {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    let (head, body) = loop {
+                        let n = stream.read(&mut chunk).await.unwrap();
+                        buf.extend_from_slice(&chunk[..n]);
+                        let text = String::from_utf8_lossy(&buf).to_string();
+                        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                            let length = head
+                                .lines()
+                                .find_map(|line| {
+                                    line.to_ascii_lowercase()
+                                        .strip_prefix("content-length: ")
+                                        .map(|len| len.parse::<usize>().unwrap())
+                                })
+                                .unwrap_or(0);
+                            if body.len() >= length {
+                                break (head.to_string(), body.to_string());
+                            }
+                        }
+                    };
+                    received.lock().unwrap().push((head, body));
+                    stream
+                        .write_all(
+                            format!(
+                                "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                                status
+                            )
+                            .as_bytes(),
+                        )
+                        .await
+                        .unwrap();
+                }
+            });
+            (format!("http://{}", addr), requests)
+        }
+
+        fn config(url: String) -> InfluxConfig {
+            InfluxConfig {
+                enabled: true,
+                url,
+                org: "home".to_string(),
+                bucket: "usage".to_string(),
+                token: Some("secret".to_string()),
+                host: Some("box".to_string()),
+                max_batch_size: 2,
+                max_buffered_points: 4,
+                ..Default::default()
+            }
+        }
+
+        #[tokio::test]
+        async fn test_export_batches() {
+            let (url, requests) = mock_server(vec![204, 204]).await;
+            let mut exporter = InfluxExporter::new(&config(url)).unwrap();
+
+            let usage = HashMap::from([
+                ("a".to_string(), 1),
+                ("b".to_string(), 2),
+                ("c".to_string(), 3),
+            ]);
+            exporter.export(&usage).await.unwrap();
+            assert_eq!(exporter.buffered(), 0);
+
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests.len(), 2);
+            let (head, body) = &requests[0];
+            assert!(
+                head.starts_with("POST /api/v2/write?org=home&bucket=usage&precision=s HTTP/1.1")
+            );
+            assert!(
+                head.to_ascii_lowercase()
+                    .contains("authorization: token secret")
+            );
+            let lines: Vec<_> = body.lines().collect();
+            assert_eq!(lines.len(), 2);
+            assert!(lines[0].starts_with("data_usage,host=box,app=a bytes=1i "));
+            assert_eq!(requests[1].1.lines().count(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_export_retries_buffered_points() {
+            let (url, requests) = mock_server(vec![503, 204, 204]).await;
+            let mut exporter = InfluxExporter::new(&config(url)).unwrap();
+
+            let usage = HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+            assert!(matches!(
+                exporter.export(&usage).await,
+                Err(InfluxError::Status(503, _))
+            ));
+            assert_eq!(exporter.buffered(), 2);
+
+            let usage = HashMap::from([
+                ("a".to_string(), 5),
+                ("b".to_string(), 6),
+                ("c".to_string(), 7),
+            ]);
+            exporter.export(&usage).await.unwrap();
+            assert_eq!(exporter.buffered(), 0);
+            assert_eq!(exporter.dropped(), 1);
+
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests.len(), 3);
+            assert!(
+                requests[1]
+                    .1
+                    .starts_with("data_usage,host=box,app=b bytes=2i ")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_line_protocol() {
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let usage = HashMap::from([
+            ("Web Content".to_string(), 2048),
+            ("a,b=c\\".to_string(), 1),
+        ]);
+        assert_eq!(
+            render(&points(&usage, "my host", time)),
+            "data_usage,host=my\\ host,app=Web\\ Content bytes=2048i 1704067200\n\
+             data_usage,host=my\\ host,app=a\\,b\\=c\\\\ bytes=1i 1704067200\n"
+        );
+    }
+
+    #[test]
+    fn test_escape_tag() {
+        assert_eq!(escape_tag(""), "_");
+        assert_eq!(escape_tag("line\nbreak"), "line_break");
+    }
+}
@@ -0,0 +1,107 @@
+pub mod agent;
+pub mod alerts_log;
+pub mod app_names;
+pub mod backoff;
+pub mod budget;
+pub mod calibrate;
+pub mod capabilities;
+#[cfg(feature = "monitor")]
+pub mod cgroup;
+#[cfg(feature = "monitor")]
+pub mod clock;
+pub mod collector;
+pub mod compare;
+pub mod compression;
+#[cfg(feature = "monitor")]
+pub mod conditions;
+#[cfg(feature = "monitor")]
+pub mod control;
+pub mod crash;
+pub mod effective_config;
+pub mod error;
+pub mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+pub mod format;
+pub mod history;
+#[cfg(feature = "monitor")]
+pub mod http;
+pub mod i18n;
+pub mod identity;
+pub mod import;
+pub mod influx;
+pub mod logging;
+pub mod merge;
+pub mod metrics;
+pub mod monitor;
+pub mod mqtt;
+pub mod notification;
+pub mod other_monitors;
+#[cfg(feature = "arrow")]
+pub mod parquet_export;
+pub mod paths;
+pub mod persistence;
+#[cfg(feature = "monitor")]
+pub mod replay;
+pub mod report;
+pub mod self_check;
+pub mod self_metrics;
+pub mod session;
+pub mod settings;
+#[cfg(feature = "monitor")]
+pub mod status;
+pub mod system_log;
+pub mod telemetry;
+pub mod test_support;
+pub mod top;
+pub mod tracker;
+pub mod tray;
+pub mod version;
+pub mod watched_paths;
+
+pub use compression::CompressionError;
+pub use error::DataGuardianError;
+#[cfg(feature = "monitor")]
+pub use monitor::MonitorError;
+pub use notification::NotificationError;
+pub use persistence::PersistenceError;
+pub use settings::SettingsError;
+pub use version::VERSION_INFO;
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::{compress_usage_data, decompress_usage_data};
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_light_modules() {
+        let settings = Settings::default();
+        settings.validate().unwrap();
+
+        let data = [("firefox".to_string(), 1024)].into();
+        let compressed = compress_usage_data(&data).unwrap();
+        assert_eq!(decompress_usage_data(&compressed).unwrap(), data);
+    }
+
+    #[cfg(feature = "monitor")]
+    #[test]
+    fn test_monitor_modules() {
+        let settings = Settings::default();
+        let source = settings.source().unwrap();
+        let mut sampler = crate::monitor::Sampler::new();
+        let deltas = sampler.advance(source.read().unwrap());
+        assert!(deltas.usage.is_empty());
+
+        let status = crate::status::StatusState::new(&settings);
+        assert!(status.report(None, None).is_none());
+    }
+
+    #[cfg(feature = "monitor")]
+    #[tokio::test]
+    async fn test_settings_load() {
+        let settings = Settings::new().unwrap();
+        assert!(settings.data_limit > 0);
+        assert!(settings.check_interval_seconds > 0);
+    }
+}
@@ -0,0 +1,196 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+pub const DEFAULT_LOG_MAX_SIZE: u64 = 10 * 1024 * 1024;
+pub const DEFAULT_LOG_MAX_FILES: usize = 5;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Pretty,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    #[default]
+    Daily,
+    Size,
+}
+
+#[derive(Debug)]
+pub struct SizeRotatingWriter {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl SizeRotatingWriter {
+    pub fn new(path: impl Into<PathBuf>, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let path = path.into();
+        let file = open_append(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_size,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.rotated_path(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+            self.file = open_append(&self.path)?;
+        }
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+pub fn file_writer(
+    path: &Path,
+    rotation: LogRotation,
+    max_size: u64,
+    max_files: usize,
+) -> io::Result<Box<dyn Write + Send>> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+
+    let rotation = match rotation {
+        LogRotation::Size => {
+            return Ok(Box::new(SizeRotatingWriter::new(
+                path, max_size, max_files,
+            )?));
+        }
+        LogRotation::Never => Rotation::NEVER,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+    };
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "log file has no name"))?;
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(file_name.to_string_lossy());
+    if max_files > 0 {
+        builder = builder.max_log_files(max_files);
+    }
+    let appender = builder
+        .build(path.parent().unwrap_or_else(|| Path::new(".")))
+        .map_err(io::Error::other)?;
+    Ok(Box::new(appender))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_size_rotation() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("dg.log");
+        let mut writer = SizeRotatingWriter::new(&path, 10, 2).unwrap();
+
+        for line in ["aaaaaaa\n", "bbbbbbb\n", "ccccccc\n", "ddddddd\n"] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ddddddd\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("dg.log.1")).unwrap(),
+            "ccccccc\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("dg.log.2")).unwrap(),
+            "bbbbbbb\n"
+        );
+        assert!(!dir.path().join("dg.log.3").exists());
+    }
+
+    #[test]
+    fn test_size_rotation_resumes_existing_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("dg.log");
+        fs::write(&path, "existing\n").unwrap();
+
+        let mut writer = SizeRotatingWriter::new(&path, 12, 1).unwrap();
+        writer.write_all(b"next\n").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "next\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("dg.log.1")).unwrap(),
+            "existing\n"
+        );
+    }
+
+    #[test]
+    fn test_daily_file_writer() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("logs").join("dg.log");
+        let mut writer = file_writer(&path, LogRotation::Daily, 0, 3).unwrap();
+        writer.write_all(b"hello\n").unwrap();
+        writer.flush().unwrap();
+
+        let files: Vec<_> = fs::read_dir(dir.path().join("logs"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].starts_with("dg.log."));
+    }
+}
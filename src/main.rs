@@ -1,126 +1,322 @@
-mod data_guardian;
+mod cli;
+#[cfg(feature = "dashboard")]
+mod dashboard;
 
 use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::io::{self, IsTerminal};
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::Parser;
 use color_eyre::Result;
-use color_eyre::eyre::Context;
-use data_guardian::settings::Settings;
-use directories::ProjectDirs;
-use sysinfo::{Pid, System};
-use tokio::time::{Duration, interval};
-use tracing::{debug, error, info, instrument};
+use color_eyre::eyre::{Context, eyre};
+use data_guardian::settings::{Settings, SettingsChanged, SettingsFieldChange};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time::{Duration, Instant};
+use tracing::{debug, error, info, instrument, warn};
+use tracing_appender::non_blocking::WorkerGuard;
 
+use cli::{Cli, Command, ConfigCommand, SettingsOverrides};
 use data_guardian::{
-    compression,
-    notification::{self, NotificationError},
+    VERSION_INFO,
+    alerts_log::{self, AlertsLog},
+    app_names::AppNames,
+    backoff::{self, Backoff},
+    capabilities::{self, Capabilities},
+    clock::{self, Clock, ClockGap, GapDetector, Ticker},
+    compression::{self, CompressionConfig, Dictionary, DictionaryKind},
+    conditions::{Conditions, PauseReason},
+    control::{self, ControlEndpoint, ControlMessage, ControlRequest, ControlResponse},
+    crash::{self, CrashGuard, CrashMarker},
+    effective_config::EffectiveConfig,
+    events::{Event, EventFilter},
+    history::{NotificationHistory, NotificationRecord},
+    logging::{self, LogFormat},
+    metrics::Metrics,
+    monitor::{self, Backend, CounterSource, Sampler},
+    notification::{
+        self, Alert, AlertOutcome, ChannelKind, DEFAULT_APP_NAME, NotificationAction,
+        NotificationError, NotificationManager, SNOOZE_DURATION, Settled,
+    },
+    other_monitors::{self, Coexistence},
+    paths::{self, PathSource, ResolvedPath},
+    persistence::{self, PersistenceError, PersistencePaths},
+    report,
+    self_check::{self, SelfCheck},
+    self_metrics::{self, PersistStats, TickStats},
+    status::{self, StatusState},
+    telemetry,
+    tracker::{self, AlertFingerprint, UsageTracker},
+    version,
+    watched_paths::DirSize,
 };
 
-type ProcessData = HashMap<Pid, (String, u64)>;
-type UsageData = HashMap<String, u64>;
+/// Usage data smaller than this has too little repetition to train on.
+const DICTIONARY_TRAINING_MIN_BYTES: usize = 16 * 1024;
 
-#[derive(Debug)]
-struct PersistenceConfig {
-    data_dir: PathBuf,
-    file_name: &'static str,
+static STATE_DIR: OnceLock<PathBuf> = OnceLock::new();
+static RUNTIME_DIR: OnceLock<PathBuf> = OnceLock::new();
+static APP_NAMES: OnceLock<AppNames> = OnceLock::new();
+
+fn persistence_paths() -> Option<PersistencePaths> {
+    STATE_DIR.get().map(PersistencePaths::new)
 }
 
-impl PersistenceConfig {
-    fn new() -> Option<Self> {
-        ProjectDirs::from("com", "DataGuardian", "DataGuardian").map(|dirs| Self {
-            data_dir: dirs.data_dir().to_path_buf(),
-            file_name: "usage.dat",
-        })
+fn require_persistence_paths() -> Result<PersistencePaths, PersistenceError> {
+    persistence_paths().ok_or(PersistenceError::NoStateDir)
+}
+
+fn control_endpoint() -> Option<ControlEndpoint> {
+    RUNTIME_DIR
+        .get()
+        .map(|dir| ControlEndpoint::for_platform(dir))
+}
+
+/// Fails unless the state and runtime directories can be written to, copies
+/// usage data from where older versions kept it, then logs where settings,
+/// state and the control socket live.
+fn check_paths(
+    config: Option<&ResolvedPath>,
+    state_dir: Option<&ResolvedPath>,
+    runtime_dir: Option<&ResolvedPath>,
+) -> Result<()> {
+    let state_dir = state_dir.ok_or(PersistenceError::NoStateDir)?;
+    let runtime_dir = runtime_dir.unwrap_or(state_dir);
+    persistence::ensure_writable(&state_dir.path)?;
+    persistence::ensure_writable(&runtime_dir.path)?;
+    if state_dir.source == PathSource::ProjectDirs {
+        migrate_legacy_data(&state_dir.path);
     }
+    info!(
+        state_dir = ?state_dir.path,
+        state_dir_source = %state_dir.source,
+        runtime_dir = ?runtime_dir.path,
+        runtime_dir_source = %runtime_dir.source,
+        config = ?config.map(|config| &config.path),
+        config_source = config.map(|config| config.source.as_str()),
+        config_found = config.is_some_and(|config| config.path.exists()),
+        "Using directory layout"
+    );
+    Ok(())
+}
 
-    fn data_path(&self) -> PathBuf {
-        self.data_dir.join(self.file_name)
+/// Writes `effective-config.json` for support requests. A failure is only
+/// logged.
+fn write_effective_config(
+    settings: &Settings,
+    config: Option<&ResolvedPath>,
+    dirs: &[(&str, ResolvedPath)],
+    overrides: &[SettingsFieldChange],
+    adjustments: &[SettingsFieldChange],
+) {
+    let Some(state_dir) = STATE_DIR.get() else {
+        return;
+    };
+    let snapshot = dirs.iter().fold(
+        EffectiveConfig::new(settings, config, overrides, adjustments),
+        |snapshot, (name, dir)| snapshot.with_path(name, dir),
+    );
+    match snapshot.write(state_dir) {
+        Ok(path) => debug!(?path, "Wrote effective configuration"),
+        Err(e) => warn!(error = %e, "Failed to write effective configuration"),
+    }
+}
+
+/// Copies usage data from the per-user data directory, where versions before
+/// the state directory kept it. A failure is logged and leaves the old files
+/// in place, to be tried again on the next start.
+fn migrate_legacy_data(state_dir: &Path) {
+    let Some(legacy) = paths::legacy_data_dir() else {
+        return;
+    };
+    let config = PersistencePaths::new(state_dir);
+    let files = [
+        config.data_path(),
+        config.backup_path(),
+        config.history_path(),
+        config.dictionary_path(),
+        config.alerts_log_path(),
+        config.crash_marker_path(),
+    ];
+    let names: Vec<_> = files
+        .iter()
+        .filter_map(|path| path.file_name()?.to_str())
+        .collect();
+    match paths::migrate(&legacy, state_dir, &names) {
+        Ok(copied) if !copied.is_empty() => {
+            info!(from = ?legacy, to = ?state_dir, files = ?copied, "Copied usage data to the state directory");
+        }
+        Ok(_) => {}
+        Err(e) => {
+            warn!(from = ?legacy, to = ?state_dir, error = %e, "Failed to copy usage data to the state directory")
+        }
     }
 }
 
 #[instrument]
-async fn load_persisted_data() -> Option<UsageData> {
-    let config = PersistenceConfig::new()?;
-    let data_path = config.data_path();
+async fn load_persisted_data() -> Option<UsageTracker> {
+    let tracker = persistence::load(&persistence_paths()?).await?;
+    Some(normalize_names(tracker))
+}
+
+/// Like `load_persisted_data`, but fails when data files exist and none of
+/// them can be read, rather than treating that as no data.
+async fn read_persisted_data() -> Result<Option<UsageTracker>> {
+    let loaded = persistence::read_usage(&require_persistence_paths()?).await;
+    Ok(readable_usage(loaded)?.map(normalize_names))
+}
+
+/// The usage that was read, `None` when there are no data files, or an
+/// error naming the file that could not be read.
+fn readable_usage(loaded: persistence::LoadedUsage) -> Result<Option<UsageTracker>> {
+    match (loaded.tracker, loaded.unreadable.first()) {
+        (Some(tracker), _) => Ok(Some(tracker)),
+        (None, Some(file)) => Err(eyre!(
+            "Failed to read {}: {}",
+            file.path.display(),
+            file.error
+        )),
+        (None, None) => Ok(None),
+    }
+}
+
+fn normalize_names(tracker: UsageTracker) -> UsageTracker {
+    match APP_NAMES.get() {
+        Some(names) => names.normalize_tracker(tracker).0,
+        None => tracker,
+    }
+}
+
+#[instrument(skip(data))]
+async fn save_persisted_data(data: &UsageTracker) -> Result<u64> {
+    Ok(persistence::save(&require_persistence_paths()?, data).await?)
+}
 
-    if !data_path.exists() {
-        debug!(?data_path, "No existing usage data found");
+#[instrument(skip(data))]
+async fn save_on_shutdown(
+    data: &mut UsageTracker,
+    compression: CompressionConfig,
+) -> Result<PathBuf> {
+    let paths = require_persistence_paths()?;
+    let now = Utc::now();
+    let compressed = data
+        .to_saved_compressed(now, version::VERSION, compression)
+        .context("Failed to compress usage data")?;
+    let path = persistence::write_with_fallback(&paths, &compressed).await?;
+    data.mark_saved(now, version::VERSION);
+    Ok(path)
+}
+
+#[derive(Debug, Default)]
+struct SaveGuard {
+    dirty: bool,
+    last_hash: Option<u64>,
+}
+
+impl SaveGuard {
+    fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn should_write(&mut self, compressed: &[u8], force: bool) -> bool {
+        self.dirty = false;
+        force || self.last_hash != Some(content_hash(compressed))
+    }
+
+    fn written(&mut self, compressed: &[u8]) {
+        self.last_hash = Some(content_hash(compressed));
+    }
+}
+
+fn content_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[instrument]
+async fn load_notification_history() -> Option<Vec<NotificationRecord>> {
+    let config = persistence_paths()?;
+    let history_path = config.history_path();
+
+    if !history_path.exists() {
+        debug!(?history_path, "No existing notification history found");
         return None;
     }
 
-    debug!(?data_path, "Loading persisted usage data");
-    match tokio::fs::read(&data_path).await {
-        Ok(contents) => {
-            debug!(size = contents.len(), "Read persisted data file");
-            match compression::decompress_usage_data(&contents) {
-                Ok(data) => {
-                    debug!(entries = data.len(), "Successfully loaded usage data");
-                    Some(data)
-                }
-                Err(e) => {
-                    error!(error = %e, "Failed to decompress persisted data");
-                    None
-                }
+    match tokio::fs::read(&history_path).await {
+        Ok(contents) => match compression::decompress_json::<Vec<NotificationRecord>>(&contents) {
+            Ok(records) => {
+                debug!(
+                    entries = records.len(),
+                    "Successfully loaded notification history"
+                );
+                Some(records)
             }
-        }
+            Err(e) => {
+                error!(error = %e, "Failed to decompress notification history");
+                None
+            }
+        },
         Err(e) => {
-            error!(error = %e, "Failed to read persisted data file");
+            error!(error = %e, "Failed to read notification history file");
             None
         }
     }
 }
 
-#[instrument(skip(data))]
-async fn save_persisted_data(data: &UsageData) -> Result<()> {
-    let config = PersistenceConfig::new()
-        .ok_or_else(|| color_eyre::eyre::eyre!("Failed to get project directories"))?;
+/// Entries of `alerts.log`, which outlive crashes and restarts.
+async fn load_alerts_log() -> Vec<NotificationRecord> {
+    let Some(config) = persistence_paths() else {
+        return Vec::new();
+    };
+    let path = config.alerts_log_path();
+    let entries = tokio::task::spawn_blocking(move || alerts_log::read_entries(&path))
+        .await
+        .unwrap_or_else(|e| Err(io::Error::other(e)));
+    match entries {
+        Ok(entries) => entries.iter().map(NotificationRecord::from).collect(),
+        Err(e) => {
+            error!(error = %e, "Failed to read the alerts log");
+            Vec::new()
+        }
+    }
+}
 
-    if !config.data_dir.exists() {
-        debug!(?config.data_dir, "Creating data directory");
-        tokio::fs::create_dir_all(&config.data_dir)
+#[instrument(skip(manager))]
+async fn save_notification_history(manager: &NotificationManager) -> Result<()> {
+    let config = require_persistence_paths()?;
+
+    if !config.state_dir.exists() {
+        tokio::fs::create_dir_all(&config.state_dir)
             .await
             .context("Failed to create data directory")?;
     }
 
-    let data_path = config.data_path();
-
-    let compressed =
-        compression::compress_usage_data(data).context("Failed to compress usage data")?;
+    let records = manager.history()?;
+    let history_path = config.history_path();
+    let compressed = compression::compress_json(&records, records.len())
+        .context("Failed to compress notification history")?;
 
-    debug!(?data_path, size = compressed.len(), "Saving usage data");
-    tokio::fs::write(&data_path, compressed)
+    debug!(
+        ?history_path,
+        entries = records.len(),
+        "Saving notification history"
+    );
+    tokio::fs::write(&history_path, compressed)
         .await
-        .context("Failed to write usage data file")?;
-
-    debug!(?data_path, "Successfully saved usage data");
+        .context("Failed to write notification history file")?;
     Ok(())
 }
 
-#[instrument]
-async fn get_current_processes() -> Result<ProcessData> {
-    tokio::task::spawn_blocking(|| {
-        let mut sys = System::new();
-        sys.refresh_all();
-
-        sys.processes()
-            .iter()
-            .map(|(pid, process)| {
-                let name = process.name().to_string_lossy().into_owned();
-                let usage = process.disk_usage();
-                (
-                    *pid,
-                    (name, usage.read_bytes.saturating_add(usage.written_bytes)),
-                )
-            })
-            .collect()
-    })
-    .await
-    .map_err(Into::into)
-}
-
 #[cfg(unix)]
 fn drop_privileges() -> Result<()> {
     use nix::unistd::{Gid, Uid, setgid, setuid};
@@ -131,65 +327,1856 @@ fn drop_privileges() -> Result<()> {
     Ok(())
 }
 
-fn setup_logging() -> Result<()> {
-    use tracing_subscriber::{EnvFilter, fmt};
-    fmt()
-        .with_env_filter(EnvFilter::from_default_env().add_directive("data_guardian=info".parse()?))
-        .with_ansi(io::stdout().is_terminal())
-        .init();
-    Ok(())
+type BoxedLayer = Box<dyn tracing_subscriber::Layer<tracing_subscriber::Registry> + Send + Sync>;
+
+fn format_layer<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'w> tracing_subscriber::fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+    use tracing_subscriber::{Layer, fmt};
+
+    match format {
+        LogFormat::Pretty => fmt::layer().with_writer(writer).with_ansi(ansi).boxed(),
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_writer(writer)
+            .boxed(),
+    }
 }
 
-async fn monitor_processes(
-    settings: &Settings,
-    app_usage: &mut UsageData,
-    prev_processes: &mut ProcessData,
-) -> Result<()> {
-    let current_processes = get_current_processes().await?;
-    let mut current_usage = UsageData::with_capacity(current_processes.len());
+/// With `json`, console logs go to stderr so stdout only carries JSON.
+fn setup_logging(settings: &Settings, daemon: bool, json: bool) -> Result<Option<WorkerGuard>> {
+    use tracing_subscriber::{EnvFilter, Layer, prelude::*};
+
+    let directive = format!(
+        "{}={level},data_guardian={level}",
+        env!("CARGO_CRATE_NAME"),
+        level = settings.log_level
+    );
+    let filter = || match std::env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(_) => EnvFilter::from_default_env(),
+        Err(_) => EnvFilter::new(&directive),
+    };
+
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+    let mut guard = None;
+    let console_is_terminal = match json {
+        true => io::stderr().is_terminal(),
+        false => io::stdout().is_terminal(),
+    };
+
+    if let Some(path) = &settings.log_file {
+        let writer = logging::file_writer(
+            path,
+            settings.log_rotation,
+            settings.log_max_size_bytes,
+            settings.log_max_files,
+        )
+        .with_context(|| format!("Failed to open log file {}", path.display()))?;
+        let (writer, file_guard) = tracing_appender::non_blocking(writer);
+        guard = Some(file_guard);
+        layers.push(
+            format_layer(settings.log_format, writer, false)
+                .with_filter(filter())
+                .boxed(),
+        );
+    }
+
+    if settings.log_file.is_none() || console_is_terminal {
+        let layer = match json {
+            true => format_layer(settings.log_format, io::stderr, console_is_terminal),
+            false => format_layer(settings.log_format, io::stdout, console_is_terminal),
+        };
+        layers.push(layer.with_filter(filter()).boxed());
+    }
+
+    #[cfg(feature = "otel")]
+    let otel_error = match settings.otel.enabled && daemon {
+        true => match telemetry::init_tracer(&settings.otel) {
+            Ok(provider) => {
+                use opentelemetry::trace::TracerProvider;
 
-    for (pid, (app_name, current_total)) in &current_processes {
-        if let Some((prev_app, prev_total)) = prev_processes.get(pid) {
-            if prev_app == app_name {
-                *current_usage.entry(app_name.clone()).or_insert(0) +=
-                    current_total.saturating_sub(*prev_total);
+                layers.push(
+                    tracing_opentelemetry::layer()
+                        .with_tracer(provider.tracer("data-guardian"))
+                        .with_filter(tracing::level_filters::LevelFilter::INFO)
+                        .boxed(),
+                );
+                None
             }
-        }
+            Err(e) => Some(e),
+        },
+        false => None,
+    };
+
+    tracing_subscriber::registry().with(layers).init();
+
+    #[cfg(feature = "otel")]
+    if let Some(e) = otel_error {
+        error!(error = %e, "Failed to initialize OpenTelemetry traces");
+    }
+    #[cfg(not(feature = "otel"))]
+    if settings.otel.enabled && daemon {
+        warn!("Ignoring [otel] settings: built without the otel feature");
     }
 
-    *prev_processes = current_processes;
+    Ok(guard)
+}
 
+/// Scans processes once. After a `gap`, the usage that built up is spread
+/// over the days the gap covered, and rate-based checks are skipped.
+#[instrument(skip_all)]
+async fn monitor_processes(daemon: &mut Daemon, gap: Option<ClockGap>) -> Result<TickStats> {
+    let Daemon {
+        settings,
+        clock,
+        notifier,
+        metrics,
+        status,
+        tracker,
+        sampler,
+        source,
+        capabilities,
+        pending_alerts,
+        ..
+    } = daemon;
+    let started = Instant::now();
+    let timeout = settings.blocking_timeout();
+    let current_processes =
+        monitor::snapshot_within(clock.as_ref(), source.clone(), timeout).await?;
+    let snapshot = started.elapsed();
+    // All of the scan's notifications share one timeout, however many go out.
+    let deadline = Instant::now() + timeout;
+    status.set_session(source.session());
+    let process_count = current_processes.len();
+    metrics.set_process_count(process_count);
+
+    let monitor::UsageDelta {
+        usage: mut current_usage,
+        mut written,
+        rejected,
+        resources,
+    } = sampler.advance(current_processes);
+    if !tracks_apps(settings, capabilities) {
+        current_usage.retain(|app, _| !monitor::is_app(app));
+    }
+    let tick_apps = current_usage.len();
+    let (current_usage, overflow_apps) =
+        tracker.bound_apps(current_usage, settings.max_tracked_apps, |total, delta| {
+            *total = total.saturating_add(delta)
+        });
+    if overflow_apps > 0 && !tracker.apps.contains_key(tracker::OTHER_APP) {
+        warn!(
+            max_tracked_apps = settings.max_tracked_apps,
+            "Too many applications, counting the rest as {}",
+            tracker::OTHER_APP
+        );
+    }
+    // Writes of apps folded into (other) are counted there as well.
+    let overflow_written = written
+        .extract_if(|app, _| !current_usage.contains_key(app))
+        .fold(0u64, |sum, (_, bytes)| sum.saturating_add(bytes));
+    if overflow_written > 0 {
+        *written.entry(tracker::OTHER_APP.to_string()).or_default() += overflow_written;
+    }
+    let delta_bytes = monitor::total(&current_usage);
+    let rejected_bytes = monitor::total(&rejected);
+    for (app, bytes) in &rejected {
+        warn!(%app, bytes = %settings.format_bytes(*bytes), "Ignoring implausible usage delta, counters were likely reset");
+    }
+    metrics.delta_rejected(rejected_bytes);
+
+    let now = Utc::now();
+    let previous_scan = tracker.state.last_scan;
+    let tick = tracker.record_scan(now);
+    let timezone = settings.reset_timezone();
+    if tracker.roll_over_at(settings.reset_period, timezone, now) {
+        info!(period = ?tracker.period_start, timezone = ?tracker.timezone, "Starting new usage period");
+    }
+
+    let period_end = tracker.period_end(settings.reset_period, timezone);
+    let today = timezone.date(now);
+    let display_names = source.display_names();
+    if gap.is_some() {
+        tracker.clear_samples();
+    }
+    let budget_delta = current_usage
+        .iter()
+        .filter(|(app, _)| monitor::is_app(app))
+        .fold(0u64, |sum, (_, delta)| sum.saturating_add(*delta));
+    let deltas = current_usage.clone();
+    let grace = settings.new_app_grace();
+    // Sent together after the scan, so the notifier takes its locks once.
+    let mut limit_alerts = Vec::new();
+    let mut limit_details = Vec::new();
     for (app, delta) in current_usage {
-        let total_usage = app_usage.entry(app.clone()).or_insert(0);
-        *total_usage += delta;
+        tracker.mark_seen(&app, now);
+        if delta > 0 {
+            tracker.mark_active(&app, now);
+        }
+        let total_usage = tracker.add_usage(&app, delta);
+        let app_written = written.get(&app).copied().unwrap_or(0);
+        match gap {
+            Some(gap) => {
+                tracker.add_daily_usage_between(&app, delta, app_written, gap.since, now, timezone)
+            }
+            None => tracker.add_daily_usage(&app, delta, app_written, today),
+        }
+        let limit = settings.effective_limit(&app);
+        // Disks and interfaces only alert on their own limit.
+        let Some(limit) = limit.filter(|_| monitor::is_app(&app)) else {
+            if let Some(limit) = limit
+                && total_usage > limit.value
+            {
+                let alert = Alert::new(&app).with_usage(total_usage, limit.value);
+                if send_alert_within(notifier, metrics, &alert, deadline).await {
+                    info!(%app, usage = %settings.format_bytes(total_usage), limit = %settings.format_bytes(limit.value), source = limit.source.as_str(), "Exceeded data limit");
+                }
+            }
+            continue;
+        };
+        tracker.record_sample(&app, now);
+        let in_grace = tracker.in_grace(&app, grace, now);
+
+        if gap.is_none()
+            && settings.anomaly.enabled
+            && let Some(baseline) = tracker.observe_delta(&app, delta, &settings.anomaly)
+        {
+            let alert = Alert::new(&app)
+                .with_display_name(display_names.get(&app).cloned())
+                .with_anomaly(delta, baseline.mean.round() as u64);
+            if !in_grace && send_alert_within(notifier, metrics, &alert, deadline).await {
+                info!(%app, delta = %settings.format_bytes(delta), mean = %settings.format_bytes(baseline.mean.round() as u64), std_dev = %settings.format_bytes(baseline.std_dev().round() as u64), "Unusual data usage detected");
+            }
+        }
+
+        let data_limit = limit.value;
+        if total_usage > data_limit {
+            let (alert, fingerprint, streak) = limit_alert(
+                tracker,
+                settings,
+                &app,
+                display_names.get(&app).cloned(),
+                total_usage,
+                data_limit,
+                now,
+            );
+            if !tracker.is_new_alert(&app, &fingerprint) {
+                debug!(%app, multiple = fingerprint.multiple, "Already alerted at this multiple of the limit this period");
+            } else if in_grace {
+                debug!(%app, usage = %settings.format_bytes(total_usage), "Holding back alert until the new application's grace period ends");
+            } else {
+                limit_alerts.push(alert);
+                limit_details.push((fingerprint, total_usage, streak));
+            }
+        } else if gap.is_none()
+            && !in_grace
+            && settings.forecast_alerts
+            && let Some(projected) = period_end
+                .and_then(|end| tracker.forecast(&app, end, now))
+                .filter(|projected| *projected > data_limit)
+        {
+            let alert = Alert::new(&app)
+                .with_display_name(display_names.get(&app).cloned())
+                .with_usage(total_usage, data_limit)
+                .with_forecast(projected, settings.reset_period);
+            if send_alert_within(notifier, metrics, &alert, deadline).await {
+                info!(%app, usage = %settings.format_bytes(total_usage), projected = %settings.format_bytes(projected), "Application is on pace to exceed data limit");
+            }
+        }
+    }
+
+    // An app that went over its limit during its grace period and has since
+    // exited is alerted when the grace ends, like one that is still running.
+    if let Some(previous_scan) = previous_scan {
+        for app in tracker.grace_ended(grace, previous_scan, now) {
+            if deltas.contains_key(&app) {
+                continue;
+            }
+            let usage = tracker.apps[&app].bytes;
+            let Some(limit) = settings
+                .effective_limit(&app)
+                .filter(|limit| usage > limit.value && monitor::is_app(&app))
+            else {
+                continue;
+            };
+            let (alert, fingerprint, streak) = limit_alert(
+                tracker,
+                settings,
+                &app,
+                display_names.get(&app).cloned(),
+                usage,
+                limit.value,
+                now,
+            );
+            if tracker.is_new_alert(&app, &fingerprint) {
+                limit_alerts.push(alert);
+                limit_details.push((fingerprint, usage, streak));
+            }
+        }
+    }
 
-        if *total_usage > settings.data_limit {
-            match notification::alert_user(&app) {
-                Ok(()) => info!(%app, %total_usage, "Application exceeded data limit"),
-                Err(NotificationError::Cooldown) => {
-                    debug!(%app, %total_usage, "Skipping notification due to cooldown");
+    // Alerts still being retried, or not tried by the deadline, are settled
+    // by a later scan.
+    let (attempted, settled) = notifier.alert_batch_settled(&limit_alerts);
+    let _ = tokio::time::timeout_at(deadline, attempted).await;
+    pending_alerts.extend(
+        limit_alerts
+            .into_iter()
+            .zip(limit_details)
+            .zip(settled)
+            .map(
+                |((alert, (fingerprint, usage, streak)), settled)| PendingAlert {
+                    alert,
+                    fingerprint,
+                    usage,
+                    streak,
+                    settled,
+                },
+            ),
+    );
+    settle_limit_alerts(settings, metrics, tracker, pending_alerts);
+
+    // Only the highest pending milestone is alerted; it covers the others.
+    if let Some(&milestone) = tracker
+        .add_budget_usage(&settings.budget, budget_delta, today)
+        .last()
+        && let (Some(total), Some(start)) =
+            (settings.budget.total_bytes, tracker.budget.period_start)
+    {
+        let used = tracker.budget.used;
+        let days_left = (settings.budget.period_end(start) - today).num_days();
+        let alert = Alert::new(DEFAULT_APP_NAME).with_budget(used, total, milestone, days_left);
+        if send_alert_within(notifier, metrics, &alert, deadline).await {
+            info!(used = %settings.format_bytes(used), total = %settings.format_bytes(total), milestone, days_left, "Data budget milestone reached");
+            tracker.budget.mark_notified(&settings.budget, milestone);
+        }
+    }
+
+    for (app, duration) in tracker.record_recoveries(
+        |app| settings.effective_limit(app).map(|limit| limit.value),
+        now,
+    ) {
+        info!(%app, over_for = %humantime::format_duration(duration.to_std().unwrap_or_default()), "Application is back under its data limit");
+    }
+
+    let (resources, _) =
+        tracker.bound_apps(resources, settings.max_tracked_apps, |total, usage| {
+            let add = |total: Option<u64>, value: Option<u64>| match (total, value) {
+                (Some(total), value) => Some(total.saturating_add(value.unwrap_or(0))),
+                (None, value) => value,
+            };
+            total.cpu_time_ms = add(total.cpu_time_ms, usage.cpu_time_ms);
+            total.memory_bytes = add(total.memory_bytes, usage.memory_bytes);
+        });
+    for (app, usage) in resources {
+        if let Some(cpu_ms) = usage.cpu_time_ms {
+            let cpu_seconds = tracker.add_cpu_time(&app, cpu_ms) / 1000;
+            if let Some(limit) = settings.cpu_limit_seconds
+                && cpu_seconds > limit
+            {
+                let alert = Alert::new(&app)
+                    .with_display_name(display_names.get(&app).cloned())
+                    .with_cpu_time(cpu_seconds, limit);
+                if send_alert_within(notifier, metrics, &alert, deadline).await {
+                    info!(%app, %cpu_seconds, "Application exceeded CPU time limit");
                 }
-                Err(e) => {
-                    error!(error = %e, app = %app, "Failed to send notification");
+            }
+        }
+        if let Some(memory) = usage.memory_bytes {
+            tracker.record_memory(&app, memory);
+            if let Some(limit) = settings.memory_limit_bytes
+                && memory > limit
+            {
+                let alert = Alert::new(&app)
+                    .with_display_name(display_names.get(&app).cloned())
+                    .with_memory(memory, limit);
+                if send_alert_within(notifier, metrics, &alert, deadline).await {
+                    info!(%app, memory = %settings.format_bytes(memory), "Application exceeded memory limit");
                 }
             }
         }
     }
 
+    let usage = tracker.usage();
+    metrics.set_app_usage(&usage);
+    metrics.observe_scan(started.elapsed());
+    status.record_scan_usage(usage, &deltas, now);
+    status.update_display_names(display_names);
+    status.update_resources(tracker.resources());
+    status.update_exceedances(tracker.exceedances());
+    status.update_last_active(tracker.last_active());
+
+    Ok(TickStats {
+        time: Utc::now(),
+        tick,
+        snapshot_ms: self_metrics::millis(snapshot),
+        tick_ms: self_metrics::millis(started.elapsed()),
+        lag_ms: 0.0,
+        processes: process_count,
+        tracked_apps: tracker.apps.len(),
+        tick_apps,
+        overflow_apps,
+        delta_bytes,
+        rejected_bytes,
+        default_interface: None,
+    })
+}
+
+/// The data limit alert for `app` with `usage` over `limit`, with the
+/// fingerprint and streak to record once it is delivered.
+fn limit_alert(
+    tracker: &mut UsageTracker,
+    settings: &Settings,
+    app: &str,
+    display_name: Option<String>,
+    usage: u64,
+    limit: u64,
+    now: DateTime<Utc>,
+) -> (Alert, AlertFingerprint, u32) {
+    let today = settings.reset_timezone().date(now);
+    let streak = tracker.record_exceedance(app, settings.reset_period, now, today);
+    let severity = settings.escalation.severity(streak);
+    let alert = Alert::new(app)
+        .with_display_name(display_name)
+        .with_usage(usage, limit)
+        .with_streak(streak, settings.reset_period, severity);
+    (alert, tracker.fingerprint(usage, limit, severity), streak)
+}
+
+/// Records each limit alert whose final result is known, and keeps the
+/// others for the next scan.
+fn settle_limit_alerts(
+    settings: &Settings,
+    metrics: &Metrics,
+    tracker: &mut UsageTracker,
+    pending: &mut Vec<PendingAlert>,
+) {
+    pending.retain_mut(|pending| {
+        let result = match pending.settled.try_recv() {
+            Ok(result) => result,
+            Err(oneshot::error::TryRecvError::Empty) => return true,
+            Err(oneshot::error::TryRecvError::Closed) => Err(NotificationError::ShowError(
+                "Notification stopped before it was sent".to_string(),
+            )),
+        };
+        if record_alert_result(metrics, &pending.alert, result) {
+            let app = &pending.alert.app;
+            info!(%app, usage = %settings.format_bytes(pending.usage), streak = pending.streak, "Application exceeded data limit");
+            tracker.record_alert(app, pending.fingerprint);
+        }
+        false
+    });
+}
+
+fn send_alert(notifier: &NotificationManager, metrics: &Metrics, alert: &Alert) -> bool {
+    record_alert_result(metrics, alert, notifier.alert(alert))
+}
+
+/// Like `send_alert`, but sends on the blocking pool and stops waiting
+/// at `deadline`, so a hung notification cannot hold up the scan. Once the
+/// deadline has passed, alerts are skipped without being sent.
+async fn send_alert_within(
+    notifier: &NotificationManager,
+    metrics: &Metrics,
+    alert: &Alert,
+    deadline: Instant,
+) -> bool {
+    let now = Instant::now();
+    if now >= deadline {
+        metrics.alert_suppressed();
+        debug!(app = %alert.app, kind = alert.kind.as_str(), "Skipping notification, this scan is out of time for notifications");
+        return false;
+    }
+    let result = tokio::time::timeout_at(deadline, notifier.alert_async(alert))
+        .await
+        .unwrap_or(Err(NotificationError::TimedOut(deadline - now)));
+    record_alert_result(metrics, alert, result)
+}
+
+/// Counts and logs the result of sending `alert`. Returns whether it was sent.
+fn record_alert_result(metrics: &Metrics, alert: &Alert, result: AlertOutcome) -> bool {
+    let app = &alert.app;
+    let kind = alert.kind.as_str();
+    match result {
+        Ok(()) => {
+            metrics.alert_sent();
+            return true;
+        }
+        Err(NotificationError::Cooldown) => {
+            metrics.alert_suppressed();
+            debug!(%app, kind, "Skipping notification due to cooldown");
+        }
+        Err(NotificationError::Snoozed) => {
+            metrics.alert_suppressed();
+            debug!(%app, kind, "Skipping notification while snoozed");
+        }
+        Err(NotificationError::RateLimited) => {
+            metrics.alert_suppressed();
+            debug!(%app, kind, "Skipping notification due to the global rate limit");
+        }
+        Err(NotificationError::ChannelsPaused) => {
+            // Already logged once when the channels were paused.
+            metrics.alert_failed();
+            debug!(%app, kind, "Skipping notification while its channels are paused");
+        }
+        Err(e) => {
+            metrics.alert_failed();
+            error!(error = %e, %app, kind, "Failed to send notification");
+        }
+    }
+    false
+}
+
+async fn handle_notification_action(
+    settings: &Settings,
+    notifier: &NotificationManager,
+    tracker: &UsageTracker,
+    display_names: &HashMap<String, String>,
+    app: &str,
+    action: NotificationAction,
+) -> Result<()> {
+    match action {
+        NotificationAction::Snooze => notifier.snooze(app, SNOOZE_DURATION)?,
+        NotificationAction::SnoozeAll => notifier.snooze_all(SNOOZE_DURATION)?,
+        NotificationAction::ShowReport => {
+            let config = require_persistence_paths()?;
+            let report_path = config.state_dir.join("report.txt");
+            tokio::fs::create_dir_all(&config.state_dir)
+                .await
+                .context("Failed to create data directory")?;
+            tokio::fs::write(
+                &report_path,
+                cli::render_report(
+                    &tracker.usage(),
+                    display_names,
+                    &tracker.resources(),
+                    settings,
+                ),
+            )
+            .await
+            .context("Failed to write usage report")?;
+
+            debug!(?report_path, "Opening usage report");
+            tokio::process::Command::new("xdg-open")
+                .arg(&report_path)
+                .spawn()
+                .context("Failed to open usage report")?;
+        }
+        NotificationAction::Quit => {}
+    }
     Ok(())
 }
 
+/// Warns about each setting that clamp mode moved into range.
+fn log_clamped(adjustments: &[SettingsFieldChange]) {
+    for change in adjustments {
+        warn!(%change, "Clamped out-of-range setting");
+    }
+}
+
+fn notify_clamped(notifier: &NotificationManager, adjustments: &[SettingsFieldChange]) {
+    if adjustments.is_empty() {
+        return;
+    }
+    let fields = adjustments
+        .iter()
+        .map(|change| change.field.clone())
+        .collect();
+    let alert = Alert::new(DEFAULT_APP_NAME).with_clamped(fields);
+    if let Err(e) = notifier.alert(&alert) {
+        warn!(error = %e, "Failed to send clamped settings notification");
+    }
+}
+
+#[cfg(feature = "metrics")]
+async fn spawn_metrics_listener(addr: std::net::SocketAddr, metrics: Arc<Metrics>) {
+    match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => {
+            info!(%addr, "Serving Prometheus metrics");
+            tokio::spawn(async move {
+                if let Err(e) = data_guardian::metrics::serve(listener, metrics).await {
+                    error!(error = %e, "Metrics listener stopped");
+                }
+            });
+        }
+        Err(e) => error!(error = %e, %addr, "Failed to bind metrics listener"),
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+async fn spawn_metrics_listener(addr: std::net::SocketAddr, _metrics: Arc<Metrics>) {
+    warn!(%addr, "Ignoring metrics_listen_addr: built without the metrics feature");
+}
+
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     color_eyre::install()?;
-    setup_logging()?;
+    let cli = Cli::parse();
+    let check = matches!(cli.command, Some(Command::Check));
+    let result = run_command(cli).await;
+    // `dg check` keeps exit codes 1 to 3 for its answers.
+    if check && let Err(e) = &result {
+        eprintln!("Error: {e:?}");
+        return Ok(exit_code(report::EXIT_CHECK_ERROR));
+    }
+    result
+}
+
+fn exit_code(code: i32) -> ExitCode {
+    u8::try_from(code).map_or(ExitCode::FAILURE, ExitCode::from)
+}
+
+/// Runs the command and returns the code to exit with, once logs are
+/// flushed and telemetry is shut down.
+async fn run_command(cli: Cli) -> Result<ExitCode> {
+    let config_path = paths::config_path(cli.config.as_deref());
+    let (mut settings, adjustments) =
+        Settings::load_adjusted(config_path.as_ref()).context("Failed to load settings")?;
+    let loaded = settings.clone();
+    cli.overrides
+        .apply(&mut settings)
+        .context("Invalid command line options")?;
+    let overrides = loaded.diff(&settings);
+    let state_dir = paths::state_dir(cli.data_dir.as_deref(), settings.data_dir.as_deref());
+    let runtime_dir = state_dir
+        .as_ref()
+        .map(|state_dir| paths::runtime_dir(settings.runtime_dir.as_deref(), state_dir));
+    if let (Some(state_dir), Some(runtime_dir)) = (&state_dir, &runtime_dir) {
+        let _ = STATE_DIR.set(state_dir.path.clone());
+        let _ = RUNTIME_DIR.set(runtime_dir.path.clone());
+    }
+    let _ = APP_NAMES.set(settings.app_names()?);
+    if cli.json
+        && matches!(
+            cli.command,
+            None | Some(Command::Dashboard { .. } | Command::Serve { .. })
+        )
+    {
+        return Err(eyre!("--json is not supported by this command"));
+    }
+    let _log_guard = match cli.command {
+        Some(Command::Dashboard { .. }) => None,
+        _ => setup_logging(&settings, cli.command.is_none(), cli.json)?,
+    };
+    log_clamped(&adjustments);
 
     #[cfg(unix)]
     drop_privileges().context("Failed to drop privileges")?;
 
-    let settings = Settings::new().context("Failed to load settings")?;
-    let mut app_usage = load_persisted_data().await.unwrap_or_default();
-    let mut prev_processes = ProcessData::new();
+    let mut code = 0;
+    let result = match cli.command {
+        Some(Command::History { since }) => {
+            cli::print_history(since.map(Into::into), cli.json).await
+        }
+        Some(Command::Report {
+            compare: true,
+            format,
+            ..
+        }) => {
+            let format = match cli.json {
+                true => cli::ReportFormat::Json,
+                false => format,
+            };
+            cli::print_comparison(&settings, format).await
+        }
+        Some(Command::Report { stats, .. }) => cli::print_report(&settings, stats, cli.json).await,
+        Some(Command::Status {
+            effective_config: true,
+            ..
+        }) => cli::print_effective_config(cli.json),
+        Some(Command::Status { fresh, .. }) => cli::print_status(&settings, cli.json, fresh).await,
+        Some(Command::Reset { app }) => cli::reset_app(&app, cli.json).await,
+        Some(Command::Cooldowns) => cli::print_cooldowns(cli.json).await,
+        Some(Command::ClearCooldown { app }) => cli::clear_cooldown(app, cli.json).await,
+        Some(Command::Follow { only, app }) => {
+            cli::follow(&settings, EventFilter { only, app }, cli.json).await
+        }
+        Some(Command::Calibrate {
+            duration,
+            interval,
+            write,
+        }) => {
+            let write = match write {
+                true => Some(
+                    config_path
+                        .as_ref()
+                        .ok_or_else(|| eyre!("No config file location; pass --config"))?,
+                ),
+                false => None,
+            };
+            cli::calibrate(&settings, duration.into(), interval.into(), write, cli.json).await
+        }
+        Some(Command::Record {
+            duration,
+            interval,
+            output,
+        }) => {
+            let interval = interval.map_or(
+                Duration::from_secs(settings.check_interval_seconds),
+                Into::into,
+            );
+            cli::record(&settings, duration.into(), interval, &output, cli.json).await
+        }
+        Some(Command::Replay {
+            trace,
+            settings: replay_settings,
+        }) => {
+            let replay_settings = match replay_settings {
+                Some(path) => Settings::from_file(&path)
+                    .with_context(|| format!("Failed to read settings from {}", path.display()))?,
+                None => settings.clone(),
+            };
+            cli::replay(replay_settings, &trace, cli.json)
+        }
+        Some(Command::Config { command }) => match command {
+            ConfigCommand::Get { key } => cli::config_get(&settings, &key, cli.json),
+            ConfigCommand::Set {
+                key,
+                value,
+                no_reload,
+            } => {
+                let config = config_path
+                    .as_ref()
+                    .ok_or_else(|| eyre!("No config file location; pass --config"))?;
+                cli::config_set(config, &key, &value, !no_reload, cli.json).await
+            }
+        },
+        Some(Command::Export { format, output, .. }) => {
+            let format = format.unwrap_or(cli::ExportFormat::Influx);
+            cli::export(&settings, format, output, cli.json).await
+        }
+        Some(Command::Health) => {
+            let health = cli::check_health().await;
+            println!("{}", serde_json::to_string(&health)?);
+            code = health.exit_code();
+            Ok(())
+        }
+        Some(Command::Check) => cli::check(&settings, cli.json).await.map(|check| {
+            code = check.exit_code();
+        }),
+        Some(Command::NotifyTest) => {
+            cli::notify_test(
+                &settings,
+                &notification_manager(&settings, &Coexistence::default()),
+                cli.json,
+            )
+            .await
+        }
+        Some(Command::Merge {
+            output,
+            inputs,
+            strategy,
+            hosts,
+        }) => cli::merge(&output, &inputs, strategy, hosts, cli.json).await,
+        Some(Command::Import {
+            file,
+            from,
+            strategy,
+            host,
+        }) => cli::import(&settings, from, &file, strategy, host, cli.json).await,
+        Some(Command::Serve { listen, .. }) => cli::serve_collector(&settings, listen).await,
+        #[cfg(feature = "dashboard")]
+        Some(Command::Dashboard { interval, samples }) => {
+            dashboard::run(&settings, interval.into(), samples).await
+        }
+        #[cfg(not(feature = "dashboard"))]
+        Some(Command::Dashboard { .. }) => Err(eyre!("dg was built without the dashboard feature")),
+        Some(command) => cli::run_control(command, cli.json).await,
+        None => {
+            run(
+                settings,
+                config_path,
+                state_dir,
+                runtime_dir,
+                cli.overrides,
+                overrides,
+                adjustments,
+            )
+            .await
+        }
+    };
+    telemetry::shutdown();
+    result.map(|()| exit_code(code))
+}
+
+type PathSizes = Vec<(String, io::Result<DirSize>)>;
+
+/// A limit alert whose final result is not known yet. Its fingerprint is
+/// only recorded once it has been delivered.
+struct PendingAlert {
+    alert: Alert,
+    fingerprint: AlertFingerprint,
+    usage: u64,
+    streak: u32,
+    settled: Settled,
+}
+
+/// The monitor task's state. Only this task touches the tracker; see the
+/// `status` module for how other tasks change and read it.
+struct Daemon {
+    settings: Settings,
+    clock: Arc<dyn Clock>,
+    gaps: GapDetector,
+    config_path: Option<ResolvedPath>,
+    /// The state and runtime directories, recorded in the effective
+    /// configuration.
+    dirs: Vec<(&'static str, ResolvedPath)>,
+    /// Re-applied to the config file's settings on every reload.
+    overrides: SettingsOverrides,
+    notifier: &'static NotificationManager,
+    metrics: Arc<Metrics>,
+    status: Arc<StatusState>,
+    tracker: UsageTracker,
+    sampler: Sampler,
+    source: Arc<dyn CounterSource>,
+    /// What startup found this machine supports.
+    capabilities: Capabilities,
+    saves: SaveGuard,
+    paused: bool,
+    auto_pause: Option<PauseReason>,
+    /// The interface of the default route, probed before each scan with
+    /// `track_network`.
+    default_interface: Option<String>,
+    warned_slow_scan: bool,
+    measuring_paths: bool,
+    session_bytes: u64,
+    scan_backoff: Backoff,
+    save_backoff: Backoff,
+    self_check: SelfCheck,
+    /// Id of the dictionary in `usage.dict`, once loaded or trained.
+    trained_dictionary: Option<u32>,
+    /// The day the daily history was last compacted.
+    compacted_on: Option<NaiveDate>,
+    /// Limit alerts sent by earlier scans that are still being retried.
+    pending_alerts: Vec<PendingAlert>,
+    #[cfg(feature = "influx")]
+    influx: Option<data_guardian::influx::InfluxExporter>,
+    #[cfg(feature = "agent")]
+    agent: Option<data_guardian::agent::Agent>,
+}
+
+impl Daemon {
+    async fn scan(&mut self, lag: Duration) -> Result<()> {
+        if self.paused || self.check_conditions().await?.is_some() {
+            return Ok(());
+        }
+        let gap = self.gaps.check(self.clock.now(), Utc::now());
+        if let Some(gap) = gap {
+            warn!(
+                since = %gap.since,
+                wall_seconds = gap.wall.num_seconds(),
+                monotonic_seconds = gap.monotonic.as_secs(),
+                "Long gap since the last scan (sleep or clock change); spreading its usage over the gap and skipping rate checks"
+            );
+        }
+        let period_start = self.tracker.period_start;
+        let mut tick = monitor_processes(self, gap).await?;
+        tick.lag_ms = self_metrics::millis(lag);
+        tick.default_interface = self.default_interface.clone();
+        self.session_bytes = self.session_bytes.saturating_add(tick.delta_bytes);
+        if tick.delta_bytes > 0
+            || self.tracker.period_start != period_start
+            || self.settings.collect_cpu
+            || self.settings.collect_memory
+        {
+            self.saves.mark_dirty();
+        }
+
+        debug!(
+            snapshot_ms = tick.snapshot_ms,
+            tick_ms = tick.tick_ms,
+            lag_ms = tick.lag_ms,
+            processes = tick.processes,
+            tracked_apps = tick.tracked_apps,
+            tick_apps = tick.tick_apps,
+            overflow_apps = tick.overflow_apps,
+            delta_bytes = tick.delta_bytes,
+            "Finished scan"
+        );
+
+        let check_interval = Duration::from_secs(self.settings.check_interval_seconds);
+        if !self.warned_slow_scan && self_metrics::is_slow_scan(tick.snapshot_ms, check_interval) {
+            warn!(
+                snapshot_ms = tick.snapshot_ms,
+                check_interval_seconds = self.settings.check_interval_seconds,
+                "Scanning processes takes more than half of the check interval; consider increasing check_interval_seconds"
+            );
+            self.warned_slow_scan = true;
+        }
+
+        self.status.record_tick(tick);
+        Ok(())
+    }
+
+    /// Scans outside the schedule. Control requests are handled one at a
+    /// time between ticks, so this never overlaps a scheduled scan and each
+    /// delta is counted once.
+    async fn scan_now(&mut self) {
+        let result = self.scan(Duration::ZERO).await;
+        self.scan_finished(result);
+    }
+
+    fn scan_finished(&mut self, result: Result<()>) {
+        let failures = match result {
+            Ok(()) => {
+                let failures = self.scan_backoff.succeeded();
+                if failures > 0 {
+                    info!(failures, "Scanning processes works again");
+                }
+                0
+            }
+            Err(e) => {
+                let escalate = self.scan_backoff.failed();
+                let interval = self.settings.check_interval_seconds;
+                self.task_failed(
+                    backoff::SCAN_TASK,
+                    &self.scan_backoff,
+                    interval,
+                    escalate,
+                    &e,
+                );
+                self.scan_backoff.failures()
+            }
+        };
+        self.status.set_scan_failures(failures);
+    }
+
+    fn save_finished(&mut self, result: Result<Option<u64>>) {
+        let failures = match result {
+            Ok(_) => {
+                let failures = self.save_backoff.succeeded();
+                if failures > 0 {
+                    info!(failures, "Saving usage data works again");
+                }
+                0
+            }
+            Err(e) => {
+                let escalate = self.save_backoff.failed();
+                let interval = self.settings.persistence_interval_seconds;
+                self.task_failed(
+                    backoff::SAVE_TASK,
+                    &self.save_backoff,
+                    interval,
+                    escalate,
+                    &e,
+                );
+                self.save_backoff.failures()
+            }
+        };
+        self.status.set_save_failures(failures);
+    }
+
+    /// `persistence_interval_seconds`, stretched by the self-check while
+    /// the service writes too much.
+    fn persistence_interval(&self) -> u64 {
+        self.self_check
+            .persistence_interval(self.settings.persistence_interval_seconds)
+    }
+
+    /// Measures the service's own I/O and storage against the `self_check`
+    /// caps. Returns whether the persistence interval changed.
+    async fn check_self(&mut self) -> bool {
+        if !self.settings.self_check.enabled {
+            return false;
+        }
+        let data_dir = persistence_paths().map(|config| config.state_dir);
+        let log_file = self.settings.log_file.clone();
+        let measured = tokio::task::spawn_blocking(move || {
+            (
+                self_check::own_io(),
+                self_check::dirs_size(data_dir.as_slice()),
+                log_file.as_deref().map(self_check::log_files_size),
+            )
+        })
+        .await;
+        let (io, data_dir_bytes, log_bytes) = match measured {
+            Ok(measured) => measured,
+            Err(e) => {
+                warn!(error = %e, "Failed to measure the service's own disk use");
+                return false;
+            }
+        };
+        let outcome = self.self_check.observe(
+            Utc::now(),
+            io,
+            data_dir_bytes,
+            log_bytes,
+            self.settings.persistence_interval_seconds,
+        );
+        debug!(usage = ?outcome.usage, "Checked the service's own disk use");
+        for exceeded in &outcome.exceeded {
+            warn!(
+                check = exceeded.check,
+                usage = %self.settings.format_bytes(exceeded.usage),
+                limit = %self.settings.format_bytes(exceeded.limit),
+                "Data Guardian's own disk use is over its self_check cap"
+            );
+            let alert = Alert::new(DEFAULT_APP_NAME).with_self_usage(
+                exceeded.check,
+                exceeded.usage,
+                exceeded.limit,
+            );
+            send_alert(self.notifier, &self.metrics, &alert);
+        }
+        if outcome.interval_changed {
+            warn!(
+                written_per_hour = ?outcome.usage.written_bytes_per_hour,
+                persistence_interval_seconds = outcome.usage.persistence_interval_seconds,
+                "Changed how often usage data is saved to keep the service's own writes down"
+            );
+        }
+        self.status.set_self_usage(Some(outcome.usage));
+        outcome.interval_changed
+    }
+
+    /// Logs a failed scan or save with when it runs next. Once it has
+    /// failed `backoff::ESCALATE_AFTER` times in a row, also sends an alert.
+    fn task_failed(
+        &self,
+        task: &str,
+        backoff: &Backoff,
+        interval_seconds: u64,
+        escalate: bool,
+        error: &color_eyre::Report,
+    ) {
+        let failures = backoff.failures();
+        let retry_in =
+            Duration::from_secs(interval_seconds.saturating_mul(backoff.factor() as u64));
+        if !escalate {
+            warn!(task, failures, ?retry_in, error = %error, "Periodic task failed");
+            return;
+        }
+        error!(
+            task,
+            failures,
+            ?retry_in,
+            error = %error,
+            "Periodic task keeps failing; retrying less often until it succeeds"
+        );
+        let alert = Alert::new(DEFAULT_APP_NAME).with_failing(task, failures);
+        send_alert(self.notifier, &self.metrics, &alert);
+    }
+
+    /// Walks the watched paths on a blocking thread. The sizes come back
+    /// through `results`, so a slow or huge tree never holds up a scan.
+    fn measure_paths(&mut self, results: &mpsc::UnboundedSender<PathSizes>) {
+        if self.paused {
+            return;
+        }
+        if self.measuring_paths {
+            debug!("Still walking the watched paths, skipping this round");
+            return;
+        }
+        self.measuring_paths = true;
+        let paths = self.settings.watched_paths.clone();
+        let results = results.clone();
+        tokio::task::spawn_blocking(move || {
+            let sizes = paths
+                .into_iter()
+                .map(|(label, watched)| {
+                    let size = watched.measure();
+                    (label, size)
+                })
+                .collect();
+            let _ = results.send(sizes);
+        });
+    }
+
+    fn record_path_sizes(&mut self, sizes: PathSizes) {
+        self.measuring_paths = false;
+        for (label, size) in sizes {
+            // Dropped by a reload while the walk was running.
+            let Some(watched) = self.settings.watched_paths.get(&label) else {
+                continue;
+            };
+            let size = match size {
+                Ok(size) => size,
+                Err(e) => {
+                    warn!(error = %e, %label, path = ?watched.path, "Failed to measure watched path");
+                    continue;
+                }
+            };
+            if size.truncated {
+                warn!(%label, files = size.files, max_files = watched.max_files, max_depth = watched.max_depth, "Stopped walking watched path at its limits; its size is a lower bound");
+            }
+            if size.errors > 0 {
+                debug!(%label, errors = size.errors, "Skipped unreadable entries in watched path");
+            }
+            let app = format!("{}{}", monitor::PATH_PREFIX, label);
+            self.tracker.record_size(&app, size.bytes);
+            if let Some(limit) = self.settings.effective_limit(&app)
+                && size.bytes > limit.value
+            {
+                let alert = Alert::new(&app).with_usage(size.bytes, limit.value);
+                if send_alert(self.notifier, &self.metrics, &alert) {
+                    info!(%app, size = %self.settings.format_bytes(size.bytes), "Watched path exceeded its size limit");
+                }
+            }
+        }
+        let usage = self.tracker.usage();
+        self.metrics.set_app_usage(&usage);
+        self.status.update_usage(usage);
+        self.saves.mark_dirty();
+    }
+
+    fn announce_start(&self) {
+        let apps = self.tracker.apps.keys().cloned().collect();
+        let alert = Alert::new(DEFAULT_APP_NAME).with_started(apps, self.settings.data_limit);
+        if let Err(e) = self.notifier.alert(&alert) {
+            warn!(error = %e, "Failed to send startup notification");
+        }
+    }
+
+    fn summarize_session(&self) {
+        let display_names = self.source.display_names();
+        let over_limit: Vec<String> = status::over_limit(&self.tracker.usage(), &self.settings)
+            .into_iter()
+            .map(|app| display_names.get(&app).cloned().unwrap_or(app))
+            .collect();
+        info!(
+            session_bytes = self.session_bytes,
+            ?over_limit,
+            "Session summary"
+        );
+        if self.settings.notify_on_shutdown {
+            let alert = Alert::new(DEFAULT_APP_NAME).with_summary(over_limit, self.session_bytes);
+            if let Err(e) = self.notifier.alert(&alert) {
+                warn!(error = %e, "Failed to send shutdown summary");
+            }
+        }
+    }
+
+    async fn persist(&mut self, force: bool) -> Result<Option<u64>> {
+        let size = self.save_usage(force).await.inspect_err(|e| {
+            self.status.record_persist_failure(format!("{:#}", e));
+        })?;
+        save_notification_history(self.notifier).await?;
+
+        #[cfg(feature = "influx")]
+        if let Some(influx) = &mut self.influx
+            && let Err(e) = influx.export(&self.tracker.usage()).await
+        {
+            warn!(error = %e, buffered = influx.buffered(), "Failed to export usage to InfluxDB");
+        }
+
+        #[cfg(feature = "agent")]
+        if let Some(agent) = &mut self.agent
+            && let Err(e) = agent.report(&self.tracker).await
+        {
+            warn!(error = %e, queued = agent.queued(), "Failed to report usage to collector");
+        }
+        Ok(size)
+    }
+
+    /// Once a day, folds daily history older than `history_days` into
+    /// monthly totals and drops those older than `history_months`, then
+    /// saves right away. The save replaces the data file with a rename, so
+    /// a crash leaves either the old file or the compacted one.
+    async fn compact_history(&mut self) {
+        let today = self.settings.reset_timezone().date(Utc::now());
+        if self.compacted_on == Some(today) {
+            return;
+        }
+        self.compacted_on = Some(today);
+        let compaction = self.tracker.compact(
+            self.settings.history_days,
+            self.settings.history_months,
+            today,
+        );
+        if compaction.is_empty() {
+            return;
+        }
+        let before_bytes = match persistence_paths() {
+            Some(config) => tokio::fs::metadata(config.data_path())
+                .await
+                .ok()
+                .map(|metadata| metadata.len()),
+            None => None,
+        };
+        self.saves.mark_dirty();
+        match self.persist(true).await {
+            Ok(after_bytes) => info!(
+                days = compaction.days,
+                months = compaction.months,
+                ?before_bytes,
+                ?after_bytes,
+                "Compacted usage history"
+            ),
+            Err(e) => warn!(error = %e, "Failed to save compacted usage history"),
+        }
+    }
+
+    /// How to compress the data file. A trained dictionary is trained on
+    /// the current usage and written next to the data file once there is
+    /// enough of it; until then the file is compressed without one.
+    async fn compression(&mut self) -> CompressionConfig {
+        let dictionary = match self.settings.compression_dictionary {
+            DictionaryKind::None => None,
+            DictionaryKind::Builtin => Some(compression::builtin_dictionary().id()),
+            DictionaryKind::Trained => {
+                if self.trained_dictionary.is_none() {
+                    self.trained_dictionary = self.train_dictionary().await;
+                }
+                self.trained_dictionary
+            }
+        };
+        CompressionConfig {
+            dictionary,
+            ..Default::default()
+        }
+    }
+
+    async fn train_dictionary(&self) -> Option<u32> {
+        let config = persistence_paths()?;
+        if let Some(id) = persistence::load_dictionary(&config).await {
+            return Some(id);
+        }
+        let json = serde_json::to_vec(&self.tracker).ok()?;
+        if json.len() < DICTIONARY_TRAINING_MIN_BYTES {
+            return None;
+        }
+        let bytes = compression::train_dictionary(&[&json], compression::MAX_DICTIONARY_SIZE);
+        let dictionary = Dictionary::new(bytes).ok()?;
+        let path = config.dictionary_path();
+        let written = async {
+            tokio::fs::create_dir_all(&config.state_dir).await?;
+            tokio::fs::write(&path, dictionary.as_bytes()).await
+        };
+        if let Err(e) = written.await {
+            warn!(error = %e, ?path, "Failed to save the compression dictionary");
+            return None;
+        }
+        info!(
+            ?path,
+            size_bytes = dictionary.as_bytes().len(),
+            "Trained a compression dictionary"
+        );
+        Some(compression::register_dictionary(dictionary))
+    }
+
+    async fn save_usage(&mut self, force: bool) -> Result<Option<u64>> {
+        if !force && !self.saves.is_dirty() {
+            debug!("Usage data unchanged since the last save");
+            return Ok(None);
+        }
+        let started = Instant::now();
+        let compression = self.compression().await;
+        // Compared without a new save stamp, which would differ every time.
+        let current = self
+            .tracker
+            .to_compressed_with(compression)
+            .context("Failed to compress usage data")?;
+        if !self.saves.should_write(&current, force) {
+            debug!("Usage data identical to the last save");
+            return Ok(None);
+        }
+        let now = Utc::now();
+        let compressed = self
+            .tracker
+            .to_saved_compressed(now, version::VERSION, compression)
+            .context("Failed to compress usage data")?;
+        let written =
+            async { persistence::write(&require_persistence_paths()?, &compressed).await };
+        if let Err(e) = written.await {
+            self.saves.mark_dirty();
+            return Err(e.into());
+        }
+        // Now the tracker matches what was written.
+        self.tracker.mark_saved(now, version::VERSION);
+        self.saves.written(&compressed);
+
+        let size = compressed.len() as u64;
+        let duration = started.elapsed();
+        self.metrics.set_persisted_bytes(size);
+        self.status.record_persist(PersistStats {
+            time: Utc::now(),
+            duration_ms: self_metrics::millis(duration),
+            size_bytes: size,
+        });
+        debug!(
+            duration_ms = self_metrics::millis(duration),
+            size_bytes = size,
+            "Persisted usage data"
+        );
+        Ok(Some(size))
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.sampler.clear();
+        self.status.set_paused(paused || self.auto_pause.is_some());
+        info!(paused, "Monitoring state changed");
+    }
+
+    async fn check_conditions(&mut self) -> Result<Option<PauseReason>> {
+        let pattern = self.settings.only_when_interface_matches.as_deref();
+        let check_interface = pattern.is_some() || self.settings.track_network;
+        let conditions = if self.settings.pause_on_battery || check_interface {
+            Conditions::probe(self.settings.pause_on_battery, check_interface).await?
+        } else {
+            Conditions::default()
+        };
+        let reason = conditions.pause_reason(self.settings.pause_on_battery, pattern);
+        let interface = conditions
+            .interface
+            .clone()
+            .filter(|_| self.settings.track_network);
+        if interface != self.default_interface {
+            info!(?interface, "Default route changed");
+            self.default_interface = interface;
+            self.status
+                .set_default_interface(self.default_interface.clone());
+        }
+
+        if reason != self.auto_pause {
+            let Conditions {
+                on_battery,
+                interface,
+            } = &conditions;
+            match reason {
+                Some(reason) => info!(
+                    reason = reason.as_str(),
+                    ?on_battery,
+                    ?interface,
+                    "Pausing monitoring"
+                ),
+                None => info!(?on_battery, ?interface, "Resuming monitoring"),
+            }
+            self.auto_pause = reason;
+            self.sampler.clear();
+            self.status.set_paused(self.paused || reason.is_some());
+        }
+        Ok(reason)
+    }
+
+    /// `app` as usage is recorded under it, so a control request can name
+    /// `Chrome.exe` when the rules count it as `chrome`.
+    fn app_name(&self, app: String) -> String {
+        match self.settings.app_names() {
+            Ok(names) => names.rename(&app).unwrap_or(app),
+            Err(_) => app,
+        }
+    }
+
+    /// Renames recorded usage after the app name rules changed.
+    fn normalize_app_names(&mut self, settings: &Settings) {
+        let Ok(names) = settings.app_names() else {
+            return;
+        };
+        let (tracker, merged) = names.normalize_tracker(std::mem::take(&mut self.tracker));
+        self.tracker = tracker;
+        info!(
+            merged,
+            "Renamed applications after the app name rules changed"
+        );
+        self.saves.mark_dirty();
+        self.status.update_usage(self.tracker.usage());
+    }
+
+    fn status_response(&self) -> ControlResponse {
+        match self.status.report(None, None) {
+            Some(mut report) => {
+                report.snoozes = self.notifier.snoozes().unwrap_or_default();
+                report.alerts = self.metrics.alerts();
+                report.paused_channels = self.notifier.paused_channels().unwrap_or_default();
+                ControlResponse::data(&report)
+            }
+            None => ControlResponse::error("No scan has completed yet"),
+        }
+    }
+
+    async fn handle_control(&mut self, request: ControlRequest) -> ControlResponse {
+        match request {
+            ControlRequest::Status => self.status_response(),
+            ControlRequest::Scan => {
+                self.scan_now().await;
+                self.status_response()
+            }
+            ControlRequest::Flush => match self.persist(true).await {
+                Ok(size) => ControlResponse::data(&serde_json::json!({ "persisted_bytes": size })),
+                Err(e) => ControlResponse::error(e),
+            },
+            ControlRequest::Reset { app } => {
+                let app = self.app_name(app);
+                if !self.tracker.reset_app(&app) {
+                    return ControlResponse::error(format!("No usage recorded for {}", app));
+                }
+                info!(%app, "Reset application usage");
+                let _ = self.notifier.clear_cooldown(&app);
+                self.saves.mark_dirty();
+                self.status.update_usage(self.tracker.usage());
+                ControlResponse::ok()
+            }
+            ControlRequest::Pause => {
+                self.set_paused(true);
+                ControlResponse::ok()
+            }
+            ControlRequest::Resume => {
+                self.set_paused(false);
+                ControlResponse::ok()
+            }
+            ControlRequest::Reload => match Settings::load_adjusted(self.config_path.as_ref()) {
+                Ok((mut settings, adjustments)) => {
+                    let loaded = settings.clone();
+                    if let Err(e) = self.overrides.apply(&mut settings) {
+                        return ControlResponse::error(e);
+                    }
+                    let overrides = loaded.diff(&settings);
+                    let kept = keep_notifier_settings(&mut settings, &self.settings);
+                    if !kept.is_empty() {
+                        warn!(
+                            settings = %kept.join(", "),
+                            "Kept the running notification settings, restart the service to apply them"
+                        );
+                    }
+                    let source = match settings.source() {
+                        Ok(source) => source,
+                        Err(e) => return ControlResponse::error(e),
+                    };
+                    match SettingsChanged::new(self.settings.clone(), settings.clone()) {
+                        Some(event) => {
+                            info!(changes = %event.summary(), "Reloaded settings");
+                            self.status.apply_settings_change(event);
+                        }
+                        None => info!("Reloaded settings, nothing changed"),
+                    }
+                    log_clamped(&adjustments);
+                    notify_clamped(self.notifier, &adjustments);
+                    write_effective_config(
+                        &settings,
+                        self.config_path.as_ref(),
+                        &self.dirs,
+                        &overrides,
+                        &adjustments,
+                    );
+                    if settings.backend != self.settings.backend {
+                        self.sampler.clear();
+                    }
+                    if settings.app_names != self.settings.app_names {
+                        self.sampler.clear();
+                        self.normalize_app_names(&settings);
+                    }
+                    self.sampler.set_max_rate(settings.max_bytes_per_second);
+                    self.self_check.set_config(settings.self_check);
+                    if !settings.self_check.enabled {
+                        self.status.set_self_usage(None);
+                    }
+                    self.source = source;
+                    self.settings = settings;
+                    ControlResponse::ok()
+                }
+                Err(e) => ControlResponse::error(e),
+            },
+            ControlRequest::Snooze { app, seconds } => {
+                let duration = seconds.map_or(SNOOZE_DURATION, Duration::from_secs);
+                let result = match &app.map(|app| self.app_name(app)) {
+                    Some(app) => self.notifier.snooze(app, duration),
+                    None => self.notifier.snooze_all(duration),
+                };
+                match result {
+                    Ok(()) => ControlResponse::ok(),
+                    Err(e) => ControlResponse::error(e),
+                }
+            }
+            ControlRequest::Cooldowns => match self.notifier.active_cooldowns() {
+                Ok(cooldowns) => ControlResponse::data(&report::Cooldowns::new(cooldowns)),
+                Err(e) => ControlResponse::error(e),
+            },
+            ControlRequest::ClearCooldown { app } => {
+                let result = match &app.map(|app| self.app_name(app)) {
+                    Some(app) => self.notifier.clear_cooldown(app).map(usize::from),
+                    None => self.notifier.clear_all_cooldowns(),
+                };
+                match result {
+                    Ok(cleared) => ControlResponse::data(&cleared),
+                    Err(e) => ControlResponse::error(e),
+                }
+            }
+            ControlRequest::Subscribe(_) => {
+                ControlResponse::error("Subscriptions are answered by the control endpoint")
+            }
+        }
+    }
+}
+
+/// Puts back the `running` values of the settings `notification_manager`
+/// reads, since the notifier is built once at startup, and returns the
+/// names of those that differed.
+fn keep_notifier_settings(settings: &mut Settings, running: &Settings) -> Vec<String> {
+    let changed = running.diff(settings);
+    settings.notifications = running.notifications.clone();
+    settings.escalation.critical_cooldown_seconds = running.escalation.critical_cooldown_seconds;
+    settings.locale = running.locale.clone();
+    settings.locales_dir = running.locales_dir.clone();
+    settings.instance_label = running.instance_label.clone();
+    settings.alerts_log = running.alerts_log;
+    let remaining = running.diff(settings);
+    changed
+        .into_iter()
+        .filter(|change| !remaining.iter().any(|left| left.field == change.field))
+        .map(|change| change.field)
+        .collect()
+}
+
+fn notification_manager(settings: &Settings, coexistence: &Coexistence) -> NotificationManager {
+    let mut config = settings.notifications.clone();
+    coexistence.apply(&mut config);
+    let manager = NotificationManager::default()
+        .with_config(config)
+        .with_critical_cooldown(Duration::from_secs(
+            settings.escalation.critical_cooldown_seconds,
+        ))
+        .with_localizer(settings.localizer())
+        .with_instance(&settings.instance_label());
+    match persistence_paths().filter(|_| settings.alerts_log) {
+        Some(config) => manager.with_alerts_log(Arc::new(AlertsLog::new(config.alerts_log_path()))),
+        None => manager,
+    }
+}
+
+fn spawn_control_socket(
+    events: broadcast::Sender<Event>,
+) -> Option<mpsc::Receiver<ControlMessage>> {
+    let endpoint = control_endpoint()?;
+    let (tx, rx) = control::channel();
+    tokio::spawn(async move {
+        if let ControlEndpoint::Socket(path) = &endpoint
+            && let Some(dir) = path.parent()
+            && let Err(e) = tokio::fs::create_dir_all(dir).await
+        {
+            error!(error = %e, ?dir, "Failed to create data directory");
+            return;
+        }
+        debug!(%endpoint, "Listening for control requests");
+        if let Err(e) = control::serve_endpoint(&endpoint, tx, events).await {
+            error!(error = %e, %endpoint, "Control endpoint stopped");
+        }
+    });
+    Some(rx)
+}
+
+/// Probes what this machine supports, logging a one-line report and why
+/// anything is missing.
+async fn probe_capabilities(settings: &Settings, state_dir: PathBuf) -> Capabilities {
+    let process_io = settings.backend == Backend::Process;
+    let desktop = settings
+        .notifications
+        .channels
+        .contains(&ChannelKind::Desktop);
+    let probe = tokio::task::spawn_blocking(move || {
+        let mut probed = Vec::new();
+        if process_io {
+            probed.push(capabilities::process_io(capabilities::read_process_io()));
+        }
+        if desktop {
+            probed.push(capabilities::notifications(
+                capabilities::reach_notification_server(),
+            ));
+        }
+        probed.push(capabilities::data_dir(&state_dir));
+        Capabilities(probed)
+    });
+    let probed = match probe.await {
+        Ok(probed) => probed,
+        Err(e) => {
+            warn!(error = %e, "Failed to probe capabilities");
+            return Capabilities::default();
+        }
+    };
+    info!(capabilities = %probed, "Probed capabilities");
+    for capability in probed.unavailable() {
+        warn!(
+            capability = %capability.name,
+            detail = capability.detail.as_deref().unwrap_or_default(),
+            "Capability unavailable"
+        );
+    }
+    if !probed.is_available(capabilities::PROCESS_IO) {
+        warn!(
+            "Not tracking applications, since their usage would always read 0; disk and interface totals are still tracked"
+        );
+    }
+    probed
+}
+
+/// Whether per-process usage is recorded. It is not when the process
+/// backend cannot read per-process counters here.
+fn tracks_apps(settings: &Settings, probed: &Capabilities) -> bool {
+    settings.backend != Backend::Process || probed.is_available(capabilities::PROCESS_IO)
+}
+
+/// Looks for other programs that warn about data usage, and logs what was
+/// found and whether network alerts go to the log only because of it.
+async fn detect_other_monitors(settings: &Settings) -> Coexistence {
+    let config = &settings.other_monitors;
+    if !config.detect {
+        return Coexistence::default();
+    }
+    let running = tokio::task::spawn_blocking(other_monitors::running_processes)
+        .await
+        .unwrap_or_default();
+    let coexistence = config.decide(config.detect(running.iter().map(String::as_str)));
+    for monitor in &coexistence.monitors {
+        info!(name = %monitor.name, process = %monitor.process, "Another data monitor is running and may warn about the same usage");
+    }
+    if coexistence.network_alerts_log_only {
+        info!(
+            route = other_monitors::NETWORK_ALERTS,
+            "Sending network interface alerts to the log only"
+        );
+    } else if !coexistence.monitors.is_empty() {
+        info!(
+            "Set other_monitors.quiet_network_alerts to send network interface alerts to the log only"
+        );
+    }
+    coexistence
+}
+
+async fn run(
+    settings: Settings,
+    config_path: Option<ResolvedPath>,
+    state_dir: Option<ResolvedPath>,
+    runtime_dir: Option<ResolvedPath>,
+    settings_overrides: SettingsOverrides,
+    overrides: Vec<SettingsFieldChange>,
+    adjustments: Vec<SettingsFieldChange>,
+) -> Result<()> {
+    check_paths(
+        config_path.as_ref(),
+        state_dir.as_ref(),
+        runtime_dir.as_ref(),
+    )?;
+    let dirs: Vec<_> = [("state_dir", state_dir), ("runtime_dir", runtime_dir)]
+        .into_iter()
+        .filter_map(|(name, dir)| Some((name, dir?)))
+        .collect();
+    write_effective_config(
+        &settings,
+        config_path.as_ref(),
+        &dirs,
+        &overrides,
+        &adjustments,
+    );
+    let instance = settings.instance_label();
+    let (action_tx, mut action_rx) = tokio::sync::mpsc::unbounded_channel();
+    let status = Arc::new(StatusState::new(&settings));
+    let coexistence = detect_other_monitors(&settings).await;
+    status.set_other_monitors(coexistence.clone());
+    let clock = clock::system();
+    let notifier = notification_manager(&settings, &coexistence)
+        .with_clock(clock.clone())
+        .with_actions(action_tx.clone())
+        .with_events(status.events())
+        .with_history(NotificationHistory::new(
+            settings.history_capacity,
+            Duration::from_secs(settings.history_retention_seconds),
+        ));
+    if let Some(records) = load_notification_history().await {
+        notifier.restore_history(records)?;
+    }
+    #[cfg(feature = "mqtt")]
+    let notifier = if settings.mqtt.enabled {
+        match data_guardian::mqtt::start(&settings.mqtt, &instance, status.clone()) {
+            Ok(channel) => {
+                info!(broker = %settings.mqtt.broker, "Publishing usage over MQTT");
+                notifier.with_channel(Arc::new(channel))
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to start MQTT publisher");
+                notifier
+            }
+        }
+    } else {
+        notifier
+    };
+    #[cfg(not(feature = "mqtt"))]
+    if settings.mqtt.enabled {
+        warn!("Ignoring [mqtt] settings: built without the mqtt feature");
+    }
+
+    #[cfg(feature = "tray")]
+    if settings.tray.enabled {
+        match data_guardian::tray::start(
+            status.clone(),
+            action_tx,
+            settings.localizer(),
+            Duration::from_secs(settings.check_interval_seconds),
+        )
+        .await
+        {
+            Ok(()) => info!("Showing tray icon"),
+            Err(e) => warn!(error = %e, "Tray icon unavailable, continuing without it"),
+        }
+    }
+
+    // Library code in this process that calls the free `alert_user` goes
+    // through the same manager.
+    if notification::init_global(notifier).is_err() {
+        warn!("Notifications were set up before the service; using that setup");
+    }
+    let notifier = notification::global();
+    notify_clamped(notifier, &adjustments);
+
+    let config = require_persistence_paths()?;
+    let capabilities = probe_capabilities(&settings, config.state_dir.clone()).await;
+    status.set_capabilities(capabilities.clone());
+    let (tracker, incidents) = persistence::recover(&config).await;
+    let (tracker, merged) = settings.app_names()?.normalize_tracker(tracker);
+    if merged > 0 {
+        info!(
+            merged,
+            "Merged applications whose names now normalize to the same one"
+        );
+    }
+    status.set_data_incident(tracker.incidents.last().cloned());
+    if let Some(crash) = CrashMarker::take(&config.crash_marker_path()) {
+        warn!(
+            message = %crash.message,
+            location = ?crash.location,
+            saved = crash.saved,
+            "Recovered from a crash at {}",
+            crash.time
+        );
+        status.set_last_crash(Some(crash));
+    }
+    let crash_guard = Arc::new(CrashGuard::new(
+        config.data_path(),
+        config.crash_marker_path(),
+    ));
+    crash_guard.update(&tracker);
+    crash_guard.install_hook();
+    if !incidents.is_empty() {
+        let alert = Alert::new(DEFAULT_APP_NAME).with_data_loss(
+            incidents
+                .iter()
+                .any(|incident| incident.restored_from.is_some()),
+        );
+        if let Err(e) = notifier.alert(&alert) {
+            warn!(error = %e, "Failed to send data loss notification");
+        }
+    }
+
+    let metrics = Arc::new(Metrics::new(settings.metrics_max_apps).with_host(&instance));
+    if let Some(addr) = settings.metrics_listen_addr {
+        spawn_metrics_listener(addr, metrics.clone()).await;
+    }
+    #[cfg(feature = "otel")]
+    if settings.otel.enabled
+        && let Err(e) = telemetry::init_metrics(&settings.otel, metrics.clone())
+    {
+        error!(error = %e, "Failed to initialize OpenTelemetry metrics");
+    }
+
+    if let Some(addr) = settings.status_listen_addr() {
+        match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => {
+                info!(%addr, "Serving status endpoint");
+                let status = status.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = data_guardian::status::serve(listener, status).await {
+                        error!(error = %e, "Status listener stopped");
+                    }
+                });
+            }
+            Err(e) => error!(error = %e, %addr, "Failed to bind status listener"),
+        }
+    }
+
+    #[cfg(feature = "influx")]
+    let influx = if settings.influx.enabled {
+        let config = data_guardian::influx::InfluxConfig {
+            host: settings
+                .influx
+                .host
+                .clone()
+                .or_else(|| Some(instance.clone())),
+            ..settings.influx.clone()
+        };
+        match data_guardian::influx::InfluxExporter::new(&config) {
+            Ok(exporter) => {
+                info!(url = %settings.influx.url, "Exporting usage to InfluxDB");
+                Some(exporter)
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to initialize InfluxDB exporter");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    #[cfg(not(feature = "influx"))]
+    if settings.influx.enabled {
+        warn!("Ignoring [influx] settings: built without the influx feature");
+    }
+
+    #[cfg(feature = "agent")]
+    let agent = if settings.agent.enabled {
+        let queue_path = persistence_paths().map(|config| {
+            config
+                .state_dir
+                .join(data_guardian::agent::AGENT_QUEUE_FILE)
+        });
+        let config = data_guardian::agent::AgentConfig {
+            host: settings
+                .agent
+                .host
+                .clone()
+                .or_else(|| Some(instance.clone())),
+            ..settings.agent.clone()
+        };
+        match data_guardian::agent::Agent::new(&config, queue_path) {
+            Ok(agent) => {
+                info!(url = %settings.agent.url, queued = agent.queued(), "Reporting usage to collector");
+                Some(agent)
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to initialize collector agent");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    #[cfg(not(feature = "agent"))]
+    if settings.agent.enabled {
+        warn!("Ignoring [agent] settings: built without the agent feature");
+    }
+    #[cfg(not(target_os = "macos"))]
+    if settings.identify_by == data_guardian::identity::IdentifyBy::Bundle {
+        warn!("Ignoring identify_by = \"bundle\": app bundles only exist on macOS");
+    }
+    if (settings.cpu_limit_seconds.is_some() && !settings.collect_cpu)
+        || (settings.memory_limit_bytes.is_some() && !settings.collect_memory)
+    {
+        warn!(
+            "Ignoring cpu_limit_seconds or memory_limit_bytes without collect_cpu or collect_memory"
+        );
+    }
+    if !settings.disk_limits.is_empty() && !settings.track_disks {
+        warn!("Ignoring disk_limits without track_disks");
+    }
+    if (!settings.interface_limits.is_empty() || !settings.metered_interfaces.is_empty())
+        && !settings.track_network
+    {
+        warn!("Ignoring interface_limits and metered_interfaces without track_network");
+    }
+    if settings.metered_data_limit.is_some() && settings.metered_interfaces.is_empty() {
+        warn!("Ignoring metered_data_limit without metered_interfaces");
+    }
+    #[cfg(not(target_os = "linux"))]
+    if settings.group_containers {
+        warn!("Ignoring group_containers: container detection is only supported on Linux");
+    }
+    #[cfg(not(windows))]
+    if settings.friendly_names {
+        warn!("Ignoring friendly_names: version metadata is only read on Windows");
+    }
+
+    let mut control_rx = spawn_control_socket(status.events());
 
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
@@ -198,27 +2185,491 @@ async fn main() -> Result<()> {
         r.store(false, Ordering::SeqCst);
     });
 
-    let mut monitor_interval = interval(Duration::from_secs(settings.check_interval_seconds));
-    let mut save_interval = interval(Duration::from_secs(settings.persistence_interval_seconds));
+    let ticker = |seconds| Ticker::new(clock.clone(), Duration::from_secs(seconds));
+    let mut monitor_interval = ticker(settings.check_interval_seconds);
+    let self_check = SelfCheck::new(settings.self_check);
+    let mut save_interval = ticker(settings.persistence_interval_seconds);
+    let mut paths_interval = ticker(settings.watched_paths_interval_seconds);
+    let (paths_tx, mut paths_rx) = mpsc::unbounded_channel();
+
+    info!(version = %VERSION_INFO, %instance, ?settings, "Starting Data Guardian service");
 
-    info!(?settings, "Starting Data Guardian service");
+    let source = settings
+        .source()
+        .context("Failed to initialize the usage backend")?;
+    let sampler = Sampler::new().with_max_rate(settings.max_bytes_per_second);
+    let gaps = GapDetector::new(Duration::from_secs(settings.check_interval_seconds));
+    let mut daemon = Daemon {
+        settings,
+        clock: clock.clone(),
+        gaps,
+        config_path,
+        dirs,
+        overrides: settings_overrides,
+        notifier,
+        metrics,
+        status,
+        tracker,
+        sampler,
+        source,
+        capabilities,
+        saves: SaveGuard::default(),
+        paused: false,
+        auto_pause: None,
+        default_interface: None,
+        warned_slow_scan: false,
+        measuring_paths: false,
+        session_bytes: 0,
+        scan_backoff: Backoff::default(),
+        save_backoff: Backoff::default(),
+        self_check,
+        trained_dictionary: None,
+        compacted_on: None,
+        pending_alerts: Vec::new(),
+        #[cfg(feature = "influx")]
+        influx,
+        #[cfg(feature = "agent")]
+        agent,
+    };
 
-    while running.load(Ordering::SeqCst) {
-        tokio::select! {
-            _ = monitor_interval.tick() => {
-                if let Err(e) = monitor_processes(&settings, &mut app_usage, &mut prev_processes).await {
-                    error!(error = %e, "Failed to monitor processes");
+    if !incidents.is_empty() {
+        daemon.saves.mark_dirty();
+    }
+    daemon.compact_history().await;
+    if daemon.settings.notify_on_start {
+        daemon.announce_start();
+    }
+
+    let result = crash::catch_unwind(async {
+        while running.load(Ordering::SeqCst) {
+            tokio::select! {
+                scheduled = monitor_interval.tick() => {
+                    if daemon.scan_backoff.ready() {
+                        let result = daemon.scan(monitor_interval.lateness(scheduled)).await;
+                        daemon.scan_finished(result);
+                    }
                 }
-            }
-            _ = save_interval.tick() => {
-                if let Err(e) = save_persisted_data(&app_usage).await {
-                    error!(error = %e, "Failed to persist data");
+                _ = save_interval.tick() => {
+                    daemon.compact_history().await;
+                    if daemon.save_backoff.ready() {
+                        let result = daemon.persist(false).await;
+                        daemon.save_finished(result);
+                    }
+                    if daemon.check_self().await {
+                        save_interval = ticker(daemon.persistence_interval());
+                    }
+                }
+                _ = paths_interval.tick(), if !daemon.settings.watched_paths.is_empty() => {
+                    daemon.measure_paths(&paths_tx);
+                }
+                Some(sizes) = paths_rx.recv() => daemon.record_path_sizes(sizes),
+                Some((app, action)) = action_rx.recv() => {
+                    if action == NotificationAction::Quit {
+                        info!("Quit requested from the tray");
+                        running.store(false, Ordering::SeqCst);
+                    } else if let Err(e) = handle_notification_action(&daemon.settings, daemon.notifier, &daemon.tracker, &daemon.source.display_names(), &app, action).await {
+                        error!(error = %e, %app, ?action, "Failed to handle notification action");
+                    }
+                }
+                Some((request, reply)) = recv_control(&mut control_rx) => {
+                    let reload = matches!(request, ControlRequest::Reload);
+                    let scanned = matches!(request, ControlRequest::Scan);
+                    let response = daemon.handle_control(request).await;
+                    if scanned {
+                        // The next scheduled scan is a full interval after
+                        // this one, not right behind it.
+                        monitor_interval = ticker(daemon.settings.check_interval_seconds);
+                    }
+                    if reload && response.ok {
+                        monitor_interval = ticker(daemon.settings.check_interval_seconds);
+                        daemon
+                            .gaps
+                            .set_interval(Duration::from_secs(daemon.settings.check_interval_seconds));
+                        save_interval = ticker(daemon.persistence_interval());
+                        paths_interval = ticker(daemon.settings.watched_paths_interval_seconds);
+                    }
+                    let _ = reply.send(response);
                 }
             }
+            crash_guard.update(&daemon.tracker);
         }
+    })
+    .await;
+    if let Err(payload) = result {
+        let message = crash::panic_message(payload.as_ref());
+        error!(%message, "Monitor loop panicked, saving usage data");
+        crash_guard.record(message, None);
+        let compression = daemon.compression().await;
+        if save_on_shutdown(&mut daemon.tracker, compression)
+            .await
+            .is_ok()
+        {
+            crash_guard.mark_saved();
+        }
+        std::panic::resume_unwind(payload);
     }
 
     info!("Shutting down gracefully...");
-    save_persisted_data(&app_usage).await?;
+    daemon.summarize_session();
+    if let Err(e) = save_notification_history(daemon.notifier).await {
+        error!(error = %e, "Failed to persist notification history");
+    }
+    if let Some(ControlEndpoint::Socket(path)) = control_endpoint() {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+    let compression = daemon.compression().await;
+    let path = save_on_shutdown(&mut daemon.tracker, compression).await?;
+    info!(?path, "Saved usage data");
     Ok(())
 }
+
+async fn recv_control(
+    control_rx: &mut Option<mpsc::Receiver<ControlMessage>>,
+) -> Option<ControlMessage> {
+    match control_rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use data_guardian::clock::MockClock;
+    use data_guardian::events::Event;
+    use data_guardian::monitor::{CounterKey, Counters, ProcessData};
+    use data_guardian::notification::NotificationConfig;
+    use data_guardian::test_support::{RecordingChannel, ScriptedSource};
+
+    use super::*;
+
+    /// A daemon that reads `snapshots` in order and alerts through
+    /// `channel` only, without retries.
+    fn test_daemon(
+        settings: Settings,
+        snapshots: impl IntoIterator<Item = ProcessData>,
+        channel: Arc<RecordingChannel>,
+    ) -> Daemon {
+        let notifier = NotificationManager::new(Duration::from_secs(60))
+            .with_config(NotificationConfig {
+                channels: Vec::new(),
+                retry_attempts: 0,
+                cooldown_after_failure: false,
+                ..NotificationConfig::default()
+            })
+            .with_channel(channel);
+        Daemon {
+            clock: clock::system(),
+            gaps: GapDetector::new(Duration::from_secs(settings.check_interval_seconds)),
+            config_path: None,
+            dirs: Vec::new(),
+            overrides: SettingsOverrides::default(),
+            notifier: Box::leak(Box::new(notifier)),
+            metrics: Arc::new(Metrics::default()),
+            status: Arc::new(StatusState::new(&settings)),
+            tracker: UsageTracker::default(),
+            sampler: Sampler::new(),
+            source: Arc::new(ScriptedSource::new(snapshots)),
+            capabilities: Capabilities::default(),
+            saves: SaveGuard::default(),
+            paused: false,
+            auto_pause: None,
+            default_interface: None,
+            warned_slow_scan: false,
+            measuring_paths: false,
+            session_bytes: 0,
+            scan_backoff: Backoff::default(),
+            save_backoff: Backoff::default(),
+            self_check: SelfCheck::new(settings.self_check),
+            trained_dictionary: None,
+            compacted_on: None,
+            pending_alerts: Vec::new(),
+            #[cfg(feature = "influx")]
+            influx: None,
+            #[cfg(feature = "agent")]
+            agent: None,
+            settings,
+        }
+    }
+
+    /// One snapshot in which `app` has read and written `bytes` in total.
+    fn snapshot(app: &str, bytes: u64) -> ProcessData {
+        ProcessData::from([(
+            CounterKey::Process(sysinfo::Pid::from(1), 0),
+            Counters::new(app.to_string(), bytes),
+        )])
+    }
+
+    #[tokio::test]
+    async fn test_failed_budget_alert_is_retried() {
+        let mut settings = Settings::default();
+        settings.budget.total_bytes = Some(1000);
+        let channel = Arc::new(RecordingChannel::failing());
+        let mut daemon = test_daemon(
+            settings,
+            [snapshot("curl", 0), snapshot("curl", 600)],
+            channel.clone(),
+        );
+
+        monitor_processes(&mut daemon, None).await.unwrap();
+        monitor_processes(&mut daemon, None).await.unwrap();
+        assert_eq!(channel.apps(), [DEFAULT_APP_NAME]);
+        assert!(daemon.tracker.budget.notified.is_empty());
+
+        // The milestone is still pending, so the next scan alerts again.
+        channel.set_failing(false);
+        monitor_processes(&mut daemon, None).await.unwrap();
+        assert_eq!(channel.apps(), [DEFAULT_APP_NAME; 2]);
+        assert_eq!(daemon.tracker.budget.notified, [50]);
+
+        monitor_processes(&mut daemon, None).await.unwrap();
+        assert_eq!(channel.apps().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retried_limit_alert_records_fingerprint() {
+        let settings = Settings {
+            data_limit: 100,
+            ..Settings::default()
+        };
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::failing());
+        let events = broadcast::Sender::new(4);
+        let mut receiver = events.subscribe();
+        let mut daemon = test_daemon(
+            settings,
+            [
+                snapshot("curl", 0),
+                snapshot("curl", 500),
+                snapshot("curl", 500),
+            ],
+            channel.clone(),
+        );
+        let notifier = NotificationManager::new(Duration::from_secs(60))
+            .with_config(NotificationConfig {
+                channels: Vec::new(),
+                retry_attempts: 1,
+                retry_backoff_ms: 10,
+                cooldown_after_failure: false,
+                ..NotificationConfig::default()
+            })
+            .with_channel(channel.clone())
+            .with_clock(Arc::new(clock.clone()))
+            .with_events(events);
+        daemon.notifier = Box::leak(Box::new(notifier));
+
+        monitor_processes(&mut daemon, None).await.unwrap();
+        monitor_processes(&mut daemon, None).await.unwrap();
+        assert_eq!(channel.apps(), ["curl"]);
+        assert_eq!(daemon.tracker.apps["curl"].alerted, None);
+        assert_eq!(daemon.pending_alerts.len(), 1);
+
+        // The retry delivers the alert after the scan has moved on.
+        channel.set_failing(false);
+        clock.advance(Duration::from_millis(10));
+        assert!(matches!(receiver.recv().await.unwrap(), Event::Alert(_)));
+        monitor_processes(&mut daemon, None).await.unwrap();
+        assert_eq!(channel.apps(), ["curl"; 2]);
+        assert!(daemon.tracker.apps["curl"].alerted.is_some());
+        assert!(daemon.pending_alerts.is_empty());
+        assert_eq!(
+            daemon.metrics.alert_counts(),
+            [("sent", 1), ("suppressed", 1), ("failed", 0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grace_ends_after_app_exits() {
+        let settings = Settings {
+            data_limit: 100,
+            new_app_grace_seconds: 600,
+            ..Settings::default()
+        };
+        let channel = Arc::new(RecordingChannel::new());
+        let mut daemon = test_daemon(
+            settings,
+            [
+                snapshot("curl", 0),
+                snapshot("curl", 500),
+                ProcessData::new(),
+            ],
+            channel.clone(),
+        );
+
+        for _ in 0..3 {
+            monitor_processes(&mut daemon, None).await.unwrap();
+        }
+        assert!(channel.apps().is_empty());
+
+        // The grace period ends between two scans, after curl has exited.
+        let seen = Utc::now() - TimeDelta::seconds(601);
+        daemon.tracker.apps.get_mut("curl").unwrap().first_seen = Some(seen);
+        daemon.tracker.state.last_scan = Some(seen + TimeDelta::seconds(599));
+        monitor_processes(&mut daemon, None).await.unwrap();
+        assert_eq!(channel.apps(), ["curl"]);
+        assert!(daemon.tracker.apps["curl"].alerted.is_some());
+
+        monitor_processes(&mut daemon, None).await.unwrap();
+        assert_eq!(channel.apps(), ["curl"]);
+    }
+
+    #[tokio::test]
+    async fn test_alerts_past_the_deadline_are_skipped() {
+        let channel = Arc::new(RecordingChannel::new());
+        let daemon = test_daemon(Settings::default(), [], channel.clone());
+        let send = async |app, deadline| {
+            send_alert_within(daemon.notifier, &daemon.metrics, &Alert::new(app), deadline).await
+        };
+
+        assert!(send("curl", Instant::now() + Duration::from_secs(60)).await);
+        assert!(!send("wget", Instant::now()).await);
+        assert_eq!(channel.apps(), ["curl"]);
+        assert_eq!(
+            daemon.metrics.alert_counts(),
+            [("sent", 1), ("suppressed", 1), ("failed", 0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_data_is_not_rewritten() {
+        let dir = STATE_DIR.get_or_init(|| tempfile::tempdir().unwrap().keep());
+        let path = PersistencePaths::new(dir).data_path();
+        let channel = Arc::new(RecordingChannel::new());
+        let mut daemon = test_daemon(Settings::default(), [], channel);
+        let save = async |daemon: &mut Daemon| {
+            daemon.saves.mark_dirty();
+            daemon.save_usage(false).await
+        };
+        daemon.tracker.add_usage("firefox", 100);
+
+        assert!(save(&mut daemon).await.unwrap().is_some());
+        let first = std::fs::read(&path).unwrap();
+        let saved_at = daemon.tracker.state.last_save;
+        assert!(saved_at.is_some());
+
+        // The save stamp alone does not make the data differ.
+        assert_eq!(save(&mut daemon).await.unwrap(), None);
+        assert_eq!(std::fs::read(&path).unwrap(), first);
+        assert_eq!(daemon.tracker.state.last_save, saved_at);
+
+        daemon.tracker.add_usage("firefox", 1);
+        assert!(save(&mut daemon).await.unwrap().is_some());
+        assert_ne!(std::fs::read(&path).unwrap(), first);
+        let saved_at = daemon.tracker.state.last_save;
+
+        // A failed write leaves the last save as it was.
+        daemon.tracker.add_usage("firefox", 1);
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::write(dir, b"").unwrap();
+        assert!(save(&mut daemon).await.is_err());
+        assert_eq!(daemon.tracker.state.last_save, saved_at);
+        assert!(daemon.saves.is_dirty());
+        std::fs::remove_file(dir).unwrap();
+        std::fs::create_dir(dir).unwrap();
+        assert!(daemon.save_usage(true).await.unwrap().is_some());
+        assert!(daemon.tracker.state.last_save > saved_at);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_data_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = PersistencePaths::new(dir.path());
+        let read = async || readable_usage(persistence::read_usage(&paths).await);
+        assert!(read().await.unwrap().is_none());
+
+        std::fs::write(paths.data_path(), b"not usage data").unwrap();
+        let error = read().await.unwrap_err().to_string();
+        assert!(error.starts_with("Failed to read "), "{error}");
+        assert!(error.contains("usage.dat"), "{error}");
+
+        let mut tracker = UsageTracker::default();
+        tracker.add_usage("curl", 100);
+        persistence::save(&paths, &tracker).await.unwrap();
+        assert_eq!(read().await.unwrap().unwrap().usage(), tracker.usage());
+    }
+
+    #[test]
+    fn test_over_limit() {
+        let mut settings = Settings {
+            data_limit: 100,
+            ..Settings::default()
+        };
+        let disk = format!("{}sda", monitor::DISK_PREFIX);
+        settings.disk_limits.insert("sda".to_string(), 1000);
+        settings.watched_paths.insert(
+            "cache".to_string(),
+            data_guardian::watched_paths::WatchedPath {
+                path: "/var/cache".into(),
+                limit: Some(1000),
+                ..Default::default()
+            },
+        );
+        let usage: monitor::UsageData = [
+            ("zoom".to_string(), 101),
+            ("curl".to_string(), 100),
+            ("steam".to_string(), 500),
+            (disk.clone(), 500),
+            ("path:cache".to_string(), 2000),
+            ("path:unlimited".to_string(), 2000),
+        ]
+        .into();
+        assert_eq!(
+            status::over_limit(&usage, &settings),
+            ["path:cache", "steam", "zoom"]
+        );
+
+        settings.disk_limits.insert("sda".to_string(), 10);
+        assert_eq!(
+            status::over_limit(&usage, &settings),
+            [disk.as_str(), "path:cache", "steam", "zoom"]
+        );
+    }
+
+    #[test]
+    fn test_reload_keeps_notifier_settings() {
+        let running = Settings::default();
+        let mut settings = Settings {
+            data_limit: 100,
+            alerts_log: !running.alerts_log,
+            locale: Some("de".to_string()),
+            ..Settings::default()
+        };
+        settings.notifications.retry_attempts += 1;
+        settings.escalation.critical_cooldown_seconds += 60;
+
+        assert_eq!(
+            keep_notifier_settings(&mut settings, &running),
+            [
+                "alerts_log",
+                "escalation.critical_cooldown_seconds",
+                "locale",
+                "notifications.retry_attempts",
+            ]
+        );
+        let changes = running.diff(&settings);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field, "data_limit");
+        assert!(keep_notifier_settings(&mut settings, &running).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_command_line_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "data_limit = 2000000\nlog_level = \"warn\"\n").unwrap();
+        let mut daemon = test_daemon(Settings::default(), [], Arc::new(RecordingChannel::new()));
+        daemon.config_path = Some(ResolvedPath {
+            path,
+            source: PathSource::Flag,
+        });
+        daemon.overrides = SettingsOverrides {
+            log_level: Some("debug".to_string()),
+            ..SettingsOverrides::default()
+        };
+
+        let response = daemon.handle_control(ControlRequest::Reload).await;
+        assert!(response.ok, "{:?}", response.error);
+        assert_eq!(daemon.settings.data_limit, 2_000_000);
+        assert_eq!(daemon.settings.log_level, "debug");
+    }
+}
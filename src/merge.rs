@@ -0,0 +1,409 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::monitor;
+use super::tracker::{AppRecord, DayUsage, TRACKER_VERSION, UsageTracker};
+
+/// Extension of the optional file next to a data file that holds the name of
+/// the machine it came from, e.g. `laptop.dat.host`.
+pub const HOST_SIDECAR_EXTENSION: &str = "host";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeStrategy {
+    /// Add up the usage of apps seen on several machines
+    Sum,
+    /// Keep the record with the most usage, for copies of the same machine's data
+    Max,
+    /// Keep each machine's apps apart as `host/app`
+    KeepPerHost,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MergeError {
+    #[error("Nothing to merge: no input data")]
+    NoInputs,
+    #[error("Input {index} has no host label, which keep-per-host needs")]
+    MissingHost { index: usize },
+    #[error("Host labels only apply to the keep-per-host strategy")]
+    UnexpectedHost,
+    #[error("Invalid host label {0:?}: it must be non-empty and must not contain '/'")]
+    InvalidHost(String),
+    #[error("Host label {0:?} is used by more than one input")]
+    DuplicateHost(String),
+    #[error("Input {index} was written by a newer version (format {version})")]
+    NewerVersion { index: usize, version: u32 },
+    #[error("Inputs cover different periods (starting {0:?} and {1:?})")]
+    PeriodMismatch(Option<NaiveDate>, Option<NaiveDate>),
+}
+
+/// One machine's usage to merge.
+#[derive(Debug, Clone, Default)]
+pub struct MergeInput {
+    pub tracker: UsageTracker,
+    /// Required by `KeepPerHost`, rejected by the other strategies.
+    pub host: Option<String>,
+}
+
+impl MergeInput {
+    pub fn new(tracker: UsageTracker) -> Self {
+        Self {
+            tracker,
+            host: None,
+        }
+    }
+
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+}
+
+/// Merges `others` into `base` and returns the combined tracker. All inputs,
+/// including a non-empty `base`, must cover the same period. Inputs are
+/// expected to be loaded through `UsageTracker::from_compressed`, which
+/// upgrades older formats; data from a newer format is rejected.
+///
+/// Samples only make sense for a single machine, so merged records start
+/// without them and forecasts warm up again.
+pub fn merge_usage(
+    base: UsageTracker,
+    others: Vec<MergeInput>,
+    strategy: MergeStrategy,
+) -> Result<UsageTracker, MergeError> {
+    if others.is_empty() {
+        return Err(MergeError::NoInputs);
+    }
+    check_inputs(&base, &others, strategy)?;
+
+    let mut merged = UsageTracker {
+        version: TRACKER_VERSION,
+        ..base
+    };
+    for input in others {
+        let mut tracker = input.tracker;
+        if let Some(host) = &input.host {
+            tracker = with_host(tracker, host);
+        }
+        merged.period_start = merged.period_start.or(tracker.period_start);
+        merged.timezone = merged.timezone.or(tracker.timezone);
+        for (app, record) in tracker.apps {
+            match merged.apps.get_mut(&app) {
+                Some(existing) => merge_record(existing, record, strategy),
+                None => {
+                    merged.apps.insert(app, without_samples(record));
+                }
+            }
+        }
+        for (history, merged) in [
+            (tracker.days, &mut merged.days),
+            (tracker.months, &mut merged.months),
+        ] {
+            for (date, apps) in history {
+                let day = merged.entry(date).or_default();
+                for (app, usage) in apps {
+                    let existing = day.entry(app).or_default();
+                    *existing = merge_day(*existing, usage, strategy);
+                }
+            }
+        }
+    }
+    Ok(merged)
+}
+
+fn check_inputs(
+    base: &UsageTracker,
+    others: &[MergeInput],
+    strategy: MergeStrategy,
+) -> Result<(), MergeError> {
+    let mut hosts = HashSet::new();
+    let mut period = (!base.apps.is_empty()).then_some(base.period_start);
+    for (index, input) in others.iter().enumerate() {
+        let index = index + 1;
+        if input.tracker.version > TRACKER_VERSION {
+            return Err(MergeError::NewerVersion {
+                index,
+                version: input.tracker.version,
+            });
+        }
+        match (&input.host, strategy) {
+            (None, MergeStrategy::KeepPerHost) => return Err(MergeError::MissingHost { index }),
+            (Some(_), MergeStrategy::Sum | MergeStrategy::Max) => {
+                return Err(MergeError::UnexpectedHost);
+            }
+            (Some(host), _) if host.is_empty() || host.contains('/') => {
+                return Err(MergeError::InvalidHost(host.clone()));
+            }
+            (Some(host), _) if !hosts.insert(host) => {
+                return Err(MergeError::DuplicateHost(host.clone()));
+            }
+            _ => {}
+        }
+        match period {
+            Some(start) if start != input.tracker.period_start => {
+                return Err(MergeError::PeriodMismatch(
+                    start,
+                    input.tracker.period_start,
+                ));
+            }
+            _ => period = Some(input.tracker.period_start),
+        }
+    }
+    Ok(())
+}
+
+/// Renames apps to `host/app`, disks to `disk:host/device`, interfaces to
+/// `net:host/interface` and watched paths to `path:host/label`, so they stay
+/// apart from other machines and keep their kind.
+fn with_host(mut tracker: UsageTracker, host: &str) -> UsageTracker {
+    let key = |app: String| {
+        if let Some(device) = monitor::disk_name(&app) {
+            format!("{}{}/{}", monitor::DISK_PREFIX, host, device)
+        } else if let Some(interface) = monitor::interface_name(&app) {
+            format!("{}{}/{}", monitor::NET_PREFIX, host, interface)
+        } else if let Some(label) = monitor::path_label(&app) {
+            format!("{}{}/{}", monitor::PATH_PREFIX, host, label)
+        } else {
+            format!("{}/{}", host, app)
+        }
+    };
+    tracker.apps = tracker
+        .apps
+        .into_iter()
+        .map(|(app, record)| (key(app), record))
+        .collect();
+    for apps in tracker.days.values_mut().chain(tracker.months.values_mut()) {
+        *apps = std::mem::take(apps)
+            .into_iter()
+            .map(|(app, usage)| (key(app), usage))
+            .collect::<HashMap<_, _>>();
+    }
+    tracker
+}
+
+/// Renames every app with `rename`, also in the daily and monthly history,
+/// and adds up apps that end up with the same name. Disks, interfaces and
+/// watched paths are passed to `rename` too. Returns how many apps were
+/// merged into another.
+pub fn rename_apps(
+    mut tracker: UsageTracker,
+    rename: impl Fn(&str) -> String,
+) -> (UsageTracker, usize) {
+    let mut merged = 0;
+    let mut apps = HashMap::with_capacity(tracker.apps.len());
+    for (app, record) in tracker.apps {
+        match apps.entry(rename(&app)) {
+            Entry::Occupied(mut entry) => {
+                merge_record(entry.get_mut(), record, MergeStrategy::Sum);
+                merged += 1;
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(record);
+            }
+        }
+    }
+    tracker.apps = apps;
+    for history in tracker.days.values_mut().chain(tracker.months.values_mut()) {
+        let mut renamed = HashMap::with_capacity(history.len());
+        for (app, usage) in std::mem::take(history) {
+            let existing: &mut DayUsage = renamed.entry(rename(&app)).or_default();
+            *existing = merge_day(*existing, usage, MergeStrategy::Sum);
+        }
+        *history = renamed;
+    }
+    (tracker, merged)
+}
+
+fn without_samples(mut record: AppRecord) -> AppRecord {
+    record.samples.clear();
+    record
+}
+
+fn merge_record(existing: &mut AppRecord, other: AppRecord, strategy: MergeStrategy) {
+    if strategy == MergeStrategy::Max {
+        if other.bytes > existing.bytes {
+            *existing = without_samples(other);
+        }
+        return;
+    }
+
+    existing.bytes = existing.bytes.saturating_add(other.bytes);
+    existing.streak = existing.streak.max(other.streak);
+    existing.last_exceeded = existing.last_exceeded.max(other.last_exceeded);
+    existing.samples.clear();
+    existing.deltas.merge(&other.deltas);
+    existing.cpu_time_ms = match (existing.cpu_time_ms, other.cpu_time_ms) {
+        (Some(a), Some(b)) => Some(a.saturating_add(b)),
+        (a, b) => a.or(b),
+    };
+    existing.peak_memory_bytes = existing.peak_memory_bytes.max(other.peak_memory_bytes);
+    existing.last_active = existing.last_active.max(other.last_active);
+
+    let stats = &mut existing.exceedance;
+    let other = other.exceedance;
+    stats.events = stats.events.saturating_add(other.events);
+    stats.first_exceeded = earliest(stats.first_exceeded, other.first_exceeded);
+    stats.last_exceeded = stats.last_exceeded.max(other.last_exceeded);
+    stats.over_since = earliest(stats.over_since, other.over_since);
+    stats.seconds_over = stats.seconds_over.saturating_add(other.seconds_over);
+}
+
+fn merge_day(existing: DayUsage, other: DayUsage, strategy: MergeStrategy) -> DayUsage {
+    match strategy {
+        MergeStrategy::Max if other.bytes > existing.bytes => other,
+        MergeStrategy::Max => existing,
+        MergeStrategy::Sum | MergeStrategy::KeepPerHost => DayUsage {
+            bytes: existing.bytes.saturating_add(other.bytes),
+            written: existing.written.saturating_add(other.written),
+            exceeded: existing.exceeded.saturating_add(other.exceeded),
+        },
+    }
+}
+
+fn earliest<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, d).unwrap()
+    }
+
+    fn machine(usage: &[(&str, u64)]) -> UsageTracker {
+        let mut tracker = UsageTracker::from(
+            usage
+                .iter()
+                .map(|(app, bytes)| (app.to_string(), *bytes))
+                .collect::<HashMap<_, _>>(),
+        );
+        tracker.period_start = Some(date(1));
+        for (app, bytes) in usage {
+            tracker.add_daily_usage(app, *bytes, 0, date(2));
+        }
+        tracker
+    }
+
+    #[test]
+    fn test_merge_sum_and_max() {
+        let inputs = || {
+            vec![
+                MergeInput::new(machine(&[("firefox", 100), ("disk:sda", 5)])),
+                MergeInput::new(machine(&[("firefox", 30), ("curl", 7)])),
+            ]
+        };
+
+        let sum = merge_usage(UsageTracker::default(), inputs(), MergeStrategy::Sum).unwrap();
+        assert_eq!(sum.period_start, Some(date(1)));
+        assert_eq!(
+            sum.usage(),
+            HashMap::from([
+                ("firefox".to_string(), 130),
+                ("curl".to_string(), 7),
+                ("disk:sda".to_string(), 5),
+            ])
+        );
+        assert_eq!(sum.days[&date(2)]["firefox"].bytes, 130);
+
+        let max = merge_usage(UsageTracker::default(), inputs(), MergeStrategy::Max).unwrap();
+        assert_eq!(max.usage()["firefox"], 100);
+        assert_eq!(max.days[&date(2)]["firefox"].bytes, 100);
+    }
+
+    #[test]
+    fn test_merge_keep_per_host() {
+        let merged = merge_usage(
+            UsageTracker::default(),
+            vec![
+                MergeInput::new(machine(&[("firefox", 100), ("disk:sda", 5)])).with_host("laptop"),
+                MergeInput::new(machine(&[("firefox", 30), ("net:wwan0", 9)])).with_host("desktop"),
+            ],
+            MergeStrategy::KeepPerHost,
+        )
+        .unwrap();
+
+        let mut apps: Vec<_> = merged.usage().into_iter().collect();
+        apps.sort();
+        assert_eq!(
+            apps,
+            [
+                ("desktop/firefox".to_string(), 30),
+                ("disk:laptop/sda".to_string(), 5),
+                ("laptop/firefox".to_string(), 100),
+                ("net:desktop/wwan0".to_string(), 9),
+            ]
+        );
+        assert_eq!(monitor::disk_name("disk:laptop/sda"), Some("laptop/sda"));
+        assert!(merged.days[&date(2)].contains_key("desktop/firefox"));
+    }
+
+    #[test]
+    fn test_merge_errors() {
+        let merge = |inputs, strategy| merge_usage(UsageTracker::default(), inputs, strategy);
+        let input = || MergeInput::new(machine(&[("firefox", 1)]));
+
+        assert_eq!(
+            merge(Vec::new(), MergeStrategy::Sum).unwrap_err(),
+            MergeError::NoInputs
+        );
+        assert_eq!(
+            merge(vec![input()], MergeStrategy::KeepPerHost).unwrap_err(),
+            MergeError::MissingHost { index: 1 }
+        );
+        assert_eq!(
+            merge(vec![input().with_host("laptop")], MergeStrategy::Sum).unwrap_err(),
+            MergeError::UnexpectedHost
+        );
+        assert_eq!(
+            merge(
+                vec![input().with_host("a"), input().with_host("a")],
+                MergeStrategy::KeepPerHost
+            )
+            .unwrap_err(),
+            MergeError::DuplicateHost("a".to_string())
+        );
+
+        let mut newer = input();
+        newer.tracker.version = TRACKER_VERSION + 1;
+        assert!(matches!(
+            merge(vec![input(), newer], MergeStrategy::Sum),
+            Err(MergeError::NewerVersion { index: 2, .. })
+        ));
+
+        let mut other_period = input();
+        other_period.tracker.period_start = Some(date(8));
+        assert_eq!(
+            merge(vec![input(), other_period], MergeStrategy::Max).unwrap_err(),
+            MergeError::PeriodMismatch(Some(date(1)), Some(date(8)))
+        );
+    }
+
+    #[test]
+    fn test_delta_stats_merge() {
+        let mut all = crate::tracker::DeltaStats::default();
+        let mut a = all;
+        let mut b = all;
+        for (i, delta) in [3, 9, 4, 12, 7].into_iter().enumerate() {
+            all.observe(delta);
+            if i < 2 {
+                a.observe(delta)
+            } else {
+                b.observe(delta)
+            }
+        }
+        a.merge(&b);
+        assert_eq!(a.count, all.count);
+        assert!((a.mean - all.mean).abs() < 1e-9);
+        assert!((a.variance() - all.variance()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_MAX_APP_LABELS: usize = 100;
+pub const OVERFLOW_LABEL: &str = "other";
+pub const MAX_LABEL_LEN: usize = 128;
+pub const SCAN_DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [u64; SCAN_DURATION_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(SCAN_DURATION_BUCKETS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Alert outcomes since the service started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertCounts {
+    pub sent: u64,
+    pub suppressed: u64,
+    pub failed: u64,
+}
+
+#[derive(Debug)]
+pub struct Metrics {
+    max_app_labels: usize,
+    alerts_sent: AtomicU64,
+    alerts_suppressed: AtomicU64,
+    alerts_failed: AtomicU64,
+    persisted_bytes: AtomicU64,
+    process_count: AtomicU64,
+    rejected_bytes: AtomicU64,
+    scan_duration: Mutex<Histogram>,
+    app_usage: Mutex<Vec<(String, u64)>>,
+    host: Option<String>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_APP_LABELS)
+    }
+}
+
+impl Metrics {
+    pub fn new(max_app_labels: usize) -> Self {
+        Self {
+            max_app_labels,
+            alerts_sent: AtomicU64::new(0),
+            alerts_suppressed: AtomicU64::new(0),
+            alerts_failed: AtomicU64::new(0),
+            persisted_bytes: AtomicU64::new(0),
+            process_count: AtomicU64::new(0),
+            rejected_bytes: AtomicU64::new(0),
+            scan_duration: Mutex::new(Histogram::default()),
+            app_usage: Mutex::new(Vec::new()),
+            host: None,
+        }
+    }
+
+    /// Adds a `host` label with the instance label to every series.
+    pub fn with_host(mut self, host: &str) -> Self {
+        self.host = Some(host.to_string());
+        self
+    }
+
+    pub fn alert_sent(&self) {
+        self.alerts_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn alert_suppressed(&self) {
+        self.alerts_suppressed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn alert_failed(&self) {
+        self.alerts_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_persisted_bytes(&self, bytes: u64) {
+        self.persisted_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    pub fn set_process_count(&self, count: usize) {
+        self.process_count.store(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn delta_rejected(&self, bytes: u64) {
+        self.rejected_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn observe_scan(&self, duration: Duration) {
+        self.scan_duration
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn set_app_usage(&self, usage: &HashMap<String, u64>) {
+        let mut apps: HashMap<String, u64> = HashMap::with_capacity(usage.len());
+        for (app, bytes) in usage {
+            *apps.entry(clean_label(app)).or_insert(0) += bytes;
+        }
+
+        let mut apps: Vec<_> = apps.into_iter().collect();
+        apps.sort_by(|(a_name, a_usage), (b_name, b_usage)| {
+            b_usage.cmp(a_usage).then_with(|| a_name.cmp(b_name))
+        });
+
+        if apps.len() > self.max_app_labels {
+            let keep = self.max_app_labels.saturating_sub(1);
+            let overflow: u64 = apps.drain(keep..).map(|(_, bytes)| bytes).sum();
+            match apps.iter_mut().find(|(name, _)| name == OVERFLOW_LABEL) {
+                Some((_, bytes)) => *bytes += overflow,
+                None => apps.push((OVERFLOW_LABEL.to_string(), overflow)),
+            }
+        }
+
+        *self.app_usage.lock().unwrap_or_else(|e| e.into_inner()) = apps;
+    }
+
+    pub fn app_usage(&self) -> Vec<(String, u64)> {
+        self.app_usage
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn alert_counts(&self) -> [(&'static str, u64); 3] {
+        let alerts = self.alerts();
+        [
+            ("sent", alerts.sent),
+            ("suppressed", alerts.suppressed),
+            ("failed", alerts.failed),
+        ]
+    }
+
+    pub fn alerts(&self) -> AlertCounts {
+        AlertCounts {
+            sent: self.alerts_sent.load(Ordering::Relaxed),
+            suppressed: self.alerts_suppressed.load(Ordering::Relaxed),
+            failed: self.alerts_failed.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let host = self
+            .host
+            .as_deref()
+            .map(|host| format!("host=\"{}\"", escape_label(host)));
+        let labels = |label: Option<String>| -> String {
+            match host.iter().cloned().chain(label).collect::<Vec<_>>() {
+                labels if labels.is_empty() => String::new(),
+                labels => format!("{{{}}}", labels.join(",")),
+            }
+        };
+
+        out.push_str("# HELP data_guardian_app_usage_bytes Disk I/O accumulated per application in the current period\n");
+        out.push_str("# TYPE data_guardian_app_usage_bytes gauge\n");
+        for (app, bytes) in self.app_usage() {
+            let _ = writeln!(
+                out,
+                "data_guardian_app_usage_bytes{} {bytes}",
+                labels(Some(format!("app=\"{}\"", escape_label(&app))))
+            );
+        }
+
+        out.push_str("# HELP data_guardian_alerts_total Usage alerts by outcome\n");
+        out.push_str("# TYPE data_guardian_alerts_total counter\n");
+        for (outcome, count) in self.alert_counts() {
+            let _ = writeln!(
+                out,
+                "data_guardian_alerts_total{} {count}",
+                labels(Some(format!("outcome=\"{outcome}\"")))
+            );
+        }
+
+        let histogram = self.scan_duration.lock().unwrap_or_else(|e| e.into_inner());
+        out.push_str("# HELP data_guardian_scan_duration_seconds Time spent scanning processes\n");
+        out.push_str("# TYPE data_guardian_scan_duration_seconds histogram\n");
+        for (bound, count) in SCAN_DURATION_BUCKETS.iter().zip(histogram.buckets) {
+            let _ = writeln!(
+                out,
+                "data_guardian_scan_duration_seconds_bucket{} {count}",
+                labels(Some(format!("le=\"{bound}\"")))
+            );
+        }
+        let _ = writeln!(
+            out,
+            "data_guardian_scan_duration_seconds_bucket{} {}",
+            labels(Some("le=\"+Inf\"".to_string())),
+            histogram.count
+        );
+        let _ = writeln!(
+            out,
+            "data_guardian_scan_duration_seconds_sum{} {}",
+            labels(None),
+            histogram.sum
+        );
+        let _ = writeln!(
+            out,
+            "data_guardian_scan_duration_seconds_count{} {}",
+            labels(None),
+            histogram.count
+        );
+        drop(histogram);
+
+        out.push_str("# HELP data_guardian_persisted_bytes Size of the persisted usage file\n");
+        out.push_str("# TYPE data_guardian_persisted_bytes gauge\n");
+        let _ = writeln!(
+            out,
+            "data_guardian_persisted_bytes{} {}",
+            labels(None),
+            self.persisted_bytes.load(Ordering::Relaxed)
+        );
+
+        out.push_str("# HELP data_guardian_processes Number of processes seen in the last scan\n");
+        out.push_str("# TYPE data_guardian_processes gauge\n");
+        let _ = writeln!(
+            out,
+            "data_guardian_processes{} {}",
+            labels(None),
+            self.process_count.load(Ordering::Relaxed)
+        );
+
+        out.push_str("# HELP data_guardian_rejected_bytes_total Implausible usage deltas left out of app totals\n");
+        out.push_str("# TYPE data_guardian_rejected_bytes_total counter\n");
+        let _ = writeln!(
+            out,
+            "data_guardian_rejected_bytes_total{} {}",
+            labels(None),
+            self.rejected_bytes.load(Ordering::Relaxed)
+        );
+
+        #[cfg(feature = "otel")]
+        {
+            out.push_str("# HELP data_guardian_otel_dropped_total Telemetry items dropped after failed OTLP exports\n");
+            out.push_str("# TYPE data_guardian_otel_dropped_total counter\n");
+            let _ = writeln!(
+                out,
+                "data_guardian_otel_dropped_total{} {}",
+                labels(None),
+                super::telemetry::dropped_exports()
+            );
+        }
+
+        out
+    }
+}
+
+pub fn clean_label(value: &str) -> String {
+    value
+        .chars()
+        .take(MAX_LABEL_LEN)
+        .map(|c| {
+            if (c.is_control() && c != '\n') || c == char::REPLACEMENT_CHARACTER {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+pub fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub fn sanitize_label(value: &str) -> String {
+    escape_label(&clean_label(value))
+}
+
+#[cfg(feature = "metrics")]
+pub async fn serve(
+    listener: tokio::net::TcpListener,
+    metrics: std::sync::Arc<Metrics>,
+) -> std::io::Result<()> {
+    use super::http::{self, Response};
+
+    http::serve(listener, move |request| match request.path.as_str() {
+        "/metrics" => Response::ok("text/plain; version=0.0.4", metrics.render()),
+        _ => Response::not_found(),
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_label() {
+        assert_eq!(sanitize_label("firefox"), "firefox");
+        assert_eq!(sanitize_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+        assert_eq!(sanitize_label("bad\u{0}\u{FFFD}name"), "bad__name");
+        assert_eq!(
+            sanitize_label(&"x".repeat(MAX_LABEL_LEN * 2)).len(),
+            MAX_LABEL_LEN
+        );
+    }
+
+    #[test]
+    fn test_app_label_overflow() {
+        let metrics = Metrics::new(3);
+        let usage = HashMap::from([
+            ("a".to_string(), 100),
+            ("b".to_string(), 50),
+            ("c".to_string(), 10),
+            ("d".to_string(), 5),
+        ]);
+        metrics.set_app_usage(&usage);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("data_guardian_app_usage_bytes{app=\"a\"} 100"));
+        assert!(rendered.contains("data_guardian_app_usage_bytes{app=\"b\"} 50"));
+        assert!(rendered.contains("data_guardian_app_usage_bytes{app=\"other\"} 15"));
+        assert!(!rendered.contains("app=\"c\""));
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.alert_sent();
+        metrics.alert_sent();
+        metrics.alert_suppressed();
+        metrics.set_process_count(42);
+        metrics.set_persisted_bytes(1024);
+        metrics.delta_rejected(4096);
+        metrics.observe_scan(Duration::from_millis(30));
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("data_guardian_alerts_total{outcome=\"sent\"} 2"));
+        assert!(rendered.contains("data_guardian_alerts_total{outcome=\"suppressed\"} 1"));
+        assert!(rendered.contains("data_guardian_alerts_total{outcome=\"failed\"} 0"));
+        assert!(rendered.contains("data_guardian_scan_duration_seconds_bucket{le=\"0.025\"} 0"));
+        assert!(rendered.contains("data_guardian_scan_duration_seconds_bucket{le=\"0.05\"} 1"));
+        assert!(rendered.contains("data_guardian_scan_duration_seconds_count 1"));
+        assert!(rendered.contains("data_guardian_persisted_bytes 1024"));
+        assert!(rendered.contains("data_guardian_processes 42"));
+        assert!(rendered.contains("data_guardian_rejected_bytes_total 4096"));
+    }
+
+    #[test]
+    fn test_host_label() {
+        let metrics = Metrics::new(3).with_host("office-nas");
+        metrics.set_app_usage(&HashMap::from([("a".to_string(), 100)]));
+        metrics.observe_scan(Duration::from_millis(30));
+
+        let rendered = metrics.render();
+        assert!(
+            rendered.contains("data_guardian_app_usage_bytes{host=\"office-nas\",app=\"a\"} 100")
+        );
+        assert!(
+            rendered.contains("data_guardian_alerts_total{host=\"office-nas\",outcome=\"sent\"} 0")
+        );
+        assert!(
+            rendered.contains("data_guardian_scan_duration_seconds_count{host=\"office-nas\"} 1")
+        );
+        assert!(rendered.contains("data_guardian_processes{host=\"office-nas\"} 0"));
+        assert!(
+            rendered
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .all(|line| line.contains("host=\"office-nas\""))
+        );
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn test_serve_metrics() {
+        use std::sync::Arc;
+
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::{TcpListener, TcpStream};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let metrics = Arc::new(Metrics::default());
+        metrics.set_process_count(7);
+        tokio::spawn(serve(listener, metrics));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("data_guardian_processes 7"));
+    }
+}
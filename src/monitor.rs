@@ -0,0 +1,1397 @@
+#![cfg_attr(not(feature = "monitor"), allow(unused_imports, dead_code))]
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "monitor")]
+use sysinfo::{DiskRefreshKind, Disks, Networks, Pid, Process, System};
+use thiserror::Error;
+#[cfg(feature = "monitor")]
+use tokio::task::JoinError;
+#[cfg(feature = "monitor")]
+use tracing::{instrument, warn};
+
+#[cfg(feature = "monitor")]
+use super::app_names::{AppNameError, AppNames};
+#[cfg(feature = "monitor")]
+use super::cgroup::CgroupSource;
+#[cfg(feature = "monitor")]
+use super::clock::Clock;
+#[cfg(feature = "monitor")]
+use super::filter::{FilterChain, FilterError, Owner};
+#[cfg(feature = "monitor")]
+use super::identity::Resolver;
+#[cfg(feature = "monitor")]
+use super::session::{RootProcess, Session, SessionStatus};
+
+#[cfg(feature = "monitor")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CounterKey {
+    /// PID and start time, so a reused PID is a different process.
+    Process(Pid, u64),
+    Cgroup(PathBuf),
+    Disk(String),
+    Interface(String),
+}
+
+pub const DISK_PREFIX: &str = "disk:";
+pub const PATH_PREFIX: &str = "path:";
+/// Traffic of a network interface, such as `net:eth0`.
+pub const NET_PREFIX: &str = "net:";
+/// Loopback traffic never leaves the machine and is not counted.
+pub const LOOPBACK_INTERFACES: &[&str] = &["lo", "lo0"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Counters {
+    pub name: String,
+    /// Bytes read and written.
+    pub bytes: u64,
+    /// The part of `bytes` that was written.
+    pub written_bytes: u64,
+    pub cpu_time_ms: Option<u64>,
+    pub memory_bytes: Option<u64>,
+}
+
+impl Counters {
+    pub fn new(name: String, bytes: u64) -> Self {
+        Self {
+            name,
+            bytes,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_written(mut self, written_bytes: u64) -> Self {
+        self.written_bytes = written_bytes;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub cpu_time_ms: Option<u64>,
+    pub memory_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Collect {
+    pub cpu: bool,
+    pub memory: bool,
+    pub disks: bool,
+    pub network: bool,
+}
+
+#[cfg(feature = "monitor")]
+pub type ProcessData = HashMap<CounterKey, Counters>;
+pub type UsageData = HashMap<String, u64>;
+pub type ResourceData = HashMap<String, ResourceUsage>;
+
+/// Usage between two snapshots, keyed by app name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageDelta {
+    pub usage: UsageData,
+    /// The part of `usage` that was written.
+    pub written: UsageData,
+    /// Deltas above the rate cap, left out of `usage`.
+    pub rejected: UsageData,
+    pub resources: ResourceData,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Process,
+    Cgroup,
+}
+
+#[cfg(feature = "monitor")]
+#[derive(Error, Debug)]
+pub enum MonitorError {
+    #[error("The cgroup backend is only supported on Linux")]
+    Unsupported,
+    #[error("cgroup v2 is not mounted at {0}")]
+    CgroupV2Unavailable(PathBuf),
+    #[error("The io controller is not enabled for child cgroups of {0}")]
+    IoControllerDisabled(PathBuf),
+    #[error("Failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Snapshot task failed: {0}")]
+    Join(#[from] JoinError),
+    #[error(transparent)]
+    Filter(#[from] FilterError),
+    #[error(transparent)]
+    AppNames(#[from] AppNameError),
+    #[error("Reading usage took longer than {}", humantime::format_duration(*.0))]
+    TimedOut(Duration),
+}
+
+/// The former name of [`MonitorError`].
+#[cfg(feature = "monitor")]
+pub type SourceError = MonitorError;
+
+#[cfg(feature = "monitor")]
+pub trait CounterSource: Debug + Send + Sync {
+    fn read(&self) -> Result<ProcessData, MonitorError>;
+
+    fn display_names(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+
+    /// The session at the last read, when only one is counted.
+    fn session(&self) -> Option<SessionStatus> {
+        None
+    }
+}
+
+#[cfg(feature = "monitor")]
+pub fn source(
+    backend: Backend,
+    resolver: Resolver,
+    collect: Collect,
+    threads: usize,
+    filter: FilterChain,
+    root: Option<RootProcess>,
+) -> Result<Arc<dyn CounterSource>, MonitorError> {
+    let inner: Arc<dyn CounterSource> = match backend {
+        Backend::Process => {
+            let source = ProcessSource::new(resolver)
+                .with_collect(collect)
+                .with_threads(threads)
+                .with_filter(filter);
+            Arc::new(match root {
+                Some(root) => source.with_session(Session::new(root)),
+                None => source,
+            })
+        }
+        Backend::Cgroup if cfg!(target_os = "linux") => {
+            if !filter.is_empty() {
+                warn!("Owner filters only apply to the process backend and are ignored");
+            }
+            if root.is_some() {
+                warn!("root_process only applies to the process backend and is ignored");
+            }
+            Arc::new(CgroupSource::detect()?)
+        }
+        Backend::Cgroup => return Err(MonitorError::Unsupported),
+    };
+    let inner: Arc<dyn CounterSource> = if collect.disks {
+        Arc::new(DiskSource::new(inner))
+    } else {
+        inner
+    };
+    if collect.network {
+        return Ok(Arc::new(NetworkSource::new(inner)));
+    }
+    Ok(inner)
+}
+
+pub fn disk_name(app: &str) -> Option<&str> {
+    app.strip_prefix(DISK_PREFIX)
+}
+
+/// Label of a `[watched_paths]` entry, whose usage is its size.
+pub fn path_label(app: &str) -> Option<&str> {
+    app.strip_prefix(PATH_PREFIX)
+}
+
+/// Name of the network interface behind a `net:` entry.
+pub fn interface_name(app: &str) -> Option<&str> {
+    app.strip_prefix(NET_PREFIX)
+}
+
+/// Whether `name` is an application rather than a disk, network interface
+/// or watched path.
+pub fn is_app(name: &str) -> bool {
+    disk_name(name).is_none() && interface_name(name).is_none() && path_label(name).is_none()
+}
+
+pub fn device_name(name: &OsStr, mount_point: &Path) -> String {
+    Path::new(name)
+        .file_name()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(mount_point.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(feature = "monitor")]
+#[derive(Debug)]
+pub struct DiskSource {
+    inner: Arc<dyn CounterSource>,
+}
+
+#[cfg(feature = "monitor")]
+impl DiskSource {
+    pub fn new(inner: Arc<dyn CounterSource>) -> Self {
+        Self { inner }
+    }
+}
+
+#[cfg(feature = "monitor")]
+impl CounterSource for DiskSource {
+    fn read(&self) -> Result<ProcessData, MonitorError> {
+        let mut data = self.inner.read()?;
+        let disks =
+            Disks::new_with_refreshed_list_specifics(DiskRefreshKind::nothing().with_io_usage());
+        for disk in disks.list() {
+            let device = device_name(disk.name(), disk.mount_point());
+            let usage = disk.usage();
+            data.entry(CounterKey::Disk(device.clone()))
+                .or_insert_with(|| {
+                    Counters::new(
+                        format!("{DISK_PREFIX}{device}"),
+                        usage
+                            .total_read_bytes
+                            .saturating_add(usage.total_written_bytes),
+                    )
+                    .with_written(usage.total_written_bytes)
+                });
+        }
+        Ok(data)
+    }
+
+    fn display_names(&self) -> HashMap<String, String> {
+        self.inner.display_names()
+    }
+
+    fn session(&self) -> Option<SessionStatus> {
+        self.inner.session()
+    }
+}
+
+/// Adds the bytes received and sent by each network interface as
+/// `net:<interface>`, with sent bytes counted as written. An interface that
+/// comes and goes, such as a VPN tunnel, only counts once it has been seen
+/// on two scans in a row, and its counters starting over count as nothing.
+#[cfg(feature = "monitor")]
+#[derive(Debug)]
+pub struct NetworkSource {
+    inner: Arc<dyn CounterSource>,
+}
+
+#[cfg(feature = "monitor")]
+impl NetworkSource {
+    pub fn new(inner: Arc<dyn CounterSource>) -> Self {
+        Self { inner }
+    }
+}
+
+#[cfg(feature = "monitor")]
+impl CounterSource for NetworkSource {
+    fn read(&self) -> Result<ProcessData, MonitorError> {
+        let mut data = self.inner.read()?;
+        let networks = Networks::new_with_refreshed_list();
+        for (interface, network) in networks.list() {
+            if LOOPBACK_INTERFACES.contains(&interface.as_str()) {
+                continue;
+            }
+            data.insert(
+                CounterKey::Interface(interface.clone()),
+                Counters::new(
+                    format!("{NET_PREFIX}{interface}"),
+                    network
+                        .total_received()
+                        .saturating_add(network.total_transmitted()),
+                )
+                .with_written(network.total_transmitted()),
+            );
+        }
+        Ok(data)
+    }
+
+    fn display_names(&self) -> HashMap<String, String> {
+        self.inner.display_names()
+    }
+
+    fn session(&self) -> Option<SessionStatus> {
+        self.inner.session()
+    }
+}
+
+/// Renames the applications `inner` reads, so every platform's spelling of
+/// an app is counted as one.
+#[cfg(feature = "monitor")]
+#[derive(Debug)]
+pub struct NormalizedSource {
+    inner: Arc<dyn CounterSource>,
+    names: AppNames,
+}
+
+#[cfg(feature = "monitor")]
+impl NormalizedSource {
+    pub fn new(inner: Arc<dyn CounterSource>, names: AppNames) -> Self {
+        Self { inner, names }
+    }
+}
+
+#[cfg(feature = "monitor")]
+impl CounterSource for NormalizedSource {
+    fn read(&self) -> Result<ProcessData, MonitorError> {
+        let mut data = self.inner.read()?;
+        for counters in data.values_mut() {
+            if let Some(name) = self.names.rename(&counters.name) {
+                counters.name = name;
+            }
+        }
+        Ok(data)
+    }
+
+    fn display_names(&self) -> HashMap<String, String> {
+        self.names
+            .normalize_map(self.inner.display_names(), |_, _| ())
+    }
+
+    fn session(&self) -> Option<SessionStatus> {
+        self.inner.session()
+    }
+}
+
+#[cfg(feature = "monitor")]
+pub trait ProcessEntry: Sync {
+    fn start_time(&self) -> u64;
+    fn name(&self) -> Cow<'_, str>;
+    fn exe(&self) -> Option<&Path>;
+    fn disk_bytes(&self) -> u64;
+    fn written_bytes(&self) -> u64 {
+        0
+    }
+    fn cpu_time_ms(&self) -> u64;
+    fn memory_bytes(&self) -> u64;
+    fn owner(&self) -> Option<Owner> {
+        None
+    }
+
+    fn parent(&self) -> Option<Pid> {
+        None
+    }
+}
+
+#[cfg(feature = "monitor")]
+impl ProcessEntry for Process {
+    fn start_time(&self) -> u64 {
+        Process::start_time(self)
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        Process::name(self).to_string_lossy()
+    }
+
+    fn exe(&self) -> Option<&Path> {
+        Process::exe(self)
+    }
+
+    fn disk_bytes(&self) -> u64 {
+        let usage = self.disk_usage();
+        usage.read_bytes.saturating_add(usage.written_bytes)
+    }
+
+    fn written_bytes(&self) -> u64 {
+        self.disk_usage().written_bytes
+    }
+
+    fn cpu_time_ms(&self) -> u64 {
+        self.accumulated_cpu_time()
+    }
+
+    fn memory_bytes(&self) -> u64 {
+        self.memory()
+    }
+
+    fn parent(&self) -> Option<Pid> {
+        Process::parent(self)
+    }
+
+    #[cfg(unix)]
+    fn owner(&self) -> Option<Owner> {
+        self.user_id().map(|uid| Owner::Uid(**uid))
+    }
+
+    #[cfg(windows)]
+    fn owner(&self) -> Option<Owner> {
+        self.user_id().map(|sid| Owner::Sid(sid.to_string()))
+    }
+}
+
+#[cfg(feature = "monitor")]
+pub const PARALLEL_MIN_PROCESSES: usize = 1024;
+
+#[cfg(feature = "monitor")]
+#[derive(Debug)]
+pub struct ProcessSource {
+    resolver: Resolver,
+    collect: Collect,
+    threads: usize,
+    filter: FilterChain,
+    session: Option<Session>,
+}
+
+#[cfg(feature = "monitor")]
+impl ProcessSource {
+    pub fn new(resolver: Resolver) -> Self {
+        Self {
+            resolver,
+            collect: Collect::default(),
+            threads: 1,
+            filter: FilterChain::default(),
+            session: None,
+        }
+    }
+
+    pub fn with_collect(mut self, collect: Collect) -> Self {
+        self.collect = collect;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = match threads {
+            0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        };
+        self
+    }
+
+    pub fn with_filter(mut self, filter: FilterChain) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Counts only `session`'s processes.
+    pub fn with_session(mut self, session: Session) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    pub fn counters<P: ProcessEntry>(&self, pid: Pid, process: &P) -> Counters {
+        let name = self
+            .resolver
+            .resolve(pid, process.start_time(), &process.name(), process.exe());
+        #[cfg(windows)]
+        self.resolver.describe(&name, process.exe());
+        Counters {
+            name,
+            bytes: process.disk_bytes(),
+            written_bytes: process.written_bytes(),
+            cpu_time_ms: self.collect.cpu.then(|| process.cpu_time_ms()),
+            memory_bytes: self.collect.memory.then(|| process.memory_bytes()),
+        }
+    }
+
+    pub fn map_processes<P: ProcessEntry>(&self, processes: &[(Pid, &P)]) -> ProcessData {
+        let names: Vec<_> = match &self.session {
+            Some(_) => processes
+                .iter()
+                .map(|(_, process)| process.name())
+                .collect(),
+            None => Vec::new(),
+        };
+        let session = self.session.as_ref().map(|session| {
+            session.select(processes.iter().zip(&names).map(|((pid, process), name)| {
+                (
+                    pid.as_u32(),
+                    process.parent().map(|parent| parent.as_u32()),
+                    name.as_ref(),
+                )
+            }))
+        });
+        let session = session.as_ref();
+        let map = |chunk: &[(Pid, &P)]| -> Vec<(CounterKey, Counters)> {
+            chunk
+                .iter()
+                .filter(|(pid, _)| session.is_none_or(|pids| pids.contains(&pid.as_u32())))
+                .filter(|(_, process)| {
+                    self.filter.is_empty() || self.filter.allows(process.owner().as_ref())
+                })
+                .map(|(pid, process)| {
+                    (
+                        CounterKey::Process(*pid, process.start_time()),
+                        self.counters(*pid, *process),
+                    )
+                })
+                .collect()
+        };
+        if self.threads <= 1 || processes.len() < PARALLEL_MIN_PROCESSES {
+            return map(processes).into_iter().collect();
+        }
+
+        let chunk_size = processes.len().div_ceil(self.threads);
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = processes
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || map(chunk)))
+                .collect();
+            let mut data = ProcessData::with_capacity(processes.len());
+            for worker in workers {
+                match worker.join() {
+                    Ok(entries) => data.extend(entries),
+                    Err(panic) => std::panic::resume_unwind(panic),
+                }
+            }
+            data
+        })
+    }
+}
+
+#[cfg(feature = "monitor")]
+impl CounterSource for ProcessSource {
+    fn read(&self) -> Result<ProcessData, MonitorError> {
+        let resolver = &self.resolver;
+        let mut sys = System::new();
+        sys.refresh_all();
+
+        let processes = sys.processes();
+        let entries: Vec<_> = processes
+            .iter()
+            .map(|(pid, process)| (*pid, process))
+            .collect();
+        let data = self.map_processes(&entries);
+
+        if resolver.is_enabled() {
+            resolver.retain(|pid, start_time| {
+                processes
+                    .get(&pid)
+                    .is_some_and(|process| process.start_time() == start_time)
+            });
+        }
+        Ok(data)
+    }
+
+    fn display_names(&self) -> HashMap<String, String> {
+        self.resolver.display_names()
+    }
+
+    fn session(&self) -> Option<SessionStatus> {
+        self.session.as_ref().and_then(Session::status)
+    }
+}
+
+#[cfg(feature = "monitor")]
+#[instrument(skip_all)]
+pub async fn snapshot(source: Arc<dyn CounterSource>) -> Result<ProcessData, MonitorError> {
+    tokio::task::spawn_blocking(move || source.read()).await?
+}
+
+/// Like `snapshot`, but gives up once `limit` has passed on `clock`. The
+/// read cannot be cancelled and finishes in the background.
+#[cfg(feature = "monitor")]
+pub async fn snapshot_within(
+    clock: &dyn Clock,
+    source: Arc<dyn CounterSource>,
+    limit: Duration,
+) -> Result<ProcessData, MonitorError> {
+    tokio::select! {
+        data = snapshot(source) => data,
+        () = clock.sleep(limit) => Err(MonitorError::TimedOut(limit)),
+    }
+}
+
+/// Usage between the snapshots `prev` and `current`, counted exactly as
+/// the daemon counts it:
+///
+/// - Only keys present in both snapshots count. A new process has no
+///   baseline yet, and one that exited adds nothing. A reused PID has a
+///   new start time, so it is a new key.
+/// - A key keeps the name it had in `prev`, so a process that rewrites its
+///   title is still counted under one app.
+/// - A counter that went down, e.g. a recreated cgroup, adds 0 rather than
+///   wrapping around.
+/// - `written` is the written part of each delta and never exceeds it.
+/// - Keys with the same name are summed into one app.
+/// - CPU time follows the same rules. Memory is the current value of every
+///   process in `current`, summed per app.
+///
+/// Nothing is rejected; see `compute_deltas_with_max` for the rate cap.
+#[cfg(feature = "monitor")]
+pub fn compute_deltas(prev: &ProcessData, current: &ProcessData) -> UsageDelta {
+    compute_deltas_with_max(prev, current, None)
+}
+
+/// Like `compute_deltas`, but a single key's delta above `max_delta` is
+/// moved to `rejected` instead of `usage`, since it most likely comes from
+/// a counter reset rather than real traffic.
+#[cfg(feature = "monitor")]
+pub fn compute_deltas_with_max(
+    prev: &ProcessData,
+    current: &ProcessData,
+    max_delta: Option<u64>,
+) -> UsageDelta {
+    let mut deltas = UsageDelta {
+        resources: resource_deltas(prev, current),
+        ..UsageDelta::default()
+    };
+    for (key, counters) in current {
+        if let Some(previous) = prev.get(key) {
+            let delta = counters.bytes.saturating_sub(previous.bytes);
+            if max_delta.is_some_and(|max| delta > max) {
+                add(&mut deltas.rejected, &previous.name, delta);
+                continue;
+            }
+            add(&mut deltas.usage, &previous.name, delta);
+            let written = counters
+                .written_bytes
+                .saturating_sub(previous.written_bytes)
+                .min(delta);
+            if written > 0 {
+                add(&mut deltas.written, &previous.name, written);
+            }
+        }
+    }
+    deltas
+}
+
+#[cfg(feature = "monitor")]
+fn add(usage: &mut UsageData, app: &str, delta: u64) {
+    let entry = usage.entry(app.to_string()).or_insert(0);
+    *entry = entry.saturating_add(delta);
+}
+
+pub fn max_delta(max_bytes_per_second: u64, elapsed: Duration) -> Option<u64> {
+    if max_bytes_per_second == 0 {
+        return None;
+    }
+    let max = (max_bytes_per_second as f64 * elapsed.as_secs_f64()).ceil();
+    Some((max as u64).max(max_bytes_per_second))
+}
+
+#[cfg(feature = "monitor")]
+pub fn resource_deltas(prev: &ProcessData, current: &ProcessData) -> ResourceData {
+    let mut resources = ResourceData::new();
+    for (key, counters) in current {
+        let previous = prev.get(key);
+        let name = previous.map_or(&counters.name, |previous| &previous.name);
+        if let Some(memory) = counters.memory_bytes {
+            let entry = resources.entry(name.clone()).or_default();
+            entry.memory_bytes = Some(entry.memory_bytes.unwrap_or(0).saturating_add(memory));
+        }
+        if let Some(cpu) = counters.cpu_time_ms {
+            let previous = previous.and_then(|previous| previous.cpu_time_ms);
+            let delta = previous.map_or(0, |previous| cpu.saturating_sub(previous));
+            let entry = resources.entry(name.clone()).or_default();
+            entry.cpu_time_ms = Some(entry.cpu_time_ms.unwrap_or(0).saturating_add(delta));
+        }
+    }
+    resources
+}
+
+pub fn total(usage: &UsageData) -> u64 {
+    usage
+        .values()
+        .fold(0u64, |sum, bytes| sum.saturating_add(*bytes))
+}
+
+#[cfg(feature = "monitor")]
+#[derive(Debug, Default)]
+pub struct Sampler {
+    prev: ProcessData,
+    sampled_at: Option<Instant>,
+    max_bytes_per_second: u64,
+}
+
+#[cfg(feature = "monitor")]
+impl Sampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_rate(mut self, max_bytes_per_second: u64) -> Self {
+        self.max_bytes_per_second = max_bytes_per_second;
+        self
+    }
+
+    pub fn set_max_rate(&mut self, max_bytes_per_second: u64) {
+        self.max_bytes_per_second = max_bytes_per_second;
+    }
+
+    pub fn clear(&mut self) {
+        self.prev.clear();
+        self.sampled_at = None;
+    }
+
+    /// Usage since the last snapshot. Each process keeps the name it was
+    /// first seen with, however often it renames itself.
+    pub fn advance(&mut self, current: ProcessData) -> UsageDelta {
+        self.advance_at(current, Instant::now())
+    }
+
+    /// Like `advance`, for a snapshot taken at `now`, such as a recorded one.
+    pub fn advance_at(&mut self, mut current: ProcessData, now: Instant) -> UsageDelta {
+        let elapsed = self
+            .sampled_at
+            .map_or(Duration::ZERO, |sampled_at| now - sampled_at);
+        let deltas = compute_deltas_with_max(
+            &self.prev,
+            &current,
+            max_delta(self.max_bytes_per_second, elapsed),
+        );
+        for (key, counters) in &mut current {
+            if let Some(previous) = self.prev.get(key)
+                && previous.name != counters.name
+            {
+                counters.name.clone_from(&previous.name);
+            }
+        }
+        self.prev = current;
+        self.sampled_at = Some(now);
+        deltas
+    }
+
+    pub fn process_count(&self) -> usize {
+        self.prev.len()
+    }
+}
+
+#[cfg(all(test, feature = "monitor"))]
+mod tests {
+    use super::*;
+
+    fn process(pid: usize, name: &str, total: u64) -> (CounterKey, Counters) {
+        started(pid, 0, name, total)
+    }
+
+    fn started(pid: usize, start_time: u64, name: &str, total: u64) -> (CounterKey, Counters) {
+        (
+            CounterKey::Process(Pid::from(pid), start_time),
+            Counters::new(name.to_string(), total),
+        )
+    }
+
+    #[test]
+    fn test_deltas() {
+        let prev = ProcessData::from([
+            process(1, "firefox", 100),
+            process(2, "firefox", 50),
+            process(3, "old", 10),
+        ]);
+        let current = ProcessData::from([
+            process(1, "firefox", 150),
+            process(2, "firefox", 60),
+            started(3, 1, "reused", 500),
+            process(4, "new", 40),
+        ]);
+
+        let UsageDelta {
+            usage,
+            written,
+            rejected,
+            ..
+        } = compute_deltas(&prev, &current);
+        assert_eq!(usage, UsageData::from([("firefox".to_string(), 60)]));
+        assert_eq!(total(&usage), 60);
+        assert!(written.is_empty());
+        assert!(rejected.is_empty());
+    }
+
+    #[test]
+    fn test_compute_deltas_cases() {
+        type Row = (usize, &'static str, u64, u64);
+        type Apps = &'static [(&'static str, u64)];
+        /// Name, previous and current snapshot, expected usage and writes.
+        type Case = (&'static str, &'static [Row], &'static [Row], Apps, Apps);
+        let snapshot = |rows: &[Row]| -> ProcessData {
+            rows.iter()
+                .map(|&(pid, name, bytes, written)| {
+                    let (key, counters) = process(pid, name, bytes);
+                    (key, counters.with_written(written))
+                })
+                .collect()
+        };
+        let usage = |apps: &[(&str, u64)]| -> UsageData {
+            apps.iter()
+                .map(|&(app, bytes)| (app.to_string(), bytes))
+                .collect()
+        };
+        let cases: &[Case] = &[
+            ("first snapshot", &[], &[(1, "a", 100, 50)], &[], &[]),
+            (
+                "growth",
+                &[(1, "a", 100, 50)],
+                &[(1, "a", 150, 60)],
+                &[("a", 50)],
+                &[("a", 10)],
+            ),
+            (
+                "idle",
+                &[(1, "a", 100, 0)],
+                &[(1, "a", 100, 0)],
+                &[("a", 0)],
+                &[],
+            ),
+            ("exited", &[(1, "a", 100, 0)], &[], &[], &[]),
+            (
+                "new process",
+                &[(1, "a", 100, 0)],
+                &[(1, "a", 100, 0), (2, "b", 70, 0)],
+                &[("a", 0)],
+                &[],
+            ),
+            (
+                "renamed",
+                &[(1, "a", 100, 0)],
+                &[(1, "b", 500, 0)],
+                &[("a", 400)],
+                &[],
+            ),
+            (
+                "counter went down",
+                &[(1, "a", 100, 80)],
+                &[(1, "a", 20, 10)],
+                &[("a", 0)],
+                &[],
+            ),
+            (
+                "written capped",
+                &[(1, "a", 100, 0)],
+                &[(1, "a", 110, 50)],
+                &[("a", 10)],
+                &[("a", 10)],
+            ),
+            (
+                "summed per name",
+                &[(1, "a", 100, 0), (2, "a", 10, 0)],
+                &[(1, "a", 150, 0), (2, "a", 30, 0)],
+                &[("a", 70)],
+                &[],
+            ),
+            (
+                "saturating",
+                &[(1, "a", 0, 0), (2, "a", 0, 0)],
+                &[(1, "a", u64::MAX, 0), (2, "a", 5, 0)],
+                &[("a", u64::MAX)],
+                &[],
+            ),
+        ];
+
+        for (case, prev, current, expected_usage, expected_written) in cases {
+            let delta = compute_deltas(&snapshot(prev), &snapshot(current));
+            assert_eq!(delta.usage, usage(expected_usage), "{case}");
+            assert_eq!(delta.written, usage(expected_written), "{case}");
+            assert!(delta.rejected.is_empty(), "{case}");
+        }
+    }
+
+    #[test]
+    fn test_deltas_written() {
+        let counters = |bytes, written| {
+            ProcessData::from([(
+                CounterKey::Process(Pid::from(1), 0),
+                Counters::new("db".to_string(), bytes).with_written(written),
+            )])
+        };
+
+        let deltas = compute_deltas(&counters(100, 40), &counters(180, 70));
+        assert_eq!(deltas.usage["db"], 80);
+        assert_eq!(deltas.written["db"], 30);
+    }
+
+    #[test]
+    fn test_deltas_reject_implausible() {
+        let prev = ProcessData::from([
+            process(1, "firefox", 100),
+            process(2, "firefox", 50),
+            process(3, "containerd-shim", u64::MAX - 10),
+        ]);
+        let current = ProcessData::from([
+            process(1, "firefox", 150),
+            process(2, "firefox", 5_000_050),
+            process(3, "containerd-shim", 20),
+        ]);
+
+        let UsageDelta {
+            usage, rejected, ..
+        } = compute_deltas_with_max(&prev, &current, Some(1000));
+        assert_eq!(usage["firefox"], 50);
+        assert_eq!(usage["containerd-shim"], 0);
+        assert_eq!(
+            rejected,
+            UsageData::from([("firefox".to_string(), 5_000_000)])
+        );
+    }
+
+    #[test]
+    fn test_max_delta() {
+        assert_eq!(max_delta(0, Duration::from_secs(60)), None);
+        assert_eq!(max_delta(100, Duration::from_secs(60)), Some(6000));
+        assert_eq!(max_delta(100, Duration::from_millis(10)), Some(100));
+        assert_eq!(max_delta(u64::MAX, Duration::from_secs(60)), Some(u64::MAX));
+    }
+
+    #[test]
+    fn test_sampler() {
+        let mut sampler = Sampler::new();
+        assert!(
+            sampler
+                .advance(ProcessData::from([process(1, "a", 10)]))
+                .usage
+                .is_empty()
+        );
+        assert_eq!(
+            sampler.advance(ProcessData::from([process(1, "a", 25)])),
+            UsageDelta {
+                usage: UsageData::from([("a".to_string(), 15)]),
+                written: UsageData::new(),
+                rejected: UsageData::new(),
+                resources: ResourceData::new(),
+            }
+        );
+        assert_eq!(sampler.process_count(), 1);
+
+        sampler.clear();
+        assert!(
+            sampler
+                .advance(ProcessData::from([process(1, "a", 30)]))
+                .usage
+                .is_empty()
+        );
+
+        let mut sampler = Sampler::new().with_max_rate(1);
+        sampler.advance(ProcessData::from([process(1, "a", 0)]));
+        let deltas = sampler.advance(ProcessData::from([process(1, "a", u64::MAX / 2)]));
+        assert!(deltas.usage.is_empty());
+        assert_eq!(deltas.rejected["a"], u64::MAX / 2);
+    }
+
+    #[test]
+    fn test_sampler_follows_renamed_process() {
+        let mut sampler = Sampler::new();
+        sampler.advance(ProcessData::from([started(7, 100, "postgres", 1000)]));
+        for (tick, title) in [
+            "postgres: checkpointer",
+            "postgres: idle",
+            "postgres: walwriter",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let bytes = 1000 + 500 * (tick as u64 + 1);
+            let deltas = sampler.advance(ProcessData::from([started(7, 100, title, bytes)]));
+            assert_eq!(
+                deltas.usage,
+                UsageData::from([("postgres".to_string(), 500)]),
+                "{title}"
+            );
+        }
+
+        // The PID was reused by another process, which starts from scratch.
+        let deltas = sampler.advance(ProcessData::from([started(7, 200, "nginx", 9000)]));
+        assert!(deltas.usage.is_empty());
+        let deltas = sampler.advance(ProcessData::from([started(7, 200, "nginx: worker", 9100)]));
+        assert_eq!(deltas.usage, UsageData::from([("nginx".to_string(), 100)]));
+    }
+
+    #[test]
+    fn test_resource_deltas() {
+        let with_resources = |pid, name, cpu, memory| {
+            let (key, mut counters) = process(pid, name, 0);
+            counters.cpu_time_ms = Some(cpu);
+            counters.memory_bytes = Some(memory);
+            (key, counters)
+        };
+        let prev = ProcessData::from([
+            with_resources(1, "make", 1000, 10),
+            with_resources(2, "make", 500, 20),
+            with_resources(3, "old", 100, 5),
+        ]);
+        let current = ProcessData::from([
+            with_resources(1, "make", 1500, 30),
+            with_resources(2, "make", 400, 20),
+            with_resources(3, "renamed", 900, 5),
+            process(4, "plain", 0),
+        ]);
+
+        let resources = resource_deltas(&prev, &current);
+        assert_eq!(
+            resources["make"],
+            ResourceUsage {
+                cpu_time_ms: Some(500),
+                memory_bytes: Some(50),
+            }
+        );
+        assert_eq!(
+            resources["old"],
+            ResourceUsage {
+                cpu_time_ms: Some(800),
+                memory_bytes: Some(5),
+            }
+        );
+        assert!(!resources.contains_key("renamed"));
+        assert!(!resources.contains_key("plain"));
+    }
+
+    struct FakeProcess {
+        name: String,
+        bytes: u64,
+        owner: Option<Owner>,
+        parent: Option<Pid>,
+    }
+
+    impl ProcessEntry for FakeProcess {
+        fn start_time(&self) -> u64 {
+            0
+        }
+
+        fn name(&self) -> Cow<'_, str> {
+            Cow::Borrowed(&self.name)
+        }
+
+        fn exe(&self) -> Option<&Path> {
+            None
+        }
+
+        fn disk_bytes(&self) -> u64 {
+            self.bytes
+        }
+
+        fn cpu_time_ms(&self) -> u64 {
+            self.bytes / 2
+        }
+
+        fn memory_bytes(&self) -> u64 {
+            self.bytes * 2
+        }
+
+        fn owner(&self) -> Option<Owner> {
+            self.owner.clone()
+        }
+
+        fn parent(&self) -> Option<Pid> {
+            self.parent
+        }
+    }
+
+    #[test]
+    fn test_map_processes_parallel() {
+        let processes: Vec<_> = (0..5000)
+            .map(|i| FakeProcess {
+                name: format!("app{}", i % 37),
+                bytes: i as u64,
+                owner: None,
+                parent: None,
+            })
+            .collect();
+        let entries: Vec<_> = processes
+            .iter()
+            .enumerate()
+            .map(|(pid, process)| (Pid::from(pid), process))
+            .collect();
+        let collect = Collect {
+            cpu: true,
+            memory: true,
+            ..Default::default()
+        };
+
+        let sequential = ProcessSource::new(Resolver::default())
+            .with_collect(collect)
+            .map_processes(&entries);
+        let parallel = ProcessSource::new(Resolver::default())
+            .with_collect(collect)
+            .with_threads(4)
+            .map_processes(&entries);
+        assert_eq!(sequential.len(), 5000);
+        assert_eq!(parallel, sequential);
+        assert_eq!(
+            sequential[&CounterKey::Process(Pid::from(40), 0)],
+            Counters {
+                name: "app3".to_string(),
+                bytes: 40,
+                written_bytes: 0,
+                cpu_time_ms: Some(20),
+                memory_bytes: Some(80),
+            }
+        );
+    }
+
+    #[test]
+    fn test_map_processes_filters_owners() {
+        let process = |name: &str, owner: Option<u32>| FakeProcess {
+            name: name.to_string(),
+            bytes: 1,
+            owner: owner.map(Owner::Uid),
+            parent: None,
+        };
+        let processes = [
+            process("sshd", Some(0)),
+            process("firefox", Some(1000)),
+            process("backup", Some(1001)),
+            process("unknown", None),
+        ];
+        let entries: Vec<_> = processes
+            .iter()
+            .enumerate()
+            .map(|(pid, process)| (Pid::from(pid), process))
+            .collect();
+        let names = |filter: FilterChain| {
+            let mut names: Vec<_> = ProcessSource::new(Resolver::default())
+                .with_filter(filter)
+                .map_processes(&entries)
+                .into_values()
+                .map(|counters| counters.name)
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(FilterChain::new()).len(), 4);
+        assert_eq!(
+            names(
+                FilterChain::new()
+                    .with_ignore_system(true)
+                    .with_ignored(vec![Owner::Uid(1001)])
+            ),
+            ["firefox", "unknown"]
+        );
+        assert_eq!(
+            names(FilterChain::new().with_watched(vec![Owner::Uid(1001)])),
+            ["backup"]
+        );
+    }
+
+    #[test]
+    fn test_map_processes_session() {
+        let process = |pid: usize, name: &str, parent: Option<usize>| {
+            (
+                Pid::from(pid),
+                FakeProcess {
+                    name: name.to_string(),
+                    bytes: 1,
+                    owner: None,
+                    parent: parent.map(Pid::from),
+                },
+            )
+        };
+        let source = ProcessSource::new(Resolver::default())
+            .with_session(Session::new(RootProcess::parse("tmux")));
+        let names = |processes: &[(Pid, FakeProcess)]| {
+            let entries: Vec<_> = processes.iter().map(|(pid, p)| (*pid, p)).collect();
+            let mut names: Vec<_> = source
+                .map_processes(&entries)
+                .into_values()
+                .map(|counters| counters.name)
+                .collect();
+            names.sort();
+            names
+        };
+
+        let mut processes = vec![
+            process(1, "init", None),
+            process(10, "tmux", Some(1)),
+            process(11, "bash", Some(10)),
+            process(12, "cargo", Some(11)),
+            process(20, "firefox", Some(1)),
+            process(21, "bash", Some(20)),
+        ];
+        assert_eq!(names(&processes), ["bash", "cargo", "tmux"]);
+        let status = source.session().unwrap();
+        assert_eq!((status.pids, status.processes), (vec![10], 3));
+
+        // The root exits and its children are reparented to init.
+        processes.retain(|(pid, _)| *pid != Pid::from(10));
+        processes[1].1.parent = Some(Pid::from(1));
+        assert!(names(&processes).is_empty());
+        let status = source.session().unwrap();
+        assert!(status.is_waiting());
+        assert_eq!(status.root, "tmux");
+
+        // A new root under a new PID is found again.
+        processes.push(process(30, "tmux", Some(1)));
+        processes.push(process(31, "vim", Some(30)));
+        assert_eq!(names(&processes), ["tmux", "vim"]);
+        assert_eq!(source.session().unwrap().pids, [30]);
+
+        let by_pid = ProcessSource::new(Resolver::default())
+            .with_session(Session::new(RootProcess::parse("20")));
+        let entries: Vec<_> = processes.iter().map(|(pid, p)| (*pid, p)).collect();
+        assert_eq!(by_pid.map_processes(&entries).len(), 2);
+        assert!(ProcessSource::new(Resolver::default()).session().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_slow_source_keeps_ticks_spaced() {
+        use std::pin::{Pin, pin};
+        use std::task::{Context, Poll, Waker};
+
+        use crate::clock::{MockClock, Ticker};
+        use crate::test_support::ScriptedSource;
+
+        const PERIOD: Duration = Duration::from_millis(100);
+        let clock = MockClock::new();
+        let start = clock.now();
+        let snapshot = ProcessData::from([process(1, "curl", 5)]);
+        let source = Arc::new(ScriptedSource::new([snapshot]).hung());
+        let mut ticker = Ticker::new(Arc::new(clock.clone()), PERIOD);
+        // Moves the clock on in steps until `future` is done.
+        fn run<T>(clock: &MockClock, mut future: Pin<&mut impl Future<Output = T>>) -> T {
+            let mut context = Context::from_waker(Waker::noop());
+            loop {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                    return output;
+                }
+                clock.advance(PERIOD / 10);
+            }
+        }
+
+        let mut starts = Vec::new();
+        for _ in 0..4 {
+            run(&clock, pin!(ticker.tick()));
+            starts.push(clock.now() - start);
+            let scan = snapshot_within(&clock, source.clone(), PERIOD * 3 / 2);
+            let result = run(&clock, pin!(scan));
+            assert!(matches!(result, Err(MonitorError::TimedOut(_))));
+        }
+        // Each scan gives up on the hung read after its timeout, and the
+        // next one starts right after a late scan rather than catching up.
+        assert_eq!(
+            starts,
+            [Duration::ZERO, PERIOD * 3 / 2, PERIOD * 3, PERIOD * 9 / 2]
+        );
+        source.release();
+        let data = snapshot_within(&clock, source, PERIOD).await;
+        assert_eq!(data.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_interfaces_coming_and_going() {
+        let interface = |name: &str, total: u64, sent: u64| {
+            (
+                CounterKey::Interface(name.to_string()),
+                Counters::new(format!("{NET_PREFIX}{name}"), total).with_written(sent),
+            )
+        };
+        let mut sampler = Sampler::new();
+        sampler.advance(ProcessData::from([interface("wlan0", 1000, 100)]));
+
+        // A VPN comes up with counters already running.
+        let delta = sampler.advance(ProcessData::from([
+            interface("wlan0", 1500, 150),
+            interface("tun0", 700, 70),
+        ]));
+        assert_eq!(
+            delta.usage,
+            UsageData::from([("net:wlan0".to_string(), 500)])
+        );
+        assert_eq!(
+            delta.written,
+            UsageData::from([("net:wlan0".to_string(), 50)])
+        );
+
+        // It goes down, then comes back with fresh counters.
+        let delta = sampler.advance(ProcessData::from([interface("wlan0", 1600, 150)]));
+        assert_eq!(
+            delta.usage,
+            UsageData::from([("net:wlan0".to_string(), 100)])
+        );
+        let delta = sampler.advance(ProcessData::from([
+            interface("wlan0", 1600, 150),
+            interface("tun0", 20, 2),
+        ]));
+        assert_eq!(total(&delta.usage), 0);
+
+        // Recreated between two scans, it starts over below its old count.
+        let delta = sampler.advance(ProcessData::from([
+            interface("wlan0", 1600, 150),
+            interface("tun0", 5, 1),
+        ]));
+        assert_eq!(total(&delta.usage), 0);
+        assert!(delta.rejected.is_empty());
+        let delta = sampler.advance(ProcessData::from([
+            interface("wlan0", 1600, 150),
+            interface("tun0", 45, 11),
+        ]));
+        assert_eq!(delta.usage["net:tun0"], 40);
+        assert_eq!(delta.written["net:tun0"], 10);
+
+        assert!(!is_app("net:tun0"));
+        assert_eq!(interface_name("net:tun0"), Some("tun0"));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_errors() {
+        let failing = Arc::new(crate::test_support::FailingSource::failing());
+        match snapshot(failing).await {
+            Err(MonitorError::Io { path, source }) => {
+                assert_eq!(path, Path::new("/proc"));
+                assert_eq!(source.kind(), std::io::ErrorKind::PermissionDenied);
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+        let panicking = Arc::new(crate::test_support::PanickingSource);
+        assert!(matches!(
+            snapshot(panicking).await,
+            Err(MonitorError::Join(e)) if e.is_panic()
+        ));
+    }
+
+    #[test]
+    fn test_device_name() {
+        assert_eq!(
+            device_name(OsStr::new("/dev/nvme0n1p2"), Path::new("/")),
+            "nvme0n1p2"
+        );
+        assert_eq!(
+            device_name(OsStr::new("Macintosh HD"), Path::new("/")),
+            "Macintosh HD"
+        );
+        assert_eq!(device_name(OsStr::new(""), Path::new("D:\\")), "D:\\");
+        assert_eq!(disk_name("disk:sda1"), Some("sda1"));
+        assert_eq!(disk_name("firefox"), None);
+    }
+
+    #[derive(Debug)]
+    struct FixedSource(ProcessData);
+
+    impl CounterSource for FixedSource {
+        fn read(&self) -> Result<ProcessData, MonitorError> {
+            Ok(self.0.clone())
+        }
+
+        fn display_names(&self) -> HashMap<String, String> {
+            [("chrome.exe".to_string(), "Google Chrome".to_string())].into()
+        }
+    }
+
+    #[test]
+    fn test_normalized_source() {
+        let config = crate::app_names::AppNameConfig {
+            lowercase: true,
+            strip_exe: true,
+            ..Default::default()
+        };
+        let inner = FixedSource(ProcessData::from([
+            process(1, "Chrome", 100),
+            process(2, "chrome.exe", 50),
+            (
+                CounterKey::Disk("SDA".to_string()),
+                Counters::new(format!("{}SDA", DISK_PREFIX), 10),
+            ),
+        ]));
+        let source = NormalizedSource::new(Arc::new(inner), AppNames::new(&config).unwrap());
+        let data = source.read().unwrap();
+        let mut names: Vec<_> = data
+            .values()
+            .map(|counters| counters.name.as_str())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["chrome", "chrome", "disk:SDA"]);
+        assert_eq!(
+            source.display_names(),
+            [("chrome".to_string(), "Google Chrome".to_string())].into()
+        );
+
+        let mut sampler = Sampler::new();
+        sampler.advance(data.clone());
+        let mut data = data;
+        for counters in data.values_mut() {
+            counters.bytes += 1;
+        }
+        assert_eq!(sampler.advance(data).usage["chrome"], 2);
+    }
+}
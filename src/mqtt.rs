@@ -0,0 +1,448 @@
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_BROKER: &str = "mqtt://localhost:1883";
+pub const DEFAULT_TOPIC_PREFIX: &str = "dataguardian";
+pub const DEFAULT_QOS: u8 = 1;
+pub const DEFAULT_PUBLISH_INTERVAL: u64 = 60;
+pub const DEFAULT_KEEP_ALIVE: u64 = 30;
+pub const MAX_QOS: u8 = 2;
+/// Name of the alert channel, for `notifications.routes`.
+pub const CHANNEL_NAME: &str = "mqtt";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    pub broker: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub client_id: Option<String>,
+    pub topic_prefix: String,
+    pub qos: u8,
+    pub publish_interval_seconds: u64,
+    pub keep_alive_seconds: u64,
+    pub clean_session: bool,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            broker: DEFAULT_BROKER.to_string(),
+            username: None,
+            password: None,
+            client_id: None,
+            topic_prefix: DEFAULT_TOPIC_PREFIX.to_string(),
+            qos: DEFAULT_QOS,
+            publish_interval_seconds: DEFAULT_PUBLISH_INTERVAL,
+            keep_alive_seconds: DEFAULT_KEEP_ALIVE,
+            clean_session: true,
+        }
+    }
+}
+
+pub fn topic_segment(value: &str) -> String {
+    let segment: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '+' | '#' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if segment.is_empty() {
+        "_".to_string()
+    } else {
+        segment
+    }
+}
+
+#[cfg(feature = "mqtt")]
+pub use publisher::*;
+
+#[cfg(feature = "mqtt")]
+mod publisher {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use chrono::{DateTime, Utc};
+    use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS};
+    use serde::Serialize;
+    use thiserror::Error;
+    use tracing::{debug, info, warn};
+
+    use super::{MqttConfig, topic_segment};
+    use crate::history::Severity;
+    use crate::notification::{Alert, AlertKind, NotificationChannel, NotificationError};
+    use crate::status::StatusState;
+
+    pub const DEFAULT_MQTT_PORT: u16 = 1883;
+    pub const REQUEST_QUEUE_SIZE: usize = 64;
+    pub const MIN_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+    pub const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+
+    #[derive(Error, Debug)]
+    pub enum MqttError {
+        #[error("Invalid MQTT broker URL: {0}")]
+        InvalidBroker(String),
+        #[error("Invalid MQTT QoS: {0} (max: 2)")]
+        InvalidQos(u8),
+    }
+
+    #[derive(Debug, Serialize)]
+    struct AlertPayload<'a> {
+        app: &'a str,
+        kind: AlertKind,
+        severity: Severity,
+        usage: Option<u64>,
+        limit: Option<u64>,
+        projected: Option<u64>,
+        delta: Option<u64>,
+        baseline: Option<u64>,
+        streak: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        instance: Option<&'a str>,
+        time: DateTime<Utc>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Topics {
+        base: String,
+    }
+
+    impl Topics {
+        fn new(prefix: &str, host: &str) -> Self {
+            Self {
+                base: format!("{}/{}", prefix.trim_end_matches('/'), topic_segment(host)),
+            }
+        }
+
+        fn usage(&self, app: &str) -> String {
+            format!("{}/usage/{}", self.base, topic_segment(app))
+        }
+
+        fn alert(&self) -> String {
+            format!("{}/alert", self.base)
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct MqttChannel {
+        client: AsyncClient,
+        topic: String,
+        qos: QoS,
+    }
+
+    impl NotificationChannel for MqttChannel {
+        fn name(&self) -> &str {
+            super::CHANNEL_NAME
+        }
+
+        fn send(&self, alert: &Alert) -> Result<(), NotificationError> {
+            let payload = serde_json::to_vec(&AlertPayload {
+                app: &alert.app,
+                kind: alert.kind,
+                severity: alert.severity,
+                usage: alert.usage,
+                limit: alert.limit,
+                projected: alert.projected,
+                delta: alert.delta,
+                baseline: alert.baseline,
+                streak: alert.streak,
+                instance: alert.instance.as_deref(),
+                time: Utc::now(),
+            })
+            .map_err(|e| NotificationError::ShowError(e.to_string()))?;
+
+            self.client
+                .try_publish(&self.topic, self.qos, false, payload)
+                .map_err(|e| NotificationError::ShowError(e.to_string()))
+        }
+    }
+
+    fn parse_broker(broker: &str) -> Result<(String, u16), MqttError> {
+        let invalid = || MqttError::InvalidBroker(broker.to_string());
+        let address = match broker.split_once("://") {
+            Some(("mqtt" | "tcp", address)) => address,
+            Some(_) => return Err(invalid()),
+            None => broker,
+        };
+        let address = address.trim_end_matches('/');
+
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) if !host.ends_with(':') && !port.contains(']') => {
+                (host, port.parse().map_err(|_| invalid())?)
+            }
+            _ => (address, DEFAULT_MQTT_PORT),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok((host.to_string(), port))
+    }
+
+    fn qos(level: u8) -> Result<QoS, MqttError> {
+        match level {
+            0 => Ok(QoS::AtMostOnce),
+            1 => Ok(QoS::AtLeastOnce),
+            2 => Ok(QoS::ExactlyOnce),
+            level => Err(MqttError::InvalidQos(level)),
+        }
+    }
+
+    /// Connects and starts publishing under `<topic_prefix>/<instance>`.
+    pub fn start(
+        config: &MqttConfig,
+        instance: &str,
+        status: Arc<StatusState>,
+    ) -> Result<MqttChannel, MqttError> {
+        let (host, port) = parse_broker(&config.broker)?;
+        let qos = qos(config.qos)?;
+        let topics = Topics::new(&config.topic_prefix, instance);
+
+        let client_id = config
+            .client_id
+            .clone()
+            .unwrap_or_else(|| format!("data-guardian-{}", topic_segment(instance)));
+        let mut options = MqttOptions::new(client_id, host, port);
+        options
+            .set_keep_alive(Duration::from_secs(config.keep_alive_seconds.max(1)))
+            .set_clean_session(config.clean_session);
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+
+        let (client, eventloop) = AsyncClient::new(options, REQUEST_QUEUE_SIZE);
+        tokio::spawn(drive(eventloop));
+        tokio::spawn(publish_usage(
+            client.clone(),
+            topics.clone(),
+            qos,
+            Duration::from_secs(config.publish_interval_seconds.max(1)),
+            status,
+        ));
+
+        Ok(MqttChannel {
+            client,
+            topic: topics.alert(),
+            qos,
+        })
+    }
+
+    async fn drive(mut eventloop: EventLoop) {
+        let mut backoff = MIN_RECONNECT_BACKOFF;
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(ack))) => {
+                    info!(
+                        session_present = ack.session_present,
+                        "Connected to MQTT broker"
+                    );
+                    backoff = MIN_RECONNECT_BACKOFF;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!(error = %e, retry_in = ?backoff, "MQTT connection failed");
+                    tokio::time::sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2).min(MAX_RECONNECT_BACKOFF);
+                }
+            }
+        }
+    }
+
+    async fn publish_usage(
+        client: AsyncClient,
+        topics: Topics,
+        qos: QoS,
+        period: Duration,
+        status: Arc<StatusState>,
+    ) {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            let Some(report) = status.report(None, None) else {
+                continue;
+            };
+            for app in &report.apps {
+                let payload = match serde_json::to_vec(app) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        debug!(error = %e, app = %app.name, "Failed to encode MQTT usage");
+                        continue;
+                    }
+                };
+                if let Err(e) = client
+                    .publish(topics.usage(&app.name), qos, true, payload)
+                    .await
+                {
+                    debug!(error = %e, app = %app.name, "Failed to queue MQTT usage");
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::collections::HashMap;
+
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::{TcpListener, TcpStream};
+
+        use super::*;
+        use crate::settings::Settings;
+
+        #[derive(Debug)]
+        enum MockPacket {
+            Connect {
+                clean_session: bool,
+            },
+            Publish {
+                topic: String,
+                retain: bool,
+                payload: Vec<u8>,
+            },
+            Other,
+        }
+
+        async fn read_packet(stream: &mut TcpStream) -> MockPacket {
+            let header = stream.read_u8().await.unwrap();
+            let mut length = 0usize;
+            for shift in (0..28).step_by(7) {
+                let byte = stream.read_u8().await.unwrap();
+                length |= ((byte & 0x7f) as usize) << shift;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body).await.unwrap();
+
+            match header >> 4 {
+                1 => MockPacket::Connect {
+                    clean_session: body[7] & 0x02 != 0,
+                },
+                3 => {
+                    let qos = (header >> 1) & 0x03;
+                    let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                    let topic = String::from_utf8(body[2..2 + topic_len].to_vec()).unwrap();
+                    let mut offset = 2 + topic_len;
+                    if qos > 0 {
+                        let id = &body[offset..offset + 2];
+                        stream.write_all(&[0x40, 0x02, id[0], id[1]]).await.unwrap();
+                        offset += 2;
+                    }
+                    MockPacket::Publish {
+                        topic,
+                        retain: header & 0x01 != 0,
+                        payload: body[offset..].to_vec(),
+                    }
+                }
+                _ => MockPacket::Other,
+            }
+        }
+
+        async fn accept(listener: &TcpListener) -> (TcpStream, bool) {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let MockPacket::Connect { clean_session } = read_packet(&mut stream).await else {
+                panic!("Expected CONNECT");
+            };
+            stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+            (stream, clean_session)
+        }
+
+        async fn next_publish(stream: &mut TcpStream) -> (String, bool, Vec<u8>) {
+            loop {
+                if let MockPacket::Publish {
+                    topic,
+                    retain,
+                    payload,
+                } = read_packet(stream).await
+                {
+                    return (topic, retain, payload);
+                }
+            }
+        }
+
+        #[test]
+        fn test_parse_broker() {
+            assert_eq!(
+                parse_broker("mqtt://broker.lan:1884").unwrap(),
+                ("broker.lan".to_string(), 1884)
+            );
+            assert_eq!(
+                parse_broker("broker.lan").unwrap(),
+                ("broker.lan".to_string(), DEFAULT_MQTT_PORT)
+            );
+            assert_eq!(
+                parse_broker("tcp://[::1]:1883/").unwrap(),
+                ("::1".to_string(), 1883)
+            );
+            assert!(parse_broker("mqtts://broker.lan").is_err());
+            assert!(parse_broker("mqtt://:1883").is_err());
+            assert!(parse_broker("mqtt://broker.lan:port").is_err());
+        }
+
+        #[test]
+        fn test_topics() {
+            let topics = Topics::new("dataguardian/", "host/1");
+            assert_eq!(topics.alert(), "dataguardian/host_1/alert");
+            assert_eq!(
+                topics.usage("Web Content+#"),
+                "dataguardian/host_1/usage/Web Content__"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_publish_and_reconnect() {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let settings = Settings::default();
+            let status = Arc::new(StatusState::new(&settings));
+            status.update_usage(HashMap::from([("firefox".to_string(), 42)]));
+
+            let config = MqttConfig {
+                enabled: true,
+                broker: format!("mqtt://{}", addr),
+                client_id: Some("dg-test".to_string()),
+                clean_session: false,
+                publish_interval_seconds: 1,
+                ..Default::default()
+            };
+            let channel = start(&config, "office-nas", status).unwrap();
+
+            let (first, clean_session) = accept(&listener).await;
+            assert!(!clean_session);
+            drop(first);
+
+            let (mut stream, _) = accept(&listener).await;
+            let (topic, retain, payload) = next_publish(&mut stream).await;
+            assert_eq!(topic, "dataguardian/office-nas/usage/firefox");
+            assert!(retain);
+            let usage: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+            assert_eq!(usage["usage"], 42);
+
+            channel
+                .send(
+                    &Alert::new("firefox")
+                        .with_usage(42, 10)
+                        .with_instance("office-nas"),
+                )
+                .unwrap();
+            let (topic, retain, payload) = loop {
+                let publish = next_publish(&mut stream).await;
+                if publish.0.ends_with("/alert") {
+                    break publish;
+                }
+            };
+            assert_eq!(topic, "dataguardian/office-nas/alert");
+            assert!(!retain);
+            let alert: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+            assert_eq!(alert["app"], "firefox");
+            assert_eq!(alert["kind"], "exceeded");
+            assert_eq!(alert["limit"], 10);
+            assert_eq!(alert["instance"], "office-nas");
+        }
+    }
+}
@@ -0,0 +1,3165 @@
+#![cfg_attr(not(feature = "notifications"), allow(unused_imports, dead_code))]
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt;
+#[cfg(target_os = "macos")]
+use std::process::Command;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+#[cfg(feature = "notifications")]
+use tokio::sync::{broadcast, oneshot};
+#[cfg(target_os = "macos")]
+use tracing::error;
+use tracing::{debug, info, warn};
+
+#[cfg(feature = "notifications")]
+use super::alerts_log::{AlertLogEntry, AlertsLog, ChannelOutcome};
+#[cfg(feature = "notifications")]
+use super::backoff::SAVE_TASK;
+#[cfg(feature = "notifications")]
+use super::clock::{self, Clock};
+#[cfg(feature = "notifications")]
+use super::events::Event;
+use super::filter;
+use super::history::{NotificationHistory, NotificationOutcome, NotificationRecord, Severity};
+use super::i18n::{self, Localizer};
+#[cfg(feature = "notifications")]
+use super::self_check::STORAGE_CHECK;
+use super::system_log::{self, SyslogConfig};
+use super::tracker::ResetPeriod;
+
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(300);
+pub const DEFAULT_APP_NAME: &str = "Data Guardian";
+pub const DEFAULT_ICON: &str = "drive-harddisk";
+pub const SNOOZE_DURATION: Duration = Duration::from_secs(60 * 60);
+pub const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+/// Failed sends in a row after which a channel is skipped for a while.
+pub const CHANNEL_FAILURE_THRESHOLD: u32 = 5;
+/// How long a failing channel is skipped at first. Each failure after the
+/// pause doubles it, up to `MAX_CHANNEL_PAUSE`.
+pub const CHANNEL_PAUSE: Duration = Duration::from_secs(5 * 60);
+pub const MAX_CHANNEL_PAUSE: Duration = Duration::from_secs(6 * 60 * 60);
+const CHANNEL_PAUSED: &str = "Channel paused after repeated failures";
+
+#[cfg(target_os = "linux")]
+const ACTION_SNOOZE: &str = "snooze";
+#[cfg(target_os = "linux")]
+const ACTION_SHOW_REPORT: &str = "show-report";
+
+#[derive(Error, Debug, Clone)]
+pub enum NotificationError {
+    #[error("Failed to show notification: {0}")]
+    ShowError(String),
+    #[error("Notification in cooldown")]
+    Cooldown,
+    #[error("Notifications snoozed")]
+    Snoozed,
+    #[error("Failed to acquire lock")]
+    LockError,
+    #[error("Notification rate limit reached")]
+    RateLimited,
+    #[error("At least one notification channel is required")]
+    NoChannels,
+    #[error("Notification cooldown must be greater than zero")]
+    ZeroCooldown,
+    #[error("Notification rate limit must allow at least one notification per non-zero window")]
+    InvalidRateLimit,
+    #[error("Notification route {0} names unknown channel {1:?}")]
+    UnknownRouteChannel(String, String),
+    #[error("Every notification channel is paused after repeated failures")]
+    ChannelsPaused,
+    #[error("Notification took longer than {}", humantime::format_duration(*.0))]
+    TimedOut(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelKind {
+    Desktop,
+    Syslog,
+    EventLog,
+    Log,
+}
+
+impl ChannelKind {
+    /// The name of the channel it sets up, as used in routes.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Desktop => "desktop",
+            Self::Syslog => "syslog",
+            Self::EventLog => "eventlog",
+            Self::Log => "log",
+        }
+    }
+}
+
+pub trait NotificationChannel: fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+    fn send(&self, alert: &Alert) -> Result<(), NotificationError>;
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct NotificationConfig {
+    pub app_name: String,
+    pub icon: Option<String>,
+    pub timeout_ms: Option<u32>,
+    pub sound: bool,
+    pub sound_name: Option<String>,
+    pub subtitle: Option<String>,
+    pub retry_attempts: u32,
+    pub retry_backoff_ms: u64,
+    pub cooldown_after_failure: bool,
+    pub channels: Vec<ChannelKind>,
+    /// Tried in order; the first that matches an alert picks its channels.
+    pub routes: Vec<NotificationRoute>,
+    pub syslog: SyslogConfig,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            app_name: DEFAULT_APP_NAME.to_string(),
+            icon: Some(DEFAULT_ICON.to_string()),
+            timeout_ms: None,
+            sound: true,
+            sound_name: None,
+            subtitle: None,
+            retry_attempts: DEFAULT_RETRY_ATTEMPTS,
+            retry_backoff_ms: DEFAULT_RETRY_BACKOFF.as_millis() as u64,
+            cooldown_after_failure: true,
+            channels: vec![ChannelKind::Desktop],
+            routes: Vec::new(),
+            syslog: SyslogConfig::default(),
+        }
+    }
+}
+
+/// Sends alerts of `severity` about apps matching `app` to `channels` only.
+/// A route without `severity` or `app` matches every alert, and an alert no
+/// route matches goes to every channel.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct NotificationRoute {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// An app name, in which `*` stands for any run of characters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    pub channels: Vec<String>,
+}
+
+impl NotificationRoute {
+    pub fn matches(&self, alert: &Alert) -> bool {
+        self.severity
+            .is_none_or(|severity| severity == alert.severity)
+            && self
+                .app
+                .as_ref()
+                .is_none_or(|pattern| filter::pattern_matches(pattern, &alert.app))
+    }
+
+    /// The first channel name that is not in `known`.
+    pub fn unknown_channel<'a>(&'a self, known: &[&str]) -> Option<&'a str> {
+        self.channels
+            .iter()
+            .map(String::as_str)
+            .find(|channel| !known.contains(channel))
+    }
+}
+
+/// The route as written in the config file, such as
+/// `{ severity = "critical", app = "backup-*", channels = ["desktop"] }`.
+impl fmt::Display for NotificationRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ ")?;
+        if let Some(severity) = self.severity {
+            write!(f, "severity = {:?}, ", severity.as_str())?;
+        }
+        if let Some(app) = &self.app {
+            write!(f, "app = {:?}, ", app)?;
+        }
+        write!(f, "channels = {:?} }}", self.channels)
+    }
+}
+
+/// The channels of the first route that matches `alert`, or `None` when it
+/// should go to every channel.
+pub fn route<'a>(routes: &'a [NotificationRoute], alert: &Alert) -> Option<&'a [String]> {
+    routes
+        .iter()
+        .find(|route| route.matches(alert))
+        .map(|route| route.channels.as_slice())
+}
+
+/// The result of one alert in [`NotificationManager::alert_batch`].
+pub type AlertOutcome = Result<(), NotificationError>;
+
+/// Resolves to an alert's final result, once any background retries are
+/// over. See [`NotificationManager::alert_batch_settled`].
+#[cfg(feature = "notifications")]
+pub type Settled = oneshot::Receiver<AlertOutcome>;
+
+#[cfg(feature = "notifications")]
+fn deliver_all(deliveries: Vec<Result<Delivery, NotificationError>>) -> Vec<AlertOutcome> {
+    deliveries
+        .into_iter()
+        .map(|delivery| delivery?.deliver())
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max: u32,
+    pub per: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertKind {
+    #[default]
+    Exceeded,
+    Forecast,
+    Anomaly,
+    Cpu,
+    Memory,
+    Started,
+    Summary,
+    DataLoss,
+    Budget,
+    Failing,
+    Clamped,
+    SelfUsage,
+    Test,
+}
+
+impl AlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Exceeded => "exceeded",
+            Self::Forecast => "forecast",
+            Self::Anomaly => "anomaly",
+            Self::Cpu => "cpu",
+            Self::Memory => "memory",
+            Self::Started => "started",
+            Self::Summary => "summary",
+            Self::DataLoss => "data_loss",
+            Self::Budget => "budget",
+            Self::Failing => "failing",
+            Self::Clamped => "clamped",
+            Self::SelfUsage => "self_usage",
+            Self::Test => "test",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub app: String,
+    pub display_name: Option<String>,
+    pub kind: AlertKind,
+    pub severity: Severity,
+    pub usage: Option<u64>,
+    pub limit: Option<u64>,
+    pub projected: Option<u64>,
+    pub delta: Option<u64>,
+    pub baseline: Option<u64>,
+    pub streak: u32,
+    pub period: ResetPeriod,
+    pub apps: Vec<String>,
+    /// For `DataLoss`, whether usage was restored from another copy.
+    pub restored: bool,
+    /// For `Budget`, the percentage milestone that was reached.
+    pub milestone: Option<u32>,
+    /// For `Budget`, days until the billing period ends.
+    pub days_left: Option<i64>,
+    /// For `Failing`, the task that keeps failing: `scan` or `save`. For
+    /// `SelfUsage`, the cap that was exceeded: `io` or `storage`.
+    pub task: Option<String>,
+    /// For `Clamped`, the settings that were moved into range.
+    pub settings: Vec<String>,
+    /// The `instance_label` of the machine that raised the alert.
+    pub instance: Option<String>,
+}
+
+impl Alert {
+    pub fn new(app: &str) -> Self {
+        Self {
+            app: app.to_string(),
+            display_name: None,
+            kind: AlertKind::Exceeded,
+            severity: Severity::Warning,
+            usage: None,
+            limit: None,
+            projected: None,
+            delta: None,
+            baseline: None,
+            streak: 0,
+            period: ResetPeriod::Never,
+            apps: Vec::new(),
+            restored: false,
+            milestone: None,
+            days_left: None,
+            task: None,
+            settings: Vec::new(),
+            instance: None,
+        }
+    }
+
+    pub fn with_display_name(mut self, display_name: Option<String>) -> Self {
+        self.display_name = display_name;
+        self
+    }
+
+    pub fn with_instance(mut self, instance: &str) -> Self {
+        self.instance = Some(instance.to_string());
+        self
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.app)
+    }
+
+    pub fn with_usage(mut self, usage: u64, limit: u64) -> Self {
+        self.usage = Some(usage);
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn with_streak(mut self, streak: u32, period: ResetPeriod, severity: Severity) -> Self {
+        self.streak = streak;
+        self.period = period;
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_forecast(mut self, projected: u64, period: ResetPeriod) -> Self {
+        self.kind = AlertKind::Forecast;
+        self.severity = Severity::Info;
+        self.projected = Some(projected);
+        self.period = period;
+        self
+    }
+
+    pub fn with_anomaly(mut self, delta: u64, baseline: u64) -> Self {
+        self.kind = AlertKind::Anomaly;
+        self.severity = Severity::Warning;
+        self.delta = Some(delta);
+        self.baseline = Some(baseline);
+        self
+    }
+
+    pub fn with_cpu_time(mut self, seconds: u64, limit_seconds: u64) -> Self {
+        self.kind = AlertKind::Cpu;
+        self.usage = Some(seconds);
+        self.limit = Some(limit_seconds);
+        self
+    }
+
+    pub fn with_memory(mut self, bytes: u64, limit_bytes: u64) -> Self {
+        self.kind = AlertKind::Memory;
+        self.usage = Some(bytes);
+        self.limit = Some(limit_bytes);
+        self
+    }
+
+    /// Announces that monitoring started for `apps` with a data limit of `limit`.
+    pub fn with_started(mut self, apps: Vec<String>, limit: u64) -> Self {
+        self.kind = AlertKind::Started;
+        self.severity = Severity::Info;
+        self.apps = apps;
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Summarizes a session that moved `usage` bytes and ended with `over_limit` apps
+    /// above their limits.
+    pub fn with_summary(mut self, over_limit: Vec<String>, usage: u64) -> Self {
+        self.kind = AlertKind::Summary;
+        self.severity = match over_limit.is_empty() {
+            true => Severity::Info,
+            false => Severity::Warning,
+        };
+        self.apps = over_limit;
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Reports that the data file could not be read at startup.
+    pub fn with_data_loss(mut self, restored: bool) -> Self {
+        self.kind = AlertKind::DataLoss;
+        self.severity = Severity::Warning;
+        self.restored = restored;
+        self
+    }
+
+    /// Reports that total usage passed `milestone` percent of the data
+    /// budget `limit`.
+    pub fn with_budget(mut self, usage: u64, limit: u64, milestone: u32, days_left: i64) -> Self {
+        self.kind = AlertKind::Budget;
+        self.severity = match milestone >= 100 {
+            true => Severity::Warning,
+            false => Severity::Info,
+        };
+        self.usage = Some(usage);
+        self.limit = Some(limit);
+        self.milestone = Some(milestone);
+        self.days_left = Some(days_left);
+        self
+    }
+
+    /// Reports that `task` failed `failures` times in a row.
+    pub fn with_failing(mut self, task: &str, failures: u32) -> Self {
+        self.kind = AlertKind::Failing;
+        self.severity = Severity::Warning;
+        self.task = Some(task.to_string());
+        self.streak = failures;
+        self
+    }
+
+    /// Reports that loading the configuration clamped `settings` into range.
+    pub fn with_clamped(mut self, settings: Vec<String>) -> Self {
+        self.kind = AlertKind::Clamped;
+        self.severity = Severity::Warning;
+        self.settings = settings;
+        self
+    }
+
+    /// Reports that the service's own disk use went over a `self_check`
+    /// cap; `check` is `io` or `storage`.
+    pub fn with_self_usage(mut self, check: &str, usage: u64, limit: u64) -> Self {
+        self.kind = AlertKind::SelfUsage;
+        self.severity = Severity::Info;
+        self.task = Some(check.to_string());
+        self.usage = Some(usage);
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn with_test(mut self) -> Self {
+        self.kind = AlertKind::Test;
+        self.severity = Severity::Info;
+        self
+    }
+
+    fn cooldown_key(&self) -> String {
+        match self.kind {
+            AlertKind::Exceeded => self.app.clone(),
+            AlertKind::Budget => format!(
+                "{}:{}:{}",
+                self.app,
+                self.kind.as_str(),
+                self.milestone.unwrap_or(0)
+            ),
+            AlertKind::Failing | AlertKind::SelfUsage => format!(
+                "{}:{}:{}",
+                self.app,
+                self.kind.as_str(),
+                self.task.as_deref().unwrap_or_default()
+            ),
+            kind => format!("{}:{}", self.app, kind.as_str()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationAction {
+    Snooze,
+    SnoozeAll,
+    ShowReport,
+    Quit,
+}
+
+/// Remaining snooze time in seconds, for everything and per app.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snoozes {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub all_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub apps: BTreeMap<String, u64>,
+}
+
+impl Snoozes {
+    pub fn is_empty(&self) -> bool {
+        self.all_seconds.is_none() && self.apps.is_empty()
+    }
+}
+
+/// A channel that is skipped after failing again and again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PausedChannel {
+    pub channel: String,
+    /// Failed sends in a row.
+    pub failures: u32,
+    /// Until the channel is tried again.
+    pub remaining_seconds: u64,
+}
+
+#[cfg(feature = "monitor")]
+pub type ActionSender = tokio::sync::mpsc::UnboundedSender<(String, NotificationAction)>;
+
+#[cfg(feature = "notifications")]
+#[derive(Debug, Default)]
+struct SnoozeState {
+    all: Option<Instant>,
+    apps: HashMap<String, Instant>,
+}
+
+#[cfg(feature = "notifications")]
+impl SnoozeState {
+    fn expire(&mut self, now: Instant) {
+        self.apps.retain(|_, until| *until > now);
+    }
+
+    /// Whether `app` is snoozed, once expired snoozes are gone.
+    fn covers(&self, app: &str, now: Instant) -> bool {
+        self.all.is_some_and(|until| until > now) || self.apps.contains_key(app)
+    }
+}
+
+#[cfg(feature = "notifications")]
+#[derive(Debug, Default)]
+struct CooldownState {
+    last_notifications: HashMap<String, LastNotification>,
+    in_flight: HashSet<String>,
+}
+
+#[cfg(feature = "notifications")]
+impl CooldownState {
+    /// Marks `key` in flight unless it is already, or was alerted less than
+    /// `cooldown` ago. Returns whether it was free.
+    fn reserve(&mut self, key: &str, cooldown: Duration, now: Instant) -> bool {
+        let in_cooldown = self.in_flight.contains(key)
+            || self
+                .last_notifications
+                .get(key)
+                .is_some_and(|last| now.duration_since(last.at) < cooldown);
+        if !in_cooldown {
+            self.in_flight.insert(key.to_string());
+        }
+        !in_cooldown
+    }
+}
+
+/// Records a send in `sent` unless `limit` sends already happened within
+/// its window. Returns whether there was room.
+#[cfg(feature = "notifications")]
+fn admit(sent: &mut VecDeque<Instant>, limit: RateLimit, now: Instant) -> bool {
+    while sent
+        .front()
+        .is_some_and(|time| now.duration_since(*time) >= limit.per)
+    {
+        sent.pop_front();
+    }
+    if sent.len() >= limit.max as usize {
+        return false;
+    }
+    sent.push_back(now);
+    true
+}
+
+/// Failures in a row of one channel, and when it may be tried again once
+/// they reach `CHANNEL_FAILURE_THRESHOLD`.
+#[cfg(feature = "notifications")]
+#[derive(Debug, Default)]
+struct ChannelCircuit {
+    failures: u32,
+    /// Pauses in a row, each twice as long as the one before.
+    pauses: u32,
+    paused_until: Option<Instant>,
+}
+
+#[cfg(feature = "notifications")]
+#[derive(Debug, Default)]
+struct ChannelCircuits(HashMap<String, ChannelCircuit>);
+
+#[cfg(feature = "notifications")]
+impl ChannelCircuits {
+    fn is_paused(&self, channel: &str, now: Instant) -> bool {
+        self.0
+            .get(channel)
+            .and_then(|circuit| circuit.paused_until)
+            .is_some_and(|until| until > now)
+    }
+
+    fn succeeded(&mut self, channel: &str) {
+        if let Some(circuit) = self.0.remove(channel)
+            && circuit.pauses > 0
+        {
+            info!(channel, "Notification channel works again");
+        }
+    }
+
+    fn failed(&mut self, channel: &str, now: Instant, error: &NotificationError) {
+        let circuit = self.0.entry(channel.to_string()).or_default();
+        circuit.failures = circuit.failures.saturating_add(1);
+        if circuit.failures < CHANNEL_FAILURE_THRESHOLD {
+            return;
+        }
+        let pause = CHANNEL_PAUSE
+            .saturating_mul(1u32.checked_shl(circuit.pauses).unwrap_or(u32::MAX))
+            .min(MAX_CHANNEL_PAUSE);
+        circuit.pauses = circuit.pauses.saturating_add(1);
+        circuit.paused_until = Some(now + pause);
+        warn!(
+            channel,
+            failures = circuit.failures,
+            pause = %humantime::format_duration(pause),
+            %error,
+            "Pausing notification channel after repeated failures"
+        );
+    }
+
+    fn paused(&self, now: Instant) -> Vec<PausedChannel> {
+        let mut paused: Vec<_> = self
+            .0
+            .iter()
+            .filter_map(|(channel, circuit)| {
+                let remaining = circuit.paused_until?.checked_duration_since(now)?;
+                Some(PausedChannel {
+                    channel: channel.clone(),
+                    failures: circuit.failures,
+                    remaining_seconds: remaining.as_secs_f64().ceil() as u64,
+                })
+            })
+            .filter(|paused| paused.remaining_seconds > 0)
+            .collect();
+        paused.sort_by(|a, b| a.channel.cmp(&b.channel));
+        paused
+    }
+}
+
+#[cfg(feature = "notifications")]
+#[derive(Debug)]
+struct LastNotification {
+    app: String,
+    at: Instant,
+    /// The cooldown that applied to the alert, by its severity.
+    cooldown: Duration,
+}
+
+#[cfg(feature = "notifications")]
+impl LastNotification {
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.cooldown
+            .checked_sub(now.duration_since(self.at))
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
+
+#[cfg(feature = "notifications")]
+#[derive(Debug, Clone)]
+struct DesktopNotifier {
+    config: NotificationConfig,
+    localizer: Arc<Localizer>,
+    actions: Option<ActionSender>,
+    template: Option<String>,
+}
+
+#[cfg(feature = "notifications")]
+#[derive(Debug)]
+pub struct NotificationManager {
+    cooldown: Duration,
+    critical_cooldown: Duration,
+    desktop: DesktopNotifier,
+    channels: Vec<Arc<dyn NotificationChannel>>,
+    cooldowns: Arc<Mutex<CooldownState>>,
+    circuits: Arc<Mutex<ChannelCircuits>>,
+    snoozes: Mutex<SnoozeState>,
+    history: Arc<Mutex<NotificationHistory>>,
+    alerts_log: Option<Arc<AlertsLog>>,
+    events: Option<broadcast::Sender<Event>>,
+    rate_limit: Option<RateLimit>,
+    sent: Mutex<VecDeque<Instant>>,
+    clock: Arc<dyn Clock>,
+    instance: Option<String>,
+}
+
+#[cfg(feature = "notifications")]
+#[derive(Debug)]
+pub struct NotificationManagerBuilder {
+    cooldown: Duration,
+    critical_cooldown: Option<Duration>,
+    config: NotificationConfig,
+    channels: Vec<Arc<dyn NotificationChannel>>,
+    rate_limit: Option<RateLimit>,
+    template: Option<String>,
+    localizer: Localizer,
+    actions: Option<ActionSender>,
+    history: NotificationHistory,
+    alerts_log: Option<Arc<AlertsLog>>,
+    events: Option<broadcast::Sender<Event>>,
+    clock: Arc<dyn Clock>,
+    instance: Option<String>,
+}
+
+#[cfg(feature = "notifications")]
+impl Default for NotificationManagerBuilder {
+    fn default() -> Self {
+        Self {
+            cooldown: DEFAULT_COOLDOWN,
+            critical_cooldown: None,
+            config: NotificationConfig::default(),
+            channels: Vec::new(),
+            rate_limit: None,
+            template: None,
+            localizer: Localizer::default(),
+            actions: None,
+            history: NotificationHistory::default(),
+            alerts_log: None,
+            events: None,
+            clock: clock::system(),
+            instance: None,
+        }
+    }
+}
+
+#[cfg(feature = "notifications")]
+impl NotificationManagerBuilder {
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    pub fn critical_cooldown(mut self, cooldown: Duration) -> Self {
+        self.critical_cooldown = Some(cooldown);
+        self
+    }
+
+    pub fn config(mut self, config: NotificationConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn channel(mut self, channel: Arc<dyn NotificationChannel>) -> Self {
+        self.channels.push(channel);
+        self
+    }
+
+    pub fn global_rate_limit(mut self, max: u32, per: Duration) -> Self {
+        self.rate_limit = Some(RateLimit { max, per });
+        self
+    }
+
+    pub fn template(mut self, template: impl Into<String>) -> Self {
+        self.template = Some(template.into());
+        self
+    }
+
+    pub fn localizer(mut self, localizer: Localizer) -> Self {
+        self.localizer = localizer;
+        self
+    }
+
+    pub fn actions(mut self, actions: ActionSender) -> Self {
+        self.actions = Some(actions);
+        self
+    }
+
+    pub fn history(mut self, history: NotificationHistory) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn alerts_log(mut self, log: Arc<AlertsLog>) -> Self {
+        self.alerts_log = Some(log);
+        self
+    }
+
+    pub fn events(mut self, events: broadcast::Sender<Event>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn instance(mut self, instance: &str) -> Self {
+        self.instance = Some(instance.to_string());
+        self
+    }
+
+    pub fn build(self) -> Result<NotificationManager, NotificationError> {
+        if self.cooldown.is_zero() {
+            return Err(NotificationError::ZeroCooldown);
+        }
+        if self
+            .rate_limit
+            .is_some_and(|limit| limit.max == 0 || limit.per.is_zero())
+        {
+            return Err(NotificationError::InvalidRateLimit);
+        }
+
+        let mut channels = system_log::channels(&self.config);
+        channels.extend(self.channels);
+        if channels.is_empty() && !self.config.channels.contains(&ChannelKind::Desktop) {
+            return Err(NotificationError::NoChannels);
+        }
+        let mut known: Vec<&str> = channels.iter().map(|channel| channel.name()).collect();
+        if self.config.channels.contains(&ChannelKind::Desktop) {
+            known.push(ChannelKind::Desktop.as_str());
+        }
+        for route in &self.config.routes {
+            if let Some(channel) = route.unknown_channel(&known) {
+                return Err(NotificationError::UnknownRouteChannel(
+                    route.to_string(),
+                    channel.to_string(),
+                ));
+            }
+        }
+
+        Ok(NotificationManager {
+            cooldown: self.cooldown,
+            critical_cooldown: self.critical_cooldown.unwrap_or(self.cooldown),
+            desktop: DesktopNotifier {
+                config: self.config,
+                localizer: Arc::new(self.localizer),
+                actions: self.actions,
+                template: self.template,
+            },
+            channels,
+            cooldowns: Arc::new(Mutex::new(CooldownState::default())),
+            circuits: Arc::new(Mutex::new(ChannelCircuits::default())),
+            snoozes: Mutex::new(SnoozeState::default()),
+            history: Arc::new(Mutex::new(self.history)),
+            alerts_log: self.alerts_log,
+            events: self.events,
+            rate_limit: self.rate_limit,
+            sent: Mutex::new(VecDeque::new()),
+            clock: self.clock,
+            instance: self.instance,
+        })
+    }
+}
+
+#[cfg(feature = "notifications")]
+impl Default for NotificationManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_COOLDOWN)
+    }
+}
+
+#[cfg(feature = "notifications")]
+impl NotificationManager {
+    pub fn new(cooldown: Duration) -> Self {
+        Self {
+            cooldown,
+            critical_cooldown: cooldown,
+            desktop: DesktopNotifier {
+                config: NotificationConfig::default(),
+                localizer: Arc::new(Localizer::default()),
+                actions: None,
+                template: None,
+            },
+            channels: Vec::new(),
+            cooldowns: Arc::new(Mutex::new(CooldownState::default())),
+            circuits: Arc::new(Mutex::new(ChannelCircuits::default())),
+            snoozes: Mutex::new(SnoozeState::default()),
+            history: Arc::new(Mutex::new(NotificationHistory::default())),
+            alerts_log: None,
+            events: None,
+            rate_limit: None,
+            sent: Mutex::new(VecDeque::new()),
+            clock: clock::system(),
+            instance: None,
+        }
+    }
+
+    pub fn builder() -> NotificationManagerBuilder {
+        NotificationManagerBuilder::default()
+    }
+
+    pub fn with_critical_cooldown(mut self, cooldown: Duration) -> Self {
+        self.critical_cooldown = cooldown;
+        self
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    pub fn critical_cooldown(&self) -> Duration {
+        self.cooldown_for(Severity::Critical)
+    }
+
+    fn cooldown_for(&self, severity: Severity) -> Duration {
+        match severity {
+            Severity::Critical => self.critical_cooldown.min(self.cooldown),
+            _ => self.cooldown,
+        }
+    }
+
+    pub fn with_actions(mut self, actions: ActionSender) -> Self {
+        self.desktop.actions = Some(actions);
+        self
+    }
+
+    pub fn with_config(mut self, config: NotificationConfig) -> Self {
+        self.channels = system_log::channels(&config);
+        self.desktop.config = config;
+        self
+    }
+
+    pub fn with_channel(mut self, channel: Arc<dyn NotificationChannel>) -> Self {
+        self.channels.push(channel);
+        self
+    }
+
+    fn targets(&self) -> Vec<Arc<dyn NotificationChannel>> {
+        let mut targets: Vec<Arc<dyn NotificationChannel>> = Vec::new();
+        if self.desktop.config.channels.contains(&ChannelKind::Desktop) {
+            targets.push(Arc::new(self.desktop.clone()));
+        }
+        targets.extend(self.channels.iter().cloned());
+        targets
+    }
+
+    /// The channels `alert` is routed to.
+    fn routed_targets(&self, alert: &Alert) -> Vec<Arc<dyn NotificationChannel>> {
+        let mut targets = self.targets();
+        if let Some(channels) = route(&self.desktop.config.routes, alert) {
+            targets.retain(|target| channels.iter().any(|name| name == target.name()));
+        }
+        targets
+    }
+
+    pub fn with_localizer(mut self, localizer: Localizer) -> Self {
+        self.desktop.localizer = Arc::new(localizer);
+        self
+    }
+
+    pub fn localizer(&self) -> &Localizer {
+        &self.desktop.localizer
+    }
+
+    pub fn config(&self) -> &NotificationConfig {
+        &self.desktop.config
+    }
+
+    pub fn with_history(mut self, history: NotificationHistory) -> Self {
+        self.history = Arc::new(Mutex::new(history));
+        self
+    }
+
+    /// Appends every delivered or failed alert to `log`.
+    pub fn with_alerts_log(mut self, log: Arc<AlertsLog>) -> Self {
+        self.alerts_log = Some(log);
+        self
+    }
+
+    /// Sends an `Event::Alert` for every delivered or failed alert.
+    pub fn with_events(mut self, events: broadcast::Sender<Event>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Stamps `instance` on every alert that does not name one already.
+    pub fn with_instance(mut self, instance: &str) -> Self {
+        self.instance = Some(instance.to_string());
+        self
+    }
+
+    pub fn history(&self) -> Result<Vec<NotificationRecord>, NotificationError> {
+        let history = self
+            .history
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+        Ok(history.snapshot())
+    }
+
+    pub fn restore_history(
+        &self,
+        records: impl IntoIterator<Item = NotificationRecord>,
+    ) -> Result<(), NotificationError> {
+        let mut history = self
+            .history
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+        history.extend(records);
+        Ok(())
+    }
+
+    pub fn is_in_cooldown(&self, app: &str) -> Result<bool, NotificationError> {
+        let now = self.clock.now();
+        let cooldowns = self
+            .cooldowns
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+
+        Ok(cooldowns.in_flight.contains(app)
+            || cooldowns
+                .last_notifications
+                .get(app)
+                .is_some_and(|last| last.remaining(now).is_some()))
+    }
+
+    pub fn snooze(&self, app: &str, duration: Duration) -> Result<(), NotificationError> {
+        let mut snoozes = self
+            .snoozes
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+
+        info!(%app, ?duration, "Snoozing notifications");
+        snoozes
+            .apps
+            .insert(app.to_string(), self.clock.now() + duration);
+        Ok(())
+    }
+
+    pub fn snooze_all(&self, duration: Duration) -> Result<(), NotificationError> {
+        let mut snoozes = self
+            .snoozes
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+
+        info!(?duration, "Snoozing all notifications");
+        snoozes.all = Some(self.clock.now() + duration);
+        Ok(())
+    }
+
+    pub fn is_snoozed(&self, app: &str) -> Result<bool, NotificationError> {
+        let now = self.clock.now();
+        let mut snoozes = self
+            .snoozes
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+
+        snoozes.expire(now);
+        Ok(snoozes.covers(app, now))
+    }
+
+    pub fn snoozes(&self) -> Result<Snoozes, NotificationError> {
+        let now = self.clock.now();
+        let snoozes = self
+            .snoozes
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+
+        let remaining = |until: Instant| until.duration_since(now).as_secs_f64().ceil() as u64;
+        Ok(Snoozes {
+            all_seconds: snoozes.all.filter(|until| *until > now).map(remaining),
+            apps: snoozes
+                .apps
+                .iter()
+                .filter(|(_, until)| **until > now)
+                .map(|(app, until)| (app.clone(), remaining(*until)))
+                .collect(),
+        })
+    }
+
+    /// Channels skipped after failing `CHANNEL_FAILURE_THRESHOLD` times in a
+    /// row, sorted by name.
+    pub fn paused_channels(&self) -> Result<Vec<PausedChannel>, NotificationError> {
+        let circuits = self
+            .circuits
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+        Ok(circuits.paused(self.clock.now()))
+    }
+
+    /// Time left before `app` can be alerted again, the longest over all its
+    /// alert kinds. `None` when it was never alerted or every cooldown has
+    /// run out; expired entries are dropped on the way.
+    pub fn cooldown_remaining(&self, app: &str) -> Result<Option<Duration>, NotificationError> {
+        Ok(self
+            .active_cooldowns()?
+            .into_iter()
+            .find_map(|(name, remaining)| (name == app).then_some(remaining)))
+    }
+
+    /// Every app still in cooldown with its time left, sorted by app.
+    pub fn active_cooldowns(&self) -> Result<Vec<(String, Duration)>, NotificationError> {
+        let now = self.clock.now();
+        let mut cooldowns = self
+            .cooldowns
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+
+        cooldowns
+            .last_notifications
+            .retain(|_, last| last.remaining(now).is_some());
+        let mut active = BTreeMap::<String, Duration>::new();
+        for last in cooldowns.last_notifications.values() {
+            let remaining = last.remaining(now).unwrap_or_default();
+            let entry = active.entry(last.app.clone()).or_default();
+            *entry = (*entry).max(remaining);
+        }
+        Ok(active.into_iter().collect())
+    }
+
+    /// Lets the next alert for `app` through, whatever its kind. Returns
+    /// whether there was a cooldown to clear.
+    pub fn clear_cooldown(&self, app: &str) -> Result<bool, NotificationError> {
+        let now = self.clock.now();
+        let mut cooldowns = self
+            .cooldowns
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+
+        let mut cleared = false;
+        cooldowns.last_notifications.retain(|_, last| {
+            let keep = last.app != app;
+            cleared |= !keep && last.remaining(now).is_some();
+            keep
+        });
+        info!(%app, "Cleared notification cooldown");
+        Ok(cleared)
+    }
+
+    /// Clears every cooldown and returns how many alerts were in one.
+    pub fn clear_all_cooldowns(&self) -> Result<usize, NotificationError> {
+        let now = self.clock.now();
+        let mut cooldowns = self
+            .cooldowns
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+
+        let cleared = cooldowns
+            .last_notifications
+            .drain()
+            .filter(|(_, last)| last.remaining(now).is_some())
+            .count();
+        info!(cleared, "Cleared all notification cooldowns");
+        Ok(cleared)
+    }
+
+    /// Sends `alert` through every channel right away, ignoring snoozes,
+    /// cooldowns and the rate limit, and returns each channel's result.
+    pub fn test_channels(&self, alert: &Alert) -> Vec<(String, Result<(), NotificationError>)> {
+        self.targets()
+            .into_iter()
+            .map(|channel| (channel.name().to_string(), channel.send(alert)))
+            .collect()
+    }
+
+    fn reserve(&self, key: &str, severity: Severity) -> Result<bool, NotificationError> {
+        let now = self.clock.now();
+        let cooldown = self.cooldown_for(severity);
+        let mut cooldowns = self
+            .cooldowns
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+        Ok(cooldowns.reserve(key, cooldown, now))
+    }
+
+    fn release(&self, key: &str) -> Result<(), NotificationError> {
+        let mut cooldowns = self
+            .cooldowns
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+        cooldowns.in_flight.remove(key);
+        Ok(())
+    }
+
+    fn within_rate_limit(&self) -> Result<bool, NotificationError> {
+        let Some(limit) = self.rate_limit else {
+            return Ok(true);
+        };
+        let now = self.clock.now();
+        let mut sent = self.sent.lock().map_err(|_| NotificationError::LockError)?;
+        Ok(admit(&mut sent, limit, now))
+    }
+
+    pub fn alert_user(&self, app: &str) -> Result<(), NotificationError> {
+        self.alert(&Alert::new(app))
+    }
+
+    pub fn alert(&self, alert: &Alert) -> Result<(), NotificationError> {
+        self.delivery(alert)?.deliver()
+    }
+
+    /// Like `alert`, but sends on tokio's blocking pool, so a slow D-Bus or
+    /// osascript round-trip does not stall the runtime. Snoozes, cooldowns
+    /// and the rate limit are checked before anything is sent.
+    pub async fn alert_async(&self, alert: &Alert) -> Result<(), NotificationError> {
+        let delivery = self.delivery(alert)?;
+        tokio::task::spawn_blocking(move || delivery.deliver())
+            .await
+            .map_err(|e| NotificationError::ShowError(e.to_string()))?
+    }
+
+    pub async fn alert_user_async(&self, app: &str) -> Result<(), NotificationError> {
+        self.alert_async(&Alert::new(app)).await
+    }
+
+    /// Checks snoozes, cooldowns and the rate limit, and reserves the
+    /// alert's cooldown slot until the delivery finishes.
+    fn delivery(&self, alert: &Alert) -> Result<Delivery, NotificationError> {
+        let app = alert.app.as_str();
+        if self.is_snoozed(app)? {
+            debug!(%app, "Skipping notification while snoozed");
+            return Err(NotificationError::Snoozed);
+        }
+
+        let key = alert.cooldown_key();
+        if !self.reserve(&key, alert.severity)? {
+            debug!(%app, "Skipping notification due to cooldown");
+            return Err(NotificationError::Cooldown);
+        }
+
+        if !self.within_rate_limit()? {
+            self.release(&key)?;
+            debug!(%app, "Skipping notification due to the global rate limit");
+            return Err(NotificationError::RateLimited);
+        }
+        Ok(self.reserved_delivery(alert))
+    }
+
+    /// Sends each of `alerts` like `alert`, in order, and returns their
+    /// results in the same order. Snoozes, cooldowns and the rate limit are
+    /// checked for the whole batch under one lock each, so a scan with many
+    /// apps over their limit does not take the cooldown lock for every app.
+    /// Alerts in the same batch share cooldowns: only the first of two with
+    /// the same app and kind is sent.
+    pub fn alert_batch(&self, alerts: &[Alert]) -> Vec<AlertOutcome> {
+        match self.deliveries(alerts) {
+            Ok(deliveries) => deliver_all(deliveries),
+            Err(_) => alerts
+                .iter()
+                .map(|_| Err(NotificationError::LockError))
+                .collect(),
+        }
+    }
+
+    /// Like `alert_batch`, but sends on tokio's blocking pool like
+    /// `alert_async`.
+    pub async fn alert_batch_async(&self, alerts: &[Alert]) -> Vec<AlertOutcome> {
+        let deliveries = match self.deliveries(alerts) {
+            Ok(deliveries) => deliveries,
+            Err(_) => {
+                return alerts
+                    .iter()
+                    .map(|_| Err(NotificationError::LockError))
+                    .collect();
+            }
+        };
+        match tokio::task::spawn_blocking(move || deliver_all(deliveries)).await {
+            Ok(results) => results,
+            Err(e) => alerts
+                .iter()
+                .map(|_| Err(NotificationError::ShowError(e.to_string())))
+                .collect(),
+        }
+    }
+
+    /// Sends `alerts` like `alert_batch_async` without waiting for them.
+    /// Returns a handle that finishes once each alert was tried once, and
+    /// for each alert a receiver of its final result, which for an alert
+    /// retried in the background only arrives after the last retry. Must be
+    /// called from a tokio runtime.
+    pub fn alert_batch_settled(
+        &self,
+        alerts: &[Alert],
+    ) -> (tokio::task::JoinHandle<()>, Vec<Settled>) {
+        let deliveries = self
+            .deliveries(alerts)
+            .unwrap_or_else(|e| alerts.iter().map(|_| Err(e.clone())).collect());
+        let mut admitted = Vec::new();
+        let settled = deliveries
+            .into_iter()
+            .map(|delivery| {
+                let (sender, receiver) = oneshot::channel();
+                match delivery {
+                    Ok(mut delivery) => {
+                        delivery.settled = Some(sender);
+                        admitted.push(delivery);
+                    }
+                    Err(e) => {
+                        let _ = sender.send(Err(e));
+                    }
+                }
+                receiver
+            })
+            .collect();
+        let attempted = tokio::task::spawn_blocking(move || {
+            for delivery in admitted {
+                let _ = delivery.deliver();
+            }
+        });
+        (attempted, settled)
+    }
+
+    /// `delivery` for each of `alerts`, reserving cooldown slots for those
+    /// let through.
+    fn deliveries(
+        &self,
+        alerts: &[Alert],
+    ) -> Result<Vec<Result<Delivery, NotificationError>>, NotificationError> {
+        let now = self.clock.now();
+        let snoozed: Vec<bool> = {
+            let mut snoozes = self
+                .snoozes
+                .lock()
+                .map_err(|_| NotificationError::LockError)?;
+            snoozes.expire(now);
+            alerts
+                .iter()
+                .map(|alert| snoozes.covers(&alert.app, now))
+                .collect()
+        };
+        let mut cooldowns = self
+            .cooldowns
+            .lock()
+            .map_err(|_| NotificationError::LockError)?;
+        let mut sent = self.sent.lock().map_err(|_| NotificationError::LockError)?;
+
+        let mut deliveries = Vec::with_capacity(alerts.len());
+        for (alert, snoozed) in alerts.iter().zip(snoozed) {
+            let app = alert.app.as_str();
+            if snoozed {
+                debug!(%app, "Skipping notification while snoozed");
+                deliveries.push(Err(NotificationError::Snoozed));
+                continue;
+            }
+            let key = alert.cooldown_key();
+            let cooldown = self.cooldown_for(alert.severity);
+            if !cooldowns.reserve(&key, cooldown, now) {
+                debug!(%app, "Skipping notification due to cooldown");
+                deliveries.push(Err(NotificationError::Cooldown));
+                continue;
+            }
+            if let Some(limit) = self.rate_limit
+                && !admit(&mut sent, limit, now)
+            {
+                cooldowns.in_flight.remove(&key);
+                debug!(%app, "Skipping notification due to the global rate limit");
+                deliveries.push(Err(NotificationError::RateLimited));
+                continue;
+            }
+            deliveries.push(Ok(()));
+        }
+        drop(sent);
+        drop(cooldowns);
+
+        Ok(alerts
+            .iter()
+            .zip(deliveries)
+            .map(|(alert, admitted)| admitted.map(|()| self.reserved_delivery(alert)))
+            .collect())
+    }
+
+    /// The delivery of `alert` once its cooldown slot is reserved.
+    fn reserved_delivery(&self, alert: &Alert) -> Delivery {
+        let mut alert = alert.clone();
+        if alert.instance.is_none() {
+            alert.instance = self.instance.clone();
+        }
+        Delivery {
+            cooldown: self.cooldown_for(alert.severity),
+            pending: self.routed_targets(&alert),
+            alert,
+            delivered: false,
+            channels: Vec::new(),
+            config: self.desktop.config.clone(),
+            cooldowns: Arc::clone(&self.cooldowns),
+            circuits: Arc::clone(&self.circuits),
+            history: Arc::clone(&self.history),
+            alerts_log: self.alerts_log.clone(),
+            events: self.events.clone(),
+            clock: Arc::clone(&self.clock),
+            settled: None,
+        }
+    }
+}
+
+#[cfg(feature = "notifications")]
+struct Delivery {
+    alert: Alert,
+    pending: Vec<Arc<dyn NotificationChannel>>,
+    delivered: bool,
+    /// The latest result of each channel tried so far.
+    channels: Vec<ChannelOutcome>,
+    config: NotificationConfig,
+    cooldown: Duration,
+    cooldowns: Arc<Mutex<CooldownState>>,
+    circuits: Arc<Mutex<ChannelCircuits>>,
+    history: Arc<Mutex<NotificationHistory>>,
+    alerts_log: Option<Arc<AlertsLog>>,
+    events: Option<broadcast::Sender<Event>>,
+    clock: Arc<dyn Clock>,
+    /// Where the final result goes, once any retries are over.
+    settled: Option<oneshot::Sender<AlertOutcome>>,
+}
+
+#[cfg(feature = "notifications")]
+impl Delivery {
+    /// Sends to every channel, retrying failures in the background if
+    /// configured. Blocks for as long as the channels take.
+    fn deliver(mut self) -> Result<(), NotificationError> {
+        let app = self.alert.app.clone();
+        let retry_attempts = self.config.retry_attempts;
+        let result = match self.attempt() {
+            Ok(()) => self.finish(NotificationOutcome::Sent),
+            Err(NotificationError::ShowError(e)) if retry_attempts > 0 => {
+                debug!(%app, error = %e, retry_attempts, "Notification failed, retrying in background");
+                let error = NotificationError::ShowError(e);
+                let result = self.result(error.clone());
+                self.spawn_retry(retry_attempts, error);
+                return result;
+            }
+            Err(e) => self.fail(e),
+        };
+        self.settle(&result);
+        result
+    }
+
+    /// The alert counts as sent if any channel delivered it, even when
+    /// another one failed with `error`.
+    fn result(&self, error: NotificationError) -> AlertOutcome {
+        match self.delivered {
+            true => Ok(()),
+            false => Err(error),
+        }
+    }
+
+    /// Finishes after `error`, the last failure.
+    fn fail(&self, error: NotificationError) -> AlertOutcome {
+        self.finish(self.outcome(&error))?;
+        self.result(error)
+    }
+
+    fn settle(&mut self, result: &AlertOutcome) {
+        if let Some(settled) = self.settled.take() {
+            let _ = settled.send(result.clone());
+        }
+    }
+
+    /// Sends to each pending channel that is not paused, and drops the ones
+    /// that are.
+    fn attempt(&mut self) -> Result<(), NotificationError> {
+        let alert = &self.alert;
+        let now = self.clock.now();
+        let circuits = self.circuits.lock().unwrap_or_else(|e| e.into_inner());
+        let mut results = Vec::new();
+        self.pending.retain(|channel| {
+            let paused = circuits.is_paused(channel.name(), now);
+            if paused {
+                results.push((
+                    channel.name().to_string(),
+                    NotificationOutcome::Failed(CHANNEL_PAUSED.to_string()),
+                ));
+            }
+            !paused
+        });
+        drop(circuits);
+        let all_paused = self.pending.is_empty() && !results.is_empty();
+        let pending = self.pending.len();
+        let mut last_error = None;
+        let mut sent = Vec::new();
+
+        self.pending.retain(|channel| match channel.send(alert) {
+            Ok(()) => {
+                results.push((channel.name().to_string(), NotificationOutcome::Sent));
+                sent.push(Ok(channel.name().to_string()));
+                false
+            }
+            Err(e) => {
+                debug!(app = %alert.app, channel = channel.name(), error = %e, "Notification channel failed");
+                results.push((
+                    channel.name().to_string(),
+                    NotificationOutcome::Failed(e.to_string()),
+                ));
+                sent.push(Err((channel.name().to_string(), e)));
+                true
+            }
+        });
+        let mut circuits = self.circuits.lock().unwrap_or_else(|e| e.into_inner());
+        let now = self.clock.now();
+        for result in sent {
+            match result {
+                Ok(channel) => circuits.succeeded(&channel),
+                Err((channel, e)) => {
+                    circuits.failed(&channel, now, &e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        drop(circuits);
+        for (channel, outcome) in results {
+            match self.channels.iter_mut().find(|c| c.channel == channel) {
+                Some(existing) => existing.outcome = outcome,
+                None => self.channels.push(ChannelOutcome { channel, outcome }),
+            }
+        }
+        if self.pending.len() < pending {
+            self.delivered = true;
+        }
+
+        match last_error {
+            Some(e) => Err(e),
+            None if all_paused => Err(NotificationError::ChannelsPaused),
+            None if pending == 0 => Err(NotificationError::ShowError(
+                "No notification channels configured".to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn outcome(&self, error: &impl ToString) -> NotificationOutcome {
+        if self.delivered {
+            NotificationOutcome::Sent
+        } else {
+            NotificationOutcome::Failed(error.to_string())
+        }
+    }
+
+    /// Retries on the current tokio runtime, or on a thread of its own
+    /// outside one, such as from a CLI command.
+    fn spawn_retry(self, attempts: u32, error: NotificationError) {
+        let retry = self.retry(attempts, error);
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(retry);
+            }
+            Err(_) => {
+                std::thread::spawn(move || {
+                    match tokio::runtime::Builder::new_current_thread()
+                        .enable_time()
+                        .build()
+                    {
+                        Ok(runtime) => runtime.block_on(retry),
+                        Err(e) => warn!(error = %e, "Failed to start notification retries"),
+                    }
+                });
+            }
+        }
+    }
+
+    /// Retries up to `attempts` times, with the backoff doubling each time.
+    /// The attempts are scheduled on `clock` from the first failure, so a
+    /// mock clock moved past all of them runs them in turn.
+    fn retry(
+        mut self,
+        attempts: u32,
+        mut last_error: NotificationError,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let mut backoff = Duration::from_millis(self.config.retry_backoff_ms);
+        let mut due = self.clock.now() + backoff;
+
+        async move {
+            for attempt in 1..=attempts {
+                let wait = due.saturating_duration_since(self.clock.now());
+                self.clock.sleep(wait).await;
+                // Channels block, so they are tried off the runtime's threads.
+                let result;
+                (self, result) = match tokio::task::spawn_blocking(move || {
+                    let result = self.attempt();
+                    (self, result)
+                })
+                .await
+                {
+                    Ok(attempted) => attempted,
+                    Err(e) => {
+                        warn!(error = %e, "Notification retry panicked");
+                        return;
+                    }
+                };
+                match result {
+                    Ok(()) => {
+                        info!(app = %self.alert.app, attempt, "Notification delivered after retry");
+                        let result = self.finish(NotificationOutcome::Sent);
+                        self.settle(&result);
+                        return;
+                    }
+                    Err(e) => {
+                        debug!(app = %self.alert.app, attempt, error = %e, "Notification retry failed");
+                        last_error = e;
+                    }
+                }
+                backoff = backoff.saturating_mul(2);
+                due += backoff;
+            }
+
+            warn!(app = %self.alert.app, attempts, error = %last_error, "Giving up on notification");
+            let result = self.fail(last_error);
+            self.settle(&result);
+        }
+    }
+
+    fn finish(&self, outcome: NotificationOutcome) -> Result<(), NotificationError> {
+        {
+            let mut cooldowns = self
+                .cooldowns
+                .lock()
+                .map_err(|_| NotificationError::LockError)?;
+
+            let key = self.alert.cooldown_key();
+            cooldowns.in_flight.remove(&key);
+            if outcome == NotificationOutcome::Sent || self.config.cooldown_after_failure {
+                cooldowns.last_notifications.insert(
+                    key,
+                    LastNotification {
+                        app: self.alert.app.clone(),
+                        at: self.clock.now(),
+                        cooldown: self.cooldown,
+                    },
+                );
+            }
+        }
+
+        let time = Utc::now();
+        let entry = AlertLogEntry {
+            time,
+            app: self.alert.app.clone(),
+            kind: self.alert.kind,
+            severity: self.alert.severity,
+            usage: self.alert.usage,
+            limit: self.alert.limit,
+            instance: self.alert.instance.clone(),
+            outcome: outcome.clone(),
+            channels: self.channels.clone(),
+        };
+        if let Some(log) = &self.alerts_log {
+            log.append(&entry);
+        }
+        if let Some(events) = &self.events {
+            let _ = events.send(Event::Alert(entry));
+        }
+
+        self.history
+            .lock()
+            .map_err(|_| NotificationError::LockError)?
+            .push(NotificationRecord {
+                app: self.alert.app.clone(),
+                severity: self.alert.severity,
+                time,
+                outcome,
+            });
+        Ok(())
+    }
+}
+
+#[cfg(feature = "notifications")]
+impl DesktopNotifier {
+    fn title(&self, alert: &Alert) -> String {
+        let key = match alert.kind {
+            AlertKind::Exceeded => i18n::ALERT_TITLE,
+            AlertKind::Forecast => i18n::ALERT_TITLE_FORECAST,
+            AlertKind::Anomaly => i18n::ALERT_TITLE_ANOMALY,
+            AlertKind::Cpu => i18n::ALERT_TITLE_CPU,
+            AlertKind::Memory => i18n::ALERT_TITLE_MEMORY,
+            AlertKind::Started => i18n::ALERT_TITLE_STARTED,
+            AlertKind::Summary => i18n::ALERT_TITLE_SUMMARY,
+            AlertKind::DataLoss => i18n::ALERT_TITLE_DATA_LOSS,
+            AlertKind::Budget => i18n::ALERT_TITLE_BUDGET,
+            AlertKind::Failing => i18n::ALERT_TITLE_FAILING,
+            AlertKind::Clamped => i18n::ALERT_TITLE_CLAMPED,
+            AlertKind::SelfUsage => i18n::ALERT_TITLE_SELF_USAGE,
+            AlertKind::Test => i18n::ALERT_TITLE_TEST,
+        };
+        self.localizer.message(key).to_string()
+    }
+
+    fn body(&self, alert: &Alert) -> String {
+        if let Some(template) = &self.template {
+            let format = |value: Option<u64>| match (alert.kind, value) {
+                (_, None) => String::new(),
+                (AlertKind::Cpu, Some(seconds)) => {
+                    humantime::format_duration(Duration::from_secs(seconds)).to_string()
+                }
+                (_, Some(bytes)) => self.localizer.format_bytes(bytes),
+            };
+            return [
+                ("app", alert.display_name().to_string()),
+                ("kind", alert.kind.as_str().to_string()),
+                ("severity", alert.severity.as_str().to_string()),
+                ("usage", format(alert.usage)),
+                ("limit", format(alert.limit)),
+            ]
+            .iter()
+            .fold(template.clone(), |body, (name, value)| {
+                body.replace(&format!("{{{}}}", name), value)
+            });
+        }
+        if alert.kind == AlertKind::Test {
+            return self
+                .localizer
+                .format(i18n::ALERT_BODY_TEST, &[("app", &self.config.app_name)]);
+        }
+        if let (AlertKind::Started, Some(limit)) = (alert.kind, alert.limit) {
+            return self.localizer.format(
+                i18n::ALERT_BODY_STARTED,
+                &[
+                    ("count", &alert.apps.len().to_string()),
+                    ("limit", &self.localizer.format_bytes(limit)),
+                ],
+            );
+        }
+        if alert.kind == AlertKind::DataLoss {
+            return self
+                .localizer
+                .message(match alert.restored {
+                    true => i18n::ALERT_BODY_DATA_RESTORED,
+                    false => i18n::ALERT_BODY_DATA_LOST,
+                })
+                .to_string();
+        }
+        if let (AlertKind::Budget, Some(usage), Some(limit)) =
+            (alert.kind, alert.usage, alert.limit)
+        {
+            return self.localizer.format(
+                i18n::ALERT_BODY_BUDGET,
+                &[
+                    ("percent", &alert.milestone.unwrap_or(0).to_string()),
+                    ("usage", &self.localizer.format_bytes(usage)),
+                    ("limit", &self.localizer.format_bytes(limit)),
+                    ("days", &alert.days_left.unwrap_or(0).to_string()),
+                ],
+            );
+        }
+        if alert.kind == AlertKind::Failing {
+            let key = match alert.task.as_deref() {
+                Some(SAVE_TASK) => i18n::ALERT_BODY_SAVE_FAILING,
+                _ => i18n::ALERT_BODY_SCAN_FAILING,
+            };
+            return self
+                .localizer
+                .format(key, &[("count", &alert.streak.to_string())]);
+        }
+        if let (AlertKind::SelfUsage, Some(usage), Some(limit)) =
+            (alert.kind, alert.usage, alert.limit)
+        {
+            let key = match alert.task.as_deref() {
+                Some(STORAGE_CHECK) => i18n::ALERT_BODY_SELF_STORAGE,
+                _ => i18n::ALERT_BODY_SELF_IO,
+            };
+            return self.localizer.format(
+                key,
+                &[
+                    ("usage", &self.localizer.format_bytes(usage)),
+                    ("limit", &self.localizer.format_bytes(limit)),
+                ],
+            );
+        }
+        if alert.kind == AlertKind::Clamped {
+            return self.localizer.format(
+                i18n::ALERT_BODY_CLAMPED,
+                &[("settings", &alert.settings.join(", "))],
+            );
+        }
+        if let (AlertKind::Summary, Some(usage)) = (alert.kind, alert.usage) {
+            let usage = self.localizer.format_bytes(usage);
+            return match alert.apps.is_empty() {
+                true => self
+                    .localizer
+                    .format(i18n::ALERT_BODY_SUMMARY, &[("usage", &usage)]),
+                false => self.localizer.format(
+                    i18n::ALERT_BODY_SUMMARY_OVER,
+                    &[("usage", &usage), ("apps", &alert.apps.join(", "))],
+                ),
+            };
+        }
+        if let (AlertKind::Forecast, Some(projected), Some(limit)) =
+            (alert.kind, alert.projected, alert.limit)
+        {
+            let key = match alert.period {
+                ResetPeriod::Weekly => i18n::ALERT_BODY_FORECAST_WEEKLY,
+                ResetPeriod::Monthly => i18n::ALERT_BODY_FORECAST_MONTHLY,
+                _ => i18n::ALERT_BODY_FORECAST_DAILY,
+            };
+            return self.localizer.format(
+                key,
+                &[
+                    ("app", alert.display_name()),
+                    ("projected", &self.localizer.format_bytes(projected)),
+                    ("limit", &self.localizer.format_bytes(limit)),
+                ],
+            );
+        }
+        if let (AlertKind::Anomaly, Some(delta), Some(baseline)) =
+            (alert.kind, alert.delta, alert.baseline)
+        {
+            return self.localizer.format(
+                i18n::ALERT_BODY_ANOMALY,
+                &[
+                    ("app", alert.display_name()),
+                    ("delta", &self.localizer.format_bytes(delta)),
+                    ("baseline", &self.localizer.format_bytes(baseline)),
+                ],
+            );
+        }
+
+        if let (AlertKind::Cpu, Some(seconds), Some(limit)) = (alert.kind, alert.usage, alert.limit)
+        {
+            let format = |seconds| {
+                humantime::format_duration(std::time::Duration::from_secs(seconds)).to_string()
+            };
+            return self.localizer.format(
+                i18n::ALERT_BODY_CPU,
+                &[
+                    ("app", alert.display_name()),
+                    ("usage", &format(seconds)),
+                    ("limit", &format(limit)),
+                ],
+            );
+        }
+        if let (AlertKind::Memory, Some(bytes), Some(limit)) =
+            (alert.kind, alert.usage, alert.limit)
+        {
+            return self.localizer.format(
+                i18n::ALERT_BODY_MEMORY,
+                &[
+                    ("app", alert.display_name()),
+                    ("usage", &self.localizer.format_bytes(bytes)),
+                    ("limit", &self.localizer.format_bytes(limit)),
+                ],
+            );
+        }
+
+        let repeat_key = match alert.period {
+            _ if alert.streak < 2 => None,
+            ResetPeriod::Never => None,
+            ResetPeriod::Daily => Some(i18n::ALERT_BODY_REPEAT_DAILY),
+            ResetPeriod::Weekly => Some(i18n::ALERT_BODY_REPEAT_WEEKLY),
+            ResetPeriod::Monthly => Some(i18n::ALERT_BODY_REPEAT_MONTHLY),
+        };
+
+        match (alert.usage, alert.limit) {
+            (Some(usage), Some(limit)) => self.localizer.format(
+                repeat_key.unwrap_or(i18n::ALERT_BODY_USAGE),
+                &[
+                    ("app", alert.display_name()),
+                    ("usage", &self.localizer.format_bytes(usage)),
+                    ("limit", &self.localizer.format_bytes(limit)),
+                    ("count", &alert.streak.to_string()),
+                ],
+            ),
+            _ => self
+                .localizer
+                .format(i18n::ALERT_BODY, &[("app", alert.display_name())]),
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    fn build_notification(&self, summary: &str, body: &str) -> notify_rust::Notification {
+        let mut notification = notify_rust::Notification::new();
+        notification
+            .appname(&self.config.app_name)
+            .summary(summary)
+            .body(body);
+
+        if let Some(icon) = &self.config.icon {
+            notification.icon(icon);
+        }
+        if let Some(timeout_ms) = self.config.timeout_ms {
+            notification.timeout(notify_rust::Timeout::Milliseconds(timeout_ms));
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(subtitle) = &self.config.subtitle {
+                notification.subtitle(subtitle);
+            }
+            if !self.config.sound {
+                notification.hint(notify_rust::Hint::SuppressSound(true));
+            } else if let Some(sound_name) = &self.config.sound_name {
+                notification.sound_name(sound_name);
+            }
+        }
+
+        #[cfg(target_os = "windows")]
+        if self.config.sound {
+            notification.sound_name(self.config.sound_name.as_deref().unwrap_or("Default"));
+        }
+
+        notification
+    }
+
+    #[cfg(target_os = "linux")]
+    fn show(&self, alert: &Alert) -> Result<(), NotificationError> {
+        info!("Sending notification for app: {}", alert.app);
+        let mut notification = self.build_notification(&self.title(alert), &self.body(alert));
+        if alert.severity == Severity::Critical {
+            notification.urgency(notify_rust::Urgency::Critical);
+        }
+
+        let actions = self.actions.clone().filter(|_| server_supports_actions());
+        if actions.is_some() {
+            notification
+                .action(ACTION_SNOOZE, self.localizer.message(i18n::ACTION_SNOOZE))
+                .action(
+                    ACTION_SHOW_REPORT,
+                    self.localizer.message(i18n::ACTION_SHOW_REPORT),
+                );
+        }
+
+        let handle = notification
+            .show()
+            .map_err(|e| NotificationError::ShowError(e.to_string()))?;
+
+        if let Some(actions) = actions {
+            let app = alert.app.clone();
+            std::thread::spawn(move || {
+                handle.wait_for_action(|action| {
+                    let action = match action {
+                        ACTION_SNOOZE => NotificationAction::Snooze,
+                        ACTION_SHOW_REPORT => NotificationAction::ShowReport,
+                        _ => return,
+                    };
+                    debug!(%app, ?action, "Notification action invoked");
+                    let _ = actions.send((app, action));
+                });
+            });
+        }
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    fn show(&self, alert: &Alert) -> Result<(), NotificationError> {
+        info!("Sending notification for app: {}", alert.app);
+
+        let script = applescript_notification(&self.config, &self.body(alert));
+
+        match Command::new("osascript").arg("-e").arg(script).output() {
+            Ok(output) if output.status.success() => Ok(()),
+            Ok(output) => {
+                let err = String::from_utf8_lossy(&output.stderr);
+                error!("Notification error: {}", err);
+                Err(NotificationError::ShowError(err.to_string()))
+            }
+            Err(e) => {
+                error!("Failed to execute osascript: {}", e);
+                Err(NotificationError::ShowError(e.to_string()))
+            }
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn show(&self, alert: &Alert) -> Result<(), NotificationError> {
+        info!("Sending notification for app: {}", alert.app);
+        self.build_notification(&self.title(alert), &self.body(alert))
+            .show()
+            .map(|_| ())
+            .map_err(|e| NotificationError::ShowError(e.to_string()))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    fn show(&self, _alert: &Alert) -> Result<(), NotificationError> {
+        Err(NotificationError::ShowError(
+            "Platform not supported".to_string(),
+        ))
+    }
+}
+
+#[cfg(feature = "notifications")]
+impl NotificationChannel for DesktopNotifier {
+    fn name(&self) -> &str {
+        "desktop"
+    }
+
+    fn send(&self, alert: &Alert) -> Result<(), NotificationError> {
+        self.show(alert)
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "notifications"))]
+fn server_supports_actions() -> bool {
+    static SUPPORTS_ACTIONS: OnceLock<bool> = OnceLock::new();
+    *SUPPORTS_ACTIONS.get_or_init(|| {
+        notify_rust::get_capabilities()
+            .map(|capabilities| capabilities.iter().any(|c| c == "actions"))
+            .unwrap_or(false)
+    })
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn applescript_notification(config: &NotificationConfig, message: &str) -> String {
+    fn escape(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+
+    let mut script = format!(
+        "display notification \"{}\" with title \"{}\"",
+        escape(message),
+        escape(&config.app_name)
+    );
+    if let Some(subtitle) = &config.subtitle {
+        script.push_str(&format!(" subtitle \"{}\"", escape(subtitle)));
+    }
+    if config.sound
+        && let Some(sound_name) = &config.sound_name
+    {
+        script.push_str(&format!(" sound name \"{}\"", escape(sound_name)));
+    }
+    script
+}
+
+#[cfg(feature = "notifications")]
+static NOTIFICATION_MANAGER: OnceLock<NotificationManager> = OnceLock::new();
+
+/// The global notification manager was set up before `init_global` ran.
+#[derive(Error, Debug)]
+#[error("The global notification manager is already initialized")]
+pub struct AlreadyInitialized;
+
+/// Sets the manager behind `alert_user` and `alert_user_async`. It has to
+/// run before either of them: their first call sets up a manager with the
+/// defaults, and from then on this returns `AlreadyInitialized`.
+#[cfg(feature = "notifications")]
+pub fn init_global(manager: NotificationManager) -> Result<(), AlreadyInitialized> {
+    init_cell(&NOTIFICATION_MANAGER, manager)
+}
+
+/// The global manager, if `init_global` or `alert_user` has set it up.
+#[cfg(feature = "notifications")]
+pub fn try_global() -> Option<&'static NotificationManager> {
+    NOTIFICATION_MANAGER.get()
+}
+
+/// The global manager, set up with the defaults if nothing has set it up
+/// yet.
+#[cfg(feature = "notifications")]
+pub fn global() -> &'static NotificationManager {
+    cell_or_default(&NOTIFICATION_MANAGER)
+}
+
+#[cfg(feature = "notifications")]
+fn init_cell(
+    cell: &OnceLock<NotificationManager>,
+    manager: NotificationManager,
+) -> Result<(), AlreadyInitialized> {
+    cell.set(manager).map_err(|_| AlreadyInitialized)
+}
+
+#[cfg(feature = "notifications")]
+fn cell_or_default(cell: &OnceLock<NotificationManager>) -> &NotificationManager {
+    cell.get_or_init(NotificationManager::default)
+}
+
+#[cfg(feature = "notifications")]
+pub fn alert_user(app: &str) -> Result<(), NotificationError> {
+    global().alert_user(app)
+}
+
+/// `alert_user` for async callers; the notification is shown on tokio's
+/// blocking pool.
+#[cfg(feature = "notifications")]
+pub async fn alert_user_async(app: &str) -> Result<(), NotificationError> {
+    global().alert_user_async(app).await
+}
+
+#[cfg(all(test, feature = "notifications"))]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    use super::*;
+    use crate::alerts_log::{ALERTS_LOG_FILE, read_entries};
+    use crate::clock::MockClock;
+    use crate::test_support::{RECORDING_FAILURE, RecordingChannel};
+
+    const TEST_COOLDOWN: Duration = Duration::from_secs(1);
+    const THREAD_COUNT: usize = 4;
+    const TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+    fn test_config() -> NotificationConfig {
+        NotificationConfig {
+            retry_attempts: 2,
+            retry_backoff_ms: 10,
+            ..Default::default()
+        }
+    }
+
+    fn test_manager() -> NotificationManager {
+        NotificationManager::new(TEST_COOLDOWN).with_config(test_config())
+    }
+
+    fn recording_manager(
+        clock: &MockClock,
+        channel: Arc<RecordingChannel>,
+        config: NotificationConfig,
+    ) -> NotificationManager {
+        NotificationManager::new(TEST_COOLDOWN)
+            .with_config(config)
+            .with_channel(channel)
+            .with_clock(Arc::new(clock.clone()))
+    }
+
+    fn mock_manager(clock: &MockClock, channel: Arc<RecordingChannel>) -> NotificationManager {
+        let config = NotificationConfig {
+            channels: Vec::new(),
+            ..test_config()
+        };
+        recording_manager(clock, channel, config)
+    }
+
+    /// Waits for the next alert to be settled, after any retries.
+    async fn settled(receiver: &mut broadcast::Receiver<Event>) -> AlertLogEntry {
+        match receiver.recv().await.unwrap() {
+            Event::Alert(entry) => entry,
+            event => panic!("Expected an alert, got {event:?}"),
+        }
+    }
+
+    #[test]
+    fn test_notification() {
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = mock_manager(&MockClock::new(), channel.clone());
+        let alert = Alert::new("test_app")
+            .with_display_name(Some("Test App".to_string()))
+            .with_usage(1_500, 1_000);
+
+        assert!(manager.alert(&alert).is_ok());
+        assert_eq!(channel.alerts(), [alert]);
+        let history = manager.history().unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].app, "test_app");
+        assert_eq!(history[0].severity, Severity::Warning);
+        assert_eq!(history[0].outcome, NotificationOutcome::Sent);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_alert_async_does_not_block() {
+        let channel = Arc::new(RecordingChannel::new().with_delay(Duration::from_millis(300)));
+        let manager = mock_manager(&MockClock::new(), channel.clone());
+        let ticks = Arc::new(AtomicU32::new(0));
+        let ticker = tokio::spawn({
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(TICK_INTERVAL).await;
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+
+        manager.alert_async(&Alert::new("slow")).await.unwrap();
+        ticker.abort();
+        assert!(ticks.load(Ordering::SeqCst) >= 5);
+        assert_eq!(channel.apps(), ["slow"]);
+        assert!(matches!(
+            manager.alert_async(&Alert::new("slow")).await,
+            Err(NotificationError::Cooldown)
+        ));
+    }
+
+    #[test]
+    fn test_notification_cooldown() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = mock_manager(&clock, channel.clone());
+        let app = "test_cooldown_app";
+
+        assert!(manager.alert_user(app).is_ok());
+        assert!(manager.is_in_cooldown(app).unwrap());
+        assert!(
+            matches!(manager.alert_user(app), Err(NotificationError::Cooldown)),
+            "Second notification should be in cooldown"
+        );
+
+        clock.advance(TEST_COOLDOWN - Duration::from_millis(1));
+        assert!(manager.is_in_cooldown(app).unwrap());
+
+        clock.advance(Duration::from_millis(1));
+        assert!(!manager.is_in_cooldown(app).unwrap());
+        assert!(
+            manager.alert_user(app).is_ok(),
+            "Notification should succeed after cooldown"
+        );
+        assert_eq!(channel.alerts().len(), 2);
+    }
+
+    #[test]
+    fn test_notification_concurrent() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = Arc::new(mock_manager(&clock, channel.clone()));
+        let app = "test_concurrent_app";
+        let barrier = Arc::new(Barrier::new(THREAD_COUNT));
+
+        let alert_concurrently = || {
+            (0..THREAD_COUNT)
+                .map(|_| {
+                    let manager = Arc::clone(&manager);
+                    let barrier = Arc::clone(&barrier);
+                    thread::spawn(move || {
+                        barrier.wait();
+                        manager.alert_user(app)
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .filter(|result| matches!(result, Err(NotificationError::Cooldown)))
+                .count()
+        };
+
+        assert_eq!(
+            alert_concurrently(),
+            THREAD_COUNT - 1,
+            "Only one concurrent notification should be attempted"
+        );
+        assert_eq!(manager.history().unwrap().len(), 1);
+
+        clock.advance(TEST_COOLDOWN / 2);
+        assert_eq!(
+            alert_concurrently(),
+            THREAD_COUNT,
+            "Concurrent notifications should be in cooldown"
+        );
+
+        clock.advance(TEST_COOLDOWN / 2);
+        assert_eq!(alert_concurrently(), THREAD_COUNT - 1);
+        assert_eq!(channel.apps(), [app, app]);
+        assert_eq!(manager.history().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_alert_batch() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::new());
+        let mut manager = mock_manager(&clock, channel.clone());
+        manager.rate_limit = Some(RateLimit {
+            max: 3,
+            per: TEST_COOLDOWN,
+        });
+        manager.snooze("snoozed", TEST_COOLDOWN).unwrap();
+        assert!(manager.alert_user("cooling").is_ok());
+
+        let alerts = ["first", "cooling", "snoozed", "first", "second", "third"].map(Alert::new);
+        let results = manager.alert_batch(&alerts);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(NotificationError::Cooldown)));
+        assert!(matches!(results[2], Err(NotificationError::Snoozed)));
+        assert!(matches!(results[3], Err(NotificationError::Cooldown)));
+        assert!(results[4].is_ok());
+        assert!(matches!(results[5], Err(NotificationError::RateLimited)));
+        assert_eq!(channel.apps(), ["cooling", "first", "second"]);
+        assert!(manager.is_in_cooldown("first").unwrap());
+        assert!(!manager.is_in_cooldown("third").unwrap());
+        assert!(manager.alert_batch(&[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_alert_batch_async_timeout() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::new().with_delay(Duration::from_millis(200)));
+        let events = broadcast::Sender::new(4);
+        let mut receiver = events.subscribe();
+        let manager = mock_manager(&clock, channel.clone()).with_events(events);
+        let alerts = ["first", "second"].map(Alert::new);
+
+        let sent = tokio::time::timeout(
+            Duration::from_millis(20),
+            manager.alert_batch_async(&alerts),
+        )
+        .await;
+        assert!(sent.is_err());
+        // The alerts still go out in the background and start their
+        // cooldowns, so giving up on waiting never sends them twice.
+        settled(&mut receiver).await;
+        settled(&mut receiver).await;
+        assert!(matches!(
+            manager.alert_batch_async(&alerts).await[..],
+            [
+                Err(NotificationError::Cooldown),
+                Err(NotificationError::Cooldown)
+            ]
+        ));
+        assert_eq!(channel.apps(), ["first", "second"]);
+    }
+
+    #[test]
+    fn test_alert_batch_concurrent() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = Arc::new(mock_manager(&clock, channel.clone()));
+        let apps = ["batch_a", "batch_b", "batch_c"];
+        let barrier = Arc::new(Barrier::new(THREAD_COUNT));
+
+        let results: Vec<Vec<AlertOutcome>> = (0..THREAD_COUNT)
+            .map(|i| {
+                let manager = Arc::clone(&manager);
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    // Every other thread goes through the single alert path.
+                    if i % 2 == 0 {
+                        manager.alert_batch(&apps.map(Alert::new))
+                    } else {
+                        apps.iter().map(|app| manager.alert_user(app)).collect()
+                    }
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        for i in 0..apps.len() {
+            let sent = results.iter().filter(|results| results[i].is_ok()).count();
+            assert_eq!(sent, 1, "Only one alert per app should be attempted");
+        }
+        let mut sent = channel.apps();
+        sent.sort();
+        assert_eq!(sent, apps);
+        assert_eq!(manager.history().unwrap().len(), apps.len());
+    }
+
+    #[test]
+    fn test_notification_history() {
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = mock_manager(&MockClock::new(), channel.clone());
+        let app = "test_history_app";
+
+        assert!(manager.alert_user(app).is_ok());
+        assert!(matches!(
+            manager.alert_user(app),
+            Err(NotificationError::Cooldown)
+        ));
+        let history = manager.history().unwrap();
+        assert_eq!(history.len(), 1, "Cooldown skips should not be recorded");
+        assert_eq!(history[0].app, app);
+        assert_eq!(history[0].outcome, NotificationOutcome::Sent);
+
+        channel.set_failing(true);
+        let config = NotificationConfig {
+            channels: Vec::new(),
+            retry_attempts: 0,
+            ..test_config()
+        };
+        let manager = recording_manager(&MockClock::new(), channel, config);
+        assert!(manager.alert_user(app).is_err());
+        assert_eq!(
+            manager.history().unwrap()[0].outcome,
+            NotificationOutcome::Failed(
+                NotificationError::ShowError(RECORDING_FAILURE.to_string()).to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_notification_retry_without_failure_cooldown() {
+        let channel = Arc::new(RecordingChannel::failing());
+        let config = |cooldown_after_failure| NotificationConfig {
+            channels: Vec::new(),
+            retry_attempts: 0,
+            cooldown_after_failure,
+            ..test_config()
+        };
+        let app = "test_retry_app";
+
+        let manager = recording_manager(&MockClock::new(), channel.clone(), config(false));
+        assert!(matches!(
+            manager.alert_user(app),
+            Err(NotificationError::ShowError(e)) if e == RECORDING_FAILURE
+        ));
+        assert!(!manager.is_in_cooldown(app).unwrap());
+
+        let manager = recording_manager(&MockClock::new(), channel.clone(), config(true));
+        assert!(manager.alert_user(app).is_err());
+        assert!(manager.is_in_cooldown(app).unwrap());
+        assert_eq!(channel.apps(), [app, app]);
+    }
+
+    #[tokio::test]
+    async fn test_notification_retry_recovers() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::failing());
+        let events = broadcast::Sender::new(4);
+        let mut receiver = events.subscribe();
+        let manager = mock_manager(&clock, channel.clone()).with_events(events);
+
+        assert!(manager.alert_user("retry_app").is_err());
+        assert!(manager.is_in_cooldown("retry_app").unwrap());
+        channel.set_failing(false);
+        // Nothing is retried until the clock reaches the first backoff.
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(channel.apps(), ["retry_app"]);
+
+        clock.advance(Duration::from_millis(test_config().retry_backoff_ms));
+        assert_eq!(
+            settled(&mut receiver).await.outcome,
+            NotificationOutcome::Sent
+        );
+        assert_eq!(
+            manager.history().unwrap()[0].outcome,
+            NotificationOutcome::Sent
+        );
+        assert_eq!(channel.apps(), ["retry_app"; 2]);
+    }
+
+    #[test]
+    fn test_notification_snooze() {
+        let manager = test_manager();
+
+        manager
+            .snooze("snoozed_app", Duration::from_secs(60))
+            .unwrap();
+        assert!(manager.is_snoozed("snoozed_app").unwrap());
+        assert!(!manager.is_snoozed("other_app").unwrap());
+        assert!(matches!(
+            manager.alert_user("snoozed_app"),
+            Err(NotificationError::Snoozed)
+        ));
+        assert!(manager.history().unwrap().is_empty());
+
+        manager.snooze("expired_app", Duration::ZERO).unwrap();
+        assert!(!manager.is_snoozed("expired_app").unwrap());
+
+        manager.snooze_all(Duration::from_secs(60)).unwrap();
+        assert!(manager.is_snoozed("other_app").unwrap());
+    }
+
+    #[test]
+    fn test_localized_body() {
+        let manager = test_manager().with_localizer(Localizer::new("es_ES.UTF-8"));
+        let alert = Alert::new("firefox").with_usage(1_500_000_000, 1_000_000_000);
+
+        assert_eq!(manager.desktop.title(&alert), "Límite de datos superado");
+        assert_eq!(
+            manager.desktop.body(&alert),
+            "La aplicación 'firefox' ha usado 1,5 GB de su límite de 1,0 GB."
+        );
+        assert_eq!(
+            manager.desktop.body(&Alert::new("firefox")),
+            "La aplicación 'firefox' ha superado el umbral de datos."
+        );
+    }
+
+    #[test]
+    fn test_escalated_body() {
+        let manager = test_manager();
+        let alert = Alert::new("chrome")
+            .with_usage(3_000_000_000, 2_000_000_000)
+            .with_streak(3, ResetPeriod::Daily, Severity::Critical);
+
+        assert_eq!(
+            manager.desktop.body(&alert),
+            "Application 'chrome' has exceeded its limit 3 days in a row (3.0 GB of 2.0 GB)."
+        );
+
+        let first = Alert::new("chrome")
+            .with_usage(3_000_000_000, 2_000_000_000)
+            .with_streak(1, ResetPeriod::Daily, Severity::Warning);
+        assert_eq!(
+            manager.desktop.body(&first),
+            "Application 'chrome' has used 3.0 GB of its 2.0 GB limit."
+        );
+    }
+
+    #[test]
+    fn test_display_name_body() {
+        let manager = test_manager();
+        let alert = Alert::new("chrome.exe")
+            .with_display_name(Some("Google Chrome".to_string()))
+            .with_usage(3_000_000_000, 2_000_000_000);
+
+        assert_eq!(alert.cooldown_key(), "chrome.exe");
+        assert_eq!(
+            manager.desktop.body(&alert),
+            "Application 'Google Chrome' has used 3.0 GB of its 2.0 GB limit."
+        );
+        assert_eq!(Alert::new("chrome.exe").display_name(), "chrome.exe");
+    }
+
+    #[test]
+    fn test_resource_bodies() {
+        let manager = test_manager();
+        let cpu = Alert::new("cc1").with_cpu_time(3720, 3600);
+        assert_eq!(cpu.cooldown_key(), "cc1:cpu");
+        assert_eq!(manager.desktop.title(&cpu), "CPU Limit Exceeded");
+        assert_eq!(
+            manager.desktop.body(&cpu),
+            "Application 'cc1' has used 1h 2m of CPU time, above its 1h limit."
+        );
+
+        let memory = Alert::new("java").with_memory(3_000_000_000, 2_000_000_000);
+        assert_eq!(memory.cooldown_key(), "java:memory");
+        assert_eq!(
+            manager.desktop.body(&memory),
+            "Application 'java' is using 3.0 GB of memory, above its 2.0 GB limit."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_notification_channels() {
+        let clock = MockClock::new();
+        let working = Arc::new(RecordingChannel::new());
+        let failing = Arc::new(RecordingChannel::failing());
+        let events = broadcast::Sender::new(4);
+        let mut receiver = events.subscribe();
+        let manager = mock_manager(&clock, working.clone())
+            .with_channel(failing.clone())
+            .with_events(events);
+
+        assert!(manager.alert_user("channel_app").is_ok());
+        // Both retries are due once the doubled backoffs have passed.
+        clock.advance(Duration::from_millis(3 * test_config().retry_backoff_ms));
+        assert_eq!(
+            settled(&mut receiver).await.outcome,
+            NotificationOutcome::Sent
+        );
+        assert_eq!(
+            manager.history().unwrap()[0].outcome,
+            NotificationOutcome::Sent
+        );
+        assert_eq!(working.apps(), ["channel_app"]);
+        assert_eq!(failing.apps(), ["channel_app"; 3]);
+
+        let manager = NotificationManager::new(TEST_COOLDOWN)
+            .with_config(NotificationConfig {
+                channels: Vec::new(),
+                retry_attempts: 0,
+                ..test_config()
+            })
+            .with_channel(failing);
+        assert!(matches!(
+            manager.alert_user("channel_app"),
+            Err(NotificationError::ShowError(e)) if e == RECORDING_FAILURE
+        ));
+        assert!(matches!(
+            manager.history().unwrap()[0].outcome,
+            NotificationOutcome::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn test_routes() {
+        let route =
+            |severity: Option<Severity>, app: Option<&str>, channels: &[&str]| NotificationRoute {
+                severity,
+                app: app.map(str::to_string),
+                channels: channels.iter().map(|name| name.to_string()).collect(),
+            };
+        let config = NotificationConfig {
+            channels: Vec::new(),
+            routes: vec![
+                route(None, Some("backup-*"), &["email"]),
+                route(Some(Severity::Warning), None, &["log"]),
+                route(Some(Severity::Critical), None, &["log", "slack"]),
+            ],
+            ..test_config()
+        };
+        let log = Arc::new(RecordingChannel::new().with_name("log"));
+        let slack = Arc::new(RecordingChannel::new().with_name("slack"));
+        let email = Arc::new(RecordingChannel::new().with_name("email"));
+        let manager = NotificationManager::builder()
+            .config(config.clone())
+            .channel(log.clone())
+            .channel(slack.clone())
+            .channel(email.clone())
+            .build()
+            .unwrap();
+
+        let critical =
+            |app: &str| Alert::new(app).with_streak(3, ResetPeriod::Daily, Severity::Critical);
+        manager.alert(&Alert::new("firefox")).unwrap();
+        manager.alert(&critical("steam")).unwrap();
+        manager.alert(&critical("Backup-Job")).unwrap();
+        manager.alert(&Alert::new("curl").with_test()).unwrap();
+        assert_eq!(manager.history().unwrap().len(), 4);
+        // The info alert matches no route, so it goes everywhere.
+        assert_eq!(log.apps(), ["firefox", "steam", "curl"]);
+        assert_eq!(slack.apps(), ["steam", "curl"]);
+        assert_eq!(email.apps(), ["Backup-Job", "curl"]);
+
+        let error = NotificationManager::builder()
+            .config(config)
+            .channel(log)
+            .channel(slack)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"Notification route { app = "backup-*", channels = ["email"] } names unknown channel "email""#
+        );
+    }
+
+    #[test]
+    fn test_failing_channel_paused() {
+        let clock = MockClock::new();
+        let broken = Arc::new(RecordingChannel::failing().with_name("broken"));
+        let working = Arc::new(RecordingChannel::new().with_name("working"));
+        let manager = NotificationManager::new(Duration::ZERO)
+            .with_config(NotificationConfig {
+                channels: Vec::new(),
+                retry_attempts: 0,
+                cooldown_after_failure: false,
+                ..test_config()
+            })
+            .with_channel(broken.clone())
+            .with_channel(working.clone())
+            .with_clock(Arc::new(clock.clone()));
+
+        for i in 0..CHANNEL_FAILURE_THRESHOLD {
+            assert!(manager.paused_channels().unwrap().is_empty());
+            let _ = manager.alert_user(&format!("app{}", i));
+        }
+        assert_eq!(
+            manager.paused_channels().unwrap(),
+            [PausedChannel {
+                channel: "broken".to_string(),
+                failures: CHANNEL_FAILURE_THRESHOLD,
+                remaining_seconds: CHANNEL_PAUSE.as_secs(),
+            }]
+        );
+
+        // The paused channel is skipped while the other keeps working.
+        assert!(manager.alert_user("skipped").is_ok());
+        assert_eq!(broken.alerts().len(), CHANNEL_FAILURE_THRESHOLD as usize);
+        assert_eq!(working.apps().last().unwrap(), "skipped");
+
+        // Failing again after the pause doubles it.
+        clock.advance(CHANNEL_PAUSE);
+        let _ = manager.alert_user("again");
+        assert_eq!(
+            manager.paused_channels().unwrap()[0].remaining_seconds,
+            2 * CHANNEL_PAUSE.as_secs()
+        );
+
+        clock.advance(2 * CHANNEL_PAUSE);
+        broken.set_failing(false);
+        assert!(manager.alert_user("recovered").is_ok());
+        assert_eq!(broken.apps().last().unwrap(), "recovered");
+        assert!(manager.paused_channels().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_all_channels_paused() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::failing());
+        let manager = NotificationManager::new(Duration::ZERO)
+            .with_config(NotificationConfig {
+                channels: Vec::new(),
+                retry_attempts: 0,
+                cooldown_after_failure: false,
+                ..test_config()
+            })
+            .with_channel(channel.clone())
+            .with_clock(Arc::new(clock));
+
+        for _ in 0..CHANNEL_FAILURE_THRESHOLD {
+            assert!(matches!(
+                manager.alert_user("app"),
+                Err(NotificationError::ShowError(_))
+            ));
+        }
+        assert!(matches!(
+            manager.alert_user("app"),
+            Err(NotificationError::ChannelsPaused)
+        ));
+        assert_eq!(channel.alerts().len(), CHANNEL_FAILURE_THRESHOLD as usize);
+        assert_eq!(
+            manager.history().unwrap().last().unwrap().outcome,
+            NotificationOutcome::Failed(NotificationError::ChannelsPaused.to_string())
+        );
+    }
+
+    #[test]
+    fn test_alerts_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = Arc::new(AlertsLog::new(dir.path().join(ALERTS_LOG_FILE)));
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = NotificationManager::new(TEST_COOLDOWN)
+            .with_config(NotificationConfig {
+                channels: Vec::new(),
+                ..test_config()
+            })
+            .with_channel(channel)
+            .with_alerts_log(log.clone());
+        let events = broadcast::Sender::new(4);
+        let mut receiver = events.subscribe();
+        let manager = manager.with_events(events);
+
+        let alert = Alert::new("logged_app").with_usage(2048, 1024);
+        assert!(manager.alert(&alert).is_ok());
+        let history = manager.history().unwrap();
+        let entries = read_entries(log.path()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].app, "logged_app");
+        assert_eq!(entries[0].time, history[0].time);
+        assert_eq!(
+            (entries[0].usage, entries[0].limit),
+            (Some(2048), Some(1024))
+        );
+        assert_eq!(
+            entries[0].channels,
+            [ChannelOutcome {
+                channel: "recording".to_string(),
+                outcome: NotificationOutcome::Sent,
+            }]
+        );
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            Event::Alert(entries[0].clone())
+        );
+    }
+
+    #[test]
+    fn test_global_before_init() {
+        // Whatever comes first wins: alerting before `init_global` keeps
+        // the defaults for good.
+        let cell = OnceLock::new();
+        assert_eq!(cell_or_default(&cell).cooldown, DEFAULT_COOLDOWN);
+        assert!(matches!(
+            init_cell(&cell, NotificationManager::new(TEST_COOLDOWN)),
+            Err(AlreadyInitialized)
+        ));
+        assert_eq!(cell_or_default(&cell).cooldown, DEFAULT_COOLDOWN);
+
+        let cell = OnceLock::new();
+        assert!(init_cell(&cell, NotificationManager::new(TEST_COOLDOWN)).is_ok());
+        assert_eq!(cell_or_default(&cell).cooldown, TEST_COOLDOWN);
+
+        for _ in 0..20 {
+            let cell = OnceLock::new();
+            let barrier = Barrier::new(2);
+            let (initialized, used) = thread::scope(|scope| {
+                let init = scope.spawn(|| {
+                    barrier.wait();
+                    init_cell(&cell, NotificationManager::new(TEST_COOLDOWN)).is_ok()
+                });
+                let alert = scope.spawn(|| {
+                    barrier.wait();
+                    cell_or_default(&cell).cooldown
+                });
+                (init.join().unwrap(), alert.join().unwrap())
+            });
+            let expected = if initialized {
+                TEST_COOLDOWN
+            } else {
+                DEFAULT_COOLDOWN
+            };
+            assert_eq!(used, expected);
+            assert_eq!(cell.get().unwrap().cooldown, expected);
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        let no_desktop = || NotificationConfig {
+            channels: Vec::new(),
+            ..test_config()
+        };
+        assert!(matches!(
+            NotificationManager::builder().config(no_desktop()).build(),
+            Err(NotificationError::NoChannels)
+        ));
+        assert!(matches!(
+            NotificationManager::builder()
+                .cooldown(Duration::ZERO)
+                .build(),
+            Err(NotificationError::ZeroCooldown)
+        ));
+        assert!(matches!(
+            NotificationManager::builder()
+                .global_rate_limit(0, TEST_COOLDOWN)
+                .build(),
+            Err(NotificationError::InvalidRateLimit)
+        ));
+
+        let targets = NotificationManager::builder().build().unwrap().targets();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].name(), "desktop");
+        assert_eq!(
+            NotificationManager::default().targets()[0].name(),
+            "desktop"
+        );
+
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = NotificationManager::builder()
+            .cooldown(Duration::from_secs(60))
+            .critical_cooldown(Duration::ZERO)
+            .config(no_desktop())
+            .channel(channel.clone())
+            .build()
+            .unwrap();
+        assert!(manager.alert_user("builder_app").is_ok());
+        assert!(matches!(
+            manager.alert_user("builder_app"),
+            Err(NotificationError::Cooldown)
+        ));
+        let critical =
+            Alert::new("builder_app").with_streak(3, ResetPeriod::Daily, Severity::Critical);
+        assert!(manager.alert(&critical).is_ok());
+        assert_eq!(channel.alerts().len(), 2);
+    }
+
+    #[test]
+    fn test_builder_rate_limit() {
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = NotificationManager::builder()
+            .config(NotificationConfig {
+                channels: Vec::new(),
+                ..test_config()
+            })
+            .channel(channel.clone())
+            .global_rate_limit(2, Duration::from_secs(60))
+            .build()
+            .unwrap();
+
+        assert!(manager.alert_user("first").is_ok());
+        assert!(manager.alert_user("second").is_ok());
+        assert!(matches!(
+            manager.alert_user("third"),
+            Err(NotificationError::RateLimited)
+        ));
+        assert!(!manager.is_in_cooldown("third").unwrap());
+        assert_eq!(channel.apps(), ["first", "second"]);
+    }
+
+    #[test]
+    fn test_builder_template() {
+        let manager = NotificationManager::builder()
+            .template("{app} ({severity}, {kind}): {usage} of {limit}")
+            .build()
+            .unwrap();
+        let alert = Alert::new("firefox").with_usage(1_500_000_000, 1_000_000_000);
+        assert_eq!(
+            manager.desktop.body(&alert),
+            "firefox (warning, exceeded): 1.5 GB of 1.0 GB"
+        );
+
+        let alert = Alert::new("ffmpeg").with_cpu_time(90, 60);
+        assert_eq!(
+            manager.desktop.body(&alert),
+            "ffmpeg (warning, cpu): 1m 30s of 1m"
+        );
+    }
+
+    #[test]
+    fn test_forecast_body() {
+        let manager = test_manager();
+        let alert = Alert::new("Dropbox")
+            .with_usage(1_200_000_000, 2_000_000_000)
+            .with_forecast(3_200_000_000, ResetPeriod::Daily);
+
+        assert_eq!(alert.severity, Severity::Info);
+        assert_eq!(manager.desktop.title(&alert), "Data Limit Forecast");
+        assert_eq!(
+            manager.desktop.body(&alert),
+            "Dropbox is on pace to use 3.2 GB of its 2.0 GB daily limit."
+        );
+    }
+
+    #[test]
+    fn test_anomaly_body() {
+        let manager = test_manager().with_localizer(Localizer::new("de"));
+        let alert = Alert::new("backup").with_anomaly(750_000_000, 2_000_000);
+
+        assert_eq!(alert.cooldown_key(), "backup:anomaly");
+        assert_eq!(manager.desktop.title(&alert), "Ungewöhnliche Datennutzung");
+        assert_eq!(
+            manager.desktop.body(&alert),
+            "'backup' hat seit der letzten Prüfung 750,0 MB übertragen, weit mehr als die üblichen 2,0 MB."
+        );
+    }
+
+    #[test]
+    fn test_lifecycle_bodies() {
+        let manager = test_manager();
+        let started = Alert::new(DEFAULT_APP_NAME).with_started(
+            vec!["firefox".to_string(), "curl".to_string()],
+            2_000_000_000,
+        );
+        assert_eq!(manager.desktop.title(&started), "Data Guardian Started");
+        assert_eq!(
+            manager.desktop.body(&started),
+            "Monitoring 2 apps with a 2.0 GB limit."
+        );
+
+        let summary = Alert::new(DEFAULT_APP_NAME).with_summary(Vec::new(), 1_500_000);
+        assert_eq!(summary.severity, Severity::Info);
+        assert_eq!(
+            manager.desktop.body(&summary),
+            "1.5 MB moved this session. No apps are over their limit."
+        );
+
+        let summary = Alert::new(DEFAULT_APP_NAME)
+            .with_summary(vec!["steam".to_string(), "zoom".to_string()], 1_500_000);
+        assert_eq!(summary.severity, Severity::Warning);
+        assert_eq!(manager.desktop.title(&summary), "Data Guardian Stopped");
+        assert_eq!(
+            manager.desktop.body(&summary),
+            "1.5 MB moved this session. Over the limit: steam, zoom."
+        );
+    }
+
+    #[test]
+    fn test_budget_body() {
+        let manager = test_manager();
+        let alert =
+            Alert::new(DEFAULT_APP_NAME).with_budget(780_000_000_000, 1_000_000_000_000, 75, 9);
+
+        assert_eq!(alert.cooldown_key(), "Data Guardian:budget:75");
+        assert_eq!(alert.severity, Severity::Info);
+        assert_eq!(manager.desktop.title(&alert), "Data Budget");
+        assert_eq!(
+            manager.desktop.body(&alert),
+            "You have used 75% of your data budget (780.0 GB of 1.0 TB) with 9 days left in the period."
+        );
+        let spent = Alert::new(DEFAULT_APP_NAME).with_budget(1, 1, 100, 0);
+        assert_eq!(spent.severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_failing_body() {
+        let manager = test_manager();
+        let alert = Alert::new(DEFAULT_APP_NAME).with_failing(SAVE_TASK, 5);
+
+        assert_eq!(alert.cooldown_key(), "Data Guardian:failing:save");
+        assert_eq!(manager.desktop.title(&alert), "Monitoring Problem");
+        assert_eq!(
+            manager.desktop.body(&alert),
+            "Saving usage data failed 5 times in a row. Data Guardian keeps trying less often; see the service log."
+        );
+        assert_eq!(
+            system_log::format_alert(&alert),
+            r#"event=task_failing app="Data Guardian" severity=warning streak=5 task=save"#
+        );
+    }
+
+    #[test]
+    fn test_self_usage_body() {
+        let manager = test_manager();
+        let alert =
+            Alert::new(DEFAULT_APP_NAME).with_self_usage(STORAGE_CHECK, 600_000_000, 512_000_000);
+
+        assert_eq!(alert.severity, Severity::Info);
+        assert_eq!(alert.cooldown_key(), "Data Guardian:self_usage:storage");
+        assert_eq!(manager.desktop.title(&alert), "Data Guardian Disk Use");
+        assert_eq!(
+            manager.desktop.body(&alert),
+            "Data Guardian's data and log files take up 600.0 MB, more than the 512.0 MB allowed by self_check."
+        );
+        assert_eq!(
+            system_log::format_alert(&alert),
+            r#"event=self_usage_exceeded app="Data Guardian" severity=info usage=600000000 limit=512000000 task=storage"#
+        );
+    }
+
+    #[test]
+    fn test_clamped_body() {
+        let manager = test_manager();
+        let alert = Alert::new(DEFAULT_APP_NAME).with_clamped(vec![
+            "check_interval_seconds".to_string(),
+            "mqtt.qos".to_string(),
+        ]);
+
+        assert_eq!(alert.severity, Severity::Warning);
+        assert_eq!(manager.desktop.title(&alert), "Settings Adjusted");
+        assert!(
+            manager
+                .desktop
+                .body(&alert)
+                .contains("value: check_interval_seconds, mqtt.qos.")
+        );
+        assert_eq!(
+            system_log::format_alert(&alert),
+            r#"event=settings_clamped app="Data Guardian" severity=warning settings="check_interval_seconds,mqtt.qos""#
+        );
+    }
+
+    #[test]
+    fn test_channels_bypass_cooldown() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = mock_manager(&clock, channel.clone());
+        manager.snooze_all(Duration::from_secs(90)).unwrap();
+        manager.snooze("firefox", Duration::from_secs(30)).unwrap();
+
+        let alert = Alert::new(DEFAULT_APP_NAME).with_test();
+        assert_eq!(manager.desktop.title(&alert), "Test Notification");
+        assert_eq!(
+            manager.desktop.body(&alert),
+            "Notifications from Data Guardian are working."
+        );
+        let results = manager.test_channels(&alert);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "recording");
+        assert!(results[0].1.is_ok());
+
+        channel.set_failing(true);
+        let results = manager.test_channels(&alert);
+        assert!(results[0].1.is_err());
+        assert_eq!(channel.alerts().len(), 2);
+        assert!(manager.history().unwrap().is_empty());
+
+        clock.advance(Duration::from_secs(40));
+        let snoozes = manager.snoozes().unwrap();
+        assert_eq!(snoozes.all_seconds, Some(50));
+        assert!(snoozes.apps.is_empty());
+    }
+
+    #[test]
+    fn test_forecast_cooldown_is_separate() {
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = NotificationManager::new(Duration::from_secs(60))
+            .with_config(NotificationConfig {
+                channels: Vec::new(),
+                ..test_config()
+            })
+            .with_channel(channel.clone());
+        let forecast = Alert::new("forecast_app")
+            .with_usage(10, 20)
+            .with_forecast(30, ResetPeriod::Daily);
+
+        assert!(manager.alert(&forecast).is_ok());
+        assert!(matches!(
+            manager.alert(&forecast),
+            Err(NotificationError::Cooldown)
+        ));
+        assert!(!manager.is_in_cooldown("forecast_app").unwrap());
+        assert!(manager.alert_user("forecast_app").is_ok());
+        assert_eq!(channel.alerts().len(), 2);
+    }
+
+    #[test]
+    fn test_critical_cooldown() {
+        let manager = test_manager().with_critical_cooldown(Duration::ZERO);
+        assert_eq!(manager.cooldown_for(Severity::Warning), TEST_COOLDOWN);
+        assert_eq!(manager.cooldown_for(Severity::Critical), Duration::ZERO);
+
+        let manager = test_manager().with_critical_cooldown(TEST_COOLDOWN * 10);
+        assert_eq!(manager.cooldown_for(Severity::Critical), TEST_COOLDOWN);
+    }
+
+    #[test]
+    fn test_critical_cooldown_ends_early() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::new());
+        let manager =
+            mock_manager(&clock, channel.clone()).with_critical_cooldown(TEST_COOLDOWN / 4);
+        let critical = Alert::new("chrome")
+            .with_usage(3_000_000_000, 2_000_000_000)
+            .with_streak(3, ResetPeriod::Daily, Severity::Critical);
+
+        manager.alert(&critical).unwrap();
+        assert!(manager.is_in_cooldown("chrome").unwrap());
+
+        clock.advance(TEST_COOLDOWN / 4);
+        assert!(!manager.is_in_cooldown("chrome").unwrap());
+        manager.alert(&critical).unwrap();
+        assert_eq!(channel.alerts().len(), 2);
+    }
+
+    #[test]
+    fn test_cooldown_inspection() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = mock_manager(&clock, channel.clone());
+        let half = TEST_COOLDOWN / 2;
+
+        assert_eq!(manager.cooldown_remaining("app").unwrap(), None);
+        assert!(manager.active_cooldowns().unwrap().is_empty());
+
+        manager.alert_user("app").unwrap();
+        clock.advance(half);
+        manager
+            .alert(&Alert::new("app").with_forecast(2_000, ResetPeriod::Daily))
+            .unwrap();
+        manager.alert_user("other").unwrap();
+        assert_eq!(
+            manager.cooldown_remaining("app").unwrap(),
+            Some(TEST_COOLDOWN)
+        );
+        assert_eq!(
+            manager.active_cooldowns().unwrap(),
+            [
+                ("app".to_string(), TEST_COOLDOWN),
+                ("other".to_string(), TEST_COOLDOWN)
+            ]
+        );
+
+        // Exactly at the end of the cooldown an alert goes through again,
+        // so the entry is gone rather than reported as zero.
+        clock.advance(TEST_COOLDOWN);
+        assert_eq!(manager.cooldown_remaining("app").unwrap(), None);
+        assert!(manager.active_cooldowns().unwrap().is_empty());
+        assert!(!manager.clear_cooldown("app").unwrap());
+        assert_eq!(manager.clear_all_cooldowns().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_clear_cooldown() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = mock_manager(&clock, channel.clone());
+
+        manager.alert_user("app").unwrap();
+        manager.alert_user("other").unwrap();
+        assert!(matches!(
+            manager.alert_user("app"),
+            Err(NotificationError::Cooldown)
+        ));
+
+        assert!(manager.clear_cooldown("app").unwrap());
+        assert!(!manager.clear_cooldown("app").unwrap());
+        assert_eq!(manager.cooldown_remaining("app").unwrap(), None);
+        assert!(manager.alert_user("app").is_ok());
+        assert!(manager.is_in_cooldown("other").unwrap());
+
+        assert_eq!(manager.clear_all_cooldowns().unwrap(), 2);
+        assert!(manager.active_cooldowns().unwrap().is_empty());
+        assert!(manager.alert_user("other").is_ok());
+        assert_eq!(channel.alerts().len(), 4);
+    }
+
+    #[test]
+    fn test_applescript_notification() {
+        let config = NotificationConfig {
+            sound: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            applescript_notification(&config, r#"Application "x" exceeded"#),
+            r#"display notification "Application \"x\" exceeded" with title "Data Guardian""#
+        );
+
+        let config = NotificationConfig {
+            subtitle: Some("Threshold".to_string()),
+            sound_name: Some("Glass".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            applescript_notification(&config, "msg"),
+            r#"display notification "msg" with title "Data Guardian" subtitle "Threshold" sound name "Glass""#
+        );
+    }
+
+    #[test]
+    fn test_notification_special_chars() {
+        let clock = MockClock::new();
+        let channel = Arc::new(RecordingChannel::new());
+        let manager = mock_manager(&clock, channel.clone());
+        const TEST_CASES: [&str; 6] = [
+            r#"test"app"#,
+            r#"test'app"#,
+            r#"test\app"#,
+            r#"test/app"#,
+            r#"test app"#,
+            r#"test_app"#,
+        ];
+
+        for app in TEST_CASES {
+            assert!(
+                manager.alert_user(app).is_ok(),
+                "Failed to handle special chars in: {}",
+                app
+            );
+            clock.advance(TEST_COOLDOWN);
+            assert!(!manager.is_in_cooldown(app).unwrap());
+        }
+        assert_eq!(channel.apps(), TEST_CASES);
+    }
+}
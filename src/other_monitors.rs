@@ -0,0 +1,201 @@
+//! Best-effort detection of other programs that warn about data usage, such
+//! as GNOME Usage, so their popups and ours do not pile up.
+
+use serde::{Deserialize, Serialize};
+
+use super::app_names;
+use super::notification::{ChannelKind, NotificationConfig, NotificationRoute};
+
+/// Process names of known monitors, and what to call them in logs.
+pub const KNOWN_MONITORS: &[(&str, &str)] = &[
+    ("gnome-usage", "GNOME Usage"),
+    ("nm-applet", "NetworkManager applet"),
+    ("GlassWire", "GlassWire"),
+    ("NetLimiter", "NetLimiter"),
+];
+/// The alerts `quiet_network_alerts` sends to the log only.
+pub const NETWORK_ALERTS: &str = "net:*";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OtherMonitorsConfig {
+    /// Look for other monitors at startup.
+    pub detect: bool,
+    /// Process names to look for on top of [`KNOWN_MONITORS`].
+    pub processes: Vec<String>,
+    /// When one is running, send alerts about network interfaces to the
+    /// log only.
+    pub quiet_network_alerts: bool,
+}
+
+impl Default for OtherMonitorsConfig {
+    fn default() -> Self {
+        Self {
+            detect: true,
+            processes: Vec::new(),
+            quiet_network_alerts: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OtherMonitor {
+    pub process: String,
+    pub name: String,
+}
+
+/// What startup found, and whether it moved network alerts to the log.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coexistence {
+    pub monitors: Vec<OtherMonitor>,
+    pub network_alerts_log_only: bool,
+}
+
+impl OtherMonitorsConfig {
+    /// The monitors among the `running` process names, each once.
+    pub fn detect<'a>(&self, running: impl IntoIterator<Item = &'a str>) -> Vec<OtherMonitor> {
+        if !self.detect {
+            return Vec::new();
+        }
+        let candidates: Vec<(&str, &str)> = KNOWN_MONITORS
+            .iter()
+            .copied()
+            .chain(
+                self.processes
+                    .iter()
+                    .map(|name| (name.as_str(), name.as_str())),
+            )
+            .collect();
+        let mut found: Vec<OtherMonitor> = Vec::new();
+        for process in running {
+            let Some((_, name)) = candidates
+                .iter()
+                .find(|(candidate, _)| is_process(process, candidate))
+            else {
+                continue;
+            };
+            if !found.iter().any(|monitor| monitor.name == *name) {
+                found.push(OtherMonitor {
+                    process: process.to_string(),
+                    name: name.to_string(),
+                });
+            }
+        }
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+        found
+    }
+
+    pub fn decide(&self, monitors: Vec<OtherMonitor>) -> Coexistence {
+        Coexistence {
+            network_alerts_log_only: self.quiet_network_alerts && !monitors.is_empty(),
+            monitors,
+        }
+    }
+}
+
+impl Coexistence {
+    pub fn is_empty(&self) -> bool {
+        self.monitors.is_empty()
+    }
+
+    /// Puts a route first that sends network interface alerts to the log
+    /// channel, setting that channel up if needed.
+    pub fn apply(&self, config: &mut NotificationConfig) {
+        if !self.network_alerts_log_only {
+            return;
+        }
+        if !config.channels.contains(&ChannelKind::Log) {
+            config.channels.push(ChannelKind::Log);
+        }
+        config.routes.insert(
+            0,
+            NotificationRoute {
+                severity: None,
+                app: Some(NETWORK_ALERTS.to_string()),
+                channels: vec![ChannelKind::Log.as_str().to_string()],
+            },
+        );
+    }
+}
+
+/// Whether `process` is `name`, ignoring case and a trailing `.exe`.
+fn is_process(process: &str, name: &str) -> bool {
+    app_names::strip_exe(process).eq_ignore_ascii_case(name)
+}
+
+/// The names of every running process.
+#[cfg(feature = "monitor")]
+pub fn running_processes() -> Vec<String> {
+    use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::nothing());
+    system
+        .processes()
+        .values()
+        .map(|process| process.name().to_string_lossy().into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notification::{Alert, route};
+
+    #[test]
+    fn test_detect() {
+        let config = OtherMonitorsConfig::default();
+        let running = ["systemd", "gnome-usage", "GNOME-USAGE", "nm-applet", "bash"];
+        let found = config.detect(running);
+        assert_eq!(
+            found,
+            [
+                OtherMonitor {
+                    process: "gnome-usage".to_string(),
+                    name: "GNOME Usage".to_string(),
+                },
+                OtherMonitor {
+                    process: "nm-applet".to_string(),
+                    name: "NetworkManager applet".to_string(),
+                },
+            ]
+        );
+        assert_eq!(config.detect(["glasswire.exe"])[0].name, "GlassWire");
+        assert!(config.detect(["gnome-usage-helper", "bash"]).is_empty());
+
+        let extended = OtherMonitorsConfig {
+            processes: vec!["vnstatd".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(extended.detect(["vnstatd"])[0].name, "vnstatd");
+
+        let off = OtherMonitorsConfig {
+            detect: false,
+            ..Default::default()
+        };
+        assert!(off.detect(running).is_empty());
+    }
+
+    #[test]
+    fn test_quiet_network_alerts() {
+        let found = OtherMonitorsConfig::default().detect(["gnome-usage"]);
+        let mut config = NotificationConfig::default();
+        let coexistence = OtherMonitorsConfig::default().decide(found.clone());
+        assert!(!coexistence.network_alerts_log_only);
+        coexistence.apply(&mut config);
+        assert_eq!(config, NotificationConfig::default());
+
+        let quiet = OtherMonitorsConfig {
+            quiet_network_alerts: true,
+            ..Default::default()
+        };
+        assert!(!quiet.decide(Vec::new()).network_alerts_log_only);
+        let coexistence = quiet.decide(found);
+        assert!(coexistence.network_alerts_log_only);
+        coexistence.apply(&mut config);
+        assert_eq!(config.channels, [ChannelKind::Desktop, ChannelKind::Log]);
+        let channels = |app| route(&config.routes, &Alert::new(app)).map(<[String]>::to_vec);
+        assert_eq!(channels("net:wlan0"), Some(vec!["log".to_string()]));
+        assert_eq!(channels("firefox"), None);
+    }
+}
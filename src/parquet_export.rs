@@ -0,0 +1,205 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_array::builder::{Date32Builder, StringBuilder, UInt32Builder, UInt64Builder};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use chrono::NaiveDate;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use thiserror::Error;
+
+use super::tracker::{DayUsage, UsageTracker};
+
+pub const BATCH_ROWS: usize = 8192;
+
+/// Schema of the Parquet export, one row per (day, app) in date then app
+/// order. Columns may be added at the end, but existing ones keep their
+/// name, type and meaning:
+///
+/// | column           | type     | meaning                                     |
+/// |------------------|----------|---------------------------------------------|
+/// | `date`           | `date32` | day in the reset timezone                   |
+/// | `app`            | `utf8`   | application name, as used by `dg reset`     |
+/// | `bytes_read`     | `uint64` | bytes read that day                         |
+/// | `bytes_written`  | `uint64` | bytes written that day                      |
+/// | `exceeded_count` | `uint32` | times the app went over its limit that day  |
+/// | `instance`       | `utf8`   | `instance_label` of the exporting machine   |
+pub fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("date", DataType::Date32, false),
+        Field::new("app", DataType::Utf8, false),
+        Field::new("bytes_read", DataType::UInt64, false),
+        Field::new("bytes_written", DataType::UInt64, false),
+        Field::new("exceeded_count", DataType::UInt32, false),
+        Field::new("instance", DataType::Utf8, false),
+    ]))
+}
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("Failed to build record batch: {0}")]
+    Arrow(#[from] ArrowError),
+    #[error("Failed to write Parquet: {0}")]
+    Parquet(#[from] ParquetError),
+}
+
+/// Writes the tracker's daily history as Parquet and returns the number of
+/// rows. Rows are built straight from the tracker in batches of
+/// `BATCH_ROWS`, so only one batch is held in Arrow form at a time.
+pub fn write_parquet<W: Write + Send>(
+    tracker: &UsageTracker,
+    instance: &str,
+    writer: W,
+) -> Result<usize, ExportError> {
+    write_batches(tracker, instance, writer, BATCH_ROWS)
+}
+
+fn write_batches<W: Write + Send>(
+    tracker: &UsageTracker,
+    instance: &str,
+    writer: W,
+    batch_rows: usize,
+) -> Result<usize, ExportError> {
+    let schema = schema();
+    let mut writer = ArrowWriter::try_new(writer, schema.clone(), None)?;
+    let mut batch = Batch::new(schema, batch_rows);
+    let mut rows = 0;
+    for (date, apps) in &tracker.days {
+        let mut apps: Vec<_> = apps.iter().collect();
+        apps.sort_by_key(|(app, _)| *app);
+        for (app, day) in apps {
+            batch.push(*date, app, day, instance);
+            rows += 1;
+            if batch.len == batch_rows {
+                writer.write(&batch.finish()?)?;
+            }
+        }
+    }
+    if batch.len > 0 {
+        writer.write(&batch.finish()?)?;
+    }
+    writer.close()?;
+    Ok(rows)
+}
+
+struct Batch {
+    schema: SchemaRef,
+    len: usize,
+    date: Date32Builder,
+    app: StringBuilder,
+    bytes_read: UInt64Builder,
+    bytes_written: UInt64Builder,
+    exceeded_count: UInt32Builder,
+    instance: StringBuilder,
+}
+
+impl Batch {
+    fn new(schema: SchemaRef, rows: usize) -> Self {
+        Self {
+            schema,
+            len: 0,
+            date: Date32Builder::with_capacity(rows),
+            app: StringBuilder::new(),
+            bytes_read: UInt64Builder::with_capacity(rows),
+            bytes_written: UInt64Builder::with_capacity(rows),
+            exceeded_count: UInt32Builder::with_capacity(rows),
+            instance: StringBuilder::new(),
+        }
+    }
+
+    fn push(&mut self, date: NaiveDate, app: &str, day: &DayUsage, instance: &str) {
+        self.len += 1;
+        self.date.append_value(epoch_days(date));
+        self.app.append_value(app);
+        self.bytes_read.append_value(day.read());
+        self.bytes_written.append_value(day.written);
+        self.exceeded_count.append_value(day.exceeded);
+        self.instance.append_value(instance);
+    }
+
+    fn finish(&mut self) -> Result<RecordBatch, ArrowError> {
+        self.len = 0;
+        RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(self.date.finish()),
+                Arc::new(self.app.finish()),
+                Arc::new(self.bytes_read.finish()),
+                Arc::new(self.bytes_written.finish()),
+                Arc::new(self.exceeded_count.finish()),
+                Arc::new(self.instance.finish()),
+            ],
+        )
+    }
+}
+
+fn epoch_days(date: NaiveDate) -> i32 {
+    (date - NaiveDate::default()).num_days() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Date32Type, UInt32Type, UInt64Type};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_parquet_roundtrip() {
+        let date = |d| NaiveDate::from_ymd_opt(2025, 6, d).unwrap();
+        let mut tracker = UsageTracker::default();
+        tracker.add_daily_usage("firefox", 300, 100, date(1));
+        tracker.add_daily_usage("curl", 50, 0, date(1));
+        tracker.add_daily_usage("firefox", 70, 70, date(2));
+        tracker.record_exceedance(
+            "firefox",
+            crate::tracker::ResetPeriod::Daily,
+            chrono::Utc::now(),
+            date(2),
+        );
+
+        let mut file = tempfile::tempfile().unwrap();
+        assert_eq!(
+            write_batches(&tracker, "office-nas", &mut file, 2).unwrap(),
+            3
+        );
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        assert_eq!(reader.schema(), &schema());
+        let mut rows = Vec::new();
+        for batch in reader.build().unwrap() {
+            let batch = batch.unwrap();
+            let column = |name| batch.column_by_name(name).unwrap();
+            for row in 0..batch.num_rows() {
+                rows.push((
+                    column("date").as_primitive::<Date32Type>().value(row),
+                    column("app").as_string::<i32>().value(row).to_string(),
+                    column("bytes_read").as_primitive::<UInt64Type>().value(row),
+                    column("bytes_written")
+                        .as_primitive::<UInt64Type>()
+                        .value(row),
+                    column("exceeded_count")
+                        .as_primitive::<UInt32Type>()
+                        .value(row),
+                ));
+                assert_eq!(
+                    column("instance").as_string::<i32>().value(row),
+                    "office-nas"
+                );
+            }
+        }
+
+        let day = |d| epoch_days(date(d));
+        assert_eq!(day(1), 20240);
+        assert_eq!(
+            rows,
+            [
+                (day(1), "curl".to_string(), 50, 0, 0),
+                (day(1), "firefox".to_string(), 200, 100, 0),
+                (day(2), "firefox".to_string(), 0, 70, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+
+pub const DATA_DIR_ENV: &str = "DATAGUARDIAN_DATA_DIR";
+pub const STATE_DIR_ENV: &str = "DATAGUARDIAN_STATE_DIR";
+pub const RUNTIME_DIR_ENV: &str = "DATAGUARDIAN_RUNTIME_DIR";
+pub const CONFIG_ENV: &str = "DATAGUARDIAN_CONFIG";
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+pub const SYSTEM_DATA_DIR: &str = "/var/lib/dataguardian";
+pub const SYSTEM_CONFIG_DIR: &str = "/etc/dataguardian";
+pub const SYSTEM_RUNTIME_DIR: &str = "/run/dataguardian";
+/// Left in the old data directory once its files were copied to the state
+/// directory, so they are not copied again.
+pub const MIGRATED_MARKER: &str = "MOVED_TO_STATE_DIR";
+
+const PROBE_FILE_NAME: &str = ".write-test";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSource {
+    Flag,
+    Env,
+    Settings,
+    ProjectDirs,
+    System,
+}
+
+impl PathSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Flag => "command line",
+            Self::Env => "environment",
+            Self::Settings => "settings",
+            Self::ProjectDirs => "user directories",
+            Self::System => "system default",
+        }
+    }
+
+    /// Whether the user asked for this path, so a missing file is an error
+    /// rather than a reason to fall back to defaults.
+    pub fn is_explicit(&self) -> bool {
+        matches!(self, Self::Flag | Self::Env | Self::Settings)
+    }
+}
+
+impl fmt::Display for PathSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedPath {
+    pub path: PathBuf,
+    pub source: PathSource,
+}
+
+/// Picks the state directory, which holds usage data and history: `flag`,
+/// then `DATAGUARDIAN_STATE_DIR` or `DATAGUARDIAN_DATA_DIR`, then the
+/// `data_dir` setting, then the per-user state directory, and finally
+/// `/var/lib/dataguardian` when running as root.
+pub fn state_dir(flag: Option<&Path>, setting: Option<&Path>) -> Option<ResolvedPath> {
+    first([
+        (flag.map(Path::to_path_buf), PathSource::Flag),
+        (
+            env_path(STATE_DIR_ENV).or_else(|| env_path(DATA_DIR_ENV)),
+            PathSource::Env,
+        ),
+        (setting.map(Path::to_path_buf), PathSource::Settings),
+        (
+            project_dirs().map(|dirs| user_state_dir(&dirs)),
+            PathSource::ProjectDirs,
+        ),
+        (
+            is_root().then(|| PathBuf::from(SYSTEM_DATA_DIR)),
+            PathSource::System,
+        ),
+    ])
+}
+
+/// Picks the directory for the control socket: `DATAGUARDIAN_RUNTIME_DIR`,
+/// then the `runtime_dir` setting, then `state` when it was chosen
+/// explicitly, then the per-user runtime directory, then
+/// `/run/dataguardian` when running as root, and finally `state`.
+pub fn runtime_dir(setting: Option<&Path>, state: &ResolvedPath) -> ResolvedPath {
+    first([
+        (env_path(RUNTIME_DIR_ENV), PathSource::Env),
+        (setting.map(Path::to_path_buf), PathSource::Settings),
+    ])
+    .or_else(|| state.source.is_explicit().then(|| state.clone()))
+    .or_else(|| {
+        first([
+            (
+                project_dirs().and_then(|dirs| dirs.runtime_dir().map(Path::to_path_buf)),
+                PathSource::ProjectDirs,
+            ),
+            (
+                (cfg!(unix) && is_root()).then(|| PathBuf::from(SYSTEM_RUNTIME_DIR)),
+                PathSource::System,
+            ),
+        ])
+    })
+    .unwrap_or_else(|| state.clone())
+}
+
+/// Where versions that kept everything in the per-user data directory
+/// stored usage data.
+pub fn legacy_data_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+/// Copies `files` that exist in `from` and not yet in `to`, then leaves
+/// [`MIGRATED_MARKER`] in `from`. Nothing happens when `from` is `to`, was
+/// migrated before or holds none of `files`. Returns the names copied.
+pub fn migrate(from: &Path, to: &Path, files: &[&str]) -> io::Result<Vec<String>> {
+    let marker = from.join(MIGRATED_MARKER);
+    if from == to || marker.exists() || !files.iter().any(|file| from.join(file).is_file()) {
+        return Ok(Vec::new());
+    }
+    fs::create_dir_all(to)?;
+    let mut copied = Vec::new();
+    for file in files {
+        let (source, target) = (from.join(file), to.join(file));
+        if !source.is_file() || target.exists() {
+            continue;
+        }
+        // Copied under another name first, so a crash never leaves half a
+        // file where the service looks for it.
+        let partial = to.join(format!("{}.migrating", file));
+        fs::copy(&source, &partial)?;
+        fs::rename(&partial, &target)?;
+        copied.push(file.to_string());
+    }
+    fs::write(
+        &marker,
+        format!(
+            "Data Guardian now keeps these files in {}. They were copied there and can be deleted.\n",
+            to.display()
+        ),
+    )?;
+    Ok(copied)
+}
+
+/// Picks the configuration file: `flag`, then `DATAGUARDIAN_CONFIG`, then the
+/// per-user config directory, and finally `/etc/dataguardian/config.toml`
+/// when running as root.
+pub fn config_path(flag: Option<&Path>) -> Option<ResolvedPath> {
+    first([
+        (flag.map(Path::to_path_buf), PathSource::Flag),
+        (env_path(CONFIG_ENV), PathSource::Env),
+        (
+            project_dirs().map(|dirs| dirs.config_dir().join(CONFIG_FILE_NAME)),
+            PathSource::ProjectDirs,
+        ),
+        (
+            is_root().then(|| Path::new(SYSTEM_CONFIG_DIR).join(CONFIG_FILE_NAME)),
+            PathSource::System,
+        ),
+    ])
+}
+
+/// Creates `dir` if needed and checks that files can be written to it.
+pub fn ensure_writable(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(PROBE_FILE_NAME);
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+pub fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("com", "DataGuardian", "DataGuardian")
+}
+
+/// `$XDG_STATE_HOME` on Linux; other platforms have no state directory, so
+/// the local data directory stands in.
+fn user_state_dir(dirs: &ProjectDirs) -> PathBuf {
+    dirs.state_dir()
+        .unwrap_or_else(|| dirs.data_local_dir())
+        .to_path_buf()
+}
+
+fn first<const N: usize>(candidates: [(Option<PathBuf>, PathSource); N]) -> Option<ResolvedPath> {
+    candidates
+        .into_iter()
+        .find_map(|(path, source)| path.map(|path| ResolvedPath { path, source }))
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(OsString::into)
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    nix::unistd::Uid::effective().is_root()
+}
+
+#[cfg(not(unix))]
+fn is_root() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_candidate_wins() {
+        let resolved = first([
+            (None, PathSource::Flag),
+            (Some(PathBuf::from("/env")), PathSource::Env),
+            (Some(PathBuf::from("/settings")), PathSource::Settings),
+        ]);
+        assert_eq!(
+            resolved,
+            Some(ResolvedPath {
+                path: PathBuf::from("/env"),
+                source: PathSource::Env,
+            })
+        );
+        assert_eq!(first([(None, PathSource::System)]), None);
+    }
+
+    #[test]
+    fn test_flag_overrides_everything() {
+        let flag = Path::new("/flag");
+        let resolved = state_dir(Some(flag), Some(Path::new("/settings"))).unwrap();
+        assert_eq!(resolved.path, flag);
+        assert_eq!(resolved.source, PathSource::Flag);
+        assert!(resolved.source.is_explicit());
+
+        let resolved = config_path(Some(flag)).unwrap();
+        assert_eq!(resolved.source, PathSource::Flag);
+        assert!(!PathSource::ProjectDirs.is_explicit());
+    }
+
+    #[test]
+    fn test_runtime_dir_follows_explicit_state_dir() {
+        let state = ResolvedPath {
+            path: PathBuf::from("/srv/dg"),
+            source: PathSource::Settings,
+        };
+        assert_eq!(runtime_dir(None, &state), state);
+
+        let runtime = runtime_dir(Some(Path::new("/run/dg")), &state);
+        assert_eq!(runtime.path, Path::new("/run/dg"));
+        assert_eq!(runtime.source, PathSource::Settings);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let root = tempfile::tempdir().unwrap();
+        let (old, new) = (root.path().join("share"), root.path().join("state"));
+        fs::create_dir_all(&old).unwrap();
+        fs::write(old.join("usage.dat"), b"old usage").unwrap();
+        fs::write(old.join("usage.dict"), b"dictionary").unwrap();
+        let files = ["usage.dat", "history.dat", "usage.dict"];
+
+        assert_eq!(
+            migrate(&old, &new, &files).unwrap(),
+            ["usage.dat", "usage.dict"]
+        );
+        assert_eq!(fs::read(new.join("usage.dat")).unwrap(), b"old usage");
+        assert!(!new.join("history.dat").exists());
+        assert!(!new.join("usage.dat.migrating").exists());
+        // The old copy stays, marked so it is not copied over newer data.
+        assert!(old.join("usage.dat").exists());
+        assert!(old.join(MIGRATED_MARKER).exists());
+
+        fs::write(new.join("usage.dat"), b"new usage").unwrap();
+        assert!(migrate(&old, &new, &files).unwrap().is_empty());
+        assert_eq!(fs::read(new.join("usage.dat")).unwrap(), b"new usage");
+        assert!(migrate(&new, &new, &files).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_ensure_writable() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("nested").join("data");
+        ensure_writable(&dir).unwrap();
+        assert!(dir.is_dir());
+        assert!(!dir.join(PROBE_FILE_NAME).exists());
+
+        let file = root.path().join("file");
+        fs::write(&file, b"").unwrap();
+        assert!(ensure_writable(&file.join("data")).is_err());
+    }
+}
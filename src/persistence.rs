@@ -0,0 +1,618 @@
+//! Reading and writing the usage data file exactly as the service does: the
+//! same directories, the same compressed format, and a new file renamed over
+//! the old one so a crash never leaves half a file behind.
+//!
+//! The functions here are async and run the file work on tokio's blocking
+//! pool; [`blocking`] has the same functions for callers without a runtime.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use thiserror::Error;
+
+use super::alerts_log;
+use super::compression::{self, CompressionError};
+use super::crash;
+use super::paths;
+use super::settings::Settings;
+use super::tracker::{DataIncident, UsageTracker};
+
+pub const DATA_FILE: &str = "usage.dat";
+pub const HISTORY_FILE: &str = "history.dat";
+/// Writes to the data file tried before [`blocking::write_with_fallback`]
+/// gives up on it.
+pub const SAVE_ATTEMPTS: u32 = 3;
+/// Wait after the first failed write, doubled after each one.
+pub const SAVE_RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+#[derive(Error, Debug)]
+pub enum PersistenceError {
+    #[error(
+        "No state directory available; pass --data-dir or set {}",
+        paths::STATE_DIR_ENV
+    )]
+    NoStateDir,
+    #[error("Directory {} is not writable; pass --data-dir or set {}: {source}", path.display(), paths::STATE_DIR_ENV)]
+    NotWritable {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to compress usage data: {0}")]
+    Compression(#[from] CompressionError),
+    #[error("Failed to {action} {}: {source}", path.display())]
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl PersistenceError {
+    fn io(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> Self {
+        let path = path.to_path_buf();
+        move |source| Self::Io {
+            action,
+            path,
+            source,
+        }
+    }
+}
+
+/// Where usage data and the files kept next to it live.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersistencePaths {
+    pub state_dir: PathBuf,
+    /// Used when the state directory cannot be written to, and read back on
+    /// the next load.
+    pub fallback_dir: PathBuf,
+}
+
+impl PersistencePaths {
+    pub fn new(state_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            state_dir: state_dir.into(),
+            fallback_dir: std::env::temp_dir().join("DataGuardian"),
+        }
+    }
+
+    /// The directories the service would use with `settings` and no command
+    /// line flags. Fails with [`PersistenceError::NoStateDir`] when there is
+    /// no home directory to put them in and no `DATAGUARDIAN_STATE_DIR` or
+    /// `data_dir` names one.
+    pub fn resolve(settings: &Settings) -> Result<Self, PersistenceError> {
+        paths::state_dir(None, settings.data_dir.as_deref())
+            .map(|dir| Self::new(dir.path))
+            .ok_or(PersistenceError::NoStateDir)
+    }
+
+    pub fn with_fallback_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.fallback_dir = dir.into();
+        self
+    }
+
+    pub fn data_path(&self) -> PathBuf {
+        self.state_dir.join(DATA_FILE)
+    }
+
+    pub fn fallback_path(&self) -> PathBuf {
+        self.fallback_dir.join(DATA_FILE)
+    }
+
+    /// The data file as it was before the last save.
+    pub fn backup_path(&self) -> PathBuf {
+        self.state_dir.join(format!("{}.bak", DATA_FILE))
+    }
+
+    pub fn history_path(&self) -> PathBuf {
+        self.state_dir.join(HISTORY_FILE)
+    }
+
+    pub fn dictionary_path(&self) -> PathBuf {
+        self.state_dir.join(compression::DICTIONARY_FILE)
+    }
+
+    pub fn alerts_log_path(&self) -> PathBuf {
+        self.state_dir.join(alerts_log::ALERTS_LOG_FILE)
+    }
+
+    pub fn crash_marker_path(&self) -> PathBuf {
+        self.state_dir.join(crash::CRASH_MARKER_FILE)
+    }
+}
+
+/// Creates `dir` if needed and checks that files can be written to it.
+pub fn ensure_writable(dir: &Path) -> Result<(), PersistenceError> {
+    paths::ensure_writable(dir).map_err(|source| PersistenceError::NotWritable {
+        path: dir.to_path_buf(),
+        source,
+    })
+}
+
+/// A data file that exists but could not be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreadableFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub error: String,
+}
+
+#[derive(Debug, Default)]
+pub struct LoadedUsage {
+    pub tracker: Option<UsageTracker>,
+    /// The file `tracker` was read from.
+    pub source: Option<PathBuf>,
+    pub unreadable: Vec<UnreadableFile>,
+}
+
+#[cfg(feature = "monitor")]
+async fn unblock<T, F>(paths: &PersistencePaths, f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce(&PersistencePaths) -> T + Send + 'static,
+{
+    let paths = paths.clone();
+    tokio::task::spawn_blocking(move || f(&paths))
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+}
+
+/// See [`blocking::read_usage`].
+#[cfg(feature = "monitor")]
+pub async fn read_usage(paths: &PersistencePaths) -> LoadedUsage {
+    unblock(paths, blocking::read_usage).await
+}
+
+/// See [`blocking::load`].
+#[cfg(feature = "monitor")]
+pub async fn load(paths: &PersistencePaths) -> Option<UsageTracker> {
+    unblock(paths, blocking::load).await
+}
+
+/// See [`blocking::recover`].
+#[cfg(feature = "monitor")]
+pub async fn recover(paths: &PersistencePaths) -> (UsageTracker, Vec<DataIncident>) {
+    unblock(paths, blocking::recover).await
+}
+
+/// See [`blocking::load_dictionary`].
+#[cfg(feature = "monitor")]
+pub async fn load_dictionary(paths: &PersistencePaths) -> Option<u32> {
+    unblock(paths, blocking::load_dictionary).await
+}
+
+/// See [`blocking::save`].
+#[cfg(feature = "monitor")]
+pub async fn save(paths: &PersistencePaths, data: &UsageTracker) -> Result<u64, PersistenceError> {
+    let compressed = data.to_compressed()?;
+    write(paths, &compressed).await?;
+    Ok(compressed.len() as u64)
+}
+
+/// See [`blocking::write`].
+#[cfg(feature = "monitor")]
+pub async fn write(paths: &PersistencePaths, compressed: &[u8]) -> Result<(), PersistenceError> {
+    let compressed = compressed.to_vec();
+    unblock(paths, move |paths| blocking::write(paths, &compressed)).await
+}
+
+/// See [`blocking::write_with_fallback`].
+#[cfg(feature = "monitor")]
+pub async fn write_with_fallback(
+    paths: &PersistencePaths,
+    compressed: &[u8],
+) -> Result<PathBuf, PersistenceError> {
+    let compressed = compressed.to_vec();
+    unblock(paths, move |paths| {
+        blocking::write_with_fallback(paths, &compressed)
+    })
+    .await
+}
+
+pub mod blocking {
+    use std::fs;
+
+    use chrono::Utc;
+    use tracing::{debug, error, warn};
+
+    use super::*;
+    use crate::compression::Dictionary;
+    use crate::version;
+
+    /// Reads the data file, or the fallback copy when it is newer. The backup
+    /// from before the last save is only used when neither can be decoded, so
+    /// a deleted data file still starts usage over.
+    pub fn read_usage(paths: &PersistencePaths) -> LoadedUsage {
+        load_dictionary(paths);
+        let mut loaded = LoadedUsage::default();
+        let primary = read_into(&mut loaded, paths.data_path());
+        let fallback = read_into(&mut loaded, paths.fallback_path());
+        let chosen = match (primary, fallback) {
+            (primary, Some(fallback))
+                if primary
+                    .as_ref()
+                    .is_none_or(|(_, primary_saved, _)| fallback.1 >= *primary_saved) =>
+            {
+                warn!(path = ?fallback.2, "Recovered usage data saved to the fallback location");
+                Some(fallback)
+            }
+            (primary, _) => primary,
+        };
+        let chosen = match chosen {
+            None if !loaded.unreadable.is_empty() => read_into(&mut loaded, paths.backup_path()),
+            chosen => chosen,
+        };
+        if let Some((tracker, _, path)) = chosen {
+            loaded.tracker = Some(tracker);
+            loaded.source = Some(path);
+        }
+        loaded
+    }
+
+    fn read_into(
+        loaded: &mut LoadedUsage,
+        path: PathBuf,
+    ) -> Option<(UsageTracker, SystemTime, PathBuf)> {
+        match read_data_file(&path) {
+            Ok(data) => data.map(|(tracker, saved)| (tracker, saved, path)),
+            Err(file) => {
+                loaded.unreadable.push(file);
+                None
+            }
+        }
+    }
+
+    /// The saved usage, or `None` when there is none. Files that cannot be
+    /// decoded are logged and left in place.
+    pub fn load(paths: &PersistencePaths) -> Option<UsageTracker> {
+        let loaded = read_usage(paths);
+        for file in &loaded.unreadable {
+            error!(error = %file.error, path = ?file.path, "Failed to decompress persisted data");
+        }
+        loaded.tracker
+    }
+
+    /// Loads usage for the service. Unreadable data files are renamed aside
+    /// and recorded as incidents in the returned tracker; the new ones are
+    /// returned as well so they can be reported once.
+    pub fn recover(paths: &PersistencePaths) -> (UsageTracker, Vec<DataIncident>) {
+        let loaded = read_usage(paths);
+        let mut tracker = loaded.tracker.unwrap_or_default();
+        let now = Utc::now();
+        let mut incidents = Vec::new();
+        for file in loaded.unreadable {
+            let mut quarantine = file.path.clone().into_os_string();
+            quarantine.push(format!(".corrupt-{}", now.format("%Y%m%dT%H%M%SZ")));
+            let quarantine = PathBuf::from(quarantine);
+            let quarantined_to = match fs::rename(&file.path, &quarantine) {
+                Ok(()) => Some(quarantine.display().to_string()),
+                Err(e) => {
+                    warn!(error = %e, path = ?file.path, "Failed to move unreadable data file aside");
+                    None
+                }
+            };
+            let incident = DataIncident {
+                time: now,
+                path: file.path.display().to_string(),
+                quarantined_to,
+                size_bytes: file.size,
+                error: file.error,
+                restored_from: loaded
+                    .source
+                    .as_ref()
+                    .map(|path| path.display().to_string()),
+            };
+            error!(
+                path = %incident.path,
+                size_bytes = incident.size_bytes,
+                error = %incident.error,
+                quarantined_to = ?incident.quarantined_to,
+                restored_from = ?incident.restored_from,
+                "Usage data file could not be read"
+            );
+            tracker.record_incident(incident.clone());
+            incidents.push(incident);
+        }
+        (tracker, incidents)
+    }
+
+    /// Registers the trained compression dictionary, if there is one, so data
+    /// files compressed with it can be read.
+    pub fn load_dictionary(paths: &PersistencePaths) -> Option<u32> {
+        let path = paths.dictionary_path();
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                warn!(error = %e, ?path, "Failed to read the compression dictionary");
+                return None;
+            }
+        };
+        match Dictionary::new(bytes) {
+            Ok(dictionary) => Some(compression::register_dictionary(dictionary)),
+            Err(e) => {
+                warn!(error = %e, ?path, "Ignoring invalid compression dictionary");
+                None
+            }
+        }
+    }
+
+    fn read_data_file(
+        data_path: &Path,
+    ) -> Result<Option<(UsageTracker, SystemTime)>, UnreadableFile> {
+        if !data_path.exists() {
+            debug!(?data_path, "No existing usage data found");
+            return Ok(None);
+        }
+
+        debug!(?data_path, "Loading persisted usage data");
+        let saved = fs::metadata(data_path)
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        match fs::read(data_path) {
+            Ok(contents) => {
+                debug!(size = contents.len(), "Read persisted data file");
+                match UsageTracker::from_compressed(&contents) {
+                    Ok(data) => {
+                        debug!(entries = data.apps.len(), "Successfully loaded usage data");
+                        if let Some(written_by) = &data.state.written_by
+                            && version::is_newer(written_by)
+                        {
+                            warn!(
+                                %written_by,
+                                version = version::VERSION,
+                                ?data_path,
+                                "Usage data was written by a newer Data Guardian; anything it added is dropped on the next save"
+                            );
+                        }
+                        Ok(Some((data, saved)))
+                    }
+                    Err(e) => Err(UnreadableFile {
+                        path: data_path.to_path_buf(),
+                        size: contents.len() as u64,
+                        error: e.to_string(),
+                    }),
+                }
+            }
+            Err(e) => {
+                error!(error = %e, ?data_path, "Failed to read persisted data file");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Compresses `data` with the default settings and writes it. Returns the
+    /// compressed size.
+    pub fn save(paths: &PersistencePaths, data: &UsageTracker) -> Result<u64, PersistenceError> {
+        let compressed = data.to_compressed()?;
+        write(paths, &compressed)?;
+        Ok(compressed.len() as u64)
+    }
+
+    /// Keeps the previous data file as a backup, then replaces it in one
+    /// rename so a failed write never leaves a truncated data file behind.
+    pub fn write(paths: &PersistencePaths, compressed: &[u8]) -> Result<(), PersistenceError> {
+        let data_path = paths.data_path();
+        if data_path.exists()
+            && let Err(e) = fs::copy(&data_path, paths.backup_path())
+        {
+            warn!(error = %e, ?data_path, "Failed to keep a backup of the usage data");
+        }
+        let mut new_path = data_path.clone().into_os_string();
+        new_path.push(".new");
+        let new_path = PathBuf::from(new_path);
+        write_data_file(&paths.state_dir, &new_path, compressed)?;
+        fs::rename(&new_path, &data_path)
+            .map_err(PersistenceError::io("replace usage data file", &data_path))?;
+        let fallback_path = paths.fallback_path();
+        if fallback_path.exists() {
+            debug!(
+                ?fallback_path,
+                "Removing usage data superseded by the data file"
+            );
+            let _ = fs::remove_file(&fallback_path);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_data_file(
+        dir: &Path,
+        data_path: &Path,
+        compressed: &[u8],
+    ) -> Result<(), PersistenceError> {
+        if !dir.exists() {
+            debug!(?dir, "Creating data directory");
+            fs::create_dir_all(dir).map_err(PersistenceError::io("create data directory", dir))?;
+        }
+
+        debug!(?data_path, size = compressed.len(), "Saving usage data");
+        fs::write(data_path, compressed)
+            .map_err(PersistenceError::io("write usage data file", data_path))?;
+
+        debug!(?data_path, "Successfully saved usage data");
+        Ok(())
+    }
+
+    /// Tries [`write`] up to [`SAVE_ATTEMPTS`] times, then writes to the
+    /// fallback directory instead. Returns the file that was written.
+    pub fn write_with_fallback(
+        paths: &PersistencePaths,
+        compressed: &[u8],
+    ) -> Result<PathBuf, PersistenceError> {
+        let data_path = paths.data_path();
+        let mut backoff = SAVE_RETRY_BACKOFF;
+        for attempt in 1..=SAVE_ATTEMPTS {
+            match write(paths, compressed) {
+                Ok(()) => return Ok(data_path),
+                Err(e) if attempt < SAVE_ATTEMPTS => {
+                    warn!(error = %e, attempt, ?data_path, "Failed to save usage data, retrying");
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                }
+                Err(e) => error!(error = %e, ?data_path, "Failed to save usage data"),
+            }
+        }
+
+        let fallback_path = paths.fallback_path();
+        write_data_file(&paths.fallback_dir, &fallback_path, compressed)?;
+        warn!(
+            path = ?fallback_path,
+            "Saved usage data to the fallback location; it is recovered on the next start"
+        );
+        Ok(fallback_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_paths(root: &Path) -> PersistencePaths {
+        PersistencePaths::new(root.join("state")).with_fallback_dir(root.join("fallback"))
+    }
+
+    fn tracker(bytes: u64) -> UsageTracker {
+        let mut tracker = UsageTracker::default();
+        tracker.add_usage("firefox", bytes);
+        tracker
+    }
+
+    #[test]
+    fn test_blocking_roundtrip() {
+        let root = tempfile::tempdir().unwrap();
+        let paths = temp_paths(root.path());
+        assert_eq!(blocking::load(&paths), None);
+
+        let size = blocking::save(&paths, &tracker(100)).unwrap();
+        assert_eq!(std::fs::metadata(paths.data_path()).unwrap().len(), size);
+        assert!(!paths.backup_path().exists());
+        blocking::save(&paths, &tracker(101)).unwrap();
+        assert_eq!(blocking::load(&paths), Some(tracker(101)));
+        assert_eq!(
+            UsageTracker::from_compressed(&std::fs::read(paths.backup_path()).unwrap()).unwrap(),
+            tracker(100)
+        );
+    }
+
+    #[test]
+    fn test_unreadable_data_is_moved_aside() {
+        let root = tempfile::tempdir().unwrap();
+        let paths = temp_paths(root.path());
+        blocking::save(&paths, &tracker(100)).unwrap();
+        blocking::save(&paths, &tracker(101)).unwrap();
+        std::fs::write(paths.data_path(), b"not gzip").unwrap();
+
+        let (recovered, incidents) = blocking::recover(&paths);
+        assert_eq!(recovered.usage()["firefox"], 100);
+        assert_eq!(incidents.len(), 1);
+        let incident = &incidents[0];
+        assert_eq!(incident.size_bytes, 8);
+        assert_eq!(
+            incident.restored_from.as_deref(),
+            Some(paths.backup_path().to_str().unwrap())
+        );
+        assert!(!paths.data_path().exists());
+        let quarantined = incident.quarantined_to.as_ref().unwrap();
+        assert_eq!(std::fs::read(quarantined).unwrap(), b"not gzip");
+        assert_eq!(recovered.incidents, incidents);
+
+        std::fs::write(paths.backup_path(), b"").unwrap();
+        std::fs::write(paths.data_path(), b"also not gzip").unwrap();
+        let (recovered, incidents) = blocking::recover(&paths);
+        assert!(recovered.apps.is_empty());
+        assert_eq!(incidents.len(), 2);
+        assert!(
+            incidents
+                .iter()
+                .all(|incident| incident.restored_from.is_none())
+        );
+
+        let (_, incidents) = blocking::recover(&paths);
+        assert!(incidents.is_empty());
+    }
+
+    #[test]
+    fn test_blocking_save_falls_back() {
+        let root = tempfile::tempdir().unwrap();
+        let paths = temp_paths(root.path());
+        // A file where the state directory should be makes every write to it
+        // fail, like a home directory that is missing or read-only.
+        std::fs::write(&paths.state_dir, b"").unwrap();
+        let compressed = tracker(100).to_compressed().unwrap();
+
+        let path = blocking::write_with_fallback(&paths, &compressed).unwrap();
+        assert_eq!(path, paths.fallback_path());
+        assert!(matches!(
+            blocking::write(&paths, &compressed),
+            Err(PersistenceError::Io { .. })
+        ));
+        assert_eq!(blocking::load(&paths), Some(tracker(100)));
+
+        std::fs::remove_file(&paths.state_dir).unwrap();
+        let compressed = tracker(101).to_compressed().unwrap();
+        let path = blocking::write_with_fallback(&paths, &compressed).unwrap();
+        assert_eq!(path, paths.data_path());
+        assert!(!paths.fallback_path().exists());
+        assert_eq!(blocking::load(&paths), Some(tracker(101)));
+    }
+
+    #[cfg(feature = "monitor")]
+    #[tokio::test]
+    async fn test_async_save_falls_back() {
+        let root = tempfile::tempdir().unwrap();
+        let paths = temp_paths(root.path());
+        std::fs::write(&paths.state_dir, b"").unwrap();
+
+        let compressed = tracker(100).to_compressed().unwrap();
+        let path = write_with_fallback(&paths, &compressed).await.unwrap();
+        assert_eq!(path, paths.fallback_path());
+        assert!(save(&paths, &tracker(100)).await.is_err());
+        assert_eq!(load(&paths).await, Some(tracker(100)));
+
+        std::fs::remove_file(&paths.state_dir).unwrap();
+        save(&paths, &tracker(101)).await.unwrap();
+        assert!(!paths.fallback_path().exists());
+        assert_eq!(load(&paths).await, Some(tracker(101)));
+        assert_eq!(recover(&paths).await, (tracker(101), Vec::new()));
+    }
+
+    #[test]
+    fn test_error_variants() {
+        let root = tempfile::tempdir().unwrap();
+        let paths = temp_paths(root.path());
+        std::fs::write(&paths.state_dir, b"").unwrap();
+
+        let error = ensure_writable(&paths.state_dir).unwrap_err();
+        assert!(
+            matches!(&error, PersistenceError::NotWritable { path, .. } if *path == paths.state_dir)
+        );
+        match blocking::save(&paths, &tracker(1)).unwrap_err() {
+            PersistenceError::Io { action, path, .. } => {
+                assert_eq!(action, "write usage data file");
+                assert_eq!(path, paths.state_dir.join("usage.dat.new"));
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+        assert!(
+            PersistenceError::NoStateDir
+                .to_string()
+                .contains(paths::STATE_DIR_ENV)
+        );
+    }
+
+    #[test]
+    fn test_resolve_uses_data_dir_setting() {
+        let settings = Settings {
+            data_dir: Some(PathBuf::from("/srv/dg")),
+            ..Settings::default()
+        };
+        let paths = PersistencePaths::resolve(&settings).unwrap();
+        assert_eq!(paths.data_path(), Path::new("/srv/dg").join(DATA_FILE));
+        assert_eq!(
+            paths.fallback_dir,
+            std::env::temp_dir().join("DataGuardian")
+        );
+    }
+}
@@ -0,0 +1,578 @@
+//! Recorded process snapshots, written by `dg record` and fed through the
+//! scan's counting and alert checks by `dg replay`, so usage reported from
+//! another machine can be reproduced exactly.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sysinfo::Pid;
+use thiserror::Error;
+
+use super::clock::{Clock, MockClock};
+use super::compression::{self, CompressionError};
+use super::monitor::{self, CounterKey, Counters, ProcessData, Sampler};
+use super::notification::{Alert, AlertKind, DEFAULT_APP_NAME};
+use super::settings::Settings;
+use super::tracker::UsageTracker;
+
+/// Bumped when `Trace` changes in a way older versions cannot read.
+pub const TRACE_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum TraceError {
+    #[error("Failed to {action} {}: {source}", path.display())]
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to compress trace: {0}")]
+    Compression(#[from] CompressionError),
+    #[error("Trace version {0} is not supported; this build reads version {TRACE_VERSION}")]
+    Version(u32),
+}
+
+/// The key of a counter, as `CounterKey` but serializable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum TraceKey {
+    Process { pid: u32, start_time: u64 },
+    Cgroup { path: PathBuf },
+    Disk { name: String },
+    Interface { name: String },
+}
+
+impl From<&CounterKey> for TraceKey {
+    fn from(key: &CounterKey) -> Self {
+        match key {
+            CounterKey::Process(pid, start_time) => Self::Process {
+                pid: pid.as_u32(),
+                start_time: *start_time,
+            },
+            CounterKey::Cgroup(path) => Self::Cgroup { path: path.clone() },
+            CounterKey::Disk(name) => Self::Disk { name: name.clone() },
+            CounterKey::Interface(name) => Self::Interface { name: name.clone() },
+        }
+    }
+}
+
+impl From<&TraceKey> for CounterKey {
+    fn from(key: &TraceKey) -> Self {
+        match key {
+            TraceKey::Process { pid, start_time } => {
+                Self::Process(Pid::from_u32(*pid), *start_time)
+            }
+            TraceKey::Cgroup { path } => Self::Cgroup(path.clone()),
+            TraceKey::Disk { name } => Self::Disk(name.clone()),
+            TraceKey::Interface { name } => Self::Interface(name.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceCounter {
+    pub key: TraceKey,
+    pub name: String,
+    pub bytes: u64,
+    #[serde(default)]
+    pub written_bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_time_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_bytes: Option<u64>,
+}
+
+/// One snapshot and when it was taken.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frame {
+    pub time: DateTime<Utc>,
+    pub counters: Vec<TraceCounter>,
+}
+
+impl Frame {
+    pub fn new(time: DateTime<Utc>, processes: &ProcessData) -> Self {
+        let counters = processes
+            .iter()
+            .map(|(key, counters)| TraceCounter {
+                key: key.into(),
+                name: counters.name.clone(),
+                bytes: counters.bytes,
+                written_bytes: counters.written_bytes,
+                cpu_time_ms: counters.cpu_time_ms,
+                memory_bytes: counters.memory_bytes,
+            })
+            .collect();
+        Self { time, counters }
+    }
+
+    pub fn processes(&self) -> ProcessData {
+        self.counters
+            .iter()
+            .map(|counter| {
+                (
+                    CounterKey::from(&counter.key),
+                    Counters {
+                        name: counter.name.clone(),
+                        bytes: counter.bytes,
+                        written_bytes: counter.written_bytes,
+                        cpu_time_ms: counter.cpu_time_ms,
+                        memory_bytes: counter.memory_bytes,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// Snapshots in the order they were taken, stored like the data file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trace {
+    pub version: u32,
+    /// The version of Data Guardian that recorded the trace.
+    pub recorded_by: String,
+    pub frames: Vec<Frame>,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self {
+            version: TRACE_VERSION,
+            recorded_by: env!("CARGO_PKG_VERSION").to_string(),
+            frames: Vec::new(),
+        }
+    }
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, time: DateTime<Utc>, processes: &ProcessData) {
+        self.frames.push(Frame::new(time, processes));
+    }
+
+    pub fn read(path: &Path) -> Result<Self, TraceError> {
+        let data = std::fs::read(path).map_err(|source| TraceError::Io {
+            action: "read trace",
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let trace: Self = compression::decompress_json(&data)?;
+        if trace.version != TRACE_VERSION {
+            return Err(TraceError::Version(trace.version));
+        }
+        Ok(trace)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<u64, TraceError> {
+        let len_hint: usize = self.frames.iter().map(|frame| frame.counters.len()).sum();
+        let data = compression::compress_json(self, len_hint * 64)?;
+        std::fs::write(path, &data).map_err(|source| TraceError::Io {
+            action: "write trace",
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(data.len() as u64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The service would have sent the alert, unless on cooldown or snoozed.
+    Alert,
+    /// Over the limit, but already alerted at this multiple this period.
+    AlreadyAlerted,
+    /// Held back during the new application's grace period.
+    Grace,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decision {
+    pub app: String,
+    pub kind: AlertKind,
+    pub outcome: Outcome,
+    pub usage: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+impl Decision {
+    fn new(alert: &Alert, outcome: Outcome) -> Self {
+        Self {
+            app: alert.app.clone(),
+            kind: alert.kind,
+            outcome,
+            usage: alert.usage,
+            limit: alert.limit,
+        }
+    }
+}
+
+/// What one frame of a trace changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Step {
+    pub time: DateTime<Utc>,
+    pub processes: usize,
+    /// Usage since the previous frame.
+    pub deltas: BTreeMap<String, u64>,
+    /// Deltas above `max_bytes_per_second`, left out of `deltas`.
+    pub rejected: BTreeMap<String, u64>,
+    /// Usage this period of the applications in `deltas`.
+    pub totals: BTreeMap<String, u64>,
+    pub decisions: Vec<Decision>,
+}
+
+/// Counts recorded frames as the service counts its scans, with the clock
+/// following the frames' times. Decisions are made before cooldowns,
+/// snoozes and notification channels, which replaying leaves out.
+#[derive(Debug)]
+pub struct Replayer {
+    settings: Settings,
+    sampler: Sampler,
+    tracker: UsageTracker,
+    clock: MockClock,
+    last: Option<DateTime<Utc>>,
+}
+
+impl Replayer {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            sampler: Sampler::new().with_max_rate(settings.max_bytes_per_second),
+            settings,
+            tracker: UsageTracker::default(),
+            clock: MockClock::new(),
+            last: None,
+        }
+    }
+
+    pub fn tracker(&self) -> &UsageTracker {
+        &self.tracker
+    }
+
+    pub fn step(&mut self, frame: &Frame) -> Step {
+        let Self {
+            settings,
+            sampler,
+            tracker,
+            clock,
+            last,
+        } = self;
+        if let Some(last) = *last {
+            clock.advance((frame.time - last).to_std().unwrap_or(Duration::ZERO));
+        }
+        *last = Some(frame.time);
+        let now = frame.time;
+
+        let monitor::UsageDelta {
+            usage,
+            written,
+            rejected,
+            resources,
+        } = sampler.advance_at(frame.processes(), clock.now());
+        let (usage, _) = tracker.bound_apps(usage, settings.max_tracked_apps, |total, delta| {
+            *total = total.saturating_add(delta)
+        });
+        let previous_scan = tracker.state.last_scan;
+        tracker.record_scan(now);
+        let timezone = settings.reset_timezone();
+        tracker.roll_over_at(settings.reset_period, timezone, now);
+        let period_end = tracker.period_end(settings.reset_period, timezone);
+        let today = timezone.date(now);
+        let grace = settings.new_app_grace();
+
+        let mut decisions = Vec::new();
+        let mut totals = BTreeMap::new();
+        let deltas: BTreeMap<_, _> = usage.into_iter().collect();
+        for (app, &delta) in &deltas {
+            tracker.mark_seen(app, now);
+            if delta > 0 {
+                tracker.mark_active(app, now);
+            }
+            let total_usage = tracker.add_usage(app, delta);
+            totals.insert(app.clone(), total_usage);
+            let app_written = written.get(app).copied().unwrap_or(0);
+            tracker.add_daily_usage(app, delta, app_written, today);
+            let limit = settings.effective_limit(app);
+            let Some(limit) = limit.filter(|_| monitor::is_app(app)) else {
+                if let Some(limit) = limit.filter(|limit| total_usage > limit.value) {
+                    let alert = Alert::new(app).with_usage(total_usage, limit.value);
+                    decisions.push(Decision::new(&alert, Outcome::Alert));
+                }
+                continue;
+            };
+            tracker.record_sample(app, now);
+            let in_grace = tracker.in_grace(app, grace, now);
+            let outcome = if in_grace {
+                Outcome::Grace
+            } else {
+                Outcome::Alert
+            };
+
+            if settings.anomaly.enabled
+                && let Some(baseline) = tracker.observe_delta(app, delta, &settings.anomaly)
+            {
+                let alert = Alert::new(app).with_anomaly(delta, baseline.mean.round() as u64);
+                decisions.push(Decision::new(&alert, outcome));
+            }
+
+            let data_limit = limit.value;
+            if total_usage > data_limit {
+                let streak = tracker.record_exceedance(app, settings.reset_period, now, today);
+                let severity = settings.escalation.severity(streak);
+                let fingerprint = tracker.fingerprint(total_usage, data_limit, severity);
+                let alert = Alert::new(app)
+                    .with_usage(total_usage, data_limit)
+                    .with_streak(streak, settings.reset_period, severity);
+                if !tracker.is_new_alert(app, &fingerprint) {
+                    decisions.push(Decision::new(&alert, Outcome::AlreadyAlerted));
+                } else {
+                    decisions.push(Decision::new(&alert, outcome));
+                    if !in_grace {
+                        tracker.record_alert(app, fingerprint);
+                    }
+                }
+            } else if !in_grace
+                && settings.forecast_alerts
+                && let Some(projected) = period_end
+                    .and_then(|end| tracker.forecast(app, end, now))
+                    .filter(|projected| *projected > data_limit)
+            {
+                let alert = Alert::new(app)
+                    .with_usage(total_usage, data_limit)
+                    .with_forecast(projected, settings.reset_period);
+                decisions.push(Decision::new(&alert, Outcome::Alert));
+            }
+        }
+
+        if let Some(previous_scan) = previous_scan {
+            for app in tracker.grace_ended(grace, previous_scan, now) {
+                if deltas.contains_key(&app) {
+                    continue;
+                }
+                let usage = tracker.apps[&app].bytes;
+                let Some(limit) = settings
+                    .effective_limit(&app)
+                    .filter(|limit| usage > limit.value && monitor::is_app(&app))
+                else {
+                    continue;
+                };
+                let streak = tracker.record_exceedance(&app, settings.reset_period, now, today);
+                let severity = settings.escalation.severity(streak);
+                let fingerprint = tracker.fingerprint(usage, limit.value, severity);
+                if tracker.is_new_alert(&app, &fingerprint) {
+                    let alert = Alert::new(&app).with_usage(usage, limit.value).with_streak(
+                        streak,
+                        settings.reset_period,
+                        severity,
+                    );
+                    decisions.push(Decision::new(&alert, Outcome::Alert));
+                    tracker.record_alert(&app, fingerprint);
+                }
+            }
+        }
+
+        let budget_delta = deltas
+            .iter()
+            .filter(|(app, _)| monitor::is_app(app))
+            .fold(0u64, |sum, (_, delta)| sum.saturating_add(*delta));
+        if let Some(&milestone) = tracker
+            .add_budget_usage(&settings.budget, budget_delta, today)
+            .last()
+            && let (Some(total), Some(start)) =
+                (settings.budget.total_bytes, tracker.budget.period_start)
+        {
+            let days_left = (settings.budget.period_end(start) - today).num_days();
+            let alert = Alert::new(DEFAULT_APP_NAME).with_budget(
+                tracker.budget.used,
+                total,
+                milestone,
+                days_left,
+            );
+            decisions.push(Decision::new(&alert, Outcome::Alert));
+            tracker.budget.mark_notified(&settings.budget, milestone);
+        }
+        tracker.record_recoveries(
+            |app| settings.effective_limit(app).map(|limit| limit.value),
+            now,
+        );
+
+        let mut resources: Vec<_> = resources.into_iter().collect();
+        resources.sort_by(|a, b| a.0.cmp(&b.0));
+        for (app, usage) in resources {
+            if let Some(cpu_ms) = usage.cpu_time_ms {
+                let cpu_seconds = tracker.add_cpu_time(&app, cpu_ms) / 1000;
+                if let Some(limit) = settings.cpu_limit_seconds
+                    && cpu_seconds > limit
+                {
+                    let alert = Alert::new(&app).with_cpu_time(cpu_seconds, limit);
+                    decisions.push(Decision::new(&alert, Outcome::Alert));
+                }
+            }
+            if let Some(memory) = usage.memory_bytes {
+                tracker.record_memory(&app, memory);
+                if let Some(limit) = settings.memory_limit_bytes
+                    && memory > limit
+                {
+                    let alert = Alert::new(&app).with_memory(memory, limit);
+                    decisions.push(Decision::new(&alert, Outcome::Alert));
+                }
+            }
+        }
+
+        Step {
+            time: now,
+            processes: frame.counters.len(),
+            deltas,
+            rejected: rejected.into_iter().collect(),
+            totals,
+            decisions,
+        }
+    }
+}
+
+/// Replays every frame of `trace` from empty usage.
+pub fn replay(trace: &Trace, settings: Settings) -> Vec<Step> {
+    let mut replayer = Replayer::new(settings);
+    trace
+        .frames
+        .iter()
+        .map(|frame| replayer.step(frame))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    fn frame(seconds: i64, processes: &[(u32, &str, u64)]) -> Frame {
+        let time = DateTime::parse_from_rfc3339("2025-06-18T12:00:00Z")
+            .unwrap()
+            .to_utc()
+            + TimeDelta::seconds(seconds);
+        let data = processes
+            .iter()
+            .map(|&(pid, name, bytes)| {
+                (
+                    CounterKey::Process(Pid::from_u32(pid), 0),
+                    Counters::new(name.to_string(), bytes),
+                )
+            })
+            .collect();
+        Frame::new(time, &data)
+    }
+
+    #[test]
+    fn test_trace_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.dgz");
+        let mut trace = Trace::new();
+        trace.frames.push(frame(0, &[(1, "firefox", 10)]));
+        let mut data = trace.frames[0].processes();
+        data.insert(
+            CounterKey::Disk("sda".to_string()),
+            Counters::new("disk:sda".to_string(), 5),
+        );
+        trace.push(trace.frames[0].time, &data);
+
+        assert!(trace.write(&path).unwrap() > 0);
+        let read = Trace::read(&path).unwrap();
+        assert_eq!(read, trace);
+        assert_eq!(read.frames[1].processes(), data);
+
+        trace.version = TRACE_VERSION + 1;
+        trace.write(&path).unwrap();
+        assert!(
+            matches!(Trace::read(&path), Err(TraceError::Version(v)) if v == TRACE_VERSION + 1)
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let settings = Settings {
+            data_limit: 100,
+            max_bytes_per_second: 10,
+            ..Settings::default()
+        };
+        let trace = Trace {
+            frames: vec![
+                frame(0, &[(1, "firefox", 1000), (2, "curl", 0)]),
+                frame(10, &[(1, "firefox", 1060), (2, "curl", 5)]),
+                frame(20, &[(1, "firefox", 1120), (2, "curl", 500), (3, "git", 9)]),
+                frame(
+                    30,
+                    &[(1, "firefox", 1130), (2, "curl", 505), (3, "git", 19)],
+                ),
+            ],
+            ..Trace::default()
+        };
+
+        let steps = replay(&trace, settings);
+        assert!(steps[0].deltas.is_empty());
+        assert_eq!(
+            steps[1].deltas,
+            [("curl".to_string(), 5), ("firefox".to_string(), 60)].into()
+        );
+        assert!(steps[1].decisions.is_empty());
+
+        // The jump of curl is above 10 bytes a second over 10 seconds.
+        assert_eq!(steps[2].rejected, [("curl".to_string(), 495)].into());
+        assert_eq!(steps[2].totals["firefox"], 120);
+        assert_eq!(
+            steps[2].decisions,
+            [Decision {
+                app: "firefox".to_string(),
+                kind: AlertKind::Exceeded,
+                outcome: Outcome::Alert,
+                usage: Some(120),
+                limit: Some(100),
+            }]
+        );
+
+        assert_eq!(steps[3].totals["git"], 10);
+        assert_eq!(steps[3].decisions[0].outcome, Outcome::AlreadyAlerted);
+        assert_eq!(replay(&trace, Settings::default())[3].decisions, []);
+    }
+
+    #[test]
+    fn test_grace_ends_after_app_exits() {
+        let settings = Settings {
+            data_limit: 100,
+            new_app_grace_seconds: 60,
+            ..Settings::default()
+        };
+        let trace = Trace {
+            frames: vec![
+                frame(0, &[(2, "curl", 0)]),
+                frame(10, &[(2, "curl", 500)]),
+                frame(20, &[]),
+                frame(70, &[]),
+                frame(80, &[]),
+            ],
+            ..Trace::default()
+        };
+
+        let steps = replay(&trace, settings);
+        assert_eq!(steps[1].decisions[0].outcome, Outcome::Grace);
+        assert_eq!(steps[2].decisions, []);
+        // curl was first seen at 10 seconds, so its grace ends at 70.
+        assert_eq!(
+            steps[3].decisions,
+            [Decision {
+                app: "curl".to_string(),
+                kind: AlertKind::Exceeded,
+                outcome: Outcome::Alert,
+                usage: Some(500),
+                limit: Some(100),
+            }]
+        );
+        assert_eq!(steps[4].decisions, []);
+    }
+}
@@ -0,0 +1,1230 @@
+//! JSON printed by `dg --json <command>`.
+//!
+//! Every top-level type carries `schema_version`. Within a version, fields
+//! are only ever added; renaming or removing one bumps `SCHEMA_VERSION`. The
+//! types reject unknown fields when parsed, so a script built against one
+//! version notices when it reads another.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::calibrate::Suggestion;
+use super::capabilities::Capabilities;
+use super::compare::{self, ChangeKind};
+use super::crash::CrashMarker;
+use super::events::Event;
+use super::history::{NotificationOutcome, NotificationRecord, Severity};
+use super::import::ImportFormat;
+use super::merge::MergeStrategy;
+use super::monitor::{self, UsageData};
+use super::notification::PausedChannel;
+use super::other_monitors::Coexistence;
+#[cfg(feature = "monitor")]
+use super::replay::Step;
+use super::self_check;
+use super::session::SessionStatus;
+use super::settings::{LimitSource, Settings};
+use super::tracker::{self, AppResources, ExceedanceStats, ResetPeriod};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub const EXIT_HEALTHY: i32 = 0;
+pub const EXIT_UNHEALTHY: i32 = 1;
+pub const EXIT_NOT_RUNNING: i32 = 3;
+
+pub const EXIT_UNDER_LIMIT: i32 = 0;
+pub const EXIT_OVER_LIMIT: i32 = 2;
+pub const EXIT_NO_DATA: i32 = 3;
+pub const EXIT_CHECK_ERROR: i32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppUsage {
+    pub app: String,
+    pub display_name: Option<String>,
+    pub bytes: u64,
+    pub limit: u64,
+    #[serde(default)]
+    pub limit_source: LimitSource,
+    pub over_limit: bool,
+    pub cpu_time_ms: Option<u64>,
+    pub peak_memory_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiskUsage {
+    pub device: String,
+    pub bytes: u64,
+    pub limit: Option<u64>,
+    pub over_limit: bool,
+}
+
+/// A network interface, with `track_network`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InterfaceUsage {
+    pub interface: String,
+    pub bytes: u64,
+    pub limit: Option<u64>,
+    pub over_limit: bool,
+    pub metered: bool,
+}
+
+/// A `[watched_paths]` entry; `bytes` is its size at the last walk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathUsage {
+    pub label: String,
+    pub path: Option<String>,
+    pub bytes: u64,
+    pub limit: Option<u64>,
+    pub over_limit: bool,
+}
+
+/// Counters since the service started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Totals {
+    pub scans: u64,
+    pub saves: u64,
+    pub alerts_sent: u64,
+    pub alerts_suppressed: u64,
+    pub alerts_failed: u64,
+}
+
+/// `dg status`. Everything but `running` is empty when the service is not
+/// running.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Status {
+    pub schema_version: u32,
+    pub running: bool,
+    pub paused: bool,
+    pub version: Option<String>,
+    pub instance: Option<String>,
+    pub uptime_seconds: Option<u64>,
+    pub last_persist: Option<DateTime<Utc>>,
+    pub totals: Option<Totals>,
+    pub apps: Vec<AppUsage>,
+    pub disks: Vec<DiskUsage>,
+    pub interfaces: Vec<InterfaceUsage>,
+    pub paths: Vec<PathUsage>,
+    pub data_incident: Option<DataIncident>,
+    pub last_crash: Option<CrashMarker>,
+    /// The service's own disk use, when `self_check` is on.
+    pub self_usage: Option<SelfUsage>,
+    /// Notification channels skipped after failing again and again.
+    #[serde(default)]
+    pub paused_channels: Vec<PausedChannel>,
+    /// Other data monitors found at startup.
+    #[serde(default)]
+    pub other_monitors: Coexistence,
+    /// The session counted when `root_process` is set.
+    #[serde(default)]
+    pub session: Option<SessionStatus>,
+    /// What the service found this machine supports at startup.
+    #[serde(default)]
+    pub capabilities: Capabilities,
+}
+
+/// The most recent data file that could not be read at startup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DataIncident {
+    pub time: DateTime<Utc>,
+    pub path: String,
+    pub quarantined_to: Option<String>,
+    pub size_bytes: u64,
+    pub error: String,
+    /// Missing when usage started over.
+    pub restored_from: Option<String>,
+}
+
+impl From<&tracker::DataIncident> for DataIncident {
+    fn from(incident: &tracker::DataIncident) -> Self {
+        Self {
+            time: incident.time,
+            path: incident.path.clone(),
+            quarantined_to: incident.quarantined_to.clone(),
+            size_bytes: incident.size_bytes,
+            error: incident.error.clone(),
+            restored_from: incident.restored_from.clone(),
+        }
+    }
+}
+
+/// The last self-check: what the service itself read and wrote, and how
+/// much its data and log files take up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SelfUsage {
+    pub time: DateTime<Utc>,
+    pub read_bytes: Option<u64>,
+    pub written_bytes: Option<u64>,
+    pub io_bytes_per_hour: Option<u64>,
+    pub written_bytes_per_hour: Option<u64>,
+    pub data_dir_bytes: u64,
+    pub log_bytes: Option<u64>,
+    pub persistence_interval_seconds: u64,
+}
+
+impl From<&self_check::SelfUsage> for SelfUsage {
+    fn from(usage: &self_check::SelfUsage) -> Self {
+        Self {
+            time: usage.time,
+            read_bytes: usage.read_bytes,
+            written_bytes: usage.written_bytes,
+            io_bytes_per_hour: usage.io_bytes_per_hour,
+            written_bytes_per_hour: usage.written_bytes_per_hour,
+            data_dir_bytes: usage.data_dir_bytes,
+            log_bytes: usage.log_bytes,
+            persistence_interval_seconds: usage.persistence_interval_seconds,
+        }
+    }
+}
+
+impl Status {
+    pub fn not_running() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            running: false,
+            paused: false,
+            version: None,
+            instance: None,
+            uptime_seconds: None,
+            last_persist: None,
+            totals: None,
+            apps: Vec::new(),
+            disks: Vec::new(),
+            interfaces: Vec::new(),
+            paths: Vec::new(),
+            data_incident: None,
+            last_crash: None,
+            self_usage: None,
+            paused_channels: Vec::new(),
+            other_monitors: Coexistence::default(),
+            session: None,
+            capabilities: Capabilities::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageSource {
+    /// Live numbers from the running service.
+    Service,
+    /// The data file, as of the last save.
+    DataFile,
+}
+
+/// `dg report`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Report {
+    pub schema_version: u32,
+    pub instance: String,
+    pub source: UsageSource,
+    pub apps: Vec<AppUsage>,
+    pub disks: Vec<DiskUsage>,
+    pub interfaces: Vec<InterfaceUsage>,
+    pub paths: Vec<PathUsage>,
+}
+
+impl Report {
+    pub fn new(
+        source: UsageSource,
+        usage: &UsageData,
+        display_names: &HashMap<String, String>,
+        resources: &HashMap<String, AppResources>,
+        settings: &Settings,
+    ) -> Self {
+        let (apps, disks, interfaces, paths) =
+            usage_rows(usage, display_names, resources, settings);
+        Self {
+            schema_version: SCHEMA_VERSION,
+            instance: settings.instance_label(),
+            source,
+            apps,
+            disks,
+            interfaces,
+            paths,
+        }
+    }
+}
+
+/// Splits usage into app, disk, network interface and watched path rows,
+/// each sorted by usage and then name.
+pub fn usage_rows(
+    usage: &UsageData,
+    display_names: &HashMap<String, String>,
+    resources: &HashMap<String, AppResources>,
+    settings: &Settings,
+) -> (
+    Vec<AppUsage>,
+    Vec<DiskUsage>,
+    Vec<InterfaceUsage>,
+    Vec<PathUsage>,
+) {
+    let mut apps = Vec::new();
+    let mut disks = Vec::new();
+    let mut interfaces = Vec::new();
+    let mut paths = Vec::new();
+    for (name, bytes) in usage {
+        let limit = settings.effective_limit(name);
+        if let Some(label) = monitor::path_label(name) {
+            let watched = settings.watched_paths.get(label);
+            let limit = limit.map(|limit| limit.value);
+            paths.push(PathUsage {
+                label: label.to_string(),
+                path: watched.map(|watched| watched.path.display().to_string()),
+                bytes: *bytes,
+                limit,
+                over_limit: limit.is_some_and(|limit| *bytes > limit),
+            });
+            continue;
+        }
+        if let Some(interface) = monitor::interface_name(name) {
+            let limit = limit.map(|limit| limit.value);
+            interfaces.push(InterfaceUsage {
+                interface: interface.to_string(),
+                bytes: *bytes,
+                limit,
+                over_limit: limit.is_some_and(|limit| *bytes > limit),
+                metered: settings.is_metered(interface),
+            });
+            continue;
+        }
+        match monitor::disk_name(name) {
+            Some(device) => {
+                let limit = limit.map(|limit| limit.value);
+                disks.push(DiskUsage {
+                    device: device.to_string(),
+                    bytes: *bytes,
+                    limit,
+                    over_limit: limit.is_some_and(|limit| *bytes > limit),
+                });
+            }
+            None => {
+                let Some(limit) = limit else {
+                    continue;
+                };
+                let resources = resources.get(name).copied().unwrap_or_default();
+                apps.push(AppUsage {
+                    app: name.clone(),
+                    display_name: display_names.get(name).cloned(),
+                    bytes: *bytes,
+                    limit: limit.value,
+                    limit_source: limit.source,
+                    over_limit: *bytes > limit.value,
+                    cpu_time_ms: resources.cpu_time_ms,
+                    peak_memory_bytes: resources.peak_memory_bytes,
+                });
+            }
+        }
+    }
+    apps.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.app.cmp(&b.app)));
+    disks.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.device.cmp(&b.device)));
+    interfaces.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.interface.cmp(&b.interface))
+    });
+    paths.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.label.cmp(&b.label)));
+    (apps, disks, interfaces, paths)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppExceedance {
+    pub app: String,
+    pub events: u64,
+    pub first_exceeded: Option<DateTime<Utc>>,
+    pub last_exceeded: Option<DateTime<Utc>>,
+    /// Includes the ongoing exceedance, if any.
+    pub seconds_over: u64,
+    pub over_limit: bool,
+}
+
+/// `dg report --stats`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Exceedances {
+    pub schema_version: u32,
+    pub apps: Vec<AppExceedance>,
+}
+
+impl Exceedances {
+    /// Takes stats whose `seconds_over` already includes any ongoing
+    /// exceedance, and sorts them by events, then time over, then name.
+    pub fn new(exceedances: &HashMap<String, ExceedanceStats>) -> Self {
+        let mut apps: Vec<_> = exceedances
+            .iter()
+            .map(|(app, stats)| AppExceedance {
+                app: app.clone(),
+                events: stats.events,
+                first_exceeded: stats.first_exceeded,
+                last_exceeded: stats.last_exceeded,
+                seconds_over: stats.seconds_over,
+                over_limit: stats.is_over(),
+            })
+            .collect();
+        apps.sort_by(|a, b| {
+            b.events
+                .cmp(&a.events)
+                .then_with(|| b.seconds_over.cmp(&a.seconds_over))
+                .then_with(|| a.app.cmp(&b.app))
+        });
+        Self {
+            schema_version: SCHEMA_VERSION,
+            apps,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppChange {
+    pub app: String,
+    pub current: u64,
+    pub previous: u64,
+    pub change_bytes: i64,
+    /// Missing for new apps.
+    pub change_percent: Option<f64>,
+    pub kind: ChangeKind,
+}
+
+/// `dg report --compare`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Comparison {
+    pub schema_version: u32,
+    pub period: ResetPeriod,
+    pub current_start: NaiveDate,
+    pub current_end: NaiveDate,
+    pub previous_start: NaiveDate,
+    pub previous_end: NaiveDate,
+    pub history_start: Option<NaiveDate>,
+    pub partial: bool,
+    pub apps: Vec<AppChange>,
+}
+
+impl From<&compare::Comparison> for Comparison {
+    fn from(comparison: &compare::Comparison) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            period: comparison.period,
+            current_start: comparison.current_start,
+            current_end: comparison.current_end,
+            previous_start: comparison.previous_start,
+            previous_end: comparison.previous_end,
+            history_start: comparison.history_start,
+            partial: comparison.partial,
+            apps: comparison
+                .apps
+                .iter()
+                .map(|change| AppChange {
+                    app: change.app.clone(),
+                    current: change.current,
+                    previous: change.previous,
+                    change_bytes: change.change_bytes,
+                    change_percent: change.change_percent,
+                    kind: change.kind,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Notification {
+    pub time: DateTime<Utc>,
+    pub severity: Severity,
+    pub app: String,
+    pub sent: bool,
+    pub error: Option<String>,
+}
+
+/// `dg history`, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct History {
+    pub schema_version: u32,
+    pub notifications: Vec<Notification>,
+}
+
+impl History {
+    pub fn new(records: &[NotificationRecord]) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            notifications: records
+                .iter()
+                .map(|record| {
+                    let error = match &record.outcome {
+                        NotificationOutcome::Sent => None,
+                        NotificationOutcome::Failed(e) => Some(e.clone()),
+                    };
+                    Notification {
+                        time: record.time,
+                        severity: record.severity,
+                        app: record.app.clone(),
+                        sent: error.is_none(),
+                        error,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One line of `dg follow`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FollowEvent {
+    pub schema_version: u32,
+    pub event: Event,
+}
+
+impl FollowEvent {
+    pub fn new(event: Event) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            event,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelResult {
+    pub channel: String,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+/// `dg notify-test`. Service fields are `None` when the service could not be
+/// asked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotifyTest {
+    pub schema_version: u32,
+    pub data_limit: u64,
+    pub cooldown_seconds: u64,
+    pub critical_cooldown_seconds: u64,
+    pub service_running: Option<bool>,
+    pub paused: Option<bool>,
+    pub snoozed_seconds: Option<u64>,
+    pub snoozed_apps: BTreeMap<String, u64>,
+    pub channels: Vec<ChannelResult>,
+}
+
+/// `dg cooldowns`: seconds left per app before it can be alerted again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cooldowns {
+    pub schema_version: u32,
+    pub apps: BTreeMap<String, u64>,
+}
+
+impl Cooldowns {
+    pub fn new(cooldowns: impl IntoIterator<Item = (String, Duration)>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            apps: cooldowns
+                .into_iter()
+                .map(|(app, remaining)| (app, remaining.as_secs_f64().ceil() as u64))
+                .collect(),
+        }
+    }
+}
+
+/// Commands that only change something, such as `dg flush` or `dg reset`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ack {
+    pub schema_version: u32,
+    pub command: String,
+    pub message: String,
+}
+
+impl Ack {
+    pub fn new(command: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            command: command.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// `dg export --output <PATH>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Export {
+    pub schema_version: u32,
+    pub format: String,
+    pub path: String,
+    pub rows: u64,
+}
+
+/// `dg calibrate`: limits suggested from a sample of live usage, highest
+/// first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Calibration {
+    pub schema_version: u32,
+    pub sampled_seconds: u64,
+    pub reset_period: ResetPeriod,
+    pub apps: Vec<Suggestion>,
+    /// A `data_limit` covering every app; `None` when nothing was seen.
+    pub data_limit: Option<u64>,
+    /// The config file `data_limit` was written to with `--write`.
+    pub written_to: Option<String>,
+}
+
+/// `dg record`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recording {
+    pub schema_version: u32,
+    pub frames: usize,
+    pub bytes: u64,
+    pub path: String,
+}
+
+/// `dg replay`, one step per recorded frame.
+#[cfg(feature = "monitor")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Replay {
+    pub schema_version: u32,
+    pub recorded_by: String,
+    pub steps: Vec<Step>,
+}
+
+/// `dg config get` and `dg config set`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigValue {
+    pub schema_version: u32,
+    pub key: String,
+    pub value: serde_json::Value,
+    /// The config file the value was written to, when it changed.
+    pub written_to: Option<String>,
+    /// Whether the running service reloaded; `None` when none was asked.
+    pub reloaded: Option<bool>,
+}
+
+/// `dg merge`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Merge {
+    pub schema_version: u32,
+    pub output: String,
+    pub inputs: Vec<String>,
+    pub strategy: MergeStrategy,
+    pub apps: u64,
+}
+
+/// `dg import`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Import {
+    pub schema_version: u32,
+    pub from: ImportFormat,
+    pub file: String,
+    pub strategy: MergeStrategy,
+    pub records: u64,
+    pub apps: u64,
+    /// Records left out, per reason.
+    pub skipped: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckState {
+    UnderLimit,
+    OverLimit,
+    NoData,
+    /// There is usage data, but it could not be read.
+    Error,
+}
+
+/// `dg check`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Check {
+    pub schema_version: u32,
+    pub status: CheckState,
+    /// Whether the numbers came from the running service rather than the
+    /// data file.
+    pub running: bool,
+    pub offenders: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Check {
+    pub fn new(running: bool, offenders: Option<Vec<String>>) -> Self {
+        let status = match &offenders {
+            None => CheckState::NoData,
+            Some(offenders) if offenders.is_empty() => CheckState::UnderLimit,
+            Some(_) => CheckState::OverLimit,
+        };
+        Self {
+            schema_version: SCHEMA_VERSION,
+            status,
+            running,
+            offenders: offenders.unwrap_or_default(),
+            error: None,
+        }
+    }
+
+    /// The data file exists but could not be read, so nothing is known.
+    pub fn failed(error: impl Into<String>) -> Self {
+        Self {
+            status: CheckState::Error,
+            error: Some(error.into()),
+            ..Self::new(false, None)
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self.status {
+            CheckState::UnderLimit => EXIT_UNDER_LIMIT,
+            CheckState::OverLimit => EXIT_OVER_LIMIT,
+            CheckState::NoData => EXIT_NO_DATA,
+            CheckState::Error => EXIT_CHECK_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthState {
+    Ok,
+    Unhealthy,
+    NotRunning,
+}
+
+/// Liveness verdict for `dg health`, printed as one line of JSON with or
+/// without `--json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Health {
+    pub schema_version: u32,
+    pub status: HealthState,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub problems: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_tick_age_seconds: Option<u64>,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+impl Health {
+    pub fn unhealthy(problem: impl Into<String>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            status: HealthState::Unhealthy,
+            problems: vec![problem.into()],
+            last_tick_age_seconds: None,
+            paused: false,
+        }
+    }
+
+    pub fn not_running(problem: impl Into<String>) -> Self {
+        Self {
+            status: HealthState::NotRunning,
+            ..Self::unhealthy(problem)
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self.status {
+            HealthState::Ok => EXIT_HEALTHY,
+            HealthState::Unhealthy => EXIT_UNHEALTHY,
+            HealthState::NotRunning => EXIT_NOT_RUNNING,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::watched_paths::WatchedPath;
+
+    fn time() -> DateTime<Utc> {
+        "2025-06-18T12:00:00Z".parse().unwrap()
+    }
+
+    fn roundtrip<T>(value: &T) -> serde_json::Value
+    where
+        T: Serialize + for<'de> Deserialize<'de> + PartialEq + std::fmt::Debug,
+    {
+        let json = serde_json::to_value(value).unwrap();
+        assert_eq!(&serde_json::from_value::<T>(json.clone()).unwrap(), value);
+        json
+    }
+
+    #[test]
+    fn test_status_shape() {
+        let settings = Settings {
+            data_limit: 100,
+            data_limit_source: LimitSource::Global,
+            disk_limits: HashMap::from([("sda".to_string(), 10)]),
+            metered_interfaces: vec!["wwan0".to_string()],
+            metered_data_limit: Some(50),
+            watched_paths: HashMap::from([(
+                "downloads".to_string(),
+                WatchedPath {
+                    path: "/home/me/Downloads".into(),
+                    limit: Some(1000),
+                    ..WatchedPath::default()
+                },
+            )]),
+            ..Settings::default()
+        };
+        let usage = UsageData::from([
+            ("firefox".to_string(), 150),
+            ("curl".to_string(), 5),
+            ("disk:sda".to_string(), 20),
+            ("net:wwan0".to_string(), 60),
+            ("path:downloads".to_string(), 400),
+        ]);
+        let resources = HashMap::from([(
+            "curl".to_string(),
+            AppResources {
+                cpu_time_ms: Some(7),
+                peak_memory_bytes: None,
+            },
+        )]);
+        let display_names = HashMap::from([("firefox".to_string(), "Firefox".to_string())]);
+        let (apps, disks, interfaces, paths) =
+            usage_rows(&usage, &display_names, &resources, &settings);
+        let status = Status {
+            version: Some("1.0.2 (abc 2025-06-18)".to_string()),
+            instance: Some("office-nas".to_string()),
+            uptime_seconds: Some(60),
+            last_persist: Some(time()),
+            totals: Some(Totals {
+                scans: 12,
+                saves: 1,
+                alerts_sent: 2,
+                alerts_suppressed: 3,
+                alerts_failed: 0,
+            }),
+            apps,
+            disks,
+            interfaces,
+            paths,
+            data_incident: Some(DataIncident::from(&tracker::DataIncident {
+                time: time(),
+                path: "usage.dat".to_string(),
+                quarantined_to: Some("usage.dat.corrupt-20250618T120000Z".to_string()),
+                size_bytes: 512,
+                error: "invalid gzip header".to_string(),
+                restored_from: None,
+            })),
+            last_crash: Some(CrashMarker {
+                time: time(),
+                message: "index out of bounds".to_string(),
+                location: None,
+                saved: true,
+            }),
+            self_usage: Some(SelfUsage::from(&self_check::SelfUsage {
+                time: time(),
+                read_bytes: Some(4096),
+                written_bytes: Some(8192),
+                io_bytes_per_hour: None,
+                written_bytes_per_hour: None,
+                data_dir_bytes: 2048,
+                log_bytes: None,
+                persistence_interval_seconds: 300,
+            })),
+            running: true,
+            ..Status::not_running()
+        };
+
+        assert_eq!(
+            roundtrip(&status),
+            json!({
+                "schema_version": 1,
+                "running": true,
+                "paused": false,
+                "version": "1.0.2 (abc 2025-06-18)",
+                "instance": "office-nas",
+                "uptime_seconds": 60,
+                "last_persist": "2025-06-18T12:00:00Z",
+                "totals": {
+                    "scans": 12,
+                    "saves": 1,
+                    "alerts_sent": 2,
+                    "alerts_suppressed": 3,
+                    "alerts_failed": 0
+                },
+                "apps": [
+                    {
+                        "app": "firefox",
+                        "display_name": "Firefox",
+                        "bytes": 150,
+                        "limit": 100,
+                        "limit_source": "global",
+                        "over_limit": true,
+                        "cpu_time_ms": null,
+                        "peak_memory_bytes": null
+                    },
+                    {
+                        "app": "curl",
+                        "display_name": null,
+                        "bytes": 5,
+                        "limit": 100,
+                        "limit_source": "global",
+                        "over_limit": false,
+                        "cpu_time_ms": 7,
+                        "peak_memory_bytes": null
+                    }
+                ],
+                "disks": [
+                    { "device": "sda", "bytes": 20, "limit": 10, "over_limit": true }
+                ],
+                "interfaces": [
+                    {
+                        "interface": "wwan0",
+                        "bytes": 60,
+                        "limit": 50,
+                        "over_limit": true,
+                        "metered": true
+                    }
+                ],
+                "paths": [
+                    {
+                        "label": "downloads",
+                        "path": "/home/me/Downloads",
+                        "bytes": 400,
+                        "limit": 1000,
+                        "over_limit": false
+                    }
+                ],
+                "data_incident": {
+                    "time": "2025-06-18T12:00:00Z",
+                    "path": "usage.dat",
+                    "quarantined_to": "usage.dat.corrupt-20250618T120000Z",
+                    "size_bytes": 512,
+                    "error": "invalid gzip header",
+                    "restored_from": null
+                },
+                "last_crash": {
+                    "time": "2025-06-18T12:00:00Z",
+                    "message": "index out of bounds",
+                    "location": null,
+                    "saved": true
+                },
+                "self_usage": {
+                    "time": "2025-06-18T12:00:00Z",
+                    "read_bytes": 4096,
+                    "written_bytes": 8192,
+                    "io_bytes_per_hour": null,
+                    "written_bytes_per_hour": null,
+                    "data_dir_bytes": 2048,
+                    "log_bytes": null,
+                    "persistence_interval_seconds": 300
+                },
+                "paused_channels": [],
+                "other_monitors": { "monitors": [], "network_alerts_log_only": false },
+                "session": null,
+                "capabilities": []
+            })
+        );
+        assert_eq!(
+            roundtrip(&Status::not_running()),
+            json!({
+                "schema_version": 1,
+                "running": false,
+                "paused": false,
+                "version": null,
+                "instance": null,
+                "uptime_seconds": null,
+                "last_persist": null,
+                "totals": null,
+                "apps": [],
+                "disks": [],
+                "interfaces": [],
+                "paths": [],
+                "data_incident": null,
+                "last_crash": null,
+                "self_usage": null,
+                "paused_channels": [],
+                "other_monitors": { "monitors": [], "network_alerts_log_only": false },
+                "session": null,
+                "capabilities": []
+            })
+        );
+    }
+
+    #[test]
+    fn test_report_shapes() {
+        let report = Report::new(
+            UsageSource::DataFile,
+            &UsageData::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+            &Settings {
+                instance_label: Some("office-nas".to_string()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            roundtrip(&report),
+            json!({
+                "schema_version": 1,
+                "instance": "office-nas",
+                "source": "data_file",
+                "apps": [],
+                "disks": [],
+                "interfaces": [],
+                "paths": []
+            })
+        );
+
+        let stats = ExceedanceStats {
+            events: 2,
+            first_exceeded: Some(time()),
+            last_exceeded: Some(time()),
+            over_since: Some(time()),
+            seconds_over: 90,
+        };
+        let exceedances = Exceedances::new(&HashMap::from([("firefox".to_string(), stats)]));
+        assert_eq!(
+            roundtrip(&exceedances),
+            json!({
+                "schema_version": 1,
+                "apps": [{
+                    "app": "firefox",
+                    "events": 2,
+                    "first_exceeded": "2025-06-18T12:00:00Z",
+                    "last_exceeded": "2025-06-18T12:00:00Z",
+                    "seconds_over": 90,
+                    "over_limit": true
+                }]
+            })
+        );
+
+        let date = |d| NaiveDate::from_ymd_opt(2025, 6, d).unwrap();
+        let comparison = Comparison::from(&compare::Comparison {
+            period: ResetPeriod::Weekly,
+            current_start: date(16),
+            current_end: date(18),
+            previous_start: date(9),
+            previous_end: date(15),
+            history_start: None,
+            partial: true,
+            apps: vec![compare::AppChange::new("curl".to_string(), 5, 0)],
+        });
+        assert_eq!(
+            roundtrip(&comparison),
+            json!({
+                "schema_version": 1,
+                "period": "weekly",
+                "current_start": "2025-06-16",
+                "current_end": "2025-06-18",
+                "previous_start": "2025-06-09",
+                "previous_end": "2025-06-15",
+                "history_start": null,
+                "partial": true,
+                "apps": [{
+                    "app": "curl",
+                    "current": 5,
+                    "previous": 0,
+                    "change_bytes": 5,
+                    "change_percent": null,
+                    "kind": "new"
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_command_shapes() {
+        let history = History::new(&[NotificationRecord {
+            app: "firefox".to_string(),
+            severity: Severity::Warning,
+            time: time(),
+            outcome: NotificationOutcome::Failed("no bus".to_string()),
+        }]);
+        assert_eq!(
+            roundtrip(&history),
+            json!({
+                "schema_version": 1,
+                "notifications": [{
+                    "time": "2025-06-18T12:00:00Z",
+                    "severity": "warning",
+                    "app": "firefox",
+                    "sent": false,
+                    "error": "no bus"
+                }]
+            })
+        );
+
+        let notify_test = NotifyTest {
+            schema_version: SCHEMA_VERSION,
+            data_limit: 100,
+            cooldown_seconds: 300,
+            critical_cooldown_seconds: 60,
+            service_running: Some(true),
+            paused: Some(false),
+            snoozed_seconds: None,
+            snoozed_apps: BTreeMap::from([("curl".to_string(), 30)]),
+            channels: vec![ChannelResult {
+                channel: "log".to_string(),
+                ok: true,
+                error: None,
+            }],
+        };
+        assert_eq!(
+            roundtrip(&notify_test),
+            json!({
+                "schema_version": 1,
+                "data_limit": 100,
+                "cooldown_seconds": 300,
+                "critical_cooldown_seconds": 60,
+                "service_running": true,
+                "paused": false,
+                "snoozed_seconds": null,
+                "snoozed_apps": { "curl": 30 },
+                "channels": [{ "channel": "log", "ok": true, "error": null }]
+            })
+        );
+
+        assert_eq!(
+            roundtrip(&Cooldowns::new([(
+                "curl".to_string(),
+                Duration::from_millis(1500)
+            )])),
+            json!({ "schema_version": 1, "apps": { "curl": 2 } })
+        );
+
+        assert_eq!(
+            roundtrip(&Ack::new("reset", "Reset usage for curl")),
+            json!({ "schema_version": 1, "command": "reset", "message": "Reset usage for curl" })
+        );
+        let check = Check::new(false, Some(vec!["curl".to_string()]));
+        assert_eq!(check.exit_code(), EXIT_OVER_LIMIT);
+        assert_eq!(
+            roundtrip(&check),
+            json!({ "schema_version": 1, "status": "over_limit", "running": false, "offenders": ["curl"] })
+        );
+        assert_eq!(
+            Check::new(true, Some(Vec::new())).exit_code(),
+            EXIT_UNDER_LIMIT
+        );
+        assert_eq!(Check::new(false, None).status, CheckState::NoData);
+        let failed = Check::failed("unsupported format");
+        assert_eq!(failed.exit_code(), EXIT_CHECK_ERROR);
+        assert_eq!(
+            roundtrip(&failed),
+            json!({ "schema_version": 1, "status": "error", "running": false, "offenders": [], "error": "unsupported format" })
+        );
+        let export = Export {
+            schema_version: SCHEMA_VERSION,
+            format: "parquet".to_string(),
+            path: "usage.parquet".to_string(),
+            rows: 3,
+        };
+        assert_eq!(
+            roundtrip(&export),
+            json!({ "schema_version": 1, "format": "parquet", "path": "usage.parquet", "rows": 3 })
+        );
+        let config = ConfigValue {
+            schema_version: SCHEMA_VERSION,
+            key: "data_limit".to_string(),
+            value: json!(2_000_000_000),
+            written_to: Some("config.toml".to_string()),
+            reloaded: None,
+        };
+        assert_eq!(
+            roundtrip(&config),
+            json!({
+                "schema_version": 1,
+                "key": "data_limit",
+                "value": 2_000_000_000,
+                "written_to": "config.toml",
+                "reloaded": null
+            })
+        );
+        let merge = Merge {
+            schema_version: SCHEMA_VERSION,
+            output: "all.dat".to_string(),
+            inputs: vec!["a.dat".to_string(), "b.dat".to_string()],
+            strategy: MergeStrategy::KeepPerHost,
+            apps: 4,
+        };
+        assert_eq!(
+            roundtrip(&merge),
+            json!({
+                "schema_version": 1,
+                "output": "all.dat",
+                "inputs": ["a.dat", "b.dat"],
+                "strategy": "keep-per-host",
+                "apps": 4
+            })
+        );
+        let import = Import {
+            schema_version: SCHEMA_VERSION,
+            from: ImportFormat::Vnstat,
+            file: "vnstat.json".to_string(),
+            strategy: MergeStrategy::Sum,
+            records: 60,
+            apps: 2,
+            skipped: BTreeMap::from([("malformed".to_string(), 1)]),
+        };
+        assert_eq!(
+            roundtrip(&import),
+            json!({
+                "schema_version": 1,
+                "from": "vnstat",
+                "file": "vnstat.json",
+                "strategy": "sum",
+                "records": 60,
+                "apps": 2,
+                "skipped": { "malformed": 1 }
+            })
+        );
+        assert_eq!(
+            roundtrip(&Health::not_running("Data Guardian is not running")),
+            json!({
+                "schema_version": 1,
+                "status": "not_running",
+                "problems": ["Data Guardian is not running"],
+                "paused": false
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_fields() {
+        let mut json = serde_json::to_value(Ack::new("flush", "OK")).unwrap();
+        json["extra"] = json!(true);
+        assert!(serde_json::from_value::<Ack>(json).is_err());
+
+        let mut json = serde_json::to_value(Status::not_running()).unwrap();
+        json["apps"] = json!([{
+            "app": "curl",
+            "display_name": null,
+            "bytes": 1,
+            "limit": 2,
+            "over_limit": false,
+            "cpu_time_ms": null,
+            "peak_memory_bytes": null,
+            "renamed": 1
+        }]);
+        assert!(serde_json::from_value::<Status>(json).is_err());
+    }
+}
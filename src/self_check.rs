@@ -0,0 +1,377 @@
+//! Keeps an eye on the service's own disk use: the I/O of its process and
+//! the size of its data directory and log files.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::watched_paths::WatchedPath;
+
+pub const DEFAULT_MAX_IO_BYTES_PER_HOUR: u64 = 1024 * 1024 * 1024;
+pub const DEFAULT_MAX_STORAGE_BYTES: u64 = 512 * 1024 * 1024;
+pub const DEFAULT_MAX_WRITE_BYTES_PER_HOUR: u64 = 64 * 1024 * 1024;
+/// The persistence interval is stretched to at most this many times the
+/// configured one.
+pub const MAX_SAVE_FACTOR: u64 = 16;
+pub const IO_CHECK: &str = "io";
+pub const STORAGE_CHECK: &str = "storage";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelfCheckConfig {
+    pub enabled: bool,
+    /// Alert when the service reads and writes more than this per hour.
+    /// 0 turns the cap off.
+    pub max_io_bytes_per_hour: u64,
+    /// Alert when the data directory and log files together grow past this.
+    /// 0 turns the cap off.
+    pub max_storage_bytes: u64,
+    /// Save less often while the service writes more than this per hour.
+    /// 0 turns it off.
+    pub max_write_bytes_per_hour: u64,
+}
+
+impl Default for SelfCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_io_bytes_per_hour: DEFAULT_MAX_IO_BYTES_PER_HOUR,
+            max_storage_bytes: DEFAULT_MAX_STORAGE_BYTES,
+            max_write_bytes_per_hour: DEFAULT_MAX_WRITE_BYTES_PER_HOUR,
+        }
+    }
+}
+
+/// Bytes read and written by the service's process since it started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OwnIo {
+    pub read_bytes: u64,
+    pub written_bytes: u64,
+}
+
+/// What the last self-check found, as shown by `dg status`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelfUsage {
+    pub time: DateTime<Utc>,
+    /// Read by the service since it started; `None` where unknown.
+    pub read_bytes: Option<u64>,
+    pub written_bytes: Option<u64>,
+    /// Read and written since the previous check, as a rate per hour.
+    pub io_bytes_per_hour: Option<u64>,
+    pub written_bytes_per_hour: Option<u64>,
+    pub data_dir_bytes: u64,
+    /// The log file and its rotated copies; `None` when logging to a file
+    /// is off.
+    pub log_bytes: Option<u64>,
+    /// The persistence interval in effect, after any stretching.
+    pub persistence_interval_seconds: u64,
+}
+
+impl SelfUsage {
+    pub fn storage_bytes(&self) -> u64 {
+        self.data_dir_bytes
+            .saturating_add(self.log_bytes.unwrap_or(0))
+    }
+}
+
+/// A cap the service went over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exceeded {
+    /// `IO_CHECK` or `STORAGE_CHECK`.
+    pub check: &'static str,
+    pub usage: u64,
+    pub limit: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfCheckOutcome {
+    pub usage: SelfUsage,
+    /// Caps that were crossed since the previous check.
+    pub exceeded: Vec<Exceeded>,
+    /// Whether the persistence interval in effect changed.
+    pub interval_changed: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SelfCheck {
+    config: SelfCheckConfig,
+    previous: Option<(DateTime<Utc>, OwnIo)>,
+    save_factor: u64,
+    io_over: bool,
+    storage_over: bool,
+}
+
+impl SelfCheck {
+    pub fn new(config: SelfCheckConfig) -> Self {
+        Self {
+            config,
+            save_factor: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_config(&mut self, config: SelfCheckConfig) {
+        if !config.enabled || config.max_write_bytes_per_hour == 0 {
+            self.save_factor = 1;
+        }
+        self.config = config;
+    }
+
+    /// `base_seconds` stretched while the service writes too much.
+    pub fn persistence_interval(&self, base_seconds: u64) -> u64 {
+        base_seconds.saturating_mul(self.save_factor.max(1))
+    }
+
+    /// Records a check at `now`. The persistence interval doubles while the
+    /// write rate is over `max_write_bytes_per_hour`, and halves again once
+    /// it drops below a quarter of it.
+    pub fn observe(
+        &mut self,
+        now: DateTime<Utc>,
+        io: Option<OwnIo>,
+        data_dir_bytes: u64,
+        log_bytes: Option<u64>,
+        base_interval_seconds: u64,
+    ) -> SelfCheckOutcome {
+        let per_hour = |bytes: u64, since: DateTime<Utc>| {
+            let millis = (now - since).num_milliseconds();
+            (millis > 0).then(|| (bytes as f64 * 3_600_000.0 / millis as f64) as u64)
+        };
+        let rates = match (io, self.previous) {
+            (Some(io), Some((since, previous))) => {
+                let read = io.read_bytes.saturating_sub(previous.read_bytes);
+                let written = io.written_bytes.saturating_sub(previous.written_bytes);
+                per_hour(read.saturating_add(written), since).zip(per_hour(written, since))
+            }
+            _ => None,
+        };
+        self.previous = io.map(|io| (now, io));
+
+        let factor = self.save_factor.max(1);
+        let max_write = self.config.max_write_bytes_per_hour;
+        self.save_factor = match rates {
+            Some((_, written)) if max_write > 0 && written > max_write => {
+                (factor * 2).min(MAX_SAVE_FACTOR)
+            }
+            Some((_, written)) if written < max_write / 4 => (factor / 2).max(1),
+            _ => factor,
+        };
+
+        let usage = SelfUsage {
+            time: now,
+            read_bytes: io.map(|io| io.read_bytes),
+            written_bytes: io.map(|io| io.written_bytes),
+            io_bytes_per_hour: rates.map(|(io, _)| io),
+            written_bytes_per_hour: rates.map(|(_, written)| written),
+            data_dir_bytes,
+            log_bytes,
+            persistence_interval_seconds: self.persistence_interval(base_interval_seconds),
+        };
+        let mut exceeded = Vec::new();
+        let io_rate = usage.io_bytes_per_hour.unwrap_or(0);
+        if crossed(
+            &mut self.io_over,
+            io_rate,
+            self.config.max_io_bytes_per_hour,
+        ) {
+            exceeded.push(Exceeded {
+                check: IO_CHECK,
+                usage: io_rate,
+                limit: self.config.max_io_bytes_per_hour,
+            });
+        }
+        let storage = usage.storage_bytes();
+        if crossed(
+            &mut self.storage_over,
+            storage,
+            self.config.max_storage_bytes,
+        ) {
+            exceeded.push(Exceeded {
+                check: STORAGE_CHECK,
+                usage: storage,
+                limit: self.config.max_storage_bytes,
+            });
+        }
+        SelfCheckOutcome {
+            usage,
+            exceeded,
+            interval_changed: self.save_factor != factor,
+        }
+    }
+}
+
+/// Whether `value` just went over `limit`, tracking the state in `over`.
+fn crossed(over: &mut bool, value: u64, limit: u64) -> bool {
+    let was_over = std::mem::replace(over, limit > 0 && value > limit);
+    *over && !was_over
+}
+
+/// Total size of the files below each of `dirs`, skipping missing ones.
+pub fn dirs_size(dirs: &[PathBuf]) -> u64 {
+    dirs.iter()
+        .filter_map(|dir| {
+            WatchedPath {
+                path: dir.clone(),
+                ..Default::default()
+            }
+            .measure()
+            .ok()
+        })
+        .fold(0u64, |sum, size| sum.saturating_add(size.bytes))
+}
+
+/// Total size of `log_file` and its rotated copies, which share its name
+/// as a prefix.
+pub fn log_files_size(log_file: &Path) -> u64 {
+    let (Some(dir), Some(name)) = (log_file.parent(), log_file.file_name()) else {
+        return 0;
+    };
+    let dir = match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => dir,
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let name = name.to_string_lossy();
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(name.as_ref())
+        })
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .fold(0u64, |sum, metadata| sum.saturating_add(metadata.len()))
+}
+
+/// I/O of the current process, where the platform reports it.
+#[cfg(feature = "monitor")]
+pub fn own_io() -> Option<OwnIo> {
+    use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+    let pid = sysinfo::get_current_pid().ok()?;
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        false,
+        ProcessRefreshKind::nothing().with_disk_usage(),
+    );
+    let usage = system.process(pid)?.disk_usage();
+    Some(OwnIo {
+        read_bytes: usage.total_read_bytes,
+        written_bytes: usage.total_written_bytes,
+    })
+}
+
+#[cfg(not(feature = "monitor"))]
+pub fn own_io() -> Option<OwnIo> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn io(read_bytes: u64, written_bytes: u64) -> Option<OwnIo> {
+        Some(OwnIo {
+            read_bytes,
+            written_bytes,
+        })
+    }
+
+    #[test]
+    fn test_caps_alert_once() {
+        let mut check = SelfCheck::new(SelfCheckConfig {
+            enabled: true,
+            max_io_bytes_per_hour: 1000,
+            max_storage_bytes: 500,
+            max_write_bytes_per_hour: 0,
+        });
+        let start = Utc::now();
+        let hour = TimeDelta::hours(1);
+
+        let first = check.observe(start, io(0, 0), 100, Some(100), 300);
+        assert!(first.exceeded.is_empty());
+        assert_eq!(first.usage.io_bytes_per_hour, None);
+
+        let second = check.observe(start + hour, io(800, 400), 400, Some(200), 300);
+        assert_eq!(second.usage.io_bytes_per_hour, Some(1200));
+        assert_eq!(second.usage.written_bytes_per_hour, Some(400));
+        assert_eq!(
+            second.exceeded,
+            [
+                Exceeded {
+                    check: IO_CHECK,
+                    usage: 1200,
+                    limit: 1000
+                },
+                Exceeded {
+                    check: STORAGE_CHECK,
+                    usage: 600,
+                    limit: 500
+                },
+            ]
+        );
+        assert!(!second.interval_changed);
+
+        // Still over: nothing new to report.
+        let third = check.observe(start + hour * 2, io(1600, 800), 400, Some(200), 300);
+        assert!(third.exceeded.is_empty());
+
+        // Back under, then over again.
+        check.observe(start + hour * 3, io(1600, 800), 100, None, 300);
+        let fifth = check.observe(start + hour * 4, io(3000, 800), 100, None, 300);
+        assert_eq!(fifth.exceeded.len(), 1);
+        assert_eq!(fifth.exceeded[0].check, IO_CHECK);
+    }
+
+    #[test]
+    fn test_write_rate_stretches_saves() {
+        let mut check = SelfCheck::new(SelfCheckConfig {
+            enabled: true,
+            max_write_bytes_per_hour: 100,
+            ..Default::default()
+        });
+        let start = Utc::now();
+        let hour = TimeDelta::hours(1);
+        check.observe(start, io(0, 0), 0, None, 300);
+
+        let mut written = 0;
+        for hours in 1..=6 {
+            written += 1000;
+            check.observe(start + hour * hours, io(0, written), 0, None, 300);
+        }
+        assert_eq!(check.persistence_interval(300), 300 * MAX_SAVE_FACTOR);
+
+        // Below a quarter of the threshold the interval comes back down.
+        let calm = check.observe(start + hour * 7, io(0, written + 10), 0, None, 300);
+        assert!(calm.interval_changed);
+        assert_eq!(
+            calm.usage.persistence_interval_seconds,
+            300 * MAX_SAVE_FACTOR / 2
+        );
+
+        check.set_config(SelfCheckConfig::default());
+        assert_eq!(check.persistence_interval(300), 300);
+    }
+
+    #[test]
+    fn test_storage_size() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("usage.dat"), [0; 100]).unwrap();
+        let missing = dir.path().join("missing");
+        assert_eq!(dirs_size(&[dir.path().to_path_buf(), missing]), 100);
+
+        let logs = tempfile::tempdir().unwrap();
+        std::fs::write(logs.path().join("dg.log"), [0; 10]).unwrap();
+        std::fs::write(logs.path().join("dg.log.1"), [0; 20]).unwrap();
+        std::fs::write(logs.path().join("other.log"), [0; 40]).unwrap();
+        assert_eq!(log_files_size(&logs.path().join("dg.log")), 30);
+    }
+}
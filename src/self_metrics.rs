@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::self_check::SelfUsage;
+
+pub const DEFAULT_SELF_METRICS_WINDOW: usize = 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TickStats {
+    pub time: DateTime<Utc>,
+    /// The scan's number in the data file, counting across restarts.
+    #[serde(default)]
+    pub tick: u64,
+    pub snapshot_ms: f64,
+    pub tick_ms: f64,
+    pub lag_ms: f64,
+    pub processes: usize,
+    pub tracked_apps: usize,
+    #[serde(default)]
+    pub tick_apps: usize,
+    #[serde(default)]
+    pub overflow_apps: usize,
+    pub delta_bytes: u64,
+    #[serde(default)]
+    pub rejected_bytes: u64,
+    /// The interface the default route went through, with `track_network`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_interface: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistStats {
+    pub time: DateTime<Utc>,
+    pub duration_ms: f64,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistFailure {
+    pub time: DateTime<Utc>,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SelfMetricsSummary {
+    pub ticks: usize,
+    pub avg_snapshot_ms: f64,
+    pub max_snapshot_ms: f64,
+    pub avg_lag_ms: f64,
+    pub max_lag_ms: f64,
+    pub last_tick: Option<TickStats>,
+    pub last_persist: Option<PersistStats>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persist_failure: Option<PersistFailure>,
+    /// Scans completed since the service started, not just in the window.
+    #[serde(default)]
+    pub total_ticks: u64,
+    #[serde(default)]
+    pub total_saves: u64,
+    /// Scans that failed in a row, until one succeeds.
+    #[serde(default)]
+    pub scan_failures: u32,
+    /// Saves that failed in a row, until one succeeds.
+    #[serde(default)]
+    pub save_failures: u32,
+    /// The service's own disk use, when `self_check` is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub self_usage: Option<SelfUsage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SelfMetrics {
+    capacity: usize,
+    ticks: VecDeque<TickStats>,
+    total_ticks: u64,
+    total_saves: u64,
+    last_persist: Option<PersistStats>,
+    persist_failure: Option<PersistFailure>,
+    scan_failures: u32,
+    save_failures: u32,
+    self_usage: Option<SelfUsage>,
+}
+
+impl Default for SelfMetrics {
+    fn default() -> Self {
+        Self::new(DEFAULT_SELF_METRICS_WINDOW)
+    }
+}
+
+impl SelfMetrics {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ticks: VecDeque::with_capacity(capacity.max(1)),
+            total_ticks: 0,
+            total_saves: 0,
+            last_persist: None,
+            persist_failure: None,
+            scan_failures: 0,
+            save_failures: 0,
+            self_usage: None,
+        }
+    }
+
+    pub fn record_tick(&mut self, tick: TickStats) {
+        if self.ticks.len() == self.capacity {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back(tick);
+        self.total_ticks += 1;
+    }
+
+    pub fn record_persist(&mut self, persist: PersistStats) {
+        self.last_persist = Some(persist);
+        self.persist_failure = None;
+        self.total_saves += 1;
+    }
+
+    /// Remembers a failed save until the next one succeeds.
+    pub fn record_persist_failure(&mut self, failure: PersistFailure) {
+        self.persist_failure = Some(failure);
+    }
+
+    pub fn set_scan_failures(&mut self, failures: u32) {
+        self.scan_failures = failures;
+    }
+
+    pub fn set_save_failures(&mut self, failures: u32) {
+        self.save_failures = failures;
+    }
+
+    pub fn set_self_usage(&mut self, usage: Option<SelfUsage>) {
+        self.self_usage = usage;
+    }
+
+    pub fn summary(&self) -> SelfMetricsSummary {
+        let count = self.ticks.len();
+        let (snapshot_sum, snapshot_max, lag_sum, lag_max) = self.ticks.iter().fold(
+            (0.0, 0.0f64, 0.0, 0.0f64),
+            |(snapshot_sum, snapshot_max, lag_sum, lag_max), tick| {
+                (
+                    snapshot_sum + tick.snapshot_ms,
+                    snapshot_max.max(tick.snapshot_ms),
+                    lag_sum + tick.lag_ms,
+                    lag_max.max(tick.lag_ms),
+                )
+            },
+        );
+        let avg = |sum: f64| if count == 0 { 0.0 } else { sum / count as f64 };
+
+        SelfMetricsSummary {
+            ticks: count,
+            avg_snapshot_ms: avg(snapshot_sum),
+            max_snapshot_ms: snapshot_max,
+            avg_lag_ms: avg(lag_sum),
+            max_lag_ms: lag_max,
+            last_tick: self.ticks.back().cloned(),
+            last_persist: self.last_persist.clone(),
+            persist_failure: self.persist_failure.clone(),
+            total_ticks: self.total_ticks,
+            total_saves: self.total_saves,
+            scan_failures: self.scan_failures,
+            save_failures: self.save_failures,
+            self_usage: self.self_usage.clone(),
+        }
+    }
+}
+
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+pub fn is_slow_scan(snapshot_ms: f64, check_interval: Duration) -> bool {
+    snapshot_ms > millis(check_interval) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(snapshot_ms: f64, lag_ms: f64) -> TickStats {
+        TickStats {
+            time: Utc::now(),
+            tick: 1,
+            snapshot_ms,
+            tick_ms: snapshot_ms,
+            lag_ms,
+            processes: 10,
+            tracked_apps: 3,
+            tick_apps: 3,
+            overflow_apps: 0,
+            delta_bytes: 0,
+            rejected_bytes: 0,
+            default_interface: None,
+        }
+    }
+
+    #[test]
+    fn test_rolling_window() {
+        let mut metrics = SelfMetrics::new(2);
+        assert_eq!(metrics.summary(), SelfMetricsSummary::default());
+
+        metrics.record_tick(tick(100.0, 0.0));
+        metrics.record_tick(tick(10.0, 4.0));
+        metrics.record_tick(tick(20.0, 2.0));
+
+        let summary = metrics.summary();
+        assert_eq!(summary.ticks, 2);
+        assert_eq!(summary.avg_snapshot_ms, 15.0);
+        assert_eq!(summary.max_snapshot_ms, 20.0);
+        assert_eq!(summary.avg_lag_ms, 3.0);
+        assert_eq!(summary.max_lag_ms, 4.0);
+        assert_eq!(summary.last_tick.unwrap().snapshot_ms, 20.0);
+        assert_eq!(summary.total_ticks, 3);
+        assert_eq!(summary.total_saves, 0);
+    }
+
+    #[test]
+    fn test_slow_scan() {
+        let interval = Duration::from_secs(2);
+        assert!(!is_slow_scan(999.0, interval));
+        assert!(!is_slow_scan(1000.0, interval));
+        assert!(is_slow_scan(1001.0, interval));
+    }
+}
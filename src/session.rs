@@ -0,0 +1,196 @@
+//! Counting only one session, such as a tmux server and everything it
+//! started, instead of the whole machine.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::app_names;
+
+/// The process a session hangs off, given by PID or by process name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RootProcess {
+    Pid(u32),
+    Name(String),
+}
+
+impl RootProcess {
+    /// A PID when `root` is a number, a process name otherwise.
+    pub fn parse(root: &str) -> Self {
+        let root = root.trim();
+        match root.parse() {
+            Ok(pid) => Self::Pid(pid),
+            Err(_) => Self::Name(root.to_string()),
+        }
+    }
+
+    /// Whether the process `pid` named `name` is a root. Names are compared
+    /// ignoring case and a trailing `.exe`.
+    pub fn matches(&self, pid: u32, name: &str) -> bool {
+        match self {
+            Self::Pid(root) => *root == pid,
+            Self::Name(root) => app_names::strip_exe(name).eq_ignore_ascii_case(root),
+        }
+    }
+}
+
+impl fmt::Display for RootProcess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pid(pid) => write!(f, "PID {}", pid),
+            Self::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Where the session stood at the last scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionStatus {
+    /// The `root_process` setting.
+    pub root: String,
+    /// The processes matching it. Empty while waiting for one to start.
+    pub pids: Vec<u32>,
+    /// Processes counted, the roots included.
+    pub processes: usize,
+}
+
+impl SessionStatus {
+    pub fn is_waiting(&self) -> bool {
+        self.pids.is_empty()
+    }
+}
+
+/// `roots` and every process below them. `parents` maps each running
+/// process to its parent.
+pub fn descendants(parents: &HashMap<u32, Option<u32>>, roots: &[u32]) -> HashSet<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (pid, parent) in parents {
+        if let Some(parent) = parent {
+            children.entry(*parent).or_default().push(*pid);
+        }
+    }
+    let mut found: HashSet<u32> = HashSet::new();
+    let mut pending: Vec<u32> = roots.to_vec();
+    while let Some(pid) = pending.pop() {
+        // A PID seen twice means a cycle from PID reuse, so stop there.
+        if found.insert(pid)
+            && let Some(children) = children.get(&pid)
+        {
+            pending.extend(children);
+        }
+    }
+    found
+}
+
+/// Finds the session again on every scan, so it follows the root when it
+/// restarts under a new PID.
+#[derive(Debug)]
+pub struct Session {
+    root: RootProcess,
+    status: Mutex<Option<SessionStatus>>,
+}
+
+impl Session {
+    pub fn new(root: RootProcess) -> Self {
+        Self {
+            root,
+            status: Mutex::new(None),
+        }
+    }
+
+    /// The PIDs in the session among `processes`, given as PID, parent and
+    /// name. Empty while no root is running.
+    pub fn select<'a>(
+        &self,
+        processes: impl IntoIterator<Item = (u32, Option<u32>, &'a str)>,
+    ) -> HashSet<u32> {
+        let mut parents = HashMap::new();
+        let mut roots = Vec::new();
+        for (pid, parent, name) in processes {
+            parents.insert(pid, parent);
+            if self.root.matches(pid, name) {
+                roots.push(pid);
+            }
+        }
+        roots.sort_unstable();
+        // A root started by another root is already counted below it.
+        let pids = descendants(&parents, &roots);
+
+        let status = SessionStatus {
+            root: self.root.to_string(),
+            processes: pids.len(),
+            pids: roots,
+        };
+        let mut last = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        match (
+            last.as_ref().map(SessionStatus::is_waiting),
+            status.is_waiting(),
+        ) {
+            (None | Some(false), true) => {
+                warn!(root = %self.root, "Root process not found, counting nothing until it starts")
+            }
+            (None | Some(true), false) => {
+                info!(root = %self.root, pids = ?status.pids, "Counting only processes under the root process")
+            }
+            _ => {}
+        }
+        *last = Some(status);
+        pids
+    }
+
+    /// The status at the last scan, or `None` before the first.
+    pub fn status(&self) -> Option<SessionStatus> {
+        self.status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_descendants() {
+        // 1 ─┬─ 10 (tmux) ─┬─ 11 (bash) ── 12 (cargo)
+        //    │             └─ 13 (bash)
+        //    └─ 20 (firefox) ── 21
+        let parents = HashMap::from([
+            (1, None),
+            (10, Some(1)),
+            (11, Some(10)),
+            (12, Some(11)),
+            (13, Some(10)),
+            (20, Some(1)),
+            (21, Some(20)),
+            // A cycle, which only a reused PID can make.
+            (30, Some(31)),
+            (31, Some(30)),
+        ]);
+        let sorted = |pids: HashSet<u32>| {
+            let mut pids: Vec<_> = pids.into_iter().collect();
+            pids.sort();
+            pids
+        };
+        assert_eq!(sorted(descendants(&parents, &[10])), [10, 11, 12, 13]);
+        assert_eq!(sorted(descendants(&parents, &[11, 20])), [11, 12, 20, 21]);
+        assert_eq!(sorted(descendants(&parents, &[30])), [30, 31]);
+        assert!(descendants(&parents, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_root() {
+        assert_eq!(RootProcess::parse("1234"), RootProcess::Pid(1234));
+        assert_eq!(
+            RootProcess::parse(" tmux "),
+            RootProcess::Name("tmux".to_string())
+        );
+        assert!(RootProcess::parse("tmux").matches(7, "TMUX.exe"));
+        assert!(!RootProcess::parse("tmux").matches(7, "tmux-helper"));
+        assert!(RootProcess::parse("7").matches(7, "bash"));
+    }
+}
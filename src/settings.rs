@@ -0,0 +1,2282 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::TimeDelta;
+use config::{Config, Environment, File};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use tracing::warn;
+
+use super::agent::AgentConfig;
+use super::app_names::{AppNameConfig, AppNameError, AppNames};
+use super::budget::BudgetConfig;
+use super::collector::CollectorConfig;
+use super::compression::DictionaryKind;
+#[cfg(feature = "monitor")]
+use super::filter::{FilterChain, FilterError};
+use super::format::{self, BytesStyle};
+use super::history;
+use super::i18n::Localizer;
+use super::identity::IdentifyBy;
+#[cfg(feature = "monitor")]
+use super::identity::{IdentityConfig, Resolver};
+use super::influx::InfluxConfig;
+use super::logging::{self, LogFormat, LogRotation};
+use super::metrics;
+use super::monitor::{self, Backend};
+#[cfg(feature = "monitor")]
+use super::monitor::{Collect, CounterSource, MonitorError, NormalizedSource};
+use super::mqtt::{self, MqttConfig};
+use super::notification::NotificationConfig;
+use super::other_monitors::OtherMonitorsConfig;
+use super::paths::{self, ResolvedPath};
+use super::self_check::SelfCheckConfig;
+#[cfg(feature = "monitor")]
+use super::session::RootProcess;
+use super::telemetry::OtelConfig;
+use super::tracker::{self, AnomalyConfig, EscalationConfig, ResetPeriod, ResetTimezone};
+use super::tray::TrayConfig;
+use super::watched_paths::{self, WatchedPath};
+
+pub const MIN_DATA_LIMIT: u64 = 1024 * 1024;
+pub const MIN_CHECK_INTERVAL: u64 = 1;
+pub const MIN_PERSISTENCE_INTERVAL: u64 = 10;
+pub const MIN_HISTORY_RETENTION: u64 = 60;
+pub const MIN_HISTORY_DAYS: u64 = 1;
+/// Longest `instance_label`, which also has to fit in a DNS label.
+pub const MAX_INSTANCE_LABEL_LEN: usize = 63;
+
+pub const DEFAULT_DATA_LIMIT: u64 = 1024 * 1024 * 1024;
+pub const DEFAULT_CHECK_INTERVAL: u64 = 60;
+pub const DEFAULT_PERSISTENCE_INTERVAL: u64 = 300;
+pub const DEFAULT_HISTORY_CAPACITY: usize = history::DEFAULT_HISTORY_CAPACITY;
+pub const DEFAULT_HISTORY_RETENTION: u64 = history::DEFAULT_HISTORY_RETENTION.as_secs();
+pub const DEFAULT_HISTORY_DAYS: u64 = tracker::DAILY_HISTORY_DAYS;
+pub const DEFAULT_HISTORY_MONTHS: u32 = tracker::DEFAULT_HISTORY_MONTHS;
+pub const DEFAULT_MAX_BYTES_PER_SECOND: u64 = 10 * 1024 * 1024 * 1024;
+pub const DEFAULT_MAX_TRACKED_APPS: usize = 10_000;
+pub const DEFAULT_METRICS_MAX_APPS: usize = metrics::DEFAULT_MAX_APP_LABELS;
+
+#[derive(Error, Debug)]
+pub enum SettingsError {
+    #[error("Invalid data limit: {0} (min: {1})")]
+    InvalidDataLimit(u64, u64),
+    #[error("Invalid check interval: {0} seconds (min: {1})")]
+    InvalidCheckInterval(u64, u64),
+    #[error("Invalid persistence interval: {0} seconds (min: {1})")]
+    InvalidPersistenceInterval(u64, u64),
+    #[error("Invalid history retention: {0} seconds (min: {1})")]
+    InvalidHistoryRetention(u64, u64),
+    #[error("Invalid history days: {0} (min: {1})")]
+    InvalidHistoryDays(u64, u64),
+    #[error("Invalid watched paths interval: {0} seconds (min: {1})")]
+    InvalidWatchInterval(u64, u64),
+    #[error("Watched path {0} needs a path")]
+    MissingWatchedPath(String),
+    #[error("Invalid budget start day: {0} (expected 1 to 31)")]
+    InvalidBudgetStartDay(u32),
+    #[error("Invalid budget milestone: {0}% (must be positive)")]
+    InvalidBudgetMilestone(u32),
+    #[error("Invalid metrics app label cap: {0} (min: 1)")]
+    InvalidMetricsMaxApps(usize),
+    #[error(
+        "Invalid instance label: {0:?} (up to 63 letters, digits, '.', '-' or '_', starting with a letter or digit)"
+    )]
+    InvalidInstanceLabel(String),
+    #[error("Invalid log level: {0}")]
+    InvalidLogLevel(String),
+    #[error("Invalid reset timezone: {0} (expected an IANA name like Europe/Berlin)")]
+    InvalidTimezone(String),
+    #[error("Invalid anomaly threshold: {0} standard deviations (must be positive)")]
+    InvalidAnomalyThreshold(f64),
+    #[error("Invalid MQTT QoS: {0} (max: {1})")]
+    InvalidMqttQos(u8, u8),
+    #[error("InfluxDB export requires influx.org and influx.bucket")]
+    IncompleteInfluxConfig,
+    #[error("Agent mode requires agent.url")]
+    IncompleteAgentConfig,
+    #[error("At least one notification channel must be enabled")]
+    NoNotificationChannels,
+    #[error(
+        "Notification route {0} names unknown channel {1:?} (expected one of notifications.channels, or mqtt with mqtt.enabled)"
+    )]
+    UnknownRouteChannel(String, String),
+    #[error(transparent)]
+    AppNames(#[from] AppNameError),
+    #[error("Unknown setting: {0}")]
+    UnknownSetting(String),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+    #[error("Configuration error: {0}")]
+    Config(#[from] config::ConfigError),
+    #[error("Invalid config file: {0}")]
+    Toml(#[from] toml_edit::TomlError),
+    #[error("Failed to write config file: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Where a data limit comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitSource {
+    /// The built-in `data_limit`.
+    #[default]
+    Default,
+    /// `data_limit` from the config file, the environment or `dg config set`.
+    Global,
+    /// The disk's entry in `disk_limits`.
+    Disk,
+    /// The interface's entry in `interface_limits`.
+    Interface,
+    /// `metered_data_limit`, when stricter than the interface's own limit.
+    Metered,
+    /// The `limit` of a `[watched_paths]` entry.
+    Path,
+}
+
+impl LimitSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Global => "global",
+            Self::Disk => "disk",
+            Self::Interface => "interface",
+            Self::Metered => "metered",
+            Self::Path => "path",
+        }
+    }
+}
+
+/// The data limit an application, disk, interface or watched path is held
+/// to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EffectiveLimit {
+    pub value: u64,
+    pub source: LimitSource,
+}
+
+/// What loading does with values outside their allowed range.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationMode {
+    /// Refuse to load.
+    #[default]
+    Strict,
+    /// Move them to the nearest bound and warn.
+    Clamp,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub validation: ValidationMode,
+    pub data_limit: u64,
+    /// Whether `data_limit` was set or left at its built-in value.
+    #[serde(skip)]
+    pub data_limit_source: LimitSource,
+    pub check_interval_seconds: u64,
+    /// How long a scan waits for a usage snapshot or for notifications.
+    /// Defaults to half the check interval.
+    pub blocking_timeout_seconds: Option<u64>,
+    pub persistence_interval_seconds: u64,
+    pub data_dir: Option<PathBuf>,
+    /// Where the control socket is created. Defaults to `$XDG_RUNTIME_DIR`.
+    pub runtime_dir: Option<PathBuf>,
+    /// Names this machine in alerts, metrics and exports. Defaults to the
+    /// host name.
+    pub instance_label: Option<String>,
+    pub history_capacity: usize,
+    pub history_retention_seconds: u64,
+    /// Days of per-app usage kept day by day in the data file.
+    pub history_days: u64,
+    /// Months of per-app usage kept as monthly totals once their days are
+    /// older than `history_days`; zero drops them instead.
+    pub history_months: u32,
+    /// Appends every alert to `alerts.log` in the data directory.
+    pub alerts_log: bool,
+    /// Preset dictionary for compressing the data file.
+    pub compression_dictionary: DictionaryKind,
+    pub notifications: NotificationConfig,
+    pub locale: Option<String>,
+    pub locales_dir: Option<PathBuf>,
+    /// Units for byte counts in tables, notifications and logs.
+    pub bytes_style: BytesStyle,
+    pub reset_period: ResetPeriod,
+    pub reset_timezone: Option<String>,
+    pub forecast_alerts: bool,
+    /// Holds back alerts for apps first seen less than this many seconds
+    /// ago. 0 turns the grace period off.
+    pub new_app_grace_seconds: u64,
+    pub notify_on_start: bool,
+    pub notify_on_shutdown: bool,
+    pub escalation: EscalationConfig,
+    pub anomaly: AnomalyConfig,
+    pub budget: BudgetConfig,
+    /// Caps on the service's own I/O and storage.
+    pub self_check: SelfCheckConfig,
+    pub pause_on_battery: bool,
+    pub only_when_interface_matches: Option<String>,
+    pub backend: Backend,
+    pub ignore_system_processes: bool,
+    pub ignored_users: Vec<String>,
+    pub watched_users: Vec<String>,
+    /// Count only this process and its descendants, given by PID or name.
+    pub root_process: Option<String>,
+    pub max_bytes_per_second: u64,
+    pub max_tracked_apps: usize,
+    pub snapshot_threads: usize,
+    pub collect_cpu: bool,
+    pub collect_memory: bool,
+    pub cpu_limit_seconds: Option<u64>,
+    pub memory_limit_bytes: Option<u64>,
+    pub track_disks: bool,
+    pub disk_limits: HashMap<String, u64>,
+    pub track_network: bool,
+    pub interface_limits: HashMap<String, u64>,
+    /// Interfaces billed by the byte, such as `wwan0`, held to
+    /// `metered_data_limit` on top of their own limit.
+    pub metered_interfaces: Vec<String>,
+    pub metered_data_limit: Option<u64>,
+    pub watched_paths: HashMap<String, WatchedPath>,
+    pub watched_paths_interval_seconds: u64,
+    pub resolve_sandboxed_apps: bool,
+    pub identify_by: IdentifyBy,
+    pub friendly_names: bool,
+    pub group_containers: bool,
+    pub app_names: AppNameConfig,
+    pub other_monitors: OtherMonitorsConfig,
+    pub metrics_listen_addr: Option<SocketAddr>,
+    pub metrics_max_apps: usize,
+    pub status_port: Option<u16>,
+    pub status_bind_addr: IpAddr,
+    pub otel: OtelConfig,
+    pub mqtt: MqttConfig,
+    pub influx: InfluxConfig,
+    pub tray: TrayConfig,
+    pub agent: AgentConfig,
+    pub collector: CollectorConfig,
+    pub log_level: String,
+    pub log_format: LogFormat,
+    pub log_file: Option<PathBuf>,
+    pub log_rotation: LogRotation,
+    pub log_max_size_bytes: u64,
+    pub log_max_files: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            validation: ValidationMode::Strict,
+            data_limit: DEFAULT_DATA_LIMIT,
+            data_limit_source: LimitSource::Default,
+            check_interval_seconds: DEFAULT_CHECK_INTERVAL,
+            blocking_timeout_seconds: None,
+            persistence_interval_seconds: DEFAULT_PERSISTENCE_INTERVAL,
+            data_dir: None,
+            runtime_dir: None,
+            instance_label: None,
+            history_capacity: DEFAULT_HISTORY_CAPACITY,
+            history_retention_seconds: DEFAULT_HISTORY_RETENTION,
+            history_days: DEFAULT_HISTORY_DAYS,
+            history_months: DEFAULT_HISTORY_MONTHS,
+            alerts_log: false,
+            compression_dictionary: DictionaryKind::None,
+            notifications: NotificationConfig::default(),
+            locale: None,
+            locales_dir: None,
+            bytes_style: BytesStyle::default(),
+            reset_period: ResetPeriod::Never,
+            reset_timezone: None,
+            forecast_alerts: false,
+            new_app_grace_seconds: 0,
+            notify_on_start: false,
+            notify_on_shutdown: false,
+            escalation: EscalationConfig::default(),
+            anomaly: AnomalyConfig::default(),
+            budget: BudgetConfig::default(),
+            self_check: SelfCheckConfig::default(),
+            pause_on_battery: false,
+            only_when_interface_matches: None,
+            backend: Backend::Process,
+            ignore_system_processes: false,
+            ignored_users: Vec::new(),
+            watched_users: Vec::new(),
+            root_process: None,
+            max_bytes_per_second: DEFAULT_MAX_BYTES_PER_SECOND,
+            max_tracked_apps: DEFAULT_MAX_TRACKED_APPS,
+            snapshot_threads: 1,
+            collect_cpu: false,
+            collect_memory: false,
+            cpu_limit_seconds: None,
+            memory_limit_bytes: None,
+            track_disks: false,
+            disk_limits: HashMap::new(),
+            track_network: false,
+            interface_limits: HashMap::new(),
+            metered_interfaces: Vec::new(),
+            metered_data_limit: None,
+            watched_paths: HashMap::new(),
+            watched_paths_interval_seconds: watched_paths::DEFAULT_WATCH_INTERVAL,
+            resolve_sandboxed_apps: false,
+            identify_by: IdentifyBy::Process,
+            friendly_names: false,
+            group_containers: false,
+            app_names: AppNameConfig::default(),
+            other_monitors: OtherMonitorsConfig::default(),
+            metrics_listen_addr: None,
+            metrics_max_apps: DEFAULT_METRICS_MAX_APPS,
+            status_port: None,
+            status_bind_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            otel: OtelConfig::default(),
+            mqtt: MqttConfig::default(),
+            influx: InfluxConfig::default(),
+            tray: TrayConfig::default(),
+            agent: AgentConfig::default(),
+            collector: CollectorConfig::default(),
+            log_level: logging::DEFAULT_LOG_LEVEL.to_string(),
+            log_format: LogFormat::default(),
+            log_file: None,
+            log_rotation: LogRotation::default(),
+            log_max_size_bytes: logging::DEFAULT_LOG_MAX_SIZE,
+            log_max_files: logging::DEFAULT_LOG_MAX_FILES,
+        }
+    }
+}
+
+impl Settings {
+    pub fn new() -> Result<Self, SettingsError> {
+        Self::load(paths::config_path(None).as_ref())
+    }
+
+    /// Loads settings from the environment and `config`. A missing file is
+    /// only an error when the path was given explicitly.
+    pub fn load(config: Option<&ResolvedPath>) -> Result<Self, SettingsError> {
+        Ok(Self::load_adjusted(config)?.0)
+    }
+
+    /// Like `load`, but also returns the values clamped in clamp mode so
+    /// the caller can report them once logging is set up.
+    pub fn load_adjusted(
+        config: Option<&ResolvedPath>,
+    ) -> Result<(Self, Vec<SettingsFieldChange>), SettingsError> {
+        let mut builder = Config::builder();
+
+        builder = builder.add_source(
+            Environment::with_prefix("DATAGUARDIAN")
+                .prefix_separator("_")
+                .separator("__"),
+        );
+
+        if let Some(config) = config {
+            builder = builder.add_source(
+                File::from(config.path.as_path()).required(config.source.is_explicit()),
+            );
+        }
+
+        // `data_limit` has no default here, so the built config only has it
+        // when it was set; the field's own default fills it in otherwise.
+        builder = builder.set_default("check_interval_seconds", DEFAULT_CHECK_INTERVAL)?;
+        builder =
+            builder.set_default("persistence_interval_seconds", DEFAULT_PERSISTENCE_INTERVAL)?;
+        builder = builder.set_default("history_capacity", DEFAULT_HISTORY_CAPACITY as u64)?;
+        builder = builder.set_default("history_retention_seconds", DEFAULT_HISTORY_RETENTION)?;
+
+        Self::from_config(builder.build()?)?.check()
+    }
+
+    pub fn from_file(config_path: impl AsRef<std::path::Path>) -> Result<Self, SettingsError> {
+        let config = Config::builder()
+            .add_source(File::from(config_path.as_ref()))
+            .build()?;
+        Ok(Self::from_config(config)?.check()?.0)
+    }
+
+    /// Deserializes `config`, noting whether it set `data_limit`.
+    fn from_config(config: Config) -> Result<Self, SettingsError> {
+        let data_limit_source = match config.get::<config::Value>("data_limit") {
+            Ok(_) => LimitSource::Global,
+            Err(_) => LimitSource::Default,
+        };
+        let settings: Settings = config.try_deserialize()?;
+        Ok(Self {
+            data_limit_source,
+            ..settings
+        })
+    }
+
+    /// Writes the settings that differ from those in the config file at
+    /// `path` into it, keeping every other key and comment. A missing file
+    /// is created. Returns what was changed.
+    pub fn save(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Vec<SettingsFieldChange>, SettingsError> {
+        let path = path.as_ref();
+        let (text, current) = match std::fs::read_to_string(path) {
+            Ok(text) => (text, Self::from_file(path)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (String::new(), Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let changes = current.diff(self);
+        if changes.is_empty() {
+            return Ok(changes);
+        }
+
+        let mut document = text.parse::<toml_edit::DocumentMut>()?;
+        let value = |settings: &Settings| serde_json::to_value(settings).unwrap_or_default();
+        merge_toml(document.as_item_mut(), &value(&current), &value(self));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp = path.with_extension("toml.tmp");
+        std::fs::write(&temp, document.to_string())?;
+        std::fs::rename(&temp, path)?;
+        Ok(changes)
+    }
+
+    /// The value of the dotted `key`, such as `data_limit` or
+    /// `mqtt.broker`.
+    pub fn get(&self, key: &str) -> Result<serde_json::Value, SettingsError> {
+        let settings = serde_json::to_value(self).unwrap_or_default();
+        key.split('.')
+            .try_fold(&settings, |value, part| value.get(part))
+            .cloned()
+            .ok_or_else(|| SettingsError::UnknownSetting(key.to_string()))
+    }
+
+    /// A copy with the dotted `key` set to `value`, read as a TOML value, a
+    /// byte count such as `2GB`, or else a string. The copy is validated
+    /// strictly, so nothing is clamped.
+    pub fn with_value(&self, key: &str, value: &str) -> Result<Self, SettingsError> {
+        let unknown = || SettingsError::UnknownSetting(key.to_string());
+        let (parents, name) = key.rsplit_once('.').unwrap_or(("", key));
+        let candidates = [
+            value
+                .parse::<toml_edit::Value>()
+                .ok()
+                .map(|value| json_value(&value)),
+            format::parse_bytes(value).map(Into::into),
+            Some(value.into()),
+        ];
+
+        let mut error = None;
+        for candidate in candidates.into_iter().flatten() {
+            let mut settings = serde_json::to_value(self).unwrap_or_default();
+            let table = parents
+                .split('.')
+                .filter(|part| !part.is_empty())
+                .try_fold(&mut settings, |value, part| value.get_mut(part))
+                .and_then(|table| table.as_object_mut())
+                .ok_or_else(unknown)?;
+            table.insert(name.to_string(), candidate);
+            match serde_json::from_value::<Settings>(settings) {
+                // Unknown keys are ignored when parsing, so check it stuck.
+                Ok(mut updated) if updated.get(key).is_ok() => {
+                    updated.validate()?;
+                    updated.data_limit_source = match key {
+                        "data_limit" => LimitSource::Global,
+                        _ => self.data_limit_source,
+                    };
+                    return Ok(updated);
+                }
+                Ok(_) => return Err(unknown()),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        Err(SettingsError::InvalidValue(
+            key.to_string(),
+            error.map(|e| e.to_string()).unwrap_or_default(),
+        ))
+    }
+
+    /// Validates according to `validation`.
+    fn check(self) -> Result<(Self, Vec<SettingsFieldChange>), SettingsError> {
+        match self.validation {
+            ValidationMode::Strict => self.validate().map(|()| (self, Vec::new())),
+            ValidationMode::Clamp => self.validate_and_clamp(),
+        }
+    }
+
+    /// Moves every value that has a bound back into range, then validates
+    /// the result. Returns the corrected settings and what was changed.
+    /// Mistakes without a nearest valid value, such as an unknown timezone
+    /// or log level, are still errors.
+    pub fn validate_and_clamp(&self) -> Result<(Self, Vec<SettingsFieldChange>), SettingsError> {
+        let mut clamped = self.clone();
+        clamped.data_limit = clamped.data_limit.max(MIN_DATA_LIMIT);
+        clamped.check_interval_seconds = clamped.check_interval_seconds.max(MIN_CHECK_INTERVAL);
+        clamped.persistence_interval_seconds = clamped
+            .persistence_interval_seconds
+            .max(MIN_PERSISTENCE_INTERVAL);
+        clamped.history_retention_seconds =
+            clamped.history_retention_seconds.max(MIN_HISTORY_RETENTION);
+        clamped.history_days = clamped.history_days.max(MIN_HISTORY_DAYS);
+        clamped.watched_paths_interval_seconds = clamped
+            .watched_paths_interval_seconds
+            .max(watched_paths::MIN_WATCH_INTERVAL);
+        clamped.metrics_max_apps = clamped.metrics_max_apps.max(1);
+        clamped.budget.start_day = clamped.budget.start_day.clamp(1, 31);
+        clamped.mqtt.qos = clamped.mqtt.qos.min(mqtt::MAX_QOS);
+
+        clamped.validate()?;
+        let adjustments = self.diff(&clamped);
+        Ok((clamped, adjustments))
+    }
+
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if self.data_limit < MIN_DATA_LIMIT {
+            return Err(SettingsError::InvalidDataLimit(
+                self.data_limit,
+                MIN_DATA_LIMIT,
+            ));
+        }
+
+        if self.check_interval_seconds < MIN_CHECK_INTERVAL {
+            return Err(SettingsError::InvalidCheckInterval(
+                self.check_interval_seconds,
+                MIN_CHECK_INTERVAL,
+            ));
+        }
+
+        if self.persistence_interval_seconds < MIN_PERSISTENCE_INTERVAL {
+            return Err(SettingsError::InvalidPersistenceInterval(
+                self.persistence_interval_seconds,
+                MIN_PERSISTENCE_INTERVAL,
+            ));
+        }
+
+        if self.history_retention_seconds < MIN_HISTORY_RETENTION {
+            return Err(SettingsError::InvalidHistoryRetention(
+                self.history_retention_seconds,
+                MIN_HISTORY_RETENTION,
+            ));
+        }
+
+        if self.history_days < MIN_HISTORY_DAYS {
+            return Err(SettingsError::InvalidHistoryDays(
+                self.history_days,
+                MIN_HISTORY_DAYS,
+            ));
+        }
+
+        if self.watched_paths_interval_seconds < watched_paths::MIN_WATCH_INTERVAL {
+            return Err(SettingsError::InvalidWatchInterval(
+                self.watched_paths_interval_seconds,
+                watched_paths::MIN_WATCH_INTERVAL,
+            ));
+        }
+
+        if let Some((label, _)) = self
+            .watched_paths
+            .iter()
+            .find(|(_, watched)| watched.path.as_os_str().is_empty())
+        {
+            return Err(SettingsError::MissingWatchedPath(label.clone()));
+        }
+
+        if self
+            .log_level
+            .parse::<tracing::level_filters::LevelFilter>()
+            .is_err()
+        {
+            return Err(SettingsError::InvalidLogLevel(self.log_level.clone()));
+        }
+
+        if let Some(label) = &self.instance_label
+            && !is_valid_instance_label(label)
+        {
+            return Err(SettingsError::InvalidInstanceLabel(label.clone()));
+        }
+
+        if self.metrics_max_apps == 0 {
+            return Err(SettingsError::InvalidMetricsMaxApps(self.metrics_max_apps));
+        }
+
+        self.app_names()?;
+
+        if let Some(timezone) = &self.reset_timezone
+            && ResetTimezone::parse(timezone).is_none()
+        {
+            return Err(SettingsError::InvalidTimezone(timezone.clone()));
+        }
+
+        if !(self.anomaly.std_devs.is_finite() && self.anomaly.std_devs > 0.0) {
+            return Err(SettingsError::InvalidAnomalyThreshold(
+                self.anomaly.std_devs,
+            ));
+        }
+
+        if !(1..=31).contains(&self.budget.start_day) {
+            return Err(SettingsError::InvalidBudgetStartDay(self.budget.start_day));
+        }
+
+        if let Some(milestone) = self.budget.milestones.iter().find(|m| **m == 0) {
+            return Err(SettingsError::InvalidBudgetMilestone(*milestone));
+        }
+
+        if self.mqtt.qos > mqtt::MAX_QOS {
+            return Err(SettingsError::InvalidMqttQos(self.mqtt.qos, mqtt::MAX_QOS));
+        }
+
+        if self.influx.enabled && (self.influx.org.is_empty() || self.influx.bucket.is_empty()) {
+            return Err(SettingsError::IncompleteInfluxConfig);
+        }
+
+        if self.agent.enabled && self.agent.url.is_empty() {
+            return Err(SettingsError::IncompleteAgentConfig);
+        }
+
+        if self.notifications.channels.is_empty() {
+            return Err(SettingsError::NoNotificationChannels);
+        }
+
+        let mut channels: Vec<&str> = self
+            .notifications
+            .channels
+            .iter()
+            .map(|kind| kind.as_str())
+            .collect();
+        if self.mqtt.enabled {
+            channels.push(mqtt::CHANNEL_NAME);
+        }
+        for route in &self.notifications.routes {
+            if let Some(channel) = route.unknown_channel(&channels) {
+                return Err(SettingsError::UnknownRouteChannel(
+                    route.to_string(),
+                    channel.to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `blocking_timeout_seconds`, or half the check interval when it is
+    /// unset or 0.
+    pub fn blocking_timeout(&self) -> Duration {
+        match self.blocking_timeout_seconds {
+            Some(seconds) if seconds > 0 => Duration::from_secs(seconds),
+            _ => Duration::from_secs(self.check_interval_seconds) / 2,
+        }
+    }
+
+    /// How long alerts are held back for a newly seen app.
+    pub fn new_app_grace(&self) -> TimeDelta {
+        i64::try_from(self.new_app_grace_seconds)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+            .unwrap_or(TimeDelta::MAX)
+    }
+
+    pub fn reset_timezone(&self) -> ResetTimezone {
+        self.reset_timezone
+            .as_deref()
+            .and_then(ResetTimezone::parse)
+            .unwrap_or_default()
+    }
+
+    pub fn is_metered(&self, interface: &str) -> bool {
+        self.metered_interfaces.iter().any(|name| name == interface)
+    }
+
+    /// The limit `name` is held to and where it comes from. Applications
+    /// share `data_limit`; disks, interfaces and watched paths, named with
+    /// their `disk:`, `net:` or `path:` prefix, have their own limits and
+    /// none without one. A metered interface is held to the stricter of its
+    /// own limit and `metered_data_limit`.
+    pub fn effective_limit(&self, name: &str) -> Option<EffectiveLimit> {
+        let limit = |value, source| Some(EffectiveLimit { value, source });
+        if let Some(disk) = monitor::disk_name(name) {
+            return limit(*self.disk_limits.get(disk)?, LimitSource::Disk);
+        }
+        if let Some(interface) = monitor::interface_name(name) {
+            let own = self.interface_limits.get(interface).copied();
+            let metered = self
+                .metered_data_limit
+                .filter(|_| self.is_metered(interface));
+            return match (own, metered) {
+                (Some(own), Some(metered)) if metered < own => limit(metered, LimitSource::Metered),
+                (Some(own), _) => limit(own, LimitSource::Interface),
+                (None, metered) => limit(metered?, LimitSource::Metered),
+            };
+        }
+        if let Some(label) = monitor::path_label(name) {
+            return limit(self.watched_paths.get(label)?.limit?, LimitSource::Path);
+        }
+        limit(self.data_limit, self.data_limit_source)
+    }
+
+    /// `instance_label`, or the host name when it is unset.
+    pub fn instance_label(&self) -> String {
+        self.instance_label
+            .clone()
+            .unwrap_or_else(default_instance_label)
+    }
+
+    pub fn status_listen_addr(&self) -> Option<SocketAddr> {
+        self.status_port
+            .map(|port| SocketAddr::new(self.status_bind_addr, port))
+    }
+
+    pub fn localizer(&self) -> Localizer {
+        let mut localizer = match &self.locale {
+            Some(locale) => Localizer::new(locale),
+            None => Localizer::from_env(),
+        };
+
+        if let Some(dir) = self.locales_dir.clone().or_else(default_locales_dir)
+            && let Err(e) = localizer.load_dir(&dir)
+        {
+            warn!(error = %e, ?dir, "Failed to load message catalogs");
+        }
+        localizer.with_bytes_style(self.bytes_style)
+    }
+
+    /// Formats `bytes` for tables and log messages in `bytes_style`.
+    pub fn format_bytes(&self, bytes: u64) -> String {
+        format::format_bytes(bytes, self.bytes_style)
+    }
+
+    #[cfg(feature = "monitor")]
+    pub fn resolver(&self) -> Resolver {
+        Resolver::new(IdentityConfig {
+            resolve_sandboxed_apps: self.resolve_sandboxed_apps,
+            identify_by: self.identify_by,
+            friendly_names: self.friendly_names,
+            group_containers: self.group_containers,
+        })
+    }
+
+    pub fn app_names(&self) -> Result<AppNames, AppNameError> {
+        AppNames::new(&self.app_names)
+    }
+
+    #[cfg(feature = "monitor")]
+    pub fn source(&self) -> Result<Arc<dyn CounterSource>, MonitorError> {
+        let collect = Collect {
+            cpu: self.collect_cpu,
+            memory: self.collect_memory,
+            disks: self.track_disks,
+            network: self.track_network,
+        };
+        let source = monitor::source(
+            self.backend,
+            self.resolver(),
+            collect,
+            self.snapshot_threads,
+            self.filter_chain()?,
+            self.root_process
+                .as_deref()
+                .filter(|root| !root.trim().is_empty())
+                .map(RootProcess::parse),
+        )?;
+        let names = self.app_names()?;
+        if names.is_empty() {
+            return Ok(source);
+        }
+        Ok(Arc::new(NormalizedSource::new(source, names)))
+    }
+
+    /// The owner filters, with user names resolved to UIDs or SIDs.
+    #[cfg(feature = "monitor")]
+    pub fn filter_chain(&self) -> Result<FilterChain, FilterError> {
+        FilterChain::resolve(
+            self.ignore_system_processes,
+            &self.ignored_users,
+            &self.watched_users,
+        )
+    }
+
+    /// Every setting that differs from `other`, sorted by name. Nested
+    /// tables and maps are compared entry by entry, so a changed
+    /// `notifications.cooldown_seconds` or `disk_limits.sda1` is reported on
+    /// its own.
+    pub fn diff(&self, other: &Settings) -> Vec<SettingsFieldChange> {
+        let mut changes = Vec::new();
+        let value = |settings: &Settings| serde_json::to_value(settings).unwrap_or_default();
+        diff_values(String::new(), value(self), value(other), &mut changes);
+        changes.sort_by(|a, b| a.field.cmp(&b.field));
+        changes
+    }
+}
+
+/// Settings whose values are kept out of logs.
+/// Whether `label` is safe as a metric label, MQTT topic level and file
+/// name: ASCII letters, digits, `.`, `-` and `_`, starting with a letter or
+/// digit.
+pub fn is_valid_instance_label(label: &str) -> bool {
+    label.len() <= MAX_INSTANCE_LABEL_LEN
+        && label
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric())
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+/// The host name, with anything `is_valid_instance_label` rejects
+/// replaced, or `localhost` when there is none.
+pub fn default_instance_label() -> String {
+    let label = host_name()
+        .unwrap_or_default()
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                true => c,
+                false => '-',
+            },
+        )
+        .take(MAX_INSTANCE_LABEL_LEN)
+        .collect::<String>();
+    match is_valid_instance_label(&label) {
+        true => label,
+        false => "localhost".to_string(),
+    }
+}
+
+#[cfg(feature = "monitor")]
+fn host_name() -> Option<String> {
+    sysinfo::System::host_name()
+}
+
+#[cfg(not(feature = "monitor"))]
+fn host_name() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+}
+
+const SECRET_FIELDS: [&str; 5] = [
+    "mqtt.password",
+    "influx.token",
+    "agent.token",
+    "collector.token",
+    "otel.headers",
+];
+
+/// Whether the setting named `field`, such as `mqtt.password` or
+/// `otel.headers.authorization`, holds credentials.
+pub fn is_secret_field(field: &str) -> bool {
+    SECRET_FIELDS.iter().any(|secret| {
+        field == *secret
+            || field
+                .strip_prefix(secret)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+/// One setting that differs between two `Settings`, named by its path in
+/// the config file. A value that is unset on one side is `null`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SettingsFieldChange {
+    pub field: String,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+impl SettingsFieldChange {
+    /// Whether the values are credentials that must not be logged.
+    pub fn is_secret(&self) -> bool {
+        is_secret_field(&self.field)
+    }
+}
+
+impl std::fmt::Display for SettingsFieldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_secret() {
+            return write!(f, "{} changed", self.field);
+        }
+        write!(f, "{}: {} -> {}", self.field, self.old, self.new)
+    }
+}
+
+/// Sent to library consumers when a reload applies settings that differ
+/// from the running ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingsChanged {
+    pub old: Arc<Settings>,
+    pub new: Arc<Settings>,
+    pub changes: Vec<SettingsFieldChange>,
+}
+
+impl SettingsChanged {
+    /// The event for replacing `old` with `new`, or `None` when nothing
+    /// differs.
+    pub fn new(old: Settings, new: Settings) -> Option<Self> {
+        let changes = old.diff(&new);
+        if changes.is_empty() {
+            return None;
+        }
+        Some(Self {
+            old: Arc::new(old),
+            new: Arc::new(new),
+            changes,
+        })
+    }
+
+    /// The changes on one line, for the reload log message.
+    pub fn summary(&self) -> String {
+        self.changes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Updates `item` from `old` to `new`, leaving the parts that did not
+/// change as they are.
+fn merge_toml(item: &mut toml_edit::Item, old: &serde_json::Value, new: &serde_json::Value) {
+    use serde_json::Value;
+
+    if old == new {
+        return;
+    }
+    let Value::Object(new) = new else {
+        let mut value = toml_value(new);
+        if let (Some(value), Some(existing)) = (&mut value, item.as_value()) {
+            *value.decor_mut() = existing.decor().clone();
+        }
+        *item = value.map_or(toml_edit::Item::None, toml_edit::Item::Value);
+        return;
+    };
+    if !item.is_table_like() {
+        *item = toml_edit::Item::Table(toml_edit::Table::new());
+    }
+    let Some(table) = item.as_table_like_mut() else {
+        return;
+    };
+    let old = old.as_object();
+    for (key, new) in new {
+        let old = old.and_then(|old| old.get(key)).unwrap_or(&Value::Null);
+        if new.is_null() {
+            table.remove(key);
+            continue;
+        }
+        merge_toml(table.entry(key).or_insert(toml_edit::Item::None), old, new);
+    }
+    for key in old.into_iter().flat_map(|old| old.keys()) {
+        if !new.contains_key(key) {
+            table.remove(key);
+        }
+    }
+}
+
+fn toml_value(value: &serde_json::Value) -> Option<toml_edit::Value> {
+    use serde_json::Value;
+
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(value) => (*value).into(),
+        Value::Number(number) => match number.as_i64() {
+            Some(number) => number.into(),
+            None => number.as_f64()?.into(),
+        },
+        Value::String(value) => value.as_str().into(),
+        Value::Array(values) => values
+            .iter()
+            .filter_map(toml_value)
+            .collect::<toml_edit::Array>()
+            .into(),
+        Value::Object(values) => values
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), toml_value(value)?)))
+            .collect::<toml_edit::InlineTable>()
+            .into(),
+    })
+}
+
+fn json_value(value: &toml_edit::Value) -> serde_json::Value {
+    use toml_edit::Value;
+
+    match value {
+        Value::String(value) => value.value().as_str().into(),
+        Value::Integer(value) => (*value.value()).into(),
+        Value::Float(value) => (*value.value()).into(),
+        Value::Boolean(value) => (*value.value()).into(),
+        Value::Datetime(value) => value.value().to_string().into(),
+        Value::Array(values) => values.iter().map(json_value).collect(),
+        Value::InlineTable(values) => values
+            .iter()
+            .map(|(key, value)| (key.to_string(), json_value(value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+    }
+}
+
+fn diff_values(
+    field: String,
+    old: serde_json::Value,
+    new: serde_json::Value,
+    changes: &mut Vec<SettingsFieldChange>,
+) {
+    use serde_json::Value;
+
+    match (old, new) {
+        (Value::Object(mut old), Value::Object(new)) => {
+            let join = |key: &str| match field.as_str() {
+                "" => key.to_string(),
+                _ => format!("{}.{}", field, key),
+            };
+            for (key, new) in new {
+                let old = old.remove(&key).unwrap_or_default();
+                diff_values(join(&key), old, new, changes);
+            }
+            for (key, old) in old {
+                diff_values(join(&key), old, Value::Null, changes);
+            }
+        }
+        (old, new) if old != new => changes.push(SettingsFieldChange { field, old, new }),
+        _ => {}
+    }
+}
+
+fn default_locales_dir() -> Option<PathBuf> {
+    paths::project_dirs().map(|proj_dirs| proj_dirs.config_dir().join("locales"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::notification::ChannelKind;
+    use crate::paths::PathSource;
+    use crate::system_log::SyslogFacility;
+
+    #[test]
+    fn test_settings_default() {
+        let settings = Settings::default();
+        assert_eq!(settings.data_limit, DEFAULT_DATA_LIMIT);
+        assert_eq!(settings.check_interval_seconds, DEFAULT_CHECK_INTERVAL);
+        assert_eq!(
+            settings.persistence_interval_seconds,
+            DEFAULT_PERSISTENCE_INTERVAL
+        );
+        assert!(settings.validate().is_ok());
+        assert_eq!(
+            settings.blocking_timeout(),
+            Duration::from_secs(DEFAULT_CHECK_INTERVAL) / 2
+        );
+        let settings = Settings {
+            blocking_timeout_seconds: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(settings.blocking_timeout(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_settings_load() {
+        let settings = Settings::new().unwrap();
+        assert!(settings.data_limit >= MIN_DATA_LIMIT);
+        assert!(settings.check_interval_seconds >= MIN_CHECK_INTERVAL);
+        assert!(settings.persistence_interval_seconds >= MIN_PERSISTENCE_INTERVAL);
+    }
+
+    #[test]
+    fn test_validate_data_limit() {
+        let settings = Settings {
+            data_limit: MIN_DATA_LIMIT - 1,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+
+        let settings = Settings {
+            data_limit: MIN_DATA_LIMIT,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_effective_limit() {
+        let limit = |value, source| Some(EffectiveLimit { value, source });
+        let settings = Settings::default();
+        for app in ["firefox", "Firefox.exe", "container:postgres", ""] {
+            assert_eq!(
+                settings.effective_limit(app),
+                limit(DEFAULT_DATA_LIMIT, LimitSource::Default)
+            );
+        }
+
+        let settings = settings.with_value("data_limit", "2GB").unwrap();
+        assert_eq!(
+            settings.effective_limit("firefox"),
+            limit(2_000_000_000, LimitSource::Global)
+        );
+        assert_eq!(
+            settings.effective_limit("curl"),
+            settings.effective_limit("firefox")
+        );
+        // Other settings leave where `data_limit` came from alone.
+        let settings = settings.with_value("check_interval_seconds", "30").unwrap();
+        assert_eq!(
+            settings.effective_limit("firefox").unwrap().source,
+            LimitSource::Global
+        );
+
+        // Setting the built-in value is still setting it.
+        let settings = settings
+            .with_value("data_limit", &DEFAULT_DATA_LIMIT.to_string())
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(settings.effective_limit("firefox")).unwrap(),
+            serde_json::json!({ "value": DEFAULT_DATA_LIMIT, "source": "global" })
+        );
+
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        fs::write(&config_path, format!("data_limit = {}", DEFAULT_DATA_LIMIT)).unwrap();
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(settings.data_limit_source, LimitSource::Global);
+        fs::write(&config_path, "check_interval_seconds = 30").unwrap();
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(settings.data_limit_source, LimitSource::Default);
+    }
+
+    #[test]
+    fn test_pseudo_app_limits() {
+        let limit = |value, source| Some(EffectiveLimit { value, source });
+        let settings = Settings {
+            data_limit: 100,
+            disk_limits: HashMap::from([("sda".to_string(), 10)]),
+            interface_limits: HashMap::from([
+                ("eth0".to_string(), 5000),
+                ("wwan0".to_string(), 2000),
+                ("usb0".to_string(), 100),
+            ]),
+            metered_interfaces: vec!["wwan0".to_string(), "usb0".to_string(), "ppp0".to_string()],
+            metered_data_limit: Some(500),
+            watched_paths: HashMap::from([
+                (
+                    "downloads".to_string(),
+                    WatchedPath {
+                        limit: Some(1000),
+                        ..WatchedPath::default()
+                    },
+                ),
+                ("cache".to_string(), WatchedPath::default()),
+            ]),
+            ..Settings::default()
+        };
+        assert_eq!(
+            settings.effective_limit("disk:sda"),
+            limit(10, LimitSource::Disk)
+        );
+        // Disks without an entry have no limit, not `data_limit`.
+        assert_eq!(settings.effective_limit("disk:sdb"), None);
+        assert_eq!(
+            settings.effective_limit("net:eth0"),
+            limit(5000, LimitSource::Interface)
+        );
+        // The stricter of the metered and per-interface limits wins.
+        assert_eq!(
+            settings.effective_limit("net:wwan0"),
+            limit(500, LimitSource::Metered)
+        );
+        assert_eq!(
+            settings.effective_limit("net:usb0"),
+            limit(100, LimitSource::Interface)
+        );
+        assert_eq!(
+            settings.effective_limit("net:ppp0"),
+            limit(500, LimitSource::Metered)
+        );
+        assert_eq!(settings.effective_limit("net:wlan0"), None);
+        assert_eq!(
+            settings.effective_limit("path:downloads"),
+            limit(1000, LimitSource::Path)
+        );
+        assert_eq!(settings.effective_limit("path:cache"), None);
+        assert_eq!(settings.effective_limit("path:music"), None);
+        assert_eq!(Settings::default().effective_limit("net:wwan0"), None);
+    }
+
+    #[test]
+    fn test_validate_check_interval() {
+        let settings = Settings {
+            check_interval_seconds: MIN_CHECK_INTERVAL - 1,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+
+        let settings = Settings {
+            check_interval_seconds: MIN_CHECK_INTERVAL,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_persistence_interval() {
+        let settings = Settings {
+            persistence_interval_seconds: MIN_PERSISTENCE_INTERVAL - 1,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+
+        let settings = Settings {
+            persistence_interval_seconds: MIN_PERSISTENCE_INTERVAL,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_history_retention() {
+        let settings = Settings {
+            history_retention_seconds: MIN_HISTORY_RETENTION - 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::InvalidHistoryRetention(_, _))
+        ));
+
+        let settings = Settings {
+            history_retention_seconds: MIN_HISTORY_RETENTION,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+
+        let settings = Settings {
+            history_days: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::InvalidHistoryDays(0, MIN_HISTORY_DAYS))
+        ));
+    }
+
+    #[test]
+    fn test_settings_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+
+        let config_content = format!(
+            r#"
+            data_limit = {}
+            check_interval_seconds = {}
+            persistence_interval_seconds = {}
+            "#,
+            MIN_DATA_LIMIT * 2,
+            MIN_CHECK_INTERVAL * 2,
+            MIN_PERSISTENCE_INTERVAL * 2
+        );
+        fs::write(&config_path, config_content).unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(settings.data_limit, MIN_DATA_LIMIT * 2);
+        assert_eq!(settings.check_interval_seconds, MIN_CHECK_INTERVAL * 2);
+        assert_eq!(
+            settings.persistence_interval_seconds,
+            MIN_PERSISTENCE_INTERVAL * 2
+        );
+    }
+
+    #[test]
+    fn test_notification_settings_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+
+        fs::write(
+            &config_path,
+            r#"
+            notify_on_start = true
+
+            [notifications]
+            icon = "/usr/share/icons/dg.png"
+            timeout_ms = 5000
+            sound = false
+            channels = ["log"]
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(settings.data_limit, DEFAULT_DATA_LIMIT);
+        assert_eq!(settings.notifications.app_name, "Data Guardian");
+        assert_eq!(
+            settings.notifications.icon.as_deref(),
+            Some("/usr/share/icons/dg.png")
+        );
+        assert_eq!(settings.notifications.timeout_ms, Some(5000));
+        assert!(!settings.notifications.sound);
+        assert_eq!(settings.notifications.channels, [ChannelKind::Log]);
+        assert!(settings.notify_on_start);
+        assert!(!settings.notify_on_shutdown);
+    }
+
+    #[test]
+    fn test_settings_localizer() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("de.toml"), "[alert]\ntitle = \"Achtung\"\n").unwrap();
+
+        let settings = Settings {
+            locale: Some("de_AT".to_string()),
+            locales_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let localizer = settings.localizer();
+        assert_eq!(localizer.locale(), "de");
+        assert_eq!(localizer.message("alert.title"), "Achtung");
+        assert_eq!(localizer.message("action.snooze"), "1 Std. schlummern");
+    }
+
+    #[test]
+    fn test_escalation_settings_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            reset_period = "daily"
+            reset_timezone = "Europe/Berlin"
+            forecast_alerts = true
+
+            [escalation]
+            repeat_threshold = 2
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(settings.reset_period, ResetPeriod::Daily);
+        assert_eq!(
+            settings.reset_timezone(),
+            ResetTimezone::Iana(chrono_tz::Europe::Berlin)
+        );
+        assert!(settings.forecast_alerts);
+        assert_eq!(settings.escalation.repeat_threshold, 2);
+        assert_eq!(
+            settings.escalation.critical_cooldown_seconds,
+            EscalationConfig::default().critical_cooldown_seconds
+        );
+
+        let settings = Settings {
+            reset_timezone: Some("Mars/Olympus_Mons".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::InvalidTimezone(_))
+        ));
+        assert_eq!(settings.reset_timezone(), ResetTimezone::Local);
+    }
+
+    #[test]
+    fn test_anomaly_settings_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            [anomaly]
+            enabled = true
+            std_devs = 3.5
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert!(settings.anomaly.enabled);
+        assert_eq!(settings.anomaly.std_devs, 3.5);
+        assert_eq!(
+            settings.anomaly.warmup_ticks,
+            AnomalyConfig::default().warmup_ticks
+        );
+
+        let settings = Settings {
+            anomaly: AnomalyConfig {
+                std_devs: 0.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::InvalidAnomalyThreshold(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_owner_filters_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            ignore_system_processes = true
+            ignored_users = ["root", "1001"]
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert!(settings.ignore_system_processes);
+        assert_eq!(settings.ignored_users, ["root", "1001"]);
+        assert!(settings.watched_users.is_empty());
+        #[cfg(feature = "monitor")]
+        {
+            let filter = settings.filter_chain().unwrap();
+            assert!(!filter.allows(Some(&crate::filter::Owner::Uid(1001))));
+
+            let settings = Settings {
+                watched_users: vec!["no-such-user-dg".to_string()],
+                ..Default::default()
+            };
+            assert!(settings.filter_chain().is_err());
+            assert!(settings.source().is_err());
+        }
+    }
+
+    #[test]
+    fn test_budget_settings_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            [budget]
+            total_bytes = 1000000000000
+            start_day = 15
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(settings.budget.total_bytes, Some(1_000_000_000_000));
+        assert_eq!(settings.budget.start_day, 15);
+        assert_eq!(settings.budget.milestones, [50, 75, 90, 100]);
+
+        let settings = Settings {
+            budget: BudgetConfig {
+                start_day: 32,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::InvalidBudgetStartDay(32))
+        ));
+        let settings = Settings {
+            budget: BudgetConfig {
+                milestones: vec![50, 0],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::InvalidBudgetMilestone(0))
+        ));
+    }
+
+    #[test]
+    fn test_pause_conditions_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            pause_on_battery = true
+            only_when_interface_matches = "usb*"
+            resolve_sandboxed_apps = true
+            identify_by = "bundle"
+            friendly_names = true
+            group_containers = true
+            backend = "cgroup"
+            track_disks = true
+            track_network = true
+            metered_interfaces = ["wwan0", "usb0"]
+            metered_data_limit = 500
+            max_bytes_per_second = 0
+            max_tracked_apps = 500
+            snapshot_threads = 4
+
+            [disk_limits]
+            nvme0n1p2 = 1000
+
+            [interface_limits]
+            eth0 = 5000
+            wwan0 = 2000
+            usb0 = 100
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert!(settings.pause_on_battery);
+        assert_eq!(
+            settings.only_when_interface_matches.as_deref(),
+            Some("usb*")
+        );
+        assert!(!Settings::default().pause_on_battery);
+        assert_eq!(settings.identify_by, IdentifyBy::Bundle);
+        assert!(settings.friendly_names);
+        assert!(!Settings::default().friendly_names);
+        assert!(settings.group_containers);
+        assert_eq!(settings.backend, Backend::Cgroup);
+        assert_eq!(Settings::default().backend, Backend::Process);
+        assert!(settings.track_disks);
+        assert_eq!(settings.max_bytes_per_second, 0);
+        assert_eq!(settings.max_tracked_apps, 500);
+        assert_eq!(settings.snapshot_threads, 4);
+        assert_eq!(
+            Settings::default().max_bytes_per_second,
+            DEFAULT_MAX_BYTES_PER_SECOND
+        );
+        assert_eq!(settings.disk_limits.get("nvme0n1p2"), Some(&1000));
+        assert!(settings.track_network);
+        let limit = |name| settings.effective_limit(name).map(|limit| limit.value);
+        assert_eq!(limit("net:eth0"), Some(5000));
+        assert_eq!(limit("net:wwan0"), Some(500));
+        assert_eq!(limit("net:usb0"), Some(100));
+        assert_eq!(limit("net:wlan0"), None);
+        #[cfg(feature = "monitor")]
+        {
+            assert!(settings.resolver().is_enabled());
+            assert!(!Settings::default().resolver().is_enabled());
+        }
+    }
+
+    #[test]
+    fn test_watched_paths_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            watched_paths_interval_seconds = 600
+
+            [watched_paths.downloads]
+            path = "/home/me/Downloads"
+            limit = 5000000000
+
+            [watched_paths.cache]
+            path = "/var/cache"
+            max_depth = 4
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(settings.watched_paths_interval_seconds, 600);
+        assert_eq!(
+            settings.watched_paths["downloads"],
+            WatchedPath {
+                path: PathBuf::from("/home/me/Downloads"),
+                limit: Some(5_000_000_000),
+                ..WatchedPath::default()
+            }
+        );
+        assert_eq!(settings.watched_paths["cache"].limit, None);
+        assert_eq!(settings.watched_paths["cache"].max_depth, 4);
+
+        let mut settings = Settings::default();
+        settings
+            .watched_paths
+            .insert("empty".to_string(), WatchedPath::default());
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::MissingWatchedPath(label)) if label == "empty"
+        ));
+        let settings = Settings {
+            watched_paths_interval_seconds: 1,
+            ..Settings::default()
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::InvalidWatchInterval(1, _))
+        ));
+    }
+
+    #[test]
+    fn test_metrics_settings_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            metrics_listen_addr = "127.0.0.1:9184"
+            metrics_max_apps = 20
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(
+            settings.metrics_listen_addr,
+            Some("127.0.0.1:9184".parse().unwrap())
+        );
+        assert_eq!(settings.metrics_max_apps, 20);
+
+        let settings = Settings {
+            metrics_max_apps: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::InvalidMetricsMaxApps(0))
+        ));
+    }
+
+    #[test]
+    fn test_instance_label() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(&config_path, r#"instance_label = "office-nas_2.lan""#).unwrap();
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(settings.instance_label(), "office-nas_2.lan");
+
+        for label in ["", "-nas", "office nas", "nas/1", "ünas", &"a".repeat(64)] {
+            let settings = Settings {
+                instance_label: Some(label.to_string()),
+                ..Default::default()
+            };
+            assert!(
+                matches!(
+                    settings.validate(),
+                    Err(SettingsError::InvalidInstanceLabel(_))
+                ),
+                "{:?}",
+                label
+            );
+        }
+
+        let default = Settings::default().instance_label();
+        assert!(is_valid_instance_label(&default), "{:?}", default);
+    }
+
+    #[test]
+    fn test_status_listen_addr() {
+        let settings = Settings::default();
+        assert_eq!(settings.status_listen_addr(), None);
+
+        let settings = Settings {
+            status_port: Some(9185),
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.status_listen_addr(),
+            Some("127.0.0.1:9185".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_otel_settings_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            [otel]
+            enabled = true
+            endpoint = "http://collector:4318"
+
+            [otel.headers]
+            authorization = "Bearer token"
+
+            [otel.resource_attributes]
+            "deployment.environment" = "lab"
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert!(settings.otel.enabled);
+        assert_eq!(
+            settings.otel.endpoint.as_deref(),
+            Some("http://collector:4318")
+        );
+        assert_eq!(settings.otel.headers["authorization"], "Bearer token");
+        assert_eq!(
+            settings.otel.resource_attributes["deployment.environment"],
+            "lab"
+        );
+        assert_eq!(settings.otel.service_name, "data-guardian");
+    }
+
+    #[test]
+    fn test_logging_settings_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            log_level = "debug"
+            log_format = "json"
+            log_file = "/var/log/dg.log"
+            log_rotation = "size"
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(settings.log_level, "debug");
+        assert_eq!(settings.log_format, LogFormat::Json);
+        assert_eq!(settings.log_file, Some(PathBuf::from("/var/log/dg.log")));
+        assert_eq!(settings.log_rotation, LogRotation::Size);
+        assert_eq!(settings.log_max_files, logging::DEFAULT_LOG_MAX_FILES);
+
+        let settings = Settings {
+            log_level: "loud".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::InvalidLogLevel(_))
+        ));
+    }
+
+    #[test]
+    fn test_mqtt_settings_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            [mqtt]
+            enabled = true
+            broker = "mqtt://homeassistant.local:1883"
+            username = "dg"
+            password = "secret"
+            qos = 0
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert!(settings.mqtt.enabled);
+        assert_eq!(settings.mqtt.broker, "mqtt://homeassistant.local:1883");
+        assert_eq!(settings.mqtt.qos, 0);
+        assert_eq!(settings.mqtt.topic_prefix, mqtt::DEFAULT_TOPIC_PREFIX);
+
+        let mut settings = Settings::default();
+        settings.mqtt.qos = 3;
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::InvalidMqttQos(3, 2))
+        ));
+    }
+
+    #[test]
+    fn test_influx_settings() {
+        let mut settings = Settings::default();
+        settings.influx.enabled = true;
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::IncompleteInfluxConfig)
+        ));
+
+        settings.influx.org = "home".to_string();
+        settings.influx.bucket = "usage".to_string();
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_agent_settings_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            [agent]
+            enabled = true
+            url = "https://collector.example.com/collect"
+            token = "secret"
+
+            [collector]
+            listen_addr = "127.0.0.1:9000"
+            "#,
+        )
+        .unwrap();
+
+        let mut settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(settings.agent.token.as_deref(), Some("secret"));
+        assert_eq!(settings.agent.max_queued_snapshots, 100);
+        assert_eq!(settings.collector.listen_addr.port(), 9000);
+        assert!(settings.validate().is_ok());
+
+        settings.agent.url.clear();
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::IncompleteAgentConfig)
+        ));
+    }
+
+    #[test]
+    fn test_notification_channels_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            [notifications]
+            channels = ["syslog", "eventlog"]
+
+            [notifications.syslog]
+            facility = "local3"
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(
+            settings.notifications.channels,
+            [ChannelKind::Syslog, ChannelKind::EventLog]
+        );
+        assert_eq!(
+            settings.notifications.syslog.facility,
+            SyslogFacility::Local3
+        );
+
+        fs::write(
+            &config_path,
+            r#"
+            [notifications]
+            channels = []
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            Settings::from_file(&config_path),
+            Err(SettingsError::NoNotificationChannels)
+        ));
+    }
+
+    #[test]
+    fn test_notification_routes_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(
+            &config_path,
+            r#"
+            [notifications]
+            channels = ["desktop", "syslog"]
+
+            [[notifications.routes]]
+            severity = "critical"
+            channels = ["desktop", "syslog"]
+
+            [[notifications.routes]]
+            app = "backup-*"
+            channels = ["syslog"]
+            "#,
+        )
+        .unwrap();
+
+        let mut settings = Settings::from_file(&config_path).unwrap();
+        assert_eq!(settings.notifications.routes.len(), 2);
+        assert_eq!(
+            settings.notifications.routes[1].app.as_deref(),
+            Some("backup-*")
+        );
+        assert!(settings.validate().is_ok());
+
+        settings.notifications.routes[1].channels = vec!["slack".to_string()];
+        let err = settings.validate().unwrap_err();
+        assert!(matches!(err, SettingsError::UnknownRouteChannel(..)));
+        assert!(
+            err.to_string()
+                .contains(r#"{ app = "backup-*", channels = ["slack"] }"#),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_settings_load_config_path() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        fs::write(
+            &config_path,
+            "data_limit = 2097152\ndata_dir = \"/srv/dg\"\n",
+        )
+        .unwrap();
+        let resolved = |path: &std::path::Path, source| ResolvedPath {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let settings = Settings::load(Some(&resolved(&config_path, PathSource::Flag))).unwrap();
+        assert_eq!(settings.data_limit, 2097152);
+        assert_eq!(settings.data_dir, Some(PathBuf::from("/srv/dg")));
+
+        let missing = dir.path().join("missing.toml");
+        assert!(Settings::load(Some(&resolved(&missing, PathSource::Flag))).is_err());
+        assert!(Settings::load(Some(&resolved(&missing, PathSource::ProjectDirs))).is_ok());
+    }
+
+    #[test]
+    fn test_settings_serialization() {
+        let settings = Settings::default();
+        let serialized = serde_json::to_string(&settings).unwrap();
+        let deserialized: Settings = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(settings, deserialized);
+    }
+
+    #[test]
+    fn test_validate_and_clamp() {
+        let settings = Settings {
+            check_interval_seconds: 0,
+            persistence_interval_seconds: 5,
+            metrics_max_apps: 0,
+            budget: BudgetConfig {
+                start_day: 40,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+
+        let (clamped, adjustments) = settings.validate_and_clamp().unwrap();
+        assert!(clamped.validate().is_ok());
+        assert_eq!(clamped.check_interval_seconds, MIN_CHECK_INTERVAL);
+        assert_eq!(
+            clamped.persistence_interval_seconds,
+            MIN_PERSISTENCE_INTERVAL
+        );
+        assert_eq!(clamped.budget.start_day, 31);
+        assert_eq!(
+            adjustments
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "budget.start_day: 40 -> 31",
+                "check_interval_seconds: 0 -> 1",
+                "metrics_max_apps: 0 -> 1",
+                "persistence_interval_seconds: 5 -> 10",
+            ]
+        );
+
+        let (_, adjustments) = Settings::default().validate_and_clamp().unwrap();
+        assert!(adjustments.is_empty());
+
+        // Values without a nearest valid one still fail.
+        let settings = Settings {
+            log_level: "loud".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            settings.validate_and_clamp(),
+            Err(SettingsError::InvalidLogLevel(_))
+        ));
+    }
+
+    #[test]
+    fn test_validation_mode_from_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test_config.toml");
+        fs::write(&config_path, "check_interval_seconds = 0\n").unwrap();
+        assert!(matches!(
+            Settings::from_file(&config_path),
+            Err(SettingsError::InvalidCheckInterval(0, _))
+        ));
+
+        fs::write(
+            &config_path,
+            "validation = \"clamp\"\ncheck_interval_seconds = 0\n",
+        )
+        .unwrap();
+        let config = ResolvedPath {
+            path: config_path,
+            source: PathSource::Flag,
+        };
+        let (settings, adjustments) = Settings::load_adjusted(Some(&config)).unwrap();
+        assert_eq!(settings.validation, ValidationMode::Clamp);
+        assert_eq!(settings.check_interval_seconds, MIN_CHECK_INTERVAL);
+        assert_eq!(adjustments.len(), 1);
+        assert_eq!(adjustments[0].field, "check_interval_seconds");
+    }
+
+    #[test]
+    fn test_get_and_set_values() {
+        let settings = Settings::default();
+        assert_eq!(
+            settings.get("data_limit").unwrap(),
+            serde_json::json!(DEFAULT_DATA_LIMIT)
+        );
+        assert_eq!(
+            settings.get("notifications.sound").unwrap(),
+            serde_json::json!(true)
+        );
+        assert!(matches!(
+            settings.get("notifications.volume"),
+            Err(SettingsError::UnknownSetting(_))
+        ));
+
+        let updated = settings.with_value("data_limit", "2GB").unwrap();
+        assert_eq!(updated.data_limit, 2_000_000_000);
+        let updated = updated.with_value("reset_period", "daily").unwrap();
+        assert_eq!(updated.reset_period, ResetPeriod::Daily);
+        let updated = updated.with_value("mqtt.topic_prefix", "42").unwrap();
+        assert_eq!(updated.mqtt.topic_prefix, "42");
+        let updated = updated.with_value("disk_limits.sda1", "500").unwrap();
+        assert_eq!(updated.disk_limits["sda1"], 500);
+        let updated = updated.with_value("anomaly.std_devs", "4").unwrap();
+        assert_eq!(updated.anomaly.std_devs, 4.0);
+
+        assert!(matches!(
+            settings.with_value("no_such_setting", "1"),
+            Err(SettingsError::UnknownSetting(_))
+        ));
+        assert!(matches!(
+            settings.with_value("data_limit.bytes", "1"),
+            Err(SettingsError::UnknownSetting(_))
+        ));
+        assert!(matches!(
+            settings.with_value("data_limit", "lots"),
+            Err(SettingsError::InvalidValue(..))
+        ));
+        assert!(matches!(
+            settings.with_value("check_interval_seconds", "0"),
+            Err(SettingsError::InvalidCheckInterval(..))
+        ));
+    }
+
+    #[test]
+    fn test_save_merges_into_file() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        fs::write(
+            &config_path,
+            r#"# Managed by hand
+data_limit = 2097152 # 2 MiB
+log_level = "debug"
+
+[notifications]
+# Quiet, please
+sound = false
+"#,
+        )
+        .unwrap();
+
+        let mut settings = Settings::from_file(&config_path).unwrap();
+        settings.data_limit = 100 * 1024 * 1024;
+        settings.notifications.timeout_ms = Some(5000);
+        settings.disk_limits = HashMap::from([("sda1".to_string(), 500)]);
+        let changes = settings.save(&config_path).unwrap();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| change.field.as_str())
+                .collect::<Vec<_>>(),
+            ["data_limit", "disk_limits.sda1", "notifications.timeout_ms"]
+        );
+
+        let text = fs::read_to_string(&config_path).unwrap();
+        assert!(text.starts_with("# Managed by hand\ndata_limit = 104857600 # 2 MiB\n"));
+        assert!(text.contains("# Quiet, please\nsound = false\ntimeout_ms = 5000\n"));
+        assert!(text.contains("[disk_limits]\nsda1 = 500\n"));
+        assert_eq!(Settings::from_file(&config_path).unwrap(), settings);
+        assert!(settings.save(&config_path).unwrap().is_empty());
+
+        settings.disk_limits.clear();
+        settings.notifications.timeout_ms = None;
+        settings.save(&config_path).unwrap();
+        let text = fs::read_to_string(&config_path).unwrap();
+        assert!(!text.contains("sda1") && !text.contains("timeout_ms"));
+        assert_eq!(Settings::from_file(&config_path).unwrap(), settings);
+
+        let new_path = dir.path().join("new").join("config.toml");
+        settings.save(&new_path).unwrap();
+        assert_eq!(Settings::from_file(&new_path).unwrap(), settings);
+    }
+
+    /// Settings where every field differs from the default. Listing each
+    /// field without `..Default::default()` makes a new field fail to build
+    /// here until it is covered.
+    fn changed_settings() -> Settings {
+        Settings {
+            validation: ValidationMode::Clamp,
+            data_limit: 2 * DEFAULT_DATA_LIMIT,
+            data_limit_source: LimitSource::Global,
+            check_interval_seconds: 30,
+            blocking_timeout_seconds: Some(5),
+            persistence_interval_seconds: 600,
+            data_dir: Some(PathBuf::from("/var/lib/dg")),
+            runtime_dir: Some(PathBuf::from("/run/dg")),
+            instance_label: Some("office-nas".to_string()),
+            history_capacity: DEFAULT_HISTORY_CAPACITY + 1,
+            history_retention_seconds: 3600,
+            history_days: 90,
+            history_months: 12,
+            alerts_log: true,
+            compression_dictionary: DictionaryKind::Trained,
+            notifications: NotificationConfig {
+                sound: false,
+                ..Default::default()
+            },
+            locale: Some("de".to_string()),
+            locales_dir: Some(PathBuf::from("/etc/dg/locales")),
+            bytes_style: BytesStyle::Binary,
+            reset_period: ResetPeriod::Monthly,
+            reset_timezone: Some("Europe/Berlin".to_string()),
+            forecast_alerts: true,
+            new_app_grace_seconds: 600,
+            notify_on_start: true,
+            notify_on_shutdown: true,
+            escalation: EscalationConfig {
+                repeat_threshold: 9,
+                ..Default::default()
+            },
+            anomaly: AnomalyConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            budget: BudgetConfig {
+                total_bytes: Some(DEFAULT_DATA_LIMIT),
+                ..Default::default()
+            },
+            self_check: SelfCheckConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            pause_on_battery: true,
+            only_when_interface_matches: Some("wlan0".to_string()),
+            backend: Backend::Cgroup,
+            ignore_system_processes: true,
+            ignored_users: vec!["nobody".to_string()],
+            watched_users: vec!["alice".to_string()],
+            root_process: Some("tmux".to_string()),
+            max_bytes_per_second: 1024,
+            max_tracked_apps: DEFAULT_MAX_TRACKED_APPS + 1,
+            snapshot_threads: 4,
+            collect_cpu: true,
+            collect_memory: true,
+            cpu_limit_seconds: Some(60),
+            memory_limit_bytes: Some(1024),
+            track_disks: true,
+            disk_limits: HashMap::from([("sda1".to_string(), 500)]),
+            track_network: true,
+            interface_limits: HashMap::from([("eth0".to_string(), 5000)]),
+            metered_interfaces: vec!["wwan0".to_string()],
+            metered_data_limit: Some(500),
+            watched_paths: HashMap::from([(
+                "downloads".to_string(),
+                WatchedPath {
+                    path: PathBuf::from("/tmp"),
+                    ..Default::default()
+                },
+            )]),
+            watched_paths_interval_seconds: 120,
+            resolve_sandboxed_apps: true,
+            identify_by: IdentifyBy::Bundle,
+            friendly_names: true,
+            group_containers: true,
+            app_names: AppNameConfig {
+                lowercase: true,
+                ..Default::default()
+            },
+            other_monitors: OtherMonitorsConfig {
+                quiet_network_alerts: true,
+                ..Default::default()
+            },
+            metrics_listen_addr: Some("127.0.0.1:9090".parse().unwrap()),
+            metrics_max_apps: DEFAULT_METRICS_MAX_APPS + 1,
+            status_port: Some(8080),
+            status_bind_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            otel: OtelConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            mqtt: MqttConfig {
+                password: Some("secret".to_string()),
+                ..Default::default()
+            },
+            influx: InfluxConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            tray: TrayConfig { enabled: false },
+            agent: AgentConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            collector: CollectorConfig {
+                token: Some("secret".to_string()),
+                ..Default::default()
+            },
+            log_level: "debug".to_string(),
+            log_format: LogFormat::Json,
+            log_file: Some(PathBuf::from("/var/log/dg.log")),
+            log_rotation: LogRotation::Size,
+            log_max_size_bytes: logging::DEFAULT_LOG_MAX_SIZE + 1,
+            log_max_files: logging::DEFAULT_LOG_MAX_FILES + 1,
+        }
+    }
+
+    #[test]
+    fn test_diff_every_field() {
+        let old = Settings::default();
+        let new = changed_settings();
+        assert!(old.diff(&old).is_empty());
+
+        // One change per field, each under its own name.
+        let changes = old.diff(&new);
+        let fields = changes
+            .iter()
+            .map(|change| change.field.split('.').next().unwrap())
+            .collect::<Vec<_>>();
+        let value = serde_json::to_value(&old).unwrap();
+        let expected = value.as_object().unwrap().keys().collect::<Vec<_>>();
+        assert_eq!(fields, expected);
+
+        let find = |field: &str| changes.iter().find(|change| change.field == field).unwrap();
+        assert_eq!(find("check_interval_seconds").old, 60);
+        assert_eq!(find("check_interval_seconds").new, 30);
+        assert_eq!(find("notifications.sound").new, false);
+        assert_eq!(find("disk_limits.sda1").old, serde_json::Value::Null);
+        assert_eq!(find("watched_paths.downloads").new["path"], "/tmp");
+        assert_eq!(find("backend").new, "cgroup");
+
+        let reverse = new.diff(&old);
+        assert_eq!(reverse.len(), changes.len());
+        assert!(
+            reverse
+                .iter()
+                .zip(&changes)
+                .all(|(a, b)| a.field == b.field && a.old == b.new && a.new == b.old)
+        );
+    }
+
+    #[test]
+    fn test_settings_changed_summary() {
+        let old = Settings::default();
+        assert!(SettingsChanged::new(old.clone(), old.clone()).is_none());
+
+        let new = Settings {
+            check_interval_seconds: 30,
+            mqtt: MqttConfig {
+                password: Some("secret".to_string()),
+                ..Default::default()
+            },
+            otel: OtelConfig {
+                headers: HashMap::from([("authorization".to_string(), "Bearer x".to_string())]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let event = SettingsChanged::new(old, new.clone()).unwrap();
+        assert_eq!(*event.new, new);
+        assert_eq!(
+            event.summary(),
+            "check_interval_seconds: 60 -> 30, mqtt.password changed, \
+             otel.headers.authorization changed"
+        );
+    }
+}
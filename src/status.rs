@@ -0,0 +1,1095 @@
+//! What the running service reports about itself.
+//!
+//! The monitor task owns the usage tracker and is the only one to touch it.
+//! Other tasks change it by sending a control message, which the monitor
+//! handles between scans, and read it through [`StatusState`], which keeps
+//! copies the monitor publishes after every scan. The large maps in those
+//! copies are shared behind `Arc`s: a reader holds the lock only to clone a
+//! few pointers and builds its report after releasing it, and the monitor
+//! swaps in new maps without waiting for readers, dropping the old ones
+//! after releasing the lock. Neither side holds the lock for longer than a
+//! few pointer copies, so neither can starve the other.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, watch};
+
+use super::capabilities::Capabilities;
+use super::crash::CrashMarker;
+use super::events::{EVENT_CAPACITY, Event};
+use super::http::{self, Request, Response};
+use super::metrics::AlertCounts;
+use super::monitor;
+use super::notification::{PausedChannel, Snoozes};
+use super::other_monitors::Coexistence;
+use super::report::SCHEMA_VERSION;
+pub use super::report::{EXIT_HEALTHY, EXIT_NOT_RUNNING, EXIT_UNHEALTHY, Health, HealthState};
+use super::self_check::SelfUsage;
+use super::self_metrics::{
+    PersistFailure, PersistStats, SelfMetrics, SelfMetricsSummary, TickStats,
+};
+use super::session::SessionStatus;
+use super::settings::{Settings, SettingsChanged};
+use super::top::{SortKey, UsageSnapshot};
+use super::tracker::{AppResources, DataIncident, ExceedanceStats, ResetPeriod};
+use super::version::{VERSION_INFO, VersionInfo};
+
+/// Settings changes kept for subscribers that have not received them yet.
+pub const SETTINGS_EVENT_CAPACITY: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsSummary {
+    #[serde(default)]
+    pub instance_label: String,
+    pub data_limit: u64,
+    pub check_interval_seconds: u64,
+    pub persistence_interval_seconds: u64,
+    pub reset_period: ResetPeriod,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub disk_limits: HashMap<String, u64>,
+    /// Each interface's limit, `metered_data_limit` included.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub interface_limits: HashMap<String, u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub metered_interfaces: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub path_limits: HashMap<String, u64>,
+}
+
+/// The limit of each of `names`, by name without `prefix`.
+fn limits<'a>(
+    settings: &Settings,
+    prefix: &str,
+    names: impl IntoIterator<Item = &'a String>,
+) -> HashMap<String, u64> {
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let limit = settings.effective_limit(&format!("{}{}", prefix, name))?;
+            Some((name.clone(), limit.value))
+        })
+        .collect()
+}
+
+impl From<&Settings> for SettingsSummary {
+    fn from(settings: &Settings) -> Self {
+        Self {
+            instance_label: settings.instance_label(),
+            data_limit: settings.data_limit,
+            check_interval_seconds: settings.check_interval_seconds,
+            persistence_interval_seconds: settings.persistence_interval_seconds,
+            reset_period: settings.reset_period,
+            disk_limits: limits(settings, monitor::DISK_PREFIX, settings.disk_limits.keys()),
+            interface_limits: limits(
+                settings,
+                monitor::NET_PREFIX,
+                settings
+                    .interface_limits
+                    .keys()
+                    .chain(&settings.metered_interfaces),
+            ),
+            metered_interfaces: settings.metered_interfaces.clone(),
+            path_limits: limits(
+                settings,
+                monitor::PATH_PREFIX,
+                settings.watched_paths.keys(),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppStatus {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    pub usage: u64,
+    pub limit: u64,
+    pub over: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_time_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_memory_bytes: Option<u64>,
+    /// `seconds_over` includes the ongoing exceedance, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exceedance: Option<ExceedanceStats>,
+    /// The last scan in which usage grew.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_active: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskStatus {
+    pub name: String,
+    pub usage: u64,
+    pub limit: Option<u64>,
+    pub over: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterfaceStatus {
+    pub name: String,
+    pub usage: u64,
+    pub limit: Option<u64>,
+    pub over: bool,
+    #[serde(default)]
+    pub metered: bool,
+    /// Whether the default route went through it at the last scan.
+    #[serde(default)]
+    pub default_route: bool,
+}
+
+/// A watched path; `usage` is its size at the last walk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathStatus {
+    pub name: String,
+    pub usage: u64,
+    pub limit: Option<u64>,
+    pub over: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusReport {
+    pub uptime: u64,
+    pub settings_summary: SettingsSummary,
+    pub apps: Vec<AppStatus>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disks: Vec<DiskStatus>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interfaces: Vec<InterfaceStatus>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<PathStatus>,
+    pub last_persist: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub paused: bool,
+    #[serde(default)]
+    pub self_metrics: SelfMetricsSummary,
+    #[serde(default, skip_serializing_if = "Snoozes::is_empty")]
+    pub snoozes: Snoozes,
+    /// Only filled in by the control socket, like `alerts`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paused_channels: Vec<PausedChannel>,
+    #[serde(default)]
+    pub version: VersionInfo,
+    /// Only filled in by the control socket, which can see the daemon's
+    /// alert counters.
+    #[serde(default)]
+    pub alerts: AlertCounts,
+    /// The most recent data file that could not be read at startup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_incident: Option<DataIncident>,
+    /// The crash this run recovered from, if the last one ended in a panic.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_crash: Option<CrashMarker>,
+    /// Other data monitors found at startup.
+    #[serde(default, skip_serializing_if = "Coexistence::is_empty")]
+    pub other_monitors: Coexistence,
+    /// The session counted when `root_process` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionStatus>,
+    /// What startup found this machine supports.
+    #[serde(default, skip_serializing_if = "Capabilities::is_empty")]
+    pub capabilities: Capabilities,
+    /// Whether anything is over its limit, counting applications left out
+    /// by `top` or `app`.
+    #[serde(default)]
+    pub over_limit: bool,
+    /// What is over its limit, as named by [`over_limit()`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub offenders: Vec<String>,
+}
+
+/// Names whose usage is over their limit, sorted. Disks, interfaces and
+/// watched paths keep their `disk:`, `net:` and `path:` prefix; those
+/// without a limit never count.
+pub fn over_limit<'a>(
+    usage: impl IntoIterator<Item = (&'a String, &'a u64)>,
+    settings: &Settings,
+) -> Vec<String> {
+    let mut offenders: Vec<_> = usage
+        .into_iter()
+        .filter(|&(name, usage)| {
+            settings
+                .effective_limit(name)
+                .is_some_and(|limit| *usage > limit.value)
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    offenders.sort();
+    offenders
+}
+
+impl Health {
+    /// Healthy when a scan finished within twice the check interval (or
+    /// scanning is paused) and the last save did not fail.
+    pub fn check(report: &StatusReport, now: DateTime<Utc>) -> Self {
+        let max_age = report
+            .settings_summary
+            .check_interval_seconds
+            .saturating_mul(2);
+        let age = report
+            .self_metrics
+            .last_tick
+            .as_ref()
+            .map(|tick| (now - tick.time).num_seconds().max(0) as u64);
+
+        let mut problems = Vec::new();
+        if !report.paused && age.is_none_or(|age| age > max_age) {
+            problems.push(format!("no scan finished in the last {} seconds", max_age));
+        }
+        if let Some(failure) = &report.self_metrics.persist_failure {
+            problems.push(format!("last save failed: {}", failure.error));
+        }
+        Self {
+            schema_version: SCHEMA_VERSION,
+            status: match problems.is_empty() {
+                true => HealthState::Ok,
+                false => HealthState::Unhealthy,
+            },
+            problems,
+            last_tick_age_seconds: age,
+            paused: report.paused,
+        }
+    }
+}
+
+/// Usage after a scan, handed to `StatusState::watch_usage` receivers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LiveUsage {
+    /// When the scan finished; `None` until the first one has.
+    pub time: Option<DateTime<Utc>>,
+    /// Usage this period, by app, disk and watched path.
+    pub apps: HashMap<String, u64>,
+    pub total: u64,
+    /// Bytes per second since the previous scan. Empty after the first scan
+    /// and after changes that are not scans, such as `dg reset`.
+    pub rates: HashMap<String, f64>,
+}
+
+/// Cloning is cheap: the maps that grow with the number of apps are shared.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    settings: Arc<Settings>,
+    usage: Option<Arc<HashMap<String, u64>>>,
+    display_names: Arc<HashMap<String, String>>,
+    resources: Arc<HashMap<String, AppResources>>,
+    exceedances: Arc<HashMap<String, ExceedanceStats>>,
+    last_active: Arc<HashMap<String, DateTime<Utc>>>,
+    last_persist: Option<DateTime<Utc>>,
+    paused: bool,
+    self_metrics: SelfMetrics,
+    data_incident: Option<DataIncident>,
+    last_crash: Option<CrashMarker>,
+    default_interface: Option<String>,
+    other_monitors: Coexistence,
+    session: Option<SessionStatus>,
+    capabilities: Capabilities,
+}
+
+#[derive(Debug)]
+pub struct StatusState {
+    started: Instant,
+    snapshot: Mutex<Snapshot>,
+    live: watch::Sender<Arc<LiveUsage>>,
+    settings_events: broadcast::Sender<SettingsChanged>,
+    events: broadcast::Sender<Event>,
+}
+
+impl StatusState {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            started: Instant::now(),
+            snapshot: Mutex::new(Snapshot {
+                settings: Arc::new(settings.clone()),
+                usage: None,
+                display_names: Arc::default(),
+                resources: Arc::default(),
+                exceedances: Arc::default(),
+                last_active: Arc::default(),
+                last_persist: None,
+                paused: false,
+                self_metrics: SelfMetrics::default(),
+                data_incident: None,
+                last_crash: None,
+                default_interface: None,
+                other_monitors: Coexistence::default(),
+                session: None,
+                capabilities: Capabilities::default(),
+            }),
+            live: watch::Sender::new(Arc::default()),
+            settings_events: broadcast::Sender::new(SETTINGS_EVENT_CAPACITY),
+            events: broadcast::Sender::new(EVENT_CAPACITY),
+        }
+    }
+
+    /// A receiver that sees the usage after every scan. Each value is shared,
+    /// so holding on to one is cheap and it never changes.
+    pub fn watch_usage(&self) -> watch::Receiver<Arc<LiveUsage>> {
+        self.live.subscribe()
+    }
+
+    pub fn update_settings(&self, settings: &Settings) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .settings = Arc::new(settings.clone());
+    }
+
+    /// A receiver of every reload that changed the settings. One that falls
+    /// more than `SETTINGS_EVENT_CAPACITY` events behind skips the oldest.
+    pub fn subscribe_settings(&self) -> broadcast::Receiver<SettingsChanged> {
+        self.settings_events.subscribe()
+    }
+
+    /// The sender of live events, for the control socket and the
+    /// notification manager. A receiver that falls more than
+    /// `EVENT_CAPACITY` events behind skips the oldest.
+    pub fn events(&self) -> broadcast::Sender<Event> {
+        self.events.clone()
+    }
+
+    fn send_event(&self, event: Event) {
+        let _ = self.events.send(event);
+    }
+
+    /// Stores the settings a reload applied and sends `event` to subscribers.
+    pub fn apply_settings_change(&self, event: SettingsChanged) {
+        self.update_settings(&event.new);
+        self.send_event(Event::Reload {
+            time: Utc::now(),
+            fields: event
+                .changes
+                .iter()
+                .map(|change| change.field.clone())
+                .collect(),
+        });
+        let _ = self.settings_events.send(event);
+    }
+
+    pub fn update_usage(&self, usage: HashMap<String, u64>) {
+        self.publish(usage, Utc::now(), |_, _| None);
+    }
+
+    /// Stores the usage after a scan at `time` that added `deltas`, and
+    /// publishes it with each app's rate since the previous scan.
+    pub fn record_scan_usage(
+        &self,
+        usage: HashMap<String, u64>,
+        deltas: &monitor::UsageData,
+        time: DateTime<Utc>,
+    ) {
+        let apps: BTreeMap<_, _> = deltas
+            .iter()
+            .filter(|(_, bytes)| **bytes > 0)
+            .map(|(app, bytes)| (app.clone(), *bytes))
+            .collect();
+        if !apps.is_empty() {
+            self.send_event(Event::Delta { time, apps });
+        }
+        self.publish(usage, time, |app, previous| {
+            let previous = previous?;
+            let seconds = (time - previous).num_milliseconds() as f64 / 1000.0;
+            (seconds > 0.0).then(|| deltas.get(app).copied().unwrap_or(0) as f64 / seconds)
+        });
+    }
+
+    fn publish(
+        &self,
+        usage: HashMap<String, u64>,
+        time: DateTime<Utc>,
+        rate: impl Fn(&str, Option<DateTime<Utc>>) -> Option<f64>,
+    ) {
+        let previous = self.live.borrow().time;
+        let live = LiveUsage {
+            time: Some(time),
+            total: monitor::total(&usage),
+            rates: usage
+                .keys()
+                .filter_map(|app| Some((app.clone(), rate(app, previous)?)))
+                .collect(),
+            apps: usage.clone(),
+        };
+        self.replace(|snapshot| &mut snapshot.usage, Some(Arc::new(usage)));
+        self.live.send_replace(Arc::new(live));
+    }
+
+    /// Puts `value` in the field `field` picks, dropping the old value once
+    /// the lock is released.
+    fn replace<T>(&self, field: impl FnOnce(&mut Snapshot) -> &mut T, value: T) {
+        let old = {
+            let mut snapshot = self.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+            std::mem::replace(field(&mut snapshot), value)
+        };
+        drop(old);
+    }
+
+    pub fn update_display_names(&self, display_names: HashMap<String, String>) {
+        self.replace(
+            |snapshot| &mut snapshot.display_names,
+            Arc::new(display_names),
+        );
+    }
+
+    pub fn update_resources(&self, resources: HashMap<String, AppResources>) {
+        self.replace(|snapshot| &mut snapshot.resources, Arc::new(resources));
+    }
+
+    pub fn update_exceedances(&self, exceedances: HashMap<String, ExceedanceStats>) {
+        self.replace(|snapshot| &mut snapshot.exceedances, Arc::new(exceedances));
+    }
+
+    pub fn update_last_active(&self, last_active: HashMap<String, DateTime<Utc>>) {
+        self.replace(|snapshot| &mut snapshot.last_active, Arc::new(last_active));
+    }
+
+    pub fn record_tick(&self, tick: TickStats) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .self_metrics
+            .record_tick(tick);
+    }
+
+    pub fn record_persist(&self, persist: PersistStats) {
+        self.send_event(Event::Save {
+            time: persist.time,
+            size_bytes: persist.size_bytes,
+            duration_ms: persist.duration_ms,
+        });
+        let mut snapshot = self.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+        snapshot.last_persist = Some(persist.time);
+        snapshot.self_metrics.record_persist(persist);
+    }
+
+    pub fn record_persist_failure(&self, error: impl ToString) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .self_metrics
+            .record_persist_failure(PersistFailure {
+                time: Utc::now(),
+                error: error.to_string(),
+            });
+    }
+
+    pub fn set_scan_failures(&self, failures: u32) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .self_metrics
+            .set_scan_failures(failures);
+    }
+
+    pub fn set_save_failures(&self, failures: u32) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .self_metrics
+            .set_save_failures(failures);
+    }
+
+    pub fn set_self_usage(&self, usage: Option<SelfUsage>) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .self_metrics
+            .set_self_usage(usage);
+    }
+
+    pub fn set_data_incident(&self, incident: Option<DataIncident>) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .data_incident = incident;
+    }
+
+    pub fn set_last_crash(&self, crash: Option<CrashMarker>) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .last_crash = crash;
+    }
+
+    pub fn set_other_monitors(&self, coexistence: Coexistence) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .other_monitors = coexistence;
+    }
+
+    pub fn set_session(&self, session: Option<SessionStatus>) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .session = session;
+    }
+
+    pub fn set_capabilities(&self, capabilities: Capabilities) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .capabilities = capabilities;
+    }
+
+    pub fn set_default_interface(&self, interface: Option<String>) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .default_interface = interface;
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .paused = paused;
+    }
+
+    pub fn report(&self, top: Option<usize>, app: Option<&str>) -> Option<StatusReport> {
+        let snapshot = self
+            .snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let usage = snapshot.usage.as_deref()?;
+
+        let limit = snapshot.settings.data_limit;
+        let now = Utc::now();
+        let offenders = over_limit(usage, &snapshot.settings);
+        let (apps, other): (Vec<_>, Vec<_>) = usage
+            .iter()
+            .filter(|(name, _)| app.is_none_or(|app| app == name.as_str()))
+            .partition(|(name, _)| monitor::is_app(name));
+        let apps = UsageSnapshot::new(
+            apps.into_iter()
+                .map(|(name, usage)| (name.clone(), *usage))
+                .collect(),
+            limit,
+        )
+        .top_n(top.unwrap_or(usize::MAX), SortKey::Usage)
+        .into_iter()
+        .map(|view| {
+            let resources = snapshot
+                .resources
+                .get(&view.name)
+                .copied()
+                .unwrap_or_default();
+            AppStatus {
+                display_name: snapshot.display_names.get(&view.name).cloned(),
+                usage: view.bytes,
+                limit: view.limit,
+                over: view.over,
+                cpu_time_ms: resources.cpu_time_ms,
+                peak_memory_bytes: resources.peak_memory_bytes,
+                exceedance: snapshot
+                    .exceedances
+                    .get(&view.name)
+                    .map(|stats| stats.at(now)),
+                last_active: snapshot.last_active.get(&view.name).copied(),
+                name: view.name,
+            }
+        })
+        .collect();
+        let limit_of = |name: &str| {
+            snapshot
+                .settings
+                .effective_limit(name)
+                .map(|limit| limit.value)
+        };
+        let mut disks: Vec<_> = other
+            .iter()
+            .filter_map(|&(name, usage)| {
+                let limit = limit_of(name);
+                let name = monitor::disk_name(name)?;
+                Some(DiskStatus {
+                    name: name.to_string(),
+                    usage: *usage,
+                    limit,
+                    over: limit.is_some_and(|limit| *usage > limit),
+                })
+            })
+            .collect();
+        disks.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut interfaces: Vec<_> = other
+            .iter()
+            .filter_map(|&(name, usage)| {
+                let limit = limit_of(name);
+                let name = monitor::interface_name(name)?;
+                Some(InterfaceStatus {
+                    name: name.to_string(),
+                    usage: *usage,
+                    limit,
+                    over: limit.is_some_and(|limit| *usage > limit),
+                    metered: snapshot.settings.is_metered(name),
+                    default_route: snapshot.default_interface.as_deref() == Some(name),
+                })
+            })
+            .collect();
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut paths: Vec<_> = other
+            .iter()
+            .filter_map(|&(name, usage)| {
+                let limit = limit_of(name);
+                let name = monitor::path_label(name)?;
+                Some(PathStatus {
+                    name: name.to_string(),
+                    usage: *usage,
+                    limit,
+                    over: limit.is_some_and(|limit| *usage > limit),
+                })
+            })
+            .collect();
+        paths.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Some(StatusReport {
+            uptime: self.started.elapsed().as_secs(),
+            settings_summary: snapshot.settings.as_ref().into(),
+            apps,
+            disks,
+            interfaces,
+            paths,
+            last_persist: snapshot.last_persist,
+            paused: snapshot.paused,
+            self_metrics: snapshot.self_metrics.summary(),
+            snoozes: Snoozes::default(),
+            paused_channels: Vec::new(),
+            version: VERSION_INFO,
+            alerts: AlertCounts::default(),
+            data_incident: snapshot.data_incident.clone(),
+            last_crash: snapshot.last_crash.clone(),
+            other_monitors: snapshot.other_monitors.clone(),
+            session: snapshot.session.clone(),
+            capabilities: snapshot.capabilities.clone(),
+            over_limit: !offenders.is_empty(),
+            offenders,
+        })
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        if request.path != "/status" {
+            return Response::not_found();
+        }
+
+        let top = match request.query.get("top").map(|top| top.parse::<usize>()) {
+            Some(Ok(top)) => Some(top),
+            Some(Err(_)) => return Response::error(400, "Invalid top parameter"),
+            None => None,
+        };
+        let app = request.query.get("app").map(String::as_str);
+
+        match self.report(top, app) {
+            Some(report) => Response::json(&report),
+            None => Response::error(503, "Waiting for the first scan"),
+        }
+    }
+}
+
+pub async fn serve(listener: TcpListener, state: Arc<StatusState>) -> io::Result<()> {
+    http::serve(listener, move |request| state.handle(request)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::watched_paths::WatchedPath;
+
+    async fn get(addr: std::net::SocketAddr, target: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target).as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        let status = response[9..12].parse().unwrap();
+        let body = response
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body.to_string())
+            .unwrap_or_default();
+        (status, body)
+    }
+
+    async fn spawn_server(state: Arc<StatusState>) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, state));
+        addr
+    }
+
+    fn tick(time: DateTime<Utc>) -> TickStats {
+        TickStats {
+            time,
+            tick: 1,
+            snapshot_ms: 1.0,
+            tick_ms: 1.0,
+            lag_ms: 0.0,
+            processes: 1,
+            tracked_apps: 1,
+            tick_apps: 1,
+            overflow_apps: 0,
+            delta_bytes: 0,
+            rejected_bytes: 0,
+            default_interface: None,
+        }
+    }
+
+    #[test]
+    fn test_health() {
+        let settings = Settings {
+            check_interval_seconds: 30,
+            ..Settings::default()
+        };
+        let state = StatusState::new(&settings);
+        state.update_usage(HashMap::new());
+        let now = Utc::now();
+        state.record_tick(tick(now - chrono::Duration::seconds(45)));
+
+        let health = Health::check(&state.report(None, None).unwrap(), now);
+        assert_eq!(health.status, HealthState::Ok);
+        assert_eq!(health.last_tick_age_seconds, Some(45));
+        assert_eq!(health.exit_code(), EXIT_HEALTHY);
+
+        let later = now + chrono::Duration::seconds(30);
+        let health = Health::check(&state.report(None, None).unwrap(), later);
+        assert_eq!(health.status, HealthState::Unhealthy);
+        assert_eq!(health.problems, ["no scan finished in the last 60 seconds"]);
+        assert_eq!(health.exit_code(), EXIT_UNHEALTHY);
+
+        state.set_paused(true);
+        assert_eq!(
+            Health::check(&state.report(None, None).unwrap(), later).status,
+            HealthState::Ok
+        );
+
+        state.record_persist_failure("disk full");
+        let health = Health::check(&state.report(None, None).unwrap(), later);
+        assert_eq!(health.problems, ["last save failed: disk full"]);
+        assert_eq!(
+            serde_json::to_string(&health).unwrap(),
+            r#"{"schema_version":1,"status":"unhealthy","problems":["last save failed: disk full"],"last_tick_age_seconds":75,"paused":true}"#
+        );
+
+        state.record_persist(PersistStats {
+            time: later,
+            duration_ms: 1.0,
+            size_bytes: 10,
+        });
+        assert_eq!(
+            Health::check(&state.report(None, None).unwrap(), later).status,
+            HealthState::Ok
+        );
+        assert_eq!(
+            Health::not_running("connection refused").exit_code(),
+            EXIT_NOT_RUNNING
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_unavailable_before_first_scan() {
+        let state = Arc::new(StatusState::new(&Settings::default()));
+        let addr = spawn_server(state).await;
+
+        let (status, _) = get(addr, "/status").await;
+        assert_eq!(status, 503);
+    }
+
+    #[tokio::test]
+    async fn test_status_endpoint() {
+        let settings = Settings {
+            disk_limits: HashMap::from([("sda1".to_string(), 500)]),
+            interface_limits: HashMap::from([("eth0".to_string(), 5000)]),
+            metered_interfaces: vec!["wwan0".to_string()],
+            metered_data_limit: Some(100),
+            watched_paths: HashMap::from([(
+                "cache".to_string(),
+                WatchedPath {
+                    path: "/var/cache".into(),
+                    limit: Some(4096),
+                    ..WatchedPath::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let state = Arc::new(StatusState::new(&settings));
+        state.update_usage(HashMap::from([
+            ("small".to_string(), 10),
+            ("large".to_string(), settings.data_limit + 1),
+            ("medium".to_string(), 100),
+            ("disk:sda1".to_string(), 1000),
+            ("disk:nvme0n1p2".to_string(), 20),
+            ("path:cache".to_string(), 8192),
+            ("net:eth0".to_string(), 1000),
+            ("net:wwan0".to_string(), 150),
+        ]));
+        state.set_default_interface(Some("wwan0".to_string()));
+        state.update_display_names(HashMap::from([(
+            "medium".to_string(),
+            "Medium App".to_string(),
+        )]));
+        state.update_resources(HashMap::from([(
+            "large".to_string(),
+            AppResources {
+                cpu_time_ms: Some(1500),
+                peak_memory_bytes: None,
+            },
+        )]));
+        let addr = spawn_server(state.clone()).await;
+
+        let (status, body) = get(addr, "/status").await;
+        assert_eq!(status, 200);
+        let report: StatusReport = serde_json::from_str(&body).unwrap();
+        assert_eq!(report.settings_summary.data_limit, settings.data_limit);
+        assert_eq!(report.last_persist, None);
+        let names: Vec<_> = report.apps.iter().map(|app| app.name.as_str()).collect();
+        assert_eq!(names, ["large", "medium", "small"]);
+        assert!(report.apps[0].over);
+        assert_eq!(report.apps[0].display_name, None);
+        assert_eq!(report.apps[1].display_name.as_deref(), Some("Medium App"));
+        assert_eq!(report.apps[0].cpu_time_ms, Some(1500));
+        assert_eq!(report.apps[0].peak_memory_bytes, None);
+        assert!(!body.contains("peak_memory_bytes"));
+        assert_eq!(
+            report.disks,
+            [
+                DiskStatus {
+                    name: "nvme0n1p2".to_string(),
+                    usage: 20,
+                    limit: None,
+                    over: false,
+                },
+                DiskStatus {
+                    name: "sda1".to_string(),
+                    usage: 1000,
+                    limit: Some(500),
+                    over: true,
+                },
+            ]
+        );
+        assert_eq!(
+            report.paths,
+            [PathStatus {
+                name: "cache".to_string(),
+                usage: 8192,
+                limit: Some(4096),
+                over: true,
+            }]
+        );
+
+        assert_eq!(
+            report.interfaces,
+            [
+                InterfaceStatus {
+                    name: "eth0".to_string(),
+                    usage: 1000,
+                    limit: Some(5000),
+                    over: false,
+                    metered: false,
+                    default_route: false,
+                },
+                InterfaceStatus {
+                    name: "wwan0".to_string(),
+                    usage: 150,
+                    limit: Some(100),
+                    over: true,
+                    metered: true,
+                    default_route: true,
+                },
+            ]
+        );
+
+        assert!(report.over_limit);
+        assert_eq!(
+            report.offenders,
+            ["disk:sda1", "large", "net:wwan0", "path:cache"]
+        );
+
+        let (_, body) = get(addr, "/status?top=1").await;
+        let report: StatusReport = serde_json::from_str(&body).unwrap();
+        assert_eq!(report.apps.len(), 1);
+        assert_eq!(report.apps[0].name, "large");
+
+        let (_, body) = get(addr, "/status?app=small").await;
+        let report: StatusReport = serde_json::from_str(&body).unwrap();
+        assert_eq!(report.apps.len(), 1);
+        assert_eq!(report.apps[0].usage, 10);
+        assert!(report.disks.is_empty());
+        assert_eq!(report.offenders.len(), 4);
+
+        let (status, _) = get(addr, "/status?top=abc").await;
+        assert_eq!(status, 400);
+
+        let (status, _) = get(addr, "/nope").await;
+        assert_eq!(status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_settings_change_events() {
+        let old = Settings::default();
+        let state = StatusState::new(&old);
+        let mut receiver = state.subscribe_settings();
+        let mut events = state.events().subscribe();
+
+        let new = Settings {
+            data_limit: 2 * old.data_limit,
+            ..Default::default()
+        };
+        let event = SettingsChanged::new(old, new.clone()).unwrap();
+        state.apply_settings_change(event.clone());
+
+        assert_eq!(receiver.recv().await.unwrap(), event);
+        assert_eq!(event.changes[0].field, "data_limit");
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            Event::Reload { fields, .. } if fields == ["data_limit"]
+        ));
+        state.update_usage(HashMap::new());
+        let report = state.report(None, None).unwrap();
+        assert_eq!(report.settings_summary.data_limit, new.data_limit);
+    }
+
+    #[tokio::test]
+    async fn test_watch_usage_follows_scans() {
+        use crate::monitor::{CounterKey, CounterSource, Counters, ProcessData, Sampler};
+        use crate::test_support::ScriptedSource;
+        use sysinfo::Pid;
+
+        let counters = |bytes| {
+            ProcessData::from([(
+                CounterKey::Process(Pid::from(1), 0),
+                Counters::new("curl".to_string(), bytes),
+            )])
+        };
+        let source: Arc<dyn CounterSource> =
+            Arc::new(ScriptedSource::new([0, 100, 300, 600].map(counters)));
+        let state = Arc::new(StatusState::new(&Settings::default()));
+        let mut receiver = state.watch_usage();
+        assert_eq!(receiver.borrow().time, None);
+
+        let reader = tokio::spawn(async move {
+            let mut seen = Vec::new();
+            while receiver.changed().await.is_ok() {
+                let live = receiver.borrow_and_update().clone();
+                let done = live.total == 600;
+                seen.push(live);
+                if done {
+                    break;
+                }
+            }
+            seen
+        });
+
+        let mut sampler = Sampler::new();
+        let mut usage = HashMap::new();
+        let start = Utc::now();
+        for tick in 1..=4 {
+            let current = monitor::snapshot(source.clone()).await.unwrap();
+            let deltas = sampler.advance(current).usage;
+            for (app, delta) in &deltas {
+                *usage.entry(app.clone()).or_insert(0) += delta;
+            }
+            state.record_scan_usage(
+                usage.clone(),
+                &deltas,
+                start + chrono::Duration::seconds(tick * 10),
+            );
+            tokio::task::yield_now().await;
+        }
+
+        let seen = reader.await.unwrap();
+        let times = seen
+            .iter()
+            .map(|live| live.time.unwrap())
+            .collect::<Vec<_>>();
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+        let last = seen.last().unwrap();
+        assert_eq!(last.time, Some(start + chrono::Duration::seconds(40)));
+        assert_eq!(last.apps, HashMap::from([("curl".to_string(), 600)]));
+        assert_eq!(last.rates, HashMap::from([("curl".to_string(), 30.0)]));
+        assert!(Arc::ptr_eq(last, &state.watch_usage().borrow()));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_reads_during_heavy_ticks() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        const APPS: u64 = 5_000;
+        const TICKS: u64 = 40;
+        const READERS: usize = 4;
+        const TIMEOUT: Duration = Duration::from_secs(30);
+
+        let state = Arc::new(StatusState::new(&Settings::default()));
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..READERS)
+            .map(|_| {
+                let state = Arc::clone(&state);
+                let done = Arc::clone(&done);
+                tokio::spawn(async move {
+                    let mut reports = 0;
+                    let mut last = 0;
+                    while !done.load(Ordering::Acquire) {
+                        if let Some(report) = state.report(Some(10), None) {
+                            // Every app has the tick's number as its usage, so
+                            // a report mixing two ticks has differing values.
+                            let usage = report.apps[0].usage;
+                            assert!(report.apps.iter().all(|app| app.usage == usage));
+                            assert!(usage >= last, "Usage went backwards");
+                            last = usage;
+                            reports += 1;
+                        }
+                        let live = state.watch_usage().borrow().clone();
+                        assert_eq!(live.total, live.apps.values().sum::<u64>());
+                        tokio::task::yield_now().await;
+                    }
+                    reports
+                })
+            })
+            .collect();
+
+        let writer = tokio::spawn({
+            let state = Arc::clone(&state);
+            async move {
+                for tick in 1..=TICKS {
+                    let usage: HashMap<_, _> =
+                        (0..APPS).map(|i| (format!("app{}", i), tick)).collect();
+                    let names = usage
+                        .keys()
+                        .map(|app| (app.clone(), app.to_uppercase()))
+                        .collect();
+                    state.record_scan_usage(usage, &HashMap::new(), Utc::now());
+                    state.update_display_names(names);
+                    tokio::task::yield_now().await;
+                }
+            }
+        });
+        tokio::time::timeout(TIMEOUT, writer)
+            .await
+            .expect("Readers held up the ticks")
+            .unwrap();
+        done.store(true, Ordering::Release);
+        let mut reports = 0;
+        for reader in readers {
+            reports += tokio::time::timeout(TIMEOUT, reader)
+                .await
+                .expect("A reader never finished")
+                .unwrap();
+        }
+        assert!(reports > 0);
+        let report = state.report(Some(1), None).unwrap();
+        assert_eq!(report.apps[0].usage, TICKS);
+        assert_eq!(
+            report.apps[0].display_name.as_deref().unwrap(),
+            report.apps[0].name.to_uppercase()
+        );
+    }
+}
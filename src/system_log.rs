@@ -0,0 +1,569 @@
+#![cfg_attr(not(feature = "notifications"), allow(unused_imports, dead_code))]
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use super::history::Severity;
+use super::notification::{
+    Alert, AlertKind, ChannelKind, NotificationChannel, NotificationConfig, NotificationError,
+};
+
+pub const SYSLOG_PROCESS: &str = "dg";
+pub const ALERT_EVENT: &str = "data_limit_exceeded";
+pub const FORECAST_EVENT: &str = "data_limit_forecast";
+pub const ANOMALY_EVENT: &str = "data_usage_anomaly";
+pub const CPU_EVENT: &str = "cpu_limit_exceeded";
+pub const MEMORY_EVENT: &str = "memory_limit_exceeded";
+pub const STARTED_EVENT: &str = "service_started";
+pub const SUMMARY_EVENT: &str = "session_summary";
+pub const DATA_LOSS_EVENT: &str = "data_file_unreadable";
+pub const BUDGET_EVENT: &str = "data_budget_milestone";
+pub const FAILING_EVENT: &str = "task_failing";
+pub const CLAMPED_EVENT: &str = "settings_clamped";
+pub const SELF_USAGE_EVENT: &str = "self_usage_exceeded";
+pub const TEST_EVENT: &str = "notification_test";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogFacility {
+    Kern,
+    #[default]
+    User,
+    Mail,
+    Daemon,
+    Auth,
+    Syslog,
+    Lpr,
+    News,
+    Uucp,
+    Cron,
+    Authpriv,
+    Ftp,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyslogConfig {
+    pub facility: SyslogFacility,
+    pub socket: Option<PathBuf>,
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub fn format_alert(alert: &Alert) -> String {
+    let app = escape(&alert.app);
+    let event = match alert.kind {
+        AlertKind::Exceeded => ALERT_EVENT,
+        AlertKind::Forecast => FORECAST_EVENT,
+        AlertKind::Anomaly => ANOMALY_EVENT,
+        AlertKind::Cpu => CPU_EVENT,
+        AlertKind::Memory => MEMORY_EVENT,
+        AlertKind::Started => STARTED_EVENT,
+        AlertKind::Summary => SUMMARY_EVENT,
+        AlertKind::DataLoss => DATA_LOSS_EVENT,
+        AlertKind::Budget => BUDGET_EVENT,
+        AlertKind::Failing => FAILING_EVENT,
+        AlertKind::Clamped => CLAMPED_EVENT,
+        AlertKind::SelfUsage => SELF_USAGE_EVENT,
+        AlertKind::Test => TEST_EVENT,
+    };
+    let mut message = format!(
+        "event={} app=\"{}\" severity={}",
+        event,
+        app,
+        alert.severity.as_str()
+    );
+    match (alert.usage, alert.limit) {
+        (Some(usage), Some(limit)) => {
+            message.push_str(&format!(" usage={} limit={}", usage, limit))
+        }
+        (Some(usage), None) => message.push_str(&format!(" usage={}", usage)),
+        (None, Some(limit)) => message.push_str(&format!(" limit={}", limit)),
+        (None, None) => {}
+    }
+    if let Some(projected) = alert.projected {
+        message.push_str(&format!(" projected={}", projected));
+    }
+    if let (Some(delta), Some(baseline)) = (alert.delta, alert.baseline) {
+        message.push_str(&format!(" delta={} baseline={}", delta, baseline));
+    }
+    if alert.streak > 0 {
+        message.push_str(&format!(" streak={}", alert.streak));
+    }
+    match alert.kind {
+        AlertKind::Started => message.push_str(&format!(" apps={}", alert.apps.len())),
+        AlertKind::Summary => message.push_str(&format!(
+            " over_limit=\"{}\"",
+            escape(&alert.apps.join(","))
+        )),
+        AlertKind::DataLoss => message.push_str(&format!(" restored={}", alert.restored)),
+        AlertKind::Clamped => message.push_str(&format!(
+            " settings=\"{}\"",
+            escape(&alert.settings.join(","))
+        )),
+        AlertKind::Failing | AlertKind::SelfUsage => {
+            if let Some(task) = &alert.task {
+                message.push_str(&format!(" task={}", task));
+            }
+        }
+        AlertKind::Budget => {
+            if let Some(milestone) = alert.milestone {
+                message.push_str(&format!(" milestone={}", milestone));
+            }
+            if let Some(days_left) = alert.days_left {
+                message.push_str(&format!(" days_left={}", days_left));
+            }
+        }
+        _ => {}
+    }
+    if let Some(instance) = &alert.instance {
+        message.push_str(&format!(" instance={}", instance));
+    }
+    message
+}
+
+/// Writes alerts to the service log, for hosts without a desktop session or syslog.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogChannel;
+
+impl NotificationChannel for LogChannel {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    fn send(&self, alert: &Alert) -> Result<(), NotificationError> {
+        let message = format_alert(alert);
+        match alert.severity {
+            Severity::Info => info!("{}", message),
+            Severity::Warning => warn!("{}", message),
+            Severity::Critical => error!("{}", message),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "notifications")]
+pub fn channels(config: &NotificationConfig) -> Vec<Arc<dyn NotificationChannel>> {
+    let mut channels: Vec<Arc<dyn NotificationChannel>> = Vec::new();
+    for kind in &config.channels {
+        match kind {
+            ChannelKind::Desktop => {}
+            ChannelKind::Log => channels.push(Arc::new(LogChannel)),
+            #[cfg(unix)]
+            ChannelKind::Syslog => {
+                channels.push(Arc::new(SyslogChannel::new(config.syslog.clone())))
+            }
+            #[cfg(windows)]
+            ChannelKind::EventLog => {
+                if let Err(e) = register_event_source(&config.app_name) {
+                    warn!(error = %e, source = %config.app_name, "Failed to register event source, run once elevated to register it");
+                }
+                channels.push(Arc::new(EventLogChannel::new(&config.app_name)));
+            }
+            #[allow(unreachable_patterns)]
+            kind => warn!(
+                ?kind,
+                "Notification channel is not supported on this platform"
+            ),
+        }
+    }
+    channels
+}
+
+#[cfg(all(unix, feature = "notifications"))]
+pub use unix::SyslogChannel;
+
+#[cfg(all(unix, feature = "notifications"))]
+mod unix {
+    use std::fmt;
+    use std::sync::Mutex;
+
+    use syslog::{Facility, Formatter3164, Logger, LoggerBackend};
+
+    use super::{SYSLOG_PROCESS, SyslogConfig, SyslogFacility, format_alert};
+    use crate::history::Severity;
+    use crate::notification::{Alert, NotificationChannel, NotificationError};
+
+    impl From<SyslogFacility> for Facility {
+        fn from(facility: SyslogFacility) -> Self {
+            match facility {
+                SyslogFacility::Kern => Facility::LOG_KERN,
+                SyslogFacility::User => Facility::LOG_USER,
+                SyslogFacility::Mail => Facility::LOG_MAIL,
+                SyslogFacility::Daemon => Facility::LOG_DAEMON,
+                SyslogFacility::Auth => Facility::LOG_AUTH,
+                SyslogFacility::Syslog => Facility::LOG_SYSLOG,
+                SyslogFacility::Lpr => Facility::LOG_LPR,
+                SyslogFacility::News => Facility::LOG_NEWS,
+                SyslogFacility::Uucp => Facility::LOG_UUCP,
+                SyslogFacility::Cron => Facility::LOG_CRON,
+                SyslogFacility::Authpriv => Facility::LOG_AUTHPRIV,
+                SyslogFacility::Ftp => Facility::LOG_FTP,
+                SyslogFacility::Local0 => Facility::LOG_LOCAL0,
+                SyslogFacility::Local1 => Facility::LOG_LOCAL1,
+                SyslogFacility::Local2 => Facility::LOG_LOCAL2,
+                SyslogFacility::Local3 => Facility::LOG_LOCAL3,
+                SyslogFacility::Local4 => Facility::LOG_LOCAL4,
+                SyslogFacility::Local5 => Facility::LOG_LOCAL5,
+                SyslogFacility::Local6 => Facility::LOG_LOCAL6,
+                SyslogFacility::Local7 => Facility::LOG_LOCAL7,
+            }
+        }
+    }
+
+    pub struct SyslogChannel {
+        config: SyslogConfig,
+        logger: Mutex<Option<Logger<LoggerBackend, Formatter3164>>>,
+    }
+
+    impl fmt::Debug for SyslogChannel {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("SyslogChannel")
+                .field("config", &self.config)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl SyslogChannel {
+        pub fn new(config: SyslogConfig) -> Self {
+            Self {
+                config,
+                logger: Mutex::new(None),
+            }
+        }
+
+        fn connect(&self) -> syslog::Result<Logger<LoggerBackend, Formatter3164>> {
+            let formatter = Formatter3164 {
+                facility: self.config.facility.into(),
+                hostname: None,
+                process: SYSLOG_PROCESS.to_string(),
+                pid: std::process::id(),
+            };
+            match &self.config.socket {
+                Some(path) => syslog::unix_custom(formatter, path),
+                None => syslog::unix(formatter),
+            }
+        }
+    }
+
+    impl NotificationChannel for SyslogChannel {
+        fn name(&self) -> &str {
+            "syslog"
+        }
+
+        fn send(&self, alert: &Alert) -> Result<(), NotificationError> {
+            let mut logger = self
+                .logger
+                .lock()
+                .map_err(|_| NotificationError::LockError)?;
+            if logger.is_none() {
+                *logger = Some(
+                    self.connect()
+                        .map_err(|e| NotificationError::ShowError(e.to_string()))?,
+                );
+            }
+
+            let message = format_alert(alert);
+            let Some(connection) = logger.as_mut() else {
+                return Err(NotificationError::LockError);
+            };
+            let result = match alert.severity {
+                Severity::Info => connection.info(message),
+                Severity::Warning => connection.warning(message),
+                Severity::Critical => connection.crit(message),
+            };
+            result.map_err(|e| {
+                *logger = None;
+                NotificationError::ShowError(e.to_string())
+            })
+        }
+    }
+}
+
+#[cfg(all(windows, feature = "notifications"))]
+pub use windows::{EventLogChannel, register_event_source};
+
+#[cfg(all(windows, feature = "notifications"))]
+mod windows {
+    use std::io;
+    use std::iter;
+    use std::ptr;
+    use std::sync::Mutex;
+
+    use windows_sys::Win32::Foundation::{ERROR_SUCCESS, HANDLE};
+    use windows_sys::Win32::System::EventLog::{
+        DeregisterEventSource, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE,
+        EVENTLOG_WARNING_TYPE, RegisterEventSourceW, ReportEventW,
+    };
+    use windows_sys::Win32::System::Registry::{
+        HKEY, HKEY_LOCAL_MACHINE, KEY_WRITE, REG_DWORD, REG_EXPAND_SZ, REG_OPTION_NON_VOLATILE,
+        RegCloseKey, RegCreateKeyExW, RegSetValueExW,
+    };
+
+    use super::format_alert;
+    use crate::history::Severity;
+    use crate::notification::{Alert, NotificationChannel, NotificationError};
+
+    const EVENT_ID: u32 = 1;
+    const EVENT_MESSAGE_FILE: &str = r"%SystemRoot%\System32\EventCreate.exe";
+    const TYPES_SUPPORTED: u32 = 7;
+
+    fn wide(value: &str) -> Vec<u16> {
+        value.encode_utf16().chain(iter::once(0)).collect()
+    }
+
+    #[derive(Debug)]
+    struct EventSource(HANDLE);
+
+    // Event log handles may be used from any thread.
+    unsafe impl Send for EventSource {}
+
+    impl Drop for EventSource {
+        fn drop(&mut self) {
+            unsafe { DeregisterEventSource(self.0) };
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct EventLogChannel {
+        source: String,
+        handle: Mutex<Option<EventSource>>,
+    }
+
+    impl EventLogChannel {
+        pub fn new(source: &str) -> Self {
+            Self {
+                source: source.to_string(),
+                handle: Mutex::new(None),
+            }
+        }
+    }
+
+    impl NotificationChannel for EventLogChannel {
+        fn name(&self) -> &str {
+            "eventlog"
+        }
+
+        fn send(&self, alert: &Alert) -> Result<(), NotificationError> {
+            let mut handle = self
+                .handle
+                .lock()
+                .map_err(|_| NotificationError::LockError)?;
+            if handle.is_none() {
+                let source = wide(&self.source);
+                let raw = unsafe { RegisterEventSourceW(ptr::null(), source.as_ptr()) };
+                if raw.is_null() {
+                    return Err(NotificationError::ShowError(
+                        io::Error::last_os_error().to_string(),
+                    ));
+                }
+                *handle = Some(EventSource(raw));
+            }
+            let Some(EventSource(raw)) = handle.as_ref() else {
+                return Err(NotificationError::LockError);
+            };
+
+            let event_type = match alert.severity {
+                Severity::Info => EVENTLOG_INFORMATION_TYPE,
+                Severity::Warning => EVENTLOG_WARNING_TYPE,
+                Severity::Critical => EVENTLOG_ERROR_TYPE,
+            };
+            let message = wide(&format_alert(alert));
+            let strings = [message.as_ptr()];
+            let reported = unsafe {
+                ReportEventW(
+                    *raw,
+                    event_type,
+                    0,
+                    EVENT_ID,
+                    ptr::null_mut(),
+                    strings.len() as u16,
+                    0,
+                    strings.as_ptr(),
+                    ptr::null(),
+                )
+            };
+            if reported == 0 {
+                let error = io::Error::last_os_error();
+                *handle = None;
+                return Err(NotificationError::ShowError(error.to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    pub fn register_event_source(source: &str) -> io::Result<()> {
+        let key_path = wide(&format!(
+            r"SYSTEM\CurrentControlSet\Services\EventLog\Application\{}",
+            source
+        ));
+        let mut key: HKEY = ptr::null_mut();
+        let status = unsafe {
+            RegCreateKeyExW(
+                HKEY_LOCAL_MACHINE,
+                key_path.as_ptr(),
+                0,
+                ptr::null(),
+                REG_OPTION_NON_VOLATILE,
+                KEY_WRITE,
+                ptr::null(),
+                &mut key,
+                ptr::null_mut(),
+            )
+        };
+        if status != ERROR_SUCCESS {
+            return Err(io::Error::from_raw_os_error(status as i32));
+        }
+
+        let message_file: Vec<u8> = wide(EVENT_MESSAGE_FILE)
+            .iter()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        let values = [
+            ("EventMessageFile", REG_EXPAND_SZ, message_file),
+            (
+                "TypesSupported",
+                REG_DWORD,
+                TYPES_SUPPORTED.to_le_bytes().to_vec(),
+            ),
+        ];
+        let mut result = Ok(());
+        for (name, kind, data) in values {
+            let name = wide(name);
+            let status = unsafe {
+                RegSetValueExW(
+                    key,
+                    name.as_ptr(),
+                    0,
+                    kind,
+                    data.as_ptr(),
+                    data.len() as u32,
+                )
+            };
+            if status != ERROR_SUCCESS {
+                result = Err(io::Error::from_raw_os_error(status as i32));
+                break;
+            }
+        }
+        unsafe { RegCloseKey(key) };
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Severity;
+    use crate::tracker::ResetPeriod;
+
+    #[test]
+    fn test_format_alert() {
+        let alert = Alert::new(r#"my "app""#)
+            .with_usage(1_500_000_000, 1_000_000_000)
+            .with_streak(3, ResetPeriod::Daily, Severity::Critical);
+        assert_eq!(
+            format_alert(&alert),
+            r#"event=data_limit_exceeded app="my \"app\"" severity=critical usage=1500000000 limit=1000000000 streak=3"#
+        );
+        assert_eq!(
+            format_alert(&Alert::new("firefox")),
+            r#"event=data_limit_exceeded app="firefox" severity=warning"#
+        );
+        assert_eq!(
+            format_alert(&Alert::new("firefox").with_instance("office-nas")),
+            r#"event=data_limit_exceeded app="firefox" severity=warning instance=office-nas"#
+        );
+        assert_eq!(
+            format_alert(
+                &Alert::new("dropbox")
+                    .with_usage(1024, 2048)
+                    .with_forecast(4096, ResetPeriod::Daily)
+            ),
+            r#"event=data_limit_forecast app="dropbox" severity=info usage=1024 limit=2048 projected=4096"#
+        );
+        assert_eq!(
+            format_alert(&Alert::new("backup").with_anomaly(5000, 20)),
+            r#"event=data_usage_anomaly app="backup" severity=warning delta=5000 baseline=20"#
+        );
+        assert_eq!(
+            format_alert(&Alert::new("cc1").with_cpu_time(4000, 3600)),
+            r#"event=cpu_limit_exceeded app="cc1" severity=warning usage=4000 limit=3600"#
+        );
+        assert_eq!(
+            format_alert(&Alert::new("Data Guardian").with_started(vec!["curl".into()], 2048)),
+            r#"event=service_started app="Data Guardian" severity=info limit=2048 apps=1"#
+        );
+        assert_eq!(
+            format_alert(
+                &Alert::new("Data Guardian")
+                    .with_summary(vec!["steam".into(), "zoom".into()], 4096)
+            ),
+            r#"event=session_summary app="Data Guardian" severity=warning usage=4096 over_limit="steam,zoom""#
+        );
+        assert_eq!(
+            format_alert(&Alert::new("Data Guardian").with_budget(780, 1000, 75, 9)),
+            r#"event=data_budget_milestone app="Data Guardian" severity=info usage=780 limit=1000 milestone=75 days_left=9"#
+        );
+    }
+
+    #[cfg(feature = "notifications")]
+    #[test]
+    fn test_log_channel() {
+        let config = NotificationConfig {
+            channels: vec![ChannelKind::Log],
+            ..NotificationConfig::default()
+        };
+        let channels = channels(&config);
+        assert_eq!(channels.len(), 1);
+        assert_eq!(channels[0].name(), "log");
+        channels[0].send(&Alert::new("firefox")).unwrap();
+    }
+
+    #[test]
+    fn test_facility_config() {
+        let config: SyslogConfig = serde_json::from_str(r#"{"facility":"local3"}"#).unwrap();
+        assert_eq!(config.facility, SyslogFacility::Local3);
+        assert_eq!(config.socket, None);
+        assert!(serde_json::from_str::<SyslogConfig>(r#"{"facility":"bogus"}"#).is_err());
+    }
+
+    #[cfg(all(unix, feature = "notifications"))]
+    #[test]
+    fn test_syslog_channel() {
+        use std::os::unix::net::UnixDatagram;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+
+        let channel = SyslogChannel::new(SyslogConfig {
+            facility: SyslogFacility::Daemon,
+            socket: Some(path),
+        });
+        let alert = Alert::new("firefox").with_usage(2048, 1024);
+        channel.send(&alert).unwrap();
+
+        let mut buf = [0; 1024];
+        let len = server.recv(&mut buf).unwrap();
+        let line = String::from_utf8_lossy(&buf[..len]);
+        // daemon (3) * 8 + warning (4)
+        assert!(line.starts_with("<28>"), "{}", line);
+        assert!(line.contains(&format!("{}[{}]: ", SYSLOG_PROCESS, std::process::id())));
+        assert!(line.ends_with(
+            r#"event=data_limit_exceeded app="firefox" severity=warning usage=2048 limit=1024"#
+        ));
+    }
+}
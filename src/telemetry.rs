@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_SERVICE_NAME: &str = "data-guardian";
+pub const DEFAULT_EXPORT_INTERVAL: u64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OtelConfig {
+    pub enabled: bool,
+    pub endpoint: Option<String>,
+    pub headers: HashMap<String, String>,
+    pub service_name: String,
+    pub resource_attributes: HashMap<String, String>,
+    pub export_interval_seconds: u64,
+}
+
+impl Default for OtelConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            headers: HashMap::new(),
+            service_name: DEFAULT_SERVICE_NAME.to_string(),
+            resource_attributes: HashMap::new(),
+            export_interval_seconds: DEFAULT_EXPORT_INTERVAL,
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+pub use exporter::*;
+
+#[cfg(not(feature = "otel"))]
+pub fn shutdown() {}
+
+#[cfg(feature = "otel")]
+mod exporter {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, OnceLock};
+    use std::time::Duration;
+
+    use opentelemetry::KeyValue;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_otlp::{ExporterBuildError, WithExportConfig, WithHttpConfig};
+    use opentelemetry_sdk::Resource;
+    use opentelemetry_sdk::error::OTelSdkResult;
+    use opentelemetry_sdk::metrics::data::ResourceMetrics;
+    use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+    use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider, Temporality};
+    use opentelemetry_sdk::trace::{SdkTracerProvider, SpanData, SpanExporter};
+    use sysinfo::System;
+    use thiserror::Error;
+    use tracing::warn;
+
+    use super::OtelConfig;
+    use crate::metrics::Metrics;
+
+    static DROPPED_EXPORTS: AtomicU64 = AtomicU64::new(0);
+    static TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+    static METER_PROVIDER: OnceLock<SdkMeterProvider> = OnceLock::new();
+
+    #[derive(Error, Debug)]
+    pub enum TelemetryError {
+        #[error("Failed to build OTLP exporter: {0}")]
+        Build(#[from] ExporterBuildError),
+        #[error("Telemetry is already initialized")]
+        AlreadyInitialized,
+    }
+
+    pub fn dropped_exports() -> u64 {
+        DROPPED_EXPORTS.load(Ordering::Relaxed)
+    }
+
+    #[derive(Debug)]
+    struct CountingSpanExporter(opentelemetry_otlp::SpanExporter);
+
+    impl SpanExporter for CountingSpanExporter {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            let len = batch.len() as u64;
+            let result = self.0.export(batch).await;
+            if result.is_err() {
+                DROPPED_EXPORTS.fetch_add(len, Ordering::Relaxed);
+            }
+            result
+        }
+
+        fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+            self.0.shutdown_with_timeout(timeout)
+        }
+
+        fn force_flush(&mut self) -> OTelSdkResult {
+            self.0.force_flush()
+        }
+
+        fn set_resource(&mut self, resource: &Resource) {
+            self.0.set_resource(resource);
+        }
+    }
+
+    struct CountingMetricExporter(opentelemetry_otlp::MetricExporter);
+
+    impl PushMetricExporter for CountingMetricExporter {
+        async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+            let result = self.0.export(metrics).await;
+            if result.is_err() {
+                DROPPED_EXPORTS.fetch_add(1, Ordering::Relaxed);
+            }
+            result
+        }
+
+        fn force_flush(&self) -> OTelSdkResult {
+            self.0.force_flush()
+        }
+
+        fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+            self.0.shutdown_with_timeout(timeout)
+        }
+
+        fn temporality(&self) -> Temporality {
+            self.0.temporality()
+        }
+    }
+
+    fn resource(config: &OtelConfig) -> Resource {
+        let mut builder = Resource::builder().with_service_name(config.service_name.clone());
+        if let Some(host) = System::host_name() {
+            builder = builder.with_attribute(KeyValue::new("host.name", host));
+        }
+        builder
+            .with_attributes(
+                config
+                    .resource_attributes
+                    .iter()
+                    .map(|(key, value)| KeyValue::new(key.clone(), value.clone())),
+            )
+            .build()
+    }
+
+    fn signal_endpoint(config: &OtelConfig, path: &str) -> Option<String> {
+        config
+            .endpoint
+            .as_ref()
+            .map(|endpoint| format!("{}{}", endpoint.trim_end_matches('/'), path))
+    }
+
+    pub fn init_tracer(config: &OtelConfig) -> Result<SdkTracerProvider, TelemetryError> {
+        let mut builder = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_headers(config.headers.clone());
+        if let Some(endpoint) = signal_endpoint(config, "/v1/traces") {
+            builder = builder.with_endpoint(endpoint);
+        }
+
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(CountingSpanExporter(builder.build()?))
+            .with_resource(resource(config))
+            .build();
+        TRACER_PROVIDER
+            .set(provider.clone())
+            .map_err(|_| TelemetryError::AlreadyInitialized)?;
+        Ok(provider)
+    }
+
+    pub fn init_metrics(config: &OtelConfig, metrics: Arc<Metrics>) -> Result<(), TelemetryError> {
+        let mut builder = opentelemetry_otlp::MetricExporter::builder()
+            .with_http()
+            .with_headers(config.headers.clone());
+        if let Some(endpoint) = signal_endpoint(config, "/v1/metrics") {
+            builder = builder.with_endpoint(endpoint);
+        }
+
+        let reader = PeriodicReader::builder(CountingMetricExporter(builder.build()?))
+            .with_interval(Duration::from_secs(config.export_interval_seconds))
+            .build();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader)
+            .with_resource(resource(config))
+            .build();
+
+        let meter = provider.meter("data-guardian");
+        let usage_metrics = metrics.clone();
+        meter
+            .u64_observable_gauge("data_guardian.app.usage")
+            .with_unit("By")
+            .with_description("Disk I/O accumulated per application in the current period")
+            .with_callback(move |observer| {
+                for (app, bytes) in usage_metrics.app_usage() {
+                    observer.observe(bytes, &[KeyValue::new("app", app)]);
+                }
+            })
+            .build();
+        meter
+            .u64_observable_counter("data_guardian.alerts")
+            .with_description("Usage alerts by outcome")
+            .with_callback(move |observer| {
+                for (outcome, count) in metrics.alert_counts() {
+                    observer.observe(count, &[KeyValue::new("outcome", outcome)]);
+                }
+            })
+            .build();
+
+        METER_PROVIDER
+            .set(provider)
+            .map_err(|_| TelemetryError::AlreadyInitialized)
+    }
+
+    pub fn shutdown() {
+        if let Some(provider) = METER_PROVIDER.get()
+            && let Err(e) = provider.shutdown()
+        {
+            warn!(error = %e, "Failed to flush OpenTelemetry metrics");
+        }
+        if let Some(provider) = TRACER_PROVIDER.get()
+            && let Err(e) = provider.shutdown()
+        {
+            warn!(error = %e, "Failed to flush OpenTelemetry traces");
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_signal_endpoint() {
+            let config = OtelConfig {
+                endpoint: Some("http://collector:4318/".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                signal_endpoint(&config, "/v1/traces").as_deref(),
+                Some("http://collector:4318/v1/traces")
+            );
+            assert_eq!(signal_endpoint(&OtelConfig::default(), "/v1/traces"), None);
+        }
+
+        #[test]
+        fn test_resource_attributes() {
+            let config = OtelConfig {
+                resource_attributes: [("deployment.environment".to_string(), "lab".to_string())]
+                    .into(),
+                ..Default::default()
+            };
+            let resource = resource(&config);
+            assert_eq!(
+                resource
+                    .get(&opentelemetry::Key::new("service.name"))
+                    .map(|value| value.to_string()),
+                Some(super::super::DEFAULT_SERVICE_NAME.to_string())
+            );
+            assert_eq!(
+                resource
+                    .get(&opentelemetry::Key::new("deployment.environment"))
+                    .map(|value| value.to_string()),
+                Some("lab".to_string())
+            );
+        }
+    }
+}
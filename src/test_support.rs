@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+#[cfg(feature = "monitor")]
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use super::notification::{Alert, NotificationChannel, NotificationError};
+
+pub const RECORDING_FAILURE: &str = "recording channel set to fail";
+
+pub fn create_test_data(size: usize) -> HashMap<String, u64> {
+    let mut data = HashMap::with_capacity(size);
+    for i in 0..size {
+        data.insert(format!("process_{}", i), i as u64);
+    }
+    data
+}
+
+#[derive(Debug, Default)]
+pub struct RecordingChannel {
+    name: Option<String>,
+    failing: AtomicBool,
+    alerts: Mutex<Vec<Alert>>,
+    delay: Duration,
+}
+
+impl RecordingChannel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn failing() -> Self {
+        let channel = Self::new();
+        channel.set_failing(true);
+        channel
+    }
+
+    /// Names the channel `name` instead of `recording`, for routes.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Makes every send block for `delay`, like a slow platform call.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+
+    pub fn alerts(&self) -> Vec<Alert> {
+        self.alerts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn apps(&self) -> Vec<String> {
+        self.alerts().into_iter().map(|alert| alert.app).collect()
+    }
+}
+
+impl NotificationChannel for RecordingChannel {
+    fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("recording")
+    }
+
+    fn send(&self, alert: &Alert) -> Result<(), NotificationError> {
+        std::thread::sleep(self.delay);
+        self.alerts
+            .lock()
+            .map_err(|_| NotificationError::LockError)?
+            .push(alert.clone());
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(NotificationError::ShowError(RECORDING_FAILURE.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// A usage source whose reads fail while set to, like a `/proc` that cannot
+/// be read.
+#[cfg(feature = "monitor")]
+#[derive(Debug, Default)]
+pub struct FailingSource {
+    failing: AtomicBool,
+    reads: std::sync::atomic::AtomicU32,
+}
+
+#[cfg(feature = "monitor")]
+impl FailingSource {
+    pub fn failing() -> Self {
+        let source = Self::default();
+        source.set_failing(true);
+        source
+    }
+
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+
+    pub fn reads(&self) -> u32 {
+        self.reads.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(feature = "monitor")]
+impl super::monitor::CounterSource for FailingSource {
+    fn read(&self) -> Result<super::monitor::ProcessData, super::monitor::MonitorError> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(super::monitor::MonitorError::Io {
+                path: "/proc".into(),
+                source: std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+            });
+        }
+        Ok(Default::default())
+    }
+}
+
+/// A usage source that returns the given snapshots in order, then keeps
+/// returning the last one.
+#[cfg(feature = "monitor")]
+#[derive(Debug, Default)]
+pub struct ScriptedSource {
+    snapshots: Mutex<std::collections::VecDeque<super::monitor::ProcessData>>,
+    hung: Mutex<bool>,
+    released: Condvar,
+}
+
+#[cfg(feature = "monitor")]
+impl ScriptedSource {
+    pub fn new(snapshots: impl IntoIterator<Item = super::monitor::ProcessData>) -> Self {
+        Self {
+            snapshots: Mutex::new(snapshots.into_iter().collect()),
+            ..Self::default()
+        }
+    }
+
+    /// Makes every read block until `release`, like a hung process table.
+    pub fn hung(self) -> Self {
+        *self.hung.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        self
+    }
+
+    /// Lets blocked reads and all later ones through.
+    pub fn release(&self) {
+        *self.hung.lock().unwrap_or_else(|e| e.into_inner()) = false;
+        self.released.notify_all();
+    }
+}
+
+#[cfg(feature = "monitor")]
+impl super::monitor::CounterSource for ScriptedSource {
+    fn read(&self) -> Result<super::monitor::ProcessData, super::monitor::MonitorError> {
+        let hung = self.hung.lock().unwrap_or_else(|e| e.into_inner());
+        drop(
+            self.released
+                .wait_while(hung, |hung| *hung)
+                .unwrap_or_else(|e| e.into_inner()),
+        );
+        let mut snapshots = self.snapshots.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(match snapshots.len() {
+            0 | 1 => snapshots.front().cloned().unwrap_or_default(),
+            _ => snapshots.pop_front().unwrap_or_default(),
+        })
+    }
+}
+
+pub const PANICKING_SOURCE_MESSAGE: &str = "panicking source read";
+
+/// A usage source that panics on every read, like a bug in a backend.
+#[cfg(feature = "monitor")]
+#[derive(Debug, Default)]
+pub struct PanickingSource;
+
+#[cfg(feature = "monitor")]
+impl super::monitor::CounterSource for PanickingSource {
+    fn read(&self) -> Result<super::monitor::ProcessData, super::monitor::MonitorError> {
+        panic!("{}", PANICKING_SOURCE_MESSAGE)
+    }
+}
@@ -0,0 +1,190 @@
+//! Ranking of apps by usage, rate or name, shared by the status endpoint and
+//! `dg dashboard`.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::monitor::UsageData;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// Most bytes first.
+    #[default]
+    Usage,
+    /// Fastest first.
+    Rate,
+    Name,
+}
+
+impl SortKey {
+    /// The key after this one, for cycling through usage, rate and name.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Usage => Self::Rate,
+            Self::Rate => Self::Name,
+            Self::Name => Self::Usage,
+        }
+    }
+}
+
+/// One app's row in a ranking.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppUsageView {
+    pub name: String,
+    pub bytes: u64,
+    pub limit: u64,
+    /// `bytes` as a percentage of `limit`; `None` when the limit is zero.
+    pub percent: Option<f64>,
+    /// Bytes per second, or 0 when no rate is known.
+    pub rate: f64,
+    pub over: bool,
+}
+
+/// Usage of a set of apps, with the limit they share and their current
+/// rates.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UsageSnapshot {
+    pub apps: UsageData,
+    pub rates: HashMap<String, f64>,
+    pub limit: u64,
+}
+
+impl UsageSnapshot {
+    pub fn new(apps: UsageData, limit: u64) -> Self {
+        Self {
+            apps,
+            rates: HashMap::new(),
+            limit,
+        }
+    }
+
+    pub fn with_rates(mut self, rates: HashMap<String, f64>) -> Self {
+        self.rates = rates;
+        self
+    }
+
+    /// The first `n` apps by `key`. Ties are broken by name, so the order is
+    /// the same on every call.
+    pub fn top_n(&self, n: usize, key: SortKey) -> Vec<AppUsageView> {
+        let rate = |name: &str| self.rates.get(name).copied().unwrap_or(0.0);
+        let mut apps: Vec<_> = self.apps.iter().collect();
+        apps.sort_by(|(a, a_bytes), (b, b_bytes)| {
+            let order = match key {
+                SortKey::Usage => b_bytes.cmp(a_bytes),
+                SortKey::Rate => rate(b).total_cmp(&rate(a)),
+                SortKey::Name => Ordering::Equal,
+            };
+            order.then_with(|| a.cmp(b))
+        });
+        apps.into_iter()
+            .take(n)
+            .map(|(name, bytes)| AppUsageView {
+                name: name.clone(),
+                bytes: *bytes,
+                limit: self.limit,
+                percent: (self.limit > 0).then(|| *bytes as f64 * 100.0 / self.limit as f64),
+                rate: rate(name),
+                over: *bytes > self.limit,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tracker::OTHER_APP;
+    use super::*;
+
+    fn snapshot(apps: &[(&str, u64)], limit: u64) -> UsageSnapshot {
+        let apps = apps
+            .iter()
+            .map(|(name, bytes)| (name.to_string(), *bytes))
+            .collect();
+        UsageSnapshot::new(apps, limit)
+    }
+
+    fn names(views: &[AppUsageView]) -> Vec<&str> {
+        views.iter().map(|view| view.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_ties_break_by_name() {
+        let snapshot = snapshot(
+            &[("zoom", 50), ("curl", 50), ("steam", 80), ("apt", 50)],
+            100,
+        )
+        .with_rates(HashMap::from([
+            ("zoom".to_string(), 2.0),
+            ("curl".to_string(), 2.0),
+        ]));
+
+        assert_eq!(
+            names(&snapshot.top_n(3, SortKey::Usage)),
+            ["steam", "apt", "curl"]
+        );
+        assert_eq!(
+            names(&snapshot.top_n(4, SortKey::Rate)),
+            ["curl", "zoom", "apt", "steam"]
+        );
+        assert_eq!(
+            names(&snapshot.top_n(4, SortKey::Name)),
+            ["apt", "curl", "steam", "zoom"]
+        );
+        assert_eq!(SortKey::Name.next(), SortKey::Usage);
+    }
+
+    #[test]
+    fn test_fewer_apps_than_n() {
+        let views = snapshot(&[("curl", 150), ("apt", 50)], 100).top_n(10, SortKey::Usage);
+        assert_eq!(
+            views,
+            [
+                AppUsageView {
+                    name: "curl".to_string(),
+                    bytes: 150,
+                    limit: 100,
+                    percent: Some(150.0),
+                    rate: 0.0,
+                    over: true,
+                },
+                AppUsageView {
+                    name: "apt".to_string(),
+                    bytes: 50,
+                    limit: 100,
+                    percent: Some(50.0),
+                    rate: 0.0,
+                    over: false,
+                },
+            ]
+        );
+        assert!(UsageSnapshot::default().top_n(5, SortKey::Rate).is_empty());
+        assert!(
+            snapshot(&[("apt", 1)], 100)
+                .top_n(0, SortKey::Usage)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_zero_limit() {
+        let views = snapshot(&[("apt", 10), ("idle", 0)], 0).top_n(2, SortKey::Usage);
+        assert_eq!(views[0].percent, None);
+        assert!(views[0].over);
+        assert_eq!(views[1].percent, None);
+        assert!(!views[1].over);
+    }
+
+    #[test]
+    fn test_overflow_group_ranks_like_an_app() {
+        // Apps past `max_tracked_apps` are summed into one entry, which is
+        // ranked and limited like any other app.
+        let snapshot = snapshot(&[(OTHER_APP, 300), ("curl", 200), ("apt", 100)], 250);
+        let views = snapshot.top_n(2, SortKey::Usage);
+        assert_eq!(names(&views), [OTHER_APP, "curl"]);
+        assert!(views[0].over);
+        assert_eq!(views[0].percent, Some(120.0));
+    }
+}
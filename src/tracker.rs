@@ -0,0 +1,1828 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::budget::{BudgetConfig, BudgetState};
+use super::compression::{self, CompressionConfig, CompressionError};
+use super::history::Severity;
+use super::monitor;
+
+pub const TRACKER_VERSION: u32 = 1;
+pub const OTHER_APP: &str = "(other)";
+pub const DEFAULT_REPEAT_THRESHOLD: u32 = 3;
+pub const FORECAST_WINDOW: TimeDelta = TimeDelta::hours(1);
+pub const FORECAST_MIN_SPAN: TimeDelta = TimeDelta::minutes(10);
+pub const FORECAST_SAMPLE_INTERVAL: TimeDelta = TimeDelta::minutes(1);
+pub const DEFAULT_ANOMALY_STD_DEVS: f64 = 4.0;
+pub const DEFAULT_ANOMALY_MIN_DELTA: u64 = 10 * 1024 * 1024;
+pub const DEFAULT_ANOMALY_WARMUP_TICKS: u64 = 30;
+/// Days of per-app usage kept for comparisons, enough for two months.
+pub const DAILY_HISTORY_DAYS: u64 = 62;
+/// Months of rolled-up usage kept before the daily history.
+pub const DEFAULT_HISTORY_MONTHS: u32 = 24;
+/// Data file incidents kept in the state.
+pub const MAX_INCIDENTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResetPeriod {
+    #[default]
+    Never,
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl ResetPeriod {
+    pub fn period_start(self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Self::Never => None,
+            Self::Daily => Some(date),
+            Self::Weekly => {
+                date.checked_sub_days(Days::new(date.weekday().num_days_from_monday().into()))
+            }
+            Self::Monthly => date.with_day(1),
+        }
+    }
+
+    pub fn next_period_start(self, start: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Self::Never => None,
+            Self::Daily => start.checked_add_days(Days::new(1)),
+            Self::Weekly => start.checked_add_days(Days::new(7)),
+            Self::Monthly => start.checked_add_months(Months::new(1)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResetTimezone {
+    #[default]
+    Local,
+    Iana(Tz),
+}
+
+impl ResetTimezone {
+    pub fn parse(name: &str) -> Option<Self> {
+        name.parse().ok().map(Self::Iana)
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Self::Local => None,
+            Self::Iana(tz) => Some(tz.name()),
+        }
+    }
+
+    pub fn date(&self, now: DateTime<Utc>) -> NaiveDate {
+        match self {
+            Self::Local => now.with_timezone(&Local).date_naive(),
+            Self::Iana(tz) => now.with_timezone(tz).date_naive(),
+        }
+    }
+
+    pub fn start_of_day(&self, date: NaiveDate) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Local => start_of_day(&Local, date),
+            Self::Iana(tz) => start_of_day(tz, date),
+        }
+    }
+}
+
+fn start_of_day<Z: TimeZone>(zone: &Z, date: NaiveDate) -> Option<DateTime<Utc>> {
+    let midnight = date.and_hms_opt(0, 0, 0)?;
+    // Midnight falls into a DST gap in some zones; the day then starts when the clocks resume.
+    (0..=24 * 4)
+        .map(|quarter| midnight + TimeDelta::minutes(15 * quarter))
+        .find_map(|time| time.and_local_timezone(zone.clone()).earliest())
+        .map(|start| start.to_utc())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageSample {
+    pub time: DateTime<Utc>,
+    pub bytes: u64,
+}
+
+pub fn project(current: u64, rate: f64, remaining: TimeDelta) -> u64 {
+    let seconds = remaining.num_milliseconds().max(0) as f64 / 1000.0;
+    let projected = current as f64 + rate.max(0.0) * seconds;
+    if projected >= u64::MAX as f64 {
+        u64::MAX
+    } else {
+        projected.round() as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeltaStats {
+    pub count: u64,
+    pub mean: f64,
+    pub m2: f64,
+}
+
+impl DeltaStats {
+    pub fn observe(&mut self, delta: u64) {
+        let value = delta as f64;
+        self.count += 1;
+        let diff = value - self.mean;
+        self.mean += diff / self.count as f64;
+        self.m2 += diff * (value - self.mean);
+    }
+
+    /// Combines the stats of two independent series of deltas.
+    pub fn merge(&mut self, other: &DeltaStats) {
+        let count = self.count + other.count;
+        if count == 0 {
+            return;
+        }
+        let diff = other.mean - self.mean;
+        let weight = other.count as f64 / count as f64;
+        self.m2 += other.m2 + diff * diff * self.count as f64 * weight;
+        self.mean += diff * weight;
+        self.count = count;
+    }
+
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnomalyConfig {
+    pub enabled: bool,
+    pub std_devs: f64,
+    pub min_delta_bytes: u64,
+    pub warmup_ticks: u64,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            std_devs: DEFAULT_ANOMALY_STD_DEVS,
+            min_delta_bytes: DEFAULT_ANOMALY_MIN_DELTA,
+            warmup_ticks: DEFAULT_ANOMALY_WARMUP_TICKS,
+        }
+    }
+}
+
+impl AnomalyConfig {
+    pub fn is_anomaly(&self, stats: &DeltaStats, delta: u64) -> bool {
+        stats.count >= self.warmup_ticks
+            && delta >= self.min_delta_bytes
+            && delta as f64 > stats.mean + self.std_devs * stats.std_dev()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppRecord {
+    pub bytes: u64,
+    /// When usage was first recorded for the app. Unknown for apps tracked
+    /// before this was kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<DateTime<Utc>>,
+    pub streak: u32,
+    pub last_exceeded: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "VecDeque::is_empty")]
+    pub samples: VecDeque<UsageSample>,
+    pub deltas: DeltaStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_time_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_memory_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "ExceedanceStats::is_empty")]
+    pub exceedance: ExceedanceStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alerted: Option<AlertFingerprint>,
+    /// The last scan in which usage grew.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_active: Option<DateTime<Utc>>,
+}
+
+/// The last data limit alert sent for an app. Kept with the usage data so
+/// neither a restart nor an expired cooldown repeats it within a period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertFingerprint {
+    /// The period the alert was sent in.
+    pub period_start: Option<NaiveDate>,
+    /// How many times over the limit usage was, at least 1.
+    pub multiple: u64,
+    pub severity: Severity,
+}
+
+impl AlertFingerprint {
+    /// Whether an alert with this fingerprint says more than `last`: it is
+    /// from a later period, usage reached a higher multiple of the limit, or
+    /// the alert escalated.
+    pub fn supersedes(&self, last: &AlertFingerprint) -> bool {
+        self.period_start != last.period_start
+            || self.multiple > last.multiple
+            || self.severity > last.severity
+    }
+}
+
+/// How often an app has gone over its data limit. Unlike usage, these
+/// survive period roll-overs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExceedanceStats {
+    pub events: u64,
+    pub first_exceeded: Option<DateTime<Utc>>,
+    pub last_exceeded: Option<DateTime<Utc>>,
+    /// Start of the ongoing exceedance, if the app is over its limit now.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub over_since: Option<DateTime<Utc>>,
+    /// Time spent over the limit in finished exceedances.
+    pub seconds_over: u64,
+}
+
+impl ExceedanceStats {
+    pub fn is_empty(&self) -> bool {
+        self.events == 0
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.over_since.is_some()
+    }
+
+    /// Marks the app as over its limit, returning whether this starts a new
+    /// exceedance.
+    pub fn exceed(&mut self, now: DateTime<Utc>) -> bool {
+        if self.is_over() {
+            return false;
+        }
+        self.events += 1;
+        self.first_exceeded.get_or_insert(now);
+        self.last_exceeded = Some(now);
+        self.over_since = Some(now);
+        true
+    }
+
+    /// Ends the ongoing exceedance, returning how long it lasted.
+    pub fn recover(&mut self, now: DateTime<Utc>) -> Option<TimeDelta> {
+        let over = (now - self.over_since.take()?).max(TimeDelta::zero());
+        self.seconds_over = self.seconds_over.saturating_add(over.num_seconds() as u64);
+        Some(over)
+    }
+
+    /// Total time over the limit, including the ongoing exceedance.
+    pub fn time_over(&self, now: DateTime<Utc>) -> u64 {
+        let ongoing = self
+            .over_since
+            .map_or(0, |since| (now - since).num_seconds().max(0) as u64);
+        self.seconds_over.saturating_add(ongoing)
+    }
+
+    /// A copy whose `seconds_over` includes the ongoing exceedance, for
+    /// reports taken at `now`.
+    pub fn at(&self, now: DateTime<Utc>) -> Self {
+        Self {
+            seconds_over: self.time_over(now),
+            ..*self
+        }
+    }
+}
+
+/// A data file that could not be read at startup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataIncident {
+    pub time: DateTime<Utc>,
+    /// The unreadable file, and where it was moved aside to if that worked.
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantined_to: Option<String>,
+    pub size_bytes: u64,
+    pub error: String,
+    /// The copy the usage was restored from; without one, usage started over.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restored_from: Option<String>,
+}
+
+/// What `UsageTracker::compact` removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Compaction {
+    /// Days folded into monthly buckets, or dropped without them.
+    pub days: usize,
+    /// Monthly buckets dropped for being too old.
+    pub months: usize,
+}
+
+impl Compaction {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The first day of `date`'s month.
+fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+/// One app's usage on one day, or in a month of `UsageTracker::months`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredDayUsage")]
+pub struct DayUsage {
+    /// Bytes read and written.
+    pub bytes: u64,
+    /// The part of `bytes` that was written.
+    pub written: u64,
+    /// Times the app went over its data limit on this day.
+    pub exceeded: u32,
+}
+
+impl DayUsage {
+    pub fn read(&self) -> u64 {
+        self.bytes.saturating_sub(self.written)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredDayUsage {
+    Usage {
+        #[serde(default)]
+        bytes: u64,
+        #[serde(default)]
+        written: u64,
+        #[serde(default)]
+        exceeded: u32,
+    },
+    /// Days saved before reads and writes were told apart.
+    Bytes(u64),
+}
+
+impl From<StoredDayUsage> for DayUsage {
+    fn from(stored: StoredDayUsage) -> Self {
+        match stored {
+            StoredDayUsage::Usage {
+                bytes,
+                written,
+                exceeded,
+            } => Self {
+                bytes,
+                written,
+                exceeded,
+            },
+            StoredDayUsage::Bytes(bytes) => Self {
+                bytes,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppResources {
+    pub cpu_time_ms: Option<u64>,
+    pub peak_memory_bytes: Option<u64>,
+}
+
+impl AppRecord {
+    pub fn resources(&self) -> Option<AppResources> {
+        (self.cpu_time_ms.is_some() || self.peak_memory_bytes.is_some()).then_some(AppResources {
+            cpu_time_ms: self.cpu_time_ms,
+            peak_memory_bytes: self.peak_memory_bytes,
+        })
+    }
+
+    pub fn record_sample(&mut self, now: DateTime<Utc>) {
+        if self
+            .samples
+            .back()
+            .is_some_and(|last| now - last.time < FORECAST_SAMPLE_INTERVAL)
+        {
+            return;
+        }
+        self.samples.push_back(UsageSample {
+            time: now,
+            bytes: self.bytes,
+        });
+        while self
+            .samples
+            .front()
+            .is_some_and(|first| now - first.time > FORECAST_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn rate(&self) -> Option<f64> {
+        let (first, last) = (self.samples.front()?, self.samples.back()?);
+        let span = last.time - first.time;
+        if span < FORECAST_MIN_SPAN {
+            return None;
+        }
+        let bytes = last.bytes.saturating_sub(first.bytes) as f64;
+        Some(bytes * 1000.0 / span.num_milliseconds() as f64)
+    }
+
+    pub fn record_exceedance(&mut self, reset_period: ResetPeriod, period: NaiveDate) -> u32 {
+        if self.last_exceeded == Some(period) {
+            return self.streak;
+        }
+
+        let consecutive = self
+            .last_exceeded
+            .and_then(|last| reset_period.next_period_start(last))
+            .is_some_and(|next| next == period);
+
+        self.streak = if consecutive { self.streak + 1 } else { 1 };
+        self.last_exceeded = Some(period);
+        self.streak
+    }
+
+    pub fn current_streak(&self, reset_period: ResetPeriod, period: NaiveDate) -> u32 {
+        match self.last_exceeded {
+            Some(last) if last == period => self.streak,
+            Some(last) if reset_period.next_period_start(last) == Some(period) => self.streak,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EscalationConfig {
+    pub repeat_threshold: u32,
+    pub critical_cooldown_seconds: u64,
+}
+
+impl Default for EscalationConfig {
+    fn default() -> Self {
+        Self {
+            repeat_threshold: DEFAULT_REPEAT_THRESHOLD,
+            critical_cooldown_seconds: 60,
+        }
+    }
+}
+
+impl EscalationConfig {
+    pub fn severity(&self, streak: u32) -> Severity {
+        if self.repeat_threshold > 0 && streak >= self.repeat_threshold {
+            Severity::Critical
+        } else {
+            Severity::Warning
+        }
+    }
+}
+
+/// When the service last scanned and saved, and which version wrote the
+/// data file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateInfo {
+    /// Scans counted into this file, across restarts.
+    pub ticks: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_scan: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_save: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub written_by: Option<String>,
+}
+
+impl StateInfo {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredUsage {
+    Tracker(UsageTracker),
+    Legacy(HashMap<String, u64>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageTracker {
+    pub version: u32,
+    pub period_start: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    pub apps: HashMap<String, AppRecord>,
+    /// Usage per app for each recent day. Unlike `apps`, these are not
+    /// cleared when a period rolls over.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub days: BTreeMap<NaiveDate, HashMap<String, DayUsage>>,
+    /// Usage per app for each month before `days`, keyed by the month's
+    /// first day. A month can also have days left in `days`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub months: BTreeMap<NaiveDate, HashMap<String, DayUsage>>,
+    /// The last `MAX_INCIDENTS` unreadable data files, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub incidents: Vec<DataIncident>,
+    #[serde(default, skip_serializing_if = "BudgetState::is_empty")]
+    pub budget: BudgetState,
+    #[serde(default, skip_serializing_if = "StateInfo::is_empty")]
+    pub state: StateInfo,
+}
+
+impl Default for UsageTracker {
+    fn default() -> Self {
+        Self {
+            version: TRACKER_VERSION,
+            period_start: None,
+            timezone: None,
+            apps: HashMap::new(),
+            days: BTreeMap::new(),
+            months: BTreeMap::new(),
+            incidents: Vec::new(),
+            budget: BudgetState::default(),
+            state: StateInfo::default(),
+        }
+    }
+}
+
+impl From<HashMap<String, u64>> for UsageTracker {
+    fn from(usage: HashMap<String, u64>) -> Self {
+        Self {
+            apps: usage
+                .into_iter()
+                .map(|(app, bytes)| {
+                    let record = AppRecord {
+                        bytes,
+                        ..Default::default()
+                    };
+                    (app, record)
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl UsageTracker {
+    pub fn from_compressed(data: &[u8]) -> Result<Self, CompressionError> {
+        Ok(match compression::decompress_json(data)? {
+            StoredUsage::Tracker(tracker) => tracker,
+            StoredUsage::Legacy(usage) => usage.into(),
+        })
+    }
+
+    pub fn to_compressed(&self) -> Result<Vec<u8>, CompressionError> {
+        compression::compress_json(self, self.apps.len())
+    }
+
+    pub fn to_compressed_with(
+        &self,
+        config: CompressionConfig,
+    ) -> Result<Vec<u8>, CompressionError> {
+        compression::compress_json_with_config(self, self.apps.len(), config)
+    }
+
+    pub fn usage(&self) -> HashMap<String, u64> {
+        self.apps
+            .iter()
+            .map(|(app, record)| (app.clone(), record.bytes))
+            .collect()
+    }
+
+    pub fn bound_apps<V>(
+        &self,
+        data: HashMap<String, V>,
+        max_apps: usize,
+        merge: impl Fn(&mut V, V),
+    ) -> (HashMap<String, V>, usize) {
+        if max_apps == 0 {
+            return (data, 0);
+        }
+        let mut room = max_apps.saturating_sub(self.apps.len());
+        let mut bounded = HashMap::with_capacity(data.len().min(max_apps + 1));
+        let mut overflow = 0;
+        for (app, value) in data {
+            let app = if self.apps.contains_key(&app) || !monitor::is_app(&app) {
+                app
+            } else if room > 0 {
+                room -= 1;
+                app
+            } else {
+                overflow += 1;
+                OTHER_APP.to_string()
+            };
+            match bounded.entry(app) {
+                Entry::Occupied(mut entry) => merge(entry.get_mut(), value),
+                Entry::Vacant(entry) => {
+                    entry.insert(value);
+                }
+            }
+        }
+        (bounded, overflow)
+    }
+
+    /// Notes `now` as the first time `app` was seen, unless it is already
+    /// tracked.
+    pub fn mark_seen(&mut self, app: &str, now: DateTime<Utc>) {
+        self.apps
+            .entry(app.to_string())
+            .or_insert_with(|| AppRecord {
+                first_seen: Some(now),
+                ..Default::default()
+            });
+    }
+
+    pub fn mark_active(&mut self, app: &str, now: DateTime<Utc>) {
+        self.apps.entry(app.to_string()).or_default().last_active = Some(now);
+    }
+
+    pub fn last_active(&self) -> HashMap<String, DateTime<Utc>> {
+        self.apps
+            .iter()
+            .filter_map(|(app, record)| Some((app.clone(), record.last_active?)))
+            .collect()
+    }
+
+    /// Counts a scan and returns its number.
+    pub fn record_scan(&mut self, now: DateTime<Utc>) -> u64 {
+        self.state.ticks = self.state.ticks.saturating_add(1);
+        self.state.last_scan = Some(now);
+        self.state.ticks
+    }
+
+    pub fn mark_saved(&mut self, now: DateTime<Utc>, version: &str) {
+        self.state.last_save = Some(now);
+        self.state.written_by = Some(version.to_string());
+    }
+
+    /// The data as it would be saved at `now` by `version`. The tracker is
+    /// left as it was; call `mark_saved` once the write succeeds.
+    pub fn to_saved_compressed(
+        &mut self,
+        now: DateTime<Utc>,
+        version: &str,
+        config: CompressionConfig,
+    ) -> Result<Vec<u8>, CompressionError> {
+        let state = self.state.clone();
+        self.mark_saved(now, version);
+        let compressed = self.to_compressed_with(config);
+        self.state = state;
+        compressed
+    }
+
+    /// Whether `app` was first seen less than `grace` before `now`.
+    pub fn in_grace(&self, app: &str, grace: TimeDelta, now: DateTime<Utc>) -> bool {
+        self.apps
+            .get(app)
+            .and_then(|record| record.first_seen)
+            .is_some_and(|seen| now - seen < grace)
+    }
+
+    /// Apps whose grace period ended after `since` and by `now`, sorted by
+    /// name.
+    pub fn grace_ended(
+        &self,
+        grace: TimeDelta,
+        since: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Vec<String> {
+        let mut apps: Vec<_> = self
+            .apps
+            .iter()
+            .filter(|(_, record)| {
+                record
+                    .first_seen
+                    .is_some_and(|seen| since < seen + grace && seen + grace <= now)
+            })
+            .map(|(app, _)| app.clone())
+            .collect();
+        apps.sort();
+        apps
+    }
+
+    pub fn add_usage(&mut self, app: &str, delta: u64) -> u64 {
+        let record = self.apps.entry(app.to_string()).or_default();
+        record.bytes = record.bytes.checked_add(delta).unwrap_or_else(|| {
+            warn!(%app, %delta, "Usage total saturated");
+            u64::MAX
+        });
+        record.bytes
+    }
+
+    /// Replaces the usage of `app` with a measured size, as for watched
+    /// paths, whose size is not reset when a new period starts.
+    pub fn record_size(&mut self, app: &str, bytes: u64) {
+        self.apps.entry(app.to_string()).or_default().bytes = bytes;
+    }
+
+    pub fn resources(&self) -> HashMap<String, AppResources> {
+        self.apps
+            .iter()
+            .filter_map(|(app, record)| Some((app.clone(), record.resources()?)))
+            .collect()
+    }
+
+    pub fn add_cpu_time(&mut self, app: &str, delta_ms: u64) -> u64 {
+        let record = self.apps.entry(app.to_string()).or_default();
+        let total = record.cpu_time_ms.unwrap_or(0).saturating_add(delta_ms);
+        record.cpu_time_ms = Some(total);
+        total
+    }
+
+    pub fn record_memory(&mut self, app: &str, bytes: u64) -> u64 {
+        let record = self.apps.entry(app.to_string()).or_default();
+        let peak = record.peak_memory_bytes.unwrap_or(0).max(bytes);
+        record.peak_memory_bytes = Some(peak);
+        peak
+    }
+
+    /// Adds `delta` bytes, `written` of them written, to the bucket of `app`
+    /// for `date`. Old buckets stay until `compact`.
+    pub fn add_daily_usage(&mut self, app: &str, delta: u64, written: u64, date: NaiveDate) {
+        let day = self.day_mut(app, date);
+        day.bytes = day.bytes.saturating_add(delta);
+        day.written = day.written.saturating_add(written.min(delta));
+    }
+
+    /// Folds the days before the last `history_days` into one bucket per
+    /// month, and drops the months before the last `history_months` ahead
+    /// of `today`'s month. With `history_months` at zero, old days are
+    /// dropped outright. The new history is built on the side and replaces
+    /// the old one only once it is complete.
+    pub fn compact(
+        &mut self,
+        history_days: u64,
+        history_months: u32,
+        today: NaiveDate,
+    ) -> Compaction {
+        let oldest_day = today
+            .checked_sub_days(Days::new(history_days))
+            .unwrap_or(NaiveDate::MIN);
+        let oldest_month = month_start(today)
+            .checked_sub_months(Months::new(history_months))
+            .unwrap_or(NaiveDate::MIN);
+
+        let mut old_days = self.days.clone();
+        let days = old_days.split_off(&oldest_day);
+        let mut months = self.months.clone();
+        for (date, apps) in &old_days {
+            let month = months.entry(month_start(*date)).or_default();
+            for (app, usage) in apps {
+                let total = month.entry(app.clone()).or_default();
+                total.bytes = total.bytes.saturating_add(usage.bytes);
+                total.written = total.written.saturating_add(usage.written);
+                total.exceeded = total.exceeded.saturating_add(usage.exceeded);
+            }
+        }
+        let kept_months = match history_months {
+            0 => BTreeMap::new(),
+            _ => months.split_off(&oldest_month),
+        };
+        let compaction = Compaction {
+            days: old_days.len(),
+            months: self
+                .months
+                .keys()
+                .filter(|month| !kept_months.contains_key(month))
+                .count(),
+        };
+        self.days = days;
+        self.months = kept_months;
+        compaction
+    }
+
+    /// Spreads usage that built up between `start` and `end`, e.g. while the
+    /// machine was asleep, over the days in between in proportion to the time
+    /// of each day the span covers.
+    pub fn add_daily_usage_between(
+        &mut self,
+        app: &str,
+        delta: u64,
+        written: u64,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        timezone: ResetTimezone,
+    ) {
+        let last = timezone.date(end);
+        let span = (end - start).num_milliseconds();
+        let (mut day, mut from) = (timezone.date(start), start);
+        let (mut spread, mut spread_written) = (0u64, 0u64);
+        while span > 0 && day < last {
+            let Some(next) = day.succ_opt() else {
+                break;
+            };
+            let until = timezone.start_of_day(next).unwrap_or(end).clamp(from, end);
+            let share = |bytes: u64| {
+                (u128::from(bytes) * (until - from).num_milliseconds() as u128 / span as u128)
+                    as u64
+            };
+            let (bytes, bytes_written) = (share(delta), share(written));
+            self.add_daily_usage(app, bytes, bytes_written, day);
+            spread += bytes;
+            spread_written += bytes_written;
+            (day, from) = (next, until);
+        }
+        self.add_daily_usage(app, delta - spread, written - spread_written, last);
+    }
+
+    /// Drops every app's forecast samples, so rates start over.
+    pub fn clear_samples(&mut self) {
+        for record in self.apps.values_mut() {
+            record.samples.clear();
+        }
+    }
+
+    fn day_mut(&mut self, app: &str, date: NaiveDate) -> &mut DayUsage {
+        self.days
+            .entry(date)
+            .or_default()
+            .entry(app.to_string())
+            .or_default()
+    }
+
+    /// Total usage per app over the days from `start` up to, but not
+    /// including, `end`.
+    pub fn usage_between(&self, start: NaiveDate, end: NaiveDate) -> HashMap<String, u64> {
+        let mut usage = HashMap::new();
+        for apps in self.days.range(start..end).map(|(_, apps)| apps) {
+            for (app, day) in apps {
+                let total: &mut u64 = usage.entry(app.clone()).or_default();
+                *total = total.saturating_add(day.bytes);
+            }
+        }
+        usage
+    }
+
+    /// Adds the bytes apps used on `today` to the data budget. A new billing
+    /// period starts from the daily history of its first days. Returns a
+    /// newly reached milestone.
+    pub fn add_budget_usage(
+        &mut self,
+        config: &BudgetConfig,
+        delta: u64,
+        today: NaiveDate,
+    ) -> Vec<u32> {
+        let days = &self.days;
+        self.budget.add(config, delta, today, |start| {
+            days.range(start..=today)
+                .flat_map(|(_, apps)| apps)
+                .filter(|(app, _)| monitor::is_app(app))
+                .fold(0u64, |sum, (_, day)| sum.saturating_add(day.bytes))
+        })
+    }
+
+    pub fn record_incident(&mut self, incident: DataIncident) {
+        self.incidents.push(incident);
+        let excess = self.incidents.len().saturating_sub(MAX_INCIDENTS);
+        self.incidents.drain(..excess);
+    }
+
+    pub fn reset_app(&mut self, app: &str) -> bool {
+        let mut found = self.apps.remove(app).is_some();
+        for apps in self.days.values_mut().chain(self.months.values_mut()) {
+            found |= apps.remove(app).is_some();
+        }
+        self.days.retain(|_, apps| !apps.is_empty());
+        self.months.retain(|_, apps| !apps.is_empty());
+        found
+    }
+
+    pub fn roll_over(&mut self, reset_period: ResetPeriod, today: NaiveDate) -> bool {
+        let current = reset_period.period_start(today);
+        if self.period_start == current {
+            return false;
+        }
+        if let (Some(start), Some(current)) = (self.period_start, current)
+            && current < start
+        {
+            return false;
+        }
+
+        let had_period = self.period_start.is_some();
+        self.period_start = current;
+        if had_period {
+            for (app, record) in &mut self.apps {
+                if monitor::path_label(app).is_none() {
+                    record.bytes = 0;
+                }
+                record.samples.clear();
+                record.cpu_time_ms = None;
+                record.peak_memory_bytes = None;
+            }
+        }
+        had_period
+    }
+
+    fn active_timezone(&self, configured: ResetTimezone) -> ResetTimezone {
+        if self.period_start.is_none() || self.timezone.as_deref() == configured.name() {
+            return configured;
+        }
+        match &self.timezone {
+            Some(name) => ResetTimezone::parse(name).unwrap_or(configured),
+            None => ResetTimezone::Local,
+        }
+    }
+
+    pub fn roll_over_at(
+        &mut self,
+        reset_period: ResetPeriod,
+        timezone: ResetTimezone,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let active = self.active_timezone(timezone);
+        if active != timezone
+            && let (Some(current), Some(start)) = (
+                reset_period.period_start(active.date(now)),
+                self.period_start,
+            )
+            && current <= start
+        {
+            return false;
+        }
+        self.timezone = timezone.name().map(str::to_string);
+        self.roll_over(reset_period, timezone.date(now))
+    }
+
+    pub fn record_sample(&mut self, app: &str, now: DateTime<Utc>) {
+        if let Some(record) = self.apps.get_mut(app) {
+            record.record_sample(now);
+        }
+    }
+
+    pub fn observe_delta(
+        &mut self,
+        app: &str,
+        delta: u64,
+        config: &AnomalyConfig,
+    ) -> Option<DeltaStats> {
+        let stats = &mut self.apps.entry(app.to_string()).or_default().deltas;
+        let baseline = *stats;
+        stats.observe(delta);
+        config.is_anomaly(&baseline, delta).then_some(baseline)
+    }
+
+    pub fn period_end(
+        &self,
+        reset_period: ResetPeriod,
+        timezone: ResetTimezone,
+    ) -> Option<DateTime<Utc>> {
+        let next = reset_period.next_period_start(self.period_start?)?;
+        self.active_timezone(timezone).start_of_day(next)
+    }
+
+    pub fn forecast(
+        &self,
+        app: &str,
+        period_end: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Option<u64> {
+        let record = self.apps.get(app)?;
+        let remaining = period_end - now;
+        if remaining <= TimeDelta::zero() {
+            return None;
+        }
+        Some(project(record.bytes, record.rate()?, remaining))
+    }
+
+    /// Records that `app` is over its limit at `now`, on `today` in the reset
+    /// timezone, and returns its streak of periods over the limit.
+    pub fn record_exceedance(
+        &mut self,
+        app: &str,
+        reset_period: ResetPeriod,
+        now: DateTime<Utc>,
+        today: NaiveDate,
+    ) -> u32 {
+        let record = self.apps.entry(app.to_string()).or_default();
+        let started = record.exceedance.exceed(now);
+        let streak = match self.period_start {
+            Some(period) => record.record_exceedance(reset_period, period),
+            None => 1,
+        };
+        if started {
+            self.day_mut(app, today).exceeded += 1;
+        }
+        streak
+    }
+
+    /// Fingerprint of a data limit alert for `usage` over `limit` in the
+    /// current period.
+    pub fn fingerprint(&self, usage: u64, limit: u64, severity: Severity) -> AlertFingerprint {
+        AlertFingerprint {
+            period_start: self.period_start,
+            multiple: usage.checked_div(limit).unwrap_or(1).max(1),
+            severity,
+        }
+    }
+
+    /// Whether a data limit alert for `app` with `fingerprint` should be
+    /// sent, given the last one that was.
+    pub fn is_new_alert(&self, app: &str, fingerprint: &AlertFingerprint) -> bool {
+        self.apps
+            .get(app)
+            .and_then(|record| record.alerted.as_ref())
+            .is_none_or(|last| fingerprint.supersedes(last))
+    }
+
+    pub fn record_alert(&mut self, app: &str, fingerprint: AlertFingerprint) {
+        self.apps.entry(app.to_string()).or_default().alerted = Some(fingerprint);
+    }
+
+    /// Ends the exceedance of every app whose usage is back within `limit`,
+    /// or which no longer has one, e.g. after a roll-over or a raised
+    /// limit, and returns how long each one was over.
+    pub fn record_recoveries(
+        &mut self,
+        limit: impl Fn(&str) -> Option<u64>,
+        now: DateTime<Utc>,
+    ) -> Vec<(String, TimeDelta)> {
+        let mut recovered: Vec<_> = self
+            .apps
+            .iter_mut()
+            .filter(|(app, record)| limit(app).is_none_or(|limit| record.bytes <= limit))
+            .filter_map(|(app, record)| Some((app.clone(), record.exceedance.recover(now)?)))
+            .collect();
+        recovered.sort_by(|a, b| a.0.cmp(&b.0));
+        recovered
+    }
+
+    pub fn exceedances(&self) -> HashMap<String, ExceedanceStats> {
+        self.apps
+            .iter()
+            .filter(|(_, record)| !record.exceedance.is_empty())
+            .map(|(app, record)| (app.clone(), record.exceedance))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::{America, Asia, Australia, Europe, Pacific, UTC};
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_period_start() {
+        let wednesday = date(2025, 6, 18);
+        assert_eq!(ResetPeriod::Never.period_start(wednesday), None);
+        assert_eq!(ResetPeriod::Daily.period_start(wednesday), Some(wednesday));
+        assert_eq!(
+            ResetPeriod::Weekly.period_start(wednesday),
+            Some(date(2025, 6, 16))
+        );
+        assert_eq!(
+            ResetPeriod::Monthly.period_start(wednesday),
+            Some(date(2025, 6, 1))
+        );
+        assert_eq!(
+            ResetPeriod::Monthly.next_period_start(date(2025, 12, 1)),
+            Some(date(2026, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_streak_consecutive_days() {
+        let mut record = AppRecord::default();
+        let period = ResetPeriod::Daily;
+
+        assert_eq!(record.record_exceedance(period, date(2025, 6, 1)), 1);
+        assert_eq!(record.record_exceedance(period, date(2025, 6, 1)), 1);
+        assert_eq!(record.record_exceedance(period, date(2025, 6, 2)), 2);
+        assert_eq!(record.record_exceedance(period, date(2025, 6, 3)), 3);
+    }
+
+    #[test]
+    fn test_streak_resets_after_quiet_period() {
+        let mut record = AppRecord::default();
+        let period = ResetPeriod::Daily;
+
+        record.record_exceedance(period, date(2025, 6, 1));
+        record.record_exceedance(period, date(2025, 6, 2));
+        assert_eq!(record.current_streak(period, date(2025, 6, 3)), 2);
+        assert_eq!(record.current_streak(period, date(2025, 6, 4)), 0);
+        assert_eq!(record.record_exceedance(period, date(2025, 6, 4)), 1);
+    }
+
+    #[test]
+    fn test_escalation_severity() {
+        let escalation = EscalationConfig::default();
+        assert_eq!(escalation.severity(1), Severity::Warning);
+        assert_eq!(escalation.severity(2), Severity::Warning);
+        assert_eq!(escalation.severity(3), Severity::Critical);
+
+        let disabled = EscalationConfig {
+            repeat_threshold: 0,
+            ..Default::default()
+        };
+        assert_eq!(disabled.severity(10), Severity::Warning);
+    }
+
+    #[test]
+    fn test_roll_over_resets_usage() {
+        let mut tracker = UsageTracker::default();
+        let period = ResetPeriod::Daily;
+
+        assert!(!tracker.roll_over(period, date(2025, 6, 1)));
+        tracker.add_usage("app", 100);
+        tracker.record_size("path:downloads", 500);
+        assert_eq!(
+            tracker.record_exceedance("app", period, Utc::now(), date(2025, 6, 1)),
+            1
+        );
+        assert!(!tracker.roll_over(period, date(2025, 6, 1)));
+        assert_eq!(tracker.usage()["app"], 100);
+
+        assert!(tracker.roll_over(period, date(2025, 6, 2)));
+        assert_eq!(tracker.usage()["app"], 0);
+        assert_eq!(tracker.usage()["path:downloads"], 500);
+        assert_eq!(
+            tracker.record_exceedance("app", period, Utc::now(), date(2025, 6, 1)),
+            2
+        );
+    }
+
+    #[test]
+    fn test_new_app_grace() {
+        let mut tracker = UsageTracker::default();
+        let start = Utc::now();
+        let grace = TimeDelta::minutes(10);
+
+        // Apps tracked before first_seen was kept get no grace.
+        tracker.add_usage("old", 100);
+        tracker.mark_seen("old", start);
+        assert!(!tracker.in_grace("old", grace, start));
+
+        tracker.mark_seen("new", start);
+        tracker.add_usage("new", 100);
+        tracker.mark_seen("new", start + TimeDelta::minutes(5));
+        assert!(tracker.in_grace("new", grace, start + TimeDelta::minutes(9)));
+        assert!(!tracker.in_grace("new", TimeDelta::zero(), start));
+
+        let minutes = |minutes| start + TimeDelta::minutes(minutes);
+        assert!(
+            tracker
+                .grace_ended(grace, minutes(5), minutes(9))
+                .is_empty()
+        );
+        assert_eq!(tracker.grace_ended(grace, minutes(9), minutes(10)), ["new"]);
+        assert!(
+            tracker
+                .grace_ended(grace, minutes(10), minutes(11))
+                .is_empty()
+        );
+
+        tracker.roll_over(ResetPeriod::Daily, date(2025, 6, 1));
+        assert!(tracker.roll_over(ResetPeriod::Daily, date(2025, 6, 2)));
+        assert_eq!(tracker.apps["new"].first_seen, Some(start));
+        assert!(!tracker.in_grace("new", grace, start + grace));
+    }
+
+    #[test]
+    fn test_exceedance_stats() {
+        let mut tracker = UsageTracker::default();
+        let period = ResetPeriod::Daily;
+        let start = Utc::now();
+        tracker.roll_over(period, date(2025, 6, 1));
+        tracker.add_usage("app", 100);
+        tracker.add_usage("quiet", 10);
+
+        tracker.record_exceedance("app", period, start, date(2025, 6, 1));
+        tracker.record_exceedance(
+            "app",
+            period,
+            start + TimeDelta::minutes(1),
+            date(2025, 6, 1),
+        );
+        assert!(tracker.record_recoveries(|_| Some(50), start).is_empty());
+        let stats = tracker.exceedances()["app"];
+        assert_eq!(stats.events, 1);
+        assert_eq!(stats.over_since, Some(start));
+        assert_eq!(stats.time_over(start + TimeDelta::minutes(5)), 300);
+        assert!(!tracker.exceedances().contains_key("quiet"));
+
+        tracker.roll_over(period, date(2025, 6, 2));
+        let recovered = tracker.record_recoveries(|_| Some(50), start + TimeDelta::minutes(10));
+        assert_eq!(recovered, vec![("app".to_string(), TimeDelta::minutes(10))]);
+
+        let later = start + TimeDelta::hours(1);
+        tracker.add_usage("app", 100);
+        tracker.record_exceedance("app", period, later, date(2025, 6, 2));
+        let stats = tracker.exceedances()["app"];
+        assert_eq!(stats.events, 2);
+        assert_eq!(stats.first_exceeded, Some(start));
+        assert_eq!(stats.last_exceeded, Some(later));
+        assert_eq!(stats.seconds_over, 600);
+        assert_eq!(stats.time_over(later + TimeDelta::minutes(1)), 660);
+        assert_eq!(tracker.apps["app"].streak, 2);
+        assert_eq!(tracker.days[&date(2025, 6, 1)]["app"].exceeded, 1);
+        assert_eq!(tracker.days[&date(2025, 6, 2)]["app"].exceeded, 1);
+    }
+
+    #[test]
+    fn test_daily_usage() {
+        let mut tracker = UsageTracker::default();
+        tracker.add_daily_usage("app", 10, 4, date(2025, 6, 1));
+        tracker.add_daily_usage("app", 5, 9, date(2025, 6, 1));
+        tracker.add_daily_usage("other", 7, 0, date(2025, 6, 2));
+        tracker.add_daily_usage("app", 1, 0, date(2025, 6, 3));
+
+        let usage = tracker.usage_between(date(2025, 6, 1), date(2025, 6, 3));
+        assert_eq!(
+            usage,
+            HashMap::from([("app".to_string(), 15), ("other".to_string(), 7)])
+        );
+        let day = tracker.days[&date(2025, 6, 1)]["app"];
+        assert_eq!((day.bytes, day.written, day.read()), (15, 9, 6));
+        let stored: DayUsage = serde_json::from_str("42").unwrap();
+        assert_eq!(stored.read(), 42);
+        let json = serde_json::to_string(&day).unwrap();
+        assert_eq!(serde_json::from_str::<DayUsage>(&json).unwrap(), day);
+
+        tracker.add_daily_usage("app", 1, 0, date(2025, 8, 3));
+        assert_eq!(tracker.days.keys().next(), Some(&date(2025, 6, 1)));
+        tracker.compact(DAILY_HISTORY_DAYS, 0, date(2025, 8, 3));
+        assert_eq!(tracker.days.keys().next(), Some(&date(2025, 6, 2)));
+        assert!(tracker.months.is_empty());
+
+        assert!(tracker.reset_app("other"));
+        assert_eq!(tracker.days.keys().next(), Some(&date(2025, 6, 3)));
+    }
+
+    #[test]
+    fn test_budget_starts_from_history() {
+        let config = BudgetConfig {
+            total_bytes: Some(1000),
+            start_day: 5,
+            ..BudgetConfig::default()
+        };
+        let mut tracker = UsageTracker::default();
+        tracker.add_daily_usage("firefox", 300, 0, date(2025, 6, 4));
+        tracker.add_daily_usage("firefox", 400, 0, date(2025, 6, 5));
+        tracker.add_daily_usage("disk:sda", 900, 0, date(2025, 6, 5));
+        tracker.add_daily_usage("curl", 100, 0, date(2025, 6, 6));
+
+        assert_eq!(
+            tracker.add_budget_usage(&config, 100, date(2025, 6, 6)),
+            [50]
+        );
+        assert_eq!(tracker.budget.used, 500);
+        tracker.budget.mark_notified(&config, 50);
+        assert!(
+            tracker
+                .add_budget_usage(&config, 10, date(2025, 6, 6))
+                .is_empty()
+        );
+        assert_eq!(tracker.budget.used, 510);
+
+        let restored = UsageTracker::from_compressed(&tracker.to_compressed().unwrap()).unwrap();
+        assert_eq!(restored.budget, tracker.budget);
+    }
+
+    #[test]
+    fn test_compact_multi_year_history() {
+        let mut tracker = UsageTracker::default();
+        let mut day = date(2022, 1, 1);
+        while day <= date(2025, 6, 18) {
+            tracker.add_daily_usage("firefox", 100, 10, day);
+            if day.day() == 1 {
+                tracker.add_daily_usage("curl", 5, 0, day);
+                tracker.day_mut("curl", day).exceeded = 1;
+            }
+            day = day.succ_opt().unwrap();
+        }
+
+        let today = date(2025, 6, 18);
+        let compaction = tracker.compact(62, 24, today);
+        assert_eq!(
+            compaction,
+            Compaction {
+                days: (date(2025, 4, 17) - date(2022, 1, 1)).num_days() as usize,
+                months: 0,
+            }
+        );
+        assert_eq!(tracker.days.keys().next(), Some(&date(2025, 4, 17)));
+        assert_eq!(tracker.days.keys().last(), Some(&today));
+        assert_eq!(tracker.days.len(), 63);
+
+        let mut expected = Vec::new();
+        let mut month = date(2023, 6, 1);
+        while month <= date(2025, 4, 1) {
+            expected.push(month);
+            month = month.checked_add_months(Months::new(1)).unwrap();
+        }
+        assert_eq!(tracker.months.keys().copied().collect::<Vec<_>>(), expected);
+        assert_eq!(
+            tracker.months[&date(2023, 6, 1)]["firefox"],
+            DayUsage {
+                bytes: 3000,
+                written: 300,
+                exceeded: 0,
+            }
+        );
+        assert_eq!(tracker.months[&date(2024, 2, 1)]["firefox"].bytes, 2900);
+        // April is split: the 1st to the 16th are rolled up, the rest are
+        // still days.
+        assert_eq!(tracker.months[&date(2025, 4, 1)]["firefox"].bytes, 1600);
+        assert_eq!(tracker.months[&date(2025, 4, 1)]["curl"].exceeded, 1);
+        assert_eq!(tracker.days[&date(2025, 5, 1)]["curl"].exceeded, 1);
+
+        assert!(tracker.compact(62, 24, today).is_empty());
+        let restored = UsageTracker::from_compressed(&tracker.to_compressed().unwrap()).unwrap();
+        assert_eq!(restored, tracker);
+
+        let compaction = tracker.compact(30, 1, date(2025, 7, 2));
+        assert_eq!(compaction.months, expected.len());
+        assert_eq!(
+            tracker.months.keys().collect::<Vec<_>>(),
+            [&date(2025, 6, 1)]
+        );
+        assert_eq!(tracker.months[&date(2025, 6, 1)]["firefox"].bytes, 100);
+        assert_eq!(tracker.days.keys().next(), Some(&date(2025, 6, 2)));
+    }
+
+    #[test]
+    fn test_daily_usage_between() {
+        let utc = ResetTimezone::Iana(UTC);
+        let time = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let mut tracker = UsageTracker::default();
+        // Asleep from 18:00 on the 1st to 06:00 on the 3rd: 6h, 24h and 6h.
+        tracker.add_daily_usage_between(
+            "app",
+            3600,
+            360,
+            time("2025-06-01T18:00:00Z"),
+            time("2025-06-03T06:00:00Z"),
+            utc,
+        );
+        let bytes: Vec<_> = tracker
+            .days
+            .iter()
+            .map(|(date, apps)| (date.day(), apps["app"].bytes, apps["app"].written))
+            .collect();
+        assert_eq!(bytes, [(1, 600, 60), (2, 2400, 240), (3, 600, 60)]);
+
+        // A clock set back lands everything on the current day.
+        tracker.add_daily_usage_between(
+            "app",
+            7,
+            0,
+            time("2025-06-03T06:00:00Z"),
+            time("2025-06-02T06:00:00Z"),
+            utc,
+        );
+        assert_eq!(tracker.days[&date(2025, 6, 2)]["app"].bytes, 2407);
+    }
+
+    #[test]
+    fn test_reset_app() {
+        let mut tracker = UsageTracker::from(HashMap::from([("app".to_string(), 5)]));
+        assert!(tracker.reset_app("app"));
+        assert!(!tracker.reset_app("app"));
+        assert!(tracker.usage().is_empty());
+    }
+
+    #[test]
+    fn test_alert_fingerprints() {
+        let mut tracker = UsageTracker::default();
+        tracker.roll_over(ResetPeriod::Daily, date(2025, 6, 1));
+        let first = tracker.fingerprint(1_500, 1_000, Severity::Warning);
+        assert_eq!(first.multiple, 1);
+        assert!(tracker.is_new_alert("app", &first));
+        tracker.record_alert("app", first);
+
+        // Restored after a restart, the same alert stays quiet.
+        let compressed = tracker.to_compressed().unwrap();
+        let mut tracker = UsageTracker::from_compressed(&compressed).unwrap();
+        assert!(!tracker.is_new_alert("app", &first));
+        let same = tracker.fingerprint(1_999, 1_000, Severity::Warning);
+        assert!(!tracker.is_new_alert("app", &same));
+
+        let doubled = tracker.fingerprint(2_000, 1_000, Severity::Warning);
+        assert_eq!(doubled.multiple, 2);
+        assert!(tracker.is_new_alert("app", &doubled));
+        let critical = tracker.fingerprint(1_500, 1_000, Severity::Critical);
+        assert!(tracker.is_new_alert("app", &critical));
+        assert!(tracker.is_new_alert("other", &first));
+
+        tracker.roll_over(ResetPeriod::Daily, date(2025, 6, 2));
+        let next_day = tracker.fingerprint(1_500, 1_000, Severity::Warning);
+        assert!(tracker.is_new_alert("app", &next_day));
+
+        // Resetting an app re-arms its alerts.
+        tracker.record_alert("app", next_day);
+        assert!(tracker.reset_app("app"));
+        assert!(tracker.is_new_alert("app", &next_day));
+        assert_eq!(tracker.fingerprint(5, 0, Severity::Warning).multiple, 1);
+    }
+
+    #[test]
+    fn test_compressed_roundtrip() {
+        let mut tracker = UsageTracker::default();
+        tracker.roll_over(ResetPeriod::Daily, date(2025, 6, 1));
+        tracker.add_usage("app", 42);
+        tracker.record_exceedance("app", ResetPeriod::Daily, Utc::now(), date(2025, 6, 1));
+
+        let compressed = tracker.to_compressed().unwrap();
+        assert_eq!(UsageTracker::from_compressed(&compressed).unwrap(), tracker);
+    }
+
+    #[test]
+    fn test_state_info() {
+        let mut tracker = UsageTracker::default();
+        let start: DateTime<Utc> = "2025-06-18T12:00:00Z".parse().unwrap();
+        assert_eq!(tracker.record_scan(start), 1);
+        tracker.mark_active("app", start);
+        let later = start + TimeDelta::minutes(1);
+        assert_eq!(tracker.record_scan(later), 2);
+        tracker.mark_saved(later, "1.0.2");
+        assert_eq!(
+            tracker.state,
+            StateInfo {
+                ticks: 2,
+                last_scan: Some(later),
+                last_save: Some(later),
+                written_by: Some("1.0.2".to_string()),
+            }
+        );
+        assert_eq!(
+            tracker.last_active(),
+            HashMap::from([("app".to_string(), start)])
+        );
+
+        let compressed = tracker.to_compressed().unwrap();
+        assert_eq!(UsageTracker::from_compressed(&compressed).unwrap(), tracker);
+        // Files from before the state was kept still load.
+        let old = serde_json::json!({ "version": 1, "period_start": null, "apps": {} });
+        let old: UsageTracker = serde_json::from_value(old).unwrap();
+        assert!(old.state.is_empty());
+    }
+
+    #[test]
+    fn test_loads_legacy_usage_data() {
+        let legacy = HashMap::from([("app".to_string(), 42), ("apps".to_string(), 7)]);
+        let compressed = compression::compress_usage_data(&legacy).unwrap();
+
+        let tracker = UsageTracker::from_compressed(&compressed).unwrap();
+        assert_eq!(tracker.version, TRACKER_VERSION);
+        assert_eq!(tracker.usage(), legacy);
+    }
+
+    #[test]
+    fn test_bound_apps() {
+        let mut tracker = UsageTracker::default();
+        tracker.add_usage("a", 1);
+        let usage = HashMap::from([
+            ("a".to_string(), 10),
+            ("b".to_string(), 20),
+            ("c".to_string(), 30),
+            ("d".to_string(), 40),
+            ("disk:sda1".to_string(), 50),
+        ]);
+
+        let (bounded, overflow) = tracker.bound_apps(usage.clone(), 2, |a, b| *a += b);
+        assert_eq!(overflow, 2);
+        assert_eq!(bounded.len(), 4);
+        assert_eq!(bounded["a"], 10);
+        assert_eq!(bounded["disk:sda1"], 50);
+        assert_eq!(bounded.values().sum::<u64>(), 150);
+        assert!(bounded[OTHER_APP] >= 50);
+
+        let (bounded, overflow) = tracker.bound_apps(usage.clone(), 0, |a, b| *a += b);
+        assert_eq!((bounded, overflow), (usage, 0));
+    }
+
+    #[test]
+    fn test_add_usage_saturates() {
+        let mut tracker = UsageTracker::default();
+        assert_eq!(tracker.add_usage("app", u64::MAX - 1), u64::MAX - 1);
+        assert_eq!(tracker.add_usage("app", 5), u64::MAX);
+        assert_eq!(tracker.add_usage("app", 5), u64::MAX);
+    }
+
+    #[test]
+    fn test_resources() {
+        let mut tracker = UsageTracker::default();
+        tracker.roll_over(ResetPeriod::Daily, date(2025, 6, 1));
+        tracker.add_usage("plain", 10);
+        assert_eq!(tracker.add_cpu_time("app", 1500), 1500);
+        assert_eq!(tracker.add_cpu_time("app", 500), 2000);
+        assert_eq!(tracker.record_memory("app", 300), 300);
+        assert_eq!(tracker.record_memory("app", 100), 300);
+        assert_eq!(
+            tracker.resources(),
+            HashMap::from([(
+                "app".to_string(),
+                AppResources {
+                    cpu_time_ms: Some(2000),
+                    peak_memory_bytes: Some(300),
+                }
+            )])
+        );
+
+        let restored = UsageTracker::from_compressed(&tracker.to_compressed().unwrap()).unwrap();
+        assert_eq!(restored, tracker);
+
+        tracker.roll_over(ResetPeriod::Daily, date(2025, 6, 2));
+        assert!(tracker.resources().is_empty());
+    }
+
+    #[test]
+    fn test_loads_records_without_resources() {
+        let json = r#"{"version":1,"period_start":null,"apps":{"app":{"bytes":5}}}"#;
+        let compressed = compression::compress_json(
+            &serde_json::from_str::<serde_json::Value>(json).unwrap(),
+            1,
+        )
+        .unwrap();
+
+        let tracker = UsageTracker::from_compressed(&compressed).unwrap();
+        assert_eq!(tracker.apps["app"].bytes, 5);
+        assert_eq!(tracker.apps["app"].resources(), None);
+    }
+
+    #[test]
+    fn test_never_keeps_usage() {
+        let mut tracker = UsageTracker::from(HashMap::from([("app".to_string(), 5)]));
+        assert!(!tracker.roll_over(ResetPeriod::Never, date(2025, 6, 1)));
+        assert_eq!(tracker.add_usage("app", u64::MAX), u64::MAX);
+        assert_eq!(
+            tracker.record_exceedance("app", ResetPeriod::Never, Utc::now(), date(2025, 6, 1)),
+            1
+        );
+        assert_eq!(tracker.apps["app"].streak, 0);
+    }
+
+    fn feed(tracker: &mut UsageTracker, start: DateTime<Utc>, series: &[(i64, u64)]) {
+        for (minute, bytes) in series {
+            let record = tracker.apps.entry("app".to_string()).or_default();
+            record.bytes = *bytes;
+            tracker.record_sample("app", start + TimeDelta::minutes(*minute));
+        }
+    }
+
+    #[test]
+    fn test_project() {
+        assert_eq!(project(1000, 10.0, TimeDelta::seconds(60)), 1600);
+        assert_eq!(project(1000, 0.0, TimeDelta::hours(5)), 1000);
+        assert_eq!(project(1000, 10.0, TimeDelta::seconds(-60)), 1000);
+        assert_eq!(project(u64::MAX - 1, 1e30, TimeDelta::hours(1)), u64::MAX);
+    }
+
+    #[test]
+    fn test_forecast_linear_series() {
+        let start = date(2025, 6, 1).and_hms_opt(12, 0, 0).unwrap().and_utc();
+        let end = date(2025, 6, 2).and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let mut tracker = UsageTracker::default();
+
+        let series: Vec<_> = (0..=90)
+            .map(|minute| (minute, minute as u64 * 60_000))
+            .collect();
+        feed(&mut tracker, start, &series);
+
+        let record = &tracker.apps["app"];
+        assert_eq!(record.samples.len(), 61);
+        assert_eq!(record.rate(), Some(1000.0));
+
+        let now = start + TimeDelta::minutes(90);
+        let remaining = (end - now).num_seconds() as u64;
+        assert_eq!(
+            tracker.forecast("app", end, now),
+            Some(90 * 60_000 + remaining * 1000)
+        );
+        assert_eq!(tracker.forecast("app", now, now), None);
+        assert_eq!(tracker.forecast("missing", end, now), None);
+    }
+
+    #[test]
+    fn test_forecast_uses_last_hour() {
+        let start = date(2025, 6, 1).and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let end = start + TimeDelta::hours(3);
+        let mut tracker = UsageTracker::default();
+
+        feed(
+            &mut tracker,
+            start,
+            &[(0, 0), (60, 3_600_000), (90, 3_600_000)],
+        );
+        assert_eq!(tracker.apps["app"].samples.len(), 2);
+        assert_eq!(tracker.apps["app"].rate(), Some(0.0));
+        assert_eq!(
+            tracker.forecast("app", end, start + TimeDelta::minutes(90)),
+            Some(3_600_000)
+        );
+    }
+
+    #[test]
+    fn test_forecast_needs_enough_history() {
+        let start = date(2025, 6, 1).and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let mut tracker = UsageTracker::default();
+
+        feed(&mut tracker, start, &[(0, 0), (0, 500), (5, 1000)]);
+        assert_eq!(tracker.apps["app"].samples.len(), 2);
+        assert_eq!(tracker.apps["app"].rate(), None);
+        assert_eq!(
+            tracker.forecast("app", start + TimeDelta::hours(1), start),
+            None
+        );
+
+        tracker.roll_over(ResetPeriod::Daily, date(2025, 6, 1));
+        tracker.roll_over(ResetPeriod::Daily, date(2025, 6, 2));
+        assert!(tracker.apps["app"].samples.is_empty());
+    }
+
+    #[test]
+    fn test_delta_stats() {
+        let mut stats = DeltaStats::default();
+        for delta in [2, 4, 4, 4, 5, 5, 7, 9] {
+            stats.observe(delta);
+        }
+        assert_eq!(stats.count, 8);
+        assert_eq!(stats.mean, 5.0);
+        assert!((stats.variance() - 32.0 / 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_anomaly_after_warmup() {
+        let config = AnomalyConfig {
+            enabled: true,
+            std_devs: 3.0,
+            min_delta_bytes: 1000,
+            warmup_ticks: 10,
+        };
+        let mut tracker = UsageTracker::default();
+
+        assert!(tracker.observe_delta("app", 50_000, &config).is_none());
+        for tick in 0..9 {
+            let delta = if tick % 2 == 0 { 900 } else { 1100 };
+            assert!(tracker.observe_delta("app", delta, &config).is_none());
+        }
+        assert_eq!(tracker.apps["app"].deltas.count, 10);
+
+        assert!(tracker.observe_delta("app", 1000, &config).is_none());
+        let baseline = tracker.observe_delta("app", 100_000, &config).unwrap();
+        assert_eq!(baseline.count, 11);
+        assert_eq!(tracker.apps["app"].deltas.count, 12);
+    }
+
+    #[test]
+    fn test_anomaly_floor_on_quiet_apps() {
+        let config = AnomalyConfig {
+            enabled: true,
+            std_devs: 3.0,
+            min_delta_bytes: 1000,
+            warmup_ticks: 5,
+        };
+        let mut tracker = UsageTracker::default();
+
+        for _ in 0..20 {
+            tracker.observe_delta("quiet", 0, &config);
+        }
+        assert!(tracker.observe_delta("quiet", 999, &config).is_none());
+        assert!(tracker.observe_delta("quiet", 5000, &config).is_some());
+    }
+
+    #[test]
+    fn test_anomaly_state_persists() {
+        let mut tracker = UsageTracker::default();
+        for delta in [10, 20, 30] {
+            tracker.observe_delta("app", delta, &AnomalyConfig::default());
+        }
+
+        let restored = UsageTracker::from_compressed(&tracker.to_compressed().unwrap()).unwrap();
+        assert_eq!(restored.apps["app"].deltas, tracker.apps["app"].deltas);
+        assert_eq!(restored.apps["app"].deltas.mean, 20.0);
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        date(y, m, d).and_hms_opt(h, 0, 0).unwrap().and_utc()
+    }
+
+    #[test]
+    fn test_start_of_day_in_dst_gap() {
+        // Santiago springs forward from 00:00 to 01:00.
+        let santiago = ResetTimezone::Iana(America::Santiago);
+        assert_eq!(
+            santiago.start_of_day(date(2024, 9, 8)),
+            Some(utc(2024, 9, 8, 4))
+        );
+        // Havana falls back from 01:00 to 00:00, so midnight happens twice.
+        let havana = ResetTimezone::Iana(America::Havana);
+        assert_eq!(
+            havana.start_of_day(date(2024, 11, 3)),
+            Some(utc(2024, 11, 3, 4))
+        );
+    }
+
+    #[test]
+    fn test_timezone_change_waits_for_period_end() {
+        let utc_zone = ResetTimezone::Iana(UTC);
+        let auckland = ResetTimezone::Iana(Pacific::Auckland);
+        let mut tracker = UsageTracker::default();
+
+        assert!(!tracker.roll_over_at(ResetPeriod::Daily, utc_zone, utc(2025, 6, 1, 20)));
+        tracker.add_usage("app", 10);
+        assert_eq!(tracker.timezone.as_deref(), Some("UTC"));
+
+        // Auckland is already on June 2nd, but the UTC period has not ended yet.
+        assert!(!tracker.roll_over_at(ResetPeriod::Daily, auckland, utc(2025, 6, 1, 22)));
+        assert_eq!(tracker.usage()["app"], 10);
+        assert_eq!(
+            tracker.period_end(ResetPeriod::Daily, auckland),
+            Some(utc(2025, 6, 2, 0))
+        );
+
+        assert!(tracker.roll_over_at(ResetPeriod::Daily, auckland, utc(2025, 6, 2, 0)));
+        assert_eq!(tracker.period_start, Some(date(2025, 6, 2)));
+        assert_eq!(tracker.timezone.as_deref(), Some("Pacific/Auckland"));
+        assert!(!tracker.roll_over_at(ResetPeriod::Daily, auckland, utc(2025, 6, 2, 11)));
+        assert!(tracker.roll_over_at(ResetPeriod::Daily, auckland, utc(2025, 6, 2, 12)));
+    }
+
+    #[test]
+    fn test_timezone_change_to_earlier_zone() {
+        let utc_zone = ResetTimezone::Iana(UTC);
+        let los_angeles = ResetTimezone::Iana(America::Los_Angeles);
+        let mut tracker = UsageTracker::default();
+
+        tracker.roll_over_at(ResetPeriod::Daily, utc_zone, utc(2025, 6, 1, 20));
+        tracker.add_usage("app", 10);
+
+        assert!(!tracker.roll_over_at(ResetPeriod::Daily, los_angeles, utc(2025, 6, 2, 1)));
+        assert_eq!(tracker.period_start, Some(date(2025, 6, 1)));
+        assert_eq!(tracker.timezone.as_deref(), Some("America/Los_Angeles"));
+        assert!(!tracker.roll_over_at(ResetPeriod::Daily, los_angeles, utc(2025, 6, 2, 6)));
+        assert!(tracker.roll_over_at(ResetPeriod::Daily, los_angeles, utc(2025, 6, 2, 7)));
+        assert_eq!(tracker.usage()["app"], 0);
+    }
+
+    const DST_TRANSITIONS: [(Tz, (i32, u32, u32)); 10] = [
+        (America::New_York, (2024, 3, 10)),
+        (America::New_York, (2024, 11, 3)),
+        (Europe::Berlin, (2024, 3, 31)),
+        (Europe::Berlin, (2024, 10, 27)),
+        (America::Santiago, (2024, 9, 8)),
+        (America::Havana, (2024, 11, 3)),
+        (America::Sao_Paulo, (2018, 11, 4)),
+        (America::Sao_Paulo, (2019, 2, 17)),
+        (Asia::Beirut, (2024, 3, 31)),
+        (Australia::Lord_Howe, (2024, 4, 7)),
+    ];
+
+    proptest! {
+        #[test]
+        fn prop_day_bounds_contain_now(
+            transition in 0..DST_TRANSITIONS.len(),
+            offset in -36 * 60i64..36 * 60,
+        ) {
+            let (tz, (y, m, d)) = DST_TRANSITIONS[transition];
+            let zone = ResetTimezone::Iana(tz);
+            let now = utc(y, m, d, 0) + TimeDelta::minutes(offset);
+
+            let today = zone.date(now);
+            let start = zone.start_of_day(today).unwrap();
+            let end = zone.start_of_day(today.succ_opt().unwrap()).unwrap();
+            prop_assert!(start <= now && now < end, "{} <= {} < {}", start, now, end);
+            prop_assert!(end - start >= TimeDelta::hours(22));
+            prop_assert!(end - start <= TimeDelta::hours(26));
+        }
+
+        #[test]
+        fn prop_rolls_over_once_per_day(
+            transition in 0..DST_TRANSITIONS.len(),
+            offset in -36 * 60i64..0,
+            step in 1..120i64,
+        ) {
+            let (tz, (y, m, d)) = DST_TRANSITIONS[transition];
+            let zone = ResetTimezone::Iana(tz);
+            let start = utc(y, m, d, 0) + TimeDelta::minutes(offset);
+            let mut tracker = UsageTracker::default();
+            tracker.roll_over_at(ResetPeriod::Daily, zone, start);
+
+            let mut resets = Vec::new();
+            let mut now = start;
+            while now < start + TimeDelta::hours(72) {
+                now += TimeDelta::minutes(step);
+                if tracker.roll_over_at(ResetPeriod::Daily, zone, now) {
+                    resets.push((now, tracker.period_start.unwrap()));
+                }
+            }
+
+            let days = (zone.date(now) - zone.date(start)).num_days();
+            prop_assert_eq!(resets.len() as i64, days);
+            for (time, period) in resets {
+                let boundary = zone.start_of_day(period).unwrap();
+                prop_assert!(time >= boundary && time - boundary < TimeDelta::minutes(step));
+            }
+        }
+    }
+}
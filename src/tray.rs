@@ -0,0 +1,523 @@
+use serde::{Deserialize, Serialize};
+
+use super::i18n::{self, Localizer};
+use super::notification::NotificationAction;
+#[cfg(feature = "monitor")]
+use super::status::{AppStatus, StatusReport};
+
+pub const TRAY_ID: &str = "data-guardian";
+pub const TRAY_TITLE: &str = "Data Guardian";
+pub const TOP_APPS: usize = 5;
+pub const ICON_SIZE: u32 = 32;
+
+const NORMAL_COLOR: [u8; 3] = [0x2e, 0xa0, 0x43];
+const OVER_LIMIT_COLOR: [u8; 3] = [0xd0, 0x30, 0x30];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrayConfig {
+    pub enabled: bool,
+}
+
+impl Default for TrayConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayCommand {
+    SnoozeAll,
+    OpenReport,
+    Quit,
+}
+
+impl TrayCommand {
+    pub const ALL: [TrayCommand; 3] = [Self::SnoozeAll, Self::OpenReport, Self::Quit];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::SnoozeAll => "snooze_all",
+            Self::OpenReport => "open_report",
+            Self::Quit => "quit",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|command| command.id() == id)
+    }
+
+    pub fn label(self, localizer: &Localizer) -> String {
+        let key = match self {
+            Self::SnoozeAll => i18n::TRAY_SNOOZE_ALL,
+            Self::OpenReport => i18n::TRAY_OPEN_REPORT,
+            Self::Quit => i18n::TRAY_QUIT,
+        };
+        localizer.message(key).to_string()
+    }
+
+    pub fn action(self) -> NotificationAction {
+        match self {
+            Self::SnoozeAll => NotificationAction::SnoozeAll,
+            Self::OpenReport => NotificationAction::ShowReport,
+            Self::Quit => NotificationAction::Quit,
+        }
+    }
+}
+
+#[cfg(feature = "monitor")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrayView {
+    pub over_limit: bool,
+    pub apps: Vec<AppStatus>,
+}
+
+#[cfg(feature = "monitor")]
+impl TrayView {
+    pub fn from_report(report: Option<StatusReport>) -> Self {
+        let Some(mut report) = report else {
+            return Self::default();
+        };
+        let over_limit = report.apps.iter().any(|app| app.over);
+        report.apps.truncate(TOP_APPS);
+        Self {
+            over_limit,
+            apps: report.apps,
+        }
+    }
+
+    pub fn labels(&self, localizer: &Localizer) -> Vec<String> {
+        if self.apps.is_empty() {
+            return vec![localizer.message(i18n::TRAY_NO_USAGE).to_string()];
+        }
+        self.apps
+            .iter()
+            .map(|app| {
+                format!(
+                    "{}{}: {}",
+                    if app.over { "⚠ " } else { "" },
+                    app.name,
+                    localizer.format_bytes(app.usage)
+                )
+            })
+            .collect()
+    }
+
+    pub fn tooltip(&self, localizer: &Localizer) -> String {
+        self.labels(localizer).join("\n")
+    }
+}
+
+pub fn icon_rgba(over_limit: bool) -> Vec<u8> {
+    let [r, g, b] = if over_limit {
+        OVER_LIMIT_COLOR
+    } else {
+        NORMAL_COLOR
+    };
+    let center = (ICON_SIZE as f64 - 1.0) / 2.0;
+    let radius = ICON_SIZE as f64 / 2.0 - 1.0;
+
+    let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let distance = (x as f64 - center).hypot(y as f64 - center);
+            let alpha = if distance <= radius { 0xff } else { 0 };
+            rgba.extend_from_slice(&[r, g, b, alpha]);
+        }
+    }
+    rgba
+}
+
+#[cfg(feature = "tray")]
+pub use platform::*;
+
+#[cfg(feature = "tray")]
+mod platform {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use thiserror::Error;
+
+    use super::super::i18n::Localizer;
+    use super::super::notification::ActionSender;
+    use super::super::status::StatusState;
+
+    #[derive(Error, Debug)]
+    pub enum TrayError {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        #[error("Failed to register the StatusNotifierItem: {0}")]
+        Sni(#[from] ksni::Error),
+        #[cfg(windows)]
+        #[error("Failed to create the tray icon: {0}")]
+        Icon(String),
+        #[error("Tray icons are not supported on this platform")]
+        Unsupported,
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub async fn start(
+        status: Arc<StatusState>,
+        actions: ActionSender,
+        localizer: Localizer,
+        refresh: Duration,
+    ) -> Result<(), TrayError> {
+        super::sni::start(status, actions, localizer, refresh).await
+    }
+
+    #[cfg(windows)]
+    pub async fn start(
+        status: Arc<StatusState>,
+        actions: ActionSender,
+        localizer: Localizer,
+        refresh: Duration,
+    ) -> Result<(), TrayError> {
+        super::windows::start(status, actions, localizer, refresh).await
+    }
+
+    #[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+    pub async fn start(
+        _status: Arc<StatusState>,
+        _actions: ActionSender,
+        _localizer: Localizer,
+        _refresh: Duration,
+    ) -> Result<(), TrayError> {
+        Err(TrayError::Unsupported)
+    }
+}
+
+#[cfg(all(feature = "tray", unix, not(target_os = "macos")))]
+mod sni {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use ksni::TrayMethods;
+    use ksni::menu::{MenuItem, StandardItem};
+    use tracing::debug;
+
+    use super::super::i18n::Localizer;
+    use super::super::notification::ActionSender;
+    use super::super::status::StatusState;
+    use super::{ICON_SIZE, TRAY_ID, TRAY_TITLE, TrayCommand, TrayError, TrayView, icon_rgba};
+
+    #[derive(Debug)]
+    struct StatusTray {
+        view: TrayView,
+        localizer: Localizer,
+        actions: ActionSender,
+    }
+
+    impl StatusTray {
+        fn command_item(&self, command: TrayCommand) -> MenuItem<Self> {
+            StandardItem {
+                label: command.label(&self.localizer),
+                activate: Box::new(move |tray: &mut Self| {
+                    let _ = tray.actions.send((String::new(), command.action()));
+                }),
+                ..Default::default()
+            }
+            .into()
+        }
+    }
+
+    impl ksni::Tray for StatusTray {
+        fn id(&self) -> String {
+            TRAY_ID.to_string()
+        }
+
+        fn title(&self) -> String {
+            TRAY_TITLE.to_string()
+        }
+
+        fn status(&self) -> ksni::Status {
+            match self.view.over_limit {
+                true => ksni::Status::NeedsAttention,
+                false => ksni::Status::Active,
+            }
+        }
+
+        fn icon_pixmap(&self) -> Vec<ksni::Icon> {
+            let data = icon_rgba(self.view.over_limit)
+                .chunks_exact(4)
+                .flat_map(|pixel| [pixel[3], pixel[0], pixel[1], pixel[2]])
+                .collect();
+            vec![ksni::Icon {
+                width: ICON_SIZE as i32,
+                height: ICON_SIZE as i32,
+                data,
+            }]
+        }
+
+        fn attention_icon_pixmap(&self) -> Vec<ksni::Icon> {
+            self.icon_pixmap()
+        }
+
+        fn tool_tip(&self) -> ksni::ToolTip {
+            ksni::ToolTip {
+                title: TRAY_TITLE.to_string(),
+                description: self.view.tooltip(&self.localizer),
+                ..Default::default()
+            }
+        }
+
+        fn menu(&self) -> Vec<MenuItem<Self>> {
+            let mut menu: Vec<_> = self
+                .view
+                .labels(&self.localizer)
+                .into_iter()
+                .map(|label| {
+                    StandardItem {
+                        label,
+                        enabled: false,
+                        ..Default::default()
+                    }
+                    .into()
+                })
+                .collect();
+            menu.push(MenuItem::Separator);
+            menu.push(self.command_item(TrayCommand::SnoozeAll));
+            menu.push(self.command_item(TrayCommand::OpenReport));
+            menu.push(MenuItem::Separator);
+            menu.push(self.command_item(TrayCommand::Quit));
+            menu
+        }
+    }
+
+    pub async fn start(
+        status: Arc<StatusState>,
+        actions: ActionSender,
+        localizer: Localizer,
+        refresh: Duration,
+    ) -> Result<(), TrayError> {
+        let tray = StatusTray {
+            view: TrayView::from_report(status.report(None, None)),
+            localizer,
+            actions,
+        };
+        let handle = tray.spawn().await?;
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(refresh);
+            loop {
+                interval.tick().await;
+                let view = TrayView::from_report(status.report(None, None));
+                let updated = handle
+                    .update(|tray: &mut StatusTray| {
+                        if tray.view != view {
+                            tray.view = view;
+                        }
+                    })
+                    .await;
+                if updated.is_none() {
+                    debug!("Tray service stopped");
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "tray", windows))]
+mod windows {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use tokio::sync::oneshot;
+    use tracing::{debug, warn};
+    use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+    use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        DispatchMessageW, MSG, PM_REMOVE, PeekMessageW, TranslateMessage,
+    };
+
+    use super::super::i18n::Localizer;
+    use super::super::notification::ActionSender;
+    use super::super::status::StatusState;
+    use super::{ICON_SIZE, TRAY_TITLE, TrayCommand, TrayError, TrayView, icon_rgba};
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    fn icon(view: &TrayView) -> Result<Icon, TrayError> {
+        Icon::from_rgba(icon_rgba(view.over_limit), ICON_SIZE, ICON_SIZE)
+            .map_err(|e| TrayError::Icon(e.to_string()))
+    }
+
+    fn menu(view: &TrayView, localizer: &Localizer) -> Menu {
+        let menu = Menu::new();
+        for label in view.labels(localizer) {
+            let _ = menu.append(&MenuItem::new(label, false, None));
+        }
+        let _ = menu.append(&PredefinedMenuItem::separator());
+        for command in TrayCommand::ALL {
+            if command == TrayCommand::Quit {
+                let _ = menu.append(&PredefinedMenuItem::separator());
+            }
+            let _ = menu.append(&MenuItem::with_id(
+                command.id(),
+                command.label(localizer),
+                true,
+                None,
+            ));
+        }
+        menu
+    }
+
+    fn build(view: &TrayView, localizer: &Localizer) -> Result<TrayIcon, TrayError> {
+        TrayIconBuilder::new()
+            .with_menu(Box::new(menu(view, localizer)))
+            .with_icon(icon(view)?)
+            .with_tooltip(format!("{}\n{}", TRAY_TITLE, view.tooltip(localizer)))
+            .build()
+            .map_err(|e| TrayError::Icon(e.to_string()))
+    }
+
+    fn pump_messages() {
+        unsafe {
+            let mut msg: MSG = std::mem::zeroed();
+            while PeekMessageW(&mut msg, std::ptr::null_mut(), 0, 0, PM_REMOVE) != 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+
+    pub async fn start(
+        status: Arc<StatusState>,
+        actions: ActionSender,
+        localizer: Localizer,
+        refresh: Duration,
+    ) -> Result<(), TrayError> {
+        let (ready_tx, ready_rx) = oneshot::channel();
+
+        let spawned = std::thread::Builder::new()
+            .name("tray".to_string())
+            .spawn(move || {
+                let mut view = TrayView::from_report(status.report(None, None));
+                let tray = match build(&view, &localizer) {
+                    Ok(tray) => {
+                        let _ = ready_tx.send(Ok(()));
+                        tray
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+
+                MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+                    if let Some(command) = TrayCommand::from_id(&event.id.0) {
+                        let _ = actions.send((String::new(), command.action()));
+                    }
+                }));
+
+                let mut next_refresh = Instant::now() + refresh;
+                loop {
+                    pump_messages();
+                    if Instant::now() >= next_refresh {
+                        next_refresh = Instant::now() + refresh;
+                        let current = TrayView::from_report(status.report(None, None));
+                        if current != view {
+                            view = current;
+                            tray.set_menu(Some(Box::new(menu(&view, &localizer))));
+                            let tooltip = format!("{}\n{}", TRAY_TITLE, view.tooltip(&localizer));
+                            if let Err(e) = icon(&view)
+                                .and_then(|icon| {
+                                    tray.set_icon(Some(icon))
+                                        .map_err(|e| TrayError::Icon(e.to_string()))
+                                })
+                                .and_then(|()| {
+                                    tray.set_tooltip(Some(tooltip))
+                                        .map_err(|e| TrayError::Icon(e.to_string()))
+                                })
+                            {
+                                warn!(error = %e, "Failed to update tray icon");
+                            }
+                        }
+                    }
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+            });
+        if let Err(e) = spawned {
+            return Err(TrayError::Icon(e.to_string()));
+        }
+
+        match ready_rx.await {
+            Ok(result) => result,
+            Err(_) => {
+                debug!("Tray thread exited before reporting");
+                Err(TrayError::Unsupported)
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "monitor"))]
+mod tests {
+    use super::super::settings::Settings;
+    use super::super::status::StatusState;
+    use super::*;
+
+    #[test]
+    fn test_view_from_report() {
+        assert_eq!(TrayView::from_report(None), TrayView::default());
+
+        let settings = Settings {
+            data_limit: 1000,
+            ..Default::default()
+        };
+        let status = StatusState::new(&settings);
+        status.update_usage(
+            (0..7)
+                .map(|i| (format!("app{}", i), 1100 - i * 150))
+                .collect(),
+        );
+        let report = status.report(None, None);
+
+        let view = TrayView::from_report(report);
+        assert!(view.over_limit);
+        assert_eq!(view.apps.len(), TOP_APPS);
+
+        let localizer = Localizer::new("en");
+        let labels = view.labels(&localizer);
+        assert_eq!(labels[0], "⚠ app0: 1.1 KB");
+        assert_eq!(labels[1], "app1: 950 B");
+        assert_eq!(labels.len(), TOP_APPS);
+    }
+
+    #[test]
+    fn test_empty_view_labels() {
+        let localizer = Localizer::new("en");
+        assert_eq!(
+            TrayView::default().labels(&localizer),
+            ["No usage recorded yet"]
+        );
+    }
+
+    #[test]
+    fn test_commands() {
+        for command in TrayCommand::ALL {
+            assert_eq!(TrayCommand::from_id(command.id()), Some(command));
+        }
+        assert_eq!(TrayCommand::from_id("nope"), None);
+        assert_eq!(
+            TrayCommand::SnoozeAll.label(&Localizer::new("en")),
+            "Snooze all 1h"
+        );
+        assert_eq!(
+            TrayCommand::OpenReport.action(),
+            NotificationAction::ShowReport
+        );
+    }
+
+    #[test]
+    fn test_icon_color() {
+        let normal = icon_rgba(false);
+        let over = icon_rgba(true);
+        assert_eq!(normal.len(), (ICON_SIZE * ICON_SIZE * 4) as usize);
+
+        let center = ((ICON_SIZE / 2 * ICON_SIZE + ICON_SIZE / 2) * 4) as usize;
+        assert_eq!(normal[center..center + 4], [0x2e, 0xa0, 0x43, 0xff]);
+        assert_eq!(over[center..center + 4], [0xd0, 0x30, 0x30, 0xff]);
+        assert_eq!(normal[3], 0);
+    }
+}
@@ -0,0 +1,81 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_HASH: &str = env!("DG_GIT_HASH");
+pub const BUILD_DATE: &str = env!("DG_BUILD_DATE");
+
+/// The version line printed by `dg --version`.
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("DG_GIT_HASH"),
+    " ",
+    env!("DG_BUILD_DATE"),
+    ")"
+);
+
+pub const VERSION_INFO: VersionInfo = VersionInfo {
+    version: Cow::Borrowed(VERSION),
+    git_hash: Cow::Borrowed(GIT_HASH),
+    build_date: Cow::Borrowed(BUILD_DATE),
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionInfo {
+    pub version: Cow<'static, str>,
+    pub git_hash: Cow<'static, str>,
+    pub build_date: Cow<'static, str>,
+}
+
+impl fmt::Display for VersionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} {})",
+            self.version, self.git_hash, self.build_date
+        )
+    }
+}
+
+/// Whether `version` is a later release than this binary. Pre-release and
+/// build suffixes are ignored, and versions that do not parse never count.
+pub fn is_newer(version: &str) -> bool {
+    match (release(version), release(VERSION)) {
+        (Some(other), Some(own)) => other > own,
+        _ => false,
+    }
+}
+
+fn release(version: &str) -> Option<Vec<u64>> {
+    let core = version.split(['-', '+']).next()?;
+    core.split('.').map(|part| part.parse().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_info() {
+        assert_eq!(VERSION_INFO.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(VERSION_INFO.to_string(), LONG_VERSION);
+        assert!(!VERSION_INFO.git_hash.is_empty());
+
+        let date = &VERSION_INFO.build_date;
+        assert_eq!(date.len(), 10);
+        assert!(date.split('-').all(|part| part.parse::<u32>().is_ok()));
+    }
+
+    #[test]
+    fn test_is_newer() {
+        assert!(!is_newer(VERSION));
+        assert!(is_newer("999.0.0"));
+        assert!(!is_newer("0.0.1"));
+        assert!(!is_newer("not a version"));
+        assert_eq!(release("1.10.2-rc.1+abc"), Some(vec![1, 10, 2]));
+        assert!(release("1.10.2-rc.1+abc") > release("1.9.9"));
+    }
+}
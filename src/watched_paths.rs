@@ -0,0 +1,150 @@
+//! Sizes of the directories listed under `[watched_paths]`.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_WATCH_INTERVAL: u64 = 3600;
+pub const MIN_WATCH_INTERVAL: u64 = 60;
+pub const DEFAULT_MAX_DEPTH: usize = 32;
+pub const DEFAULT_MAX_FILES: u64 = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchedPath {
+    pub path: PathBuf,
+    /// Alert when the directory grows past this many bytes.
+    pub limit: Option<u64>,
+    /// Directories nested deeper than this are not entered.
+    pub max_depth: usize,
+    /// Stop counting after this many files.
+    pub max_files: u64,
+}
+
+impl Default for WatchedPath {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            limit: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_files: DEFAULT_MAX_FILES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirSize {
+    pub bytes: u64,
+    pub files: u64,
+    /// Entries that could not be read, usually for lack of permission.
+    pub errors: u64,
+    /// The depth or file cap was hit, so `bytes` is a lower bound.
+    pub truncated: bool,
+}
+
+impl WatchedPath {
+    /// Adds up the size of every file below `path`. Symlinks are counted
+    /// but not followed, so links to parent directories cannot loop.
+    /// Unreadable entries are skipped and counted in `errors`; only an
+    /// unreadable `path` itself is an error.
+    pub fn measure(&self) -> io::Result<DirSize> {
+        let mut size = DirSize::default();
+        let root = fs::symlink_metadata(&self.path)?;
+        if !root.is_dir() {
+            size.bytes = root.len();
+            size.files = 1;
+            return Ok(size);
+        }
+
+        let mut pending = vec![(self.path.clone(), 0)];
+        while let Some((dir, depth)) = pending.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => {
+                    size.errors += 1;
+                    continue;
+                }
+            };
+            for entry in entries {
+                let Ok(entry) = entry else {
+                    size.errors += 1;
+                    continue;
+                };
+                let Ok(metadata) = entry.metadata() else {
+                    size.errors += 1;
+                    continue;
+                };
+                if metadata.is_dir() {
+                    if depth < self.max_depth {
+                        pending.push((entry.path(), depth + 1));
+                    } else {
+                        size.truncated = true;
+                    }
+                    continue;
+                }
+                if size.files >= self.max_files {
+                    size.truncated = true;
+                    return Ok(size);
+                }
+                size.bytes = size.bytes.saturating_add(metadata.len());
+                size.files += 1;
+            }
+        }
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watched(path: &std::path::Path) -> WatchedPath {
+        WatchedPath {
+            path: path.to_path_buf(),
+            ..WatchedPath::default()
+        }
+    }
+
+    #[test]
+    fn test_measure() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), [0; 100]).unwrap();
+        fs::create_dir_all(dir.path().join("b/c")).unwrap();
+        fs::write(dir.path().join("b/one"), [0; 10]).unwrap();
+        fs::write(dir.path().join("b/c/two"), [0; 1]).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("b/c/loop")).unwrap();
+
+        // The link counts with the length of its target.
+        let link = match cfg!(unix) {
+            true => dir.path().as_os_str().len() as u64,
+            false => 0,
+        };
+
+        let size = watched(dir.path()).measure().unwrap();
+        assert_eq!(size.bytes, 111 + link);
+        assert_eq!(size.files, 3 + cfg!(unix) as u64);
+        assert_eq!(size.errors, 0);
+        assert!(!size.truncated);
+
+        let shallow = WatchedPath {
+            max_depth: 1,
+            ..watched(dir.path())
+        };
+        let size = shallow.measure().unwrap();
+        assert_eq!(size.bytes, 110);
+        assert!(size.truncated);
+
+        let few = WatchedPath {
+            max_files: 2,
+            ..watched(dir.path())
+        };
+        let size = few.measure().unwrap();
+        assert_eq!(size.files, 2);
+        assert!(size.truncated);
+
+        assert!(watched(&dir.path().join("missing")).measure().is_err());
+    }
+}
@@ -0,0 +1,39 @@
+#include <string.h>
+
+#include "data_guardian.h"
+
+int dg_ffi_roundtrip(void) {
+    const char *json = "{\"firefox\":1024}";
+    uint8_t *compressed = NULL;
+    size_t compressed_len = 0;
+    if (dg_compress_usage_json(json, strlen(json), &compressed, &compressed_len) != DG_STATUS_OK) {
+        return 1;
+    }
+
+    uint8_t *decompressed = NULL;
+    size_t decompressed_len = 0;
+    DgStatus status =
+        dg_decompress_usage_json(compressed, compressed_len, &decompressed, &decompressed_len);
+    dg_free(compressed, compressed_len);
+    if (status != DG_STATUS_OK) {
+        return 2;
+    }
+    int same = decompressed_len == strlen(json) && memcmp(decompressed, json, decompressed_len) == 0;
+    dg_free(decompressed, decompressed_len);
+    if (!same) {
+        return 3;
+    }
+
+    const char invalid[] = {(char)0xff, (char)0xfe};
+    if (dg_compress_usage_json(invalid, sizeof(invalid), &compressed, &compressed_len) != DG_STATUS_INVALID_UTF8 ||
+        compressed != NULL || compressed_len != 0) {
+        return 4;
+    }
+    if (dg_compress_usage_json(NULL, 0, &compressed, &compressed_len) != DG_STATUS_NULL_POINTER) {
+        return 5;
+    }
+    if (dg_decompress_usage_json((const uint8_t *)json, strlen(json), NULL, NULL) != DG_STATUS_NULL_POINTER) {
+        return 6;
+    }
+    return 0;
+}
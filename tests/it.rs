@@ -0,0 +1,171 @@
+use data_guardian::compression::{compress_usage_data, decompress_usage_data};
+use data_guardian::settings::Settings;
+use data_guardian::{CompressionError, DataGuardianError, NotificationError, SettingsError};
+
+fn load(bytes: &[u8]) -> Result<usize, DataGuardianError> {
+    Ok(decompress_usage_data(bytes)?.len())
+}
+
+#[test]
+fn test_root_error_types() {
+    let settings = Settings {
+        data_limit: 0,
+        ..Default::default()
+    };
+    let error: DataGuardianError = settings.validate().unwrap_err().into();
+    assert!(matches!(
+        error,
+        DataGuardianError::Settings(SettingsError::InvalidDataLimit(0, _))
+    ));
+
+    assert!(matches!(
+        load(b"not gzip"),
+        Err(DataGuardianError::Compression(CompressionError::Io(_)))
+    ));
+    let compressed = compress_usage_data(&[("firefox".to_string(), 1)].into()).unwrap();
+    assert_eq!(load(&compressed).unwrap(), 1);
+
+    let error = DataGuardianError::from(NotificationError::Snoozed);
+    assert_eq!(error.to_string(), NotificationError::Snoozed.to_string());
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+fn test_ffi_roundtrip_from_c() {
+    unsafe extern "C" {
+        fn dg_ffi_roundtrip() -> i32;
+    }
+    // Keeps the exported functions linked in for the C object.
+    let _ = data_guardian::ffi::dg_free;
+    assert_eq!(unsafe { dg_ffi_roundtrip() }, 0);
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+fn test_ffi_header_is_current() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/data_guardian.h"));
+    let committed = include_str!("../include/data_guardian.h");
+    assert!(
+        generated == committed,
+        "include/data_guardian.h is out of date, copy it from {}",
+        concat!(env!("OUT_DIR"), "/data_guardian.h")
+    );
+}
+
+#[cfg(feature = "notifications")]
+#[test]
+fn test_notifications_are_recorded() {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use data_guardian::clock::MockClock;
+    use data_guardian::notification::{Alert, NotificationConfig, NotificationManager};
+    use data_guardian::test_support::{RECORDING_FAILURE, RecordingChannel};
+
+    let clock = MockClock::new();
+    let channel = Arc::new(RecordingChannel::new());
+    let manager = NotificationManager::builder()
+        .cooldown(Duration::from_secs(60))
+        .config(NotificationConfig {
+            channels: Vec::new(),
+            retry_attempts: 0,
+            ..Default::default()
+        })
+        .channel(channel.clone())
+        .clock(Arc::new(clock.clone()))
+        .build()
+        .unwrap();
+
+    let alert = Alert::new("firefox").with_usage(2_000, 1_000);
+    manager.alert(&alert).unwrap();
+    assert_eq!(channel.alerts(), std::slice::from_ref(&alert));
+
+    assert!(matches!(
+        manager.alert(&alert),
+        Err(NotificationError::Cooldown)
+    ));
+    assert_eq!(channel.alerts().len(), 1);
+
+    clock.advance(Duration::from_secs(60));
+    channel.set_failing(true);
+    let error = manager.alert(&alert).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        NotificationError::ShowError(RECORDING_FAILURE.to_string()).to_string()
+    );
+    assert_eq!(channel.alerts(), [alert.clone(), alert]);
+}
+
+#[cfg(feature = "notifications")]
+#[test]
+fn test_global_manager_is_configurable() {
+    use std::sync::Arc;
+
+    use data_guardian::notification::{self, NotificationConfig, NotificationManager};
+    use data_guardian::test_support::RecordingChannel;
+
+    let channel = Arc::new(RecordingChannel::new());
+    let manager = NotificationManager::builder()
+        .config(NotificationConfig {
+            channels: Vec::new(),
+            retry_attempts: 0,
+            ..Default::default()
+        })
+        .channel(channel.clone())
+        .build()
+        .unwrap();
+
+    assert!(notification::init_global(manager).is_ok());
+    assert!(notification::try_global().is_some());
+    notification::alert_user("firefox").unwrap();
+    assert_eq!(channel.alerts().len(), 1);
+    assert_eq!(channel.alerts()[0].app, "firefox");
+
+    let second = NotificationManager::builder().build().unwrap();
+    assert!(notification::init_global(second).is_err());
+}
+
+#[cfg(all(windows, feature = "monitor"))]
+#[tokio::test]
+async fn test_control_pipe_roundtrip() {
+    use data_guardian::control::{
+        self, ControlClient, ControlEndpoint, ControlRequest, ControlResponse,
+    };
+
+    let endpoint = ControlEndpoint::Pipe(control::pipe_name(&format!("it-{}", std::process::id())));
+    let (tx, mut rx) = control::channel();
+    let events = tokio::sync::broadcast::Sender::new(8);
+    let server = endpoint.clone();
+    let server_events = events.clone();
+    tokio::spawn(async move { control::serve_endpoint(&server, tx, server_events).await });
+    tokio::spawn(async move {
+        while let Some((request, reply)) = rx.recv().await {
+            let response = match request {
+                ControlRequest::Pause => ControlResponse::ok(),
+                _ => ControlResponse::error("unsupported"),
+            };
+            let _ = reply.send(response);
+        }
+    });
+
+    let client = ControlClient::new(endpoint.clone());
+    let response = loop {
+        match client.request(&ControlRequest::Pause).await {
+            Ok(response) => break response,
+            Err(e) if e.is_not_running() => {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            Err(e) => panic!("{}", e),
+        }
+    };
+    assert!(response.ok);
+    let response = client.request(&ControlRequest::Flush).await.unwrap();
+    assert_eq!(response.error.as_deref(), Some("unsupported"));
+
+    // A second service for the same user cannot take over the pipe.
+    let (tx, _rx) = control::channel();
+    let err = control::serve_endpoint(&endpoint, tx, events)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+}